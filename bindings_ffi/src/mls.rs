@@ -5,8 +5,10 @@ use prost::Message;
 use std::{collections::HashMap, convert::TryInto, sync::Arc};
 use tokio::sync::Mutex;
 use xmtp_api_grpc::grpc_api_helper::Client as TonicApiClient;
+use xmtp_common::{ErrorClass, RetryState};
 use xmtp_content_types::reaction::ReactionCodec;
 use xmtp_content_types::ContentCodec;
+use xmtp_cryptography::account_id::normalize_account_address;
 use xmtp_id::associations::verify_signed_with_public_context;
 use xmtp_id::scw_verifier::RemoteSignatureVerifier;
 use xmtp_id::{
@@ -18,13 +20,28 @@ use xmtp_id::{
     },
     InboxId,
 };
+use xmtp_mls::api::{NetworkSubsystem, NetworkUsage, SubsystemUsage};
+use xmtp_mls::cold_restore::ColdRestoreSummary;
+use xmtp_mls::data_mode::DataMode;
+use xmtp_mls::groups::bulk_create::GroupSpec;
+use xmtp_mls::groups::capabilities::Capability;
 use xmtp_mls::groups::device_sync::preference_sync::UserPreferenceUpdate;
+use xmtp_mls::groups::device_sync::status::{DeviceSyncState, DeviceSyncStatus};
+use xmtp_mls::groups::digest::{DigestSender, GroupDigest};
+use xmtp_mls::groups::discovery::{GroupListing, JoinPolicy};
+use xmtp_mls::groups::duplicate::CloneGroupOptions;
 use xmtp_mls::groups::group_mutable_metadata::MessageDisappearingSettings;
 use xmtp_mls::groups::scoped_client::LocalScopedGroupClient;
+use xmtp_mls::groups::welcomes::WelcomeSendReport;
 use xmtp_mls::groups::HmacKey;
+use xmtp_mls::identity_updates::{IdentityChange, IdentityChangeKind, IdentityUpdateBatch};
+use xmtp_mls::inbox_availability::{self, InboxAvailability, InboxIdCheck};
+use xmtp_mls::inbox_state::{InboxState, InstallationInfo, KeyPackageStatus};
 use xmtp_mls::storage::group::ConversationType;
 use xmtp_mls::storage::group_message::{ContentType, MsgQueryArgs};
 use xmtp_mls::storage::group_message::{SortDirection, StoredGroupMessageWithReactions};
+use xmtp_mls::storage::history_sync_log::{HistorySyncLogEntry, HistorySyncOutcome};
+use xmtp_mls::sync_progress::{SyncPhase, SyncStatus};
 use xmtp_mls::{
     api::ApiClientWrapper,
     builder::ClientBuilder,
@@ -43,20 +60,30 @@ use xmtp_mls::{
     },
     identity::IdentityStrategy,
     storage::{
+        consent_policy::{ConsentPolicy, RequestPolicy},
         consent_record::{ConsentState, ConsentType, StoredConsentRecord},
-        group::GroupQueryArgs,
+        group::{GroupLifecycleState, GroupQueryArgs, GroupSortKey},
         group_message::{DeliveryStatus, GroupMessageKind, StoredGroupMessage},
+        join_request::StoredJoinRequest,
+        local_day::LocalDay,
+        mute_rule::MuteRule,
+        network_environment::NetworkEnvironment,
+        store_paths::{self, StoreEntry},
         EncryptedMessageStore, EncryptionKey, StorageOption,
     },
-    subscriptions::SubscribeError,
-    AbortHandle, GenericStreamHandle, StreamHandle,
+    subscriptions::{
+        burst::BurstBudget, FeatureFlagChange, GroupLeft, KeyRotated, MessagesCoalesced,
+        SubscribeError,
+    },
+    AbortHandle, GenericStreamHandle, StreamControl, StreamHandle, StreamHandleError,
 };
 use xmtp_proto::xmtp::mls::message_contents::content_types::ReactionV2;
 use xmtp_proto::xmtp::mls::message_contents::{DeviceSyncKind, EncodedContent};
 pub type RustXmtpClient = MlsClient<TonicApiClient>;
 
+/// The API client, the environment of its host if it is a known one, and the host
 #[derive(uniffi::Object, Clone)]
-pub struct XmtpApiClient(TonicApiClient);
+pub struct XmtpApiClient(TonicApiClient, Option<NetworkEnvironment>, String);
 
 #[uniffi::export(async_runtime = "tokio")]
 pub async fn connect_to_backend(
@@ -71,8 +98,9 @@ pub async fn connect_to_backend(
         host,
         is_secure
     );
-    let api_client = TonicApiClient::create(host, is_secure).await?;
-    Ok(Arc::new(XmtpApiClient(api_client)))
+    let env = NetworkEnvironment::from_host(&host);
+    let api_client = TonicApiClient::create(&host, is_secure).await?;
+    Ok(Arc::new(XmtpApiClient(api_client, env, host)))
 }
 
 /// It returns a new client of the specified `inbox_id`.
@@ -137,10 +165,15 @@ pub async fn create_client(
         legacy_signed_private_key_proto,
     );
 
+    let env = api.1;
     let mut builder = ClientBuilder::new(identity_strategy)
         .api_client(Arc::unwrap_or_clone(api).0)
         .store(store);
 
+    if let Some(env) = env {
+        builder = builder.network_environment(env);
+    }
+
     if let Some(url) = &history_sync_url {
         builder = builder.history_sync_url(url);
     }
@@ -163,6 +196,8 @@ pub async fn get_inbox_id_for_address(
     api: Arc<XmtpApiClient>,
     account_address: String,
 ) -> Result<Option<String>, GenericError> {
+    let account_address =
+        normalize_account_address(&account_address).map_err(GenericError::from_error)?;
     let api = ApiClientWrapper::new(Arc::new(api.0.clone()), Default::default());
     let results = api
         .get_inbox_ids(vec![account_address.clone()])
@@ -178,6 +213,123 @@ pub fn generate_inbox_id(account_address: String, nonce: u64) -> Result<String,
     Ok(xmtp_id_generate_inbox_id(&account_address, &nonce)?)
 }
 
+/// Find out which inbox creating a client for `account_address` with `nonce` would use, before
+/// asking the wallet for signatures. Nothing is published.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn check_inbox_availability(
+    api: Arc<XmtpApiClient>,
+    account_address: String,
+    nonce: u64,
+) -> Result<FfiInboxIdCheck, GenericError> {
+    let network = api.2.clone();
+    let api = ApiClientWrapper::new(Arc::new(api.0.clone()), Default::default());
+    let check =
+        inbox_availability::check_inbox_availability(&api, &network, &account_address, nonce)
+            .await
+            .map_err(GenericError::from_error)?;
+
+    Ok(check.into())
+}
+
+#[derive(uniffi::Enum, Debug, Clone, PartialEq, Eq)]
+pub enum FfiInboxAvailability {
+    /// Nobody created the inbox yet, creating a client creates it
+    Available,
+    /// The address already created the inbox, creating a client adds an installation to it
+    Registered,
+    /// The address belongs to another inbox
+    AddressTaken { inbox_id: String },
+    /// The inbox was created, but the address was since removed from it
+    InboxTaken,
+}
+
+impl From<InboxAvailability> for FfiInboxAvailability {
+    fn from(availability: InboxAvailability) -> Self {
+        match availability {
+            InboxAvailability::Available => Self::Available,
+            InboxAvailability::Registered => Self::Registered,
+            InboxAvailability::AddressTaken { inbox_id } => Self::AddressTaken { inbox_id },
+            InboxAvailability::InboxTaken => Self::InboxTaken,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct FfiInboxIdCheck {
+    pub account_address: String,
+    pub nonce: u64,
+    /// The inbox id the address and nonce generate
+    pub inbox_id: String,
+    pub availability: FfiInboxAvailability,
+}
+
+impl From<InboxIdCheck> for FfiInboxIdCheck {
+    fn from(check: InboxIdCheck) -> Self {
+        Self {
+            account_address: check.address,
+            nonce: check.nonce,
+            inbox_id: check.inbox_id,
+            availability: check.availability.into(),
+        }
+    }
+}
+
+/// The calendar day `timestamp_ns` falls on in a time zone `utc_offset_secs` ahead of UTC
+#[uniffi::export]
+pub fn local_day(timestamp_ns: i64, utc_offset_secs: i32) -> FfiLocalDay {
+    LocalDay::containing(timestamp_ns, utc_offset_secs).into()
+}
+
+/// The canonical path of the database of an installation of `inbox_id` in `env`, in `dir`
+#[uniffi::export]
+pub fn db_path_for_installation(
+    dir: String,
+    env: String,
+    inbox_id: String,
+    nonce: u64,
+) -> Result<String, GenericError> {
+    let path = store_paths::db_path(dir, &env, &inbox_id, nonce)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// The databases named by `db_path_for_installation` in `dir`
+#[uniffi::export]
+pub fn list_stores(dir: String) -> Result<Vec<FfiStoreEntry>, GenericError> {
+    Ok(store_paths::list_stores(dir)?
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+/// The inbox id of the identity stored in the database at `db_path`, without building a
+/// client. `None` if no identity was created yet.
+#[uniffi::export]
+pub fn store_inbox_id(
+    db_path: String,
+    encryption_key: Option<Vec<u8>>,
+) -> Result<Option<String>, GenericError> {
+    let key = encryption_key
+        .map(|key| {
+            EncryptionKey::try_from(key).map_err(|_| "Malformed 32 byte encryption key".to_string())
+        })
+        .transpose()?;
+    Ok(store_paths::read_store_inbox_id(&db_path, key)?)
+}
+
+/// Delete the databases of `env` in `dir` that do not belong to one of `inbox_ids`, returning
+/// their paths
+#[uniffi::export]
+pub fn remove_orphaned_stores(
+    dir: String,
+    env: String,
+    inbox_ids: Vec<String>,
+) -> Result<Vec<String>, GenericError> {
+    Ok(store_paths::remove_orphaned_stores(dir, &env, &inbox_ids)?
+        .into_iter()
+        .map(|store| store.path.to_string_lossy().into_owned())
+        .collect())
+}
+
 #[derive(uniffi::Object)]
 pub struct FfiSignatureRequest {
     inner: Arc<Mutex<SignatureRequest>>,
@@ -268,6 +420,14 @@ impl FfiXmtpClient {
             .map_err(Into::into)
     }
 
+    /// Look up a conversation by its [`FfiConversation::public_id`]
+    pub fn conversation_by_public_id(
+        &self,
+        public_id: String,
+    ) -> Result<FfiConversation, GenericError> {
+        Ok(self.inner_client.group_by_public_id(&public_id)?.into())
+    }
+
     pub fn dm_conversation(
         &self,
         target_inbox_id: String,
@@ -283,6 +443,81 @@ impl FfiXmtpClient {
         Ok(message.into())
     }
 
+    /// The phase of the latest sync of welcomes and groups
+    pub fn sync_status(&self) -> FfiSyncStatus {
+        self.inner_client.sync_status().into()
+    }
+
+    /// Data exchanged with the network since the client was created or the usage was reset
+    pub fn network_usage(&self) -> FfiNetworkUsage {
+        self.inner_client.network_usage().into()
+    }
+
+    /// Start the network usage accounting over, returning the usage until now
+    pub fn reset_network_usage(&self) -> FfiNetworkUsage {
+        self.inner_client.reset_network_usage().into()
+    }
+
+    /// Switch to low data mode on metered connections, and back
+    pub fn set_data_mode(&self, mode: FfiDataMode) {
+        self.inner_client.set_data_mode(mode.into());
+    }
+
+    pub fn data_mode(&self) -> FfiDataMode {
+        self.inner_client.data_mode().into()
+    }
+
+    /// Set how many recently streamed message ids are remembered so that a message streamed by
+    /// several overlapping streams is delivered once. `0`, the default, disables deduplication, so
+    /// streams feeding independent consumers each receive every message.
+    pub fn set_stream_dedupe_window(&self, size: u32) {
+        self.inner_client.set_stream_dedupe_window(size as usize);
+    }
+
+    /// Mute incoming text messages that contain `pattern`, ignoring case, or that match it as a
+    /// regular expression if `is_regex` is set. The rule applies to all conversations unless
+    /// `conversation_id` is given. The rule is synced to the other installations.
+    pub fn add_mute_rule(
+        &self,
+        pattern: String,
+        is_regex: bool,
+        conversation_id: Option<Vec<u8>>,
+    ) -> Result<FfiMuteRule, GenericError> {
+        let rule =
+            self.inner_client
+                .add_mute_rule(&pattern, is_regex, conversation_id.as_deref())?;
+        Ok(rule.into())
+    }
+
+    /// Remove a mute rule on every installation, returning `true` if there was one
+    pub fn remove_mute_rule(&self, id: String) -> Result<bool, GenericError> {
+        Ok(self.inner_client.remove_mute_rule(&id)?)
+    }
+
+    pub fn mute_rules(&self) -> Result<Vec<FfiMuteRule>, GenericError> {
+        Ok(self
+            .inner_client
+            .mute_rules()?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Summarize, per conversation, the messages from other inboxes sent after `since_ns`, for
+    /// periodic summary notifications. The conversation with the latest message comes first.
+    pub async fn notification_digest(
+        &self,
+        since_ns: i64,
+    ) -> Result<Vec<FfiConversationDigest>, GenericError> {
+        Ok(self
+            .inner_client
+            .notification_digest(since_ns)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
     pub async fn can_message(
         &self,
         account_addresses: Vec<String>,
@@ -323,7 +558,10 @@ impl FfiXmtpClient {
         &self,
         refresh_from_network: bool,
     ) -> Result<FfiInboxState, GenericError> {
-        let state = self.inner_client.inbox_state(refresh_from_network).await?;
+        let state = self
+            .inner_client
+            .inbox_state(self.inner_client.inbox_id(), refresh_from_network)
+            .await?;
         Ok(state.into())
     }
 
@@ -352,10 +590,7 @@ impl FfiXmtpClient {
         &self,
         inbox_id: String,
     ) -> Result<FfiInboxState, GenericError> {
-        let state = self
-            .inner_client
-            .get_latest_association_state(&self.inner_client.store().conn()?, &inbox_id)
-            .await?;
+        let state = self.inner_client.inbox_state(&inbox_id, true).await?;
         Ok(state.into())
     }
 
@@ -379,6 +614,37 @@ impl FfiXmtpClient {
         Ok(result.into())
     }
 
+    pub fn consent_policy(&self) -> Result<FfiConsentPolicy, GenericError> {
+        Ok(self.inner_client.consent_policy()?.into())
+    }
+
+    /// Set the defaults for the consent of conversations joined from now on, synced to the
+    /// other installations
+    pub fn set_consent_policy(&self, policy: FfiConsentPolicy) -> Result<(), GenericError> {
+        Ok(self.inner_client.set_consent_policy(policy.into())?)
+    }
+
+    /// Whether a feature flag is enabled on this installation
+    pub fn is_feature_enabled(&self, name: String) -> Result<bool, GenericError> {
+        Ok(self.inner_client.is_feature_enabled(&name)?)
+    }
+
+    /// The value of every feature flag that has a default or is set on this installation
+    pub fn feature_flags(&self) -> Result<HashMap<String, bool>, GenericError> {
+        Ok(self.inner_client.feature_flags()?)
+    }
+
+    /// Set a feature flag, or unset it with `None` to go back to the default. A `synced` flag is
+    /// set on the other installations as well.
+    pub fn set_feature_flag(
+        &self,
+        name: String,
+        enabled: Option<bool>,
+        synced: bool,
+    ) -> Result<(), GenericError> {
+        Ok(self.inner_client.set_feature_flag(&name, enabled, synced)?)
+    }
+
     pub fn sign_with_installation_key(&self, text: &str) -> Result<Vec<u8>, GenericError> {
         let inner = self.inner_client.as_ref();
         Ok(inner.context().sign_with_public_context(text)?)
@@ -465,6 +731,37 @@ impl FfiXmtpClient {
         Ok(())
     }
 
+    /// Ask the other installations for the consent and the message history now
+    pub async fn request_history_sync_now(&self) -> Result<(), GenericError> {
+        self.inner_client.request_history_sync_now().await?;
+        Ok(())
+    }
+
+    /// Bring a new installation up to date with its inbox: identity, welcomes, history request,
+    /// members and groups, in one call. Progress is streamed by `stream_sync_progress`.
+    pub async fn cold_restore(&self) -> Result<FfiColdRestoreSummary, GenericError> {
+        Ok(self.inner_client.cold_restore().await?.into())
+    }
+
+    /// Whether a device sync request of this installation is waiting for a reply
+    pub fn device_sync_status(&self) -> FfiDeviceSyncStatus {
+        self.inner_client.device_sync_status().into()
+    }
+
+    /// The sync requests of other installations that this installation answered or refused,
+    /// newest first
+    pub fn history_sync_log(
+        &self,
+        limit: Option<i64>,
+    ) -> Result<Vec<FfiHistorySyncLogEntry>, GenericError> {
+        Ok(self
+            .inner_client
+            .history_sync_log(limit)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
     /// Adds a wallet address to the existing client
     pub async fn add_wallet(
         &self,
@@ -495,6 +792,64 @@ impl FfiXmtpClient {
         Ok(())
     }
 
+    /// Publish several identity updates at once, such as adding a wallet right after creating
+    /// the client. The updates that could not be published are returned to be applied again.
+    pub async fn apply_signature_requests(
+        &self,
+        signature_requests: Vec<Arc<FfiSignatureRequest>>,
+    ) -> Result<FfiIdentityUpdateBatch, GenericError> {
+        let mut requests = Vec::with_capacity(signature_requests.len());
+        for request in signature_requests {
+            requests.push(request.inner.lock().await.clone());
+        }
+        let batch = self.inner_client.apply_signature_requests(requests).await?;
+
+        Ok(self.ffi_identity_update_batch(batch))
+    }
+
+    /// Combine signature requests of this inbox that are not signed yet, such as the
+    /// registration and the association of another wallet, so that each wallet signs once and
+    /// the actions are published as one identity update
+    pub async fn combine_signature_requests(
+        &self,
+        signature_requests: Vec<Arc<FfiSignatureRequest>>,
+    ) -> Result<Arc<FfiSignatureRequest>, GenericError> {
+        let mut requests = Vec::with_capacity(signature_requests.len());
+        for request in signature_requests {
+            requests.push(request.inner.lock().await.clone());
+        }
+        let combined = self
+            .inner_client
+            .combine_signature_requests(requests)
+            .await?;
+        let scw_verifier = self.inner_client.scw_verifier().clone();
+
+        Ok(Arc::new(FfiSignatureRequest {
+            inner: Arc::new(tokio::sync::Mutex::new(combined)),
+            scw_verifier: Arc::unwrap_or_clone(scw_verifier),
+        }))
+    }
+
+    /// Register the identity like [`FfiXmtpClient::register_identity`], and publish the other
+    /// identity updates of the onboarding in the same batch
+    pub async fn register_identity_with_updates(
+        &self,
+        signature_request: Arc<FfiSignatureRequest>,
+        updates: Vec<Arc<FfiSignatureRequest>>,
+    ) -> Result<FfiIdentityUpdateBatch, GenericError> {
+        let signature_request = signature_request.inner.lock().await.clone();
+        let mut requests = Vec::with_capacity(updates.len());
+        for request in updates {
+            requests.push(request.inner.lock().await.clone());
+        }
+        let batch = self
+            .inner_client
+            .register_identity_with_updates(signature_request, requests)
+            .await?;
+
+        Ok(self.ffi_identity_update_batch(batch))
+    }
+
     /// Revokes or removes an identity - really a wallet address - from the existing client
     pub async fn revoke_wallet(
         &self,
@@ -523,7 +878,10 @@ impl FfiXmtpClient {
         &self,
     ) -> Result<Arc<FfiSignatureRequest>, GenericError> {
         let installation_id = self.inner_client.installation_public_key();
-        let inbox_state = self.inner_client.inbox_state(true).await?;
+        let inbox_state = self
+            .inner_client
+            .inbox_state(self.inner_client.inbox_id(), true)
+            .await?;
         let other_installation_ids = inbox_state
             .installation_ids()
             .into_iter()
@@ -587,6 +945,49 @@ pub struct FfiHmacKey {
 pub struct FfiInstallation {
     pub id: Vec<u8>,
     pub client_timestamp_ns: Option<u64>,
+    pub last_active_ns: Option<i64>,
+    pub key_package_status: FfiKeyPackageStatus,
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+pub enum FfiKeyPackageStatus {
+    Unknown,
+    Valid,
+    Missing,
+    Invalid { reason: String },
+}
+
+impl From<KeyPackageStatus> for FfiKeyPackageStatus {
+    fn from(status: KeyPackageStatus) -> Self {
+        match status {
+            KeyPackageStatus::Unknown => Self::Unknown,
+            KeyPackageStatus::Valid => Self::Valid,
+            KeyPackageStatus::Missing => Self::Missing,
+            KeyPackageStatus::Invalid(reason) => Self::Invalid { reason },
+        }
+    }
+}
+
+impl From<InstallationInfo> for FfiInstallation {
+    fn from(installation: InstallationInfo) -> Self {
+        Self {
+            id: installation.id,
+            client_timestamp_ns: installation.client_timestamp_ns,
+            last_active_ns: installation.last_active_ns,
+            key_package_status: installation.key_package_status.into(),
+        }
+    }
+}
+
+impl From<InboxState> for FfiInboxState {
+    fn from(state: InboxState) -> Self {
+        Self {
+            inbox_id: state.inbox_id,
+            recovery_address: state.recovery_address,
+            installations: state.installations.into_iter().map(Into::into).collect(),
+            account_addresses: state.account_addresses,
+        }
+    }
 }
 
 impl From<AssociationState> for FfiInboxState {
@@ -602,6 +1003,8 @@ impl From<AssociationState> for FfiInboxState {
                     MemberIdentifier::Installation(inst) => Some(FfiInstallation {
                         id: inst,
                         client_timestamp_ns: m.client_timestamp_ns,
+                        last_active_ns: None,
+                        key_package_status: FfiKeyPackageStatus::Unknown,
                     }),
                 })
                 .collect(),
@@ -617,6 +1020,28 @@ pub struct FfiListConversationsOptions {
     pub limit: Option<i64>,
     pub consent_states: Option<Vec<FfiConsentState>>,
     pub include_duplicate_dms: bool,
+    pub sort_by: FfiGroupSortKey,
+    pub direction: Option<FfiDirection>,
+}
+
+#[derive(uniffi::Enum, Clone, Copy, Default, PartialEq, Debug)]
+pub enum FfiGroupSortKey {
+    #[default]
+    CreatedAt,
+    /// Time of the last message, or of the creation for conversations without messages
+    LastMessage,
+    /// Conversation name, ignoring case and accents. Conversations without a name come last.
+    Name,
+}
+
+impl From<FfiGroupSortKey> for GroupSortKey {
+    fn from(key: FfiGroupSortKey) -> Self {
+        match key {
+            FfiGroupSortKey::CreatedAt => GroupSortKey::CreatedAt,
+            FfiGroupSortKey::LastMessage => GroupSortKey::LastMessage,
+            FfiGroupSortKey::Name => GroupSortKey::Name,
+        }
+    }
 }
 
 impl From<FfiListConversationsOptions> for GroupQueryArgs {
@@ -629,6 +1054,8 @@ impl From<FfiListConversationsOptions> for GroupQueryArgs {
                 .consent_states
                 .map(|vec| vec.into_iter().map(Into::into).collect()),
             include_duplicate_dms: opts.include_duplicate_dms,
+            sort_by: opts.sort_by.into(),
+            direction: opts.direction.map(Into::into),
             ..Default::default()
         }
     }
@@ -878,36 +1305,8 @@ impl FfiConversations {
             account_addresses.join(", ")
         );
 
-        if let Some(FfiGroupPermissionsOptions::CustomPolicy) = opts.permissions {
-            if opts.custom_permission_policy_set.is_none() {
-                return Err(GenericError::Generic {
-                    err: "CustomPolicy must include policy set".to_string(),
-                });
-            }
-        } else if opts.custom_permission_policy_set.is_some() {
-            return Err(GenericError::Generic {
-                err: "Only CustomPolicy may specify a policy set".to_string(),
-            });
-        }
-
         let metadata_options = opts.clone().into_group_metadata_options();
-
-        let group_permissions = match opts.permissions {
-            Some(FfiGroupPermissionsOptions::Default) => {
-                Some(xmtp_mls::groups::PreconfiguredPolicies::Default.to_policy_set())
-            }
-            Some(FfiGroupPermissionsOptions::AdminOnly) => {
-                Some(xmtp_mls::groups::PreconfiguredPolicies::AdminsOnly.to_policy_set())
-            }
-            Some(FfiGroupPermissionsOptions::CustomPolicy) => {
-                if let Some(policy_set) = opts.custom_permission_policy_set {
-                    Some(policy_set.try_into()?)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        };
+        let group_permissions = opts.group_permissions()?;
 
         let convo = if account_addresses.is_empty() {
             let group = self
@@ -934,36 +1333,8 @@ impl FfiConversations {
             inbox_ids.join(", ")
         );
 
-        if let Some(FfiGroupPermissionsOptions::CustomPolicy) = opts.permissions {
-            if opts.custom_permission_policy_set.is_none() {
-                return Err(GenericError::Generic {
-                    err: "CustomPolicy must include policy set".to_string(),
-                });
-            }
-        } else if opts.custom_permission_policy_set.is_some() {
-            return Err(GenericError::Generic {
-                err: "Only CustomPolicy may specify a policy set".to_string(),
-            });
-        }
-
         let metadata_options = opts.clone().into_group_metadata_options();
-
-        let group_permissions = match opts.permissions {
-            Some(FfiGroupPermissionsOptions::Default) => {
-                Some(xmtp_mls::groups::PreconfiguredPolicies::Default.to_policy_set())
-            }
-            Some(FfiGroupPermissionsOptions::AdminOnly) => {
-                Some(xmtp_mls::groups::PreconfiguredPolicies::AdminsOnly.to_policy_set())
-            }
-            Some(FfiGroupPermissionsOptions::CustomPolicy) => {
-                if let Some(policy_set) = opts.custom_permission_policy_set {
-                    Some(policy_set.try_into()?)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        };
+        let group_permissions = opts.group_permissions()?;
 
         let convo = if inbox_ids.is_empty() {
             let group = self
@@ -980,6 +1351,81 @@ impl FfiConversations {
         Ok(Arc::new(convo.into()))
     }
 
+    /// Create a group with its members and first message. The group is only kept, and only
+    /// shows up in the conversation list and streams, once both the members are added and the
+    /// message is sent.
+    pub async fn create_group_with_message(
+        &self,
+        inbox_ids: Vec<String>,
+        opts: FfiCreateGroupOptions,
+        first_message: Vec<u8>,
+    ) -> Result<Arc<FfiConversation>, GenericError> {
+        log::info!(
+            "creating group with a first message and inbox ids: {}",
+            inbox_ids.join(", ")
+        );
+
+        let metadata_options = opts.clone().into_group_metadata_options();
+        let group_permissions = opts.group_permissions()?;
+
+        let convo = self
+            .inner_client
+            .create_group_with_message(
+                &inbox_ids,
+                group_permissions,
+                metadata_options,
+                &first_message,
+            )
+            .await?;
+
+        Ok(Arc::new(convo.into()))
+    }
+
+    /// Ask the admins of a discoverable group to add this inbox. `listing` is the bytes of the
+    /// listing the group's admins published, see [`FfiConversation::listing`].
+    pub async fn request_to_join(
+        &self,
+        listing: Vec<u8>,
+        message: String,
+    ) -> Result<(), GenericError> {
+        let listing = GroupListing::from_bytes(&listing)?;
+        self.inner_client.request_to_join(&listing, message).await?;
+        Ok(())
+    }
+
+    /// Create many groups at once, fetching the identities and key packages of their members
+    /// only once. Returns the result of each group, in the order of the specs.
+    pub async fn create_groups_bulk(
+        &self,
+        specs: Vec<FfiGroupSpec>,
+    ) -> Result<Vec<FfiBulkGroupResult>, GenericError> {
+        let specs = specs
+            .into_iter()
+            .map(|spec| {
+                Ok(GroupSpec {
+                    permissions_policy_set: spec.opts.group_permissions()?,
+                    opts: spec.opts.into_group_metadata_options(),
+                    member_inbox_ids: spec.member_inbox_ids,
+                })
+            })
+            .collect::<Result<Vec<_>, GenericError>>()?;
+        let results = self.inner_client.create_groups_bulk(specs).await?;
+
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                Ok(group) => FfiBulkGroupResult {
+                    conversation: Some(Arc::new(group.into())),
+                    error: None,
+                },
+                Err(err) => FfiBulkGroupResult {
+                    conversation: None,
+                    error: Some(err.to_string()),
+                },
+            })
+            .collect())
+    }
+
     pub async fn find_or_create_dm(
         &self,
         account_address: String,
@@ -1048,6 +1494,29 @@ impl FfiConversations {
         Ok(num_groups_synced)
     }
 
+    /// Like [`Self::sync_all_conversations`], but only syncs the conversations of one of the
+    /// `conversation_types`, for example the allowed groups and DMs in a background refresh
+    pub async fn sync_conversations_filtered(
+        &self,
+        consent_states: Option<Vec<FfiConsentState>>,
+        conversation_types: Option<Vec<FfiConversationType>>,
+    ) -> Result<u32, GenericError> {
+        let inner = self.inner_client.as_ref();
+        let provider = inner.mls_provider()?;
+        let consents: Option<Vec<ConsentState>> =
+            consent_states.map(|states| states.into_iter().map(|state| state.into()).collect());
+        let conversation_types: Option<Vec<ConversationType>> =
+            conversation_types.map(|types| types.into_iter().map(Into::into).collect());
+        let num_groups_synced: usize = inner
+            .sync_groups_filtered(&provider, consents, conversation_types)
+            .await?;
+        let num_groups_synced: u32 = num_groups_synced
+            .try_into()
+            .map_err(|_| GenericError::FailedToConvertToU32)?;
+
+        Ok(num_groups_synced)
+    }
+
     pub fn list(
         &self,
         opts: FfiListConversationsOptions,
@@ -1212,18 +1681,16 @@ impl FfiConversations {
         FfiStreamCloser::new(handle)
     }
 
-    /// Get notified when a preference changes either locally or is synced from another device
-    /// allowing the user to re-render the new state appropriately.
-    pub async fn stream_preferences(
+    /// Get notified when wallets or installations are added to or removed from the inbox of a
+    /// member of one of this client's conversations
+    pub async fn stream_identity_changes(
         &self,
-        callback: Arc<dyn FfiPreferenceCallback>,
+        callback: Arc<dyn FfiIdentityChangeCallback>,
     ) -> FfiStreamCloser {
-        let handle = RustXmtpClient::stream_preferences_with_callback(
+        let handle = RustXmtpClient::stream_identity_changes_with_callback(
             self.inner_client.clone(),
             move |msg| match msg {
-                Ok(m) => callback.on_preference_update(
-                    m.into_iter().filter_map(|v| v.try_into().ok()).collect(),
-                ),
+                Ok(m) => callback.on_identity_change(m.into_iter().map(Into::into).collect()),
                 Err(e) => callback.on_error(e.into()),
             },
         );
@@ -1231,15 +1698,148 @@ impl FfiConversations {
         FfiStreamCloser::new(handle)
     }
 
-    pub fn get_hmac_keys(&self) -> Result<HashMap<Vec<u8>, Vec<FfiHmacKey>>, GenericError> {
-        let inner = self.inner_client.as_ref();
-        let conversations = inner.find_groups(GroupQueryArgs {
-            include_duplicate_dms: true,
-            ..GroupQueryArgs::default()
-        })?;
-
-        let mut hmac_map = HashMap::new();
-        for conversation in conversations {
+    /// Get notified of the progress of syncs, to show how far along onboarding or a refresh is
+    pub async fn stream_sync_progress(
+        &self,
+        callback: Arc<dyn FfiSyncProgressCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_sync_progress_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(status) => callback.on_sync_progress(status.into()),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified of the messages held back by the burst budgets of conversations, see
+    /// [`FfiConversation::set_burst_budget`]
+    pub async fn stream_coalesced_messages(
+        &self,
+        callback: Arc<dyn FfiCoalescedMessagesCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_coalesced_messages_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(coalesced) => callback.on_messages_coalesced(coalesced.into()),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified when this installation leaves a conversation or is removed from it
+    pub async fn stream_groups_left(
+        &self,
+        callback: Arc<dyn FfiGroupLeftCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_groups_left_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(left) => callback.on_group_left(left.into()),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified when this installation's leaf key in a conversation is rotated
+    pub async fn stream_key_rotations(
+        &self,
+        callback: Arc<dyn FfiKeyRotationCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_key_rotations_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(rotated) => callback.on_key_rotated(rotated.into()),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified when a request, database operation or MLS operation failed and is about
+    /// to be retried
+    pub async fn stream_retries(&self, callback: Arc<dyn FfiRetryCallback>) -> FfiStreamCloser {
+        let handle =
+            RustXmtpClient::stream_retries_with_callback(self.inner_client.clone(), move |msg| {
+                match msg {
+                    Ok(state) => callback.on_retry(state.into()),
+                    Err(e) => callback.on_error(e.into()),
+                }
+            });
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified of the installations the welcomes of added members were sent to, and of
+    /// the ones that failed and are queued to be sent again
+    pub async fn stream_welcome_reports(
+        &self,
+        callback: Arc<dyn FfiWelcomeReportCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_welcome_reports_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(report) => callback.on_welcome_report(report.into()),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified when a feature flag is set on this installation or synced from another one
+    pub async fn stream_feature_flags(
+        &self,
+        callback: Arc<dyn FfiFeatureFlagCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_feature_flags_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(changes) => {
+                    callback.on_feature_flags_changed(changes.into_iter().map(Into::into).collect())
+                }
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    /// Get notified when a preference changes either locally or is synced from another device
+    /// allowing the user to re-render the new state appropriately.
+    pub async fn stream_preferences(
+        &self,
+        callback: Arc<dyn FfiPreferenceCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_preferences_with_callback(
+            self.inner_client.clone(),
+            move |msg| match msg {
+                Ok(m) => callback.on_preference_update(
+                    m.into_iter().filter_map(|v| v.try_into().ok()).collect(),
+                ),
+                Err(e) => callback.on_error(e.into()),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    pub fn get_hmac_keys(&self) -> Result<HashMap<Vec<u8>, Vec<FfiHmacKey>>, GenericError> {
+        let inner = self.inner_client.as_ref();
+        let conversations = inner.find_groups(GroupQueryArgs {
+            include_duplicate_dms: true,
+            ..GroupQueryArgs::default()
+        })?;
+
+        let mut hmac_map = HashMap::new();
+        for conversation in conversations {
             let id = conversation.group_id.clone();
             let keys = conversation
                 .hmac_keys(-1..=1)?
@@ -1269,6 +1869,20 @@ impl TryFrom<UserPreferenceUpdate> for FfiPreferenceUpdate {
     fn try_from(value: UserPreferenceUpdate) -> Result<Self, Self::Error> {
         match value {
             UserPreferenceUpdate::HmacKeyUpdate { key } => Ok(FfiPreferenceUpdate::HMAC { key }),
+            UserPreferenceUpdate::ConsentPolicyUpdate(policy) => {
+                Ok(FfiPreferenceUpdate::ConsentPolicy {
+                    policy: policy.into(),
+                })
+            }
+            UserPreferenceUpdate::FeatureFlagUpdate { name, enabled } => {
+                Ok(FfiPreferenceUpdate::FeatureFlag { name, enabled })
+            }
+            UserPreferenceUpdate::MuteRuleAdded(rule) => {
+                Ok(FfiPreferenceUpdate::MuteRuleAdded { rule: rule.into() })
+            }
+            UserPreferenceUpdate::MuteRuleRemoved { id } => {
+                Ok(FfiPreferenceUpdate::MuteRuleRemoved { id })
+            }
             // These are filtered out in the stream and should not be here
             // We're keeping preference update and consent streams separate right now.
             UserPreferenceUpdate::ConsentUpdate(_) => Err(GenericError::Generic {
@@ -1339,6 +1953,7 @@ impl From<StoredConsentRecord> for FfiConsent {
                 ConsentType::InboxId => FfiConsentEntityType::InboxId,
             },
             state: value.state.into(),
+            expires_at_ns: value.expires_at_ns,
         }
     }
 }
@@ -1386,12 +2001,169 @@ impl From<FfiConsentState> for ConsentState {
     }
 }
 
-#[derive(uniffi::Enum)]
+#[derive(uniffi::Enum, PartialEq, Debug, Clone, Copy)]
+pub enum FfiRequestPolicy {
+    Ask,
+    AllowContacts,
+    DenyUnknown,
+    ContactsOnly,
+}
+
+impl From<RequestPolicy> for FfiRequestPolicy {
+    fn from(policy: RequestPolicy) -> Self {
+        match policy {
+            RequestPolicy::Ask => FfiRequestPolicy::Ask,
+            RequestPolicy::AllowContacts => FfiRequestPolicy::AllowContacts,
+            RequestPolicy::DenyUnknown => FfiRequestPolicy::DenyUnknown,
+            RequestPolicy::ContactsOnly => FfiRequestPolicy::ContactsOnly,
+        }
+    }
+}
+
+impl From<FfiRequestPolicy> for RequestPolicy {
+    fn from(policy: FfiRequestPolicy) -> Self {
+        match policy {
+            FfiRequestPolicy::Ask => RequestPolicy::Ask,
+            FfiRequestPolicy::AllowContacts => RequestPolicy::AllowContacts,
+            FfiRequestPolicy::DenyUnknown => RequestPolicy::DenyUnknown,
+            FfiRequestPolicy::ContactsOnly => RequestPolicy::ContactsOnly,
+        }
+    }
+}
+
+/// Account-level defaults for the consent of conversations joined from a welcome
+#[derive(uniffi::Record, PartialEq, Debug, Clone)]
+pub struct FfiConsentPolicy {
+    pub dms: FfiRequestPolicy,
+    pub groups: FfiRequestPolicy,
+}
+
+impl From<ConsentPolicy> for FfiConsentPolicy {
+    fn from(policy: ConsentPolicy) -> Self {
+        Self {
+            dms: policy.dms.into(),
+            groups: policy.groups.into(),
+        }
+    }
+}
+
+impl From<FfiConsentPolicy> for ConsentPolicy {
+    fn from(policy: FfiConsentPolicy) -> Self {
+        Self {
+            dms: policy.dms.into(),
+            groups: policy.groups.into(),
+        }
+    }
+}
+
+#[derive(uniffi::Enum, PartialEq, Debug)]
+pub enum FfiGroupLifecycleState {
+    Active,
+    Left,
+    Purged,
+    /// Being created with its first message, see [`FfiConversations::create_group_with_message`]
+    Staged,
+}
+
+impl From<GroupLifecycleState> for FfiGroupLifecycleState {
+    fn from(state: GroupLifecycleState) -> Self {
+        match state {
+            GroupLifecycleState::Active => FfiGroupLifecycleState::Active,
+            GroupLifecycleState::Left => FfiGroupLifecycleState::Left,
+            GroupLifecycleState::Purged => FfiGroupLifecycleState::Purged,
+            GroupLifecycleState::Staged => FfiGroupLifecycleState::Staged,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, PartialEq, Debug)]
+pub enum FfiJoinPolicy {
+    Closed,
+    RequestToJoin,
+}
+
+impl From<JoinPolicy> for FfiJoinPolicy {
+    fn from(policy: JoinPolicy) -> Self {
+        match policy {
+            JoinPolicy::Closed => FfiJoinPolicy::Closed,
+            JoinPolicy::RequestToJoin => FfiJoinPolicy::RequestToJoin,
+        }
+    }
+}
+
+impl From<FfiJoinPolicy> for JoinPolicy {
+    fn from(policy: FfiJoinPolicy) -> Self {
+        match policy {
+            FfiJoinPolicy::Closed => JoinPolicy::Closed,
+            FfiJoinPolicy::RequestToJoin => JoinPolicy::RequestToJoin,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiJoinRequest {
+    pub inbox_id: String,
+    pub message: String,
+    pub requested_at_ns: i64,
+}
+
+impl From<StoredJoinRequest> for FfiJoinRequest {
+    fn from(request: StoredJoinRequest) -> Self {
+        Self {
+            inbox_id: request.inbox_id,
+            message: request.message,
+            requested_at_ns: request.requested_at_ns,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
 pub enum FfiDeviceSyncKind {
     Messages,
     Consent,
 }
 
+#[derive(uniffi::Enum, Debug, PartialEq)]
+pub enum FfiHistorySyncOutcome {
+    Provided,
+    UnknownInstallation,
+    RateLimited,
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiHistorySyncLogEntry {
+    pub request_id: String,
+    pub requester_inbox_id: String,
+    pub requester_installation_id: Vec<u8>,
+    /// `None` if the request did not specify a kind
+    pub kind: Option<FfiDeviceSyncKind>,
+    pub outcome: FfiHistorySyncOutcome,
+    pub created_at_ns: i64,
+}
+
+impl From<HistorySyncLogEntry> for FfiHistorySyncLogEntry {
+    fn from(entry: HistorySyncLogEntry) -> Self {
+        let kind = match entry.kind() {
+            DeviceSyncKind::MessageHistory => Some(FfiDeviceSyncKind::Messages),
+            DeviceSyncKind::Consent => Some(FfiDeviceSyncKind::Consent),
+            DeviceSyncKind::Unspecified => None,
+        };
+        let outcome = match entry.outcome {
+            HistorySyncOutcome::Provided => FfiHistorySyncOutcome::Provided,
+            HistorySyncOutcome::UnknownInstallation => FfiHistorySyncOutcome::UnknownInstallation,
+            HistorySyncOutcome::RateLimited => FfiHistorySyncOutcome::RateLimited,
+        };
+        Self {
+            request_id: entry.request_id,
+            requester_inbox_id: entry.requester_inbox_id,
+            requester_installation_id: entry.requester_installation_id,
+            kind,
+            outcome,
+            created_at_ns: entry.created_at_ns,
+        }
+    }
+}
+
 impl From<FfiDeviceSyncKind> for DeviceSyncKind {
     fn from(value: FfiDeviceSyncKind) -> Self {
         match value {
@@ -1447,6 +2219,63 @@ pub struct FfiListMessagesOptions {
     pub delivery_status: Option<FfiDeliveryStatus>,
     pub direction: Option<FfiDirection>,
     pub content_types: Option<Vec<FfiContentType>>,
+    /// Only messages that were, or were not, filtered by a mute rule
+    pub filtered: Option<bool>,
+}
+
+/// A calendar day in a time zone. Messages of the day are those sent from `start_ns`
+/// included to `end_ns` excluded.
+#[derive(uniffi::Record, Clone, Debug)]
+pub struct FfiLocalDay {
+    pub days_since_epoch: i64,
+    pub utc_offset_secs: i32,
+    pub start_ns: i64,
+    pub end_ns: i64,
+}
+
+impl From<LocalDay> for FfiLocalDay {
+    fn from(day: LocalDay) -> Self {
+        Self {
+            days_since_epoch: day.days_since_epoch,
+            utc_offset_secs: day.utc_offset_secs,
+            start_ns: day.start_ns(),
+            end_ns: day.end_ns(),
+        }
+    }
+}
+
+impl From<FfiLocalDay> for LocalDay {
+    fn from(day: FfiLocalDay) -> Self {
+        Self {
+            days_since_epoch: day.days_since_epoch,
+            utc_offset_secs: day.utc_offset_secs,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Clone, Debug)]
+pub struct FfiStoreEntry {
+    pub path: String,
+    pub env: String,
+    pub inbox_id: String,
+    pub installation_nonce: u64,
+}
+
+impl From<StoreEntry> for FfiStoreEntry {
+    fn from(store: StoreEntry) -> Self {
+        Self {
+            path: store.path.to_string_lossy().into_owned(),
+            env: store.env,
+            inbox_id: store.inbox_id,
+            installation_nonce: store.installation_nonce,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug)]
+pub struct FfiDayMessageCount {
+    pub day: FfiLocalDay,
+    pub count: i64,
 }
 
 #[derive(uniffi::Enum, Clone)]
@@ -1461,6 +2290,11 @@ pub enum FfiContentType {
     Attachment,
     RemoteAttachment,
     TransactionReference,
+    Poll,
+    PollVote,
+    Location,
+    LeaveRequest,
+    JoinRequest,
 }
 
 impl From<FfiContentType> for ContentType {
@@ -1476,6 +2310,11 @@ impl From<FfiContentType> for ContentType {
             FfiContentType::Attachment => ContentType::Attachment,
             FfiContentType::RemoteAttachment => ContentType::RemoteAttachment,
             FfiContentType::TransactionReference => ContentType::TransactionReference,
+            FfiContentType::Poll => ContentType::Poll,
+            FfiContentType::PollVote => ContentType::PollVote,
+            FfiContentType::Location => ContentType::Location,
+            FfiContentType::LeaveRequest => ContentType::LeaveRequest,
+            FfiContentType::JoinRequest => ContentType::JoinRequest,
         }
     }
 }
@@ -1489,9 +2328,63 @@ pub struct FfiCreateGroupOptions {
     pub group_pinned_frame_url: Option<String>,
     pub custom_permission_policy_set: Option<FfiPermissionPolicySet>,
     pub message_disappearing_settings: Option<FfiMessageDisappearingSettings>,
+    /// Time in NS after which the group expires
+    pub group_expires_at_ns: Option<i64>,
+}
+
+/// Template of a group created by [`FfiConversations::create_groups_bulk`]
+#[derive(uniffi::Record)]
+pub struct FfiGroupSpec {
+    pub member_inbox_ids: Vec<String>,
+    pub opts: FfiCreateGroupOptions,
+}
+
+/// Options of [`FfiConversation::clone_group`]
+#[derive(uniffi::Record, Default)]
+pub struct FfiCloneGroupOptions {
+    pub excluded_inbox_ids: Vec<String>,
+    pub group_name: Option<String>,
+}
+
+impl From<FfiCloneGroupOptions> for CloneGroupOptions {
+    fn from(opts: FfiCloneGroupOptions) -> Self {
+        Self {
+            excluded_inbox_ids: opts.excluded_inbox_ids,
+            name: opts.group_name,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiBulkGroupResult {
+    pub conversation: Option<Arc<FfiConversation>>,
+    /// Why the group could not be created, when `conversation` is `None`
+    pub error: Option<String>,
 }
 
 impl FfiCreateGroupOptions {
+    /// The policy set of the group, checking that only custom permissions specify one
+    fn group_permissions(&self) -> Result<Option<PolicySet>, GenericError> {
+        match (&self.permissions, &self.custom_permission_policy_set) {
+            (Some(FfiGroupPermissionsOptions::CustomPolicy), None) => Err(GenericError::Generic {
+                err: "CustomPolicy must include policy set".to_string(),
+            }),
+            (Some(FfiGroupPermissionsOptions::CustomPolicy), Some(policy_set)) => {
+                Ok(Some(policy_set.clone().try_into()?))
+            }
+            (_, Some(_)) => Err(GenericError::Generic {
+                err: "Only CustomPolicy may specify a policy set".to_string(),
+            }),
+            (Some(FfiGroupPermissionsOptions::Default), None) => Ok(Some(
+                xmtp_mls::groups::PreconfiguredPolicies::Default.to_policy_set(),
+            )),
+            (Some(FfiGroupPermissionsOptions::AdminOnly), None) => Ok(Some(
+                xmtp_mls::groups::PreconfiguredPolicies::AdminsOnly.to_policy_set(),
+            )),
+            (None, None) => Ok(None),
+        }
+    }
+
     pub fn into_group_metadata_options(self) -> GroupMetadataOptions {
         GroupMetadataOptions {
             name: self.group_name,
@@ -1501,6 +2394,7 @@ impl FfiCreateGroupOptions {
             message_disappearing_settings: self
                 .message_disappearing_settings
                 .map(|settings| settings.into()),
+            expires_at_ns: self.group_expires_at_ns,
         }
     }
 }
@@ -1512,6 +2406,52 @@ impl FfiConversation {
         Ok(message_id)
     }
 
+    /// Add a reaction with `content` to a message, or remove it if this inbox already reacted
+    /// with `content`. Returns whether the reaction is now added.
+    pub async fn toggle_reaction(
+        &self,
+        message_id: Vec<u8>,
+        content: String,
+    ) -> Result<bool, GenericError> {
+        Ok(self.inner.toggle_reaction(&message_id, content).await?)
+    }
+
+    /// Forward a message of this conversation to `to_conversation`, with its original sender,
+    /// sent time and conversation. Returns the id of the forwarded message.
+    pub async fn forward_message(
+        &self,
+        message_id: Vec<u8>,
+        to_conversation: Arc<FfiConversation>,
+    ) -> Result<Vec<u8>, GenericError> {
+        Ok(self
+            .inner
+            .forward_message(&message_id, &to_conversation.inner)
+            .await?)
+    }
+
+    /// Number of messages from other members sent after `read_at_ns`, leaving out muted messages
+    pub fn unread_count(&self, read_at_ns: i64) -> Result<i64, GenericError> {
+        Ok(self.inner.unread_count(read_at_ns)?)
+    }
+
+    /// Deliver at most `budget.max_messages` messages of this conversation per window to the
+    /// message streams. The other messages are still stored, and reported together to
+    /// `stream_coalesced_messages` when the window ends. `None` delivers every message. The
+    /// budget is kept across restarts.
+    pub fn set_burst_budget(&self, budget: Option<FfiBurstBudget>) -> Result<(), GenericError> {
+        Ok(self.inner.set_burst_budget(budget.map(Into::into))?)
+    }
+
+    /// Whether a message of this conversation should raise a notification: it is an
+    /// application message from another member that no mute rule filtered
+    pub fn should_notify(&self, message_id: Vec<u8>) -> Result<bool, GenericError> {
+        let provider = self.inner.mls_provider()?;
+        let Some(message) = provider.conn_ref().get_group_message(&message_id)? else {
+            return Ok(false);
+        };
+        Ok(self.inner.should_notify(&message)?)
+    }
+
     /// send a message without immediately publishing to the delivery service.
     pub fn send_optimistic(&self, content_bytes: Vec<u8>) -> Result<Vec<u8>, GenericError> {
         let id = self
@@ -1533,6 +2473,14 @@ impl FfiConversation {
         Ok(())
     }
 
+    /// Send the welcomes that failed to be sent to added members again, see
+    /// [`FfiXmtpClient::stream_welcome_reports`]
+    pub async fn retry_failed_welcomes(&self) -> Result<(), GenericError> {
+        self.inner.resume_post_commit_actions().await?;
+
+        Ok(())
+    }
+
     pub async fn find_messages(
         &self,
         opts: FfiListMessagesOptions,
@@ -1557,6 +2505,7 @@ impl FfiConversation {
                 content_types: opts
                     .content_types
                     .map(|types| types.into_iter().map(Into::into).collect()),
+                filtered: opts.filtered,
             })?
             .into_iter()
             .map(|msg| msg.into())
@@ -1565,23 +2514,60 @@ impl FfiConversation {
         Ok(messages)
     }
 
-    pub async fn find_messages_with_reactions(
+    /// Number of messages on each day from `from` to `to` that has any, for date separators
+    pub fn count_messages_by_day(
         &self,
-        opts: FfiListMessagesOptions,
-    ) -> Result<Vec<FfiMessageWithReactions>, GenericError> {
-        let delivery_status = opts.delivery_status.map(|status| status.into());
-        let direction = opts.direction.map(|dir| dir.into());
-        let kind = match self.conversation_type().await? {
-            FfiConversationType::Group => None,
-            FfiConversationType::Dm => Some(GroupMessageKind::Application),
-            FfiConversationType::Sync => None,
-        };
-
-        let messages: Vec<FfiMessageWithReactions> = self
+        from: FfiLocalDay,
+        to: FfiLocalDay,
+    ) -> Result<Vec<FfiDayMessageCount>, GenericError> {
+        Ok(self
             .inner
-            .find_messages_with_reactions(&MsgQueryArgs {
-                sent_before_ns: opts.sent_before_ns,
-                sent_after_ns: opts.sent_after_ns,
+            .count_messages_by_day(from.into(), to.into())?
+            .into_iter()
+            .map(|(day, count)| FfiDayMessageCount {
+                day: day.into(),
+                count,
+            })
+            .collect())
+    }
+
+    /// Text messages of this conversation that contain `query`, ignoring case and accents,
+    /// newest first
+    pub fn search_messages(
+        &self,
+        query: String,
+        limit: Option<i64>,
+    ) -> Result<Vec<FfiMessage>, GenericError> {
+        Ok(self
+            .inner
+            .search_messages(&query, limit)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// The image of this conversation, once the client's avatar fetcher downloaded it
+    pub fn avatar(&self) -> Result<Option<Vec<u8>>, GenericError> {
+        Ok(self.inner.avatar()?)
+    }
+
+    pub async fn find_messages_with_reactions(
+        &self,
+        opts: FfiListMessagesOptions,
+    ) -> Result<Vec<FfiMessageWithReactions>, GenericError> {
+        let delivery_status = opts.delivery_status.map(|status| status.into());
+        let direction = opts.direction.map(|dir| dir.into());
+        let kind = match self.conversation_type().await? {
+            FfiConversationType::Group => None,
+            FfiConversationType::Dm => Some(GroupMessageKind::Application),
+            FfiConversationType::Sync => None,
+        };
+
+        let messages: Vec<FfiMessageWithReactions> = self
+            .inner
+            .find_messages_with_reactions(&MsgQueryArgs {
+                sent_before_ns: opts.sent_before_ns,
+                sent_after_ns: opts.sent_after_ns,
                 kind,
                 delivery_status,
                 limit: opts.limit,
@@ -1589,6 +2575,7 @@ impl FfiConversation {
                 content_types: opts
                     .content_types
                     .map(|types| types.into_iter().map(Into::into).collect()),
+                filtered: opts.filtered,
             })?
             .into_iter()
             .map(|msg| msg.into())
@@ -1815,6 +2802,41 @@ impl FfiConversation {
             .map_err(Into::into)
     }
 
+    pub async fn pin_message(&self, message_id: Vec<u8>) -> Result<(), GenericError> {
+        self.inner.pin_message(&message_id).await?;
+        Ok(())
+    }
+
+    pub async fn unpin_message(&self, message_id: Vec<u8>) -> Result<(), GenericError> {
+        self.inner.unpin_message(&message_id).await?;
+        Ok(())
+    }
+
+    /// The pinned messages that are stored locally, oldest pin first
+    pub fn pinned_messages(&self) -> Result<Vec<FfiMessage>, GenericError> {
+        Ok(self
+            .inner
+            .pinned_messages()?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn transfer_super_admin(&self, inbox_id: String) -> Result<(), GenericError> {
+        self.inner
+            .transfer_super_admin(inbox_id)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn clone_group(
+        &self,
+        opts: FfiCloneGroupOptions,
+    ) -> Result<Arc<FfiConversation>, GenericError> {
+        let group = self.inner.clone_group(opts.into()).await?;
+        Ok(Arc::new(group.into()))
+    }
+
     pub fn group_permissions(&self) -> Result<Arc<FfiGroupPermissions>, GenericError> {
         let permissions = self.inner.permissions()?;
         Ok(Arc::new(FfiGroupPermissions {
@@ -1854,11 +2876,102 @@ impl FfiConversation {
         self.inner.created_at_ns
     }
 
+    pub async fn update_group_expiration(
+        &self,
+        expires_at_ns: Option<i64>,
+    ) -> Result<(), GenericError> {
+        self.inner.update_group_expiration(expires_at_ns).await?;
+        Ok(())
+    }
+
+    pub fn group_expiration(&self) -> Result<Option<i64>, GenericError> {
+        let provider = self.inner.mls_provider()?;
+        Ok(self.inner.group_expiration(&provider)?)
+    }
+
+    /// Leave the conversation. The other members are asked to remove this inbox, sending to
+    /// the conversation fails from now on.
+    pub async fn leave(&self) -> Result<(), GenericError> {
+        self.inner.leave().await?;
+        Ok(())
+    }
+
+    /// Rotate this installation's leaf key in the conversation now
+    pub async fn rotate_keys(&self) -> Result<(), GenericError> {
+        self.inner.key_update().await?;
+        Ok(())
+    }
+
+    /// Rotate this installation's leaf key in the conversation at least every `interval_ns`
+    /// nanoseconds. `None` goes back to the interval of the client.
+    pub fn set_key_rotation_interval(&self, interval_ns: Option<i64>) -> Result<(), GenericError> {
+        let interval = interval_ns.map(|ns| std::time::Duration::from_nanos(ns.max(0) as u64));
+        self.inner.set_key_rotation_interval(interval)?;
+        Ok(())
+    }
+
+    /// How long in nanoseconds this installation's leaf key in the conversation may go without
+    /// rotation
+    pub fn key_rotation_interval_ns(&self) -> Result<i64, GenericError> {
+        Ok(self.inner.key_rotation_interval()?.as_nanos() as i64)
+    }
+
+    /// Leave the conversation, first handing the super admin role to `successor_inbox_id` if
+    /// this inbox holds it
+    pub async fn leave_with_successor(
+        &self,
+        successor_inbox_id: String,
+    ) -> Result<(), GenericError> {
+        self.inner.leave_with_successor(successor_inbox_id).await?;
+        Ok(())
+    }
+
     pub fn is_active(&self) -> Result<bool, GenericError> {
         let provider = self.inner.mls_provider()?;
         self.inner.is_active(&provider).map_err(Into::into)
     }
 
+    /// Whether this installation left the conversation, and whether its history was purged
+    pub fn lifecycle_state(&self) -> Result<FfiGroupLifecycleState, GenericError> {
+        Ok(self.inner.lifecycle_state()?.into())
+    }
+
+    pub fn join_policy(&self) -> Result<FfiJoinPolicy, GenericError> {
+        let provider = self.inner.mls_provider()?;
+        Ok(self.inner.join_policy(&provider)?.into())
+    }
+
+    pub async fn update_join_policy(&self, join_policy: FfiJoinPolicy) -> Result<(), GenericError> {
+        self.inner.update_join_policy(join_policy.into()).await?;
+        Ok(())
+    }
+
+    /// The listing to publish for a discoverable group, see
+    /// [`FfiConversations::request_to_join`]
+    pub fn listing(&self) -> Result<Vec<u8>, GenericError> {
+        let provider = self.inner.mls_provider()?;
+        Ok(self.inner.listing(&provider)?.to_bytes())
+    }
+
+    pub fn join_requests(&self) -> Result<Vec<FfiJoinRequest>, GenericError> {
+        Ok(self
+            .inner
+            .join_requests()?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn approve_join_request(&self, inbox_id: String) -> Result<(), GenericError> {
+        self.inner.approve_join_request(&inbox_id).await?;
+        Ok(())
+    }
+
+    pub fn reject_join_request(&self, inbox_id: String) -> Result<(), GenericError> {
+        self.inner.reject_join_request(&inbox_id)?;
+        Ok(())
+    }
+
     pub fn consent_state(&self) -> Result<FfiConsentState, GenericError> {
         self.inner
             .consent_state()
@@ -1888,6 +3001,22 @@ impl FfiConversation {
         self.inner.dm_inbox_id().map_err(Into::into)
     }
 
+    /// A stable, URL-safe id of the conversation, the same in every app, for deep links and
+    /// push payloads
+    pub fn public_id(&self) -> Result<String, GenericError> {
+        self.inner.public_id().map_err(Into::into)
+    }
+
+    /// The optional features supported by every member, to check before using them
+    pub fn peer_capabilities(&self) -> Result<Vec<FfiCapability>, GenericError> {
+        Ok(self
+            .inner
+            .peer_capabilities()?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
     pub async fn conversation_type(&self) -> Result<FfiConversationType, GenericError> {
         let provider = self.inner.mls_provider()?;
         let conversation_type = self.inner.conversation_type(&provider).await?;
@@ -1934,6 +3063,23 @@ impl From<ConversationType> for FfiConversationType {
     }
 }
 
+#[derive(uniffi::Enum, PartialEq, Debug)]
+pub enum FfiCapability {
+    Polls,
+    Locations,
+    TransactionReferences,
+}
+
+impl From<Capability> for FfiCapability {
+    fn from(capability: Capability) -> Self {
+        match capability {
+            Capability::Polls => FfiCapability::Polls,
+            Capability::Locations => FfiCapability::Locations,
+            Capability::TransactionReferences => FfiCapability::TransactionReferences,
+        }
+    }
+}
+
 #[derive(uniffi::Enum, Clone)]
 pub enum FfiDeliveryStatus {
     Unpublished,
@@ -2046,176 +3192,811 @@ pub fn decode_reaction(bytes: Vec<u8>) -> Result<FfiReaction, GenericError> {
     let encoded_content = EncodedContent::decode(bytes.as_slice())
         .map_err(|e| GenericError::Generic { err: e.to_string() })?;
 
-    // Use ReactionCodec to decode into Reaction and convert to FfiReaction
-    ReactionCodec::decode(encoded_content)
-        .map(Into::into)
-        .map_err(|e| GenericError::Generic { err: e.to_string() })
+    // Use ReactionCodec to decode into Reaction and convert to FfiReaction
+    ReactionCodec::decode(encoded_content)
+        .map(Into::into)
+        .map_err(|e| GenericError::Generic { err: e.to_string() })
+}
+
+#[derive(uniffi::Enum, Clone, Default, PartialEq, Debug)]
+pub enum FfiReactionAction {
+    Unknown,
+    #[default]
+    Added,
+    Removed,
+}
+
+impl From<FfiReactionAction> for i32 {
+    fn from(action: FfiReactionAction) -> Self {
+        match action {
+            FfiReactionAction::Unknown => 0,
+            FfiReactionAction::Added => 1,
+            FfiReactionAction::Removed => 2,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Clone, Default, PartialEq, Debug)]
+pub enum FfiReactionSchema {
+    Unknown,
+    #[default]
+    Unicode,
+    Shortcode,
+    Custom,
+}
+
+impl From<FfiReactionSchema> for i32 {
+    fn from(schema: FfiReactionSchema) -> Self {
+        match schema {
+            FfiReactionSchema::Unknown => 0,
+            FfiReactionSchema::Unicode => 1,
+            FfiReactionSchema::Shortcode => 2,
+            FfiReactionSchema::Custom => 3,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Clone)]
+pub struct FfiMessage {
+    pub id: Vec<u8>,
+    pub sent_at_ns: i64,
+    pub convo_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    pub content: Vec<u8>,
+    pub kind: FfiConversationMessageKind,
+    pub delivery_status: FfiDeliveryStatus,
+    /// Metadata attached by the sending app, by namespace and then key
+    pub app_metadata: HashMap<String, HashMap<String, String>>,
+    /// Whether the sender installation was verified when the message was processed
+    pub sender_verified: bool,
+}
+
+impl From<StoredGroupMessage> for FfiMessage {
+    fn from(msg: StoredGroupMessage) -> Self {
+        let app_metadata = msg
+            .app_metadata()
+            .into_iter()
+            .map(|(namespace, entries)| (namespace, entries.into_iter().collect()))
+            .collect();
+        Self {
+            id: msg.id,
+            sent_at_ns: msg.sent_at_ns,
+            convo_id: msg.group_id,
+            sender_inbox_id: msg.sender_inbox_id,
+            content: msg.decrypted_message_bytes,
+            kind: msg.kind.into(),
+            delivery_status: msg.delivery_status.into(),
+            app_metadata,
+            sender_verified: msg.sender_verified,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiMuteRule {
+    pub id: String,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub conversation_id: Option<Vec<u8>>,
+    pub created_at_ns: i64,
+}
+
+impl From<MuteRule> for FfiMuteRule {
+    fn from(rule: MuteRule) -> Self {
+        Self {
+            conversation_id: rule.decoded_group_id(),
+            id: rule.id,
+            pattern: rule.pattern,
+            is_regex: rule.is_regex,
+            created_at_ns: rule.created_at_ns,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiConversationDigest {
+    pub conversation_id: Vec<u8>,
+    pub new_message_count: u64,
+    /// The inboxes that sent the most messages, most messages first
+    pub top_senders: Vec<FfiDigestSender>,
+    pub mentioned: bool,
+    pub last_message_ns: i64,
+}
+
+impl From<GroupDigest> for FfiConversationDigest {
+    fn from(digest: GroupDigest) -> Self {
+        Self {
+            conversation_id: digest.group_id,
+            new_message_count: digest.new_message_count,
+            top_senders: digest.top_senders.into_iter().map(Into::into).collect(),
+            mentioned: digest.mentioned,
+            last_message_ns: digest.last_message_ns,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiDigestSender {
+    pub inbox_id: String,
+    pub message_count: u64,
+}
+
+impl From<DigestSender> for FfiDigestSender {
+    fn from(sender: DigestSender) -> Self {
+        Self {
+            inbox_id: sender.inbox_id,
+            message_count: sender.message_count,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiConsent {
+    pub entity_type: FfiConsentEntityType,
+    pub state: FfiConsentState,
+    pub entity: String,
+    /// Time in NS after which the consent reverts to unknown, if it is temporary
+    pub expires_at_ns: Option<i64>,
+}
+
+impl From<FfiConsent> for StoredConsentRecord {
+    fn from(consent: FfiConsent) -> Self {
+        Self {
+            entity_type: consent.entity_type.into(),
+            state: consent.state.into(),
+            entity: consent.entity,
+            expires_at_ns: consent.expires_at_ns,
+        }
+    }
+}
+
+type FfiHandle = Box<GenericStreamHandle<Result<(), SubscribeError>>>;
+
+#[derive(uniffi::Object, Clone)]
+pub struct FfiStreamCloser {
+    stream_handle: Arc<Mutex<Option<FfiHandle>>>,
+    // for convenience, does not require locking mutex.
+    abort_handle: Arc<Box<dyn AbortHandle>>,
+    control: Option<Arc<StreamControl>>,
+}
+
+impl FfiStreamCloser {
+    pub fn new(
+        stream_handle: impl StreamHandle<StreamOutput = Result<(), SubscribeError>>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self {
+            abort_handle: Arc::new(stream_handle.abort_handle()),
+            control: stream_handle.control(),
+            stream_handle: Arc::new(Mutex::new(Some(Box::new(stream_handle)))),
+        }
+    }
+
+    fn control(&self) -> Result<&StreamControl, GenericError> {
+        self.control
+            .as_deref()
+            .ok_or_else(|| GenericError::Generic {
+                err: StreamHandleError::NotControllable.to_string(),
+            })
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl FfiStreamCloser {
+    /// Signal the stream to end
+    /// Does not wait for the stream to end.
+    pub fn end(&self) {
+        self.abort_handle.end();
+    }
+
+    /// End the stream and asynchronously wait for it to shutdown
+    pub async fn end_and_wait(&self) -> Result<(), GenericError> {
+        use xmtp_mls::StreamHandleError::*;
+        use GenericError::Generic;
+
+        if self.abort_handle.is_finished() {
+            return Ok(());
+        }
+
+        let mut stream_handle = self.stream_handle.lock().await;
+        let stream_handle = stream_handle.take();
+        if let Some(mut h) = stream_handle {
+            match h.end_and_wait().await {
+                Err(Cancelled) => Ok(()),
+                Err(Panicked(msg)) => Err(Generic { err: msg }),
+                Err(e) => Err(Generic {
+                    err: format!("error joining task {}", e),
+                }),
+                Ok(t) => t.map_err(|e| Generic { err: e.to_string() }),
+            }
+        } else {
+            log::warn!("subscription already closed");
+            Ok(())
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.abort_handle.is_finished()
+    }
+
+    pub async fn wait_for_ready(&self) {
+        let mut stream_handle = self.stream_handle.lock().await;
+        if let Some(ref mut h) = *stream_handle {
+            h.wait_for_ready().await;
+        }
+    }
+
+    /// Stop delivering messages without closing the subscription, such as during heavy UI work.
+    /// Only message streams can be paused.
+    pub fn pause(&self) -> Result<(), GenericError> {
+        self.control()?.pause();
+        Ok(())
+    }
+
+    /// Deliver the messages received while paused, then continue with live messages
+    pub fn resume(&self) -> Result<(), GenericError> {
+        self.control()?.resume();
+        Ok(())
+    }
+
+    /// Deliver again the stored messages sent after `cursor_ns`, then continue with live
+    /// messages
+    pub fn replay_from(&self, cursor_ns: i64) -> Result<(), GenericError> {
+        self.control()?.replay_from(cursor_ns);
+        Ok(())
+    }
+}
+
+/// Attests exports of the key store with a hardware-backed key of the host, e.g. App Attest or
+/// Android Key Attestation
+#[cfg(feature = "key-store-export")]
+#[uniffi::export(with_foreign)]
+pub trait FfiKeyStoreAttestation: Send + Sync {
+    fn attest(&self, digest: Vec<u8>) -> Result<Vec<u8>, GenericError>;
+    fn verify(&self, digest: Vec<u8>, attestation: Vec<u8>) -> Result<(), GenericError>;
+}
+
+#[cfg(feature = "key-store-export")]
+struct KeyStoreAttestationAdapter(Arc<dyn FfiKeyStoreAttestation>);
+
+#[cfg(feature = "key-store-export")]
+impl xmtp_mls::storage::key_store_export::KeyStoreAttestation for KeyStoreAttestationAdapter {
+    fn attest(&self, digest: &[u8]) -> Result<Vec<u8>, String> {
+        self.0.attest(digest.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn verify(&self, digest: &[u8], attestation: &[u8]) -> Result<(), String> {
+        self.0
+            .verify(digest.to_vec(), attestation.to_vec())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "key-store-export")]
+#[uniffi::export]
+impl FfiXmtpClient {
+    /// Export every MLS secret of this installation, sealed under `export_key` and attested by
+    /// `attestation`, to restore the installation on another device.
+    ///
+    /// WARNING: the export decrypts the messages of every group of this installation. Only hand
+    /// it to a platform that moves state securely between devices of the user, and stop using
+    /// this installation on the old device once it is taken.
+    pub fn export_key_store(
+        &self,
+        export_key: Vec<u8>,
+        attestation: Arc<dyn FfiKeyStoreAttestation>,
+    ) -> Result<Vec<u8>, GenericError> {
+        let export_key: EncryptionKey = export_key
+            .try_into()
+            .map_err(|_| "Malformed 32 byte export key".to_string())?;
+        let conn = self.inner_client.store().conn()?;
+        Ok(conn.export_key_store(&export_key, &KeyStoreAttestationAdapter(attestation))?)
+    }
+
+    /// Replace the MLS secrets of this installation with an export of [`Self::export_key_store`],
+    /// once `attestation` verified it. Returns the number of imported entries.
+    pub fn import_key_store(
+        &self,
+        export: Vec<u8>,
+        export_key: Vec<u8>,
+        attestation: Arc<dyn FfiKeyStoreAttestation>,
+    ) -> Result<u64, GenericError> {
+        let export_key: EncryptionKey = export_key
+            .try_into()
+            .map_err(|_| "Malformed 32 byte export key".to_string())?;
+        let conn = self.inner_client.store().conn()?;
+        let imported = conn.import_key_store(
+            &export,
+            &export_key,
+            &KeyStoreAttestationAdapter(attestation),
+        )?;
+        Ok(imported as u64)
+    }
+}
+
+#[uniffi::export(with_foreign)]
+pub trait FfiMessageCallback: Send + Sync {
+    fn on_message(&self, message: FfiMessage);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[uniffi::export(with_foreign)]
+pub trait FfiConversationCallback: Send + Sync {
+    fn on_conversation(&self, conversation: Arc<FfiConversation>);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[uniffi::export(with_foreign)]
+pub trait FfiConsentCallback: Send + Sync {
+    fn on_consent_update(&self, consent: Vec<FfiConsent>);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[uniffi::export(with_foreign)]
+pub trait FfiIdentityChangeCallback: Send + Sync {
+    fn on_identity_change(&self, changes: Vec<FfiIdentityChange>);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[derive(uniffi::Enum)]
+pub enum FfiIdentityChangeKind {
+    WalletAdded { address: String },
+    WalletRemoved { address: String },
+    InstallationAdded { installation_id: Vec<u8> },
+    InstallationRevoked { installation_id: Vec<u8> },
+}
+
+#[derive(uniffi::Record)]
+pub struct FfiIdentityChange {
+    pub inbox_id: String,
+    pub sequence_id: i64,
+    pub kind: FfiIdentityChangeKind,
+}
+
+impl From<IdentityChange> for FfiIdentityChange {
+    fn from(change: IdentityChange) -> Self {
+        let kind = match change.kind {
+            IdentityChangeKind::WalletAdded(address) => {
+                FfiIdentityChangeKind::WalletAdded { address }
+            }
+            IdentityChangeKind::WalletRemoved(address) => {
+                FfiIdentityChangeKind::WalletRemoved { address }
+            }
+            IdentityChangeKind::InstallationAdded(installation_id) => {
+                FfiIdentityChangeKind::InstallationAdded { installation_id }
+            }
+            IdentityChangeKind::InstallationRevoked(installation_id) => {
+                FfiIdentityChangeKind::InstallationRevoked { installation_id }
+            }
+        };
+        Self {
+            inbox_id: change.inbox_id,
+            sequence_id: change.sequence_id,
+            kind,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiDataMode {
+    Normal,
+    Low,
+}
+
+impl From<FfiDataMode> for DataMode {
+    fn from(mode: FfiDataMode) -> Self {
+        match mode {
+            FfiDataMode::Normal => DataMode::Normal,
+            FfiDataMode::Low => DataMode::Low,
+        }
+    }
+}
+
+impl From<DataMode> for FfiDataMode {
+    fn from(mode: DataMode) -> Self {
+        match mode {
+            DataMode::Normal => FfiDataMode::Normal,
+            DataMode::Low => FfiDataMode::Low,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, Default)]
+pub struct FfiSubsystemUsage {
+    pub requests: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl From<SubsystemUsage> for FfiSubsystemUsage {
+    fn from(usage: SubsystemUsage) -> Self {
+        Self {
+            requests: usage.requests,
+            bytes_sent: usage.bytes_sent,
+            bytes_received: usage.bytes_received,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug)]
+pub struct FfiNetworkUsage {
+    pub since_ns: i64,
+    pub sync: FfiSubsystemUsage,
+    pub streams: FfiSubsystemUsage,
+    pub publish: FfiSubsystemUsage,
+    pub identity: FfiSubsystemUsage,
+    pub key_packages: FfiSubsystemUsage,
+    pub total: FfiSubsystemUsage,
+}
+
+impl From<NetworkUsage> for FfiNetworkUsage {
+    fn from(usage: NetworkUsage) -> Self {
+        Self {
+            since_ns: usage.since_ns,
+            sync: usage.subsystem(NetworkSubsystem::Sync).into(),
+            streams: usage.subsystem(NetworkSubsystem::Streams).into(),
+            publish: usage.subsystem(NetworkSubsystem::Publish).into(),
+            identity: usage.subsystem(NetworkSubsystem::Identity).into(),
+            key_packages: usage.subsystem(NetworkSubsystem::KeyPackages).into(),
+            total: usage.total().into(),
+        }
+    }
+}
+
+#[uniffi::export(with_foreign)]
+pub trait FfiSyncProgressCallback: Send + Sync {
+    fn on_sync_progress(&self, status: FfiSyncStatus);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+pub enum FfiSyncPhase {
+    Idle,
+    FetchingIdentityUpdates { inboxes: u64 },
+    FetchingWelcomes,
+    ProcessingWelcomes { processed: u64, total: u64 },
+    RequestingHistory,
+    SyncingGroups { synced: u64, total: u64 },
+    BackfillingMessages { stored: u64, total: u64 },
+    Complete { active_groups: u64 },
+    Failed { error: String },
+}
+
+#[derive(uniffi::Record, Debug)]
+pub struct FfiSyncStatus {
+    pub phase: FfiSyncPhase,
+    pub updated_at_ns: i64,
+}
+
+impl From<SyncStatus> for FfiSyncStatus {
+    fn from(status: SyncStatus) -> Self {
+        let phase = match status.phase {
+            SyncPhase::Idle => FfiSyncPhase::Idle,
+            SyncPhase::FetchingIdentityUpdates { inboxes } => {
+                FfiSyncPhase::FetchingIdentityUpdates {
+                    inboxes: inboxes as u64,
+                }
+            }
+            SyncPhase::FetchingWelcomes => FfiSyncPhase::FetchingWelcomes,
+            SyncPhase::ProcessingWelcomes { processed, total } => {
+                FfiSyncPhase::ProcessingWelcomes {
+                    processed: processed as u64,
+                    total: total as u64,
+                }
+            }
+            SyncPhase::RequestingHistory => FfiSyncPhase::RequestingHistory,
+            SyncPhase::SyncingGroups { synced, total } => FfiSyncPhase::SyncingGroups {
+                synced: synced as u64,
+                total: total as u64,
+            },
+            SyncPhase::BackfillingMessages { stored, total } => FfiSyncPhase::BackfillingMessages {
+                stored: stored as u64,
+                total: total as u64,
+            },
+            SyncPhase::Complete { active_groups } => FfiSyncPhase::Complete {
+                active_groups: active_groups as u64,
+            },
+            SyncPhase::Failed { error } => FfiSyncPhase::Failed { error },
+        };
+        Self {
+            phase,
+            updated_at_ns: status.updated_at_ns,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, PartialEq)]
+pub struct FfiColdRestoreSummary {
+    pub groups_joined: u64,
+    pub active_groups: u64,
+    pub history_requested: bool,
+}
+
+impl From<ColdRestoreSummary> for FfiColdRestoreSummary {
+    fn from(summary: ColdRestoreSummary) -> Self {
+        Self {
+            groups_joined: summary.groups_joined as u64,
+            active_groups: summary.active_groups as u64,
+            history_requested: summary.history_requested,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+pub enum FfiDeviceSyncState {
+    Idle,
+    AwaitingReply,
+    ApplyingArchive,
 }
 
-#[derive(uniffi::Enum, Clone, Default, PartialEq, Debug)]
-pub enum FfiReactionAction {
-    Unknown,
-    #[default]
-    Added,
-    Removed,
+#[derive(uniffi::Record, Debug)]
+pub struct FfiDeviceSyncStatus {
+    pub state: FfiDeviceSyncState,
+    /// The kinds of the requests waiting for a reply
+    pub pending_requests: Vec<FfiDeviceSyncKind>,
+    pub last_error: Option<String>,
+    pub last_request_ns: Option<i64>,
+    pub last_reply_ns: Option<i64>,
+    pub updated_at_ns: i64,
 }
 
-impl From<FfiReactionAction> for i32 {
-    fn from(action: FfiReactionAction) -> Self {
-        match action {
-            FfiReactionAction::Unknown => 0,
-            FfiReactionAction::Added => 1,
-            FfiReactionAction::Removed => 2,
+impl From<DeviceSyncStatus> for FfiDeviceSyncStatus {
+    fn from(status: DeviceSyncStatus) -> Self {
+        let state = match status.state {
+            DeviceSyncState::Idle => FfiDeviceSyncState::Idle,
+            DeviceSyncState::AwaitingReply => FfiDeviceSyncState::AwaitingReply,
+            DeviceSyncState::ApplyingArchive => FfiDeviceSyncState::ApplyingArchive,
+        };
+        let pending_requests = status
+            .pending_requests
+            .into_iter()
+            .filter_map(|kind| match kind {
+                DeviceSyncKind::MessageHistory => Some(FfiDeviceSyncKind::Messages),
+                DeviceSyncKind::Consent => Some(FfiDeviceSyncKind::Consent),
+                DeviceSyncKind::Unspecified => None,
+            })
+            .collect();
+        Self {
+            state,
+            pending_requests,
+            last_error: status.last_error,
+            last_request_ns: status.last_request_ns,
+            last_reply_ns: status.last_reply_ns,
+            updated_at_ns: status.updated_at_ns,
         }
     }
 }
 
-#[derive(uniffi::Enum, Clone, Default, PartialEq, Debug)]
-pub enum FfiReactionSchema {
-    Unknown,
-    #[default]
-    Unicode,
-    Shortcode,
-    Custom,
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct FfiBurstBudget {
+    pub max_messages: u32,
+    pub window_ms: u64,
 }
 
-impl From<FfiReactionSchema> for i32 {
-    fn from(schema: FfiReactionSchema) -> Self {
-        match schema {
-            FfiReactionSchema::Unknown => 0,
-            FfiReactionSchema::Unicode => 1,
-            FfiReactionSchema::Shortcode => 2,
-            FfiReactionSchema::Custom => 3,
+impl From<FfiBurstBudget> for BurstBudget {
+    fn from(budget: FfiBurstBudget) -> Self {
+        Self {
+            max_messages: budget.max_messages,
+            window: std::time::Duration::from_millis(budget.window_ms),
         }
     }
 }
 
-#[derive(uniffi::Record, Clone)]
-pub struct FfiMessage {
-    pub id: Vec<u8>,
-    pub sent_at_ns: i64,
-    pub convo_id: Vec<u8>,
-    pub sender_inbox_id: String,
-    pub content: Vec<u8>,
-    pub kind: FfiConversationMessageKind,
-    pub delivery_status: FfiDeliveryStatus,
+#[derive(uniffi::Record, Debug)]
+pub struct FfiMessagesCoalesced {
+    pub conversation_id: Vec<u8>,
+    pub count: u32,
+    pub first_sent_at_ns: i64,
+    pub last_sent_at_ns: i64,
 }
 
-impl From<StoredGroupMessage> for FfiMessage {
-    fn from(msg: StoredGroupMessage) -> Self {
+impl From<MessagesCoalesced> for FfiMessagesCoalesced {
+    fn from(coalesced: MessagesCoalesced) -> Self {
         Self {
-            id: msg.id,
-            sent_at_ns: msg.sent_at_ns,
-            convo_id: msg.group_id,
-            sender_inbox_id: msg.sender_inbox_id,
-            content: msg.decrypted_message_bytes,
-            kind: msg.kind.into(),
-            delivery_status: msg.delivery_status.into(),
+            conversation_id: coalesced.group_id,
+            count: coalesced.count,
+            first_sent_at_ns: coalesced.first_sent_at_ns,
+            last_sent_at_ns: coalesced.last_sent_at_ns,
         }
     }
 }
 
-#[derive(uniffi::Record)]
-pub struct FfiConsent {
-    pub entity_type: FfiConsentEntityType,
-    pub state: FfiConsentState,
-    pub entity: String,
+#[uniffi::export(with_foreign)]
+pub trait FfiCoalescedMessagesCallback: Send + Sync {
+    fn on_messages_coalesced(&self, coalesced: FfiMessagesCoalesced);
+    fn on_error(&self, error: FfiSubscribeError);
 }
 
-impl From<FfiConsent> for StoredConsentRecord {
-    fn from(consent: FfiConsent) -> Self {
-        Self {
-            entity_type: consent.entity_type.into(),
-            state: consent.state.into(),
-            entity: consent.entity,
+impl FfiXmtpClient {
+    fn ffi_identity_update_batch(&self, batch: IdentityUpdateBatch) -> FfiIdentityUpdateBatch {
+        let scw_verifier = self.inner_client.scw_verifier();
+        FfiIdentityUpdateBatch {
+            published: batch
+                .published
+                .into_iter()
+                .map(|index| index as u64)
+                .collect(),
+            unpublished: batch
+                .unpublished
+                .into_iter()
+                .map(|request| {
+                    Arc::new(FfiSignatureRequest {
+                        inner: Arc::new(tokio::sync::Mutex::new(request)),
+                        scw_verifier: Arc::unwrap_or_clone(scw_verifier.clone()),
+                    })
+                })
+                .collect(),
+            error: batch.error.map(|e| e.to_string()),
         }
     }
 }
 
-type FfiHandle = Box<GenericStreamHandle<Result<(), SubscribeError>>>;
+/// The outcome of [`FfiXmtpClient::apply_signature_requests`]
+#[derive(uniffi::Record)]
+pub struct FfiIdentityUpdateBatch {
+    /// Positions of the requests that were published
+    pub published: Vec<u64>,
+    /// The requests that were not published, in the order they were given
+    pub unpublished: Vec<Arc<FfiSignatureRequest>>,
+    /// The first error met publishing the updates
+    pub error: Option<String>,
+}
 
-#[derive(uniffi::Object, Clone)]
-pub struct FfiStreamCloser {
-    stream_handle: Arc<Mutex<Option<FfiHandle>>>,
-    // for convenience, does not require locking mutex.
-    abort_handle: Arc<Box<dyn AbortHandle>>,
+#[derive(uniffi::Record, Debug)]
+pub struct FfiGroupLeft {
+    pub conversation_id: Vec<u8>,
+    /// Whether the removal is still waiting for a commit from another member
+    pub pending: bool,
 }
 
-impl FfiStreamCloser {
-    pub fn new(
-        stream_handle: impl StreamHandle<StreamOutput = Result<(), SubscribeError>>
-            + Send
-            + Sync
-            + 'static,
-    ) -> Self {
+impl From<GroupLeft> for FfiGroupLeft {
+    fn from(left: GroupLeft) -> Self {
         Self {
-            abort_handle: Arc::new(stream_handle.abort_handle()),
-            stream_handle: Arc::new(Mutex::new(Some(Box::new(stream_handle)))),
+            conversation_id: left.group_id,
+            pending: left.pending,
         }
     }
 }
 
-#[uniffi::export(async_runtime = "tokio")]
-impl FfiStreamCloser {
-    /// Signal the stream to end
-    /// Does not wait for the stream to end.
-    pub fn end(&self) {
-        self.abort_handle.end();
-    }
+#[uniffi::export(with_foreign)]
+pub trait FfiGroupLeftCallback: Send + Sync {
+    fn on_group_left(&self, left: FfiGroupLeft);
+    fn on_error(&self, error: FfiSubscribeError);
+}
 
-    /// End the stream and asynchronously wait for it to shutdown
-    pub async fn end_and_wait(&self) -> Result<(), GenericError> {
-        use xmtp_mls::StreamHandleError::*;
-        use GenericError::Generic;
+#[derive(uniffi::Record, Debug)]
+pub struct FfiKeyRotated {
+    pub conversation_id: Vec<u8>,
+    /// The installation whose leaf key was rotated
+    pub installation_id: Vec<u8>,
+    /// Epoch of the conversation after the rotation
+    pub epoch: u64,
+    pub rotated_at_ns: i64,
+}
 
-        if self.abort_handle.is_finished() {
-            return Ok(());
+impl From<KeyRotated> for FfiKeyRotated {
+    fn from(rotated: KeyRotated) -> Self {
+        Self {
+            conversation_id: rotated.group_id,
+            installation_id: rotated.installation_id,
+            epoch: rotated.epoch,
+            rotated_at_ns: rotated.rotated_at_ns,
         }
+    }
+}
 
-        let mut stream_handle = self.stream_handle.lock().await;
-        let stream_handle = stream_handle.take();
-        if let Some(mut h) = stream_handle {
-            match h.end_and_wait().await {
-                Err(Cancelled) => Ok(()),
-                Err(Panicked(msg)) => Err(Generic { err: msg }),
-                Err(e) => Err(Generic {
-                    err: format!("error joining task {}", e),
-                }),
-                Ok(t) => t.map_err(|e| Generic { err: e.to_string() }),
-            }
-        } else {
-            log::warn!("subscription already closed");
-            Ok(())
+#[uniffi::export(with_foreign)]
+pub trait FfiKeyRotationCallback: Send + Sync {
+    fn on_key_rotated(&self, rotated: FfiKeyRotated);
+    fn on_error(&self, error: FfiSubscribeError);
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiErrorClass {
+    Network,
+    Storage,
+    Mls,
+}
+
+impl From<ErrorClass> for FfiErrorClass {
+    fn from(class: ErrorClass) -> Self {
+        match class {
+            ErrorClass::Network => Self::Network,
+            ErrorClass::Storage => Self::Storage,
+            ErrorClass::Mls => Self::Mls,
         }
     }
+}
 
-    pub fn is_closed(&self) -> bool {
-        self.abort_handle.is_finished()
-    }
+#[derive(uniffi::Record, Debug)]
+pub struct FfiRetryState {
+    /// The number of the retry about to start, from 1
+    pub attempt: u64,
+    pub max_retries: u64,
+    pub delay_ms: u64,
+    /// Time since the first attempt started
+    pub elapsed_ms: u64,
+    pub error_class: Option<FfiErrorClass>,
+    pub error: String,
+}
 
-    pub async fn wait_for_ready(&self) {
-        let mut stream_handle = self.stream_handle.lock().await;
-        if let Some(ref mut h) = *stream_handle {
-            h.wait_for_ready().await;
+impl From<RetryState> for FfiRetryState {
+    fn from(state: RetryState) -> Self {
+        Self {
+            attempt: state.attempt as u64,
+            max_retries: state.max_retries as u64,
+            delay_ms: state.delay.as_millis() as u64,
+            elapsed_ms: state.elapsed.as_millis() as u64,
+            error_class: state.error_class.map(Into::into),
+            error: state.error,
         }
     }
 }
 
 #[uniffi::export(with_foreign)]
-pub trait FfiMessageCallback: Send + Sync {
-    fn on_message(&self, message: FfiMessage);
+pub trait FfiRetryCallback: Send + Sync {
+    fn on_retry(&self, state: FfiRetryState);
     fn on_error(&self, error: FfiSubscribeError);
 }
 
+#[derive(uniffi::Record, Debug)]
+pub struct FfiFailedWelcome {
+    pub installation_id: Vec<u8>,
+    pub error: String,
+}
+
+#[derive(uniffi::Record, Debug)]
+pub struct FfiWelcomeSendReport {
+    pub conversation_id: Vec<u8>,
+    pub sent: Vec<Vec<u8>>,
+    /// Retried by [`FfiConversation::retry_failed_welcomes`] or the next sync
+    pub failed: Vec<FfiFailedWelcome>,
+}
+
+impl From<WelcomeSendReport> for FfiWelcomeSendReport {
+    fn from(report: WelcomeSendReport) -> Self {
+        Self {
+            conversation_id: report.group_id,
+            sent: report.sent,
+            failed: report
+                .failed
+                .into_iter()
+                .map(|failed| FfiFailedWelcome {
+                    installation_id: failed.installation_id,
+                    error: failed.error,
+                })
+                .collect(),
+        }
+    }
+}
+
 #[uniffi::export(with_foreign)]
-pub trait FfiConversationCallback: Send + Sync {
-    fn on_conversation(&self, conversation: Arc<FfiConversation>);
+pub trait FfiWelcomeReportCallback: Send + Sync {
+    fn on_welcome_report(&self, report: FfiWelcomeSendReport);
     fn on_error(&self, error: FfiSubscribeError);
 }
 
+#[derive(uniffi::Record, Debug)]
+pub struct FfiFeatureFlagChange {
+    pub name: String,
+    /// `None` when the flag was unset and the default applies again
+    pub enabled: Option<bool>,
+}
+
+impl From<FeatureFlagChange> for FfiFeatureFlagChange {
+    fn from(change: FeatureFlagChange) -> Self {
+        Self {
+            name: change.name,
+            enabled: change.enabled,
+        }
+    }
+}
+
 #[uniffi::export(with_foreign)]
-pub trait FfiConsentCallback: Send + Sync {
-    fn on_consent_update(&self, consent: Vec<FfiConsent>);
+pub trait FfiFeatureFlagCallback: Send + Sync {
+    fn on_feature_flags_changed(&self, changes: Vec<FfiFeatureFlagChange>);
     fn on_error(&self, error: FfiSubscribeError);
 }
 
@@ -2228,6 +4009,10 @@ pub trait FfiPreferenceCallback: Send + Sync {
 #[derive(uniffi::Enum)]
 pub enum FfiPreferenceUpdate {
     HMAC { key: Vec<u8> },
+    ConsentPolicy { policy: FfiConsentPolicy },
+    FeatureFlag { name: String, enabled: Option<bool> },
+    MuteRuleAdded { rule: FfiMuteRule },
+    MuteRuleRemoved { id: String },
 }
 
 #[derive(uniffi::Object)]
@@ -3023,6 +4808,7 @@ mod tests {
                     message_disappearing_settings: Some(
                         conversation_message_disappearing_settings.clone(),
                     ),
+                    group_expires_at_ns: None,
                 },
             )
             .await
@@ -3351,7 +5137,8 @@ mod tests {
 
         let last_message = conversations[0].last_message.as_ref().unwrap();
         assert_eq!(
-            TextCodec::decode(bytes_to_encoded_content(last_message.content.clone())).unwrap(),
+            TextCodec::decode(bytes_to_encoded_content(last_message.content.clone()).unwrap())
+                .unwrap(),
             "Text message for Group 2".to_string(),
             "Last message content should be the most recent"
         );
@@ -4452,6 +6239,127 @@ mod tests {
         assert!(stream.is_closed());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
+    async fn test_overlapping_streams_deliver_message_once() {
+        let alix = new_test_client().await;
+        let bo = new_test_client().await;
+
+        let alix_group = alix
+            .conversations()
+            .create_group(
+                vec![bo.account_address.clone()],
+                FfiCreateGroupOptions::default(),
+            )
+            .await
+            .unwrap();
+        bo.conversations().sync().await.unwrap();
+        let bo_group = bo.conversation(alix_group.id()).unwrap();
+        bo.set_stream_dedupe_window(xmtp_mls::configuration::STREAM_DEDUPE_WINDOW as u32);
+
+        // Both streams deliver to the same callback
+        let stream_callback = Arc::new(RustStreamCallback::default());
+        let stream_all = bo
+            .conversations()
+            .stream_all_messages(stream_callback.clone())
+            .await;
+        let stream_group = bo_group.stream(stream_callback.clone()).await;
+        stream_all.wait_for_ready().await;
+        stream_group.wait_for_ready().await;
+
+        alix_group.send("hello".as_bytes().to_vec()).await.unwrap();
+        stream_callback.wait_for_delivery(None).await.unwrap();
+        assert!(stream_callback.wait_for_delivery(Some(2)).await.is_err());
+        assert_eq!(stream_callback.message_count(), 1);
+
+        stream_all.end_and_wait().await.unwrap();
+        stream_group.end_and_wait().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
+    async fn test_independent_streams_each_deliver_message() {
+        let alix = new_test_client().await;
+        let bo = new_test_client().await;
+
+        let alix_group = alix
+            .conversations()
+            .create_group(
+                vec![bo.account_address.clone()],
+                FfiCreateGroupOptions::default(),
+            )
+            .await
+            .unwrap();
+        bo.conversations().sync().await.unwrap();
+        let bo_group = bo.conversation(alix_group.id()).unwrap();
+
+        let all_callback = Arc::new(RustStreamCallback::default());
+        let group_callback = Arc::new(RustStreamCallback::default());
+        let stream_all = bo
+            .conversations()
+            .stream_all_messages(all_callback.clone())
+            .await;
+        let stream_group = bo_group.stream(group_callback.clone()).await;
+        stream_all.wait_for_ready().await;
+        stream_group.wait_for_ready().await;
+
+        alix_group.send("hello".as_bytes().to_vec()).await.unwrap();
+        all_callback.wait_for_delivery(None).await.unwrap();
+        group_callback.wait_for_delivery(None).await.unwrap();
+        assert_eq!(all_callback.message_count(), 1);
+        assert_eq!(group_callback.message_count(), 1);
+
+        stream_all.end_and_wait().await.unwrap();
+        stream_group.end_and_wait().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
+    async fn test_pause_resume_and_replay_stream() {
+        let alix = new_test_client().await;
+        let bo = new_test_client().await;
+
+        let alix_group = alix
+            .conversations()
+            .create_group(
+                vec![bo.account_address.clone()],
+                FfiCreateGroupOptions::default(),
+            )
+            .await
+            .unwrap();
+        bo.conversations().sync().await.unwrap();
+
+        let stream_callback = Arc::new(RustStreamCallback::default());
+        let stream = bo
+            .conversations()
+            .stream_all_messages(stream_callback.clone())
+            .await;
+        stream.wait_for_ready().await;
+
+        stream.pause().unwrap();
+        alix_group.send("first".as_bytes().to_vec()).await.unwrap();
+        alix_group.send("second".as_bytes().to_vec()).await.unwrap();
+        assert!(stream_callback.wait_for_delivery(Some(2)).await.is_err());
+        assert_eq!(stream_callback.message_count(), 0);
+
+        // Messages missed while paused are delivered from storage
+        stream.resume().unwrap();
+        stream_callback.wait_for_delivery(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(stream_callback.message_count(), 2);
+
+        // Every stored message is delivered again
+        let stored = bo
+            .conversation(alix_group.id())
+            .unwrap()
+            .find_messages(FfiListMessagesOptions::default())
+            .await
+            .unwrap();
+        stream.replay_from(0).unwrap();
+        stream_callback.wait_for_delivery(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(stream_callback.message_count(), 2 + stored.len() as u32);
+
+        stream.end_and_wait().await.unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
     async fn test_stream_all_messages() {
         let alix = new_test_client().await;
@@ -5050,6 +6958,7 @@ mod tests {
             group_pinned_frame_url: Some("https://example.com/frame.png".to_string()),
             custom_permission_policy_set: Some(custom_permissions),
             message_disappearing_settings: None,
+            group_expires_at_ns: None,
         };
 
         let alix_group = alix
@@ -5178,6 +7087,7 @@ mod tests {
             group_pinned_frame_url: Some("https://example.com/frame.png".to_string()),
             custom_permission_policy_set: Some(custom_permissions_invalid_1),
             message_disappearing_settings: None,
+            group_expires_at_ns: None,
         };
 
         let results_1 = alix
@@ -5198,6 +7108,7 @@ mod tests {
             group_pinned_frame_url: Some("https://example.com/frame.png".to_string()),
             custom_permission_policy_set: Some(custom_permissions_valid.clone()),
             message_disappearing_settings: None,
+            group_expires_at_ns: None,
         };
 
         let results_2 = alix
@@ -5218,6 +7129,7 @@ mod tests {
             group_pinned_frame_url: Some("https://example.com/frame.png".to_string()),
             custom_permission_policy_set: Some(custom_permissions_valid.clone()),
             message_disappearing_settings: None,
+            group_expires_at_ns: None,
         };
 
         let results_3 = alix
@@ -5238,6 +7150,7 @@ mod tests {
             group_pinned_frame_url: Some("https://example.com/frame.png".to_string()),
             custom_permission_policy_set: Some(custom_permissions_valid),
             message_disappearing_settings: None,
+            group_expires_at_ns: None,
         };
 
         let results_4 = alix
@@ -5644,6 +7557,7 @@ mod tests {
                 entity: bo.account_address.clone(),
                 entity_type: FfiConsentEntityType::Address,
                 state: FfiConsentState::Allowed,
+                expires_at_ns: None,
             }])
             .await
             .unwrap();
@@ -5733,6 +7647,7 @@ mod tests {
             state: FfiConsentState::Allowed,
             entity_type: FfiConsentEntityType::ConversationId,
             entity: hex::encode(bo_group.id()),
+            expires_at_ns: None,
         }])
         .await
         .unwrap();
@@ -5769,6 +7684,7 @@ mod tests {
             state: FfiConsentState::Allowed,
             entity_type: FfiConsentEntityType::ConversationId,
             entity: hex::encode(bo_dm.id()),
+            expires_at_ns: None,
         }])
         .await
         .unwrap();
@@ -5814,6 +7730,7 @@ mod tests {
             state: FfiConsentState::Allowed,
             entity_type: FfiConsentEntityType::Address,
             entity: bo.account_address.clone(),
+            expires_at_ns: None,
         }])
         .await
         .unwrap();
@@ -1,5 +1,6 @@
 use crate::conversations::Conversations;
 use crate::inbox_state::InboxState;
+use crate::retry::RetryPolicies;
 use crate::signatures::SignatureRequestType;
 use crate::ErrorWrapper;
 use napi::bindgen_prelude::{Error, Result, Uint8Array};
@@ -109,12 +110,25 @@ fn init_logging(options: LogOptions) -> Result<()> {
   Ok(())
 }
 
+/**
+ * Derive the encryption key of the database at `dbPath` from a passphrase, to pass to
+ * `createClient`. The salt is generated on first use and stored next to the database.
+ */
+#[napi]
+pub fn derive_encryption_key(db_path: String, passphrase: String) -> Result<Uint8Array> {
+  let key = EncryptedMessageStore::key_from_passphrase(&db_path, &passphrase, None)
+    .map_err(ErrorWrapper::from)?;
+  Ok(key.to_vec().into())
+}
+
 /**
  * Create a client
  *
  * Optionally specify a filter for the log level as a string.
  * It can be one of: `debug`, `info`, `warn`, `error` or 'off'.
  * By default, logging is disabled.
+ *
+ * Optionally specify retry policies for network, storage and MLS operations.
  */
 #[allow(clippy::too_many_arguments)]
 #[napi]
@@ -127,6 +141,7 @@ pub async fn create_client(
   encryption_key: Option<Uint8Array>,
   history_sync_url: Option<String>,
   log_options: Option<LogOptions>,
+  retry_policies: Option<RetryPolicies>,
 ) -> Result<Client> {
   init_logging(log_options.unwrap_or_default())?;
   let api_client = TonicApiClient::create(&host, is_secure)
@@ -155,7 +170,7 @@ pub async fn create_client(
 
   let identity_strategy = IdentityStrategy::new(
     inbox_id.clone(),
-    account_address.clone().to_lowercase(),
+    account_address.clone(),
     // this is a temporary solution
     1,
     None,
@@ -165,6 +180,7 @@ pub async fn create_client(
     Some(url) => ClientBuilder::new(identity_strategy)
       .api_client(api_client)
       .store(store)
+      .retry_policies(retry_policies.unwrap_or_default().into())
       .history_sync_url(&url)
       .build()
       .await
@@ -173,6 +189,7 @@ pub async fn create_client(
     None => ClientBuilder::new(identity_strategy)
       .api_client(api_client)
       .store(store)
+      .retry_policies(retry_policies.unwrap_or_default().into())
       .build()
       .await
       .map_err(ErrorWrapper::from)?,
@@ -55,6 +55,8 @@ pub struct Consent {
   pub entity_type: ConsentEntityType,
   pub state: ConsentState,
   pub entity: String,
+  /// Time in NS after which the consent reverts to unknown, if it is temporary
+  pub expires_at_ns: Option<i64>,
 }
 
 impl From<Consent> for StoredConsentRecord {
@@ -63,6 +65,7 @@ impl From<Consent> for StoredConsentRecord {
       entity_type: consent.entity_type.into(),
       state: consent.state.into(),
       entity: consent.entity,
+      expires_at_ns: consent.expires_at_ns,
     }
   }
 }
@@ -11,9 +11,10 @@ use xmtp_mls::groups::{GroupMetadataOptions, HmacKey as XmtpHmacKey, Preconfigur
 use xmtp_mls::storage::group::ConversationType as XmtpConversationType;
 use xmtp_mls::storage::group::GroupMembershipState as XmtpGroupMembershipState;
 use xmtp_mls::storage::group::GroupQueryArgs;
+use xmtp_mls::storage::group::GroupSortKey as XmtpGroupSortKey;
 
 use crate::conversation::MessageDisappearingSettings;
-use crate::message::Message;
+use crate::message::{Message, SortDirection};
 use crate::permissions::{GroupPermissionsOptions, PermissionPolicySet};
 use crate::ErrorWrapper;
 use crate::{client::RustXmtpClient, conversation::Conversation, streams::StreamCloser};
@@ -52,6 +53,7 @@ pub enum GroupMembershipState {
   Allowed = 0,
   Rejected = 1,
   Pending = 2,
+  PendingRemove = 3,
 }
 
 impl From<XmtpGroupMembershipState> for GroupMembershipState {
@@ -60,6 +62,7 @@ impl From<XmtpGroupMembershipState> for GroupMembershipState {
       XmtpGroupMembershipState::Allowed => GroupMembershipState::Allowed,
       XmtpGroupMembershipState::Rejected => GroupMembershipState::Rejected,
       XmtpGroupMembershipState::Pending => GroupMembershipState::Pending,
+      XmtpGroupMembershipState::PendingRemove => GroupMembershipState::PendingRemove,
     }
   }
 }
@@ -70,6 +73,25 @@ impl From<GroupMembershipState> for XmtpGroupMembershipState {
       GroupMembershipState::Allowed => XmtpGroupMembershipState::Allowed,
       GroupMembershipState::Rejected => XmtpGroupMembershipState::Rejected,
       GroupMembershipState::Pending => XmtpGroupMembershipState::Pending,
+      GroupMembershipState::PendingRemove => XmtpGroupMembershipState::PendingRemove,
+    }
+  }
+}
+
+#[napi]
+#[derive(Debug)]
+pub enum GroupSortKey {
+  CreatedAt = 0,
+  LastMessage = 1,
+  Name = 2,
+}
+
+impl From<GroupSortKey> for XmtpGroupSortKey {
+  fn from(key: GroupSortKey) -> Self {
+    match key {
+      GroupSortKey::CreatedAt => XmtpGroupSortKey::CreatedAt,
+      GroupSortKey::LastMessage => XmtpGroupSortKey::LastMessage,
+      GroupSortKey::Name => XmtpGroupSortKey::Name,
     }
   }
 }
@@ -82,6 +104,8 @@ pub struct ListConversationsOptions {
   pub created_before_ns: Option<i64>,
   pub limit: Option<i64>,
   pub conversation_type: Option<ConversationType>,
+  pub sort_by: Option<GroupSortKey>,
+  pub direction: Option<SortDirection>,
 }
 
 impl From<ListConversationsOptions> for GroupQueryArgs {
@@ -96,6 +120,8 @@ impl From<ListConversationsOptions> for GroupQueryArgs {
       .maybe_created_after_ns(opts.created_after_ns)
       .maybe_created_before_ns(opts.created_before_ns)
       .maybe_limit(opts.limit)
+      .sort_by(opts.sort_by.map(Into::into).unwrap_or_default())
+      .maybe_direction(opts.direction.map(Into::into))
   }
 }
 
@@ -136,6 +162,7 @@ impl CreateGroupOptions {
       message_disappearing_settings: self
         .message_disappearing_settings
         .map(|settings| settings.into()),
+      expires_at_ns: None,
     }
   }
 }
@@ -1,6 +1,7 @@
 use napi::bindgen_prelude::{BigInt, Result, Uint8Array};
 use napi_derive::napi;
 use xmtp_id::associations::{AssociationState, MemberIdentifier};
+use xmtp_mls::inbox_state::InboxState as MlsInboxState;
 
 use crate::{client::Client, ErrorWrapper};
 
@@ -41,6 +42,25 @@ impl From<AssociationState> for InboxState {
   }
 }
 
+impl From<MlsInboxState> for InboxState {
+  fn from(state: MlsInboxState) -> Self {
+    Self {
+      inbox_id: state.inbox_id,
+      recovery_address: state.recovery_address,
+      installations: state
+        .installations
+        .into_iter()
+        .map(|installation| Installation {
+          bytes: Uint8Array::from(installation.id.as_slice()),
+          client_timestamp_ns: installation.client_timestamp_ns.map(BigInt::from),
+          id: hex::encode(installation.id),
+        })
+        .collect(),
+      account_addresses: state.account_addresses,
+    }
+  }
+}
+
 #[napi]
 impl Client {
   /**
@@ -53,7 +73,7 @@ impl Client {
   pub async fn inbox_state(&self, refresh_from_network: bool) -> Result<InboxState> {
     let state = self
       .inner_client()
-      .inbox_state(refresh_from_network)
+      .inbox_state(self.inner_client().inbox_id(), refresh_from_network)
       .await
       .map_err(ErrorWrapper::from)?;
     Ok(state.into())
@@ -61,14 +81,9 @@ impl Client {
 
   #[napi]
   pub async fn get_latest_inbox_state(&self, inbox_id: String) -> Result<InboxState> {
-    let conn = self
-      .inner_client()
-      .store()
-      .conn()
-      .map_err(ErrorWrapper::from)?;
     let state = self
       .inner_client()
-      .get_latest_association_state(&conn, &inbox_id)
+      .inbox_state(&inbox_id, true)
       .await
       .map_err(ErrorWrapper::from)?;
     Ok(state.into())
@@ -10,6 +10,7 @@ pub mod inbox_id;
 mod inbox_state;
 mod message;
 mod permissions;
+pub mod retry;
 mod signatures;
 mod streams;
 
@@ -53,6 +53,7 @@ impl From<DeliveryStatus> for XmtpDeliveryStatus {
 }
 
 #[napi]
+#[derive(Debug)]
 pub enum SortDirection {
   Ascending,
   Descending,
@@ -102,6 +103,8 @@ pub struct Message {
   pub content: EncodedContent,
   pub kind: GroupMessageKind,
   pub delivery_status: DeliveryStatus,
+  /// Whether the sender installation was verified when the message was processed
+  pub sender_verified: bool,
 }
 
 impl From<StoredGroupMessage> for Message {
@@ -131,6 +134,7 @@ impl From<StoredGroupMessage> for Message {
       content,
       kind: msg.kind.into(),
       delivery_status: msg.delivery_status.into(),
+      sender_verified: msg.sender_verified,
     }
   }
 }
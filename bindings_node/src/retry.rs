@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use napi_derive::napi;
+use xmtp_common::retry::{Retry, RetryPolicies as XmtpRetryPolicies};
+
+/// How to retry one class of failed operations. Unset fields keep their defaults.
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct RetryPolicy {
+  /// Number of retries after the first attempt
+  pub retries: Option<u32>,
+  /// Delay before the first retry, in milliseconds
+  pub delay_ms: Option<u32>,
+  /// Factor the delay is multiplied by on each subsequent retry
+  pub multiplier: Option<u32>,
+  /// Largest random jitter added to each delay, in milliseconds
+  pub max_jitter_ms: Option<u32>,
+  /// Stop retrying once this many milliseconds have passed since the first attempt
+  pub max_elapsed_ms: Option<u32>,
+}
+
+impl From<RetryPolicy> for Retry {
+  fn from(policy: RetryPolicy) -> Self {
+    let mut builder = Retry::builder();
+    if let Some(retries) = policy.retries {
+      builder = builder.retries(retries as usize);
+    }
+    if let Some(delay_ms) = policy.delay_ms {
+      builder = builder.duration(Duration::from_millis(delay_ms.into()));
+    }
+    if let Some(multiplier) = policy.multiplier {
+      builder = builder.multiplier(multiplier);
+    }
+    if let Some(max_jitter_ms) = policy.max_jitter_ms {
+      builder = builder.max_jitter(Duration::from_millis(max_jitter_ms.into()));
+    }
+    if let Some(max_elapsed_ms) = policy.max_elapsed_ms {
+      builder = builder.max_elapsed(Duration::from_millis(max_elapsed_ms.into()));
+    }
+    builder.build()
+  }
+}
+
+/// Retry policies for each class of operations. Unset policies keep their defaults.
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct RetryPolicies {
+  /// Requests to the XMTP network
+  pub network: Option<RetryPolicy>,
+  /// Local database operations
+  pub storage: Option<RetryPolicy>,
+  /// Processing MLS messages and commits
+  pub mls: Option<RetryPolicy>,
+}
+
+impl From<RetryPolicies> for XmtpRetryPolicies {
+  fn from(policies: RetryPolicies) -> Self {
+    XmtpRetryPolicies::builder()
+      .network(policies.network.unwrap_or_default().into())
+      .storage(policies.storage.unwrap_or_default().into())
+      .mls(policies.mls.unwrap_or_default().into())
+      .build()
+  }
+}
@@ -60,7 +60,7 @@ impl Client {
   pub async fn add_wallet_signature_text(&self, new_wallet_address: String) -> Result<String> {
     let signature_request = self
       .inner_client()
-      .associate_wallet(new_wallet_address.to_lowercase())
+      .associate_wallet(new_wallet_address)
       .await
       .map_err(ErrorWrapper::from)?;
     let signature_text = signature_request.signature_text();
@@ -75,7 +75,7 @@ impl Client {
   pub async fn revoke_wallet_signature_text(&self, wallet_address: String) -> Result<String> {
     let signature_request = self
       .inner_client()
-      .revoke_wallets(vec![wallet_address.to_lowercase()])
+      .revoke_wallets(vec![wallet_address])
       .await
       .map_err(ErrorWrapper::from)?;
     let signature_text = signature_request.signature_text();
@@ -91,7 +91,7 @@ impl Client {
     let installation_id = self.inner_client().installation_public_key();
     let inbox_state = self
       .inner_client()
-      .inbox_state(true)
+      .inbox_state(self.inner_client().inbox_id(), true)
       .await
       .map_err(ErrorWrapper::from)?;
     let other_installation_ids = inbox_state
@@ -16,6 +16,7 @@ use xmtp_mls::Client as MlsClient;
 use xmtp_proto::xmtp::mls::message_contents::DeviceSyncKind;
 
 use crate::conversations::Conversations;
+use crate::retry::RetryPolicies;
 use crate::signatures::SignatureRequestType;
 
 pub type RustXmtpClient = MlsClient<XmtpHttpApiClient>;
@@ -125,6 +126,7 @@ pub async fn create_client(
   encryption_key: Option<Uint8Array>,
   history_sync_url: Option<String>,
   log_options: Option<LogOptions>,
+  retry_policies: Option<RetryPolicies>,
 ) -> Result<Client, JsError> {
   init_logging(log_options.unwrap_or_default())?;
   xmtp_mls::storage::init_sqlite().await;
@@ -152,7 +154,7 @@ pub async fn create_client(
 
   let identity_strategy = IdentityStrategy::new(
     inbox_id.clone(),
-    account_address.clone().to_lowercase(),
+    account_address.clone(),
     // this is a temporary solution
     1,
     None,
@@ -162,6 +164,7 @@ pub async fn create_client(
     Some(url) => ClientBuilder::new(identity_strategy)
       .api_client(api_client)
       .store(store)
+      .retry_policies(retry_policies.unwrap_or_default().into())
       .history_sync_url(&url)
       .build()
       .await
@@ -169,6 +172,7 @@ pub async fn create_client(
     None => ClientBuilder::new(identity_strategy)
       .api_client(api_client)
       .store(store)
+      .retry_policies(retry_policies.unwrap_or_default().into())
       .build()
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?,
@@ -57,6 +57,9 @@ pub struct Consent {
   pub entity_type: ConsentEntityType,
   pub state: ConsentState,
   pub entity: String,
+  /// Time in NS after which the consent reverts to unknown, if it is temporary
+  #[wasm_bindgen(js_name = expiresAtNs)]
+  pub expires_at_ns: Option<i64>,
 }
 
 #[wasm_bindgen]
@@ -67,6 +70,7 @@ impl Consent {
       entity_type,
       state,
       entity,
+      expires_at_ns: None,
     }
   }
 }
@@ -77,6 +81,7 @@ impl From<Consent> for StoredConsentRecord {
       entity_type: consent.entity_type.into(),
       state: consent.state.into(),
       entity: consent.entity,
+      expires_at_ns: consent.expires_at_ns,
     }
   }
 }
@@ -638,6 +638,8 @@ mod tests {
       version_minor: 123,
       authority_id: String::from("test"),
       reference_id: None,
+      sent_by_me: false,
+      sender_verified: true,
     };
     let value = crate::to_value(&stored_message).unwrap();
   }
@@ -6,9 +6,10 @@ use xmtp_mls::groups::{GroupMetadataOptions, HmacKey as XmtpHmacKey, Preconfigur
 use xmtp_mls::storage::group::ConversationType as XmtpConversationType;
 use xmtp_mls::storage::group::GroupMembershipState as XmtpGroupMembershipState;
 use xmtp_mls::storage::group::GroupQueryArgs;
+use xmtp_mls::storage::group::GroupSortKey as XmtpGroupSortKey;
 
 use crate::conversation::MessageDisappearingSettings;
-use crate::messages::Message;
+use crate::messages::{Message, SortDirection};
 use crate::permissions::{GroupPermissionsOptions, PermissionPolicySet};
 use crate::streams::{StreamCallback, StreamCloser};
 use crate::{client::RustXmtpClient, conversation::Conversation};
@@ -47,6 +48,7 @@ pub enum GroupMembershipState {
   Allowed = 0,
   Rejected = 1,
   Pending = 2,
+  PendingRemove = 3,
 }
 
 impl From<XmtpGroupMembershipState> for GroupMembershipState {
@@ -55,6 +57,7 @@ impl From<XmtpGroupMembershipState> for GroupMembershipState {
       XmtpGroupMembershipState::Allowed => GroupMembershipState::Allowed,
       XmtpGroupMembershipState::Rejected => GroupMembershipState::Rejected,
       XmtpGroupMembershipState::Pending => GroupMembershipState::Pending,
+      XmtpGroupMembershipState::PendingRemove => GroupMembershipState::PendingRemove,
     }
   }
 }
@@ -65,6 +68,25 @@ impl From<GroupMembershipState> for XmtpGroupMembershipState {
       GroupMembershipState::Allowed => XmtpGroupMembershipState::Allowed,
       GroupMembershipState::Rejected => XmtpGroupMembershipState::Rejected,
       GroupMembershipState::Pending => XmtpGroupMembershipState::Pending,
+      GroupMembershipState::PendingRemove => XmtpGroupMembershipState::PendingRemove,
+    }
+  }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub enum GroupSortKey {
+  CreatedAt = 0,
+  LastMessage = 1,
+  Name = 2,
+}
+
+impl From<GroupSortKey> for XmtpGroupSortKey {
+  fn from(key: GroupSortKey) -> Self {
+    match key {
+      GroupSortKey::CreatedAt => XmtpGroupSortKey::CreatedAt,
+      GroupSortKey::LastMessage => XmtpGroupSortKey::LastMessage,
+      GroupSortKey::Name => XmtpGroupSortKey::Name,
     }
   }
 }
@@ -81,6 +103,9 @@ pub struct ListConversationsOptions {
   #[wasm_bindgen(js_name = createdBeforeNs)]
   pub created_before_ns: Option<i64>,
   pub limit: Option<i64>,
+  #[wasm_bindgen(js_name = sortBy)]
+  pub sort_by: Option<GroupSortKey>,
+  pub direction: Option<SortDirection>,
 }
 
 impl From<ListConversationsOptions> for GroupQueryArgs {
@@ -95,6 +120,8 @@ impl From<ListConversationsOptions> for GroupQueryArgs {
       .maybe_created_after_ns(opts.created_after_ns)
       .maybe_created_before_ns(opts.created_before_ns)
       .maybe_limit(opts.limit)
+      .sort_by(opts.sort_by.map(Into::into).unwrap_or_default())
+      .maybe_direction(opts.direction.map(Into::into))
   }
 }
 
@@ -114,6 +141,8 @@ impl ListConversationsOptions {
       created_after_ns,
       created_before_ns,
       limit,
+      sort_by: None,
+      direction: None,
     }
   }
 }
@@ -171,6 +200,7 @@ impl CreateGroupOptions {
       message_disappearing_settings: self
         .message_disappearing_settings
         .map(|settings| settings.into()),
+      expires_at_ns: None,
     }
   }
 }
@@ -1,6 +1,7 @@
+use crate::retry::RetryPolicies;
 use wasm_bindgen::prelude::{wasm_bindgen, JsError};
 use xmtp_api_http::XmtpHttpApiClient;
-use xmtp_common::retry::Retry;
+use xmtp_cryptography::account_id::normalize_account_address;
 use xmtp_id::associations::generate_inbox_id as xmtp_id_generate_inbox_id;
 use xmtp_mls::api::ApiClientWrapper;
 
@@ -8,11 +9,13 @@ use xmtp_mls::api::ApiClientWrapper;
 pub async fn get_inbox_id_for_address(
   host: String,
   account_address: String,
+  retry_policies: Option<RetryPolicies>,
 ) -> Result<Option<String>, JsError> {
-  let account_address = account_address.to_lowercase();
+  let account_address = normalize_account_address(&account_address)
+    .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
   let api_client = ApiClientWrapper::new(
     XmtpHttpApiClient::new(host.clone())?.into(),
-    Retry::default(),
+    retry_policies.unwrap_or_default().into(),
   );
 
   let results = api_client
@@ -25,7 +28,6 @@ pub async fn get_inbox_id_for_address(
 
 #[wasm_bindgen(js_name = generateInboxId)]
 pub fn generate_inbox_id(account_address: String) -> Result<String, JsError> {
-  let account_address = account_address.to_lowercase();
   // ensure that the nonce is always 1 for now since this will only be used for the
   // create_client function above, which also has a hard-coded nonce of 1
   let result = xmtp_id_generate_inbox_id(&account_address, &1)
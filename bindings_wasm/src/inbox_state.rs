@@ -1,6 +1,7 @@
 use js_sys::Uint8Array;
 use wasm_bindgen::{prelude::wasm_bindgen, JsError};
 use xmtp_id::associations::{AssociationState, MemberIdentifier};
+use xmtp_mls::inbox_state::InboxState as MlsInboxState;
 
 use crate::client::Client;
 
@@ -76,6 +77,25 @@ impl From<AssociationState> for InboxState {
   }
 }
 
+impl From<MlsInboxState> for InboxState {
+  fn from(state: MlsInboxState) -> Self {
+    Self {
+      inbox_id: state.inbox_id,
+      recovery_address: state.recovery_address,
+      installations: state
+        .installations
+        .into_iter()
+        .map(|installation| Installation {
+          bytes: Uint8Array::from(installation.id.as_slice()),
+          client_timestamp_ns: installation.client_timestamp_ns,
+          id: hex::encode(installation.id),
+        })
+        .collect(),
+      account_addresses: state.account_addresses,
+    }
+  }
+}
+
 #[wasm_bindgen]
 impl Client {
   /**
@@ -88,7 +108,7 @@ impl Client {
   pub async fn inbox_state(&self, refresh_from_network: bool) -> Result<InboxState, JsError> {
     let state = self
       .inner_client()
-      .inbox_state(refresh_from_network)
+      .inbox_state(self.inner_client().inbox_id(), refresh_from_network)
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     Ok(state.into())
@@ -96,14 +116,9 @@ impl Client {
 
   #[wasm_bindgen(js_name = getLatestInboxState)]
   pub async fn get_latest_inbox_state(&self, inbox_id: String) -> Result<InboxState, JsError> {
-    let conn = self
-      .inner_client()
-      .store()
-      .conn()
-      .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     let state = self
       .inner_client()
-      .get_latest_association_state(&conn, &inbox_id)
+      .inbox_state(&inbox_id, true)
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     Ok(state.into())
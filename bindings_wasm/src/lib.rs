@@ -7,6 +7,7 @@ pub mod inbox_id;
 pub mod inbox_state;
 pub mod messages;
 pub mod permissions;
+pub mod retry;
 pub mod signatures;
 pub mod streams;
 
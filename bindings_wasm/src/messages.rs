@@ -132,11 +132,15 @@ pub struct Message {
   pub kind: GroupMessageKind,
   #[wasm_bindgen(js_name = deliveryStatus)]
   pub delivery_status: DeliveryStatus,
+  /// Whether the sender installation was verified when the message was processed
+  #[wasm_bindgen(js_name = senderVerified)]
+  pub sender_verified: bool,
 }
 
 #[wasm_bindgen]
 impl Message {
   #[wasm_bindgen(constructor)]
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     id: String,
     sent_at_ns: i64,
@@ -145,6 +149,7 @@ impl Message {
     content: EncodedContent,
     kind: GroupMessageKind,
     delivery_status: DeliveryStatus,
+    sender_verified: bool,
   ) -> Self {
     Self {
       id,
@@ -154,6 +159,7 @@ impl Message {
       content,
       kind,
       delivery_status,
+      sender_verified,
     }
   }
 }
@@ -185,6 +191,7 @@ impl From<StoredGroupMessage> for Message {
       content,
       kind: msg.kind.into(),
       delivery_status: msg.delivery_status.into(),
+      sender_verified: msg.sender_verified,
     }
   }
 }
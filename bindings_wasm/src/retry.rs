@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+use xmtp_common::retry::{Retry, RetryPolicies as XmtpRetryPolicies};
+
+/// How to retry one class of failed operations. Unset fields keep their defaults.
+#[derive(Clone, Default)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct RetryPolicy {
+  /// Number of retries after the first attempt
+  pub retries: Option<u32>,
+  /// Delay before the first retry, in milliseconds
+  #[wasm_bindgen(js_name = delayMs)]
+  pub delay_ms: Option<u32>,
+  /// Factor the delay is multiplied by on each subsequent retry
+  pub multiplier: Option<u32>,
+  /// Largest random jitter added to each delay, in milliseconds
+  #[wasm_bindgen(js_name = maxJitterMs)]
+  pub max_jitter_ms: Option<u32>,
+  /// Stop retrying once this many milliseconds have passed since the first attempt
+  #[wasm_bindgen(js_name = maxElapsedMs)]
+  pub max_elapsed_ms: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl RetryPolicy {
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    retries: Option<u32>,
+    delay_ms: Option<u32>,
+    multiplier: Option<u32>,
+    max_jitter_ms: Option<u32>,
+    max_elapsed_ms: Option<u32>,
+  ) -> Self {
+    Self {
+      retries,
+      delay_ms,
+      multiplier,
+      max_jitter_ms,
+      max_elapsed_ms,
+    }
+  }
+}
+
+impl From<RetryPolicy> for Retry {
+  fn from(policy: RetryPolicy) -> Self {
+    let mut builder = Retry::builder();
+    if let Some(retries) = policy.retries {
+      builder = builder.retries(retries as usize);
+    }
+    if let Some(delay_ms) = policy.delay_ms {
+      builder = builder.duration(Duration::from_millis(delay_ms.into()));
+    }
+    if let Some(multiplier) = policy.multiplier {
+      builder = builder.multiplier(multiplier);
+    }
+    if let Some(max_jitter_ms) = policy.max_jitter_ms {
+      builder = builder.max_jitter(Duration::from_millis(max_jitter_ms.into()));
+    }
+    if let Some(max_elapsed_ms) = policy.max_elapsed_ms {
+      builder = builder.max_elapsed(Duration::from_millis(max_elapsed_ms.into()));
+    }
+    builder.build()
+  }
+}
+
+/// Retry policies for each class of operations. Unset policies keep their defaults.
+#[derive(Clone, Default)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct RetryPolicies {
+  /// Requests to the XMTP network
+  pub network: Option<RetryPolicy>,
+  /// Local database operations
+  pub storage: Option<RetryPolicy>,
+  /// Processing MLS messages and commits
+  pub mls: Option<RetryPolicy>,
+}
+
+#[wasm_bindgen]
+impl RetryPolicies {
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    network: Option<RetryPolicy>,
+    storage: Option<RetryPolicy>,
+    mls: Option<RetryPolicy>,
+  ) -> Self {
+    Self {
+      network,
+      storage,
+      mls,
+    }
+  }
+}
+
+impl From<RetryPolicies> for XmtpRetryPolicies {
+  fn from(policies: RetryPolicies) -> Self {
+    XmtpRetryPolicies::builder()
+      .network(policies.network.unwrap_or_default().into())
+      .storage(policies.storage.unwrap_or_default().into())
+      .mls(policies.mls.unwrap_or_default().into())
+      .build()
+  }
+}
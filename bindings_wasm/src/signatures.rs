@@ -63,7 +63,7 @@ impl Client {
   ) -> Result<String, JsError> {
     let signature_request = self
       .inner_client()
-      .associate_wallet(new_wallet_address.to_lowercase())
+      .associate_wallet(new_wallet_address)
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     let signature_text = signature_request.signature_text();
@@ -82,7 +82,7 @@ impl Client {
   ) -> Result<String, JsError> {
     let signature_request = self
       .inner_client()
-      .revoke_wallets(vec![wallet_address.to_lowercase()])
+      .revoke_wallets(vec![wallet_address])
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     let signature_text = signature_request.signature_text();
@@ -99,7 +99,7 @@ impl Client {
     let installation_id = self.inner_client().installation_public_key();
     let inbox_state = self
       .inner_client()
-      .inbox_state(true)
+      .inbox_state(self.inner_client().inbox_id(), true)
       .await
       .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
     let other_installation_ids = inbox_state
@@ -24,6 +24,38 @@ pub struct NotSpecialized;
 /// All Errors are not retryable by-default.
 pub trait RetryableError<SP = NotSpecialized>: std::error::Error {
     fn is_retryable(&self) -> bool;
+
+    /// The subsystem this error originated in, used to pick a retry strategy from
+    /// [`RetryPolicies`]. `None` if the error does not belong to a specific subsystem.
+    fn error_class(&self) -> Option<ErrorClass> {
+        None
+    }
+}
+
+/// The subsystem an error originated in
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum ErrorClass {
+    /// Requests to the XMTP network
+    Network,
+    /// The local database
+    Storage,
+    /// Processing MLS messages and commits
+    Mls,
+}
+
+/// A failed attempt that is about to be retried, reported by [`retry_async!`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RetryState {
+    /// The number of the retry about to start, from 1
+    pub attempt: usize,
+    /// The number of retries the policy allows
+    pub max_retries: usize,
+    /// How long until the retry starts
+    pub delay: core::time::Duration,
+    /// Time since the first attempt started
+    pub elapsed: core::time::Duration,
+    pub error_class: Option<ErrorClass>,
+    pub error: String,
 }
 
 /// Options to specify how to retry a function
@@ -34,6 +66,8 @@ pub struct Retry {
     // The amount to multiply the duration on each subsequent attempt
     multiplier: u32,
     max_jitter_ms: usize,
+    // Stop retrying once this much time has passed since the first attempt
+    max_elapsed: Option<core::time::Duration>,
 }
 
 impl Default for Retry {
@@ -43,6 +77,7 @@ impl Default for Retry {
             duration: core::time::Duration::from_millis(50),
             multiplier: 3,
             max_jitter_ms: 25,
+            max_elapsed: None,
         }
     }
 }
@@ -65,6 +100,23 @@ impl Retry {
         let jitter = rand::thread_rng().gen_range(0..=self.max_jitter_ms);
         duration + core::time::Duration::from_millis(jitter as u64)
     }
+
+    /// Get the maximum time to spend retrying, if any
+    pub fn max_elapsed(&self) -> Option<core::time::Duration> {
+        self.max_elapsed
+    }
+
+    /// Whether another attempt is allowed after `attempts` retries,
+    /// `elapsed` time after the first attempt started
+    pub fn should_retry(&self, attempts: usize, elapsed: core::time::Duration) -> bool {
+        attempts < self.retries && self.max_elapsed.map_or(true, |max| elapsed < max)
+    }
+
+    /// The strategy to use for an error of the given class.
+    /// A single [`Retry`] applies to every class.
+    pub fn for_class(&self, _class: Option<ErrorClass>) -> Retry {
+        *self
+    }
 }
 
 /// Builder for [`Retry`]
@@ -72,6 +124,9 @@ impl Retry {
 pub struct RetryBuilder {
     retries: Option<usize>,
     duration: Option<core::time::Duration>,
+    multiplier: Option<u32>,
+    max_jitter: Option<core::time::Duration>,
+    max_elapsed: Option<core::time::Duration>,
 }
 
 /// Builder for [`Retry`].
@@ -83,6 +138,8 @@ pub struct RetryBuilder {
 /// RetryBuilder::default()
 ///     .retries(5)
 ///     .duration(core::time::Duration::from_millis(1000))
+///     .multiplier(2)
+///     .max_elapsed(core::time::Duration::from_secs(30))
 ///     .build();
 /// ```
 impl RetryBuilder {
@@ -98,6 +155,26 @@ impl RetryBuilder {
         self
     }
 
+    /// Specify the amount to multiply the duration by on each subsequent attempt.
+    /// A multiplier of `1` waits the same duration between every attempt.
+    pub fn multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = Some(multiplier);
+        self
+    }
+
+    /// Specify the maximum random jitter added to each wait
+    pub fn max_jitter(mut self, max_jitter: core::time::Duration) -> Self {
+        self.max_jitter = Some(max_jitter);
+        self
+    }
+
+    /// Stop retrying once this much time has passed since the first attempt,
+    /// even if there are retries left
+    pub fn max_elapsed(mut self, max_elapsed: core::time::Duration) -> Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
     /// Build the Retry Strategy
     pub fn build(self) -> Retry {
         let mut retry = Retry::default();
@@ -110,10 +187,115 @@ impl RetryBuilder {
             retry.duration = duration;
         }
 
+        if let Some(multiplier) = self.multiplier {
+            retry.multiplier = multiplier;
+        }
+
+        if let Some(max_jitter) = self.max_jitter {
+            retry.max_jitter_ms = max_jitter.as_millis() as usize;
+        }
+
+        retry.max_elapsed = self.max_elapsed;
+
         retry
     }
 }
 
+/// Retry strategies for each subsystem, shared by everything that retries on behalf of a client.
+///
+/// Errors that report an [`ErrorClass`] are retried according to the policy for that class,
+/// regardless of where they were raised. Errors without a class use the scope's default policy.
+///
+/// # Example
+/// ```
+/// use xmtp_common::retry::{ErrorClass, Retry, RetryPolicies};
+///
+/// let policies = RetryPolicies::builder()
+///     .network(Retry::builder().retries(10).build())
+///     .build();
+/// let network = policies.scoped(ErrorClass::Network);
+/// assert_eq!(network.for_class(None).retries(), 10);
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct RetryPolicies {
+    pub network: Retry,
+    pub storage: Retry,
+    pub mls: Retry,
+}
+
+impl RetryPolicies {
+    /// Get the builder for [`RetryPolicies`]
+    pub fn builder() -> RetryPoliciesBuilder {
+        RetryPoliciesBuilder::default()
+    }
+
+    /// The policy for errors of `class`
+    pub fn get(&self, class: ErrorClass) -> Retry {
+        match class {
+            ErrorClass::Network => self.network,
+            ErrorClass::Storage => self.storage,
+            ErrorClass::Mls => self.mls,
+        }
+    }
+
+    /// Retry with the policy for `default`, unless an error reports its own class
+    pub fn scoped(&self, default: ErrorClass) -> ScopedRetry {
+        ScopedRetry {
+            policies: *self,
+            default,
+        }
+    }
+}
+
+/// Builder for [`RetryPolicies`]. Unset policies use [`Retry::default`].
+#[derive(Default, PartialEq, Eq, Copy, Clone)]
+pub struct RetryPoliciesBuilder {
+    policies: RetryPolicies,
+}
+
+impl RetryPoliciesBuilder {
+    /// Policy for requests to the XMTP network
+    pub fn network(mut self, retry: Retry) -> Self {
+        self.policies.network = retry;
+        self
+    }
+
+    /// Policy for local database operations
+    pub fn storage(mut self, retry: Retry) -> Self {
+        self.policies.storage = retry;
+        self
+    }
+
+    /// Policy for processing MLS messages and commits
+    pub fn mls(mut self, retry: Retry) -> Self {
+        self.policies.mls = retry;
+        self
+    }
+
+    pub fn build(self) -> RetryPolicies {
+        self.policies
+    }
+}
+
+/// [`RetryPolicies`] with a default [`ErrorClass`], see [`RetryPolicies::scoped`]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ScopedRetry {
+    policies: RetryPolicies,
+    default: ErrorClass,
+}
+
+impl ScopedRetry {
+    /// Get the number of retries of the default policy
+    pub fn retries(&self) -> usize {
+        self.policies.get(self.default).retries()
+    }
+
+    /// The strategy to use for an error of the given class
+    pub fn for_class(&self, class: Option<ErrorClass>) -> Retry {
+        self.policies.get(class.unwrap_or(self.default))
+    }
+}
+
 impl Retry {
     /// Get the builder for [`Retry`]
     pub fn builder() -> RetryBuilder {
@@ -164,13 +346,44 @@ impl Retry {
 ///     }))
 /// }
 /// ```
+///
+/// A third argument is called with the [`RetryState`] before each retry, for instance to report
+/// it to the user:
+/// ```
+/// use xmtp_common::{retry_async, retry::{Retry, RetryState}};
+/// # #[derive(Debug, thiserror::Error)]
+/// # #[error("flaky")]
+/// # struct Flaky;
+/// # impl xmtp_common::RetryableError for Flaky {
+/// #     fn is_retryable(&self) -> bool { true }
+/// # }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut retries = vec![];
+/// let result = retry_async!(
+///     Retry::builder().retries(2).duration(core::time::Duration::from_millis(1)).build(),
+///     (async { Err::<(), _>(Flaky) }),
+///     |state: &RetryState| retries.push(state.attempt)
+/// );
+/// assert!(result.is_err());
+/// assert_eq!(retries, vec![1, 2]);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! retry_async {
-    ($retry: expr, $code: tt) => {{
+    ($retry: expr, $code: tt) => {
+        $crate::retry_async!($retry, $code, |_: &$crate::retry::RetryState| {})
+    };
+    ($retry: expr, $code: tt, $on_retry: expr) => {{
         use tracing::Instrument as _;
         #[allow(unused)]
         use $crate::retry::RetryableError;
+        let retry = $retry;
+        #[allow(unused_mut)]
+        let mut on_retry = $on_retry;
         let mut attempts = 0;
+        let started = $crate::time::Instant::now();
         let span = tracing::trace_span!("retry");
         loop {
             let span = span.clone();
@@ -179,13 +392,30 @@ macro_rules! retry_async {
             match res {
                 Ok(v) => break Ok(v),
                 Err(e) => {
-                    if (&e).is_retryable() && attempts < $retry.retries() {
+                    let class = (&e).error_class();
+                    let policy = retry.for_class(class);
+                    let elapsed = started.elapsed();
+                    if (&e).is_retryable() && policy.should_retry(attempts, elapsed) {
+                        attempts += 1;
+                        let delay = policy.duration(attempts);
                         tracing::warn!(
+                            attempt = attempts,
+                            max_retries = policy.retries(),
+                            delay_ms = delay.as_millis() as u64,
+                            elapsed_ms = elapsed.as_millis() as u64,
+                            error_class = ?class,
                             "retrying function that failed with error={}",
                             e.to_string()
                         );
-                        attempts += 1;
-                        $crate::time::sleep($retry.duration(attempts)).await;
+                        on_retry(&$crate::retry::RetryState {
+                            attempt: attempts,
+                            max_retries: policy.retries(),
+                            delay,
+                            elapsed,
+                            error_class: class,
+                            error: e.to_string(),
+                        });
+                        $crate::time::sleep(delay).await;
                     } else {
                         tracing::info!("error is not retryable. {:?}:{}", e, e);
                         break Err(e);
@@ -346,6 +576,68 @@ pub(crate) mod tests {
         .unwrap();
     }
 
+    #[derive(Debug, Error)]
+    #[error("storage is busy")]
+    struct BusyStorage;
+
+    impl RetryableError for BusyStorage {
+        fn is_retryable(&self) -> bool {
+            true
+        }
+
+        fn error_class(&self) -> Option<ErrorClass> {
+            Some(ErrorClass::Storage)
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn it_uses_the_policy_for_the_error_class() {
+        let policies = RetryPolicies::builder()
+            .network(Retry::builder().retries(5).build())
+            .storage(
+                Retry::builder()
+                    .retries(1)
+                    .duration(core::time::Duration::from_millis(1))
+                    .build(),
+            )
+            .build();
+
+        let mut attempts = 0;
+        let result = retry_async!(
+            policies.scoped(ErrorClass::Network),
+            (async {
+                attempts += 1;
+                Err::<(), _>(BusyStorage)
+            })
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn it_stops_after_max_elapsed() {
+        let retry = Retry::builder()
+            .retries(100)
+            .duration(core::time::Duration::from_millis(10))
+            .multiplier(1)
+            .max_jitter(core::time::Duration::ZERO)
+            .max_elapsed(core::time::Duration::from_millis(25))
+            .build();
+
+        let mut attempts = 0;
+        let result: Result<(), SomeError> = retry_async!(
+            retry,
+            (async {
+                attempts += 1;
+                retry_error_fn()
+            })
+        );
+        assert!(result.is_err());
+        assert!(attempts < 10);
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     fn backoff_retry() {
@@ -39,10 +39,11 @@ use xmtp_cryptography::{
     utils::rng,
 };
 use xmtp_id::associations::unverified::{UnverifiedRecoverableEcdsaSignature, UnverifiedSignature};
-use xmtp_id::associations::{generate_inbox_id, AssociationError, AssociationState, MemberKind};
+use xmtp_id::associations::{generate_inbox_id, AssociationError};
 use xmtp_mls::groups::device_sync::DeviceSyncContent;
 use xmtp_mls::groups::scoped_client::ScopedGroupClient;
 use xmtp_mls::groups::GroupError;
+use xmtp_mls::inbox_state::InboxState;
 use xmtp_mls::storage::group::GroupQueryArgs;
 use xmtp_mls::storage::group_message::{GroupMessageKind, MsgQueryArgs};
 use xmtp_mls::XmtpApi;
@@ -278,7 +279,8 @@ async fn main() -> color_eyre::eyre::Result<()> {
         }
         Commands::Info {} => {
             info!("Info");
-            let (recovery, ids, addrs) = pretty_association_state(&client.inbox_state(true).await?);
+            let (recovery, ids, addrs) =
+                pretty_association_state(&client.inbox_state(client.inbox_id(), true).await?);
             info!(
                 command_output = true,
                 inbox_id = client.inbox_id(),
@@ -654,19 +656,16 @@ fn pretty_delta(now: u64, then: u64) -> String {
     f.convert(Duration::from_nanos(diff))
 }
 
-fn pretty_association_state(state: &AssociationState) -> (String, Vec<String>, Vec<String>) {
-    let recovery_address = state.recovery_address().clone();
+fn pretty_association_state(state: &InboxState) -> (String, Vec<String>, Vec<String>) {
     let installation_ids = state
         .installation_ids()
         .into_iter()
         .map(hex::encode)
         .collect::<Vec<String>>();
 
-    let addresses = state
-        .members_by_kind(MemberKind::Address)
-        .into_iter()
-        .map(|m| m.identifier.to_address().unwrap())
-        .collect::<Vec<String>>();
-
-    (recovery_address, installation_ids, addresses)
+    (
+        state.recovery_address.clone(),
+        installation_ids,
+        state.account_addresses.clone(),
+    )
 }
@@ -5,11 +5,15 @@ use std::time::Duration;
 
 use futures::stream::{AbortHandle, Abortable};
 use futures::{SinkExt, Stream, StreamExt, TryStreamExt};
-use tokio::sync::oneshot;
+use tokio::sync::{broadcast, oneshot};
 use tonic::transport::ClientTlsConfig;
 use tonic::{metadata::MetadataValue, transport::Channel, Request, Streaming};
 use tracing::Instrument;
 
+use crate::keepalive::{
+    connection_events_channel, resubscribing, ConnectionEvent, KeepaliveConfig, ResubscribingStream,
+};
+
 use xmtp_proto::api_client::{ClientWithMetadata, XmtpMlsStreams};
 use xmtp_proto::xmtp::mls::api::v1::{GroupMessage, WelcomeMessage};
 use xmtp_proto::{
@@ -30,7 +34,10 @@ use xmtp_proto::{
 };
 
 #[tracing::instrument(level = "trace", skip_all)]
-pub async fn create_tls_channel(address: String) -> Result<Channel, Error> {
+pub async fn create_tls_channel(
+    address: String,
+    keepalive: &KeepaliveConfig,
+) -> Result<Channel, Error> {
     let span = tracing::debug_span!("grpc_connect", address);
     let channel = Channel::from_shared(address)
         .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
@@ -42,6 +49,10 @@ pub async fn create_tls_channel(address: String) -> Result<Channel, Error> {
         // Functionality: When set to true, this option ensures that periodic pings are sent on an idle connection to keep it alive and detect if the server is still responsive.
         // Impact: This helps maintain active connections, particularly through NATs, load balancers, and other middleboxes that might drop idle connections. It helps ensure that the connection is promptly usable when new requests need to be sent.
         .keep_alive_while_idle(true)
+        // Purpose: Sets the interval between HTTP/2 keep-alive pings.
+        // Functionality: Pings are only sent when this is set, the other keep-alive settings have no effect without it.
+        // Impact: Dead connections are detected within `interval + timeout`, after which streams are resubscribed.
+        .http2_keep_alive_interval(keepalive.interval)
         // Purpose: Sets the maximum amount of time the client will wait for a connection to be established.
         // Functionality: If a connection cannot be established within the specified duration, the attempt is aborted and an error is returned.
         // Impact: This setting prevents the client from waiting indefinitely for a connection to be established, which is crucial in scenarios where rapid failure detection is necessary to maintain responsiveness or to quickly fallback to alternative services or retry logic.
@@ -49,7 +60,7 @@ pub async fn create_tls_channel(address: String) -> Result<Channel, Error> {
         // Purpose: Configures the TCP keep-alive interval for the socket connection.
         // Functionality: This setting tells the operating system to send TCP keep-alive probes periodically when no data has been transferred over the connection within the specified interval.
         // Impact: Similar to the gRPC-level keep-alive, this helps keep the connection alive at the TCP layer and detect broken connections. It's particularly useful for detecting half-open connections and ensuring that resources are not wasted on unresponsive peers.
-        .tcp_keepalive(Some(Duration::from_secs(15)))
+        .tcp_keepalive(keepalive.tcp_keepalive)
         // Purpose: Sets a maximum duration for the client to wait for a response to a request.
         // Functionality: If a response is not received within the specified timeout, the request is canceled and an error is returned.
         // Impact: This is critical for bounding the wait time for operations, which can enhance the predictability and reliability of client interactions by avoiding indefinitely hanging requests.
@@ -57,7 +68,7 @@ pub async fn create_tls_channel(address: String) -> Result<Channel, Error> {
         // Purpose: Specifies how long the client will wait for a response to a keep-alive ping before considering the connection dead.
         // Functionality: If a ping response is not received within this duration, the connection is presumed to be lost and is closed.
         // Impact: This setting is crucial for quickly detecting unresponsive connections and freeing up resources associated with them. It ensures that the client has up-to-date information on the status of connections and can react accordingly.
-        .keep_alive_timeout(Duration::from_secs(25))
+        .keep_alive_timeout(keepalive.timeout)
         .tls_config(ClientTlsConfig::new().with_enabled_roots())
         .map_err(|e| Error::new(ErrorKind::SetupTLSConfigError).with(e))?
         .connect()
@@ -75,11 +86,22 @@ pub struct Client {
     pub(crate) identity_client: ProtoIdentityApiClient<Channel>,
     pub(crate) app_version: MetadataValue<tonic::metadata::Ascii>,
     pub(crate) libxmtp_version: MetadataValue<tonic::metadata::Ascii>,
+    pub(crate) keepalive: KeepaliveConfig,
+    pub(crate) connection_events: broadcast::Sender<ConnectionEvent>,
 }
 
 impl Client {
     #[tracing::instrument(level = "trace", skip_all)]
     pub async fn create(host: impl ToString, is_secure: bool) -> Result<Self, Error> {
+        Self::create_with_keepalive(host, is_secure, KeepaliveConfig::default()).await
+    }
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub async fn create_with_keepalive(
+        host: impl ToString,
+        is_secure: bool,
+        keepalive: KeepaliveConfig,
+    ) -> Result<Self, Error> {
         let host = host.to_string();
         let app_version = MetadataValue::try_from(&String::from("0.0.0"))
             .map_err(|e| Error::new(ErrorKind::MetadataError).with(e))?;
@@ -87,9 +109,12 @@ impl Client {
             .map_err(|e| Error::new(ErrorKind::MetadataError).with(e))?;
 
         let channel = match is_secure {
-            true => create_tls_channel(host).await?,
+            true => create_tls_channel(host, &keepalive).await?,
             false => Channel::from_shared(host)
                 .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
+                .http2_keep_alive_interval(keepalive.interval)
+                .keep_alive_timeout(keepalive.timeout)
+                .keep_alive_while_idle(true)
                 .connect()
                 .await
                 .map_err(|e| Error::new(ErrorKind::SetupConnectionError).with(e))?,
@@ -105,6 +130,8 @@ impl Client {
             app_version,
             libxmtp_version,
             identity_client,
+            keepalive,
+            connection_events: connection_events_channel(),
         })
     }
 
@@ -121,6 +148,11 @@ impl Client {
     pub fn identity_client(&self) -> &ProtoIdentityApiClient<Channel> {
         &self.identity_client
     }
+
+    /// Subscribe to connection drops and restores of this client's streams
+    pub fn connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.connection_events.subscribe()
+    }
 }
 
 impl ClientWithMetadata for Client {
@@ -416,14 +448,9 @@ impl XmtpMlsClient for Client {
     }
 }
 
+/// A stream of group messages that is resubscribed when the connection drops
 pub struct GroupMessageStream {
-    inner: tonic::codec::Streaming<GroupMessage>,
-}
-
-impl From<tonic::codec::Streaming<GroupMessage>> for GroupMessageStream {
-    fn from(inner: tonic::codec::Streaming<GroupMessage>) -> Self {
-        GroupMessageStream { inner }
-    }
+    inner: ResubscribingStream<GroupMessage>,
 }
 
 impl Stream for GroupMessageStream {
@@ -433,20 +460,13 @@ impl Stream for GroupMessageStream {
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.inner
-            .poll_next_unpin(cx)
-            .map(|data| data.map(|v| v.map_err(|e| Error::new(ErrorKind::SubscribeError).with(e))))
+        self.inner.poll_next_unpin(cx)
     }
 }
 
+/// A stream of welcome messages that is resubscribed when the connection drops
 pub struct WelcomeMessageStream {
-    inner: tonic::codec::Streaming<WelcomeMessage>,
-}
-
-impl From<tonic::codec::Streaming<WelcomeMessage>> for WelcomeMessageStream {
-    fn from(inner: tonic::codec::Streaming<WelcomeMessage>) -> Self {
-        WelcomeMessageStream { inner }
-    }
+    inner: ResubscribingStream<WelcomeMessage>,
 }
 
 impl Stream for WelcomeMessageStream {
@@ -456,9 +476,7 @@ impl Stream for WelcomeMessageStream {
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.inner
-            .poll_next_unpin(cx)
-            .map(|data| data.map(|v| v.map_err(|e| Error::new(ErrorKind::SubscribeError).with(e))))
+        self.inner.poll_next_unpin(cx)
     }
 }
 
@@ -473,12 +491,28 @@ impl XmtpMlsStreams for Client {
     ) -> Result<Self::GroupMessageStream<'_>, Error> {
         let client = &mut self.mls_client.clone();
         let res = client
-            .subscribe_group_messages(self.build_request(req))
+            .subscribe_group_messages(self.build_request(req.clone()))
             .await
             .map_err(|e| Error::new(ErrorKind::MlsError).with(e))?;
 
-        let stream = res.into_inner();
-        Ok(stream.into())
+        let this = self.clone();
+        let inner = resubscribing(
+            res.into_inner(),
+            req,
+            self.keepalive.clone(),
+            self.connection_events.clone(),
+            move |req| {
+                let this = this.clone();
+                async move {
+                    let mut client = this.mls_client.clone();
+                    let res = client
+                        .subscribe_group_messages(this.build_request(req))
+                        .await?;
+                    Ok(res.into_inner())
+                }
+            },
+        );
+        Ok(GroupMessageStream { inner })
     }
 
     async fn subscribe_welcome_messages(
@@ -487,12 +521,27 @@ impl XmtpMlsStreams for Client {
     ) -> Result<Self::WelcomeMessageStream<'_>, Error> {
         let client = &mut self.mls_client.clone();
         let res = client
-            .subscribe_welcome_messages(self.build_request(req))
+            .subscribe_welcome_messages(self.build_request(req.clone()))
             .await
             .map_err(|e| Error::new(ErrorKind::MlsError).with(e))?;
 
-        let stream = res.into_inner();
-
-        Ok(stream.into())
+        let this = self.clone();
+        let inner = resubscribing(
+            res.into_inner(),
+            req,
+            self.keepalive.clone(),
+            self.connection_events.clone(),
+            move |req| {
+                let this = this.clone();
+                async move {
+                    let mut client = this.mls_client.clone();
+                    let res = client
+                        .subscribe_welcome_messages(this.build_request(req))
+                        .await?;
+                    Ok(res.into_inner())
+                }
+            },
+        );
+        Ok(WelcomeMessageStream { inner })
     }
 }
@@ -0,0 +1,218 @@
+//! Keepalive and reconnection for long-lived streams.
+//!
+//! HTTP/2 keepalive pings detect connections that were silently dropped, for instance by a NAT
+//! or a mobile network switching cells. When a stream fails, it is transparently resubscribed
+//! starting after the last item it yielded. Streams that stay open but go quiet are left to the
+//! consumer, which knows how long silence is expected to last. Resubscribe attempts back off exponentially up to
+//! [`KeepaliveConfig::max_resubscribe_backoff`], with random jitter so that clients dropped by
+//! the same outage don't all come back at once. Each drop and restore is broadcast as a [`ConnectionEvent`], so apps can
+//! show an accurate "reconnecting…" state.
+use std::{future::Future, pin::Pin, time::Duration};
+
+use futures::Stream;
+use rand::Rng;
+use tokio::sync::broadcast;
+use tonic::{Status, Streaming};
+use xmtp_proto::{
+    xmtp::mls::api::v1::{
+        group_message, welcome_message, GroupMessage, SubscribeGroupMessagesRequest,
+        SubscribeWelcomeMessagesRequest, WelcomeMessage,
+    },
+    Error, ErrorKind,
+};
+
+/// Number of connection events buffered for slow receivers
+const CONNECTION_EVENTS_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Interval between HTTP/2 keepalive pings
+    pub interval: Duration,
+    /// How long to wait for a ping to be acknowledged before the connection is considered dead
+    pub timeout: Duration,
+    /// Interval between TCP keepalive probes
+    pub tcp_keepalive: Option<Duration>,
+    /// Delay before the first resubscribe attempt, doubled after each failed attempt
+    pub resubscribe_backoff: Duration,
+    /// Longest delay between two resubscribe attempts
+    pub max_resubscribe_backoff: Duration,
+    /// Number of failed resubscribe attempts after which the stream ends with an error
+    pub max_resubscribe_attempts: u32,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(25),
+            tcp_keepalive: Some(Duration::from_secs(15)),
+            resubscribe_backoff: Duration::from_millis(500),
+            max_resubscribe_backoff: Duration::from_secs(30),
+            max_resubscribe_attempts: 6,
+        }
+    }
+}
+
+/// A change in the state of a stream's connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A stream lost its connection and is being resubscribed
+    Dropped { reason: String },
+    /// A dropped stream was resubscribed
+    Restored,
+    /// A stream could not be resubscribed and was closed
+    Lost { reason: String },
+}
+
+pub(crate) fn connection_events_channel() -> broadcast::Sender<ConnectionEvent> {
+    broadcast::channel(CONNECTION_EVENTS_CAPACITY).0
+}
+
+/// A subscription request that can be resumed after the last item it yielded
+pub(crate) trait Resumable<Item>: Clone + Send + 'static {
+    fn resume_after(&mut self, item: &Item);
+}
+
+impl Resumable<GroupMessage> for SubscribeGroupMessagesRequest {
+    fn resume_after(&mut self, item: &GroupMessage) {
+        let Some(group_message::Version::V1(message)) = &item.version else {
+            return;
+        };
+        if let Some(filter) = self
+            .filters
+            .iter_mut()
+            .find(|filter| filter.group_id == message.group_id)
+        {
+            filter.id_cursor = message.id;
+        }
+    }
+}
+
+impl Resumable<WelcomeMessage> for SubscribeWelcomeMessagesRequest {
+    fn resume_after(&mut self, item: &WelcomeMessage) {
+        let Some(welcome_message::Version::V1(message)) = &item.version else {
+            return;
+        };
+        if let Some(filter) = self
+            .filters
+            .iter_mut()
+            .find(|filter| filter.installation_key == message.installation_key)
+        {
+            filter.id_cursor = message.id;
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    /// The delay before resubscribe attempt `attempt`, counting from 0, before jitter
+    fn resubscribe_delay(&self, attempt: u32) -> Duration {
+        self.resubscribe_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_resubscribe_backoff)
+    }
+}
+
+/// Spread `delay` randomly over its upper half
+fn with_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+pub(crate) type ResubscribingStream<Item> = Pin<Box<dyn Stream<Item = Result<Item, Error>> + Send>>;
+
+/// Wrap an established stream so that it is resubscribed with `subscribe`
+/// whenever it fails.
+pub(crate) fn resubscribing<Item, Req, F, Fut>(
+    initial: Streaming<Item>,
+    mut request: Req,
+    config: KeepaliveConfig,
+    events: broadcast::Sender<ConnectionEvent>,
+    subscribe: F,
+) -> ResubscribingStream<Item>
+where
+    Item: Send + 'static,
+    Req: Resumable<Item>,
+    F: Fn(Req) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Streaming<Item>, Status>> + Send,
+{
+    Box::pin(async_stream::stream! {
+        let mut stream = initial;
+        loop {
+            let reason = match stream.message().await {
+                Ok(Some(item)) => {
+                    request.resume_after(&item);
+                    yield Ok(item);
+                    continue;
+                }
+                // The server closed the stream
+                Ok(None) => return,
+                Err(status) => status.to_string(),
+            };
+
+            tracing::info!("stream dropped, resubscribing: {reason}");
+            let _ = events.send(ConnectionEvent::Dropped { reason });
+            let mut attempts = 0;
+            stream = loop {
+                tokio::time::sleep(with_jitter(config.resubscribe_delay(attempts))).await;
+                match subscribe(request.clone()).await {
+                    Ok(stream) => break stream,
+                    Err(status) => {
+                        attempts += 1;
+                        if attempts >= config.max_resubscribe_attempts {
+                            tracing::warn!("giving up resubscribing after {attempts} attempts: {status}");
+                            let _ = events.send(ConnectionEvent::Lost { reason: status.to_string() });
+                            yield Err(Error::new(ErrorKind::SubscribeError).with(status));
+                            return;
+                        }
+                    }
+                }
+            };
+            let _ = events.send(ConnectionEvent::Restored);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmtp_proto::xmtp::mls::api::v1::subscribe_group_messages_request::Filter;
+
+    #[test]
+    fn resumes_after_last_group_message() {
+        let mut request = SubscribeGroupMessagesRequest {
+            filters: vec![
+                Filter {
+                    group_id: vec![1],
+                    id_cursor: 0,
+                },
+                Filter {
+                    group_id: vec![2],
+                    id_cursor: 5,
+                },
+            ],
+        };
+        request.resume_after(&GroupMessage {
+            version: Some(group_message::Version::V1(group_message::V1 {
+                id: 10,
+                group_id: vec![2],
+                ..Default::default()
+            })),
+        });
+
+        assert_eq!(request.filters[0].id_cursor, 0);
+        assert_eq!(request.filters[1].id_cursor, 10);
+    }
+
+    #[test]
+    fn resubscribe_backoff_is_capped() {
+        let config = KeepaliveConfig::default();
+        assert_eq!(config.resubscribe_delay(0), Duration::from_millis(500));
+        assert_eq!(config.resubscribe_delay(2), Duration::from_secs(2));
+        assert_eq!(config.resubscribe_delay(10), Duration::from_secs(30));
+        assert_eq!(config.resubscribe_delay(u32::MAX), Duration::from_secs(30));
+
+        for _ in 0..100 {
+            let delay = with_jitter(Duration::from_secs(30));
+            assert!(delay >= Duration::from_secs(15) && delay <= Duration::from_secs(30));
+        }
+    }
+}
@@ -1,12 +1,14 @@
 pub mod auth_token;
 pub mod grpc_api_helper;
 mod identity;
+pub mod keepalive;
 pub mod replication_client;
 
 pub const LOCALHOST_ADDRESS: &str = "http://localhost:5556";
 pub const DEV_ADDRESS: &str = "https://grpc.dev.xmtp.network:443";
 
 pub use grpc_api_helper::{Client, GroupMessageStream, WelcomeMessageStream};
+pub use keepalive::{ConnectionEvent, KeepaliveConfig};
 
 mod utils {
     #[cfg(feature = "test-utils")]
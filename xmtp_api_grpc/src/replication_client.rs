@@ -17,6 +17,7 @@ use xmtp_proto::api_client::XmtpTestClient;
 use xmtp_proto::api_client::{ClientWithMetadata, XmtpIdentityClient, XmtpMlsStreams};
 
 use crate::grpc_api_helper::{create_tls_channel, GrpcMutableSubscription, Subscription};
+use crate::keepalive::KeepaliveConfig;
 use crate::{GroupMessageStream, WelcomeMessageStream};
 use xmtp_proto::v4_utils::{
     build_group_message_topic, build_identity_topic_from_hex_encoded, build_identity_update_topic,
@@ -83,7 +84,7 @@ impl ClientV4 {
             .map_err(|e| Error::new(ErrorKind::MetadataError).with(e))?;
 
         let grpc_channel = match is_secure {
-            true => create_tls_channel(grpc_url).await?,
+            true => create_tls_channel(grpc_url, &KeepaliveConfig::default()).await?,
             false => Channel::from_shared(grpc_url)
                 .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
                 .connect()
@@ -92,7 +93,7 @@ impl ClientV4 {
         };
 
         let payer_channel = match is_secure {
-            true => create_tls_channel(payer_url).await?,
+            true => create_tls_channel(payer_url, &KeepaliveConfig::default()).await?,
             false => Channel::from_shared(payer_url)
                 .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
                 .connect()
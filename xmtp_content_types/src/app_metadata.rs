@@ -0,0 +1,131 @@
+//! Small key-value metadata that apps attach to the messages they send.
+//!
+//! Metadata is stored in the [`EncodedContent`] parameters under the key
+//! `app:<namespace>:<key>`, so it works with every content type and is ignored by clients that
+//! do not know about it. Each app should use its own namespace, such as a reverse domain name.
+use std::collections::BTreeMap;
+
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use crate::CodecError;
+
+const PREFIX: &str = "app:";
+
+/// Maximum length of a namespace or key
+pub const MAX_APP_METADATA_NAME_LENGTH: usize = 64;
+
+/// Maximum size of all the app metadata of a message, counting the encoded keys and values
+pub const MAX_APP_METADATA_BYTES: usize = 1024;
+
+/// App metadata of a message, by namespace and then key
+pub type AppMetadata = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Attach a metadata entry to `content`, replacing any earlier value of the key
+pub fn set_app_metadata(
+    content: &mut EncodedContent,
+    namespace: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), CodecError> {
+    validate_name(namespace)?;
+    validate_name(key)?;
+    let previous = content
+        .parameters
+        .insert(parameter_key(namespace, key), value.to_string());
+    if let Err(e) = validate_app_metadata(content) {
+        let parameter = parameter_key(namespace, key);
+        match previous {
+            Some(previous) => content.parameters.insert(parameter, previous),
+            None => content.parameters.remove(&parameter),
+        };
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// The app metadata attached to `content`
+pub fn app_metadata(content: &EncodedContent) -> AppMetadata {
+    let mut metadata = AppMetadata::new();
+    for (parameter, value) in &content.parameters {
+        if let Some((namespace, key)) = parse_parameter_key(parameter) {
+            metadata
+                .entry(namespace.to_string())
+                .or_default()
+                .insert(key.to_string(), value.clone());
+        }
+    }
+    metadata
+}
+
+/// Check the app metadata of `content` against the naming rules and size limits
+pub fn validate_app_metadata(content: &EncodedContent) -> Result<(), CodecError> {
+    let mut size = 0;
+    for (parameter, value) in &content.parameters {
+        if !parameter.starts_with(PREFIX) {
+            continue;
+        }
+        let (namespace, key) = parse_parameter_key(parameter)
+            .ok_or_else(|| CodecError::Encode(format!("malformed app metadata key {parameter}")))?;
+        validate_name(namespace)?;
+        validate_name(key)?;
+        size += parameter.len() + value.len();
+    }
+    if size > MAX_APP_METADATA_BYTES {
+        return Err(CodecError::Encode(format!(
+            "app metadata is {size} bytes, the limit is {MAX_APP_METADATA_BYTES}"
+        )));
+    }
+    Ok(())
+}
+
+fn parameter_key(namespace: &str, key: &str) -> String {
+    format!("{PREFIX}{namespace}:{key}")
+}
+
+fn parse_parameter_key(parameter: &str) -> Option<(&str, &str)> {
+    parameter.strip_prefix(PREFIX)?.split_once(':')
+}
+
+/// Names are limited to lowercase ASCII letters, digits, `.`, `_` and `-`
+fn validate_name(name: &str) -> Result<(), CodecError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_APP_METADATA_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(CodecError::Encode(format!(
+            "invalid app metadata name {name:?}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{text::TextCodec, ContentCodec};
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_app_metadata() {
+        let mut content = TextCodec::encode("hello".to_string()).unwrap();
+        set_app_metadata(&mut content, "com.example", "thread", "42").unwrap();
+        set_app_metadata(&mut content, "com.example", "priority", "high").unwrap();
+        assert!(set_app_metadata(&mut content, "Com Example", "thread", "1").is_err());
+
+        // The encoding parameter of the text codec is not app metadata
+        let metadata = app_metadata(&content);
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata["com.example"].len(), 2);
+        assert_eq!(metadata["com.example"]["thread"], "42");
+
+        let too_large = "x".repeat(MAX_APP_METADATA_BYTES);
+        assert!(set_app_metadata(&mut content, "com.example", "blob", &too_large).is_err());
+        assert_eq!(app_metadata(&content), metadata);
+        assert!(validate_app_metadata(&content).is_ok());
+    }
+}
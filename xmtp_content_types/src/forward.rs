@@ -0,0 +1,122 @@
+//! Provenance of forwarded messages.
+//!
+//! A forwarded message is sent with the content of the original message, with the original
+//! sender, sent time and conversation stored in the [`EncodedContent`] parameters under the
+//! `forwarded:` prefix. Clients that do not know about forwarding show it as a normal message.
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use crate::{
+    group_updated::GroupUpdatedCodec, membership_change::GroupMembershipChangeCodec,
+    poll::PollVoteCodec, reaction::ReactionCodec, read_receipt::ReadReceiptCodec,
+    reply::ReplyCodec, CodecError,
+};
+
+const SENDER_INBOX_ID_KEY: &str = "forwarded:senderInboxId";
+const SENT_AT_NS_KEY: &str = "forwarded:sentAtNs";
+const CONVERSATION_ID_KEY: &str = "forwarded:conversationId";
+
+/// Content types that only make sense in the conversation they were sent in
+const NOT_FORWARDABLE: [&str; 5] = [
+    GroupUpdatedCodec::TYPE_ID,
+    GroupMembershipChangeCodec::TYPE_ID,
+    ReactionCodec::TYPE_ID,
+    ReadReceiptCodec::TYPE_ID,
+    PollVoteCodec::TYPE_ID,
+];
+
+/// Where a forwarded message was originally sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedFrom {
+    pub sender_inbox_id: String,
+    pub sent_at_ns: i64,
+    pub conversation_id: Vec<u8>,
+}
+
+/// The content to send to forward `content`, which was sent as described by `from`.
+///
+/// Replies are forwarded without the message they reply to, since it is in another
+/// conversation. Content that was already forwarded keeps the provenance of the first message.
+/// All other parameters are kept, so remote attachments point at the same encrypted payload and
+/// can be decrypted with the same secret.
+pub fn forward_content(
+    mut content: EncodedContent,
+    from: ForwardedFrom,
+) -> Result<EncodedContent, CodecError> {
+    let type_id = content
+        .r#type
+        .as_ref()
+        .map(|content_type| content_type.type_id.as_str())
+        .unwrap_or_default();
+    if NOT_FORWARDABLE.contains(&type_id) {
+        return Err(CodecError::Encode(format!("{type_id} cannot be forwarded")));
+    }
+    if type_id == ReplyCodec::TYPE_ID {
+        let provenance = forwarded_from(&content);
+        content = crate::bytes_to_encoded_content(content.content)?;
+        if let Some(provenance) = provenance {
+            set_forwarded_from(&mut content, &provenance);
+        }
+    }
+
+    if forwarded_from(&content).is_none() {
+        set_forwarded_from(&mut content, &from);
+    }
+    Ok(content)
+}
+
+/// Where `content` was originally sent, if it was forwarded
+pub fn forwarded_from(content: &EncodedContent) -> Option<ForwardedFrom> {
+    let parameters = &content.parameters;
+    Some(ForwardedFrom {
+        sender_inbox_id: parameters.get(SENDER_INBOX_ID_KEY)?.clone(),
+        sent_at_ns: parameters.get(SENT_AT_NS_KEY)?.parse().ok()?,
+        conversation_id: hex::decode(parameters.get(CONVERSATION_ID_KEY)?).ok()?,
+    })
+}
+
+fn set_forwarded_from(content: &mut EncodedContent, from: &ForwardedFrom) {
+    let parameters = &mut content.parameters;
+    parameters.insert(
+        SENDER_INBOX_ID_KEY.to_string(),
+        from.sender_inbox_id.clone(),
+    );
+    parameters.insert(SENT_AT_NS_KEY.to_string(), from.sent_at_ns.to_string());
+    parameters.insert(
+        CONVERSATION_ID_KEY.to_string(),
+        hex::encode(&from.conversation_id),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{text::TextCodec, ContentCodec};
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_forward_content() {
+        let from = ForwardedFrom {
+            sender_inbox_id: "bola".to_string(),
+            sent_at_ns: 100,
+            conversation_id: vec![1; 16],
+        };
+        let content = TextCodec::encode("hello".to_string()).unwrap();
+        assert_eq!(forwarded_from(&content), None);
+
+        let forwarded = forward_content(content, from.clone()).unwrap();
+        assert_eq!(forwarded_from(&forwarded), Some(from.clone()));
+        assert_eq!(TextCodec::decode(forwarded.clone()).unwrap(), "hello");
+
+        // Forwarding again keeps the original provenance
+        let again = ForwardedFrom {
+            sender_inbox_id: "caro".to_string(),
+            sent_at_ns: 200,
+            conversation_id: vec![2; 16],
+        };
+        let forwarded = forward_content(forwarded, again).unwrap();
+        assert_eq!(forwarded_from(&forwarded), Some(from));
+    }
+}
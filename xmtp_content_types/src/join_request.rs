@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Sent in a DM to the admins of a discoverable group by an inbox that asks to join it. The
+/// admins learn about the request without the group itself being involved, since the sender
+/// is not a member yet.
+#[derive(Clone, PartialEq, Message)]
+pub struct JoinRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub group_id: Vec<u8>,
+    /// Shown to the admins with the request
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+pub struct JoinRequestCodec {}
+
+impl JoinRequestCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "join_request";
+}
+
+impl ContentCodec<JoinRequest> for JoinRequestCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: JoinRequestCodec::AUTHORITY_ID.to_string(),
+            type_id: JoinRequestCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(request: JoinRequest) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(JoinRequestCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback: Some("Asked to join a group".to_string()),
+            compression: None,
+            content: request.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<JoinRequest, CodecError> {
+        JoinRequest::decode(content.content.as_slice())
+            .map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let request = JoinRequest {
+            group_id: vec![1, 2, 3],
+            message: "hi, I play on tuesdays too".to_string(),
+        };
+        let encoded = JoinRequestCodec::encode(request.clone()).unwrap();
+        assert_eq!(encoded.r#type.clone().unwrap().type_id, "join_request");
+        assert!(encoded.fallback.is_some());
+        assert_eq!(JoinRequestCodec::decode(encoded).unwrap(), request);
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Sent by a member that leaves a group. Members allowed to remove members commit the removal
+/// of the sender when they receive it, since MLS does not let a member commit its own removal.
+#[derive(Clone, PartialEq, Message)]
+pub struct LeaveRequest {}
+
+pub struct LeaveRequestCodec {}
+
+impl LeaveRequestCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "leave_request";
+}
+
+impl ContentCodec<LeaveRequest> for LeaveRequestCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: LeaveRequestCodec::AUTHORITY_ID.to_string(),
+            type_id: LeaveRequestCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(request: LeaveRequest) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(LeaveRequestCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback: Some("Left the conversation".to_string()),
+            compression: None,
+            content: request.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<LeaveRequest, CodecError> {
+        LeaveRequest::decode(content.content.as_slice())
+            .map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let encoded = LeaveRequestCodec::encode(LeaveRequest {}).unwrap();
+        assert_eq!(encoded.r#type.clone().unwrap().type_id, "leave_request");
+        assert!(encoded.fallback.is_some());
+        assert_eq!(LeaveRequestCodec::decode(encoded).unwrap(), LeaveRequest {});
+    }
+}
@@ -1,6 +1,12 @@
+pub mod app_metadata;
 pub mod attachment;
+pub mod forward;
 pub mod group_updated;
+pub mod join_request;
+pub mod leave_request;
+pub mod location;
 pub mod membership_change;
+pub mod poll;
 pub mod reaction;
 pub mod read_receipt;
 pub mod remote_attachment;
@@ -32,6 +38,6 @@ pub fn encoded_content_to_bytes(content: EncodedContent) -> Vec<u8> {
     buf
 }
 
-pub fn bytes_to_encoded_content(bytes: Vec<u8>) -> EncodedContent {
-    EncodedContent::decode(&mut bytes.as_slice()).unwrap()
+pub fn bytes_to_encoded_content(bytes: Vec<u8>) -> Result<EncodedContent, CodecError> {
+    EncodedContent::decode(&mut bytes.as_slice()).map_err(|e| CodecError::Decode(e.to_string()))
 }
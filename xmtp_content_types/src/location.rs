@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A location on the map. A location with `live_until_ns` set starts a live share, which is
+/// followed by updates that reference it until the share window ends.
+#[derive(Clone, PartialEq, Message)]
+pub struct Location {
+    #[prost(double, tag = "1")]
+    pub latitude: f64,
+    #[prost(double, tag = "2")]
+    pub longitude: f64,
+    /// Radius of uncertainty in meters, 0 if unknown
+    #[prost(double, tag = "3")]
+    pub accuracy_meters: f64,
+    /// End of the live share window, 0 for a location that is shared once
+    #[prost(int64, tag = "4")]
+    pub live_until_ns: i64,
+    /// Hex encoded id of the message that started the live share, empty for the first message
+    /// of a share
+    #[prost(string, tag = "5")]
+    pub live_share_reference: String,
+}
+
+impl Location {
+    pub fn is_live(&self) -> bool {
+        self.live_until_ns > 0
+    }
+}
+
+pub struct LocationCodec {}
+
+impl LocationCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "location";
+}
+
+impl ContentCodec<Location> for LocationCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: LocationCodec::AUTHORITY_ID.to_string(),
+            type_id: LocationCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(location: Location) -> Result<EncodedContent, CodecError> {
+        if !(-90.0..=90.0).contains(&location.latitude)
+            || !(-180.0..=180.0).contains(&location.longitude)
+        {
+            return Err(CodecError::Encode(format!(
+                "invalid coordinates {}, {}",
+                location.latitude, location.longitude
+            )));
+        }
+        // Updates of a live share are not worth a notification
+        let fallback = location.live_share_reference.is_empty().then(|| {
+            format!(
+                "Location: {:.5}, {:.5}",
+                location.latitude, location.longitude
+            )
+        });
+        Ok(EncodedContent {
+            r#type: Some(LocationCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback,
+            compression: None,
+            content: location.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Location, CodecError> {
+        Location::decode(content.content.as_slice()).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let location = Location {
+            latitude: 52.37403,
+            longitude: 4.88969,
+            accuracy_meters: 10.0,
+            live_until_ns: 0,
+            live_share_reference: String::new(),
+        };
+        let encoded = LocationCodec::encode(location.clone()).unwrap();
+        assert_eq!(encoded.r#type.clone().unwrap().type_id, "location");
+        assert_eq!(
+            encoded.fallback.as_deref(),
+            Some("Location: 52.37403, 4.88969")
+        );
+        assert_eq!(LocationCodec::decode(encoded).unwrap(), location);
+
+        let invalid = Location {
+            latitude: 91.0,
+            ..location
+        };
+        assert!(LocationCodec::encode(invalid).is_err());
+    }
+}
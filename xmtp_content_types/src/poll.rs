@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A question with a fixed list of answers that group members can vote on
+#[derive(Clone, PartialEq, Message)]
+pub struct Poll {
+    #[prost(string, tag = "1")]
+    pub question: String,
+    #[prost(string, repeated, tag = "2")]
+    pub options: Vec<String>,
+    /// Whether a member can vote for more than one option
+    #[prost(bool, tag = "3")]
+    pub allow_multiple_choices: bool,
+}
+
+/// A vote on a [`Poll`]. A later vote from the same inbox replaces the earlier one, and a vote
+/// without any options withdraws it.
+#[derive(Clone, PartialEq, Message)]
+pub struct PollVote {
+    /// Hex encoded id of the poll message
+    #[prost(string, tag = "1")]
+    pub poll_reference: String,
+    /// Indexes into [`Poll::options`]
+    #[prost(uint32, repeated, tag = "2")]
+    pub option_indexes: Vec<u32>,
+}
+
+pub struct PollCodec {}
+
+impl PollCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "poll";
+}
+
+impl ContentCodec<Poll> for PollCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: PollCodec::AUTHORITY_ID.to_string(),
+            type_id: PollCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(poll: Poll) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(PollCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback: Some(format!("Poll: {}", poll.question)),
+            compression: None,
+            content: poll.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Poll, CodecError> {
+        Poll::decode(content.content.as_slice()).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+pub struct PollVoteCodec {}
+
+impl PollVoteCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "pollVote";
+}
+
+impl ContentCodec<PollVote> for PollVoteCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: PollVoteCodec::AUTHORITY_ID.to_string(),
+            type_id: PollVoteCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(vote: PollVote) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(PollVoteCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback: None,
+            compression: None,
+            content: vote.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<PollVote, CodecError> {
+        PollVote::decode(content.content.as_slice()).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let poll = Poll {
+            question: "Lunch?".to_string(),
+            options: vec!["Pizza".to_string(), "Sushi".to_string()],
+            allow_multiple_choices: false,
+        };
+        let encoded = PollCodec::encode(poll.clone()).unwrap();
+        assert_eq!(encoded.r#type.clone().unwrap().type_id, "poll");
+        assert_eq!(PollCodec::decode(encoded).unwrap(), poll);
+
+        let vote = PollVote {
+            poll_reference: "0123abcd".to_string(),
+            option_indexes: vec![1],
+        };
+        let encoded = PollVoteCodec::encode(vote.clone()).unwrap();
+        assert_eq!(encoded.r#type.clone().unwrap().type_id, "pollVote");
+        assert_eq!(PollVoteCodec::decode(encoded).unwrap(), vote);
+    }
+}
@@ -1,6 +1,200 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
 pub struct TransactionReferenceCodec {}
 
 /// Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-transaction-reference/src/TransactionReference.ts
 impl TransactionReferenceCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "transactionReference";
 }
+
+// JSON format is defined here: https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-transaction-reference/src/TransactionReference.ts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReference {
+    /// CAIP-2 namespace of the chain, `eip155` if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Id of the chain within the namespace, sent as a number or a string by other SDKs
+    #[serde(deserialize_with = "deserialize_network_id")]
+    pub network_id: String,
+    /// Hash of the transaction
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TransactionMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionMetadata {
+    pub transaction_type: String,
+    pub currency: String,
+    pub amount: f64,
+    pub decimals: u32,
+    pub from_address: String,
+    pub to_address: String,
+}
+
+impl TransactionReference {
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or("eip155")
+    }
+
+    /// Check that the chain metadata is well formed. This does not check the transaction
+    /// on chain.
+    pub fn validate(&self) -> Result<(), CodecError> {
+        let invalid = |reason: &str| {
+            Err(CodecError::Encode(format!(
+                "invalid transaction reference: {reason}"
+            )))
+        };
+        if self.network_id.is_empty() {
+            return invalid("missing network id");
+        }
+        if self.namespace() == "eip155" {
+            if parse_chain_id(&self.network_id).is_none() {
+                return invalid("network id is not a chain id");
+            }
+            if !is_evm_hash(&self.reference) {
+                return invalid("reference is not a transaction hash");
+            }
+        } else if self.reference.is_empty() {
+            return invalid("missing reference");
+        }
+        if let Some(metadata) = &self.metadata {
+            if metadata.currency.is_empty() {
+                return invalid("missing currency");
+            }
+            if !metadata.amount.is_finite() || metadata.amount <= 0.0 {
+                return invalid("amount must be a positive number");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ContentCodec<TransactionReference> for TransactionReferenceCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: TransactionReferenceCodec::AUTHORITY_ID.to_string(),
+            type_id: TransactionReferenceCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: TransactionReference) -> Result<EncodedContent, CodecError> {
+        data.validate()?;
+        let fallback = format!(
+            "[Crypto transaction] Use a blockchain explorer to learn more using the transaction hash: {}",
+            data.reference
+        );
+        let content = serde_json::to_vec(&data).map_err(|e| CodecError::Encode(e.to_string()))?;
+
+        Ok(EncodedContent {
+            r#type: Some(TransactionReferenceCodec::content_type()),
+            parameters: HashMap::new(),
+            fallback: Some(fallback),
+            compression: None,
+            content,
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<TransactionReference, CodecError> {
+        serde_json::from_slice(&content.content).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+fn deserialize_network_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NetworkId {
+        Number(u64),
+        String(String),
+    }
+
+    Ok(match NetworkId::deserialize(deserializer)? {
+        NetworkId::Number(id) => id.to_string(),
+        NetworkId::String(id) => id,
+    })
+}
+
+/// Chain ids are sent in decimal or as `0x` prefixed hex
+fn parse_chain_id(network_id: &str) -> Option<u64> {
+    match network_id.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => network_id.parse().ok(),
+    }
+}
+
+fn is_evm_hash(reference: &str) -> bool {
+    reference
+        .strip_prefix("0x")
+        .is_some_and(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let reference = TransactionReference {
+            namespace: None,
+            network_id: "8453".to_string(),
+            reference: format!("0x{}", "ab".repeat(32)),
+            metadata: Some(TransactionMetadata {
+                transaction_type: "transfer".to_string(),
+                currency: "USDC".to_string(),
+                amount: 100000.0,
+                decimals: 6,
+                from_address: "0x1".to_string(),
+                to_address: "0x2".to_string(),
+            }),
+        };
+        let encoded = TransactionReferenceCodec::encode(reference.clone()).unwrap();
+        assert_eq!(
+            encoded.r#type.clone().unwrap().type_id,
+            "transactionReference"
+        );
+        assert_eq!(
+            TransactionReferenceCodec::decode(encoded).unwrap(),
+            reference
+        );
+
+        let invalid = TransactionReference {
+            reference: "0x1234".to_string(),
+            ..reference.clone()
+        };
+        assert!(TransactionReferenceCodec::encode(invalid).is_err());
+
+        for amount in [0.0, -1.0] {
+            let mut invalid = reference.clone();
+            invalid.metadata.as_mut().unwrap().amount = amount;
+            assert!(TransactionReferenceCodec::encode(invalid).is_err());
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_decode_legacy_json() {
+        let json = format!(r#"{{"networkId":1,"reference":"0x{}"}}"#, "cd".repeat(32));
+        let decoded = TransactionReferenceCodec::decode(EncodedContent {
+            content: json.into_bytes(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(decoded.network_id, "1");
+        assert_eq!(decoded.namespace(), "eip155");
+        assert!(decoded.validate().is_ok());
+    }
+}
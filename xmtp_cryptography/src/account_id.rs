@@ -0,0 +1,166 @@
+//! Account identifiers in the [CAIP-10](https://chainagnostic.org/CAIPs/caip-10) format,
+//! `namespace:reference:address`, such as `eip155:8453:0xab16a96d359ec26a11e2c2b3d8f8b8942d5bfcdb`.
+//!
+//! Plain Ethereum addresses are still accepted, and stand for Ethereum mainnet. The account ids of
+//! all EVM chains are normalized to their plain address, which is what signatures recover to, so
+//! that an account has a single identity and the identities created before CAIP-10 support keep
+//! the same inbox ids.
+use std::{fmt, str::FromStr};
+
+use crate::signature::{is_valid_ethereum_address, AddressValidationError};
+
+pub const EIP155_NAMESPACE: &str = "eip155";
+pub const ETHEREUM_MAINNET_REFERENCE: &str = "1";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccountId {
+    /// The chain namespace, such as `eip155` or `solana`
+    pub namespace: String,
+    /// The chain within the namespace, such as `1` for Ethereum mainnet
+    pub reference: String,
+    pub address: String,
+}
+
+impl AccountId {
+    pub fn ethereum(address: &str) -> Self {
+        Self {
+            namespace: EIP155_NAMESPACE.to_string(),
+            reference: ETHEREUM_MAINNET_REFERENCE.to_string(),
+            address: address.to_lowercase(),
+        }
+    }
+
+    pub fn is_evm(&self) -> bool {
+        self.namespace == EIP155_NAMESPACE
+    }
+
+    /// The string used to associate and look up the account. EVM accounts are a plain address,
+    /// other accounts are a CAIP-10 account id.
+    pub fn to_account_address(&self) -> String {
+        if self.is_evm() {
+            self.address.clone()
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = AddressValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AddressValidationError::InvalidAddresses(vec![s.to_string()]);
+        let parts: Vec<&str> = s.split(':').collect();
+        let account_id = match parts.as_slice() {
+            [address] if is_valid_ethereum_address(address) => Self::ethereum(address),
+            [namespace, reference, address] => {
+                let namespace = namespace.to_lowercase();
+                let valid = (3..=8).contains(&namespace.len())
+                    && namespace
+                        .chars()
+                        .all(|c| c == '-' || c.is_ascii_lowercase() || c.is_ascii_digit())
+                    && (1..=32).contains(&reference.len())
+                    && reference
+                        .chars()
+                        .all(|c| c == '-' || c == '_' || c.is_ascii_alphanumeric())
+                    && (1..=128).contains(&address.len())
+                    && address
+                        .chars()
+                        .all(|c| matches!(c, '-' | '.' | '%') || c.is_ascii_alphanumeric());
+                if !valid {
+                    return Err(invalid());
+                }
+                let address = if namespace == EIP155_NAMESPACE {
+                    if !is_valid_ethereum_address(address) {
+                        return Err(invalid());
+                    }
+                    address.to_lowercase()
+                } else {
+                    // Addresses of other chains, such as base58, can be case sensitive
+                    address.to_string()
+                };
+                Self {
+                    namespace,
+                    reference: reference.to_string(),
+                    address,
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(account_id)
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.namespace, self.reference, self.address)
+    }
+}
+
+/// Normalize a plain Ethereum address or a CAIP-10 account id, see
+/// [`AccountId::to_account_address`]
+pub fn normalize_account_address(address: &str) -> Result<String, AddressValidationError> {
+    Ok(AccountId::from_str(address)?.to_account_address())
+}
+
+/// Like [`crate::signature::sanitize_evm_addresses`], but also accepts CAIP-10 account ids
+pub fn sanitize_account_addresses(
+    account_addresses: &[String],
+) -> Result<Vec<String>, AddressValidationError> {
+    let mut invalid = vec![];
+    let mut sanitized = Vec::with_capacity(account_addresses.len());
+    for address in account_addresses {
+        match normalize_account_address(address) {
+            Ok(address) => sanitized.push(address),
+            Err(_) => invalid.push(address.clone()),
+        }
+    }
+    if !invalid.is_empty() {
+        return Err(AddressValidationError::InvalidAddresses(invalid));
+    }
+    Ok(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0xAb16A96D359eC26a11e2C2b3d8f8B8942d5Bfcdb";
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn normalizes_account_addresses() {
+        let lowercase = ADDRESS.to_lowercase();
+        assert_eq!(normalize_account_address(ADDRESS).unwrap(), lowercase);
+        assert_eq!(
+            normalize_account_address(&format!("eip155:1:{ADDRESS}")).unwrap(),
+            lowercase
+        );
+        assert_eq!(
+            normalize_account_address(&format!("EIP155:8453:{ADDRESS}")).unwrap(),
+            lowercase
+        );
+        let solana =
+            "solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:7S3P4HxJpyyigGzodYwHtCxZyUQe9JiBMHyRWXArAaKv";
+        assert_eq!(normalize_account_address(solana).unwrap(), solana);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn rejects_invalid_account_ids() {
+        for address in [
+            "0x123",
+            "eip155:1:0x123",
+            "eip155::0xab16a96d359ec26a11e2c2b3d8f8b8942d5bfcdb",
+            "ab:1:address",
+            "solana:1:has space",
+            "a:b:c:d",
+        ] {
+            assert!(normalize_account_address(address).is_err(), "{address}");
+        }
+        let err = sanitize_account_addresses(&[ADDRESS.to_string(), "0x123".to_string()]);
+        assert!(
+            matches!(err, Err(AddressValidationError::InvalidAddresses(invalid)) if invalid == vec!["0x123".to_string()])
+        );
+    }
+}
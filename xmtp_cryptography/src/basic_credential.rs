@@ -1,10 +1,12 @@
-use ed25519_dalek::SigningKey;
+use ed25519_dalek::{DigestSigner, Signature, SigningKey, VerifyingKey};
 use k256::schnorr::CryptoRngCore;
 use openmls_basic_credential::SignatureKeyPair;
 use openmls_traits::signatures::Signer;
 use openmls_traits::{signatures, types::SignatureScheme};
 use serde::de::Error;
+use sha2::{Digest, Sha512};
 use std::io::BufReader;
+use std::sync::Arc;
 use tls_codec::SecretTlsVecU8;
 use zeroize::Zeroizing;
 
@@ -67,45 +69,107 @@ pub trait CredentialVerify<SP = private::NotSpecialized> {
     ) -> Result<(), Self::Error>;
 }
 
+/// Signs with an installation key that is held outside of the process, such as in a secure
+/// module of the host. The private key is never handed out.
+pub trait ExternalInstallationKey: Send + Sync + std::fmt::Debug {
+    /// Opaque reference to the key, kept in place of the private key to find the key again
+    fn handle(&self) -> &[u8];
+
+    /// Sign `payload` with Ed25519
+    fn sign(&self, payload: &[u8]) -> Result<[u8; 64], signatures::SignerError>;
+
+    /// Sign `message` with Ed25519ph under `context`
+    fn sign_with_context(
+        &self,
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<[u8; 64], signatures::SignerError>;
+}
+
+#[derive(Debug, Clone)]
+enum InstallationKey {
+    Local(SigningKey),
+    External {
+        public: VerifyingKey,
+        key: Arc<dyn ExternalInstallationKey>,
+    },
+}
+
 /// The credential for an XMTP Installation
 /// an XMTP Installation often refers to one specific device,
 /// and is an ed25519 key
 // Boxing the inner value avoids creating large enums if an enum stores multiple installation
 // credentials
 #[derive(Debug, Clone)]
-pub struct XmtpInstallationCredential(Box<SigningKey>);
+pub struct XmtpInstallationCredential(Box<InstallationKey>);
 
 impl Default for XmtpInstallationCredential {
     fn default() -> Self {
-        Self(Box::new(SigningKey::generate(&mut crate::utils::rng())))
+        Self::new()
     }
 }
 
 impl XmtpInstallationCredential {
     /// Create a new [`XmtpInstallationCredential`] with [`rand_chacha::ChaCha20Rng`]
     pub fn new() -> Self {
-        Self(Box::new(SigningKey::generate(&mut crate::utils::rng())))
+        SigningKey::generate(&mut crate::utils::rng()).into()
     }
 
     /// Create a new [`XmtpInstallationCredential`] with custom RNG
     pub fn with_rng<R: CryptoRngCore + ?Sized>(rng: &mut R) -> Self {
-        Self(Box::new(SigningKey::generate(rng)))
+        SigningKey::generate(rng).into()
+    }
+
+    /// Create an [`XmtpInstallationCredential`] for the key with the `public` key that signs
+    /// through `key`
+    pub fn external(
+        public: &[u8; 32],
+        key: Arc<dyn ExternalInstallationKey>,
+    ) -> Result<Self, ed25519_dalek::SignatureError> {
+        Ok(Self(Box::new(InstallationKey::External {
+            public: VerifyingKey::from_bytes(public)?,
+            key,
+        })))
+    }
+
+    /// The handle of the key if it is held outside of the process, see
+    /// [`ExternalInstallationKey::handle`]
+    pub fn external_handle(&self) -> Option<&[u8]> {
+        match self.0.as_ref() {
+            InstallationKey::Local(_) => None,
+            InstallationKey::External { key, .. } => Some(key.handle()),
+        }
+    }
+
+    /// The private key, if it is held by this process
+    fn local(&self) -> Option<&SigningKey> {
+        match self.0.as_ref() {
+            InstallationKey::Local(key) => Some(key),
+            InstallationKey::External { .. } => None,
+        }
+    }
+
+    fn public(&self) -> &VerifyingKey {
+        match self.0.as_ref() {
+            InstallationKey::Local(key) => key.as_ref(),
+            InstallationKey::External { public, .. } => public,
+        }
     }
 
     /// Get a reference to the public [`ed25519_dalek::VerifyingKey`]
     /// Can be used to verify signatures
     pub fn verifying_key(&self) -> ed25519_dalek::VerifyingKey {
-        self.0.verifying_key()
+        *self.public()
     }
 
     /// View the public [`ed25519_dalek::VerifyingKey`] as constant-sized bytes
     pub fn public_bytes(&self) -> &[u8; 32] {
-        self.0.as_ref().as_ref().as_bytes()
+        self.public().as_bytes()
     }
 
     /// View the public [`ed25519_dalek::VerifyingKey`] as a slice
     pub fn public_slice(&self) -> &[u8] {
-        self.0.as_ref().as_ref().as_ref()
+        self.public().as_ref()
     }
 
     /// get the scheme, prefer the public [`Signer::signature_scheme`]
@@ -113,11 +177,32 @@ impl XmtpInstallationCredential {
         SignatureScheme::ED25519
     }
 
-    pub fn with_context<'k, 'v>(
-        &'k self,
-        context: &'v [u8],
-    ) -> Result<ed25519_dalek::Context<'k, 'v, SigningKey>, ed25519_dalek::SignatureError> {
-        self.0.with_context(context)
+    /// Sign `message` with Ed25519ph under `context`
+    pub fn sign_with_context(
+        &self,
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<Signature, SignerError> {
+        match self.0.as_ref() {
+            InstallationKey::Local(key) => key
+                .with_context(context)
+                .and_then(|context| context.try_sign_digest(Sha512::new_with_prefix(message)))
+                .map_err(|_| signatures::SignerError::SigningError.into()),
+            InstallationKey::External { key, .. } => {
+                let signature = key.sign_with_context(context, message)?;
+                Ok(Signature::from_bytes(&signature))
+            }
+        }
+    }
+
+    /// The key as an OpenMLS [`SignatureKeyPair`], if the private key is held by this process
+    fn key_pair(&self) -> Result<SignatureKeyPair, signatures::SignerError> {
+        let key = self.local().ok_or(signatures::SignerError::SigningError)?;
+        Ok(SignatureKeyPair::from_raw(
+            self.scheme(),
+            key.to_bytes().into(),
+            key.verifying_key().to_bytes().into(),
+        ))
     }
 
     /// Internal helper function to safely create a credential from its raw parts
@@ -131,26 +216,28 @@ impl XmtpInstallationCredential {
         });
 
         let signing_key = SigningKey::from_keypair_bytes(&keypair)?;
-        Ok(Self(Box::new(signing_key)))
+        Ok(signing_key.into())
     }
 
     /// Alias for [`ed25519_dalek::SigningKey::from_bytes`]
     pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ed25519_dalek::SignatureError> {
-        let key = SigningKey::from_bytes(bytes);
-        Ok(Self(Box::new(key)))
+        Ok(SigningKey::from_bytes(bytes).into())
     }
 
-    /// private key for this credential
+    /// private key for this credential, if it is held by this process
     #[cfg(feature = "exposed-keys")]
-    pub fn private_bytes(&self) -> [u8; 32] {
-        self.0.to_bytes()
+    pub fn private_bytes(&self) -> Option<[u8; 32]> {
+        self.local().map(SigningKey::to_bytes)
     }
 }
 
 /// The signer here must maintain compatability with `SignatureKeyPair`
 impl Signer for XmtpInstallationCredential {
     fn sign(&self, payload: &[u8]) -> Result<Vec<u8>, signatures::SignerError> {
-        SignatureKeyPair::from(self).sign(payload)
+        match self.0.as_ref() {
+            InstallationKey::Local(_) => self.key_pair()?.sign(payload),
+            InstallationKey::External { key, .. } => key.sign(payload).map(Vec::from),
+        }
     }
 
     fn signature_scheme(&self) -> SignatureScheme {
@@ -161,7 +248,7 @@ impl Signer for XmtpInstallationCredential {
 // The signer here must maintain compatability with `SignatureKeyPair`
 impl Signer for &XmtpInstallationCredential {
     fn sign(&self, payload: &[u8]) -> Result<Vec<u8>, signatures::SignerError> {
-        SignatureKeyPair::from(*self).sign(payload)
+        (*self).sign(payload)
     }
 
     fn signature_scheme(&self) -> SignatureScheme {
@@ -190,56 +277,53 @@ impl tls_codec::Deserialize for XmtpInstallationCredential {
     }
 }
 
-impl From<XmtpInstallationCredential> for SignatureKeyPair {
-    fn from(key: XmtpInstallationCredential) -> SignatureKeyPair {
-        SignatureKeyPair::from_raw(
-            key.signature_scheme(),
-            key.0.to_bytes().into(),
-            key.0.verifying_key().to_bytes().into(),
-        )
-    }
-}
+/// Fails for keys held outside of the process
+impl TryFrom<&XmtpInstallationCredential> for SignatureKeyPair {
+    type Error = SignerError;
 
-impl<'a> From<&'a XmtpInstallationCredential> for SignatureKeyPair {
-    fn from(key: &'a XmtpInstallationCredential) -> SignatureKeyPair {
-        SignatureKeyPair::from_raw(
-            key.signature_scheme(),
-            key.0.to_bytes().into(),
-            key.0.verifying_key().to_bytes().into(),
-        )
+    fn try_from(key: &XmtpInstallationCredential) -> Result<SignatureKeyPair, SignerError> {
+        Ok(key.key_pair()?)
     }
 }
 
 impl From<SigningKey> for XmtpInstallationCredential {
     fn from(signing_key: SigningKey) -> Self {
-        Self(Box::new(signing_key))
+        Self(Box::new(InstallationKey::Local(signing_key)))
     }
 }
 
 impl<'a> From<&'a SigningKey> for XmtpInstallationCredential {
     fn from(signing_key: &'a SigningKey) -> Self {
-        Self(Box::new(signing_key.clone()))
+        signing_key.clone().into()
     }
 }
 
+/// Keys held outside of the process cannot be serialized
 impl tls_codec::Serialize for XmtpInstallationCredential {
     fn tls_serialize<W: std::io::Write>(&self, writer: &mut W) -> Result<usize, tls_codec::Error> {
-        SignatureKeyPair::from(self).tls_serialize(writer)
+        self.key_pair()
+            .map_err(|_| tls_codec::Error::EncodingError("installation key is external".into()))?
+            .tls_serialize(writer)
     }
 }
 
 impl tls_codec::Size for XmtpInstallationCredential {
     fn tls_serialized_len(&self) -> usize {
-        SignatureKeyPair::from(self).tls_serialized_len()
+        self.key_pair()
+            .map(|key_pair| key_pair.tls_serialized_len())
+            .unwrap_or_default()
     }
 }
 
+/// Keys held outside of the process cannot be serialized
 impl serde::Serialize for XmtpInstallationCredential {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        SignatureKeyPair::from(self).serialize(serializer)
+        self.key_pair()
+            .map_err(|_| serde::ser::Error::custom("installation key is external"))?
+            .serialize(serializer)
     }
 }
 
@@ -283,8 +367,11 @@ mod tests {
 
         keypair.tls_serialize(&mut serialized).unwrap();
         let x_kp = XmtpInstallationCredential::tls_deserialize(&mut serialized.as_slice()).unwrap();
-        assert_eq!(keypair.private(), &x_kp.0.to_bytes());
-        assert_eq!(keypair.public(), &x_kp.0.verifying_key().to_bytes());
+        assert_eq!(keypair.private(), &x_kp.local().unwrap().to_bytes());
+        assert_eq!(
+            keypair.public(),
+            &x_kp.local().unwrap().verifying_key().to_bytes()
+        );
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
@@ -295,8 +382,11 @@ mod tests {
 
         keypair.tls_serialize(&mut serialized).unwrap();
         let mls_kp = SignatureKeyPair::tls_deserialize(&mut serialized.as_slice()).unwrap();
-        assert_eq!(mls_kp.private(), &keypair.0.to_bytes());
-        assert_eq!(mls_kp.public(), &keypair.0.verifying_key().to_bytes());
+        assert_eq!(mls_kp.private(), &keypair.local().unwrap().to_bytes());
+        assert_eq!(
+            mls_kp.public(),
+            &keypair.local().unwrap().verifying_key().to_bytes()
+        );
         assert_eq!(mls_kp.signature_scheme(), keypair.scheme());
     }
 
@@ -309,8 +399,11 @@ mod tests {
         let serialized: Vec<u8> = bincode::serialize(&keypair).unwrap();
 
         let x_kp: XmtpInstallationCredential = bincode::deserialize(serialized.as_slice()).unwrap();
-        assert_eq!(keypair.private(), &x_kp.0.to_bytes());
-        assert_eq!(keypair.public(), &x_kp.0.verifying_key().to_bytes());
+        assert_eq!(keypair.private(), &x_kp.local().unwrap().to_bytes());
+        assert_eq!(
+            keypair.public(),
+            &x_kp.local().unwrap().verifying_key().to_bytes()
+        );
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
@@ -320,16 +413,70 @@ mod tests {
         let serialized: Vec<u8> = bincode::serialize(&keypair).unwrap();
 
         let mls_kp: SignatureKeyPair = bincode::deserialize(serialized.as_slice()).unwrap();
-        assert_eq!(mls_kp.private(), &keypair.0.to_bytes());
-        assert_eq!(mls_kp.public(), &keypair.0.verifying_key().to_bytes());
+        assert_eq!(mls_kp.private(), &keypair.local().unwrap().to_bytes());
+        assert_eq!(
+            mls_kp.public(),
+            &keypair.local().unwrap().verifying_key().to_bytes()
+        );
         assert_eq!(mls_kp.signature_scheme(), keypair.scheme());
     }
 
+    /// Signs with a key of the test, standing in for a secure module
+    #[derive(Debug)]
+    struct TestExternalKey(XmtpInstallationCredential);
+
+    impl ExternalInstallationKey for TestExternalKey {
+        fn handle(&self) -> &[u8] {
+            b"test-key"
+        }
+
+        fn sign(&self, payload: &[u8]) -> Result<[u8; 64], signatures::SignerError> {
+            Ok(ed25519_dalek::Signer::sign(self.0.local().unwrap(), payload).to_bytes())
+        }
+
+        fn sign_with_context(
+            &self,
+            context: &[u8],
+            message: &[u8],
+        ) -> Result<[u8; 64], signatures::SignerError> {
+            self.0
+                .sign_with_context(context, message)
+                .map(|signature| signature.to_bytes())
+                .map_err(|e| e.inner)
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn external_key_signs_like_a_local_key() {
+        let local = XmtpInstallationCredential::new();
+        let external = XmtpInstallationCredential::external(
+            local.public_bytes(),
+            Arc::new(TestExternalKey(local.clone())),
+        )
+        .unwrap();
+        assert_eq!(external.public_bytes(), local.public_bytes());
+        assert_eq!(external.external_handle(), Some(&b"test-key"[..]));
+        assert_eq!(
+            Signer::sign(&external, b"payload").unwrap(),
+            Signer::sign(&local, b"payload").unwrap()
+        );
+        assert_eq!(
+            external.sign_with_context(b"context", b"text").unwrap(),
+            local.sign_with_context(b"context", b"text").unwrap()
+        );
+
+        // The private key never leaves the module
+        assert!(bincode::serialize(&external).is_err());
+        assert!(external.tls_serialize_detached().is_err());
+        assert!(SignatureKeyPair::try_from(&external).is_err());
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     fn secret_key_can_not_be_exposed() {
         let keypair = XmtpInstallationCredential::new();
-        let secret = keypair.0.as_ref();
+        let secret = keypair.local().unwrap();
 
         assert_ne!(keypair.public_bytes(), secret.as_bytes());
         assert_ne!(keypair.public_slice(), secret.as_bytes());
@@ -1,3 +1,4 @@
+pub mod account_id;
 pub mod basic_credential;
 pub mod hash;
 pub mod signature;
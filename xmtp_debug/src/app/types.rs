@@ -83,7 +83,10 @@ impl Identity {
         eth_key.copy_from_slice(wallet.0.to_bytes().as_slice());
         Ok(Identity {
             inbox_id,
-            installation_key: identity.installation_keys.private_bytes(),
+            installation_key: identity
+                .installation_keys
+                .private_bytes()
+                .ok_or_else(|| eyre::eyre!("installation key is held outside of the process"))?,
             eth_key,
         })
     }
@@ -1,5 +1,5 @@
 use super::hashes::generate_inbox_id;
-use super::member::{Member, MemberIdentifier, MemberKind};
+use super::member::{AddressRules, Member, MemberIdentifier, MemberKind};
 use super::serialization::DeserializationError;
 use super::signature::{SignatureError, SignatureKind};
 use super::state::AssociationState;
@@ -38,6 +38,8 @@ pub enum AssociationError {
     ChainIdMismatch(u64, u64),
     #[error("Invalid account address: Must be 42 hex characters, starting with '0x'.")]
     InvalidAccountAddress,
+    #[error("Signature of kind {0} not allowed by address rules version {1}")]
+    SignatureNotAllowedByAddressRules(String, u32),
 }
 
 pub trait IdentityAction: Send {
@@ -45,6 +47,7 @@ pub trait IdentityAction: Send {
         &self,
         existing_state: Option<AssociationState>,
         client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError>;
     fn signatures(&self) -> Vec<Vec<u8>>;
     fn replay_check(&self, state: &AssociationState) -> Result<(), AssociationError> {
@@ -72,18 +75,24 @@ impl IdentityAction for CreateInbox {
         &self,
         existing_state: Option<AssociationState>,
         _client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
         if existing_state.is_some() {
             return Err(AssociationError::MultipleCreate);
         }
 
-        let account_address = self.account_address.clone();
+        let canonical_address = address_rules.canonical_address(&self.account_address);
         let recovered_signer = self.initial_address_signature.signer.clone();
-        if recovered_signer.ne(&MemberIdentifier::Address(
-            account_address.clone().to_lowercase(),
-        )) {
+        if recovered_signer.ne(&MemberIdentifier::Address(canonical_address.clone())) {
             return Err(AssociationError::MissingExistingMember);
         }
+        // Account ids of non-EVM chains are stored in their canonical form. Other addresses are
+        // stored as they were signed, like they always have been.
+        let account_address = if canonical_address == self.account_address.to_lowercase() {
+            self.account_address.clone()
+        } else {
+            canonical_address.clone()
+        };
 
         allowed_signature_for_kind(&MemberKind::Address, &self.initial_address_signature.kind)?;
 
@@ -92,11 +101,12 @@ impl IdentityAction for CreateInbox {
             return Err(AssociationError::LegacySignatureReuse);
         }
 
-        AssociationState::new(
+        Ok(AssociationState::new(
             account_address,
             self.nonce,
             self.initial_address_signature.chain_id,
-        )
+        )?
+        .set_recovery_address(canonical_address))
     }
 
     fn signatures(&self) -> Vec<Vec<u8>> {
@@ -117,6 +127,7 @@ impl IdentityAction for AddAssociation {
         &self,
         maybe_existing_state: Option<AssociationState>,
         client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
         let existing_state = maybe_existing_state.ok_or(AssociationError::NotCreated)?;
         self.replay_check(&existing_state)?;
@@ -126,7 +137,7 @@ impl IdentityAction for AddAssociation {
         // Validate the existing member signature and get the recovedred signer
         let existing_member_identifier = &self.existing_member_signature.signer;
 
-        if new_member_address.ne(&self.new_member_identifier) {
+        if new_member_address.ne(&address_rules.canonical_identifier(&self.new_member_identifier)) {
             return Err(AssociationError::NewMemberIdSignatureMismatch);
         }
 
@@ -163,8 +174,8 @@ impl IdentityAction for AddAssociation {
             Some(member) => member.identifier.clone(),
             None => {
                 // Get the recovery address from the state as a MemberIdentifier
-                let recovery_identifier: MemberIdentifier =
-                    existing_state.recovery_address().clone().into();
+                let recovery_identifier =
+                    MemberIdentifier::Address(existing_state.recovery_address().clone());
 
                 // Check if it is a signature from the recovery address, which is allowed to add members
                 if existing_member_identifier.ne(&recovery_identifier) {
@@ -221,6 +232,7 @@ impl IdentityAction for RevokeAssociation {
         &self,
         maybe_existing_state: Option<AssociationState>,
         _client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
         let existing_state = maybe_existing_state.ok_or(AssociationError::NotCreated)?;
         self.replay_check(&existing_state)?;
@@ -243,21 +255,20 @@ impl IdentityAction for RevokeAssociation {
         let state_recovery_address = existing_state.recovery_address();
 
         // Ensure this message is signed by the recovery address
-        if recovery_signer.ne(&MemberIdentifier::Address(
-            state_recovery_address.clone().to_lowercase(),
-        )) {
+        if recovery_signer.ne(&MemberIdentifier::Address(state_recovery_address.clone())) {
             return Err(AssociationError::MissingExistingMember);
         }
 
+        let revoked_member = address_rules.canonical_identifier(&self.revoked_member);
         let installations_to_remove: Vec<Member> = existing_state
-            .members_by_parent(&self.revoked_member)
+            .members_by_parent(&revoked_member)
             .into_iter()
             // Only remove children if they are installations
             .filter(|child| child.kind() == MemberKind::Installation)
             .collect();
 
         // Actually apply the revocation to the parent
-        let new_state = existing_state.remove(&self.revoked_member);
+        let new_state = existing_state.remove(&revoked_member);
 
         Ok(installations_to_remove
             .iter()
@@ -283,6 +294,7 @@ impl IdentityAction for ChangeRecoveryAddress {
         &self,
         existing_state: Option<AssociationState>,
         _client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
         let existing_state = existing_state.ok_or(AssociationError::NotCreated)?;
         self.replay_check(&existing_state)?;
@@ -300,11 +312,14 @@ impl IdentityAction for ChangeRecoveryAddress {
         }
 
         let recovery_signer = &self.recovery_address_signature.signer;
-        if recovery_signer.ne(&existing_state.recovery_address().clone().into()) {
+        if recovery_signer.ne(&MemberIdentifier::Address(
+            existing_state.recovery_address().clone(),
+        )) {
             return Err(AssociationError::MissingExistingMember);
         }
 
-        Ok(existing_state.set_recovery_address(self.new_recovery_address.clone()))
+        Ok(existing_state
+            .set_recovery_address(address_rules.canonical_address(&self.new_recovery_address)))
     }
 
     fn signatures(&self) -> Vec<Vec<u8>> {
@@ -326,17 +341,20 @@ impl IdentityAction for Action {
         &self,
         existing_state: Option<AssociationState>,
         client_timestamp_ns: u64,
+        address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
         match self {
-            Action::CreateInbox(event) => event.update_state(existing_state, client_timestamp_ns),
+            Action::CreateInbox(event) => {
+                event.update_state(existing_state, client_timestamp_ns, address_rules)
+            }
             Action::AddAssociation(event) => {
-                event.update_state(existing_state, client_timestamp_ns)
+                event.update_state(existing_state, client_timestamp_ns, address_rules)
             }
             Action::RevokeAssociation(event) => {
-                event.update_state(existing_state, client_timestamp_ns)
+                event.update_state(existing_state, client_timestamp_ns, address_rules)
             }
             Action::ChangeRecoveryAddress(event) => {
-                event.update_state(existing_state, client_timestamp_ns)
+                event.update_state(existing_state, client_timestamp_ns, address_rules)
             }
         }
     }
@@ -351,12 +369,27 @@ impl IdentityAction for Action {
     }
 }
 
+impl Action {
+    fn verified_signatures(&self) -> Vec<&VerifiedSignature> {
+        match self {
+            Action::CreateInbox(event) => vec![&event.initial_address_signature],
+            Action::AddAssociation(event) => vec![
+                &event.existing_member_signature,
+                &event.new_member_signature,
+            ],
+            Action::RevokeAssociation(event) => vec![&event.recovery_address_signature],
+            Action::ChangeRecoveryAddress(event) => vec![&event.recovery_address_signature],
+        }
+    }
+}
+
 /// An `IdentityUpdate` contains one or more Actions that can be applied to the AssociationState
 #[derive(Debug, Clone)]
 pub struct IdentityUpdate {
     pub inbox_id: String,
     pub client_timestamp_ns: u64,
     pub actions: Vec<Action>,
+    pub address_rules: AddressRules,
 }
 
 impl IdentityUpdate {
@@ -365,8 +398,14 @@ impl IdentityUpdate {
             inbox_id,
             actions,
             client_timestamp_ns,
+            address_rules: AddressRules::Legacy,
         }
     }
+
+    pub fn with_address_rules(mut self, address_rules: AddressRules) -> Self {
+        self.address_rules = address_rules;
+        self
+    }
 }
 
 impl IdentityAction for IdentityUpdate {
@@ -374,10 +413,24 @@ impl IdentityAction for IdentityUpdate {
         &self,
         existing_state: Option<AssociationState>,
         _client_timestamp_ns: u64,
+        _address_rules: AddressRules,
     ) -> Result<AssociationState, AssociationError> {
+        if let Some(signature) = self
+            .actions
+            .iter()
+            .flat_map(Action::verified_signatures)
+            .find(|signature| !self.address_rules.allows_signature(&signature.kind))
+        {
+            return Err(AssociationError::SignatureNotAllowedByAddressRules(
+                signature.kind.to_string(),
+                self.address_rules.version(),
+            ));
+        }
+
         let mut state = existing_state.clone();
         for action in &self.actions {
-            state = Some(action.update_state(state, self.client_timestamp_ns)?);
+            state =
+                Some(action.update_state(state, self.client_timestamp_ns, self.address_rules)?);
         }
 
         let new_state = state.ok_or(AssociationError::NotCreated)?;
@@ -436,12 +489,14 @@ fn allowed_signature_for_kind(
             SignatureKind::Erc1271 => true,
             SignatureKind::InstallationKey => false,
             SignatureKind::LegacyDelegated => true,
+            SignatureKind::Ed25519Account => true,
         },
         MemberKind::Installation => match signature_kind {
             SignatureKind::Erc191 => false,
             SignatureKind::Erc1271 => false,
             SignatureKind::InstallationKey => true,
             SignatureKind::LegacyDelegated => false,
+            SignatureKind::Ed25519Account => false,
         },
     };
 
@@ -5,8 +5,11 @@
 use std::collections::HashMap;
 
 use crate::scw_verifier::SmartContractSignatureVerifier;
+use prost::Message;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use xmtp_common::time::now_ns;
+use xmtp_proto::xmtp::identity::associations::Signature as SignatureWrapperProto;
 
 use super::{
     unsigned_actions::{
@@ -20,7 +23,7 @@ use super::{
         UnverifiedRevokeAssociation, UnverifiedSignature, UnverifiedSmartContractWalletSignature,
     },
     verified_signature::VerifiedSignature,
-    MemberIdentifier, MemberKind, SignatureError,
+    AddressRules, DeserializationError, MemberIdentifier, MemberKind, SignatureError,
 };
 
 /// The SignatureField is used to map the signatures from a [SignatureRequest] back to the correct
@@ -164,6 +167,12 @@ pub enum SignatureRequestError {
     Signature(#[from] SignatureError),
     #[error("Unable to get block number")]
     BlockNumber,
+    #[error("Unable to serialize signature request: {0}")]
+    Serialization(String),
+    #[error("No signature requests to combine")]
+    EmptyCombination,
+    #[error("Signature requests of different inboxes can not be combined")]
+    InboxMismatch,
 }
 
 /// A signature request is meant to be sent over the FFI barrier (wrapped in a mutex) to platform SDKs.
@@ -295,17 +304,195 @@ impl SignatureRequest {
             .into_iter()
             .map(|pending_action| build_action(pending_action, &self.signatures))
             .collect::<Result<Vec<UnverifiedAction>, SignatureRequestError>>()?;
+        let address_rules =
+            AddressRules::required_for(actions.iter().flat_map(UnverifiedAction::addresses));
 
-        Ok(UnverifiedIdentityUpdate::new(
-            self.inbox_id,
-            self.client_timestamp_ns,
-            actions,
-        ))
+        Ok(
+            UnverifiedIdentityUpdate::new(self.inbox_id, self.client_timestamp_ns, actions)
+                .with_address_rules(address_rules),
+        )
     }
 
     pub fn inbox_id(&self) -> crate::InboxIdRef<'_> {
         &self.inbox_id
     }
+
+    pub fn client_timestamp_ns(&self) -> u64 {
+        self.client_timestamp_ns
+    }
+
+    /// Combine `requests` of the same inbox into one request holding all their actions in order,
+    /// so that each signer signs once and the actions are published as a single identity update.
+    ///
+    /// The combined request has a new signature text, so the signatures collected so far are
+    /// dropped and must be added again.
+    pub fn combine(requests: Vec<SignatureRequest>) -> Result<Self, SignatureRequestError> {
+        let mut requests = requests.into_iter();
+        let first = requests
+            .next()
+            .ok_or(SignatureRequestError::EmptyCombination)?;
+        let mut builder = SignatureRequestBuilder::new(first.inbox_id);
+        builder.client_timestamp_ns = first.client_timestamp_ns;
+        builder.actions = first.pending_actions;
+        for request in requests {
+            if request.inbox_id != builder.inbox_id {
+                return Err(SignatureRequestError::InboxMismatch);
+            }
+            builder.actions.extend(request.pending_actions);
+        }
+        Ok(builder.build())
+    }
+
+    /// Serialize the request with the signatures collected so far, so that it can be stored
+    /// while waiting for the remaining signatures
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SignatureRequestError> {
+        let snapshot = SignatureRequestSnapshot {
+            inbox_id: self.inbox_id.clone(),
+            client_timestamp_ns: self.client_timestamp_ns,
+            actions: self
+                .pending_actions
+                .iter()
+                .map(PendingActionSnapshot::try_from)
+                .collect::<Result<_, _>>()?,
+            signatures: self
+                .signatures
+                .iter()
+                .map(|(signer, signature)| {
+                    (
+                        signer.into(),
+                        SignatureWrapperProto::from(signature.clone()).encode_to_vec(),
+                    )
+                })
+                .collect(),
+        };
+        serde_json::to_vec(&snapshot)
+            .map_err(|e| SignatureRequestError::Serialization(e.to_string()))
+    }
+
+    /// Restore a request serialized with [`Self::to_bytes`]. The signatures were checked when
+    /// they were added, and are checked again when the identity update is published.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureRequestError> {
+        let snapshot: SignatureRequestSnapshot = serde_json::from_slice(bytes)
+            .map_err(|e| SignatureRequestError::Serialization(e.to_string()))?;
+        let mut builder = SignatureRequestBuilder::new(snapshot.inbox_id);
+        builder.client_timestamp_ns = snapshot.client_timestamp_ns;
+        for action in snapshot.actions {
+            builder = match action {
+                PendingActionSnapshot::CreateInbox { signer, nonce } => {
+                    builder.create_inbox(signer.into(), nonce)
+                }
+                PendingActionSnapshot::AddAssociation {
+                    new_member,
+                    existing_member,
+                } => builder.add_association(new_member.into(), existing_member.into()),
+                PendingActionSnapshot::RevokeAssociation {
+                    recovery_address,
+                    revoked_member,
+                } => builder.revoke_association(recovery_address.into(), revoked_member.into()),
+                PendingActionSnapshot::ChangeRecoveryAddress {
+                    recovery_address,
+                    new_recovery_address,
+                } => builder.change_recovery_address(recovery_address.into(), new_recovery_address),
+            };
+        }
+
+        let mut request = builder.build();
+        for (signer, signature) in snapshot.signatures {
+            let signature = SignatureWrapperProto::decode(signature.as_slice())
+                .map_err(|e| SignatureRequestError::Serialization(e.to_string()))?
+                .try_into()
+                .map_err(|e: DeserializationError| {
+                    SignatureRequestError::Serialization(e.to_string())
+                })?;
+            request.signatures.insert(signer.into(), signature);
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignatureRequestSnapshot {
+    inbox_id: String,
+    client_timestamp_ns: u64,
+    actions: Vec<PendingActionSnapshot>,
+    /// Signers and their encoded signatures
+    signatures: Vec<(MemberSnapshot, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PendingActionSnapshot {
+    CreateInbox {
+        signer: MemberSnapshot,
+        nonce: u64,
+    },
+    AddAssociation {
+        new_member: MemberSnapshot,
+        existing_member: MemberSnapshot,
+    },
+    RevokeAssociation {
+        recovery_address: MemberSnapshot,
+        revoked_member: MemberSnapshot,
+    },
+    ChangeRecoveryAddress {
+        recovery_address: MemberSnapshot,
+        new_recovery_address: String,
+    },
+}
+
+impl TryFrom<&PendingIdentityAction> for PendingActionSnapshot {
+    type Error = SignatureRequestError;
+
+    fn try_from(action: &PendingIdentityAction) -> Result<Self, Self::Error> {
+        let signer = |field: SignatureField| {
+            action
+                .pending_signatures
+                .get(&field)
+                .map(MemberSnapshot::from)
+                .ok_or(SignatureRequestError::MissingSigner)
+        };
+        Ok(match &action.unsigned_action {
+            UnsignedAction::CreateInbox(create) => Self::CreateInbox {
+                signer: signer(SignatureField::InitialAddress)?,
+                nonce: create.nonce,
+            },
+            UnsignedAction::AddAssociation(_) => Self::AddAssociation {
+                new_member: signer(SignatureField::NewMember)?,
+                existing_member: signer(SignatureField::ExistingMember)?,
+            },
+            UnsignedAction::RevokeAssociation(revoke) => Self::RevokeAssociation {
+                recovery_address: signer(SignatureField::RecoveryAddress)?,
+                revoked_member: (&revoke.revoked_member).into(),
+            },
+            UnsignedAction::ChangeRecoveryAddress(change) => Self::ChangeRecoveryAddress {
+                recovery_address: signer(SignatureField::RecoveryAddress)?,
+                new_recovery_address: change.new_recovery_address.clone(),
+            },
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum MemberSnapshot {
+    Address(String),
+    Installation(Vec<u8>),
+}
+
+impl From<&MemberIdentifier> for MemberSnapshot {
+    fn from(member: &MemberIdentifier) -> Self {
+        match member {
+            MemberIdentifier::Address(address) => Self::Address(address.clone()),
+            MemberIdentifier::Installation(id) => Self::Installation(id.clone()),
+        }
+    }
+}
+
+impl From<MemberSnapshot> for MemberIdentifier {
+    fn from(member: MemberSnapshot) -> Self {
+        match member {
+            MemberSnapshot::Address(address) => Self::Address(address),
+            MemberSnapshot::Installation(id) => Self::Installation(id),
+        }
+    }
 }
 
 fn build_action(
@@ -486,6 +673,86 @@ pub(crate) mod tests {
         assert_eq!(state.members().len(), 2);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn resume_serialized_request() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let installation_key = XmtpInstallationCredential::new();
+        let account_address = wallet.get_address();
+        let inbox_id = generate_inbox_id(&account_address, &0).unwrap();
+        let existing_member_identifier: MemberIdentifier = account_address.into();
+
+        let mut signature_request = SignatureRequestBuilder::new(inbox_id)
+            .create_inbox(existing_member_identifier.clone(), 0)
+            .add_association((&installation_key).into(), existing_member_identifier)
+            .build();
+        add_installation_key_signature(&mut signature_request, &installation_key).await;
+
+        // The wallet signature is added after the request is restored
+        let mut restored =
+            SignatureRequest::from_bytes(&signature_request.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            restored.signature_text(),
+            signature_request.signature_text()
+        );
+        assert_eq!(restored.missing_signatures().len(), 1);
+        add_wallet_signature(&mut restored, &wallet).await;
+
+        let identity_update = restored.build_identity_update().expect("should be valid");
+        let state =
+            get_state(vec![convert_to_verified(&identity_update).await]).expect("should be valid");
+        assert_eq!(state.members().len(), 2);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn combine_requests() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let wallet_2 = LocalWallet::new(&mut rand::thread_rng());
+        let installation_key = XmtpInstallationCredential::new();
+        let account_address = wallet.get_address();
+        let inbox_id = generate_inbox_id(&account_address, &0).unwrap();
+        let existing_member_identifier: MemberIdentifier = account_address.into();
+
+        let mut register = SignatureRequestBuilder::new(&inbox_id)
+            .create_inbox(existing_member_identifier.clone(), 0)
+            .add_association(
+                (&installation_key).into(),
+                existing_member_identifier.clone(),
+            )
+            .build();
+        add_installation_key_signature(&mut register, &installation_key).await;
+        let add_wallet = SignatureRequestBuilder::new(&inbox_id)
+            .add_association(wallet_2.get_address().into(), existing_member_identifier)
+            .build();
+
+        let mut combined = SignatureRequest::combine(vec![register, add_wallet]).unwrap();
+        // the installation signature was over the text of the first request only
+        let installation_member: MemberIdentifier = (&installation_key).into();
+        assert!(combined
+            .missing_signatures()
+            .contains(&&installation_member));
+        add_installation_key_signature(&mut combined, &installation_key).await;
+        add_wallet_signature(&mut combined, &wallet).await;
+        add_wallet_signature(&mut combined, &wallet_2).await;
+
+        let identity_update = combined.build_identity_update().expect("should be valid");
+        let state =
+            get_state(vec![convert_to_verified(&identity_update).await]).expect("should be valid");
+        assert_eq!(state.members().len(), 3);
+
+        let other = SignatureRequestBuilder::new("other").build();
+        let first = SignatureRequestBuilder::new(&inbox_id).build();
+        assert!(matches!(
+            SignatureRequest::combine(vec![first, other]),
+            Err(SignatureRequestError::InboxMismatch)
+        ));
+        assert!(matches!(
+            SignatureRequest::combine(vec![]),
+            Err(SignatureRequestError::EmptyCombination)
+        ));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
     async fn create_and_revoke() {
@@ -1,4 +1,7 @@
+use std::str::FromStr;
+
 use sha2::{Digest, Sha256};
+use xmtp_cryptography::account_id::AccountId;
 
 use super::AssociationError;
 
@@ -18,14 +21,44 @@ fn is_valid_address(account_address: &str) -> bool {
         && account_address[2..].chars().all(|c| c.is_ascii_hexdigit())
 }
 
-/// Generates an inbox ID if the account address is valid.
+/// Generates an inbox ID if the account address is valid. The account address is either an
+/// Ethereum address, or a CAIP-10 account id. The account ids of EVM accounts generate the same
+/// inbox ID as their plain address, see [`AccountId::to_account_address`].
 pub fn generate_inbox_id(account_address: &str, nonce: &u64) -> Result<String, AssociationError> {
-    if !is_valid_address(account_address) {
+    let account_id = AccountId::from_str(account_address)
+        .map_err(|_| AssociationError::InvalidAccountAddress)?;
+    let account_address = account_id.to_account_address();
+    if account_id.is_evm() && !is_valid_address(&account_address) {
         return Err(AssociationError::InvalidAccountAddress);
     }
-    Ok(sha256_string(format!(
-        "{}{}",
-        account_address.to_lowercase(),
-        nonce
-    )))
+    Ok(sha256_string(format!("{}{}", account_address, nonce)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn caip10_account_ids() {
+        let address = "0xAb16A96D359eC26a11e2C2b3d8f8B8942d5Bfcdb";
+        let inbox_id = generate_inbox_id(address, &0).unwrap();
+        assert_eq!(
+            generate_inbox_id(&format!("eip155:1:{address}"), &0).unwrap(),
+            inbox_id
+        );
+        assert_eq!(
+            generate_inbox_id(&format!("eip155:8453:{address}"), &0).unwrap(),
+            inbox_id
+        );
+        assert!(generate_inbox_id("eip155:1:0x123", &0).is_err());
+        assert!(generate_inbox_id(&address[2..], &0).is_err());
+
+        let solana =
+            "solana:4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZ:7S3P4HxJpyyigGzodYwHtCxZyUQe9JiBMHyRWXArAaKv";
+        assert_eq!(
+            generate_inbox_id(solana, &0).unwrap(),
+            sha256_string(format!("{solana}0"))
+        );
+    }
 }
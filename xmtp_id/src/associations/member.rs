@@ -1,5 +1,12 @@
+use std::str::FromStr;
+
 use ed25519_dalek::VerifyingKey;
-use xmtp_cryptography::XmtpInstallationCredential;
+use xmtp_cryptography::{
+    account_id::{normalize_account_address, AccountId},
+    XmtpInstallationCredential,
+};
+
+use super::{serialization::DeserializationError, SignatureKind};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum MemberKind {
@@ -95,9 +102,87 @@ impl std::fmt::Display for MemberIdentifier {
     }
 }
 
+/// The rules that turn an account address found in an identity update into the address stored
+/// in the association state, and compared with the signers of the update.
+///
+/// Every identity update names the version of its rules. The versions only differ on the account
+/// ids of non-EVM chains, which can only be signed for with a
+/// [`SignatureKind::Ed25519Account`] signature, so clients and validation services that do not
+/// know a version reject the updates that need it as a whole, instead of reading them differently.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressRules {
+    /// Addresses are lowercased
+    #[default]
+    Legacy,
+    /// Addresses are lowercased too, except the CAIP-10 account ids of non-EVM chains, whose
+    /// addresses can be case sensitive. They are kept in the form of [`AccountId`], and can sign
+    /// with [`SignatureKind::Ed25519Account`] signatures.
+    Caip10,
+}
+
+impl AddressRules {
+    pub fn from_version(version: u32) -> Result<Self, DeserializationError> {
+        match version {
+            0 => Ok(Self::Legacy),
+            1 => Ok(Self::Caip10),
+            version => Err(DeserializationError::UnsupportedAddressRules(version)),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Self::Legacy => 0,
+            Self::Caip10 => 1,
+        }
+    }
+
+    /// The rules to write an update naming `addresses` with. Updates are only written with
+    /// [`Self::Caip10`] when they need it, so that older clients keep reading all other updates.
+    pub fn required_for(addresses: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        if addresses
+            .into_iter()
+            .any(|address| non_evm_account_id(address.as_ref()).is_some())
+        {
+            Self::Caip10
+        } else {
+            Self::Legacy
+        }
+    }
+
+    pub fn canonical_address(&self, address: &str) -> String {
+        match (self, non_evm_account_id(address)) {
+            (Self::Caip10, Some(account_id)) => account_id.to_string(),
+            _ => address.to_lowercase(),
+        }
+    }
+
+    pub fn canonical_identifier(&self, identifier: &MemberIdentifier) -> MemberIdentifier {
+        match identifier {
+            MemberIdentifier::Address(address) => {
+                MemberIdentifier::Address(self.canonical_address(address))
+            }
+            MemberIdentifier::Installation(_) => identifier.clone(),
+        }
+    }
+
+    pub fn allows_signature(&self, kind: &SignatureKind) -> bool {
+        *kind != SignatureKind::Ed25519Account || *self == Self::Caip10
+    }
+}
+
+pub(crate) fn non_evm_account_id(address: &str) -> Option<AccountId> {
+    AccountId::from_str(address)
+        .ok()
+        .filter(|account_id| !account_id.is_evm())
+}
+
+/// Builds the identifier of an address provided by the user, see [`normalize_account_address`].
+/// Identity updates read from the network keep their addresses as they were signed.
 impl From<String> for MemberIdentifier {
     fn from(address: String) -> Self {
-        MemberIdentifier::Address(address.to_lowercase())
+        MemberIdentifier::Address(
+            normalize_account_address(&address).unwrap_or_else(|_| address.to_lowercase()),
+        )
     }
 }
 
@@ -4,6 +4,7 @@ mod hashes;
 pub(super) mod member;
 pub(super) mod serialization;
 pub mod signature;
+pub mod signature_text;
 pub(super) mod state;
 #[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
@@ -13,7 +14,7 @@ pub mod verified_signature;
 
 pub use self::association_log::*;
 pub use self::hashes::generate_inbox_id;
-pub use self::member::{Member, MemberIdentifier, MemberKind};
+pub use self::member::{AddressRules, Member, MemberIdentifier, MemberKind};
 pub use self::serialization::{map_vec, try_map_vec, DeserializationError};
 pub use self::signature::*;
 pub use self::state::{AssociationState, AssociationStateDiff};
@@ -23,7 +24,11 @@ pub fn apply_update(
     initial_state: AssociationState,
     update: IdentityUpdate,
 ) -> Result<AssociationState, AssociationError> {
-    update.update_state(Some(initial_state), update.client_timestamp_ns)
+    update.update_state(
+        Some(initial_state),
+        update.client_timestamp_ns,
+        update.address_rules,
+    )
 }
 
 /// Get the current state from an array of `IdentityUpdate`s. Entire operation fails if any operation fails
@@ -32,7 +37,7 @@ pub fn get_state<Updates: AsRef<[IdentityUpdate]>>(
 ) -> Result<AssociationState, AssociationError> {
     let mut state = None;
     for update in updates.as_ref().iter() {
-        let res = update.update_state(state, update.client_timestamp_ns);
+        let res = update.update_state(state, update.client_timestamp_ns, update.address_rules);
         state = Some(res?);
     }
 
@@ -720,4 +725,86 @@ pub(crate) mod tests {
             ));
         }
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn non_evm_accounts_need_the_caip10_address_rules() {
+        let account_id = format!(
+            "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp:{}",
+            bs58::encode(rand_vec::<32>()).into_string()
+        );
+        let inbox_id = generate_inbox_id(&account_id, &0).unwrap();
+        let create = IdentityUpdate::new_test(
+            vec![Action::CreateInbox(CreateInbox {
+                nonce: 0,
+                account_address: account_id.clone(),
+                initial_address_signature: VerifiedSignature::new(
+                    MemberIdentifier::Address(account_id.clone()),
+                    SignatureKind::Ed25519Account,
+                    rand_vec::<64>(),
+                    None,
+                ),
+            })],
+            inbox_id,
+        );
+
+        // Clients that only know the legacy rules never read an account id as it was signed
+        let legacy_result = get_state(vec![create.clone()]);
+        assert!(matches!(
+            legacy_result,
+            Err(AssociationError::SignatureNotAllowedByAddressRules(_, 0))
+        ));
+
+        let state = get_state(vec![create.with_address_rules(AddressRules::Caip10)]).unwrap();
+        assert!(state
+            .get(&MemberIdentifier::Address(account_id.clone()))
+            .is_some());
+        assert_eq!(state.recovery_address(), &account_id);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn evm_addresses_follow_the_same_rules_in_all_versions() {
+        let signer = rand_hexstring();
+        let inbox_id = generate_inbox_id(&signer, &0).unwrap();
+        let wallet = rand_hexstring();
+        let update = IdentityUpdate::new_test(
+            vec![
+                Action::CreateInbox(CreateInbox {
+                    nonce: 0,
+                    account_address: signer.clone(),
+                    initial_address_signature: VerifiedSignature::new(
+                        MemberIdentifier::Address(signer.clone()),
+                        SignatureKind::Erc191,
+                        rand_vec::<32>(),
+                        None,
+                    ),
+                }),
+                Action::AddAssociation(AddAssociation {
+                    existing_member_signature: VerifiedSignature::new(
+                        MemberIdentifier::Address(signer.clone()),
+                        SignatureKind::Erc191,
+                        rand_vec::<32>(),
+                        None,
+                    ),
+                    new_member_signature: VerifiedSignature::new(
+                        MemberIdentifier::Address(wallet.clone()),
+                        SignatureKind::Erc191,
+                        rand_vec::<32>(),
+                        None,
+                    ),
+                    new_member_identifier: MemberIdentifier::Address(wallet.to_uppercase()),
+                }),
+            ],
+            inbox_id,
+        );
+
+        let legacy_state = get_state(vec![update.clone()]).unwrap();
+        let state = get_state(vec![update.with_address_rules(AddressRules::Caip10)]).unwrap();
+        assert_eq!(legacy_state.members().len(), state.members().len());
+        for member in legacy_state.members() {
+            assert_eq!(state.get(&member.identifier), Some(&member));
+        }
+        assert_eq!(legacy_state.recovery_address(), state.recovery_address());
+        assert_eq!(state.recovery_address(), &signer);
+        assert!(state.get(&MemberIdentifier::Address(wallet)).is_some());
+    }
 }
@@ -3,7 +3,7 @@ use std::collections::{HashMap, HashSet};
 use crate::scw_verifier::ValidationResponse;
 
 use super::{
-    member::Member,
+    member::{non_evm_account_id, AddressRules, Member},
     signature::{AccountId, ValidatedLegacySignedPublicKey},
     state::{AssociationState, AssociationStateDiff},
     unsigned_actions::{
@@ -12,9 +12,10 @@ use super::{
     },
     unverified::{
         UnverifiedAction, UnverifiedAddAssociation, UnverifiedChangeRecoveryAddress,
-        UnverifiedCreateInbox, UnverifiedIdentityUpdate, UnverifiedInstallationKeySignature,
-        UnverifiedLegacyDelegatedSignature, UnverifiedRecoverableEcdsaSignature,
-        UnverifiedRevokeAssociation, UnverifiedSignature, UnverifiedSmartContractWalletSignature,
+        UnverifiedCreateInbox, UnverifiedEd25519AccountSignature, UnverifiedIdentityUpdate,
+        UnverifiedInstallationKeySignature, UnverifiedLegacyDelegatedSignature,
+        UnverifiedRecoverableEcdsaSignature, UnverifiedRevokeAssociation, UnverifiedSignature,
+        UnverifiedSmartContractWalletSignature,
     },
     verified_signature::VerifiedSignature,
     MemberIdentifier, SignatureError,
@@ -33,6 +34,7 @@ use xmtp_proto::xmtp::{
             AssociationState as AssociationStateProto,
             AssociationStateDiff as AssociationStateDiffProto,
             ChangeRecoveryAddress as ChangeRecoveryAddressProto, CreateInbox as CreateInboxProto,
+            Ed25519AccountSignature as Ed25519AccountSignatureProto,
             IdentityAction as IdentityActionProto, IdentityUpdate as IdentityUpdateProto,
             LegacyDelegatedSignature as LegacyDelegatedSignatureProto, Member as MemberProto,
             MemberIdentifier as MemberIdentifierProto, MemberMap as MemberMapProto,
@@ -76,6 +78,8 @@ pub enum DeserializationError {
     Ed25519(#[from] ed25519_dalek::ed25519::Error),
     #[error("Unable to deserialize")]
     Bincode,
+    #[error("Unsupported address rules version {0}")]
+    UnsupportedAddressRules(u32),
 }
 
 impl TryFrom<IdentityUpdateProto> for UnverifiedIdentityUpdate {
@@ -86,6 +90,7 @@ impl TryFrom<IdentityUpdateProto> for UnverifiedIdentityUpdate {
             client_timestamp_ns,
             inbox_id,
             actions,
+            address_rules_version,
         } = proto;
         let all_actions = actions
             .into_iter()
@@ -100,11 +105,10 @@ impl TryFrom<IdentityUpdateProto> for UnverifiedIdentityUpdate {
             .map(UnverifiedAction::try_from)
             .collect::<Result<Vec<UnverifiedAction>, DeserializationError>>()?;
 
-        Ok(UnverifiedIdentityUpdate::new(
-            inbox_id,
-            client_timestamp_ns,
-            processed_actions,
-        ))
+        Ok(
+            UnverifiedIdentityUpdate::new(inbox_id, client_timestamp_ns, processed_actions)
+                .with_address_rules(AddressRules::from_version(address_rules_version)?),
+        )
     }
 }
 
@@ -191,6 +195,9 @@ impl TryFrom<SignatureWrapperProto> for UnverifiedSignature {
                     sig.block_number,
                 ),
             ),
+            SignatureKindProto::Ed25519Account(sig) => UnverifiedSignature::Ed25519Account(
+                UnverifiedEd25519AccountSignature::new(sig.bytes, sig.account_id),
+            ),
         };
 
         Ok(unverified_sig)
@@ -222,6 +229,7 @@ impl From<UnverifiedIdentityUpdate> for IdentityUpdateProto {
             inbox_id: value.inbox_id,
             client_timestamp_ns: value.client_timestamp_ns,
             actions: map_vec(value.actions),
+            address_rules_version: value.address_rules.version(),
         }
     }
 }
@@ -295,6 +303,12 @@ impl From<UnverifiedSignature> for SignatureWrapperProto {
                     bytes: sig.signature_bytes,
                 })
             }
+            UnverifiedSignature::Ed25519Account(sig) => {
+                SignatureKindProto::Ed25519Account(Ed25519AccountSignatureProto {
+                    account_id: sig.account_id,
+                    bytes: sig.signature_bytes,
+                })
+            }
         };
 
         Self {
@@ -332,7 +346,16 @@ impl From<SmartContractWalletValidationResponseProto> for ValidationResponse {
 impl From<MemberIdentifierKindProto> for MemberIdentifier {
     fn from(proto: MemberIdentifierKindProto) -> Self {
         match proto {
-            MemberIdentifierKindProto::Address(address) => address.into(),
+            // Addresses are lowercased, except the account ids of non-EVM chains, which can be
+            // case sensitive. They are made canonical with the rules of the update they are part of.
+            MemberIdentifierKindProto::Address(address)
+                if non_evm_account_id(&address).is_some() =>
+            {
+                MemberIdentifier::Address(address)
+            }
+            MemberIdentifierKindProto::Address(address) => {
+                MemberIdentifier::Address(address.to_lowercase())
+            }
             MemberIdentifierKindProto::InstallationPublicKey(public_key) => public_key.into(),
         }
     }
@@ -656,6 +679,49 @@ pub(crate) mod tests {
         assert_eq!(serialized_update, reserialized);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_round_trip_address_rules() {
+        let account_id = format!(
+            "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp:{}",
+            bs58::encode(rand_vec::<32>()).into_string()
+        );
+        let identity_update = UnverifiedIdentityUpdate::new(
+            generate_inbox_id(&rand_hexstring(), &0).unwrap(),
+            rand_u64(),
+            vec![UnverifiedAction::AddAssociation(UnverifiedAddAssociation {
+                new_member_signature: UnverifiedSignature::new_ed25519_account(
+                    rand_vec::<64>(),
+                    account_id.clone(),
+                ),
+                existing_member_signature: UnverifiedSignature::new_recoverable_ecdsa(vec![1, 2]),
+                unsigned_action: UnsignedAddAssociation {
+                    new_member_identifier: MemberIdentifier::Address(account_id),
+                },
+            })],
+        )
+        .with_address_rules(AddressRules::Caip10);
+
+        let serialized_update = IdentityUpdateProto::from(identity_update.clone());
+        assert_eq!(serialized_update.address_rules_version, 1);
+
+        // The account id keeps the case of its base58 address
+        let deserialized_update: UnverifiedIdentityUpdate = serialized_update
+            .clone()
+            .try_into()
+            .expect("deserialization error");
+        assert_eq!(deserialized_update, identity_update);
+
+        let unsupported_update = IdentityUpdateProto {
+            address_rules_version: 2,
+            ..serialized_update
+        };
+        assert!(matches!(
+            UnverifiedIdentityUpdate::try_from(unsupported_update),
+            Err(DeserializationError::UnsupportedAddressRules(2))
+        ));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     fn test_accound_id() {
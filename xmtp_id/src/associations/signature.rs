@@ -1,4 +1,4 @@
-use ed25519_dalek::{DigestSigner, Signature, VerifyingKey};
+use ed25519_dalek::{Signature, VerifyingKey};
 use ethers::signers::{LocalWallet, Signer};
 use prost::Message;
 use sha2::{Digest as _, Sha512};
@@ -52,6 +52,10 @@ pub enum SignatureError {
     AccountIdError(#[from] AccountIdError),
     #[error(transparent)]
     Signer(#[from] SignerError),
+    #[error("Ed25519 signatures are not supported for accounts of namespace {0}")]
+    UnsupportedAccountNamespace(String),
+    #[error("Invalid base58 address: {0}")]
+    Base58(#[from] bs58::decode::Error),
 }
 
 /// Xmtp Installation Credential for Specialized for XMTP Identity
@@ -67,12 +71,7 @@ impl CredentialSign<InboxIdInstallationCredential> for XmtpInstallationCredentia
         &self,
         text: impl AsRef<str>,
     ) -> Result<Vec<u8>, Self::Error> {
-        let mut prehashed: Sha512 = Sha512::new();
-        prehashed.update(text.as_ref());
-        let context = self.with_context(T::context())?;
-        let sig = context
-            .try_sign_digest(prehashed)
-            .map_err(SignatureError::from)?;
+        let sig = self.sign_with_context(T::context(), text.as_ref().as_bytes())?;
         Ok(sig.to_bytes().into())
     }
 }
@@ -123,6 +122,8 @@ pub enum SignatureKind {
     Erc1271,
     InstallationKey,
     LegacyDelegated,
+    /// An Ed25519 signature of a non-EVM account, such as a Solana wallet
+    Ed25519Account,
 }
 
 impl std::fmt::Display for SignatureKind {
@@ -132,6 +133,7 @@ impl std::fmt::Display for SignatureKind {
             SignatureKind::Erc1271 => write!(f, "erc-1271"),
             SignatureKind::InstallationKey => write!(f, "installation-key"),
             SignatureKind::LegacyDelegated => write!(f, "legacy-delegated"),
+            SignatureKind::Ed25519Account => write!(f, "ed25519-account"),
         }
     }
 }
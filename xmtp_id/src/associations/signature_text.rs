@@ -0,0 +1,167 @@
+//! Builders for the challenge text that wallets sign to create or change an inbox, and helpers
+//! to verify the signatures.
+//!
+//! The text is signed with EIP-191 `personal_sign`, the same scheme Sign-In with Ethereum
+//! uses, so any wallet or backend that handles SIWE messages can sign and check it. The
+//! builders produce exactly the text that [`super::builder::SignatureRequest::signature_text`]
+//! asks to sign, so tooling does not need to copy the templates.
+use super::{
+    generate_inbox_id,
+    unsigned_actions::{
+        SignatureTextCreator, UnsignedAction, UnsignedAddAssociation,
+        UnsignedChangeRecoveryAddress, UnsignedCreateInbox, UnsignedIdentityUpdate,
+        UnsignedRevokeAssociation,
+    },
+    verified_signature::VerifiedSignature,
+    AssociationError, MemberIdentifier, SignatureError,
+};
+
+/// Builds the text for an identity update made of one or more actions
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignatureTextBuilder {
+    inbox_id: String,
+    client_timestamp_ns: u64,
+    actions: Vec<UnsignedAction>,
+}
+
+impl SignatureTextBuilder {
+    /// `client_timestamp_ns` is shown in the text, and must be the timestamp of the update
+    pub fn new<S: AsRef<str>>(inbox_id: S, client_timestamp_ns: u64) -> Self {
+        Self {
+            inbox_id: inbox_id.as_ref().to_string(),
+            client_timestamp_ns,
+            actions: vec![],
+        }
+    }
+
+    /// Start a builder for the update that creates the inbox of `account_address`
+    pub fn for_new_inbox(
+        account_address: &str,
+        nonce: u64,
+        client_timestamp_ns: u64,
+    ) -> Result<Self, AssociationError> {
+        let inbox_id = generate_inbox_id(account_address, &nonce)?;
+        Ok(Self::new(inbox_id, client_timestamp_ns).create_inbox(account_address, nonce))
+    }
+
+    pub fn create_inbox(self, account_address: &str, nonce: u64) -> Self {
+        self.action(UnsignedAction::CreateInbox(UnsignedCreateInbox {
+            nonce,
+            account_address: account_address.to_string(),
+        }))
+    }
+
+    pub fn add_wallet(self, address: &str) -> Self {
+        self.add_member(MemberIdentifier::Address(address.to_string()))
+    }
+
+    pub fn add_installation(self, installation_id: Vec<u8>) -> Self {
+        self.add_member(MemberIdentifier::Installation(installation_id))
+    }
+
+    pub fn revoke_wallet(self, address: &str) -> Self {
+        self.revoke_member(MemberIdentifier::Address(address.to_string()))
+    }
+
+    pub fn revoke_installation(self, installation_id: Vec<u8>) -> Self {
+        self.revoke_member(MemberIdentifier::Installation(installation_id))
+    }
+
+    pub fn change_recovery_address(self, new_recovery_address: &str) -> Self {
+        self.action(UnsignedAction::ChangeRecoveryAddress(
+            UnsignedChangeRecoveryAddress {
+                new_recovery_address: new_recovery_address.to_string(),
+            },
+        ))
+    }
+
+    /// The text to sign
+    pub fn build(&self) -> String {
+        UnsignedIdentityUpdate::new(
+            self.actions.clone(),
+            self.inbox_id.clone(),
+            self.client_timestamp_ns,
+        )
+        .signature_text()
+    }
+
+    fn add_member(self, new_member_identifier: MemberIdentifier) -> Self {
+        self.action(UnsignedAction::AddAssociation(UnsignedAddAssociation {
+            new_member_identifier,
+        }))
+    }
+
+    fn revoke_member(self, revoked_member: MemberIdentifier) -> Self {
+        self.action(UnsignedAction::RevokeAssociation(
+            UnsignedRevokeAssociation { revoked_member },
+        ))
+    }
+
+    fn action(mut self, action: UnsignedAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+}
+
+/// The address of the wallet that signed `signature_text` with EIP-191 `personal_sign`
+pub fn recover_wallet_signer(
+    signature_text: &str,
+    signature: &[u8],
+) -> Result<String, SignatureError> {
+    VerifiedSignature::from_recoverable_ecdsa(signature_text, signature)?
+        .signer
+        .address()
+        .map(str::to_string)
+        .ok_or(SignatureError::Invalid)
+}
+
+/// Check that `signature_text` was signed by `expected_address` with EIP-191 `personal_sign`.
+/// Smart contract wallets are verified with a
+/// [`crate::scw_verifier::SmartContractSignatureVerifier`] instead.
+pub fn verify_wallet_signature(
+    signature_text: &str,
+    signature: &[u8],
+    expected_address: &str,
+) -> Result<(), SignatureError> {
+    VerifiedSignature::from_recoverable_ecdsa_with_expected_address(
+        signature_text,
+        signature,
+        expected_address,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{associations::builder::SignatureRequestBuilder, InboxOwner};
+    use ethers::signers::{LocalWallet, Signer};
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_text_matches_signature_request() {
+        let wallet: LocalWallet = LocalWallet::new(&mut rand::thread_rng());
+        let address = wallet.get_address();
+        let request = SignatureRequestBuilder::new(generate_inbox_id(&address, &0).unwrap())
+            .create_inbox(MemberIdentifier::Address(address.clone()), 0)
+            .add_association(
+                MemberIdentifier::Installation(vec![1, 2, 3]),
+                MemberIdentifier::Address(address.clone()),
+            )
+            .build();
+
+        let text = SignatureTextBuilder::for_new_inbox(&address, 0, request.client_timestamp_ns())
+            .unwrap()
+            .add_installation(vec![1, 2, 3])
+            .build();
+        assert_eq!(text, request.signature_text());
+
+        let signature = wallet.sign_message(&text).await.unwrap().to_vec();
+        assert_eq!(recover_wallet_signer(&text, &signature).unwrap(), address);
+        assert!(verify_wallet_signature(&text, &signature, &address).is_ok());
+        assert!(verify_wallet_signature("other text", &signature, &address).is_err());
+    }
+}
@@ -69,7 +69,7 @@ impl AssociationState {
 
     pub fn set_recovery_address(&self, recovery_address: String) -> Self {
         let mut new_state = self.clone();
-        new_state.recovery_address = recovery_address.to_lowercase();
+        new_state.recovery_address = recovery_address;
 
         new_state
     }
@@ -8,8 +8,8 @@ use super::{
         UnsignedRevokeAssociation,
     },
     verified_signature::VerifiedSignature,
-    AccountId, Action, AddAssociation, CreateInbox, IdentityUpdate, RevokeAssociation,
-    SignatureError,
+    AccountId, Action, AddAssociation, AddressRules, CreateInbox, IdentityUpdate,
+    RevokeAssociation, SignatureError,
 };
 use futures::future::try_join_all;
 use xmtp_proto::xmtp::message_contents::SignedPublicKey as LegacySignedPublicKeyProto;
@@ -19,6 +19,7 @@ pub struct UnverifiedIdentityUpdate {
     pub inbox_id: String,
     pub client_timestamp_ns: u64,
     pub actions: Vec<UnverifiedAction>,
+    pub address_rules: AddressRules,
 }
 
 impl UnverifiedIdentityUpdate {
@@ -27,9 +28,15 @@ impl UnverifiedIdentityUpdate {
             inbox_id,
             client_timestamp_ns,
             actions,
+            address_rules: AddressRules::Legacy,
         }
     }
 
+    pub fn with_address_rules(mut self, address_rules: AddressRules) -> Self {
+        self.address_rules = address_rules;
+        self
+    }
+
     fn signature_text(&self) -> String {
         let unsigned_actions = self
             .actions
@@ -52,6 +59,14 @@ impl UnverifiedIdentityUpdate {
             .collect()
     }
 
+    /// Whether verifying the update needs a smart contract call. Other signatures are checked
+    /// locally, without calling the verifier.
+    pub fn has_smart_contract_wallet_signature(&self) -> bool {
+        self.signatures()
+            .iter()
+            .any(|signature| matches!(signature, UnverifiedSignature::SmartContractWallet(_)))
+    }
+
     pub async fn to_verified(
         &self,
         scw_verifier: impl SmartContractSignatureVerifier,
@@ -65,11 +80,10 @@ impl UnverifiedIdentityUpdate {
         )
         .await?;
 
-        Ok(IdentityUpdate::new(
-            actions,
-            self.inbox_id.clone(),
-            self.client_timestamp_ns,
-        ))
+        Ok(
+            IdentityUpdate::new(actions, self.inbox_id.clone(), self.client_timestamp_ns)
+                .with_address_rules(self.address_rules),
+        )
     }
 }
 
@@ -99,6 +113,38 @@ impl UnverifiedAction {
         }
     }
 
+    /// The addresses named by the action, and the accounts signing it with an
+    /// [`UnverifiedSignature::Ed25519Account`] signature
+    pub(crate) fn addresses(&self) -> Vec<String> {
+        let named = match self {
+            UnverifiedAction::CreateInbox(action) => {
+                Some(action.unsigned_action.account_address.clone())
+            }
+            UnverifiedAction::AddAssociation(action) => action
+                .unsigned_action
+                .new_member_identifier
+                .address()
+                .map(str::to_string),
+            UnverifiedAction::RevokeAssociation(action) => action
+                .unsigned_action
+                .revoked_member
+                .address()
+                .map(str::to_string),
+            UnverifiedAction::ChangeRecoveryAddress(action) => {
+                Some(action.unsigned_action.new_recovery_address.clone())
+            }
+        };
+        let signers = self
+            .signatures()
+            .into_iter()
+            .filter_map(|signature| match signature {
+                UnverifiedSignature::Ed25519Account(sig) => Some(sig.account_id),
+                _ => None,
+            });
+
+        named.into_iter().chain(signers).collect()
+    }
+
     fn signatures(&self) -> Vec<UnverifiedSignature> {
         match self {
             UnverifiedAction::CreateInbox(action) => vec![action.initial_address_signature.clone()],
@@ -244,6 +290,7 @@ pub enum UnverifiedSignature {
     RecoverableEcdsa(UnverifiedRecoverableEcdsaSignature),
     SmartContractWallet(UnverifiedSmartContractWalletSignature),
     LegacyDelegated(UnverifiedLegacyDelegatedSignature),
+    Ed25519Account(UnverifiedEd25519AccountSignature),
 }
 
 impl UnverifiedSignature {
@@ -276,6 +323,11 @@ impl UnverifiedSignature {
                 &sig.legacy_key_signature.signature_bytes,
                 sig.signed_public_key_proto.clone(),
             ),
+            UnverifiedSignature::Ed25519Account(sig) => VerifiedSignature::from_ed25519_account(
+                signature_text,
+                &sig.signature_bytes,
+                &sig.account_id,
+            ),
         }
     }
 
@@ -314,6 +366,12 @@ impl UnverifiedSignature {
             signed_public_key_proto,
         ))
     }
+
+    pub fn new_ed25519_account(signature: Vec<u8>, account_id: String) -> Self {
+        Self::Ed25519Account(UnverifiedEd25519AccountSignature::new(
+            signature, account_id,
+        ))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -404,6 +462,22 @@ impl UnverifiedLegacyDelegatedSignature {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnverifiedEd25519AccountSignature {
+    pub(crate) signature_bytes: Vec<u8>,
+    /// The CAIP-10 account id of the signer
+    pub(crate) account_id: String,
+}
+
+impl UnverifiedEd25519AccountSignature {
+    pub fn new(signature_bytes: Vec<u8>, account_id: String) -> Self {
+        Self {
+            signature_bytes,
+            account_id,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(target_arch = "wasm32")]
@@ -413,7 +487,7 @@ mod tests {
     use xmtp_common::rand_hexstring;
 
     use super::{
-        UnverifiedAction, UnverifiedCreateInbox, UnverifiedIdentityUpdate,
+        AddressRules, UnverifiedAction, UnverifiedCreateInbox, UnverifiedIdentityUpdate,
         UnverifiedRecoverableEcdsaSignature, UnverifiedSignature,
     };
 
@@ -436,6 +510,7 @@ mod tests {
                     },
                 ),
             })],
+            address_rules: AddressRules::Legacy,
         };
         assert!(
             update
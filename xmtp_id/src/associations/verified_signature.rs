@@ -2,6 +2,8 @@
 use ethers::types::Signature as EthersSignature;
 use ethers::utils::hash_message;
 use ethers::{core::k256::ecdsa::VerifyingKey as EcdsaVerifyingKey, utils::public_key_to_address};
+use std::str::FromStr;
+use xmtp_cryptography::account_id::AccountId as Caip10AccountId;
 use xmtp_cryptography::signature::h160addr_to_string;
 use xmtp_cryptography::CredentialVerify;
 use xmtp_proto::xmtp::message_contents::SignedPublicKey as LegacySignedPublicKeyProto;
@@ -13,6 +15,8 @@ use super::{
     ValidatedLegacySignedPublicKey,
 };
 
+const SOLANA_NAMESPACE: &str = "solana";
+
 #[derive(Debug, Clone)]
 pub struct VerifiedSignature {
     pub signer: MemberIdentifier,
@@ -128,6 +132,34 @@ impl VerifiedSignature {
         ))
     }
 
+    /// Verifies an Ed25519 signature of the non-EVM account `account_id`, a CAIP-10 account id
+    /// whose address is the public key of the account. Only Solana accounts, whose addresses are
+    /// base58 encoded, are supported.
+    pub fn from_ed25519_account<Text: AsRef<str>>(
+        signature_text: Text,
+        signature_bytes: &[u8],
+        account_id: &str,
+    ) -> Result<Self, SignatureError> {
+        let account_id = Caip10AccountId::from_str(account_id)?;
+        if account_id.namespace != SOLANA_NAMESPACE {
+            return Err(SignatureError::UnsupportedAccountNamespace(
+                account_id.namespace,
+            ));
+        }
+        let public_key = bs58::decode(&account_id.address).into_vec()?;
+        let verifying_key =
+            ed25519_dalek::VerifyingKey::from_bytes(public_key.as_slice().try_into()?)?;
+        let signature = ed25519_dalek::Signature::from_slice(signature_bytes)?;
+        verifying_key.verify_strict(signature_text.as_ref().as_bytes(), &signature)?;
+
+        Ok(Self::new(
+            MemberIdentifier::Address(account_id.to_string()),
+            SignatureKind::Ed25519Account,
+            signature_bytes.to_vec(),
+            None,
+        ))
+    }
+
     /// Verifies a smart contract wallet signature using the provided signature verifier.
     pub async fn from_smart_contract_wallet<Text: AsRef<str>>(
         signature_text: Text,
@@ -254,6 +286,47 @@ mod tests {
         .expect_err("should fail with incorrect verifying key");
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_ed25519_account() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&xmtp_common::rand_array::<32>());
+        let address = bs58::encode(key.verifying_key().as_bytes()).into_string();
+        let account_id = format!("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp:{address}");
+        let signature_text = "test signature text";
+        let sig = ed25519_dalek::Signer::sign(&key, signature_text.as_bytes()).to_bytes();
+
+        let verified_sig =
+            VerifiedSignature::from_ed25519_account(signature_text, &sig, &account_id)
+                .expect("should succeed");
+        assert_eq!(
+            verified_sig.signer,
+            MemberIdentifier::Address(account_id.clone())
+        );
+        assert_eq!(verified_sig.kind, SignatureKind::Ed25519Account);
+        assert_eq!(verified_sig.raw_bytes, sig);
+
+        VerifiedSignature::from_ed25519_account("wrong signature text", &sig, &account_id)
+            .expect_err("should fail with incorrect signature text");
+
+        let other_address = bs58::encode(xmtp_common::rand_array::<32>()).into_string();
+        VerifiedSignature::from_ed25519_account(
+            signature_text,
+            &sig,
+            &format!("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp:{other_address}"),
+        )
+        .expect_err("should fail with another account");
+
+        let result = VerifiedSignature::from_ed25519_account(
+            signature_text,
+            &sig,
+            &format!("cosmos:cosmoshub-4:{address}"),
+        );
+        assert!(matches!(
+            result,
+            Err(SignatureError::UnsupportedAccountNamespace(_))
+        ));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     fn validate_good_key_round_trip() {
@@ -211,3 +211,24 @@ impl SmartContractSignatureVerifier for MultiSmartContractSignatureVerifier {
         )))
     }
 }
+
+/// Verifies updates without smart contract wallet signatures, which never reach a verifier.
+/// Needs no network, so verification can run on any thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OfflineSignatureVerifier;
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl SmartContractSignatureVerifier for OfflineSignatureVerifier {
+    async fn is_valid_signature(
+        &self,
+        _account_id: AccountId,
+        _hash: [u8; 32],
+        _signature: Bytes,
+        _block_number: Option<BlockNumber>,
+    ) -> Result<ValidationResponse, VerifierError> {
+        Err(VerifierError::Provider(ProviderError::CustomError(
+            "Smart contract wallets are not verified offline".to_string(),
+        )))
+    }
+}
@@ -0,0 +1,69 @@
+//! Benchmarks for listing conversations, over a database of 10k groups and 1M messages.
+//!
+//! Fails if listing the conversations takes longer than [`LIST_BUDGET`], so that a regression
+//! of the `conversation_list` view is caught before the numbers are compared.
+//! CI runs it with `-- --test`, which checks the budget without taking samples.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+use xmtp_common::bench;
+use xmtp_mls::{
+    storage::{group::GroupQueryArgs, DbConnection, EncryptedMessageStore, StorageOption},
+    utils::bench::populate_conversations,
+};
+
+pub const NUM_GROUPS: usize = 10_000;
+pub const MESSAGES_PER_GROUP: usize = 100;
+pub const LIST_BUDGET: Duration = Duration::from_millis(50);
+pub const SAMPLE_SIZE: usize = 10;
+
+fn setup() -> (DbConnection, Runtime) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let store = runtime.block_on(async {
+        EncryptedMessageStore::new_unencrypted(StorageOption::Ephemeral)
+            .await
+            .unwrap()
+    });
+    let conn = store.conn().unwrap();
+    populate_conversations(&conn, NUM_GROUPS, MESSAGES_PER_GROUP).unwrap();
+    (conn, runtime)
+}
+
+fn list_conversations(c: &mut Criterion) {
+    bench::logger();
+    let mut benchmark_group = c.benchmark_group("list_conversations");
+    benchmark_group.sample_size(SAMPLE_SIZE);
+
+    let (conn, _runtime) = setup();
+
+    let cases = [
+        ("all", GroupQueryArgs::default()),
+        ("limit_20", GroupQueryArgs::default().limit(20)),
+    ];
+    for (name, args) in cases.iter() {
+        // The first query warms the page cache, the second is the one held to the budget
+        conn.fetch_conversation_list(args).unwrap();
+        let start = Instant::now();
+        let conversations = conn.fetch_conversation_list(args).unwrap();
+        let elapsed = start.elapsed();
+        assert!(!conversations.is_empty());
+        assert!(
+            elapsed < LIST_BUDGET,
+            "listing conversations ({name}) took {elapsed:?}, over the {LIST_BUDGET:?} budget"
+        );
+
+        benchmark_group.throughput(Throughput::Elements(NUM_GROUPS as u64));
+        benchmark_group.bench_with_input(BenchmarkId::from_parameter(name), args, |b, args| {
+            b.iter(|| conn.fetch_conversation_list(args).unwrap())
+        });
+    }
+
+    benchmark_group.finish();
+}
+
+criterion_group!(
+    name = conversation_list;
+    config = Criterion::default().sample_size(SAMPLE_SIZE);
+    targets = list_conversations
+);
+criterion_main!(conversation_list);
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xmtp_content_types::{
+    app_metadata::{app_metadata, validate_app_metadata},
+    bytes_to_encoded_content,
+    group_updated::GroupUpdatedCodec,
+    location::LocationCodec,
+    membership_change::GroupMembershipChangeCodec,
+    poll::{PollCodec, PollVoteCodec},
+    reaction::{LegacyReaction, ReactionCodec},
+    text::TextCodec,
+    transaction_reference::TransactionReferenceCodec,
+    ContentCodec,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(content) = bytes_to_encoded_content(data.to_vec()) else {
+        return;
+    };
+    let _ = validate_app_metadata(&content);
+    let _ = app_metadata(&content);
+    let _ = LegacyReaction::decode(&content.content);
+    let _ = TextCodec::decode(content.clone());
+    let _ = ReactionCodec::decode(content.clone());
+    let _ = GroupUpdatedCodec::decode(content.clone());
+    let _ = GroupMembershipChangeCodec::decode(content.clone());
+    let _ = LocationCodec::decode(content.clone());
+    let _ = PollCodec::decode(content.clone());
+    let _ = PollVoteCodec::decode(content.clone());
+    let _ = TransactionReferenceCodec::decode(content);
+});
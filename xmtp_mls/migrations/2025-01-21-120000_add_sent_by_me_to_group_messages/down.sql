@@ -0,0 +1 @@
+ALTER TABLE group_messages DROP COLUMN sent_by_me;
@@ -0,0 +1 @@
+ALTER TABLE group_messages ADD COLUMN sent_by_me BOOLEAN NOT NULL DEFAULT 0;
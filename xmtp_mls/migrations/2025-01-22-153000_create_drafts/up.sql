@@ -0,0 +1,10 @@
+-- Partially composed messages, at most one per conversation
+CREATE TABLE drafts(
+    -- The conversation the draft belongs to
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    -- The encoded content of the draft
+    "encoded_content" BLOB NOT NULL,
+    -- Time in nanoseconds the draft was last saved
+    "updated_at_ns" bigint NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
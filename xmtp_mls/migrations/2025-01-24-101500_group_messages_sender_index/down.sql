@@ -0,0 +1 @@
+DROP INDEX group_messages_group_id_sender_sort_idx;
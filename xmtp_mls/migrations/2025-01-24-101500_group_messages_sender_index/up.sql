@@ -0,0 +1 @@
+CREATE INDEX group_messages_group_id_sender_sort_idx ON group_messages(group_id, sender_inbox_id, sent_at_ns);
@@ -0,0 +1,12 @@
+-- Installations this installation sent a welcome to, that have not been seen in the group yet
+CREATE TABLE pending_welcomes(
+    "group_id" BLOB NOT NULL,
+    -- The installation the welcome was sent to
+    "installation_id" BLOB NOT NULL,
+    -- Time in nanoseconds the latest welcome was sent
+    "sent_at_ns" bigint NOT NULL,
+    -- Number of times the installation was re-added with a fresh key package
+    "readd_attempts" integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (group_id, installation_id),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
@@ -0,0 +1,8 @@
+-- When each installation was last seen doing something
+CREATE TABLE installation_activity(
+    "installation_id" BLOB PRIMARY KEY NOT NULL,
+    -- Time in nanoseconds the installation was last seen sending a message or rotating its key package
+    "last_active_ns" bigint NOT NULL,
+    -- Hash of the last key package fetched for the installation, to detect rotations
+    "key_package_hash" BLOB
+);
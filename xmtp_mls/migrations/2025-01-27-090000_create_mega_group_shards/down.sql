@@ -0,0 +1 @@
+DROP TABLE mega_group_shards;
@@ -0,0 +1,14 @@
+-- Groups that make up a mega group created by this installation
+CREATE TABLE mega_group_shards(
+    -- The shard
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    -- Id of the first shard of the mega group
+    "mega_group_id" BLOB NOT NULL,
+    -- Position of the shard in the mega group, starting at 0
+    "shard_index" integer NOT NULL,
+    -- Sent time of the last message of the shard relayed to the other shards
+    "relayed_until_ns" BIGINT NOT NULL DEFAULT 0,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+CREATE UNIQUE INDEX mega_group_shards_mega_group_id_idx ON mega_group_shards(mega_group_id, shard_index);
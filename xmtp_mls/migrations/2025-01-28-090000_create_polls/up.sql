@@ -0,0 +1,24 @@
+CREATE TABLE polls(
+    -- Id of the message that created the poll
+    "id" BLOB PRIMARY KEY NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "creator_inbox_id" TEXT NOT NULL,
+    "question" TEXT NOT NULL,
+    -- JSON array of the answers that can be voted for
+    "options" TEXT NOT NULL,
+    "allow_multiple_choices" BOOLEAN NOT NULL,
+    "created_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+-- The latest vote of each inbox on a poll
+CREATE TABLE poll_votes(
+    "poll_id" BLOB NOT NULL,
+    "voter_inbox_id" TEXT NOT NULL,
+    -- Id of the message the vote was cast in
+    "message_id" BLOB NOT NULL,
+    "voted_at_ns" BIGINT NOT NULL,
+    -- JSON array of the indexes of the options voted for
+    "option_indexes" TEXT NOT NULL,
+    PRIMARY KEY (poll_id, voter_inbox_id)
+);
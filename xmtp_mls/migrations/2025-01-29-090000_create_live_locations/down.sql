@@ -0,0 +1,2 @@
+DROP INDEX idx_live_locations_expires_at_ns;
+DROP TABLE live_locations;
@@ -0,0 +1,18 @@
+-- The latest position of each sender that is sharing their live location in a group
+CREATE TABLE live_locations(
+    "group_id" BLOB NOT NULL,
+    "sender_inbox_id" TEXT NOT NULL,
+    -- Id of the message that started the live share
+    "share_id" BLOB NOT NULL,
+    -- Id of the message with the latest position
+    "message_id" BLOB NOT NULL,
+    "latitude" DOUBLE NOT NULL,
+    "longitude" DOUBLE NOT NULL,
+    "accuracy_meters" DOUBLE NOT NULL,
+    "updated_at_ns" BIGINT NOT NULL,
+    "expires_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (group_id, sender_inbox_id),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+CREATE INDEX idx_live_locations_expires_at_ns ON live_locations(expires_at_ns);
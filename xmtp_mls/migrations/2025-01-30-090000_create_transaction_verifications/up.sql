@@ -0,0 +1,9 @@
+-- Cached on-chain status of transaction reference messages
+CREATE TABLE transaction_verifications(
+    -- Id of the transaction reference message
+    "message_id" BLOB PRIMARY KEY NOT NULL,
+    "network_id" TEXT NOT NULL,
+    "reference" TEXT NOT NULL,
+    "status" INTEGER NOT NULL,
+    "checked_at_ns" BIGINT NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Append-only log of the installation keys observed for each inbox
+CREATE TABLE installation_key_log(
+    "inbox_id" TEXT NOT NULL,
+    "installation_id" BLOB NOT NULL,
+    "first_seen_ns" BIGINT NOT NULL,
+    "last_seen_ns" BIGINT NOT NULL,
+    -- Set once the installation is no longer part of the inbox
+    "revoked_at_ns" BIGINT,
+    -- Whether the user accepted this key, or the latest change to it
+    "acknowledged" BOOLEAN NOT NULL,
+    PRIMARY KEY (inbox_id, installation_id)
+);
@@ -0,0 +1,9 @@
+-- Peers the user verified out of band, and the code they compared
+CREATE TABLE conversation_verifications(
+    "group_id" BLOB NOT NULL,
+    "peer_inbox_id" TEXT NOT NULL,
+    "verification_code" TEXT NOT NULL,
+    "verified_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (group_id, peer_inbox_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id)
+);
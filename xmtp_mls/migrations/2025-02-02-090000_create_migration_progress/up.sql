@@ -0,0 +1,7 @@
+-- Progress of the migrations that backfill data in batches after startup
+CREATE TABLE migration_progress (
+    name TEXT PRIMARY KEY NOT NULL,
+    -- Rowid of the last row migrated
+    cursor BIGINT NOT NULL DEFAULT 0,
+    completed_at_ns BIGINT
+);
@@ -0,0 +1,2 @@
+DROP INDEX groups_expires_at_ns_idx;
+ALTER TABLE groups DROP COLUMN expires_at_ns;
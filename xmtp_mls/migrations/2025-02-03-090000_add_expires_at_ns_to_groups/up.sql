@@ -0,0 +1,3 @@
+-- Time in NS after which the group stops accepting messages and its history is purged
+ALTER TABLE groups ADD COLUMN expires_at_ns BIGINT;
+CREATE INDEX groups_expires_at_ns_idx ON groups(expires_at_ns) WHERE expires_at_ns IS NOT NULL;
@@ -0,0 +1,15 @@
+-- The latest reaction of each inbox with each content on a message
+CREATE TABLE message_reactions(
+    -- Id of the message reacted to
+    "reference_id" BLOB NOT NULL,
+    "reactor_inbox_id" TEXT NOT NULL,
+    "content" TEXT NOT NULL,
+    "group_id" BLOB NOT NULL,
+    -- Id of the message of the latest reaction
+    "message_id" BLOB NOT NULL,
+    "reacted_at_ns" BIGINT NOT NULL,
+    -- Whether the latest reaction removed the earlier ones
+    "removed" BOOLEAN NOT NULL,
+    PRIMARY KEY (reference_id, reactor_inbox_id, content),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
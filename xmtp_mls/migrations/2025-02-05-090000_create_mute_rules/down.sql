@@ -0,0 +1,2 @@
+DROP TABLE filtered_messages;
+DROP TABLE mute_rules;
@@ -0,0 +1,19 @@
+-- Keyword and regex rules that filter incoming messages
+CREATE TABLE mute_rules(
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    "pattern" TEXT NOT NULL,
+    "is_regex" BOOLEAN NOT NULL,
+    -- The conversation the rule applies to, or all conversations when NULL
+    "group_id" BLOB,
+    "created_at_ns" BIGINT NOT NULL
+);
+
+-- Incoming messages that matched a mute rule when they were stored
+CREATE TABLE filtered_messages(
+    "message_id" BLOB PRIMARY KEY NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "rule_id" INTEGER NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+CREATE INDEX filtered_messages_group_id ON filtered_messages(group_id);
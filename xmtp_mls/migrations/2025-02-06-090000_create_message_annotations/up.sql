@@ -0,0 +1,9 @@
+-- Metadata computed for incoming messages by the message enrichers of the client
+CREATE TABLE message_annotations(
+    "message_id" BLOB NOT NULL,
+    "key" TEXT NOT NULL,
+    "value" TEXT NOT NULL,
+    "group_id" BLOB NOT NULL,
+    PRIMARY KEY (message_id, key),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS avatar_blobs_evict_on_update;
+DROP TRIGGER IF EXISTS avatar_blobs_evict_on_delete;
+DROP TRIGGER IF EXISTS group_avatars_group_deleted;
+DROP TABLE IF EXISTS group_avatars;
+DROP TABLE IF EXISTS avatar_blobs;
@@ -0,0 +1,39 @@
+-- Images referenced by the image url of a group, stored once per digest
+CREATE TABLE avatar_blobs(
+    "digest" BLOB PRIMARY KEY NOT NULL,
+    "data" BLOB NOT NULL
+);
+
+-- The image url of each group, and the digest of its image once fetched
+CREATE TABLE group_avatars(
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    "url" TEXT NOT NULL,
+    "digest" BLOB,
+    "updated_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+CREATE INDEX group_avatars_digest ON group_avatars(digest);
+
+-- Forget the avatar of deleted groups
+CREATE TRIGGER group_avatars_group_deleted AFTER DELETE ON "groups"
+BEGIN
+    DELETE FROM group_avatars WHERE group_id = OLD.id;
+END;
+
+-- Evict images no group uses anymore
+CREATE TRIGGER avatar_blobs_evict_on_delete AFTER DELETE ON group_avatars
+WHEN OLD.digest IS NOT NULL
+BEGIN
+    DELETE FROM avatar_blobs
+    WHERE digest = OLD.digest
+    AND NOT EXISTS (SELECT 1 FROM group_avatars WHERE digest = OLD.digest);
+END;
+
+CREATE TRIGGER avatar_blobs_evict_on_update AFTER UPDATE OF digest ON group_avatars
+WHEN OLD.digest IS NOT NULL
+BEGIN
+    DELETE FROM avatar_blobs
+    WHERE digest = OLD.digest
+    AND NOT EXISTS (SELECT 1 FROM group_avatars WHERE digest = OLD.digest);
+END;
@@ -0,0 +1,3 @@
+DROP INDEX groups_left_at_ns_idx;
+ALTER TABLE groups DROP COLUMN left_at_ns;
+ALTER TABLE groups DROP COLUMN lifecycle_state;
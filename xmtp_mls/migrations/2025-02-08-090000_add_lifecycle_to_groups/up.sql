@@ -0,0 +1,5 @@
+-- Where the group is in its lifecycle once this installation leaves it, see `GroupLifecycleState`
+ALTER TABLE groups ADD COLUMN lifecycle_state INTEGER NOT NULL DEFAULT 1;
+-- Time in NS at which this installation was removed from the group
+ALTER TABLE groups ADD COLUMN left_at_ns BIGINT;
+CREATE INDEX groups_left_at_ns_idx ON groups(left_at_ns) WHERE left_at_ns IS NOT NULL;
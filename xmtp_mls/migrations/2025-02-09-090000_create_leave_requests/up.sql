@@ -0,0 +1,9 @@
+-- Members that asked to leave a group, until a member with the permission removes them
+CREATE TABLE leave_requests(
+    "group_id" BLOB NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    -- Time in nanoseconds the leave request was sent
+    "requested_at_ns" bigint NOT NULL,
+    PRIMARY KEY (group_id, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
@@ -0,0 +1,13 @@
+-- Inboxes that asked to join a discoverable group, until an admin approves or rejects them
+CREATE TABLE join_requests(
+    "group_id" BLOB NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    -- Shown to the admins with the request
+    "message" TEXT NOT NULL,
+    -- Time in nanoseconds the join request was sent
+    "requested_at_ns" bigint NOT NULL,
+    -- Enum, pending = 1, approved = 2, rejected = 3
+    "state" INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (group_id, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
@@ -0,0 +1,9 @@
+-- Local copy of the pinned messages of each group, kept in sync with the group metadata
+CREATE TABLE pinned_messages(
+    "group_id" BLOB NOT NULL,
+    "message_id" BLOB NOT NULL,
+    -- Order of the pin in the group, oldest pin first
+    "position" INTEGER NOT NULL,
+    PRIMARY KEY (group_id, message_id),
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
@@ -0,0 +1,2 @@
+DROP INDEX consent_records_expires_at_ns_idx;
+ALTER TABLE consent_records DROP COLUMN expires_at_ns;
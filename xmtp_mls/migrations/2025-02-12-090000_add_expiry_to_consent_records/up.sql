@@ -0,0 +1,3 @@
+-- Time in NS after which the consent record reverts to `Unknown`, or NULL if it never expires
+ALTER TABLE consent_records ADD COLUMN expires_at_ns BIGINT;
+CREATE INDEX consent_records_expires_at_ns_idx ON consent_records(expires_at_ns) WHERE expires_at_ns IS NOT NULL;
@@ -0,0 +1,2 @@
+-- JSON-encoded `ConsentPolicy`, or NULL for the default policy
+ALTER TABLE user_preferences ADD COLUMN consent_policy TEXT;
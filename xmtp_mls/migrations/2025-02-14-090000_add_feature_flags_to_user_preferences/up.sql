@@ -0,0 +1,2 @@
+-- JSON-encoded map of feature flag names to their `FeatureFlag`, or NULL if none was set
+ALTER TABLE user_preferences ADD COLUMN feature_flags TEXT;
@@ -0,0 +1 @@
+DROP TABLE history_sync_log;
@@ -0,0 +1,14 @@
+-- Sync requests from other installations that this installation answered or refused
+CREATE TABLE history_sync_log(
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    "request_id" TEXT NOT NULL,
+    "requester_inbox_id" TEXT NOT NULL,
+    "requester_installation_id" BLOB NOT NULL,
+    -- The `DeviceSyncKind` requested
+    "kind" INTEGER NOT NULL,
+    -- The `HistorySyncOutcome`
+    "outcome" INTEGER NOT NULL,
+    "created_at_ns" BIGINT NOT NULL
+);
+
+CREATE INDEX history_sync_log_requester ON history_sync_log(requester_installation_id, created_at_ns);
@@ -0,0 +1 @@
+DROP TRIGGER IF EXISTS group_intents_state_guard;
@@ -0,0 +1,32 @@
+-- Only the transitions of `IntentTransition` may change the state of an intent:
+-- ToPublish -> Published (Publish), Published -> Committed (Commit),
+-- Published -> ToPublish (Republish) and any state -> Error (Fail).
+--
+-- Published -> ToPublish is allowed on purpose: an intent whose commit was published in an
+-- epoch the group has since left, or whose commit could not be merged, is sent again. Republish
+-- also clears what was published, so a ToPublish intent never carries a payload hash.
+
+-- Repair the intents already in states no sequence of transitions leads to, before the guard
+-- refuses to touch them. Unknown states can't be completed.
+UPDATE group_intents SET state = 4 WHERE state NOT IN (1, 2, 3, 4);
+-- A ToPublish intent keeping what it was published with was moved back without Republish
+UPDATE group_intents
+SET payload_hash = NULL, post_commit_data = NULL, published_in_epoch = NULL, staged_commit = NULL
+WHERE state = 1
+    AND (payload_hash IS NOT NULL OR published_in_epoch IS NOT NULL OR staged_commit IS NOT NULL);
+-- A Published intent without a payload hash can't be matched with its message, send it again
+UPDATE group_intents
+SET state = 1, post_commit_data = NULL, published_in_epoch = NULL, staged_commit = NULL
+WHERE state = 2 AND payload_hash IS NULL;
+
+CREATE TRIGGER group_intents_state_guard BEFORE UPDATE OF state ON group_intents
+FOR EACH ROW
+WHEN NOT (
+    NEW.state = OLD.state
+    OR NEW.state = 4
+    OR (OLD.state = 1 AND NEW.state = 2)
+    OR (OLD.state = 2 AND NEW.state IN (1, 3))
+)
+BEGIN
+    SELECT RAISE(ABORT, 'illegal intent state transition');
+END;
@@ -0,0 +1,23 @@
+-- Actions to take once the commit of an intent is merged, e.g. sending the welcomes of an add.
+-- They are queued when the intent is committed and kept once completed, so that an action that
+-- was interrupted is resumed, and a completed one is never taken twice.
+CREATE TABLE post_commit_actions(
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "intent_id" INTEGER NOT NULL,
+    -- A serialized `PostCommitAction`
+    "action" BLOB NOT NULL,
+    "attempts" INTEGER NOT NULL DEFAULT 0,
+    "created_at_ns" BIGINT NOT NULL,
+    "completed_at_ns" BIGINT,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX post_commit_actions_pending ON post_commit_actions(group_id)
+    WHERE completed_at_ns IS NULL;
+
+-- Intents committed before this migration still carry their action
+INSERT INTO post_commit_actions (group_id, intent_id, action, created_at_ns)
+SELECT group_id, id, post_commit_data, 0
+FROM group_intents
+WHERE state = 3 AND post_commit_data IS NOT NULL;
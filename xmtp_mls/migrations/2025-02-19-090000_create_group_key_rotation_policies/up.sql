@@ -0,0 +1,8 @@
+-- How long this installation's leaf key in a group may go without rotation, for groups that do
+-- not use the interval the client is configured with
+CREATE TABLE group_key_rotation_policies(
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    "interval_ns" BIGINT NOT NULL,
+    "updated_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
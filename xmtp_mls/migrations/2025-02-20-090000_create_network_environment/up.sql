@@ -0,0 +1,6 @@
+-- The network environment the database was first used with. A single row.
+CREATE TABLE network_environment(
+    "id" INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    "environment" TEXT NOT NULL,
+    "recorded_at_ns" BIGINT NOT NULL
+);
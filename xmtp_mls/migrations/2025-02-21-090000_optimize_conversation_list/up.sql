@@ -0,0 +1,63 @@
+-- The latest readable message of every group, read from the index alone. The WHERE clause must
+-- stay identical to the one of the `conversation_list` view for SQLite to use the index.
+CREATE INDEX group_messages_readable_latest_idx ON group_messages(group_id, sent_at_ns DESC, id)
+    WHERE kind = 1 AND content_type IN (1, 4, 6, 7, 8, 9);
+
+DROP VIEW IF EXISTS conversation_list;
+
+-- The previous version ranked every readable message of every group with a window function
+-- before joining, which scanned the whole table. Looking up the latest message per group is a
+-- single index seek, so the cost grows with the number of groups only.
+CREATE VIEW conversation_list AS
+/* Filtering for readable content types only or
+content types with a text fallback
+
+Content Types numeric values come from xmtp_mls/src/storage/encrypted_store/group_message.rs
+pub enum ContentType {
+    Unknown = 0,
+    Text = 1,
+    GroupMembershipChange = 2,
+    GroupUpdated = 3,
+    Reaction = 4,
+    ReadReceipt = 5,
+    Reply = 6,
+    Attachment = 7,
+    RemoteAttachment = 8,
+    TransactionReference = 9,
+}*/
+SELECT
+    g.id AS id,
+    g.created_at_ns,
+    g.membership_state,
+    g.installations_last_checked,
+    g.added_by_inbox_id,
+    g.welcome_id,
+    g.dm_id,
+    g.rotated_at_ns,
+    g.conversation_type,
+    gm.id AS message_id,
+    gm.decrypted_message_bytes,
+    gm.sent_at_ns,
+    gm.kind AS message_kind,
+    gm.sender_installation_id,
+    gm.sender_inbox_id,
+    gm.delivery_status,
+    gm.content_type,
+    gm.version_major,
+    gm.version_minor,
+    gm.authority_id,
+    gm.sender_verified
+FROM
+    groups g
+    LEFT JOIN group_messages gm
+    ON gm.id = (
+        SELECT latest.id
+        FROM group_messages latest
+        WHERE
+            latest.group_id = g.id
+            AND latest.kind = 1
+            AND latest.content_type IN (1, 4, 6, 7, 8, 9)
+        ORDER BY latest.sent_at_ns DESC
+        LIMIT 1
+    )
+ORDER BY COALESCE(gm.sent_at_ns, g.created_at_ns) DESC;
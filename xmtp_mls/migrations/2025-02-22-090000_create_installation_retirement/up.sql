@@ -0,0 +1,5 @@
+-- Set once the MLS state of this installation was exported to another device. A single row.
+CREATE TABLE installation_retirement(
+    "id" INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    "retired_at_ns" BIGINT NOT NULL
+);
@@ -0,0 +1,23 @@
+-- Votes that arrived before their poll, kept until the poll arrives
+CREATE TABLE orphan_poll_votes(
+    -- Id of the message the vote was cast in
+    "message_id" BLOB PRIMARY KEY NOT NULL,
+    "poll_id" BLOB NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "voter_inbox_id" TEXT NOT NULL,
+    "voted_at_ns" BIGINT NOT NULL,
+    -- JSON array of the indexes of the options voted for
+    "option_indexes" TEXT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+
+CREATE INDEX orphan_poll_votes_poll_id ON orphan_poll_votes(poll_id);
+
+-- Votes recorded for polls that never arrived were counted without being validated
+INSERT OR IGNORE INTO orphan_poll_votes
+SELECT v.message_id, v.poll_id, m.group_id, v.voter_inbox_id, v.voted_at_ns, v.option_indexes
+FROM poll_votes v
+JOIN group_messages m ON m.id = v.message_id
+WHERE v.poll_id NOT IN (SELECT id FROM polls);
+
+DELETE FROM poll_votes WHERE poll_id NOT IN (SELECT id FROM polls);
@@ -0,0 +1,8 @@
+ALTER TABLE group_avatars
+    DROP COLUMN failed_attempts;
+
+ALTER TABLE group_avatars
+    DROP COLUMN retry_at_ns;
+
+ALTER TABLE group_avatars
+    DROP COLUMN skipped;
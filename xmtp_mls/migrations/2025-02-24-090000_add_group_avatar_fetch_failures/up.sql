@@ -0,0 +1,10 @@
+-- Failed fetches are retried with an exponential backoff
+ALTER TABLE group_avatars
+    ADD COLUMN "failed_attempts" INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE group_avatars
+    ADD COLUMN "retry_at_ns" BIGINT NOT NULL DEFAULT 0;
+
+-- Set when the image is larger than the client stores, it is not fetched again until the url changes
+ALTER TABLE group_avatars
+    ADD COLUMN "skipped" BOOLEAN NOT NULL DEFAULT FALSE;
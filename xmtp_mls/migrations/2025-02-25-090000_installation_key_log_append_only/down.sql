@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS installation_key_log_no_rewrite;
+
+DROP TRIGGER IF EXISTS installation_key_log_no_delete;
+
+ALTER TABLE installation_key_log
+    DROP COLUMN reappeared_at_ns;
@@ -0,0 +1,23 @@
+-- Set when a revoked installation is seen again after it was revoked
+ALTER TABLE installation_key_log
+    ADD COLUMN "reappeared_at_ns" BIGINT;
+
+-- Observations are only ever added: rows are never deleted, the binding of a key to its inbox and
+-- the time it was first seen never change, and the other times only move forward
+CREATE TRIGGER installation_key_log_no_delete BEFORE DELETE ON installation_key_log
+BEGIN
+    SELECT RAISE(ABORT, 'installation_key_log is append-only');
+END;
+
+CREATE TRIGGER installation_key_log_no_rewrite BEFORE UPDATE ON installation_key_log
+WHEN NEW.inbox_id IS NOT OLD.inbox_id
+    OR NEW.installation_id IS NOT OLD.installation_id
+    OR NEW.first_seen_ns IS NOT OLD.first_seen_ns
+    OR NEW.last_seen_ns < OLD.last_seen_ns
+    OR (OLD.revoked_at_ns IS NOT NULL
+        AND (NEW.revoked_at_ns IS NULL OR NEW.revoked_at_ns < OLD.revoked_at_ns))
+    OR (OLD.reappeared_at_ns IS NOT NULL
+        AND (NEW.reappeared_at_ns IS NULL OR NEW.reappeared_at_ns < OLD.reappeared_at_ns))
+BEGIN
+    SELECT RAISE(ABORT, 'installation_key_log is append-only');
+END;
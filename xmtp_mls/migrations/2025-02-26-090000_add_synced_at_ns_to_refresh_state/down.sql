@@ -0,0 +1,2 @@
+ALTER TABLE refresh_state
+    DROP COLUMN synced_at_ns;
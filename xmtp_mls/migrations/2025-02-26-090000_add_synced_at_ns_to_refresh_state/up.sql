@@ -0,0 +1,3 @@
+-- Time the entity was last synced with the network, whether or not anything new was received
+ALTER TABLE refresh_state
+    ADD COLUMN "synced_at_ns" BIGINT;
@@ -0,0 +1,21 @@
+CREATE TABLE mute_rules(
+    "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    "pattern" TEXT NOT NULL,
+    "is_regex" BOOLEAN NOT NULL,
+    "group_id" BLOB,
+    "created_at_ns" BIGINT NOT NULL
+);
+
+CREATE TABLE filtered_messages_old(
+    "message_id" BLOB PRIMARY KEY NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "rule_id" INTEGER NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+INSERT INTO filtered_messages_old (message_id, group_id, rule_id)
+SELECT message_id, group_id, 0 FROM filtered_messages;
+DROP TABLE filtered_messages;
+ALTER TABLE filtered_messages_old RENAME TO filtered_messages;
+CREATE INDEX filtered_messages_group_id ON filtered_messages(group_id);
+
+ALTER TABLE user_preferences DROP COLUMN mute_rules;
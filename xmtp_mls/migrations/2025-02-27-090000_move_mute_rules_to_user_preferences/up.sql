@@ -0,0 +1,33 @@
+-- Mute rules are kept in the user preferences, as a JSON-encoded list of `MuteRule`, so that
+-- they sync with the other preferences
+ALTER TABLE user_preferences ADD COLUMN mute_rules TEXT;
+
+INSERT INTO user_preferences (hmac_key, consent_policy, feature_flags, mute_rules)
+SELECT
+    (SELECT hmac_key FROM user_preferences ORDER BY id DESC LIMIT 1),
+    (SELECT consent_policy FROM user_preferences ORDER BY id DESC LIMIT 1),
+    (SELECT feature_flags FROM user_preferences ORDER BY id DESC LIMIT 1),
+    json_group_array(json_object(
+        'id', CAST(id AS TEXT),
+        'pattern', pattern,
+        'is_regex', json(CASE WHEN is_regex THEN 'true' ELSE 'false' END),
+        'group_id', lower(hex(group_id)),
+        'created_at_ns', created_at_ns
+    ))
+FROM (SELECT * FROM mute_rules ORDER BY id)
+HAVING count(*) > 0;
+
+-- Rule ids are now strings
+CREATE TABLE filtered_messages_new(
+    "message_id" BLOB PRIMARY KEY NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "rule_id" TEXT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES "groups"(id)
+);
+INSERT INTO filtered_messages_new (message_id, group_id, rule_id)
+SELECT message_id, group_id, CAST(rule_id AS TEXT) FROM filtered_messages;
+DROP TABLE filtered_messages;
+ALTER TABLE filtered_messages_new RENAME TO filtered_messages;
+CREATE INDEX filtered_messages_group_id ON filtered_messages(group_id);
+
+DROP TABLE mute_rules;
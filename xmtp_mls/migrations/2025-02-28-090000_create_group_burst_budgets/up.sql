@@ -0,0 +1,7 @@
+-- How many messages of a group the message streams deliver per window, see `BurstBudget`
+CREATE TABLE group_burst_budgets(
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    "max_messages" INTEGER NOT NULL,
+    "window_ms" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
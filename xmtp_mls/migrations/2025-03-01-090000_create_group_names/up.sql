@@ -0,0 +1,7 @@
+-- The names of groups, copied from their mutable metadata so that groups are sorted by name
+-- without loading their MLS state
+CREATE TABLE group_names(
+    "group_id" BLOB PRIMARY KEY NOT NULL,
+    "name" TEXT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+DROP TABLE dm_public_ids;
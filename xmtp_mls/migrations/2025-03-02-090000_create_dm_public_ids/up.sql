@@ -0,0 +1,6 @@
+-- The SHA-256 of the dm_id of each DM, so that DMs are found by their public id without
+-- hashing every dm_id. Existing DMs are backfilled by a background migration.
+CREATE TABLE dm_public_ids(
+    "dm_id_hash" BLOB PRIMARY KEY NOT NULL,
+    "dm_id" TEXT NOT NULL
+);
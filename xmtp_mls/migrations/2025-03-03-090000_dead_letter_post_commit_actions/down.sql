@@ -0,0 +1 @@
+ALTER TABLE post_commit_actions DROP COLUMN dead_lettered_at_ns;
@@ -0,0 +1,3 @@
+-- Actions that kept failing are given up on instead of being taken on every sync. They are kept
+-- as long as completed actions, to be inspected.
+ALTER TABLE post_commit_actions ADD COLUMN dead_lettered_at_ns BIGINT;
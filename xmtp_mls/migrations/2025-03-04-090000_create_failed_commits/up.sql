@@ -0,0 +1,9 @@
+-- Commits from the network that this installation could not process. A member that fails to
+-- apply a commit the others applied is on a forked copy of the group.
+CREATE TABLE failed_commits(
+    "group_id" BLOB NOT NULL,
+    "cursor" BIGINT NOT NULL,
+    "error" TEXT NOT NULL,
+    "failed_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY ("group_id", "cursor")
+);
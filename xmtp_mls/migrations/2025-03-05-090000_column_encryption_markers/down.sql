@@ -0,0 +1,2 @@
+ALTER TABLE group_avatars DROP COLUMN "url_lookup";
+DROP TABLE encrypted_columns;
@@ -0,0 +1,10 @@
+-- Columns whose values have all been sealed by column encryption. Once a column is listed here,
+-- every value in it must be sealed, and the store refuses to open without the column key.
+CREATE TABLE encrypted_columns(
+    "table_name" TEXT NOT NULL,
+    "column_name" TEXT NOT NULL,
+    PRIMARY KEY ("table_name", "column_name")
+);
+
+-- Keyed hash of the url, so that sealed urls can be compared in SQL
+ALTER TABLE group_avatars ADD COLUMN "url_lookup" BLOB;
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use super::{ApiClientWrapper, WrappedApiError};
+use super::{ApiClientWrapper, NetworkSubsystem, WrappedApiError};
 use crate::XmtpApi;
 use futures::future::try_join_all;
 use xmtp_id::{
@@ -70,11 +70,14 @@ where
         &self,
         update: UnverifiedIdentityUpdate,
     ) -> Result<(), WrappedApiError> {
-        self.api_client
-            .publish_identity_update(PublishIdentityUpdateRequest {
+        self.tracked(
+            NetworkSubsystem::Identity,
+            PublishIdentityUpdateRequest {
                 identity_update: Some(update.into()),
-            })
-            .await?;
+            },
+            |request| self.api_client.publish_identity_update(request),
+        )
+        .await?;
 
         Ok(())
     }
@@ -89,10 +92,13 @@ where
         let chunked_results: Result<Vec<GetIdentityUpdatesResponse>, WrappedApiError> =
             try_join_all(chunks.map(|chunk| async move {
                 let result = self
-                    .api_client
-                    .get_identity_updates_v2(GetIdentityUpdatesV2Request {
-                        requests: chunk.iter().map(|filter| filter.into()).collect(),
-                    })
+                    .tracked(
+                        NetworkSubsystem::Identity,
+                        GetIdentityUpdatesV2Request {
+                            requests: chunk.iter().map(|filter| filter.into()).collect(),
+                        },
+                        |request| self.api_client.get_identity_updates_v2(request),
+                    )
                     .await?;
 
                 Ok(result)
@@ -127,13 +133,16 @@ where
             &account_addresses
         );
         let result = self
-            .api_client
-            .get_inbox_ids(GetInboxIdsRequest {
-                requests: account_addresses
-                    .into_iter()
-                    .map(|address| GetInboxIdsRequestProto { address })
-                    .collect(),
-            })
+            .tracked(
+                NetworkSubsystem::Identity,
+                GetInboxIdsRequest {
+                    requests: account_addresses
+                        .into_iter()
+                        .map(|address| GetInboxIdsRequestProto { address })
+                        .collect(),
+                },
+                |request| self.api_client.get_inbox_ids(request),
+            )
             .await?;
 
         Ok(result
@@ -152,7 +161,7 @@ pub(crate) mod tests {
     use super::super::test_utils::*;
     use super::GetIdentityUpdatesV2Filter;
     use crate::api::ApiClientWrapper;
-    use xmtp_common::{rand_hexstring, Retry};
+    use xmtp_common::{rand_hexstring, RetryPolicies};
     use xmtp_id::associations::unverified::UnverifiedIdentityUpdate;
     use xmtp_proto::xmtp::identity::api::v1::{
         get_identity_updates_response::{
@@ -182,7 +191,7 @@ pub(crate) mod tests {
             .withf(move |req| req.identity_update.as_ref().unwrap().inbox_id.eq(&inbox_id))
             .returning(move |_| Ok(PublishIdentityUpdateResponse {}));
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let result = wrapper.publish_identity_update(identity_update).await;
 
         assert!(result.is_ok());
@@ -212,7 +221,7 @@ pub(crate) mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let result = wrapper
             .get_identity_updates_v2(vec![GetIdentityUpdatesV2Filter {
                 inbox_id: inbox_id_clone_2.clone(),
@@ -258,7 +267,7 @@ pub(crate) mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let result = wrapper
             .get_inbox_ids(vec![address.clone()])
             .await
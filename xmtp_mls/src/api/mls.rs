@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use super::ApiClientWrapper;
+use super::{ApiClientWrapper, NetworkSubsystem};
 use crate::XmtpApi;
 use xmtp_common::retry_async;
 use xmtp_proto::api_client::XmtpMlsStreams;
@@ -61,7 +61,7 @@ pub enum IdentityUpdate {
     Invalid,
 }
 
-type KeyPackageMap = HashMap<Vec<u8>, Vec<u8>>;
+pub(crate) type KeyPackageMap = HashMap<Vec<u8>, Vec<u8>>;
 
 impl<ApiClient> ApiClientWrapper<ApiClient>
 where
@@ -83,19 +83,23 @@ where
         let mut id_cursor = id_cursor;
         loop {
             let mut result = retry_async!(
-                self.retry_strategy,
+                self.retry(),
                 (async {
-                    self.api_client
-                        .query_group_messages(QueryGroupMessagesRequest {
+                    self.tracked(
+                        NetworkSubsystem::Sync,
+                        QueryGroupMessagesRequest {
                             group_id: group_id.clone(),
                             paging_info: Some(PagingInfo {
                                 id_cursor: id_cursor.unwrap_or(0),
                                 limit: MAX_PAGE_SIZE,
                                 direction: SortDirection::Ascending as i32,
                             }),
-                        })
-                        .await
-                })
+                        },
+                        |request| self.api_client.query_group_messages(request),
+                    )
+                    .await
+                }),
+                self.on_retry()
             )?;
             let num_messages = result.messages.len();
             out.append(&mut result.messages);
@@ -126,19 +130,23 @@ where
             "query latest group message"
         );
         let result = retry_async!(
-            self.retry_strategy,
+            self.retry(),
             (async {
-                self.api_client
-                    .query_group_messages(QueryGroupMessagesRequest {
+                self.tracked(
+                    NetworkSubsystem::Sync,
+                    QueryGroupMessagesRequest {
                         group_id: group_id.as_ref().to_vec(),
                         paging_info: Some(PagingInfo {
                             id_cursor: 0,
                             limit: 1,
                             direction: SortDirection::Descending as i32,
                         }),
-                    })
-                    .await
-            })
+                    },
+                    |request| self.api_client.query_group_messages(request),
+                )
+                .await
+            }),
+            self.on_retry()
         )?;
 
         Ok(result.messages.into_iter().next())
@@ -161,19 +169,23 @@ where
         let mut id_cursor = id_cursor;
         loop {
             let mut result = retry_async!(
-                self.retry_strategy,
+                self.retry(),
                 (async {
-                    self.api_client
-                        .query_welcome_messages(QueryWelcomeMessagesRequest {
+                    self.tracked(
+                        NetworkSubsystem::Sync,
+                        QueryWelcomeMessagesRequest {
                             installation_key: installation_id.as_ref().to_vec(),
                             paging_info: Some(PagingInfo {
                                 id_cursor: id_cursor.unwrap_or(0),
                                 limit: page_size,
                                 direction: SortDirection::Ascending as i32,
                             }),
-                        })
-                        .await
-                })
+                        },
+                        |request| self.api_client.query_welcome_messages(request),
+                    )
+                    .await
+                }),
+                self.on_retry()
             )?;
 
             let num_messages = result.messages.len();
@@ -206,17 +218,21 @@ where
     ) -> Result<(), ApiError> {
         tracing::debug!(inbox_id = self.inbox_id, "upload key packages");
         retry_async!(
-            self.retry_strategy,
+            self.retry(),
             (async {
-                self.api_client
-                    .upload_key_package(UploadKeyPackageRequest {
+                self.tracked(
+                    NetworkSubsystem::KeyPackages,
+                    UploadKeyPackageRequest {
                         key_package: Some(KeyPackageUpload {
                             key_package_tls_serialized: key_package.clone(),
                         }),
                         is_inbox_id_credential,
-                    })
-                    .await
-            })
+                    },
+                    |request| self.api_client.upload_key_package(request),
+                )
+                .await
+            }),
+            self.on_retry()
         )?;
 
         Ok(())
@@ -229,14 +245,18 @@ where
     ) -> Result<KeyPackageMap, ApiError> {
         tracing::debug!(inbox_id = self.inbox_id, "fetch key packages");
         let res = retry_async!(
-            self.retry_strategy,
+            self.retry(),
             (async {
-                self.api_client
-                    .fetch_key_packages(FetchKeyPackagesRequest {
+                self.tracked(
+                    NetworkSubsystem::KeyPackages,
+                    FetchKeyPackagesRequest {
                         installation_keys: installation_keys.clone(),
-                    })
-                    .await
-            })
+                    },
+                    |request| self.api_client.fetch_key_packages(request),
+                )
+                .await
+            }),
+            self.on_retry()
         )?;
 
         if res.key_packages.len() != installation_keys.len() {
@@ -266,14 +286,18 @@ where
     ) -> Result<(), ApiError> {
         tracing::debug!(inbox_id = self.inbox_id, "send welcome messages");
         retry_async!(
-            self.retry_strategy,
+            self.retry(),
             (async {
-                self.api_client
-                    .send_welcome_messages(SendWelcomeMessagesRequest {
+                self.tracked(
+                    NetworkSubsystem::Publish,
+                    SendWelcomeMessagesRequest {
                         messages: messages.to_vec(),
-                    })
-                    .await
-            })
+                    },
+                    |request| self.api_client.send_welcome_messages(request),
+                )
+                .await
+            }),
+            self.on_retry()
         )?;
 
         Ok(())
@@ -291,14 +315,18 @@ where
         );
 
         retry_async!(
-            self.retry_strategy,
+            self.retry(),
             (async {
-                self.api_client
-                    .send_group_messages(SendGroupMessagesRequest {
+                self.tracked(
+                    NetworkSubsystem::Publish,
+                    SendGroupMessagesRequest {
                         messages: group_messages.clone(),
-                    })
-                    .await
-            })
+                    },
+                    |request| self.api_client.send_group_messages(request),
+                )
+                .await
+            }),
+            self.on_retry()
         )?;
 
         Ok(())
@@ -376,7 +404,7 @@ pub mod tests {
                     .eq(&key_package)
             })
             .returning(move |_| Ok(()));
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let result = wrapper.upload_key_package(key_package_clone, false).await;
         assert!(result.is_ok());
     }
@@ -399,7 +427,7 @@ pub mod tests {
                 ],
             })
         });
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let result = wrapper
             .fetch_key_packages(installation_keys.clone())
             .await
@@ -437,7 +465,7 @@ pub mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let result = wrapper
             .query_group_messages(group_id_clone, None)
@@ -469,7 +497,7 @@ pub mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let result = wrapper
             .query_group_messages(group_id_clone, None)
@@ -520,7 +548,7 @@ pub mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let result = wrapper
             .query_group_messages(group_id_clone2, None)
@@ -554,7 +582,7 @@ pub mod tests {
                 })
             });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let result = wrapper
             .query_group_messages(group_id_clone, None)
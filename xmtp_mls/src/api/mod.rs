@@ -2,17 +2,20 @@ pub mod identity;
 pub mod mls;
 #[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
+pub mod usage;
 
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
-use crate::XmtpApi;
+use crate::{subscriptions::LocalEvents, XmtpApi};
 use thiserror::Error;
-use xmtp_common::{Retry, RetryableError};
+use tokio::sync::broadcast;
+use xmtp_common::{ErrorClass, RetryPolicies, RetryState, RetryableError, ScopedRetry};
 use xmtp_id::{associations::DeserializationError as AssociationDeserializationError, InboxId};
 use xmtp_proto::Error as ApiError;
 
 pub use identity::*;
 pub use mls::*;
+pub use usage::{NetworkSubsystem, NetworkUsage, NetworkUsageTracker, SubsystemUsage};
 
 #[derive(Debug, Error)]
 pub enum WrappedApiError {
@@ -26,24 +29,33 @@ impl RetryableError for WrappedApiError {
     fn is_retryable(&self) -> bool {
         matches!(self, Self::Api(_))
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Network)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ApiClientWrapper<ApiClient> {
     pub(crate) api_client: Arc<ApiClient>,
-    pub(crate) retry_strategy: Retry,
+    pub(crate) retry_policies: RetryPolicies,
     pub(crate) inbox_id: Option<InboxId>,
+    pub(crate) usage: Arc<NetworkUsageTracker>,
+    /// Where retries are reported, once the wrapper belongs to a client
+    pub(crate) retry_events: Option<broadcast::Sender<LocalEvents>>,
 }
 
 impl<ApiClient> ApiClientWrapper<ApiClient>
 where
     ApiClient: XmtpApi,
 {
-    pub fn new(api_client: Arc<ApiClient>, retry_strategy: Retry) -> Self {
+    pub fn new(api_client: Arc<ApiClient>, retry_policies: RetryPolicies) -> Self {
         Self {
             api_client,
-            retry_strategy,
+            retry_policies,
             inbox_id: None,
+            usage: Default::default(),
+            retry_events: None,
         }
     }
 
@@ -52,4 +64,46 @@ where
     pub(crate) fn attach_inbox_id(&mut self, inbox_id: Option<InboxId>) {
         self.inbox_id = inbox_id;
     }
+
+    /// Report the retries of requests to the streams of a client
+    pub(crate) fn attach_retry_events(&mut self, events: broadcast::Sender<LocalEvents>) {
+        self.retry_events = Some(events);
+    }
+
+    /// Requests are retried with the network policy, unless they fail with an error of
+    /// another class
+    fn retry(&self) -> ScopedRetry {
+        self.retry_policies.scoped(ErrorClass::Network)
+    }
+
+    fn on_retry(&self) -> impl FnMut(&RetryState) + '_ {
+        move |state| {
+            if let Some(events) = &self.retry_events {
+                let _ = events.send(LocalEvents::Retrying(state.clone()));
+            }
+        }
+    }
+
+    pub fn usage(&self) -> &NetworkUsageTracker {
+        &self.usage
+    }
+
+    /// Make a request, and account for it in the usage of `subsystem`
+    async fn tracked<Request, Response, E, Fut>(
+        &self,
+        subsystem: NetworkSubsystem,
+        request: Request,
+        send: impl FnOnce(Request) -> Fut,
+    ) -> Result<Response, E>
+    where
+        Request: prost::Message,
+        Response: prost::Message,
+        Fut: Future<Output = Result<Response, E>>,
+    {
+        let bytes_sent = request.encoded_len();
+        let result = send(request).await;
+        let bytes_received = result.as_ref().map_or(0, Response::encoded_len);
+        self.usage.record(subsystem, bytes_sent, bytes_received);
+        result
+    }
 }
@@ -0,0 +1,135 @@
+//! Accounting of the data the client exchanges with the network, so apps can attribute data
+//! usage to XMTP and to each part of the client.
+//!
+//! Sizes are the encoded sizes of the protobuf requests and responses, without the transport
+//! overhead of gRPC, HTTP and TLS.
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use xmtp_common::time::now_ns;
+
+/// The part of the client a request was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkSubsystem {
+    /// Queries of welcomes and group messages
+    Sync,
+    /// Envelopes received from subscriptions
+    Streams,
+    /// Publishing group messages and welcomes
+    Publish,
+    /// Identity updates and inbox id lookups
+    Identity,
+    /// Uploading and fetching key packages
+    KeyPackages,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubsystemUsage {
+    /// Number of requests, or of envelopes received for streams
+    pub requests: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl SubsystemUsage {
+    fn add(&mut self, other: &SubsystemUsage) {
+        self.requests += other.requests;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkUsage {
+    /// Time in nanoseconds the accounting started, or was last reset
+    pub since_ns: i64,
+    pub subsystems: HashMap<NetworkSubsystem, SubsystemUsage>,
+}
+
+impl Default for NetworkUsage {
+    fn default() -> Self {
+        Self {
+            since_ns: now_ns(),
+            subsystems: HashMap::new(),
+        }
+    }
+}
+
+impl NetworkUsage {
+    pub fn subsystem(&self, subsystem: NetworkSubsystem) -> SubsystemUsage {
+        self.subsystems.get(&subsystem).copied().unwrap_or_default()
+    }
+
+    /// Usage summed over all the subsystems
+    pub fn total(&self) -> SubsystemUsage {
+        let mut total = SubsystemUsage::default();
+        for usage in self.subsystems.values() {
+            total.add(usage);
+        }
+        total
+    }
+}
+
+/// Tracks the network usage of an API client, shared by its clones
+#[derive(Debug, Default)]
+pub struct NetworkUsageTracker {
+    usage: Mutex<NetworkUsage>,
+}
+
+impl NetworkUsageTracker {
+    pub(crate) fn record(
+        &self,
+        subsystem: NetworkSubsystem,
+        bytes_sent: usize,
+        bytes_received: usize,
+    ) {
+        self.usage
+            .lock()
+            .subsystems
+            .entry(subsystem)
+            .or_default()
+            .add(&SubsystemUsage {
+                requests: 1,
+                bytes_sent: bytes_sent as u64,
+                bytes_received: bytes_received as u64,
+            });
+    }
+
+    pub fn usage(&self) -> NetworkUsage {
+        self.usage.lock().clone()
+    }
+
+    /// Start the accounting over, returning the usage until now
+    pub fn reset(&self) -> NetworkUsage {
+        std::mem::take(&mut *self.usage.lock())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_network_usage() {
+        let tracker = NetworkUsageTracker::default();
+        tracker.record(NetworkSubsystem::Sync, 10, 100);
+        tracker.record(NetworkSubsystem::Sync, 10, 50);
+        tracker.record(NetworkSubsystem::Streams, 0, 30);
+
+        let usage = tracker.usage();
+        assert_eq!(
+            usage.subsystem(NetworkSubsystem::Sync),
+            SubsystemUsage {
+                requests: 2,
+                bytes_sent: 20,
+                bytes_received: 150,
+            }
+        );
+        assert_eq!(usage.subsystem(NetworkSubsystem::Identity).requests, 0);
+        assert_eq!(usage.total().bytes_received, 180);
+
+        assert_eq!(tracker.reset(), usage);
+        assert_eq!(tracker.usage().total(), SubsystemUsage::default());
+    }
+}
@@ -0,0 +1,144 @@
+//! Worker running the [`BackgroundMigration`]s after startup, see
+//! [`crate::storage::background_migration`].
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use crate::{
+    client::ClientError,
+    configuration::{BACKGROUND_MIGRATION_BATCH_DELAY, BACKGROUND_MIGRATION_BATCH_SIZE},
+    diagnostics::WorkerKind,
+    groups::disappearing_messages::WORKER_RESTART_DELAY,
+    storage::{
+        background_migration::{BackgroundMigration, BACKGROUND_MIGRATIONS},
+        ProviderTransactions, StorageError,
+    },
+    Client,
+};
+
+/// Progress key of the backfill of the group names, which are read from the MLS group state
+const BACKFILL_GROUP_NAMES: &str = "backfill_group_names";
+
+pub struct BackgroundMigrationsWorker<ApiClient, V> {
+    client: Client<ApiClient, V>,
+}
+
+impl<ApiClient, V> BackgroundMigrationsWorker<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    pub fn new(client: Client<ApiClient, V>) -> Self {
+        Self { client }
+    }
+
+    pub(crate) fn spawn_worker(self) {
+        crate::spawn(None, async move {
+            let inbox_id = self.client.inbox_id().to_string();
+            let installation_id = hex::encode(self.client.installation_public_key());
+            let workers = self.client.workers.clone();
+            workers.started(WorkerKind::BackgroundMigrations);
+            while let Err(err) = self.run().await {
+                match err {
+                    ClientError::Storage(StorageError::PoolNeedsConnection) => {
+                        tracing::warn!(
+                            inbox_id,
+                            installation_id,
+                            "Pool disconnected. task will restart on reconnect"
+                        );
+                        break;
+                    }
+                    _ => {
+                        tracing::error!(
+                            inbox_id,
+                            installation_id,
+                            "background migration error {err}"
+                        );
+                        workers.failed(WorkerKind::BackgroundMigrations, &err);
+                        xmtp_common::time::sleep(WORKER_RESTART_DELAY).await;
+                        workers.started(WorkerKind::BackgroundMigrations);
+                    }
+                }
+            }
+            workers.stopped(WorkerKind::BackgroundMigrations);
+        });
+    }
+
+    /// Run every migration to completion, one batch at a time
+    async fn run(&self) -> Result<(), ClientError> {
+        for migration in BACKGROUND_MIGRATIONS {
+            self.run_migration(*migration).await?;
+        }
+        self.backfill_group_names().await?;
+        Ok(())
+    }
+
+    /// Record the names of the groups created before names were stored, see
+    /// [`crate::storage::group_name`]
+    async fn backfill_group_names(&self) -> Result<(), ClientError> {
+        let conn = self.client.store().conn()?;
+        if conn.background_migration_complete(BACKFILL_GROUP_NAMES)? {
+            return Ok(());
+        }
+        let group_ids = conn.groups_without_name()?;
+        drop(conn);
+        for batch in group_ids.chunks(BACKGROUND_MIGRATION_BATCH_SIZE as usize) {
+            let provider = self.client.mls_provider()?;
+            for group_id in batch {
+                let name = self
+                    .client
+                    .group_with_conn(provider.conn_ref(), group_id)
+                    .and_then(|group| Ok(group.group_name(&provider)?));
+                match name {
+                    Ok(name) => provider.conn_ref().set_group_name(group_id, &name)?,
+                    Err(err) => tracing::warn!(
+                        group_id = hex::encode(group_id),
+                        "could not read the name of the group: {err}"
+                    ),
+                }
+            }
+            // Release the connection while waiting
+            drop(provider);
+            xmtp_common::time::sleep(BACKGROUND_MIGRATION_BATCH_DELAY).await;
+        }
+        self.client
+            .store()
+            .conn()?
+            .complete_migration(BACKFILL_GROUP_NAMES)?;
+        tracing::debug!("background migration {BACKFILL_GROUP_NAMES} complete");
+        Ok(())
+    }
+
+    async fn run_migration(&self, migration: &dyn BackgroundMigration) -> Result<(), ClientError> {
+        loop {
+            let provider = self.client.mls_provider()?;
+            let complete = provider.transaction(|provider| {
+                provider
+                    .conn_ref()
+                    .run_background_migration_batch(migration, BACKGROUND_MIGRATION_BATCH_SIZE)
+            })?;
+            if complete {
+                tracing::debug!("background migration {} complete", migration.name());
+                return Ok(());
+            }
+            // Release the connection while waiting
+            drop(provider);
+            xmtp_common::time::sleep(BACKGROUND_MIGRATION_BATCH_DELAY).await;
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Start the worker completing the background migrations. It exits once they are all done.
+    pub fn start_background_migrations_worker(&self) {
+        tracing::trace!(
+            inbox_id = self.inbox_id(),
+            installation_id = hex::encode(self.installation_public_key()),
+            "starting background migrations worker"
+        );
+        BackgroundMigrationsWorker::new(self.clone()).spawn_worker();
+    }
+}
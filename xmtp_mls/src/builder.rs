@@ -1,20 +1,34 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use thiserror::Error;
 use tracing::debug;
 
 use xmtp_cryptography::signature::AddressValidationError;
-use xmtp_id::scw_verifier::{RemoteSignatureVerifier, SmartContractSignatureVerifier};
+use xmtp_id::{
+    associations::MemberIdentifier,
+    scw_verifier::{RemoteSignatureVerifier, SmartContractSignatureVerifier},
+};
 
 use crate::{
-    api::ApiClientWrapper,
+    api::{ApiClientWrapper, GetIdentityUpdatesV2Filter},
     client::Client,
+    configuration::{GROUP_KEY_ROTATION_INTERVAL_NS, MAX_GROUP_SIZE, STREAM_SILENCE_WINDOW},
+    groups::{
+        avatars::{AvatarFetcher, SharedAvatarFetcher},
+        enrichers::{MessageEnricher, MessageEnrichers},
+        outbound::{OutboundInterceptor, OutboundInterceptors},
+        pipeline::{EnvelopeInterceptor, EnvelopeInterceptors},
+        request_expiry::RequestExpiry,
+        transactions::{SharedTransactionVerifier, TransactionVerifier},
+    },
     identity::{Identity, IdentityStrategy},
-    identity_updates::load_identity_updates,
-    storage::EncryptedMessageStore,
-    StorageError, XmtpApi, XmtpOpenMlsProvider,
+    identity_updates::{is_member_of_association_state, load_identity_updates},
+    storage::{
+        identity::StoredIdentity, network_environment::NetworkEnvironment, EncryptedMessageStore,
+    },
+    Fetch, StorageError, XmtpApi, XmtpOpenMlsProvider,
 };
-use xmtp_common::Retry;
+use xmtp_common::RetryPolicies;
 
 #[derive(Error, Debug)]
 pub enum ClientBuilderError {
@@ -30,6 +44,15 @@ pub enum ClientBuilderError {
     // SerializationError { source: serde_json::Error },
     #[error("Database was configured with a different wallet")]
     StoredIdentityMismatch,
+    #[error(
+        "Database belongs to the {stored} environment, but the client connects to {configured}"
+    )]
+    NetworkEnvironmentMismatch {
+        stored: NetworkEnvironment,
+        configured: NetworkEnvironment,
+    },
+    #[error("The installation of the database is not a member of its inbox on {configured}")]
+    IdentityNotOnNetwork { configured: NetworkEnvironment },
 
     #[error("Uncovered Case")]
     UncoveredCase,
@@ -55,6 +78,20 @@ pub struct ClientBuilder<ApiClient, V = RemoteSignatureVerifier<ApiClient>> {
     history_sync_url: Option<String>,
     app_version: Option<String>,
     scw_verifier: Option<V>,
+    retry_policies: RetryPolicies,
+    envelope_interceptors: EnvelopeInterceptors,
+    message_enrichers: MessageEnrichers,
+    outbound_interceptors: OutboundInterceptors,
+    max_group_size: usize,
+    transaction_verifier: SharedTransactionVerifier,
+    avatar_fetcher: SharedAvatarFetcher,
+    dm_network_lookup: bool,
+    stream_silence_window: Option<Duration>,
+    key_rotation_interval: Duration,
+    network_environment: Option<NetworkEnvironment>,
+    left_group_retention: Option<Duration>,
+    request_expiry: Option<RequestExpiry>,
+    feature_flags: HashMap<String, bool>,
 }
 
 impl<ApiClient, V> Client<ApiClient, V> {
@@ -75,6 +112,20 @@ impl<ApiClient, V> ClientBuilder<ApiClient, V> {
             history_sync_url: None,
             app_version: None,
             scw_verifier: None,
+            retry_policies: RetryPolicies::default(),
+            envelope_interceptors: EnvelopeInterceptors::default(),
+            message_enrichers: MessageEnrichers::default(),
+            outbound_interceptors: OutboundInterceptors::default(),
+            max_group_size: MAX_GROUP_SIZE,
+            transaction_verifier: None,
+            avatar_fetcher: None,
+            dm_network_lookup: true,
+            stream_silence_window: Some(STREAM_SILENCE_WINDOW),
+            key_rotation_interval: Duration::from_nanos(GROUP_KEY_ROTATION_INTERVAL_NS as u64),
+            network_environment: None,
+            left_group_retention: None,
+            request_expiry: None,
+            feature_flags: HashMap::new(),
         }
     }
 
@@ -98,6 +149,111 @@ impl<ApiClient, V> ClientBuilder<ApiClient, V> {
         self
     }
 
+    /// Retry strategies used for network requests, storage and MLS processing.
+    /// Defaults to [`RetryPolicies::default`].
+    pub fn retry_policies(mut self, policies: RetryPolicies) -> Self {
+        self.retry_policies = policies;
+        self
+    }
+
+    /// Observe incoming group messages as they are processed. See [`EnvelopeInterceptor`].
+    pub fn envelope_interceptor(mut self, interceptor: impl EnvelopeInterceptor + 'static) -> Self {
+        self.envelope_interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Compute metadata for incoming messages before they are stored. See [`MessageEnricher`].
+    pub fn message_enricher(mut self, enricher: impl MessageEnricher + 'static) -> Self {
+        self.message_enrichers.push(Arc::new(enricher));
+        self
+    }
+
+    /// Observe and annotate intents before they are published. See [`OutboundInterceptor`].
+    pub fn outbound_interceptor(mut self, interceptor: impl OutboundInterceptor + 'static) -> Self {
+        self.outbound_interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Maximum number of inboxes in a group. Adding members beyond it fails with
+    /// [`crate::groups::GroupError::UserLimitExceeded`]. Defaults to [`MAX_GROUP_SIZE`].
+    pub fn max_group_size(mut self, max_group_size: usize) -> Self {
+        self.max_group_size = max_group_size;
+        self
+    }
+
+    /// Check the on-chain status of transaction reference messages. See [`TransactionVerifier`].
+    pub fn transaction_verifier(mut self, verifier: impl TransactionVerifier + 'static) -> Self {
+        self.transaction_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Keep local copies of the images of groups, downloaded with `fetcher`. See
+    /// [`AvatarFetcher`].
+    pub fn avatar_fetcher(mut self, fetcher: impl AvatarFetcher + 'static) -> Self {
+        self.avatar_fetcher = Some(Arc::new(fetcher));
+        self
+    }
+
+    /// Whether [`Client::find_or_create_dm_by_inbox_id`] checks the network for a DM that this
+    /// installation was added to before creating a new one. Defaults to `true`.
+    pub fn dm_network_lookup(mut self, enabled: bool) -> Self {
+        self.dm_network_lookup = enabled;
+        self
+    }
+
+    /// How long a message stream may go without receiving anything before it is subscribed
+    /// again, in case the connection died silently. `None` disables the watchdog. Defaults to
+    /// [`STREAM_SILENCE_WINDOW`].
+    pub fn stream_silence_window(mut self, window: Option<Duration>) -> Self {
+        self.stream_silence_window = window;
+        self
+    }
+
+    /// How long this installation's leaf key in a group may go without being rotated. The key is
+    /// rotated with the next message sent after that. Groups can override it with
+    /// [`MlsGroup::set_key_rotation_interval`](crate::groups::MlsGroup::set_key_rotation_interval).
+    /// Defaults to [`GROUP_KEY_ROTATION_INTERVAL_NS`].
+    pub fn key_rotation_interval(mut self, interval: Duration) -> Self {
+        self.key_rotation_interval = interval;
+        self
+    }
+
+    /// The network environment the API client connects to. The database records the environment
+    /// it is first used with, and building the client fails with
+    /// [`ClientBuilderError::NetworkEnvironmentMismatch`] if it was used with another one. Not
+    /// checked unless set.
+    ///
+    /// A database holding an identity from before environments were recorded is only recorded
+    /// once its installation is found in its inbox on the network, and fails with
+    /// [`ClientBuilderError::IdentityNotOnNetwork`] otherwise.
+    pub fn network_environment(mut self, env: NetworkEnvironment) -> Self {
+        self.network_environment = Some(env);
+        self
+    }
+
+    /// How long to keep the messages of a group after this installation is removed from it.
+    /// The group itself is kept so it can still be shown. `None`, the default, keeps the
+    /// messages forever.
+    pub fn left_group_retention(mut self, retention: Option<Duration>) -> Self {
+        self.left_group_retention = retention;
+        self
+    }
+
+    /// How long conversations may wait for the user's consent before they are denied, or
+    /// deleted, by the background cleanup and [`Client::run_maintenance`]. `None`, the default,
+    /// keeps requests until the user decides.
+    pub fn request_expiry(mut self, expiry: Option<RequestExpiry>) -> Self {
+        self.request_expiry = expiry;
+        self
+    }
+
+    /// The default of a feature flag, used until the flag is set on this installation with
+    /// [`Client::set_feature_flag`]. Flags without a default are disabled.
+    pub fn feature_flag(mut self, name: impl Into<String>, enabled: bool) -> Self {
+        self.feature_flags.insert(name.into(), enabled);
+        self
+    }
+
     pub fn app_version(mut self, version: String) -> Self {
         self.app_version = Some(version);
         self
@@ -160,6 +316,34 @@ where
     Ok((builder, Arc::new(api_client)))
 }
 
+/// Whether the installation of `identity` belongs to its inbox on the network of `api_client`,
+/// or `None` if the inbox has no identity updates there yet. Inbox ids are derived from the
+/// wallet, so the same inbox may exist on several networks with other installations.
+async fn identity_on_network<C>(
+    api_client: &ApiClientWrapper<C>,
+    identity: &Identity,
+) -> Result<Option<bool>, ClientBuilderError>
+where
+    C: XmtpApi + 'static + Send + Sync,
+{
+    let filters = vec![GetIdentityUpdatesV2Filter {
+        inbox_id: identity.inbox_id().to_string(),
+        sequence_id: None,
+    }];
+    let mut updates = api_client.get_identity_updates_v2(filters).await?;
+    if updates
+        .remove(identity.inbox_id())
+        .is_none_or(|updates| updates.is_empty())
+    {
+        return Ok(None);
+    }
+    let installation: MemberIdentifier = identity.installation_keys.public_slice().to_vec().into();
+    let is_member =
+        is_member_of_association_state(api_client, identity.inbox_id(), &installation, None)
+            .await?;
+    Ok(Some(is_member))
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
 async fn inner_build<C, V>(
     client: ClientBuilder<C, V>,
@@ -174,6 +358,20 @@ where
         identity_strategy,
         history_sync_url,
         mut scw_verifier,
+        retry_policies,
+        envelope_interceptors,
+        message_enrichers,
+        outbound_interceptors,
+        max_group_size,
+        transaction_verifier,
+        avatar_fetcher,
+        dm_network_lookup,
+        stream_silence_window,
+        key_rotation_interval,
+        network_environment,
+        left_group_retention,
+        request_expiry,
+        feature_flags,
         ..
     } = client;
 
@@ -185,15 +383,45 @@ where
             parameter: "scw_verifier",
         })?;
 
-    let api_client_wrapper = ApiClientWrapper::new(api_client, Retry::default());
+    let api_client_wrapper = ApiClientWrapper::new(api_client, retry_policies);
     let store = store
         .take()
         .ok_or(ClientBuilderError::MissingParameter { parameter: "store" })?;
     let conn = store.conn()?;
+    // Checked before the identity is loaded, which would fail with confusing errors
+    let mut unverified_environment = None;
+    if let Some(configured) = network_environment {
+        match conn.network_environment()? {
+            Some(stored) if stored != configured => {
+                return Err(ClientBuilderError::NetworkEnvironmentMismatch { stored, configured });
+            }
+            Some(_) => (),
+            // A database used before environments were recorded may already be mismatched, so its
+            // identity is looked up on the network before the environment is recorded
+            None if Fetch::<StoredIdentity>::fetch(&conn, &())?.is_some() => {
+                unverified_environment = Some(configured);
+            }
+            None => {
+                conn.record_network_environment(configured)?;
+            }
+        }
+    }
     let provider = XmtpOpenMlsProvider::new(conn);
     let identity = identity_strategy
         .initialize_identity(&api_client_wrapper, &provider, &scw_verifier)
         .await?;
+    if let Some(configured) = unverified_environment {
+        match identity_on_network(&api_client_wrapper, &identity).await? {
+            Some(true) => {
+                provider.conn_ref().record_network_environment(configured)?;
+            }
+            Some(false) => {
+                return Err(ClientBuilderError::IdentityNotOnNetwork { configured });
+            }
+            // Not published yet, checked again on the next build
+            None => (),
+        }
+    }
 
     debug!(
         inbox_id = identity.inbox_id(),
@@ -208,17 +436,40 @@ where
     )
     .await?;
 
-    let client = Client::new(
+    let mut client = Client::new(
         api_client_wrapper,
         identity,
         store,
         scw_verifier,
         history_sync_url.clone(),
     );
+    client.retry_policies = retry_policies;
+    client.envelope_interceptors = envelope_interceptors;
+    client.message_enrichers = message_enrichers;
+    client.outbound_interceptors = outbound_interceptors;
+    client.max_group_size = max_group_size;
+    client.transaction_verifier = transaction_verifier;
+    client.avatar_fetcher = avatar_fetcher;
+    client.dm_network_lookup = dm_network_lookup;
+    client.stream_silence_window = stream_silence_window;
+    client.key_rotation_interval = key_rotation_interval;
+    client.left_group_retention = left_group_retention;
+    client.request_expiry = request_expiry;
+    client.feature_flag_defaults = Arc::new(feature_flags);
+
+    if let Err(err) = client.recover_staged_groups() {
+        tracing::error!("failed to recover staged groups: {err}");
+    }
+    if let Err(err) = client.load_group_burst_budgets() {
+        tracing::error!("failed to load group burst budgets: {err}");
+    }
 
     if history_sync_url.is_some() {
         client.start_sync_worker();
     }
+    client.start_background_migrations_worker();
+    client.start_group_avatars_worker();
+    client.start_post_commit_actions_worker();
 
     Ok(client)
 }
@@ -232,10 +483,11 @@ pub(crate) mod tests {
     use crate::api::ApiClientWrapper;
     use crate::builder::ClientBuilderError;
     use crate::identity::IdentityError;
+    use crate::storage::network_environment::NetworkEnvironment;
     use crate::utils::test::TestClient;
     use crate::XmtpApi;
     use crate::{api::test_utils::*, identity::Identity, storage::identity::StoredIdentity, Store};
-    use xmtp_common::{rand_vec, tmp_path, Retry};
+    use xmtp_common::{rand_vec, tmp_path, RetryPolicies};
 
     use openmls::credentials::{Credential, CredentialType};
     use prost::Message;
@@ -524,6 +776,126 @@ pub(crate) mod tests {
         assert!(client1.installation_public_key() != client4.installation_public_key());
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn network_environment_mismatch() {
+        let (legacy_key, legacy_account_address) = generate_random_legacy_key().await;
+        let identity_strategy = IdentityStrategy::new(
+            generate_inbox_id(&legacy_account_address, &0).unwrap(),
+            legacy_account_address.clone(),
+            0,
+            Some(legacy_key),
+        );
+        let store = EncryptedMessageStore::new(
+            StorageOption::Persistent(tmp_path()),
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+
+        ClientBuilder::new(identity_strategy)
+            .store(store.clone())
+            .api_client(<TestClient as XmtpTestClient>::create_local().await)
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .network_environment(NetworkEnvironment::Local)
+            .build_with_verifier()
+            .await
+            .unwrap();
+
+        let err = ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .store(store.clone())
+            .api_client(<TestClient as XmtpTestClient>::create_local().await)
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .network_environment(NetworkEnvironment::Production)
+            .build_with_verifier()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientBuilderError::NetworkEnvironmentMismatch {
+                stored: NetworkEnvironment::Local,
+                configured: NetworkEnvironment::Production,
+            }
+        ));
+
+        // Clients that do not set the environment are not checked
+        ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .store(store)
+            .api_client(<TestClient as XmtpTestClient>::create_local().await)
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .build_with_verifier()
+            .await
+            .unwrap();
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn network_environment_of_existing_database_is_verified() {
+        let wallet = generate_local_wallet();
+        let address = wallet.get_address();
+        let build = |store: EncryptedMessageStore, strategy: IdentityStrategy| async move {
+            let mut builder = ClientBuilder::new(strategy.clone())
+                .store(store)
+                .api_client(<TestClient as XmtpTestClient>::create_local().await)
+                .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true));
+            // The databases are created before environments were recorded
+            if matches!(strategy, IdentityStrategy::CachedOnly) {
+                builder = builder.network_environment(NetworkEnvironment::Local);
+            }
+            builder.build_with_verifier().await
+        };
+        let new_store = || async {
+            EncryptedMessageStore::new(
+                StorageOption::Persistent(tmp_path()),
+                EncryptedMessageStore::generate_enc_key(),
+            )
+            .await
+            .unwrap()
+        };
+        let strategy = IdentityStrategy::new(
+            generate_inbox_id(&address, &0).unwrap(),
+            address.clone(),
+            0,
+            None,
+        );
+
+        let kept_store = new_store().await;
+        let kept = build(kept_store.clone(), strategy.clone()).await.unwrap();
+        register_client(&kept, &wallet).await;
+        let revoked_store = new_store().await;
+        let revoked = build(revoked_store.clone(), strategy).await.unwrap();
+        register_client(&revoked, &wallet).await;
+
+        let mut revoke = kept
+            .revoke_installations(vec![revoked.installation_public_key().to_vec()])
+            .await
+            .unwrap();
+        xmtp_id::associations::test_utils::add_wallet_signature(&mut revoke, &wallet).await;
+        kept.apply_signature_request(revoke).await.unwrap();
+
+        build(kept_store.clone(), IdentityStrategy::CachedOnly)
+            .await
+            .unwrap();
+        assert_eq!(
+            kept_store.conn().unwrap().network_environment().unwrap(),
+            Some(NetworkEnvironment::Local)
+        );
+
+        let err = build(revoked_store.clone(), IdentityStrategy::CachedOnly)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientBuilderError::IdentityNotOnNetwork {
+                configured: NetworkEnvironment::Local
+            }
+        ));
+        assert_eq!(
+            revoked_store.conn().unwrap().network_environment().unwrap(),
+            None
+        );
+    }
+
     // Should return error if inbox associated with given account_address doesn't match the provided one.
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
@@ -553,7 +925,7 @@ pub(crate) mod tests {
             })
         });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let identity = IdentityStrategy::new("other_inbox_id".to_string(), address, nonce, None);
         assert!(matches!(
@@ -594,7 +966,7 @@ pub(crate) mod tests {
             })
         });
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let identity = IdentityStrategy::new(inbox_id.clone(), address, nonce, None);
         assert!(dbg!(
@@ -634,7 +1006,7 @@ pub(crate) mod tests {
             .unwrap();
 
         stored.store(&store.conn().unwrap()).unwrap();
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
         let identity = IdentityStrategy::new(inbox_id.clone(), address, nonce, None);
         assert!(identity
             .initialize_identity(&wrapper, &store.mls_provider().unwrap(), &scw_verifier)
@@ -672,7 +1044,7 @@ pub(crate) mod tests {
 
         stored.store(&store.conn().unwrap()).unwrap();
 
-        let wrapper = ApiClientWrapper::new(mock_api.into(), Retry::default());
+        let wrapper = ApiClientWrapper::new(mock_api.into(), RetryPolicies::default());
 
         let inbox_id = "inbox_id".to_string();
         let identity = IdentityStrategy::new(inbox_id.clone(), address.clone(), nonce, None);
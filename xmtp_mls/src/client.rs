@@ -4,6 +4,7 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use futures::stream::{self, FuturesUnordered, StreamExt};
@@ -15,7 +16,10 @@ use openmls::{
 use thiserror::Error;
 use tokio::sync::broadcast;
 
-use xmtp_cryptography::signature::{sanitize_evm_addresses, AddressValidationError};
+use xmtp_cryptography::{
+    account_id::{normalize_account_address, sanitize_account_addresses},
+    signature::AddressValidationError,
+};
 use xmtp_id::{
     associations::{
         builder::{SignatureRequest, SignatureRequestError},
@@ -31,32 +35,51 @@ use xmtp_proto::xmtp::mls::api::v1::{welcome_message, GroupMessage, WelcomeMessa
 use crate::groups::device_sync::WorkerHandle;
 
 use crate::{
-    api::ApiClientWrapper,
+    api::{ApiClientWrapper, KeyPackageMap, NetworkUsage},
+    configuration::{GROUP_KEY_ROTATION_INTERVAL_NS, MAX_GROUP_SIZE, STREAM_SILENCE_WINDOW},
+    data_mode::SharedDataMode,
+    diagnostics::WorkerRegistry,
     groups::{
-        device_sync::preference_sync::UserPreferenceUpdate, group_metadata::DmMembers,
-        group_permissions::PolicySet, GroupError, GroupMetadataOptions, MlsGroup,
+        avatars::SharedAvatarFetcher,
+        bulk_create::PrefetchedKeyPackages,
+        device_sync::{preference_sync::UserPreferenceUpdate, status::DeviceSyncTracker},
+        enrichers::MessageEnrichers,
+        group_metadata::DmMembers,
+        group_permissions::PolicySet,
+        outbound::OutboundInterceptors,
+        pipeline::EnvelopeInterceptors,
+        request_expiry::RequestExpiry,
+        transactions::SharedTransactionVerifier,
+        GroupError, GroupMetadataOptions, MegaGroup, MlsGroup,
     },
     identity::{parse_credential, Identity, IdentityError},
-    identity_updates::{load_identity_updates, IdentityUpdateError},
+    identity_updates::{load_identity_updates, IdentityUpdateBatch, IdentityUpdateError},
     intents::ProcessIntentError,
     mutex_registry::MutexRegistry,
     storage::{
+        consent_policy::ConsentPolicy,
         consent_record::{ConsentState, ConsentType, StoredConsentRecord},
         db_connection::DbConnection,
-        group::{GroupMembershipState, GroupQueryArgs, StoredGroup},
+        feature_flag::FeatureFlag,
+        group::{ConversationType, GroupMembershipState, GroupQueryArgs, StoredGroup},
         group_message::StoredGroupMessage,
+        maintenance::{OptimizeLevel, OptimizeReport},
         refresh_state::EntityKind,
         wallet_addresses::WalletEntry,
         xmtp_openmls_provider::XmtpOpenMlsProvider,
         EncryptedMessageStore, NotFound, StorageError,
     },
-    subscriptions::{LocalEventError, LocalEvents},
+    subscriptions::{
+        burst::BurstLimiter, dedupe::StreamDedupe, FeatureFlagChange, LocalEventError, LocalEvents,
+    },
+    sync_progress::{SyncPhase, SyncProgress},
     types::InstallationId,
+    utils::hash::sha256,
     verified_key_package_v2::{KeyPackageVerificationError, VerifiedKeyPackageV2},
     Fetch, Store, XmtpApi,
 };
 use crate::{groups::ConversationListItem, storage::ProviderTransactions};
-use xmtp_common::{retry_async, retryable, Retry};
+use xmtp_common::{retry_async, retryable, ErrorClass, RetryPolicies};
 
 /// Enum representing the network the Client is connected to
 #[derive(Clone, Copy, Default, Debug)]
@@ -125,10 +148,22 @@ impl xmtp_common::RetryableError for ClientError {
             ClientError::Diesel(diesel_error) => retryable!(diesel_error),
             ClientError::Api(api_error) => retryable!(api_error),
             ClientError::Storage(storage_error) => retryable!(storage_error),
-            ClientError::Generic(err) => err.contains("database is locked"),
             _ => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        use xmtp_common::RetryableError;
+        match self {
+            ClientError::Group(group_error) => group_error.error_class(),
+            ClientError::Diesel(diesel_error) => diesel_error.error_class(),
+            ClientError::Api(api_error) => api_error.error_class(),
+            ClientError::QueryError(api_error) => api_error.error_class(),
+            ClientError::Storage(storage_error) => storage_error.error_class(),
+            ClientError::Identity(identity_error) => identity_error.error_class(),
+            _ => None,
+        }
+    }
 }
 
 impl From<String> for ClientError {
@@ -151,6 +186,46 @@ pub struct Client<ApiClient, V = RemoteSignatureVerifier<ApiClient>> {
     pub(crate) local_events: broadcast::Sender<LocalEvents>,
     /// The method of verifying smart contract wallet signatures for this Client
     pub(crate) scw_verifier: Arc<V>,
+    /// State of the background workers started by this Client
+    pub(crate) workers: Arc<WorkerRegistry>,
+    /// Retry strategies shared by everything this Client retries
+    pub(crate) retry_policies: RetryPolicies,
+    /// Observers of incoming group messages
+    pub(crate) envelope_interceptors: EnvelopeInterceptors,
+    /// Metadata computers run on incoming messages before they are stored
+    pub(crate) message_enrichers: MessageEnrichers,
+    /// Observers of intents about to be published
+    pub(crate) outbound_interceptors: OutboundInterceptors,
+    /// Maximum number of inboxes in a group
+    pub(crate) max_group_size: usize,
+    /// Checks the on-chain status of transaction reference messages
+    pub(crate) transaction_verifier: SharedTransactionVerifier,
+    /// Downloads the images of groups
+    pub(crate) avatar_fetcher: SharedAvatarFetcher,
+    /// Whether to look for an existing DM on the network before creating one
+    pub(crate) dm_network_lookup: bool,
+    /// Phase of the latest sync of welcomes and groups
+    pub(crate) sync_progress: Arc<SyncProgress>,
+    /// State of the device sync requests of this installation
+    pub(crate) device_sync: Arc<DeviceSyncTracker>,
+    /// Normal or low data mode, changed at runtime
+    pub(crate) data_mode: Arc<SharedDataMode>,
+    /// Message ids recently delivered to stream callbacks
+    pub(crate) stream_dedupe: Arc<StreamDedupe>,
+    /// Burst budgets of the groups, limiting the messages delivered to stream callbacks
+    pub(crate) stream_bursts: Arc<BurstLimiter>,
+    /// Silence after which message streams subscribe again
+    pub(crate) stream_silence_window: Option<Duration>,
+    /// How long the leaf key of a group may go without rotation, unless the group sets its own
+    pub(crate) key_rotation_interval: Duration,
+    /// How long the history of groups that this installation left is kept
+    pub(crate) left_group_retention: Option<Duration>,
+    /// How long conversations may wait for consent before they expire
+    pub(crate) request_expiry: Option<RequestExpiry>,
+    /// Values of the feature flags that are not set on this installation
+    pub(crate) feature_flag_defaults: Arc<HashMap<String, bool>>,
+    /// Key packages fetched ahead of time by [`Client::create_groups_bulk`]
+    pub(crate) prefetched_key_packages: Arc<PrefetchedKeyPackages>,
 
     #[cfg(any(test, feature = "test-utils"))]
     pub(crate) sync_worker_handle: Arc<parking_lot::Mutex<Option<Arc<WorkerHandle>>>>,
@@ -165,6 +240,26 @@ impl<ApiClient, V> Clone for Client<ApiClient, V> {
             history_sync_url: self.history_sync_url.clone(),
             local_events: self.local_events.clone(),
             scw_verifier: self.scw_verifier.clone(),
+            workers: self.workers.clone(),
+            retry_policies: self.retry_policies,
+            envelope_interceptors: self.envelope_interceptors.clone(),
+            message_enrichers: self.message_enrichers.clone(),
+            outbound_interceptors: self.outbound_interceptors.clone(),
+            max_group_size: self.max_group_size,
+            transaction_verifier: self.transaction_verifier.clone(),
+            avatar_fetcher: self.avatar_fetcher.clone(),
+            dm_network_lookup: self.dm_network_lookup,
+            sync_progress: self.sync_progress.clone(),
+            device_sync: self.device_sync.clone(),
+            data_mode: self.data_mode.clone(),
+            stream_dedupe: self.stream_dedupe.clone(),
+            stream_bursts: self.stream_bursts.clone(),
+            stream_silence_window: self.stream_silence_window,
+            key_rotation_interval: self.key_rotation_interval,
+            left_group_retention: self.left_group_retention,
+            request_expiry: self.request_expiry,
+            feature_flag_defaults: self.feature_flag_defaults.clone(),
+            prefetched_key_packages: self.prefetched_key_packages.clone(),
 
             #[cfg(any(test, feature = "test-utils"))]
             sync_worker_handle: self.sync_worker_handle.clone(),
@@ -247,21 +342,53 @@ where
             mutexes: MutexRegistry::new(),
         });
         let (tx, _) = broadcast::channel(32);
+        api_client.attach_retry_events(tx.clone());
 
         Self {
             api_client: api_client.into(),
             context,
             history_sync_url,
+            stream_bursts: Arc::new(BurstLimiter::new(tx.clone())),
             local_events: tx,
             #[cfg(any(test, feature = "test-utils"))]
             sync_worker_handle: Arc::new(parking_lot::Mutex::default()),
             scw_verifier: scw_verifier.into(),
+            workers: Default::default(),
+            retry_policies: RetryPolicies::default(),
+            envelope_interceptors: EnvelopeInterceptors::default(),
+            message_enrichers: MessageEnrichers::default(),
+            outbound_interceptors: OutboundInterceptors::default(),
+            max_group_size: MAX_GROUP_SIZE,
+            transaction_verifier: None,
+            avatar_fetcher: None,
+            dm_network_lookup: true,
+            sync_progress: Default::default(),
+            device_sync: Default::default(),
+            data_mode: Default::default(),
+            stream_dedupe: Default::default(),
+            stream_silence_window: Some(STREAM_SILENCE_WINDOW),
+            key_rotation_interval: Duration::from_nanos(GROUP_KEY_ROTATION_INTERVAL_NS as u64),
+            left_group_retention: None,
+            request_expiry: None,
+            feature_flag_defaults: Default::default(),
+            prefetched_key_packages: Default::default(),
         }
     }
 
     pub fn scw_verifier(&self) -> &Arc<V> {
         &self.scw_verifier
     }
+
+    /// Data exchanged with the network since the client was created, or since the last
+    /// [`Client::reset_network_usage`]
+    pub fn network_usage(&self) -> NetworkUsage {
+        self.api_client.usage().usage()
+    }
+
+    /// Start the network usage accounting over, returning the usage until now
+    pub fn reset_network_usage(&self) -> NetworkUsage {
+        self.api_client.usage().reset()
+    }
 }
 
 impl<ApiClient, V> Client<ApiClient, V>
@@ -279,6 +406,9 @@ where
         if self.history_sync_url.is_some() {
             self.start_sync_worker();
         }
+        self.start_background_migrations_worker();
+        self.start_group_avatars_worker();
+        self.start_post_commit_actions_worker();
         Ok(())
     }
 }
@@ -323,7 +453,7 @@ where
         conn: &DbConnection,
         addresses: &[String],
     ) -> Result<Vec<Option<String>>, ClientError> {
-        let sanitized_addresses = sanitize_evm_addresses(addresses)?;
+        let sanitized_addresses = sanitize_account_addresses(addresses)?;
 
         let local_results: Vec<WalletEntry> =
             conn.fetch_wallets_list_with_key(&sanitized_addresses)?;
@@ -374,20 +504,6 @@ where
         self.context.inbox_sequence_id(conn)
     }
 
-    /// Get the [`AssociationState`] for the client's `inbox_id`
-    pub async fn inbox_state(
-        &self,
-        refresh_from_network: bool,
-    ) -> Result<AssociationState, ClientError> {
-        let conn = self.store().conn()?;
-        let inbox_id = self.inbox_id();
-        if refresh_from_network {
-            load_identity_updates(&self.api_client, &conn, &[inbox_id]).await?;
-        }
-        let state = self.get_association_state(&conn, inbox_id, None).await?;
-        Ok(state)
-    }
-
     /// Get the [`AssociationState`] for each `inbox_id`
     pub async fn inbox_addresses(
         &self,
@@ -414,6 +530,18 @@ where
         records: &[StoredConsentRecord],
     ) -> Result<(), ClientError> {
         let conn = self.store().conn()?;
+        // Address records are stored with the normalized address, so that they are found
+        // whatever the case or format of the address that is looked up
+        let records = records
+            .iter()
+            .map(|record| {
+                let mut record = record.clone();
+                if record.entity_type == ConsentType::Address {
+                    record.entity = normalize_account_address(&record.entity)?;
+                }
+                Ok(record)
+            })
+            .collect::<Result<Vec<_>, AddressValidationError>>()?;
 
         let mut new_records = Vec::new();
         let mut addresses_to_lookup = Vec::new();
@@ -441,7 +569,7 @@ where
             }
         }
 
-        new_records.extend_from_slice(records);
+        new_records.extend(records);
         let changed_records = conn.insert_or_replace_consent_records(&new_records)?;
 
         if self.history_sync_url.is_some() && !changed_records.is_empty() {
@@ -465,13 +593,14 @@ where
     ) -> Result<ConsentState, ClientError> {
         let conn = self.store().conn()?;
         let record = if entity_type == ConsentType::Address {
+            let address = normalize_account_address(&entity)?;
             if let Some(inbox_id) = self
-                .find_inbox_id_from_address(&conn, entity.clone())
+                .find_inbox_id_from_address(&conn, address.clone())
                 .await?
             {
                 conn.get_consent_record(inbox_id, ConsentType::InboxId)?
             } else {
-                conn.get_consent_record(entity, entity_type)?
+                conn.get_consent_record(address, entity_type)?
             }
         } else {
             conn.get_consent_record(entity, entity_type)?
@@ -483,6 +612,111 @@ where
         }
     }
 
+    /// The account-level defaults for the consent of conversations joined from now on
+    pub fn consent_policy(&self) -> Result<ConsentPolicy, ClientError> {
+        Ok(self.store().conn()?.consent_policy()?)
+    }
+
+    /// Replace the consent policy, and sync it to the other installations
+    pub fn set_consent_policy(&self, policy: ConsentPolicy) -> Result<(), ClientError> {
+        // The other preferences are read and written back along with the policy
+        self.mls_provider()?
+            .transaction(|provider| provider.conn_ref().set_consent_policy(&policy))?;
+        if self.history_sync_url.is_some() {
+            let _ = self
+                .local_events
+                .send(LocalEvents::OutgoingPreferenceUpdates(vec![
+                    UserPreferenceUpdate::ConsentPolicyUpdate(policy),
+                ]));
+        }
+        Ok(())
+    }
+
+    /// Whether a feature flag is enabled: the value set on this installation, or else the default
+    /// the client was built with, or else `false`
+    pub fn is_feature_enabled(&self, name: &str) -> Result<bool, ClientError> {
+        let flag = self.store().conn()?.feature_flag(name)?;
+        Ok(flag.map(|flag| flag.enabled).unwrap_or_else(|| {
+            self.feature_flag_defaults
+                .get(name)
+                .copied()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// The value of every feature flag that has a default or is set on this installation
+    pub fn feature_flags(&self) -> Result<HashMap<String, bool>, ClientError> {
+        let mut flags = (*self.feature_flag_defaults).clone();
+        for (name, flag) in self.store().conn()?.feature_flags()? {
+            flags.insert(name, flag.enabled);
+        }
+        Ok(flags)
+    }
+
+    /// Set a feature flag on this installation, or unset it with `None` so that the default
+    /// applies again. A `synced` change is sent to the other installations as well. Changes are
+    /// emitted on [`Client::stream_feature_flags_with_callback`].
+    pub fn set_feature_flag(
+        &self,
+        name: &str,
+        enabled: Option<bool>,
+        synced: bool,
+    ) -> Result<(), ClientError> {
+        let flag = enabled.map(|enabled| FeatureFlag { enabled, synced });
+        // The other flags are read and written back along with this one
+        let changed = self
+            .mls_provider()?
+            .transaction(|provider| provider.conn_ref().set_feature_flag(name, flag))?;
+        if !changed {
+            return Ok(());
+        }
+        let _ = self
+            .local_events
+            .send(LocalEvents::FeatureFlagsChanged(vec![FeatureFlagChange {
+                name: name.to_string(),
+                enabled,
+            }]));
+        if synced && self.history_sync_url.is_some() {
+            let _ = self
+                .local_events
+                .send(LocalEvents::OutgoingPreferenceUpdates(vec![
+                    UserPreferenceUpdate::FeatureFlagUpdate {
+                        name: name.to_string(),
+                        enabled,
+                    },
+                ]));
+        }
+        Ok(())
+    }
+
+    /// Database maintenance meant to be run periodically while the app is idle: reverts the
+    /// consent records that expired to [`ConsentState::Unknown`] and expires the message
+    /// requests that waited too long, see [`Self::expire_requests`], emitting the new consent on
+    /// the consent stream and to the other installations, then runs
+    /// [`EncryptedMessageStore::optimize`].
+    pub fn run_maintenance(
+        &self,
+        level: OptimizeLevel,
+        budget: Duration,
+    ) -> Result<OptimizeReport, ClientError> {
+        let expired = self
+            .store()
+            .conn()?
+            .expire_consent_records(xmtp_common::time::now_ns())?;
+        if !expired.is_empty() {
+            tracing::info!(count = expired.len(), "reverted expired consent records");
+            let updates = expired
+                .into_iter()
+                .map(UserPreferenceUpdate::ConsentUpdate)
+                .collect();
+            let _ = self
+                .local_events
+                .send(LocalEvents::OutgoingPreferenceUpdates(updates));
+        }
+        self.expire_requests(xmtp_common::time::now_ns())?;
+        Ok(self.store().optimize(level, budget)?)
+    }
+
     /// Gets a reference to the client's store
     pub fn store(&self) -> &EncryptedMessageStore {
         &self.context.store
@@ -559,6 +793,29 @@ where
         Ok(group)
     }
 
+    /// Create a [`MegaGroup`], for communities larger than the maximum group size
+    pub async fn create_mega_group(
+        &self,
+        permissions_policy_set: Option<PolicySet>,
+        opts: GroupMetadataOptions,
+    ) -> Result<MegaGroup<Self>, ClientError> {
+        tracing::info!("creating mega group");
+        Ok(MegaGroup::create(Arc::new(self.clone()), permissions_policy_set, opts).await?)
+    }
+
+    /// Look up a [`MegaGroup`] created by this installation, by the id of its first shard
+    pub fn mega_group(&self, id: Vec<u8>) -> Result<MegaGroup<Self>, ClientError> {
+        Ok(MegaGroup::load(Arc::new(self.clone()), id)?)
+    }
+
+    /// Look up the [`MegaGroup`] a group this installation is a member of is a shard of
+    pub async fn mega_group_for_shard(
+        &self,
+        group_id: Vec<u8>,
+    ) -> Result<MegaGroup<Self>, ClientError> {
+        Ok(MegaGroup::from_shard(Arc::new(self.clone()), group_id).await?)
+    }
+
     /// Create a new Direct Message with the default settings
     async fn create_dm_by_inbox_id(
         &self,
@@ -606,21 +863,44 @@ where
         self.find_or_create_dm_by_inbox_id(inbox_id).await
     }
 
-    /// Find or create a Direct Message by inbox_id with the default settings
+    /// Find or create a Direct Message by inbox_id with the default settings.
+    ///
+    /// When no DM with `inbox_id` is found locally, the welcomes are synced first, unless
+    /// disabled with [`crate::builder::ClientBuilder::dm_network_lookup`]. A fresh installation
+    /// then joins the DM that the peer or the other installations of this inbox already have,
+    /// instead of creating a parallel one.
     pub async fn find_or_create_dm_by_inbox_id(
         &self,
         inbox_id: InboxId,
     ) -> Result<MlsGroup<Self>, ClientError> {
         tracing::info!("finding or creating dm with inbox_id: {}", inbox_id);
         let provider = self.mls_provider()?;
+        if let Some(group) = self.find_dm_by_inbox_id(&provider, &inbox_id)? {
+            return Ok(group);
+        }
+        if self.dm_network_lookup {
+            self.sync_welcomes(&provider).await?;
+            if let Some(group) = self.find_dm_by_inbox_id(&provider, &inbox_id)? {
+                tracing::info!(
+                    "found existing dm with inbox_id {} on the network",
+                    inbox_id
+                );
+                return Ok(group);
+            }
+        }
+        self.create_dm_by_inbox_id(inbox_id).await
+    }
+
+    fn find_dm_by_inbox_id(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        inbox_id: &str,
+    ) -> Result<Option<MlsGroup<Self>>, ClientError> {
         let group = provider.conn_ref().find_dm_group(&DmMembers {
             member_one_inbox_id: self.inbox_id(),
-            member_two_inbox_id: &inbox_id,
+            member_two_inbox_id: inbox_id,
         })?;
-        if let Some(group) = group {
-            return Ok(MlsGroup::new(self.clone(), group.id, group.created_at_ns));
-        }
-        self.create_dm_by_inbox_id(inbox_id).await
+        Ok(group.map(|group| MlsGroup::new(self.clone(), group.id, group.created_at_ns)))
     }
 
     pub(crate) fn create_sync_group(
@@ -658,6 +938,15 @@ where
         self.group_with_conn(conn, &group_id)
     }
 
+    /// Look up a conversation by its [`MlsGroup::public_id`], e.g. from a deep link
+    pub fn group_by_public_id(&self, public_id: &str) -> Result<MlsGroup<Self>, ClientError> {
+        let conn = self.store().conn()?;
+        let group = conn
+            .find_group_by_public_id(public_id)?
+            .ok_or_else(|| NotFound::GroupByPublicId(public_id.to_string()))?;
+        Ok(MlsGroup::new(self.clone(), group.id, group.created_at_ns))
+    }
+
     /**
      * Look up a DM group by the target's inbox_id.
      *
@@ -716,6 +1005,7 @@ where
             .into_iter()
             .map(|conversation_item| {
                 let message = conversation_item.message_id.and_then(|message_id| {
+                    let sender_inbox_id = conversation_item.sender_inbox_id?;
                     // Only construct StoredGroupMessage if all fields are Some
                     Some(StoredGroupMessage {
                         id: message_id,
@@ -723,13 +1013,15 @@ where
                         decrypted_message_bytes: conversation_item.decrypted_message_bytes?,
                         sent_at_ns: conversation_item.sent_at_ns?,
                         sender_installation_id: conversation_item.sender_installation_id?,
-                        sender_inbox_id: conversation_item.sender_inbox_id?,
+                        sent_by_me: sender_inbox_id == self.inbox_id(),
+                        sender_inbox_id,
                         kind: conversation_item.kind?,
                         delivery_status: conversation_item.delivery_status?,
                         content_type: conversation_item.content_type?,
                         version_major: conversation_item.version_major?,
                         version_minor: conversation_item.version_minor?,
                         authority_id: conversation_item.authority_id?,
+                        sender_verified: conversation_item.sender_verified?,
                         reference_id: None, // conversation_item does not use message reference_id
                     })
                 });
@@ -752,6 +1044,22 @@ where
         &self,
         signature_request: SignatureRequest,
     ) -> Result<(), ClientError> {
+        self.register_identity_with_updates(signature_request, vec![])
+            .await?
+            .into_result()
+    }
+
+    /// Register the identity like [`Self::register_identity`], and apply other signature
+    /// requests prepared during onboarding, such as adding a wallet, in the same batch. See
+    /// [`Self::apply_signature_requests`].
+    ///
+    /// Fails if the registration itself could not be published and loaded. The other requests
+    /// that could not be published are returned in the batch.
+    pub async fn register_identity_with_updates(
+        &self,
+        signature_request: SignatureRequest,
+        updates: Vec<SignatureRequest>,
+    ) -> Result<IdentityUpdateBatch, ClientError> {
         tracing::info!("registering identity");
         // Register the identity before applying the signature request
         let provider: XmtpOpenMlsProvider = self.store().conn()?.into();
@@ -760,9 +1068,20 @@ where
             .register(&provider, &self.api_client)
             .await?;
 
-        self.apply_signature_request(signature_request).await?;
+        let mut requests = vec![signature_request];
+        requests.extend(updates);
+        let mut batch = self.apply_signature_requests(requests).await?;
+        // The identity is not ready until its registration is published and loaded
+        if !batch.published.contains(&0) || !batch.loaded {
+            return Err(batch
+                .error
+                .take()
+                .unwrap_or_else(|| ClientError::Generic("identity was not registered".into())));
+        }
         self.identity().set_ready();
-        Ok(())
+        // Checks made before the registration are stale now
+        crate::inbox_availability::clear_inbox_availability_cache();
+        Ok(batch)
     }
 
     /// Upload a new key package to the network replacing an existing key package
@@ -820,19 +1139,40 @@ where
         Ok(welcomes)
     }
 
-    /// Fetches the current key package from the network for each of the `installation_id`s specified
+    /// Fetches the current key package from the network for each of the `installation_id`s specified,
+    /// unless it was prefetched
     #[tracing::instrument(level = "trace", skip_all)]
     pub(crate) async fn get_key_packages_for_installation_ids(
         &self,
         installation_ids: Vec<Vec<u8>>,
     ) -> Result<Vec<VerifiedKeyPackageV2>, ClientError> {
-        let key_package_results = self.api_client.fetch_key_packages(installation_ids).await?;
+        let mut key_package_results = KeyPackageMap::new();
+        let mut missing = vec![];
+        for installation_id in installation_ids {
+            match self.prefetched_key_packages.get(&installation_id) {
+                Some(bytes) => {
+                    key_package_results.insert(installation_id, bytes);
+                }
+                None => missing.push(installation_id),
+            }
+        }
+        if !missing.is_empty() {
+            key_package_results.extend(self.api_client.fetch_key_packages(missing).await?);
+        }
 
         let crypto_provider = XmtpOpenMlsProvider::new_crypto();
-        Ok(key_package_results
+        let key_packages: Vec<VerifiedKeyPackageV2> = key_package_results
             .values()
             .map(|bytes| VerifiedKeyPackageV2::from_bytes(&crypto_provider, bytes.as_slice()))
-            .collect::<Result<_, _>>()?)
+            .collect::<Result<_, _>>()?;
+
+        // A new key package means the installation is still alive
+        let conn = self.store().conn()?;
+        for (installation_id, bytes) in key_package_results.iter() {
+            conn.record_installation_key_package(installation_id, &sha256(bytes))?;
+        }
+
+        Ok(key_packages)
     }
 
     /// Download all unread welcome messages and converts to a group struct, ignoring malformed messages.
@@ -842,25 +1182,36 @@ where
         &self,
         provider: &XmtpOpenMlsProvider,
     ) -> Result<Vec<MlsGroup<Self>>, GroupError> {
+        self.report_sync_phase(SyncPhase::FetchingWelcomes);
         let envelopes = self.query_welcome_messages(provider.conn_ref()).await?;
         let num_envelopes = envelopes.len();
+        self.report_sync_phase(SyncPhase::ProcessingWelcomes {
+            processed: 0,
+            total: num_envelopes,
+        });
 
+        let processed = AtomicUsize::new(0);
         let groups: Vec<MlsGroup<Self>> = stream::iter(envelopes.into_iter())
             .filter_map(|envelope: WelcomeMessage| async {
-                let welcome_v1 = match envelope.version {
-                    Some(welcome_message::Version::V1(v1)) => v1,
+                let group = match envelope.version {
+                    Some(welcome_message::Version::V1(welcome_v1)) => retry_async!(
+                        self.retry_policies.scoped(ErrorClass::Mls),
+                        (async { self.process_new_welcome(provider, &welcome_v1).await }),
+                        LocalEvents::report_retries(&self.local_events)
+                    )
+                    .ok(),
                     _ => {
                         tracing::error!(
                             "failed to extract welcome message, invalid payload only v1 supported."
                         );
-                        return None;
+                        None
                     }
                 };
-                retry_async!(
-                    Retry::default(),
-                    (async { self.process_new_welcome(provider, &welcome_v1).await })
-                )
-                .ok()
+                self.report_sync_phase(SyncPhase::ProcessingWelcomes {
+                    processed: processed.fetch_add(1, Ordering::SeqCst) + 1,
+                    total: num_envelopes,
+                });
+                group
             })
             .collect()
             .await;
@@ -929,13 +1280,33 @@ where
         &self,
         groups: Vec<MlsGroup<Self>>,
         provider: &XmtpOpenMlsProvider,
+    ) -> Result<usize, GroupError> {
+        self.sync_groups(
+            groups,
+            provider,
+            self.data_mode().prefetch_identity_updates(),
+        )
+        .await
+    }
+
+    /// Sync `groups`, checking their members for new installations first if
+    /// `update_installations` is set. Returns the number of active groups synced.
+    pub(crate) async fn sync_groups(
+        &self,
+        groups: Vec<MlsGroup<Self>>,
+        provider: &XmtpOpenMlsProvider,
+        update_installations: bool,
     ) -> Result<usize, GroupError> {
         let active_group_count = Arc::new(AtomicUsize::new(0));
+        let synced_count = AtomicUsize::new(0);
+        let total = groups.len();
+        self.report_sync_phase(SyncPhase::SyncingGroups { synced: 0, total });
 
         let sync_futures = groups
             .into_iter()
             .map(|group| {
                 let active_group_count = Arc::clone(&active_group_count);
+                let synced_count = &synced_count;
                 async move {
                     tracing::info!(
                         inbox_id = self.inbox_id(),
@@ -953,11 +1324,18 @@ where
                         })
                         .await?;
                     if is_active {
-                        group.maybe_update_installations(provider, None).await?;
+                        if update_installations {
+                            group.maybe_update_installations(provider, None).await?;
+                        }
+                        group.maybe_rotate_key(provider)?;
 
                         group.sync_with_conn(provider).await?;
                         active_group_count.fetch_add(1, Ordering::SeqCst);
                     }
+                    self.report_sync_phase(SyncPhase::SyncingGroups {
+                        synced: synced_count.fetch_add(1, Ordering::SeqCst) + 1,
+                        total,
+                    });
 
                     Ok::<(), GroupError>(())
                 }
@@ -980,10 +1358,39 @@ where
         provider: &XmtpOpenMlsProvider,
         consent_states: Option<Vec<ConsentState>>,
     ) -> Result<usize, ClientError> {
+        self.sync_groups_filtered(provider, consent_states, None)
+            .await
+    }
+
+    /// Sync all unread welcome messages, and then only the groups with one of the
+    /// `consent_states` and one of the `conversation_types`, or every group for a `None` filter.
+    /// Background refreshes can skip the requests and spam of an inbox this way.
+    /// Returns the number of active groups synced.
+    pub async fn sync_groups_filtered(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        consent_states: Option<Vec<ConsentState>>,
+        conversation_types: Option<Vec<ConversationType>>,
+    ) -> Result<usize, ClientError> {
+        let result = self
+            .sync_groups_filtered_inner(provider, consent_states, conversation_types)
+            .await;
+        self.report_sync_result(result)
+    }
+
+    async fn sync_groups_filtered_inner(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        consent_states: Option<Vec<ConsentState>>,
+        conversation_types: Option<Vec<ConversationType>>,
+    ) -> Result<usize, ClientError> {
+        // Welcomes are always processed, the consent of a new group is only known after
         self.sync_welcomes(provider).await?;
         let query_args = GroupQueryArgs {
             consent_states,
-            include_sync_groups: true,
+            include_sync_groups: conversation_types
+                .as_ref()
+                .map_or(true, |types| types.contains(&ConversationType::Sync)),
             include_duplicate_dms: true,
             ..GroupQueryArgs::default()
         };
@@ -991,9 +1398,19 @@ where
             .conn_ref()
             .find_groups(query_args)?
             .into_iter()
+            // Left groups are kept for display, but there is nothing to sync anymore
+            .filter(|g| !g.has_left())
+            .filter(|g| {
+                conversation_types
+                    .as_ref()
+                    .map_or(true, |types| types.contains(&g.conversation_type))
+            })
             .map(|g| MlsGroup::new(self.clone(), g.id, g.created_at_ns))
             .collect();
         let active_groups_count = self.sync_all_groups(groups, provider).await?;
+        self.report_sync_phase(SyncPhase::Complete {
+            active_groups: active_groups_count,
+        });
 
         Ok(active_groups_count)
     }
@@ -1030,7 +1447,7 @@ where
         &self,
         account_addresses: &[String],
     ) -> Result<HashMap<String, bool>, ClientError> {
-        let account_addresses = sanitize_evm_addresses(account_addresses)?;
+        let account_addresses = sanitize_account_addresses(account_addresses)?;
         let inbox_id_map = self
             .api_client
             .get_inbox_ids(account_addresses.clone())
@@ -1070,18 +1487,22 @@ pub(crate) mod tests {
     use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxOwner};
 
     use crate::{
+        api::NetworkSubsystem,
         builder::ClientBuilder,
-        groups::GroupMetadataOptions,
+        groups::{device_sync::preference_sync::UserPreferenceUpdate, GroupMetadataOptions},
         hpke::{decrypt_welcome, encrypt_welcome},
         identity::serialize_key_package_hash_ref,
         storage::{
             consent_record::{ConsentState, ConsentType, StoredConsentRecord},
-            group::GroupQueryArgs,
-            group_message::MsgQueryArgs,
+            group::{ConversationType, GroupQueryArgs, GroupSortKey},
+            group_message::{MsgQueryArgs, SortDirection},
+            maintenance::OptimizeLevel,
             schema::identity_updates,
         },
+        subscriptions::{FeatureFlagChange, LocalEvents},
         XmtpApi,
     };
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
@@ -1407,6 +1828,93 @@ pub(crate) mod tests {
         assert_eq!(bo_messages2.len(), 2);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_network_usage() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        // Registering the identity published an identity update and a key package
+        let usage = alix.reset_network_usage();
+        assert!(usage.subsystem(NetworkSubsystem::Identity).requests > 0);
+        assert!(usage.subsystem(NetworkSubsystem::KeyPackages).bytes_sent > 0);
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let usage = alix.network_usage();
+        assert!(
+            usage
+                .subsystem(NetworkSubsystem::KeyPackages)
+                .bytes_received
+                > 0
+        );
+        assert!(usage.subsystem(NetworkSubsystem::Publish).bytes_sent > 0);
+        assert_eq!(usage.subsystem(NetworkSubsystem::Streams).requests, 0);
+        assert_eq!(
+            usage.total().requests,
+            usage.subsystems.values().map(|u| u.requests).sum()
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(
+        not(target_arch = "wasm32"),
+        tokio::test(flavor = "multi_thread", worker_threads = 2)
+    )]
+    async fn test_sync_groups_filtered() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alix_bo_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_bo_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let alix_bo_dm = alix
+            .find_or_create_dm_by_inbox_id(bo.inbox_id().to_string())
+            .await
+            .unwrap();
+
+        let provider = bo.mls_provider().unwrap();
+        bo.sync_welcomes(&provider).await.unwrap();
+        let bo_group = bo.group(alix_bo_group.group_id.clone()).unwrap();
+        let bo_dm = bo.group(alix_bo_dm.group_id.clone()).unwrap();
+        bo_group
+            .update_consent_state(ConsentState::Allowed)
+            .unwrap();
+        bo_dm.update_consent_state(ConsentState::Allowed).unwrap();
+
+        alix_bo_group.send_message(b"group").await.unwrap();
+        alix_bo_dm.send_message(b"dm").await.unwrap();
+
+        let synced = bo
+            .sync_groups_filtered(
+                &provider,
+                Some(vec![ConsentState::Allowed]),
+                Some(vec![ConversationType::Group]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(synced, 1);
+        assert_eq!(
+            bo_group
+                .find_messages(&MsgQueryArgs::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(bo_dm
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(
         not(target_arch = "wasm32"),
@@ -1523,6 +2031,124 @@ pub(crate) mod tests {
         assert_eq!(address_consent, ConsentState::Denied);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_maintenance_reverts_expired_consent() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let record = StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Denied,
+            bo.inbox_id().to_string(),
+        )
+        .expiring_at(1);
+        alix.set_consent_states(&[record]).await.unwrap();
+        let mut events = alix.local_events.subscribe();
+
+        alix.run_maintenance(OptimizeLevel::Light, Duration::from_secs(5))
+            .unwrap();
+
+        let consent = alix
+            .get_consent_state(ConsentType::InboxId, bo.inbox_id().to_string())
+            .await
+            .unwrap();
+        assert_eq!(consent, ConsentState::Unknown);
+        let Ok(LocalEvents::OutgoingPreferenceUpdates(updates)) = events.try_recv() else {
+            panic!("expected the expired consent to be emitted");
+        };
+        assert!(matches!(
+            &updates[..],
+            [UserPreferenceUpdate::ConsentUpdate(record)]
+                if record.entity == bo.inbox_id() && record.state == ConsentState::Unknown
+        ));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_find_groups_sorted_by_name() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        for name in ["bravo", "Charlie", "alpha"] {
+            alix.create_group(
+                None,
+                GroupMetadataOptions {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+        let provider = alix.mls_provider().unwrap();
+        let names = |args: GroupQueryArgs| {
+            alix.find_groups(args)
+                .unwrap()
+                .into_iter()
+                .map(|group| group.group_name(&provider).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(GroupQueryArgs::default().sort_by(GroupSortKey::Name)),
+            vec!["alpha", "bravo", "Charlie"]
+        );
+        let conversation_names: Vec<_> = alix
+            .list_conversations(GroupQueryArgs::default().sort_by(GroupSortKey::Name))
+            .unwrap()
+            .into_iter()
+            .map(|conversation| conversation.group.group_name(&provider).unwrap())
+            .collect();
+        assert_eq!(conversation_names, vec!["alpha", "bravo", "Charlie"]);
+        // the limit applies after sorting
+        assert_eq!(
+            names(
+                GroupQueryArgs::default()
+                    .sort_by(GroupSortKey::Name)
+                    .direction(SortDirection::Descending)
+                    .limit(2)
+            ),
+            vec!["Charlie", "bravo"]
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_feature_flags_override_defaults() {
+        let mut alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        alix.feature_flag_defaults =
+            Arc::new(HashMap::from([("new_sync_engine".to_string(), true)]));
+        assert!(alix.is_feature_enabled("new_sync_engine").unwrap());
+        assert!(!alix.is_feature_enabled("unknown").unwrap());
+        let mut events = alix.local_events.subscribe();
+
+        alix.set_feature_flag("new_sync_engine", Some(false), false)
+            .unwrap();
+        assert!(!alix.is_feature_enabled("new_sync_engine").unwrap());
+        let Ok(LocalEvents::FeatureFlagsChanged(changes)) = events.try_recv() else {
+            panic!("expected the flag change to be emitted");
+        };
+        assert_eq!(
+            changes,
+            vec![FeatureFlagChange {
+                name: "new_sync_engine".to_string(),
+                enabled: Some(false),
+            }]
+        );
+        // flags that are not synced stay on this installation
+        assert!(events.try_recv().is_err());
+
+        // setting the same value again changes nothing
+        alix.set_feature_flag("new_sync_engine", Some(false), false)
+            .unwrap();
+        assert!(events.try_recv().is_err());
+
+        alix.set_feature_flag("new_sync_engine", None, false)
+            .unwrap();
+        assert!(alix.is_feature_enabled("new_sync_engine").unwrap());
+        assert_eq!(
+            alix.feature_flags().unwrap(),
+            HashMap::from([("new_sync_engine".to_string(), true)])
+        );
+    }
+
     async fn get_key_package_init_key<
         ApiClient: XmtpApi,
         Verifier: SmartContractSignatureVerifier,
@@ -1651,4 +2277,33 @@ pub(crate) mod tests {
         assert_eq!(conversations.len(), 1);
         assert_eq!(conversations[0].group_id, dm1.group_id);
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_find_or_create_dm_joins_dm_from_network() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo_wallet = generate_local_wallet();
+        let bo = ClientBuilder::new_test_client(&bo_wallet).await;
+        let dm = alix
+            .find_or_create_dm_by_inbox_id(bo.inbox_id().to_string())
+            .await
+            .unwrap();
+
+        // Bo has not synced the welcome, the DM is found on the network
+        let bo_dm = bo
+            .find_or_create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        assert_eq!(bo_dm.group_id, dm.group_id);
+
+        // A reinstalled client of bo joins the same DM once alix adds it
+        let bo2 = ClientBuilder::new_test_client(&bo_wallet).await;
+        dm.update_installations().await.unwrap();
+        let bo2_dm = bo2
+            .find_or_create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        assert_eq!(bo2_dm.group_id, dm.group_id);
+        assert_eq!(bo2.find_groups(GroupQueryArgs::default()).unwrap().len(), 1);
+    }
 }
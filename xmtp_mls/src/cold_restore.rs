@@ -0,0 +1,193 @@
+//! Restoring an inbox on a new installation in one call.
+//!
+//! An installation created for an inbox that was used from another app starts with an empty
+//! database. [`Client::cold_restore`] brings it up to date in the order that needs the fewest
+//! round trips:
+//!
+//! 1. The identity updates of this inbox are loaded, so that welcomes and requests from the
+//!    other installations of the inbox can be verified.
+//! 2. Welcomes are processed, creating the groups this installation was added to.
+//! 3. The other installations are asked for the consent and the message history. Their replies
+//!    are applied by the sync worker whenever they arrive, which is usually after the restore
+//!    returned.
+//! 4. The identity updates of every member of every group are loaded in a single request, rather
+//!    than group by group.
+//! 5. Every group is synced, including the sync group, which applies the preference updates of
+//!    the other installations.
+//!
+//! Every step is reported as a [`SyncPhase`], streamed by
+//! [`Client::stream_sync_progress_with_callback`]. The restore ends with
+//! [`SyncPhase::Complete`], or [`SyncPhase::Failed`] at the first step that fails. The history
+//! reply is not part of the restore, it is reported afterwards as
+//! [`SyncPhase::BackfillingMessages`] on the same stream.
+use std::collections::HashSet;
+
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    client::ClientError,
+    groups::{validated_commit::extract_group_membership, GroupError, MlsGroup},
+    identity_updates::load_identity_updates,
+    storage::group::GroupQueryArgs,
+    sync_progress::SyncPhase,
+    Client, XmtpApi,
+};
+
+/// The outcome of [`Client::cold_restore`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColdRestoreSummary {
+    /// Groups joined from welcomes
+    pub groups_joined: usize,
+    /// Active groups synced, including the groups joined
+    pub active_groups: usize,
+    /// Whether the other installations were asked for the history. `false` without a history
+    /// sync url, or if sending the request failed. The reply is applied later.
+    pub history_requested: bool,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Bring a new installation up to date with its inbox, see the [module docs](self).
+    ///
+    /// New installations of the members are not added to the groups while restoring, the regular
+    /// check of the installations of each group picks them up later.
+    pub async fn cold_restore(&self) -> Result<ColdRestoreSummary, ClientError> {
+        let result = self.restore().await;
+        self.report_sync_result(result)
+    }
+
+    async fn restore(&self) -> Result<ColdRestoreSummary, ClientError> {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+
+        self.report_sync_phase(SyncPhase::FetchingIdentityUpdates { inboxes: 1 });
+        load_identity_updates(&self.api_client, conn, &[self.inbox_id()]).await?;
+
+        let groups_joined = self.sync_welcomes(&provider).await?.len();
+
+        let history_requested = if self.history_sync_url.is_some() {
+            self.report_sync_phase(SyncPhase::RequestingHistory);
+            match self.request_history_sync_now().await {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::warn!("failed to request the history while restoring: {err}");
+                    false
+                }
+            }
+        } else {
+            false
+        };
+
+        let groups = conn
+            .find_groups(GroupQueryArgs {
+                include_sync_groups: true,
+                include_duplicate_dms: true,
+                ..GroupQueryArgs::default()
+            })?
+            .into_iter()
+            .filter(|g| !g.has_left())
+            .map(|g| MlsGroup::new(self.clone(), g.id, g.created_at_ns))
+            .collect::<Vec<_>>();
+
+        let mut members = HashSet::new();
+        for group in &groups {
+            let membership = group.load_mls_group_with_lock(&provider, |mls_group| {
+                Ok::<_, GroupError>(extract_group_membership(mls_group.extensions())?)
+            })?;
+            members.extend(membership.members.into_keys());
+        }
+        members.remove(self.inbox_id());
+        let members = members.iter().map(String::as_str).collect::<Vec<_>>();
+        self.report_sync_phase(SyncPhase::FetchingIdentityUpdates {
+            inboxes: members.len(),
+        });
+        load_identity_updates(&self.api_client, conn, &members).await?;
+
+        let active_groups = self.sync_groups(groups, &provider, false).await?;
+        self.report_sync_phase(SyncPhase::Complete { active_groups });
+
+        Ok(ColdRestoreSummary {
+            groups_joined,
+            active_groups,
+            history_requested,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn restores_groups_of_a_new_installation() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        for _ in 0..2 {
+            let group = amal
+                .create_group(None, GroupMetadataOptions::default())
+                .unwrap();
+            group
+                .add_members_by_inbox_id(&[bola.inbox_id()])
+                .await
+                .unwrap();
+            group.send_message(b"hello").await.unwrap();
+        }
+
+        let mut changes = bola.sync_progress.subscribe();
+        let summary = bola.cold_restore().await.unwrap();
+        assert_eq!(
+            summary,
+            ColdRestoreSummary {
+                groups_joined: 2,
+                active_groups: 2,
+                history_requested: false,
+            }
+        );
+
+        let mut phases = vec![];
+        while let Ok(status) = changes.try_recv() {
+            phases.push(status.phase);
+        }
+        assert_eq!(
+            phases.first(),
+            Some(&SyncPhase::FetchingIdentityUpdates { inboxes: 1 })
+        );
+        // Once for this inbox, once for the members of its groups
+        assert_eq!(
+            phases
+                .iter()
+                .filter(|phase| matches!(phase, SyncPhase::FetchingIdentityUpdates { .. }))
+                .count(),
+            2
+        );
+        assert!(!phases.contains(&SyncPhase::RequestingHistory));
+        assert_eq!(
+            phases.last(),
+            Some(&SyncPhase::Complete { active_groups: 2 })
+        );
+
+        // The messages sent after the welcomes were received
+        let args = MsgQueryArgs {
+            kind: Some(GroupMessageKind::Application),
+            ..MsgQueryArgs::default()
+        };
+        for group in bola.find_groups(GroupQueryArgs::default()).unwrap() {
+            assert_eq!(group.find_messages(&args).unwrap().len(), 1);
+        }
+    }
+}
@@ -25,16 +25,86 @@ const SYNC_UPDATE_INSTALLATIONS_INTERVAL_NS: i64 = NS_IN_HOUR / 2; // 30 min
 
 pub const SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS: i64 = 5 * NS_IN_SEC;
 
+/// Installations that have not been seen in a group this long after being sent a welcome
+/// are re-added with a fresh key package
+pub const WELCOME_ACK_TIMEOUT_NS: i64 = 3 * NS_IN_DAY;
+
+/// Number of times an installation is re-added before asking the user to intervene
+pub const MAX_WELCOME_READD_ATTEMPTS: i32 = 2;
+
 pub const MAX_GROUP_SIZE: usize = 400;
 
+/// Number of inboxes whose identity updates are verified at the same time, such as when
+/// joining a large group
+pub const MAX_CONCURRENT_IDENTITY_VALIDATIONS: usize = 16;
+
+/// Number of updates of a live location share that are kept as messages for each sender
+pub const LIVE_LOCATION_HISTORY_LIMIT: i64 = 20;
+
 pub const MAX_PAST_EPOCHS: usize = 3;
 
+/// Number of rows a background migration rewrites in one transaction
+pub const BACKGROUND_MIGRATION_BATCH_SIZE: i64 = 500;
+
+/// Pause between two batches of a background migration, to leave the database to the client
+pub const BACKGROUND_MIGRATION_BATCH_DELAY: std::time::Duration =
+    std::time::Duration::from_millis(50);
+
+/// Number of groups whose commits and welcomes are published at the same time by
+/// [`crate::Client::create_groups_bulk`]
+pub const BULK_GROUP_PUBLISH_CONCURRENCY: usize = 10;
+
+/// Suggested number of recently streamed message ids remembered to drop duplicates delivered by
+/// overlapping streams, for apps that opt in to deduplication
+pub const STREAM_DEDUPE_WINDOW: usize = 1_000;
+
+/// Time a message stream may go without receiving anything before it is subscribed again, in
+/// case NAT or a proxy dropped the connection without closing it
+pub const STREAM_SILENCE_WINDOW: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Default pause between two encrypted snapshots of an in-memory store to disk, see
+/// [`crate::storage::EncryptedMessageStore::spawn_periodic_snapshots`]
+pub const IN_MEMORY_SNAPSHOT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Pause between two checks for group avatars to fetch
+pub const GROUP_AVATAR_FETCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Largest group avatar image stored, in bytes
+pub const MAX_GROUP_AVATAR_SIZE: usize = 5 * 1024 * 1024;
+
+/// Longest wait before fetching a group avatar again after failed fetches. The wait starts at
+/// [`GROUP_AVATAR_FETCH_INTERVAL`] and doubles with each failure.
+pub const MAX_GROUP_AVATAR_RETRY_DELAY: std::time::Duration =
+    std::time::Duration::from_secs(60 * 60 * 24);
+
+/// Most messages pinned in a group at once
+pub const MAX_PINNED_MESSAGES: usize = 10;
+
+/// Senders listed for each group in a notification digest
+pub const DIGEST_TOP_SENDERS: usize = 3;
+
 pub const MAX_DB_POOL_SIZE: u32 = 25;
 
+/// How many times longer the intervals of the background network loops are in low data mode
+pub const LOW_DATA_INTERVAL_FACTOR: u32 = 8;
+
 /// the max amount of data that can be sent in one gRPC call
 /// we leave 5 * 1024 * 1024 as extra buffer room
 pub const GRPC_DATA_LIMIT: usize = 45 * 1024 * 1024;
 
+/// The most welcomes sent in one request, see [`crate::groups::welcomes`]
+pub const MAX_WELCOMES_PER_BATCH: usize = 250;
+
+/// Number of times the welcome of a commit is sent to an installation before giving up on it
+pub const MAX_WELCOME_SEND_ATTEMPTS: i32 = 5;
+
+/// Number of times a post commit action is started before it is dead-lettered, see
+/// [`crate::storage::post_commit_action`]
+pub const MAX_POST_COMMIT_ACTION_ATTEMPTS: i32 = 5;
+
+/// How long the answers of [`crate::inbox_availability::check_inbox_availability`] are cached
+pub const INBOX_AVAILABILITY_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// MLS Extension Types
 ///
 /// Copied from draft-ietf-mls-protocol-16:
@@ -0,0 +1,99 @@
+//! Low data mode, for metered connections.
+//!
+//! The mode can be changed at any time with [`Client::set_data_mode`]. In [`DataMode::Low`]
+//! the client:
+//! - checks group members for new installations less often, and waits longer before
+//!   subscribing a silent message stream again, see
+//!   [`crate::configuration::LOW_DATA_INTERVAL_FACTOR`]
+//! - does not fetch the identity updates of the members of every group when syncing all
+//!   groups or applying a history sync reply. They are still fetched before sending to a group.
+//! - sends the messages queued in a group together, in a single request
+//! - defers fetching group avatars until the mode is back to [`DataMode::Normal`]
+//!
+//! The client never downloads remote attachments itself. Apps should check
+//! [`DataMode::download_attachments`] before fetching their payloads.
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    configuration::{sync_update_installations_interval_ns, LOW_DATA_INTERVAL_FACTOR},
+    Client,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataMode {
+    #[default]
+    Normal,
+    Low,
+}
+
+impl DataMode {
+    pub(crate) fn update_installations_interval_ns(&self) -> i64 {
+        match self {
+            DataMode::Normal => sync_update_installations_interval_ns(),
+            DataMode::Low => {
+                sync_update_installations_interval_ns() * LOW_DATA_INTERVAL_FACTOR as i64
+            }
+        }
+    }
+
+    /// How long a message stream may stay silent before it is subscribed again
+    pub(crate) fn stream_silence_window(&self, window: Duration) -> Duration {
+        match self {
+            DataMode::Normal => window,
+            DataMode::Low => window.saturating_mul(LOW_DATA_INTERVAL_FACTOR),
+        }
+    }
+
+    /// Whether syncing all groups also checks their members for new installations
+    pub(crate) fn prefetch_identity_updates(&self) -> bool {
+        *self == DataMode::Normal
+    }
+
+    pub(crate) fn batch_publishes(&self) -> bool {
+        *self == DataMode::Low
+    }
+
+    /// Whether attachments should be downloaded when their message is received, rather than
+    /// when the user opens them
+    pub fn download_attachments(&self) -> bool {
+        *self == DataMode::Normal
+    }
+}
+
+/// The data mode of a client, shared by its clones
+#[derive(Debug, Default)]
+pub struct SharedDataMode {
+    low: AtomicBool,
+}
+
+impl SharedDataMode {
+    pub fn get(&self) -> DataMode {
+        if self.low.load(Ordering::Relaxed) {
+            DataMode::Low
+        } else {
+            DataMode::Normal
+        }
+    }
+
+    fn set(&self, mode: DataMode) {
+        self.low.store(mode == DataMode::Low, Ordering::Relaxed);
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    pub fn data_mode(&self) -> DataMode {
+        self.data_mode.get()
+    }
+
+    pub fn set_data_mode(&self, mode: DataMode) {
+        tracing::info!(
+            inbox_id = self.context.inbox_id(),
+            ?mode,
+            "setting data mode"
+        );
+        self.data_mode.set(mode);
+    }
+}
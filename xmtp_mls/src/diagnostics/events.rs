@@ -0,0 +1,35 @@
+//! Log events kept in memory, so they can be attached to a diagnostic bundle. They are
+//! recorded by `RecentEventsLayer`, with the `diagnostic-events` feature.
+use std::{collections::VecDeque, sync::LazyLock};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Number of events kept in memory. Older events are dropped first.
+const MAX_RECENT_EVENTS: usize = 512;
+
+static RECENT_EVENTS: LazyLock<Mutex<VecDeque<RecordedEvent>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(MAX_RECENT_EVENTS)));
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecordedEvent {
+    pub timestamp_ns: i64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+#[cfg_attr(not(feature = "diagnostic-events"), allow(dead_code))]
+pub(super) fn record(event: RecordedEvent) {
+    let mut events = RECENT_EVENTS.lock();
+    if events.len() >= MAX_RECENT_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Events recorded by `RecentEventsLayer`, oldest first. Always empty without the
+/// `diagnostic-events` feature.
+pub fn recent_events() -> Vec<RecordedEvent> {
+    RECENT_EVENTS.lock().iter().cloned().collect()
+}
@@ -0,0 +1,68 @@
+//! A [`tracing_subscriber::Layer`] that keeps the most recent events in memory,
+//! so they can be attached to a diagnostic bundle.
+use std::fmt::Write as _;
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::layer::{Context, Layer};
+use xmtp_common::time::now_ns;
+
+use super::events::{record, RecordedEvent};
+
+/// Records events at or above `level` for [`Client::export_diagnostics`](crate::Client::export_diagnostics).
+///
+/// Apps add this layer to their subscriber alongside their usual logging.
+/// Without it, diagnostic bundles contain no events.
+pub struct RecentEventsLayer {
+    level: Level,
+}
+
+impl RecentEventsLayer {
+    pub fn new(level: Level) -> Self {
+        Self { level }
+    }
+}
+
+impl Default for RecentEventsLayer {
+    fn default() -> Self {
+        Self::new(Level::INFO)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RecentEventsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > self.level {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        record(RecordedEvent {
+            timestamp_ns: now_ns(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.message.is_empty() {
+            self.message.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.message, "{}={value:?}", field.name());
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! The client's counters in the Prometheus text exposition format or in OpenMetrics, so
+//! services embedding the client can serve them on their `/metrics` endpoint as-is.
+use std::fmt::Write;
+
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use crate::{
+    api::{NetworkSubsystem, NetworkUsage},
+    client::ClientError,
+    storage::maintenance::StorageStats,
+    Client,
+};
+
+const SUBSYSTEMS: [NetworkSubsystem; 5] = [
+    NetworkSubsystem::Sync,
+    NetworkSubsystem::Streams,
+    NetworkSubsystem::Publish,
+    NetworkSubsystem::Identity,
+    NetworkSubsystem::KeyPackages,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetricsFormat {
+    /// Prometheus text exposition format 0.0.4
+    #[default]
+    Prometheus,
+    /// OpenMetrics 1.0.0 text format
+    OpenMetrics,
+}
+
+impl MetricsFormat {
+    /// The `Content-Type` header to serve the metrics with
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Self::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+/// A metric with all of its samples
+struct MetricFamily {
+    name: &'static str,
+    help: &'static str,
+    metric_type: MetricType,
+    samples: Vec<(Option<(&'static str, String)>, u64)>,
+}
+
+impl MetricFamily {
+    fn new(name: &'static str, help: &'static str, metric_type: MetricType) -> Self {
+        Self {
+            name,
+            help,
+            metric_type,
+            samples: vec![],
+        }
+    }
+
+    fn sample(mut self, value: impl TryInto<u64>) -> Self {
+        self.samples
+            .push((None, value.try_into().unwrap_or_default()));
+        self
+    }
+
+    fn labeled(mut self, label: &'static str, label_value: String, value: u64) -> Self {
+        self.samples.push((Some((label, label_value)), value));
+        self
+    }
+
+    fn write(&self, out: &mut String, format: MetricsFormat) {
+        // OpenMetrics names counter families without the `_total` suffix of their samples
+        let family = match (format, self.metric_type) {
+            (MetricsFormat::OpenMetrics, MetricType::Counter) => {
+                self.name.strip_suffix("_total").unwrap_or(self.name)
+            }
+            _ => self.name,
+        };
+        let metric_type = match self.metric_type {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        };
+        let _ = writeln!(out, "# HELP {family} {}", self.help);
+        let _ = writeln!(out, "# TYPE {family} {metric_type}");
+        for (label, value) in &self.samples {
+            match label {
+                Some((name, label_value)) => {
+                    let label_value = escape_label_value(label_value);
+                    let _ = writeln!(out, "{}{{{name}=\"{label_value}\"}} {value}", self.name);
+                }
+                None => {
+                    let _ = writeln!(out, "{} {value}", self.name);
+                }
+            }
+        }
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+fn subsystem_label(subsystem: NetworkSubsystem) -> String {
+    match subsystem {
+        NetworkSubsystem::Sync => "sync",
+        NetworkSubsystem::Streams => "streams",
+        NetworkSubsystem::Publish => "publish",
+        NetworkSubsystem::Identity => "identity",
+        NetworkSubsystem::KeyPackages => "key_packages",
+    }
+    .to_string()
+}
+
+fn metric_families(storage: &StorageStats, network: &NetworkUsage) -> Vec<MetricFamily> {
+    let mut rows = MetricFamily::new(
+        "xmtp_storage_rows",
+        "Number of rows in each table of the database",
+        MetricType::Gauge,
+    );
+    for (table, count) in &storage.row_counts {
+        rows = rows.labeled(
+            "table",
+            table.clone(),
+            (*count).try_into().unwrap_or_default(),
+        );
+    }
+
+    let mut requests = MetricFamily::new(
+        "xmtp_network_requests_total",
+        "Number of network requests, or of envelopes received for streams",
+        MetricType::Counter,
+    );
+    let mut sent = MetricFamily::new(
+        "xmtp_network_sent_bytes_total",
+        "Encoded size of the requests sent to the network",
+        MetricType::Counter,
+    );
+    let mut received = MetricFamily::new(
+        "xmtp_network_received_bytes_total",
+        "Encoded size of the responses received from the network",
+        MetricType::Counter,
+    );
+    for subsystem in SUBSYSTEMS {
+        let usage = network.subsystem(subsystem);
+        let label = subsystem_label(subsystem);
+        requests = requests.labeled("subsystem", label.clone(), usage.requests);
+        sent = sent.labeled("subsystem", label.clone(), usage.bytes_sent);
+        received = received.labeled("subsystem", label, usage.bytes_received);
+    }
+
+    vec![
+        MetricFamily::new(
+            "xmtp_storage_page_size_bytes",
+            "Page size of the database",
+            MetricType::Gauge,
+        )
+        .sample(storage.page_size),
+        MetricFamily::new(
+            "xmtp_storage_pages",
+            "Number of pages in the database",
+            MetricType::Gauge,
+        )
+        .sample(storage.page_count),
+        MetricFamily::new(
+            "xmtp_storage_free_pages",
+            "Number of unused pages in the database",
+            MetricType::Gauge,
+        )
+        .sample(storage.freelist_count),
+        rows,
+        requests,
+        sent,
+        received,
+    ]
+}
+
+/// Render the storage stats and network usage in `format`
+pub fn render_metrics(
+    storage: &StorageStats,
+    network: &NetworkUsage,
+    format: MetricsFormat,
+) -> String {
+    let mut out = String::new();
+    for family in metric_families(storage, network) {
+        family.write(&mut out, format);
+    }
+    if format == MetricsFormat::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// The storage stats and network usage of the client, ready to be served on a `/metrics`
+    /// endpoint with the [`MetricsFormat::content_type`] of `format`
+    pub fn export_metrics(&self, format: MetricsFormat) -> Result<String, ClientError> {
+        Ok(render_metrics(
+            &self.store().stats()?,
+            &self.network_usage(),
+            format,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::api::SubsystemUsage;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn renders_counters_in_both_formats() {
+        let storage = StorageStats {
+            page_size: 4096,
+            page_count: 10,
+            freelist_count: 2,
+            row_counts: [("groups".to_string(), 3)].into(),
+        };
+        let mut network = NetworkUsage::default();
+        network.subsystems.insert(
+            NetworkSubsystem::Sync,
+            SubsystemUsage {
+                requests: 4,
+                bytes_sent: 100,
+                bytes_received: 2000,
+            },
+        );
+
+        let prometheus = render_metrics(&storage, &network, MetricsFormat::Prometheus);
+        assert!(prometheus.contains("# TYPE xmtp_network_requests_total counter\n"));
+        assert!(prometheus.contains("xmtp_network_requests_total{subsystem=\"sync\"} 4\n"));
+        assert!(prometheus.contains("xmtp_network_requests_total{subsystem=\"publish\"} 0\n"));
+        assert!(prometheus.contains("xmtp_storage_rows{table=\"groups\"} 3\n"));
+        assert!(prometheus.contains("xmtp_storage_pages 10\n"));
+        assert!(!prometheus.contains("# EOF"));
+
+        let open_metrics = render_metrics(&storage, &network, MetricsFormat::OpenMetrics);
+        assert!(open_metrics.contains("# TYPE xmtp_network_received_bytes counter\n"));
+        assert!(
+            open_metrics.contains("xmtp_network_received_bytes_total{subsystem=\"sync\"} 2000\n")
+        );
+        assert!(open_metrics.ends_with("# EOF\n"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn escapes_label_values() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
@@ -0,0 +1,264 @@
+//! Diagnostic bundles for bug reports.
+//!
+//! [`Client::export_diagnostics`] gathers everything support needs to look into a problem
+//! into a single serializable [`DiagnosticBundle`]. Identifiers are pseudonymized and
+//! log messages scrubbed according to the requested [`RedactionLevel`].
+//!
+//! Pseudonyms are keyed hashes under a random key that is drawn for each bundle and never
+//! leaves the device, so they can't be matched against identifiers known from elsewhere, nor
+//! across bundles.
+mod events;
+#[cfg(feature = "diagnostic-events")]
+mod layer;
+mod metrics;
+mod workers;
+
+pub use events::{recent_events, RecordedEvent};
+#[cfg(feature = "diagnostic-events")]
+pub use layer::RecentEventsLayer;
+pub use metrics::{render_metrics, MetricsFormat};
+pub use workers::{WorkerKind, WorkerRegistry, WorkerState, WorkerStatus};
+
+use std::collections::BTreeMap;
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use crate::{
+    client::ClientError,
+    groups::debug_info::GroupDebugInfo,
+    storage::{group::GroupQueryArgs, maintenance::StorageStats},
+    Client,
+};
+
+/// Placeholder for identifiers removed from log messages
+const REDACTED: &str = "[redacted]";
+/// Shortest run of hex characters treated as an identifier.
+/// Group ids are 16 bytes, addresses 20 bytes and inbox/installation ids 32 bytes.
+const MIN_IDENTIFIER_HEX_LEN: usize = 32;
+
+/// How much personally identifiable information a [`DiagnosticBundle`] may contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum RedactionLevel {
+    /// Keep everything as-is. Only for bundles that never leave the device.
+    None,
+    /// Replace identifiers with stable pseudonyms, and remove identifiers from log messages
+    #[default]
+    Identifiers,
+    /// Like [`RedactionLevel::Identifiers`], and drop log messages entirely
+    Strict,
+}
+
+/// Applies a [`RedactionLevel`] to the contents of a single bundle
+struct Redactor {
+    level: RedactionLevel,
+    /// Key of the pseudonyms, only ever kept in memory
+    key: [u8; 32],
+}
+
+impl Redactor {
+    fn new(level: RedactionLevel) -> Self {
+        Self {
+            level,
+            key: rand::random(),
+        }
+    }
+
+    /// Pseudonymize an identifier. The same identifier always maps to the same pseudonym
+    /// within the bundle, so entries in the bundle can still be correlated with each other.
+    fn identifier(&self, id: &[u8]) -> String {
+        match self.level {
+            RedactionLevel::None => hex::encode(id),
+            RedactionLevel::Identifiers | RedactionLevel::Strict => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key size");
+                mac.update(id);
+                hex::encode(&mac.finalize().into_bytes()[..8])
+            }
+        }
+    }
+
+    fn message(&self, message: &str) -> String {
+        match self.level {
+            RedactionLevel::None => message.to_string(),
+            RedactionLevel::Identifiers => redact_identifiers(message),
+            RedactionLevel::Strict => String::new(),
+        }
+    }
+}
+
+/// Replace every long run of hex characters, optionally `0x` prefixed, with [`REDACTED`]
+fn redact_identifiers(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut run = String::new();
+    let flush = |run: &mut String, out: &mut String| {
+        let digits = run.strip_prefix("0x").unwrap_or(run);
+        if digits.len() >= MIN_IDENTIFIER_HEX_LEN {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(run);
+        }
+        run.clear();
+    };
+    for c in text.chars() {
+        if c.is_ascii_hexdigit() || (c == 'x' && run == "0") {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut run, &mut out);
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientDiagnostics {
+    pub libxmtp_version: String,
+    pub inbox_id: String,
+    pub installation_id: String,
+    pub history_sync_enabled: bool,
+}
+
+/// Everything gathered by [`Client::export_diagnostics`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiagnosticBundle {
+    pub generated_at_ns: i64,
+    pub redaction: RedactionLevel,
+    pub client: ClientDiagnostics,
+    pub workers: Vec<WorkerStatus>,
+    /// Recent log events, if a `RecentEventsLayer` is installed
+    pub events: Vec<RecordedEvent>,
+    pub storage: StorageStats,
+    pub groups: Vec<GroupDebugInfo>,
+    /// Groups whose debug info could not be collected, with the error
+    pub group_errors: BTreeMap<String, String>,
+}
+
+impl DiagnosticBundle {
+    /// Serialize the bundle into a single file that can be attached to a bug report
+    pub fn to_archive(&self) -> Result<Vec<u8>, serde_json::Error> {
+        serde_json::to_vec_pretty(self)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Collect a [`DiagnosticBundle`] from local state. Does not make any network requests.
+    pub fn export_diagnostics(
+        &self,
+        redaction: RedactionLevel,
+    ) -> Result<DiagnosticBundle, ClientError> {
+        let redactor = Redactor::new(redaction);
+        let client = ClientDiagnostics {
+            libxmtp_version: env!("CARGO_PKG_VERSION").to_string(),
+            inbox_id: redactor.identifier(self.inbox_id().as_bytes()),
+            installation_id: redactor.identifier(self.installation_public_key().as_ref()),
+            history_sync_enabled: self.history_sync_url.is_some(),
+        };
+
+        let events = recent_events()
+            .into_iter()
+            .map(|event| RecordedEvent {
+                message: redactor.message(&event.message),
+                ..event
+            })
+            .collect();
+
+        let provider = self.mls_provider()?;
+        let mut groups = Vec::new();
+        let mut group_errors = BTreeMap::new();
+        for group in self.find_groups(GroupQueryArgs::default())? {
+            let group_id = redactor.identifier(&group.group_id);
+            match group.debug_info_with_provider(&provider) {
+                Ok(info) => groups.push(GroupDebugInfo { group_id, ..info }),
+                Err(e) => {
+                    group_errors.insert(group_id, redactor.message(&e.to_string()));
+                }
+            }
+        }
+
+        Ok(DiagnosticBundle {
+            generated_at_ns: now_ns(),
+            redaction,
+            client,
+            workers: self.workers.statuses(),
+            events,
+            storage: self.store().stats()?,
+            groups,
+            group_errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn redacts_identifiers_from_messages() {
+        let inbox_id = "a".repeat(64);
+        let message = format!("added {inbox_id} from 0x{} at epoch 12", "b".repeat(40));
+        assert_eq!(
+            redact_identifiers(&message),
+            "added [redacted] from [redacted] at epoch 12"
+        );
+        assert_eq!(redact_identifiers("cafe 0x12"), "cafe 0x12");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn pseudonyms_are_stable_within_a_bundle() {
+        let redactor = Redactor::new(RedactionLevel::Identifiers);
+        let pseudonym = redactor.identifier(b"group");
+        assert_eq!(pseudonym, redactor.identifier(b"group"));
+        assert_ne!(pseudonym, redactor.identifier(b"other group"));
+        assert_ne!(
+            pseudonym,
+            hex::encode(&crate::utils::hash::sha256(b"group")[..8])
+        );
+        assert_eq!(
+            Redactor::new(RedactionLevel::None).identifier(b"group"),
+            hex::encode(b"group")
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn export_pseudonymizes_identifiers() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let bundle = amal
+            .export_diagnostics(RedactionLevel::Identifiers)
+            .unwrap();
+        assert_ne!(bundle.client.inbox_id, amal.inbox_id());
+        assert_eq!(bundle.groups.len(), 1);
+        assert_ne!(bundle.groups[0].group_id, hex::encode(&group.group_id));
+        assert!(bundle.group_errors.is_empty());
+
+        // pseudonyms can't be correlated across bundles
+        let other = amal
+            .export_diagnostics(RedactionLevel::Identifiers)
+            .unwrap();
+        assert_ne!(other.client.inbox_id, bundle.client.inbox_id);
+        assert_ne!(other.groups[0].group_id, bundle.groups[0].group_id);
+
+        let archive = String::from_utf8(bundle.to_archive().unwrap()).unwrap();
+        assert!(!archive.contains(amal.inbox_id()));
+        assert!(!archive.contains(&hex::encode(&group.group_id)));
+    }
+}
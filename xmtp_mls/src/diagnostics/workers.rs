@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use xmtp_common::time::now_ns;
+
+/// Background workers spawned by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum WorkerKind {
+    DeviceSync,
+    DisappearingMessages,
+    BackgroundMigrations,
+    GroupAvatars,
+    PostCommitActions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WorkerState {
+    Running,
+    /// The worker failed and is waiting to restart
+    Restarting,
+    /// The worker exited, and will only be restarted by reconnecting the database
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkerStatus {
+    pub kind: WorkerKind,
+    pub state: WorkerState,
+    /// Number of times the worker restarted after an error
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// Time in nanoseconds the worker last changed state
+    pub updated_at_ns: i64,
+}
+
+/// Tracks the state of the client's background workers
+#[derive(Debug, Default)]
+pub struct WorkerRegistry {
+    workers: Mutex<HashMap<WorkerKind, WorkerStatus>>,
+}
+
+impl WorkerRegistry {
+    pub(crate) fn started(&self, kind: WorkerKind) {
+        self.update(kind, |status| status.state = WorkerState::Running);
+    }
+
+    pub(crate) fn failed(&self, kind: WorkerKind, error: &impl std::fmt::Display) {
+        self.update(kind, |status| {
+            status.state = WorkerState::Restarting;
+            status.restarts += 1;
+            status.last_error = Some(error.to_string());
+        });
+    }
+
+    pub(crate) fn stopped(&self, kind: WorkerKind) {
+        self.update(kind, |status| status.state = WorkerState::Stopped);
+    }
+
+    /// Status of every worker that was started, in no particular order
+    pub fn statuses(&self) -> Vec<WorkerStatus> {
+        self.workers.lock().values().cloned().collect()
+    }
+
+    fn update(&self, kind: WorkerKind, f: impl FnOnce(&mut WorkerStatus)) {
+        let mut workers = self.workers.lock();
+        let status = workers.entry(kind).or_insert_with(|| WorkerStatus {
+            kind,
+            state: WorkerState::Running,
+            restarts: 0,
+            last_error: None,
+            updated_at_ns: 0,
+        });
+        f(status);
+        status.updated_at_ns = now_ns();
+    }
+}
@@ -0,0 +1,52 @@
+//! Entry points for the fuzz targets in `<PackageRoot>/fuzz/`.
+//!
+//! Each function runs one of the decoding paths that parse bytes from the network or from other
+//! group members, discarding the result: the fuzz targets only look for panics.
+use prost::Message;
+use xmtp_proto::xmtp::mls::{
+    api::v1::GroupMessage,
+    message_contents::{plaintext_envelope::Content, EncodedContent, PlaintextEnvelope},
+};
+
+use crate::groups::{
+    intents::{
+        PostCommitAction, ReaddInstallationsIntentData, RemoveInstallationsIntentData,
+        SendMessageIntentData, TransferSuperAdminIntentData, UpdateAdminListIntentData,
+        UpdateGroupMembershipIntentData, UpdateMetadataIntentData, UpdatePermissionIntentData,
+        UpdatePinnedMessagesIntentData,
+    },
+    pipeline, QueryableContentFields,
+};
+
+/// Decode a group message envelope as received from the network, up to the MLS message
+pub fn decode_group_message(bytes: &[u8]) {
+    if let Ok(envelope) = GroupMessage::decode(bytes) {
+        let _ = pipeline::decode(&envelope);
+    }
+}
+
+/// Decode the plaintext of an application message, and the queryable fields of its content
+pub fn decode_application_message(bytes: &[u8]) {
+    let Ok(envelope) = PlaintextEnvelope::decode(bytes) else {
+        return;
+    };
+    if let Some(Content::V1(v1)) = envelope.content {
+        if let Ok(content) = EncodedContent::decode(v1.content.as_slice()) {
+            let _ = QueryableContentFields::try_from(content);
+        }
+    }
+}
+
+/// Decode `bytes` as the data of every kind of intent, and as a post commit action
+pub fn decode_intent_data(bytes: &[u8]) {
+    let _ = SendMessageIntentData::from_bytes(bytes);
+    let _ = UpdateMetadataIntentData::try_from(bytes.to_vec());
+    let _ = UpdateGroupMembershipIntentData::try_from(bytes.to_vec());
+    let _ = UpdateAdminListIntentData::try_from(bytes.to_vec());
+    let _ = UpdatePermissionIntentData::try_from(bytes.to_vec());
+    let _ = ReaddInstallationsIntentData::from_bytes(bytes);
+    let _ = RemoveInstallationsIntentData::from_bytes(bytes);
+    let _ = TransferSuperAdminIntentData::from_bytes(bytes);
+    let _ = UpdatePinnedMessagesIntentData::from_bytes(bytes);
+    let _ = PostCommitAction::from_bytes(bytes);
+}
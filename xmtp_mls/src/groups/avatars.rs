@@ -0,0 +1,304 @@
+//! Local copies of the images referenced by the image url of groups.
+//!
+//! Apps download images with their own HTTP stack by implementing [`AvatarFetcher`] and
+//! registering it with [`crate::builder::ClientBuilder::avatar_fetcher`]. Whenever the image url
+//! of a group changes, the group avatars worker fetches the new image in the background. Images
+//! are stored once per digest, see [`StoredGroupAvatar`].
+//!
+//! A failed fetch is retried after [`GROUP_AVATAR_FETCH_INTERVAL`], doubling the wait with each
+//! failure up to [`MAX_GROUP_AVATAR_RETRY_DELAY`]. An image larger than
+//! [`MAX_GROUP_AVATAR_SIZE`] is not fetched again until the image url of the group changes.
+use std::sync::Arc;
+
+use thiserror::Error;
+use xmtp_common::time::{now_ns, Duration};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    configuration::{
+        GROUP_AVATAR_FETCH_INTERVAL, MAX_GROUP_AVATAR_RETRY_DELAY, MAX_GROUP_AVATAR_SIZE,
+    },
+    diagnostics::WorkerKind,
+    groups::disappearing_messages::WORKER_RESTART_DELAY,
+    storage::{group_avatar::StoredGroupAvatar, StorageError},
+    Client,
+};
+
+#[derive(Debug, Error)]
+#[error("avatar fetch failed: {0}")]
+pub struct AvatarFetchError(pub String);
+
+/// Downloads the image at the image url of a group
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait AvatarFetcher: Send + Sync {
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>, AvatarFetchError>;
+}
+
+/// The fetcher registered on a client
+pub(crate) type SharedAvatarFetcher = Option<Arc<dyn AvatarFetcher>>;
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Fetch the images of the groups whose image url changed since they were last fetched.
+    /// Failed fetches are retried once their backoff ends, see the [module docs](self).
+    /// Returns the number of images stored.
+    pub async fn fetch_group_avatars(&self) -> Result<usize, ClientError> {
+        let Some(fetcher) = self.avatar_fetcher.clone() else {
+            return Ok(0);
+        };
+        let pending = self.store().conn()?.pending_group_avatars()?;
+        let mut stored = 0;
+        for StoredGroupAvatar {
+            group_id,
+            url,
+            failed_attempts,
+            ..
+        } in pending
+        {
+            let data = match fetcher.fetch(&url).await {
+                Ok(data) if data.len() > MAX_GROUP_AVATAR_SIZE => {
+                    tracing::warn!(
+                        group_id = hex::encode(&group_id),
+                        "group avatar of {} bytes is too large, skipping until the url changes",
+                        data.len()
+                    );
+                    self.store().conn()?.skip_group_avatar(&group_id, &url)?;
+                    continue;
+                }
+                Ok(data) => data,
+                Err(e) => {
+                    let retry_in = avatar_retry_delay(failed_attempts);
+                    tracing::warn!(
+                        group_id = hex::encode(&group_id),
+                        "{e}, retrying in {}s",
+                        retry_in.as_secs()
+                    );
+                    self.store().conn()?.record_group_avatar_failure(
+                        &group_id,
+                        &url,
+                        now_ns().saturating_add(retry_in.as_nanos() as i64),
+                    )?;
+                    continue;
+                }
+            };
+            if self
+                .store()
+                .conn()?
+                .store_group_avatar(&group_id, &url, &data)?
+            {
+                stored += 1;
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Start the worker fetching the images of groups. Does nothing without an [`AvatarFetcher`].
+    pub fn start_group_avatars_worker(&self) {
+        if self.avatar_fetcher.is_none() {
+            return;
+        }
+        tracing::trace!(
+            inbox_id = self.inbox_id(),
+            installation_id = hex::encode(self.installation_public_key()),
+            "starting group avatars worker"
+        );
+        GroupAvatarsWorker::new(self.clone()).spawn_worker();
+    }
+}
+
+/// Wait before fetching an avatar again after `failed_attempts` earlier failures
+fn avatar_retry_delay(failed_attempts: i32) -> Duration {
+    let factor = 2u32.saturating_pow(failed_attempts.max(0) as u32);
+    GROUP_AVATAR_FETCH_INTERVAL
+        .saturating_mul(factor)
+        .min(MAX_GROUP_AVATAR_RETRY_DELAY)
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// The image of this group, once it is fetched
+    pub fn avatar(&self) -> Result<Option<Vec<u8>>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.group_avatar(&self.group_id)?)
+    }
+}
+
+pub struct GroupAvatarsWorker<ApiClient, V> {
+    client: Client<ApiClient, V>,
+}
+
+impl<ApiClient, V> GroupAvatarsWorker<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    pub fn new(client: Client<ApiClient, V>) -> Self {
+        Self { client }
+    }
+
+    pub(crate) fn spawn_worker(self) {
+        crate::spawn(None, async move {
+            let inbox_id = self.client.inbox_id().to_string();
+            let installation_id = hex::encode(self.client.installation_public_key());
+            let workers = self.client.workers.clone();
+            workers.started(WorkerKind::GroupAvatars);
+            while let Err(err) = self.run().await {
+                match err {
+                    ClientError::Storage(StorageError::PoolNeedsConnection) => {
+                        tracing::warn!(
+                            inbox_id,
+                            installation_id,
+                            "Pool disconnected. task will restart on reconnect"
+                        );
+                        break;
+                    }
+                    _ => {
+                        tracing::error!(inbox_id, installation_id, "group avatars error {err}");
+                        workers.failed(WorkerKind::GroupAvatars, &err);
+                        xmtp_common::time::sleep(WORKER_RESTART_DELAY).await;
+                        workers.started(WorkerKind::GroupAvatars);
+                    }
+                }
+            }
+            workers.stopped(WorkerKind::GroupAvatars);
+        });
+    }
+
+    async fn run(&self) -> Result<(), ClientError> {
+        loop {
+            // Images are left for when the client is back on an unmetered connection
+            if self.client.data_mode().download_attachments() {
+                let stored = self.client.fetch_group_avatars().await?;
+                if stored > 0 {
+                    tracing::debug!("stored {stored} group avatars");
+                }
+            }
+            xmtp_common::time::sleep(GROUP_AVATAR_FETCH_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    struct UrlBytesFetcher;
+
+    /// Fails on urls ending in `fail`, and returns too large an image for urls ending in `large`
+    struct FlakyFetcher;
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AvatarFetcher for FlakyFetcher {
+        async fn fetch(&self, url: &str) -> Result<Vec<u8>, AvatarFetchError> {
+            if url.ends_with("fail") {
+                return Err(AvatarFetchError("unreachable".to_string()));
+            }
+            if url.ends_with("large") {
+                return Ok(vec![0; MAX_GROUP_AVATAR_SIZE + 1]);
+            }
+            Ok(url.as_bytes().to_vec())
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AvatarFetcher for UrlBytesFetcher {
+        async fn fetch(&self, url: &str) -> Result<Vec<u8>, AvatarFetchError> {
+            Ok(url.as_bytes().to_vec())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_fetches_avatar_when_the_image_url_changes() {
+        let mut amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        amal.avatar_fetcher = Some(Arc::new(UrlBytesFetcher));
+        let group = amal
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    image_url_square: Some("https://a/1.png".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(group.avatar().unwrap(), None);
+        assert_eq!(amal.fetch_group_avatars().await.unwrap(), 1);
+        assert_eq!(group.avatar().unwrap(), Some(b"https://a/1.png".to_vec()));
+
+        group
+            .update_group_image_url_square("https://a/2.png".to_string())
+            .await
+            .unwrap();
+        assert_eq!(amal.fetch_group_avatars().await.unwrap(), 1);
+        assert_eq!(group.avatar().unwrap(), Some(b"https://a/2.png".to_vec()));
+        assert_eq!(amal.fetch_group_avatars().await.unwrap(), 0);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_backs_off_failed_and_skips_large_avatars() {
+        let mut amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        amal.avatar_fetcher = Some(Arc::new(FlakyFetcher));
+        let failing = amal
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    image_url_square: Some("https://a/fail".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let large = amal
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    image_url_square: Some("https://a/large".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(amal.fetch_group_avatars().await.unwrap(), 0);
+        let conn = amal.store().conn().unwrap();
+        let entry = conn.group_avatar_entry(&failing.group_id).unwrap().unwrap();
+        assert_eq!(entry.failed_attempts, 1);
+        assert!(entry.retry_at_ns > now_ns());
+        assert!(
+            conn.group_avatar_entry(&large.group_id)
+                .unwrap()
+                .unwrap()
+                .skipped
+        );
+        // Neither is fetched again right away
+        assert!(conn.pending_group_avatars().unwrap().is_empty());
+
+        large
+            .update_group_image_url_square("https://a/small.png".to_string())
+            .await
+            .unwrap();
+        assert_eq!(amal.fetch_group_avatars().await.unwrap(), 1);
+        assert_eq!(
+            large.avatar().unwrap(),
+            Some(b"https://a/small.png".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_avatar_retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(avatar_retry_delay(0), GROUP_AVATAR_FETCH_INTERVAL);
+        assert_eq!(avatar_retry_delay(1), GROUP_AVATAR_FETCH_INTERVAL * 2);
+        assert_eq!(avatar_retry_delay(1_000), MAX_GROUP_AVATAR_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,317 @@
+//! Creating many groups at once, for platforms that set up rooms programmatically.
+//!
+//! [`Client::create_groups_bulk`] does the work the groups have in common only once: the
+//! identity updates of every member are loaded in a single request, and the key packages of all
+//! their installations are fetched in a single request and reused by every group they are added
+//! to. The commits and welcomes of the groups are then published concurrently, a bounded number
+//! of groups at a time.
+//!
+//! Prefetched key packages are shared by the calls running at the same time, and released by
+//! each call when it ends, whether it succeeds, fails or is cancelled.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use futures::{stream, StreamExt};
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxId};
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{
+    group_permissions::PolicySet,
+    intents::{IntentKind, UpdateGroupMembershipIntentData},
+    GroupError, GroupMetadataOptions, MlsGroup,
+};
+use crate::{
+    api::KeyPackageMap,
+    client::ClientError,
+    configuration::BULK_GROUP_PUBLISH_CONCURRENCY,
+    identity_updates::load_identity_updates,
+    storage::{
+        group::{GroupLifecycleState, GroupMembershipState},
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    subscriptions::LocalEvents,
+    Client,
+};
+
+/// Key packages fetched ahead of time by [`Client::create_groups_bulk`], by installation id.
+/// Each package counts the calls holding it, so that a call ending does not drop the packages
+/// another call still needs.
+#[derive(Debug, Default)]
+pub(crate) struct PrefetchedKeyPackages {
+    entries: parking_lot::Mutex<HashMap<Vec<u8>, (Vec<u8>, usize)>>,
+}
+
+impl PrefetchedKeyPackages {
+    /// Share `key_packages` until the returned guard is dropped
+    fn hold(self: &Arc<Self>, key_packages: KeyPackageMap) -> PrefetchGuard {
+        let mut entries = self.entries.lock();
+        let installation_ids = key_packages.keys().cloned().collect();
+        for (installation_id, bytes) in key_packages {
+            let entry = entries.entry(installation_id).or_insert((vec![], 0));
+            // The latest package fetched replaces an older one
+            entry.0 = bytes;
+            entry.1 += 1;
+        }
+        PrefetchGuard {
+            prefetched: self.clone(),
+            installation_ids,
+        }
+    }
+
+    pub(crate) fn get(&self, installation_id: &[u8]) -> Option<Vec<u8>> {
+        self.entries
+            .lock()
+            .get(installation_id)
+            .map(|(bytes, _)| bytes.clone())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+/// Releases the key packages held by one call of [`Client::create_groups_bulk`]
+struct PrefetchGuard {
+    prefetched: Arc<PrefetchedKeyPackages>,
+    installation_ids: Vec<Vec<u8>>,
+}
+
+impl Drop for PrefetchGuard {
+    fn drop(&mut self) {
+        let mut entries = self.prefetched.entries.lock();
+        for installation_id in &self.installation_ids {
+            if let Some((_, holders)) = entries.get_mut(installation_id) {
+                *holders -= 1;
+                if *holders == 0 {
+                    entries.remove(installation_id);
+                }
+            }
+        }
+    }
+}
+
+/// Template of a group created by [`Client::create_groups_bulk`]
+#[derive(Default)]
+pub struct GroupSpec {
+    pub permissions_policy_set: Option<PolicySet>,
+    pub opts: GroupMetadataOptions,
+    pub member_inbox_ids: Vec<InboxId>,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Create a group for each of the `specs`, with its members added.
+    ///
+    /// Returns the result of each group, in the order of the specs: a group that could not be
+    /// created or whose members could not be added does not prevent the others from being
+    /// created. Fails as a whole only when the identities or key packages of the members could
+    /// not be loaded.
+    pub async fn create_groups_bulk(
+        &self,
+        specs: Vec<GroupSpec>,
+    ) -> Result<Vec<Result<MlsGroup<Self>, GroupError>>, ClientError> {
+        self.create_groups_bulk_with_lifecycle(specs, GroupLifecycleState::Active)
+            .await
+    }
+
+    /// [`Self::create_groups_bulk`], storing the groups in `lifecycle_state`. A
+    /// [`GroupLifecycleState::Staged`] group that fails before its commit is published is
+    /// discarded, see [`staged`](super::staged).
+    pub(crate) async fn create_groups_bulk_with_lifecycle(
+        &self,
+        specs: Vec<GroupSpec>,
+        lifecycle_state: GroupLifecycleState,
+    ) -> Result<Vec<Result<MlsGroup<Self>, GroupError>>, ClientError> {
+        tracing::info!("creating {} groups", specs.len());
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let my_inbox_id = self.inbox_id();
+        let inbox_ids: Vec<&str> = specs
+            .iter()
+            .flat_map(|spec| spec.member_inbox_ids.iter().map(String::as_str))
+            .filter(|inbox_id| *inbox_id != my_inbox_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        load_identity_updates(&self.api_client, conn, &inbox_ids).await?;
+        let sequence_ids = conn.get_latest_sequence_id(&inbox_ids)?;
+        let identifiers: Vec<_> = inbox_ids.iter().map(|inbox_id| (*inbox_id, None)).collect();
+        let installation_ids: Vec<Vec<u8>> = self
+            .batch_get_association_state(conn, &identifiers)
+            .await?
+            .iter()
+            .flat_map(|state| state.installation_ids())
+            .collect();
+        let _prefetched = if installation_ids.is_empty() {
+            None
+        } else {
+            let key_packages = self.api_client.fetch_key_packages(installation_ids).await?;
+            Some(self.prefetched_key_packages.hold(key_packages))
+        };
+
+        let pending: Vec<_> = specs
+            .into_iter()
+            .map(|spec| {
+                self.create_group_from_spec(&provider, spec, &sequence_ids, lifecycle_state)
+            })
+            .collect();
+        let results = stream::iter(pending)
+            .map(|pending| async {
+                let (group, intent_id) = pending?;
+                if let Some(intent_id) = intent_id {
+                    if let Err(err) = group.sync_until_intent_resolved(&provider, intent_id).await {
+                        if !group.commit_published(&provider)? {
+                            group.discard_staged_or_log(&provider);
+                        }
+                        return Err(err);
+                    }
+                }
+                Ok::<_, GroupError>(group)
+            })
+            .buffered(BULK_GROUP_PUBLISH_CONCURRENCY)
+            .collect()
+            .await;
+
+        Ok(results)
+    }
+
+    /// Create the group locally and queue the intent adding its members, without publishing it
+    fn create_group_from_spec(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        spec: GroupSpec,
+        sequence_ids: &HashMap<String, i64>,
+        lifecycle_state: GroupLifecycleState,
+    ) -> Result<(MlsGroup<Self>, Option<i32>), GroupError> {
+        let group = MlsGroup::create_and_insert_with_lifecycle(
+            Arc::new(self.clone()),
+            provider,
+            GroupMembershipState::Allowed,
+            lifecycle_state,
+            spec.permissions_policy_set.unwrap_or_default(),
+            spec.opts,
+        )?;
+        if lifecycle_state == GroupLifecycleState::Active {
+            // notify streams of our new group
+            let _ = self
+                .local_events
+                .send(LocalEvents::NewGroup(group.group_id.clone()));
+        }
+        match Self::queue_members(provider, &group, &spec.member_inbox_ids, sequence_ids) {
+            Ok(intent_id) => Ok((group, intent_id)),
+            Err(err) => {
+                group.discard_staged_or_log(provider);
+                Err(err)
+            }
+        }
+    }
+
+    /// Queue the intent adding `member_inbox_ids` to a new group, if there is anyone to add
+    fn queue_members(
+        provider: &XmtpOpenMlsProvider,
+        group: &MlsGroup<Self>,
+        member_inbox_ids: &[InboxId],
+        sequence_ids: &HashMap<String, i64>,
+    ) -> Result<Option<i32>, GroupError> {
+        let member_inbox_ids: Vec<&str> = member_inbox_ids
+            .iter()
+            .map(String::as_str)
+            .filter(|inbox_id| *inbox_id != group.client.inbox_id())
+            .collect();
+        if member_inbox_ids.is_empty() {
+            return Ok(None);
+        }
+        group.check_group_size(provider, &member_inbox_ids)?;
+
+        let membership_updates = member_inbox_ids
+            .iter()
+            .map(|inbox_id| match sequence_ids.get(*inbox_id) {
+                Some(sequence_id) => Ok((inbox_id.to_string(), *sequence_id as u64)),
+                None => Err(GroupError::MissingSequenceId),
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        let intent_data = UpdateGroupMembershipIntentData::new(membership_updates, vec![]);
+        let intent = group.queue_intent(
+            provider,
+            IntentKind::UpdateGroupMembership,
+            intent_data.into(),
+        )?;
+        Ok(Some(intent.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{api::NetworkSubsystem, builder::ClientBuilder};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_create_groups_bulk() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let specs = (0..3)
+            .map(|i| GroupSpec {
+                opts: GroupMetadataOptions {
+                    name: Some(format!("room {i}")),
+                    ..Default::default()
+                },
+                member_inbox_ids: vec![bo.inbox_id().to_string(), caro.inbox_id().to_string()],
+                ..Default::default()
+            })
+            .collect();
+        alix.reset_network_usage();
+        let groups = alix.create_groups_bulk(specs).await.unwrap();
+        assert_eq!(groups.len(), 3);
+
+        // The key packages of bo and caro were fetched once for all the groups
+        assert_eq!(
+            alix.network_usage()
+                .subsystem(NetworkSubsystem::KeyPackages)
+                .requests,
+            1
+        );
+        assert!(alix.prefetched_key_packages.is_empty());
+
+        let provider = alix.mls_provider().unwrap();
+        for (i, group) in groups.into_iter().enumerate() {
+            let group = group.unwrap();
+            assert_eq!(group.group_name(&provider).unwrap(), format!("room {i}"));
+            assert_eq!(group.members().await.unwrap().len(), 3);
+        }
+
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        assert_eq!(bo.find_groups(Default::default()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_prefetched_key_packages_are_released_by_their_last_holder() {
+        let prefetched = Arc::new(PrefetchedKeyPackages::default());
+        let first = prefetched.hold(KeyPackageMap::from([
+            (vec![1], vec![10]),
+            (vec![2], vec![20]),
+        ]));
+        let second = prefetched.hold(KeyPackageMap::from([(vec![2], vec![21])]));
+
+        drop(first);
+        assert_eq!(prefetched.get(&[1]), None);
+        // Still held by the second call, with the latest package
+        assert_eq!(prefetched.get(&[2]), Some(vec![21]));
+
+        drop(second);
+        assert!(prefetched.is_empty());
+    }
+}
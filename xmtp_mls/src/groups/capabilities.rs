@@ -0,0 +1,131 @@
+//! Negotiation of optional features between the members of a group.
+//!
+//! Every installation advertises the features it supports as private use extension types in
+//! the capabilities of its leaf node, which are set from its key package. A feature should only
+//! be used in a group once [`MlsGroup::peer_capabilities`] reports that all the members support
+//! it, so that older clients never receive something they cannot read.
+use std::collections::HashSet;
+
+use openmls::{extensions::ExtensionType, treesync::LeafNode};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+
+/// An optional feature of the protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Poll and vote content types
+    Polls,
+    /// Location and live location content types
+    Locations,
+    /// Transaction reference content type
+    TransactionReferences,
+}
+
+/// The features supported by this version of the library
+pub const SUPPORTED_CAPABILITIES: &[Capability] = &[
+    Capability::Polls,
+    Capability::Locations,
+    Capability::TransactionReferences,
+];
+
+impl Capability {
+    /// The extension type advertising the feature in leaf node capabilities
+    pub fn extension_type(&self) -> ExtensionType {
+        ExtensionType::Unknown(match self {
+            Capability::Polls => 0xff10,
+            Capability::Locations => 0xff11,
+            Capability::TransactionReferences => 0xff12,
+        })
+    }
+
+    fn from_extension_type(extension_type: &ExtensionType) -> Option<Self> {
+        SUPPORTED_CAPABILITIES
+            .iter()
+            .find(|capability| capability.extension_type() == *extension_type)
+            .copied()
+    }
+}
+
+/// The extension types to add to the capabilities of this installation's leaf nodes
+pub(crate) fn supported_extension_types() -> impl Iterator<Item = ExtensionType> {
+    SUPPORTED_CAPABILITIES
+        .iter()
+        .map(Capability::extension_type)
+}
+
+fn leaf_capabilities(leaf_node: &LeafNode) -> HashSet<Capability> {
+    leaf_node
+        .capabilities()
+        .extensions()
+        .iter()
+        .filter_map(Capability::from_extension_type)
+        .collect()
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// The optional features supported by every installation in the group
+    pub fn peer_capabilities(&self) -> Result<Vec<Capability>, GroupError> {
+        let provider = self.mls_provider()?;
+        let common = self.load_mls_group_with_lock(&provider, |mls_group| {
+            Ok(mls_group.public_group().leaves().fold(
+                None::<HashSet<Capability>>,
+                |common, (_, leaf_node)| {
+                    let capabilities = leaf_capabilities(leaf_node);
+                    Some(match common {
+                        Some(common) => common.intersection(&capabilities).copied().collect(),
+                        None => capabilities,
+                    })
+                },
+            ))
+        })?;
+        let common = common.unwrap_or_default();
+        Ok(SUPPORTED_CAPABILITIES
+            .iter()
+            .filter(|capability| common.contains(capability))
+            .copied()
+            .collect())
+    }
+
+    /// Whether every installation in the group supports `capability`
+    pub fn supports(&self, capability: Capability) -> Result<bool, GroupError> {
+        Ok(self.peer_capabilities()?.contains(&capability))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_peer_capabilities() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        assert_eq!(
+            amal_group.peer_capabilities().unwrap(),
+            SUPPORTED_CAPABILITIES
+        );
+
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        assert_eq!(
+            bola_group.peer_capabilities().unwrap(),
+            SUPPORTED_CAPABILITIES
+        );
+        assert!(amal_group.supports(Capability::Polls).unwrap());
+    }
+}
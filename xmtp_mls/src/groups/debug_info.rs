@@ -0,0 +1,161 @@
+use serde::Serialize;
+
+use super::{validated_commit::extract_group_membership, GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_intent::IntentState, refresh_state::EntityKind,
+    xmtp_openmls_provider::XmtpOpenMlsProvider, NotFound,
+};
+
+/// A snapshot of the local state of a group, meant to be attached to bug reports.
+/// Contains no message contents or member identities.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GroupDebugInfo {
+    pub group_id: String,
+    pub epoch: u64,
+    /// Number of inboxes in the group
+    pub member_count: usize,
+    /// Number of installations in the MLS tree
+    pub installation_count: usize,
+    pub is_active: bool,
+    /// Intents waiting to be published or committed
+    pub pending_intents: usize,
+    /// Intents that permanently failed to publish or apply
+    pub failed_intents: usize,
+    /// Commits from the network that failed to process
+    pub failed_commits: usize,
+    /// `true` if commits that other members applied failed to process on this installation,
+    /// which leaves it on a forked copy of the group. Failing to publish does not fork a group.
+    pub maybe_forked: bool,
+    /// Cursor of the last group message processed from the network
+    pub cursor: i64,
+    /// Time in nanoseconds the group was last synced with the network, `None` if it never was
+    pub last_synced_ns: Option<i64>,
+    /// Time in nanoseconds of the last message in the group
+    pub last_message_ns: Option<i64>,
+    /// Time in nanoseconds the members' installations were last checked
+    pub installations_last_checked_ns: i64,
+    /// Time in nanoseconds this installation last rotated its leaf node key
+    pub key_rotated_at_ns: i64,
+}
+
+impl<ScopedClient> MlsGroup<ScopedClient>
+where
+    ScopedClient: ScopedGroupClient,
+{
+    /// Gather diagnostic information about this group from local state.
+    /// Does not make any network requests.
+    pub fn debug_info(&self) -> Result<GroupDebugInfo, GroupError> {
+        let provider = self.mls_provider()?;
+        self.debug_info_with_provider(&provider)
+    }
+
+    pub fn debug_info_with_provider(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<GroupDebugInfo, GroupError> {
+        let conn = provider.conn_ref();
+        let stored_group = conn
+            .find_group(&self.group_id)?
+            .ok_or_else(|| NotFound::GroupById(self.group_id.clone()))?;
+
+        let (epoch, member_count, installation_count, is_active) =
+            self.load_mls_group_with_lock(provider, |mls_group| {
+                let membership = extract_group_membership(mls_group.extensions())?;
+                Ok((
+                    mls_group.epoch().as_u64(),
+                    membership.members.len(),
+                    mls_group.members().count(),
+                    mls_group.is_active(),
+                ))
+            })?;
+
+        let pending_intents = conn
+            .find_group_intents(
+                self.group_id.clone(),
+                Some(vec![IntentState::ToPublish, IntentState::Published]),
+                None,
+            )?
+            .len();
+        let failed_intents = conn
+            .find_group_intents(self.group_id.clone(), Some(vec![IntentState::Error]), None)?
+            .len();
+        let failed_commits = conn.failed_commits(&self.group_id)?.len();
+        let refresh_state = conn.get_refresh_state(&self.group_id, EntityKind::Group)?;
+        let cursor = refresh_state
+            .as_ref()
+            .map(|state| state.cursor)
+            .unwrap_or_default();
+
+        Ok(GroupDebugInfo {
+            group_id: hex::encode(&self.group_id),
+            epoch,
+            member_count,
+            installation_count,
+            is_active,
+            pending_intents,
+            failed_intents,
+            failed_commits,
+            maybe_forked: failed_commits > 0,
+            cursor,
+            last_synced_ns: refresh_state.and_then(|state| state.synced_at_ns),
+            last_message_ns: stored_group.last_message_ns,
+            installations_last_checked_ns: stored_group.installations_last_checked,
+            key_rotated_at_ns: stored_group.rotated_at_ns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::failed_commit::StoredFailedCommit,
+    };
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_debug_info() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        assert_eq!(group.debug_info().unwrap().last_synced_ns, None);
+        group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let synced_after_ns = xmtp_common::time::now_ns();
+        group.sync().await.unwrap();
+
+        let info = group.debug_info().unwrap();
+        assert_eq!(info.group_id, hex::encode(&group.group_id));
+        assert!(info.last_synced_ns.unwrap() >= synced_after_ns);
+        assert!(info.epoch > 0);
+        assert_eq!(info.member_count, 2);
+        assert_eq!(info.installation_count, 2);
+        assert!(info.is_active);
+        assert_eq!(info.pending_intents, 0);
+        assert!(!info.maybe_forked);
+        assert!(serde_json::to_string(&info).is_ok());
+
+        amal.store()
+            .conn()
+            .unwrap()
+            .record_failed_commit(&StoredFailedCommit {
+                group_id: group.group_id.clone(),
+                cursor: info.cursor + 1,
+                error: "wrong epoch".into(),
+                failed_at_ns: xmtp_common::time::now_ns(),
+            })
+            .unwrap();
+        let info = group.debug_info().unwrap();
+        assert_eq!(info.failed_commits, 1);
+        assert!(info.maybe_forked);
+    }
+}
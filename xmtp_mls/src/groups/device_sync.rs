@@ -5,14 +5,17 @@ pub use crate::utils::WorkerHandle;
 use crate::{
     client::ClientError,
     configuration::NS_IN_HOUR,
+    diagnostics::WorkerKind,
     storage::{
         consent_record::StoredConsentRecord,
         group::{ConversationType, GroupQueryArgs, StoredGroup},
         group_message::{GroupMessageKind, MsgQueryArgs, StoredGroupMessage},
+        history_sync_log::{HistorySyncLogEntry, HistorySyncOutcome},
         xmtp_openmls_provider::XmtpOpenMlsProvider,
         DbConnection, NotFound, StorageError,
     },
     subscriptions::{LocalEvents, StreamMessages, SubscribeError, SyncMessage},
+    sync_progress::SyncPhase,
     Client, Store,
 };
 use aes_gcm::aead::generic_array::GenericArray;
@@ -29,7 +32,7 @@ use thiserror::Error;
 use tokio::sync::OnceCell;
 use tracing::{instrument, warn};
 use xmtp_common::time::{now_ns, Duration};
-use xmtp_common::{retry_async, Retry, RetryableError};
+use xmtp_common::{retry_async, ErrorClass, Retry, RetryableError};
 use xmtp_cryptography::utils as crypto_utils;
 use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
 use xmtp_proto::api_client::trait_impls::XmtpApi;
@@ -46,9 +49,14 @@ use xmtp_proto::xmtp::mls::message_contents::{
 pub mod consent_sync;
 pub mod message_sync;
 pub mod preference_sync;
+pub mod status;
 
 pub const ENC_KEY_SIZE: usize = 32; // 256-bit key
 pub const NONCE_SIZE: usize = 12; // 96-bit nonce
+/// Archives of each kind an installation may receive in an hour
+pub const MAX_HISTORY_SYNCS_PER_HOUR: i64 = 3;
+/// Messages stored from a history reply between two progress reports
+const BACKFILL_PROGRESS_STEP: usize = 100;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
@@ -102,6 +110,10 @@ pub enum DeviceSyncError {
     UnspecifiedDeviceSyncKind,
     #[error("sync reply is too old")]
     SyncPayloadTooOld,
+    #[error("sync request from an installation that is not part of this inbox")]
+    UnknownRequester,
+    #[error("too many sync requests from this installation")]
+    RateLimited,
     #[error(transparent)]
     Subscribe(#[from] SubscribeError),
     #[error(transparent)]
@@ -112,6 +124,17 @@ impl RetryableError for DeviceSyncError {
     fn is_retryable(&self) -> bool {
         true
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::Storage(err) => err.error_class(),
+            Self::Client(err) => err.error_class(),
+            Self::Group(err) => err.error_class(),
+            Self::Subscribe(err) => err.error_class(),
+            Self::Reqwest(_) => Some(ErrorClass::Network),
+            _ => None,
+        }
+    }
 }
 
 impl From<NotFound> for DeviceSyncError {
@@ -275,7 +298,18 @@ where
             unreachable!();
         };
 
-        client.reply_to_sync_request(provider, request).await?;
+        match client.reply_to_sync_request(provider, request, &msg).await {
+            // Refused requests are logged, and must not restart the worker
+            Err(err @ (DeviceSyncError::UnknownRequester | DeviceSyncError::RateLimited)) => {
+                tracing::warn!(
+                    requester_installation_id = hex::encode(&msg.sender_installation_id),
+                    "refused sync request: {err}"
+                );
+            }
+            result => {
+                result?;
+            }
+        }
         Ok(())
     }
 
@@ -349,6 +383,8 @@ where
         crate::spawn(None, async move {
             let inbox_id = self.client.inbox_id().to_string();
             let installation_id = hex::encode(self.client.installation_public_key());
+            let workers = self.client.workers.clone();
+            workers.started(WorkerKind::DeviceSync);
             while let Err(err) = self.run().await {
                 tracing::info!("Running worker..");
                 match err {
@@ -364,11 +400,15 @@ where
                     }
                     _ => {
                         tracing::error!(inbox_id, installation_id, "sync worker error {err}");
+                        workers.failed(WorkerKind::DeviceSync, &err);
+                        self.client.device_sync.failed(&err);
                         // Wait 2 seconds before restarting.
                         xmtp_common::time::sleep(Duration::from_secs(2)).await;
+                        workers.started(WorkerKind::DeviceSync);
                     }
                 }
             }
+            workers.stopped(WorkerKind::DeviceSync);
         });
     }
 }
@@ -416,6 +456,7 @@ where
 
         // lookup if a request has already been made
         if let Ok((_msg, request)) = self.get_pending_sync_request(provider, request.kind).await {
+            self.device_sync.pending(kind);
             return Ok(request);
         }
 
@@ -437,16 +478,93 @@ where
 
         // publish the intent
         sync_group.publish_intents(provider).await?;
+        self.device_sync.requested(kind);
 
         Ok(request)
     }
 
+    /// Ask the other installations for the consent and the message history now, instead of
+    /// waiting for the sync worker to do it. The reply is applied by the sync worker, and
+    /// progress is reported by [`Client::device_sync_status`].
+    pub async fn request_history_sync_now(&self) -> Result<(), DeviceSyncError> {
+        let provider = self.mls_provider()?;
+        let result: Result<(), DeviceSyncError> = async {
+            self.ensure_sync_group(&provider).await?;
+            self.send_sync_request(&provider, DeviceSyncKind::Consent)
+                .await?;
+            self.send_sync_request(&provider, DeviceSyncKind::MessageHistory)
+                .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = &result {
+            self.device_sync.failed(e);
+        }
+        result
+    }
+
+    /// Whether the installation that sent `request_message` may receive an archive: it must
+    /// still be part of this inbox on the network, and must not have received more than
+    /// [`MAX_HISTORY_SYNCS_PER_HOUR`] archives of `kind` in the last hour. Returns the reason
+    /// to refuse.
+    async fn refuse_sync_requester(
+        &self,
+        conn: &DbConnection,
+        request_message: &StoredGroupMessage,
+        kind: DeviceSyncKind,
+    ) -> Result<Option<HistorySyncOutcome>, DeviceSyncError> {
+        if request_message.sender_inbox_id != self.inbox_id() {
+            return Ok(Some(HistorySyncOutcome::UnknownInstallation));
+        }
+        let state = self
+            .get_latest_association_state(conn, self.inbox_id())
+            .await?;
+        if !state
+            .installation_ids()
+            .contains(&request_message.sender_installation_id)
+        {
+            return Ok(Some(HistorySyncOutcome::UnknownInstallation));
+        }
+        let provided = conn.history_syncs_provided_since(
+            &request_message.sender_installation_id,
+            kind,
+            now_ns() - NS_IN_HOUR,
+        )?;
+        if provided >= MAX_HISTORY_SYNCS_PER_HOUR {
+            return Ok(Some(HistorySyncOutcome::RateLimited));
+        }
+        Ok(None)
+    }
+
+    /// Reply to a sync request sent in `request_message`, if the requester may receive an
+    /// archive. Replies and refusals are recorded in the history sync log.
     pub(crate) async fn reply_to_sync_request(
         &self,
         provider: &XmtpOpenMlsProvider,
         request: DeviceSyncRequestProto,
+        request_message: &StoredGroupMessage,
     ) -> Result<DeviceSyncReplyProto, DeviceSyncError> {
         let conn = provider.conn_ref();
+        let log = |outcome| {
+            conn.log_history_sync(
+                &request.request_id,
+                &request_message.sender_inbox_id,
+                &request_message.sender_installation_id,
+                request.kind(),
+                outcome,
+            )
+        };
+
+        if let Some(refusal) = self
+            .refuse_sync_requester(conn, request_message, request.kind())
+            .await?
+        {
+            log(refusal)?;
+            return Err(match refusal {
+                HistorySyncOutcome::RateLimited => DeviceSyncError::RateLimited,
+                _ => DeviceSyncError::UnknownRequester,
+            });
+        }
 
         let records = match request.kind() {
             DeviceSyncKind::Consent => vec![self.syncable_consent_records(conn)?],
@@ -460,10 +578,20 @@ where
             .create_sync_reply(&request.request_id, &records, request.kind())
             .await?;
         self.send_sync_reply(provider, reply.clone()).await?;
+        log(HistorySyncOutcome::Provided)?;
 
         Ok(reply)
     }
 
+    /// The sync requests of other installations that this installation answered or refused,
+    /// newest first
+    pub fn history_sync_log(
+        &self,
+        limit: Option<i64>,
+    ) -> Result<Vec<HistorySyncLogEntry>, ClientError> {
+        Ok(self.store().conn()?.history_sync_log(limit)?)
+    }
+
     async fn send_sync_reply(
         &self,
         provider: &XmtpOpenMlsProvider,
@@ -578,6 +706,18 @@ where
         &self,
         provider: &XmtpOpenMlsProvider,
         reply: DeviceSyncReplyProto,
+    ) -> Result<(), DeviceSyncError> {
+        let kind = reply.kind();
+        self.device_sync.applying();
+        let result = self.apply_sync_reply(provider, reply).await;
+        self.device_sync.applied(kind, &result);
+        result
+    }
+
+    async fn apply_sync_reply(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        reply: DeviceSyncReplyProto,
     ) -> Result<(), DeviceSyncError> {
         let conn = provider.conn_ref();
 
@@ -601,7 +741,9 @@ where
             conn.find_groups(GroupQueryArgs::default().conversation_type(ConversationType::Group))?;
         for crate::storage::group::StoredGroup { id, .. } in groups.into_iter() {
             let group = self.group_with_conn(provider.conn_ref(), &id)?;
-            group.maybe_update_installations(provider, None).await?;
+            if self.data_mode().prefetch_identity_updates() {
+                group.maybe_update_installations(provider, None).await?;
+            }
             Box::pin(group.sync_with_conn(provider)).await?;
         }
 
@@ -697,6 +839,12 @@ where
         let payload = cipher.decrypt(nonce_array, ciphertext)?;
         let payload: Vec<Syncable> = serde_json::from_slice(&payload)?;
 
+        let total = payload
+            .iter()
+            .filter(|syncable| matches!(syncable, Syncable::GroupMessage(_)))
+            .count();
+        let mut stored = 0;
+        self.report_sync_phase(SyncPhase::BackfillingMessages { stored, total });
         for syncable in payload {
             match syncable {
                 Syncable::Group(group) => {
@@ -714,6 +862,10 @@ where
                             _ => Err(err)?,
                         }
                     }
+                    stored += 1;
+                    if stored % BACKFILL_PROGRESS_STEP == 0 || stored == total {
+                        self.report_sync_phase(SyncPhase::BackfillingMessages { stored, total });
+                    }
                 }
                 Syncable::ConsentRecord(consent_record) => {
                     if let Some(existing_consent_record) =
@@ -91,6 +91,7 @@ pub(crate) mod tests {
         let amal_b = ClientBuilder::new_test_client_with_history(&wallet, HISTORY_SYNC_URL).await;
         let amal_b_provider = amal_b.mls_provider().unwrap();
         let amal_b_conn = amal_b_provider.conn_ref();
+        let mut amal_b_progress = amal_b.sync_progress.subscribe();
 
         let groups_b = amal_b.syncable_groups(amal_b_conn).unwrap();
         assert_eq!(groups_b.len(), 0);
@@ -144,6 +145,16 @@ pub(crate) mod tests {
         )
         .await
         .unwrap();
+
+        // Storing the messages of the reply was reported
+        let mut phases = vec![];
+        while let Ok(status) = amal_b_progress.try_recv() {
+            phases.push(status.phase);
+        }
+        assert!(phases.iter().any(|phase| matches!(
+            phase,
+            SyncPhase::BackfillingMessages { total, .. } if *total > 0
+        )));
     }
 
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
@@ -171,6 +182,7 @@ pub(crate) mod tests {
         let amal_b = ClientBuilder::new_test_client_with_history(&wallet, HISTORY_SYNC_URL).await;
         let amal_b_provider = amal_b.mls_provider().unwrap();
         let amal_b_conn = amal_b_provider.conn_ref();
+        let mut amal_b_progress = amal_b.sync_progress.subscribe();
 
         let groups_b = amal_b.syncable_groups(amal_b_conn).unwrap();
         assert_eq!(groups_b.len(), 0);
@@ -293,4 +305,41 @@ pub(crate) mod tests {
         // ensure nonces are different (seed isn't reused)
         assert_ne!(nonce_1, nonce_2);
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_sync_requests_of_unknown_installations_are_refused() {
+        use crate::storage::{
+            group_message::tests::generate_message, history_sync_log::HistorySyncOutcome,
+        };
+
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let provider = amal.mls_provider().unwrap();
+        let request = || -> DeviceSyncRequestProto {
+            DeviceSyncRequest::new(DeviceSyncKind::MessageHistory).into()
+        };
+
+        // another inbox
+        let mut message = generate_message(None, None, None, None);
+        message.sender_inbox_id = "mallory".to_string();
+        let err = amal
+            .reply_to_sync_request(&provider, request(), &message)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DeviceSyncError::UnknownRequester));
+
+        // an installation that is not part of the inbox
+        message.sender_inbox_id = amal.inbox_id().to_string();
+        let err = amal
+            .reply_to_sync_request(&provider, request(), &message)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DeviceSyncError::UnknownRequester));
+
+        let log = amal.history_sync_log(None).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log
+            .iter()
+            .all(|entry| entry.outcome == HistorySyncOutcome::UnknownInstallation));
+        assert_eq!(log[0].requester_inbox_id, amal.inbox_id());
+    }
 }
@@ -1,6 +1,9 @@
 use super::*;
 use crate::{
-    storage::{consent_record::StoredConsentRecord, user_preferences::StoredUserPreferences},
+    storage::{
+        consent_policy::ConsentPolicy, consent_record::StoredConsentRecord,
+        feature_flag::FeatureFlag, mute_rule::MuteRule, user_preferences::StoredUserPreferences,
+    },
     Client,
 };
 use serde::{Deserialize, Serialize};
@@ -13,7 +16,19 @@ use xmtp_proto::{
 #[repr(i32)]
 pub enum UserPreferenceUpdate {
     ConsentUpdate(StoredConsentRecord) = 1,
-    HmacKeyUpdate { key: Vec<u8> } = 2,
+    HmacKeyUpdate {
+        key: Vec<u8>,
+    } = 2,
+    ConsentPolicyUpdate(ConsentPolicy) = 3,
+    /// A synced feature flag was set, or unset with `None`
+    FeatureFlagUpdate {
+        name: String,
+        enabled: Option<bool>,
+    } = 4,
+    MuteRuleAdded(MuteRule) = 5,
+    MuteRuleRemoved {
+        id: String,
+    } = 6,
 }
 
 impl UserPreferenceUpdate {
@@ -69,6 +84,22 @@ impl UserPreferenceUpdate {
                         }
                         .store(conn)?;
                     }
+                    UserPreferenceUpdate::ConsentPolicyUpdate(policy) => {
+                        conn.set_consent_policy(&policy)?;
+                    }
+                    UserPreferenceUpdate::FeatureFlagUpdate { name, enabled } => {
+                        let flag = enabled.map(|enabled| FeatureFlag {
+                            enabled,
+                            synced: true,
+                        });
+                        conn.set_feature_flag(&name, flag)?;
+                    }
+                    UserPreferenceUpdate::MuteRuleAdded(rule) => {
+                        conn.add_mute_rule(&rule)?;
+                    }
+                    UserPreferenceUpdate::MuteRuleRemoved { id } => {
+                        conn.remove_mute_rule(&id)?;
+                    }
                 }
             } else {
                 // Don't fail on errors since this may come from a newer version of the lib
@@ -81,7 +112,7 @@ impl UserPreferenceUpdate {
 
         // Insert all of the consent records at once.
         if !consent_updates.is_empty() {
-            conn.insert_or_replace_consent_records(&consent_updates)?;
+            conn.insert_or_replace_synced_consent_records(&consent_updates)?;
         }
 
         Ok(updates)
@@ -108,11 +139,11 @@ mod tests {
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
     #[cfg_attr(target_family = "wasm", ignore)]
     async fn test_can_deserialize_between_versions() {
-        let consent_record = StoredConsentRecord {
-            entity: "hello there".to_string(),
-            entity_type: ConsentType::Address,
-            state: ConsentState::Allowed,
-        };
+        let consent_record = StoredConsentRecord::new(
+            ConsentType::Address,
+            ConsentState::Allowed,
+            "hello there".to_string(),
+        );
         let update = UserPreferenceUpdate::ConsentUpdate(consent_record);
 
         let bytes = bincode::serialize(&update).unwrap();
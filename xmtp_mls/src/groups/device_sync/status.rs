@@ -0,0 +1,188 @@
+//! State of device sync on this installation, for apps to show whether history is still on its
+//! way from the other installations.
+//!
+//! The latest [`DeviceSyncStatus`] is available from [`Client::device_sync_status`]. A request
+//! can be sent on demand with [`Client::request_history_sync_now`].
+use parking_lot::Mutex;
+use xmtp_common::time::now_ns;
+use xmtp_proto::xmtp::mls::message_contents::DeviceSyncKind;
+
+use crate::Client;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceSyncState {
+    /// No request is waiting for a reply
+    Idle,
+    /// A request was sent to the other installations, and no reply of its kind was applied
+    /// since
+    AwaitingReply,
+    /// A reply was received and its archive is being imported
+    ApplyingArchive,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceSyncStatus {
+    pub state: DeviceSyncState,
+    /// The kinds of the requests waiting for a reply, each kind once
+    pub pending_requests: Vec<DeviceSyncKind>,
+    /// The latest error of the sync worker, or of applying a reply. Cleared once a request is
+    /// sent or a reply is applied.
+    pub last_error: Option<String>,
+    /// Time in nanoseconds a request was last sent
+    pub last_request_ns: Option<i64>,
+    /// Time in nanoseconds a reply was last applied
+    pub last_reply_ns: Option<i64>,
+    /// Time in nanoseconds the status last changed
+    pub updated_at_ns: i64,
+}
+
+impl Default for DeviceSyncStatus {
+    fn default() -> Self {
+        Self {
+            state: DeviceSyncState::Idle,
+            pending_requests: vec![],
+            last_error: None,
+            last_request_ns: None,
+            last_reply_ns: None,
+            updated_at_ns: now_ns(),
+        }
+    }
+}
+
+/// Tracks the device sync status of a client
+#[derive(Debug, Default)]
+pub struct DeviceSyncTracker {
+    status: Mutex<DeviceSyncStatus>,
+}
+
+impl DeviceSyncTracker {
+    pub fn status(&self) -> DeviceSyncStatus {
+        self.status.lock().clone()
+    }
+
+    /// A request of `kind` was sent
+    pub(crate) fn requested(&self, kind: DeviceSyncKind) {
+        self.update(|status| {
+            if !status.pending_requests.contains(&kind) {
+                status.pending_requests.push(kind);
+            }
+            if status.state == DeviceSyncState::Idle {
+                status.state = DeviceSyncState::AwaitingReply;
+            }
+            status.last_request_ns = Some(now_ns());
+            status.last_error = None;
+        });
+    }
+
+    /// A request of `kind` sent earlier, e.g. before a restart, is still waiting for a reply
+    pub(crate) fn pending(&self, kind: DeviceSyncKind) {
+        if self.status.lock().pending_requests.contains(&kind) {
+            return;
+        }
+        self.update(|status| {
+            status.pending_requests.push(kind);
+            if status.state == DeviceSyncState::Idle {
+                status.state = DeviceSyncState::AwaitingReply;
+            }
+        });
+    }
+
+    pub(crate) fn applying(&self) {
+        self.update(|status| status.state = DeviceSyncState::ApplyingArchive);
+    }
+
+    /// A reply of `kind` was applied. The state stays [`DeviceSyncState::AwaitingReply`] while
+    /// requests of other kinds are pending.
+    pub(crate) fn applied<E: std::fmt::Display>(
+        &self,
+        kind: DeviceSyncKind,
+        result: &Result<(), E>,
+    ) {
+        self.update(|status| {
+            status.pending_requests.retain(|pending| *pending != kind);
+            status.state = if status.pending_requests.is_empty() {
+                DeviceSyncState::Idle
+            } else {
+                DeviceSyncState::AwaitingReply
+            };
+            match result {
+                Ok(()) => {
+                    status.last_reply_ns = Some(now_ns());
+                    status.last_error = None;
+                }
+                Err(e) => status.last_error = Some(e.to_string()),
+            }
+        });
+    }
+
+    pub(crate) fn failed(&self, error: &impl std::fmt::Display) {
+        self.update(|status| status.last_error = Some(error.to_string()));
+    }
+
+    fn update(&self, f: impl FnOnce(&mut DeviceSyncStatus)) {
+        let mut status = self.status.lock();
+        f(&mut status);
+        status.updated_at_ns = now_ns();
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Whether a request of this installation is waiting for a reply, and the times of the
+    /// latest request and reply
+    pub fn device_sync_status(&self) -> DeviceSyncStatus {
+        self.device_sync.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_request_history_sync_now() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let status = amal.device_sync_status();
+        assert_eq!(status.state, DeviceSyncState::Idle);
+        assert_eq!(status.last_request_ns, None);
+
+        amal.request_history_sync_now().await.unwrap();
+        let status = amal.device_sync_status();
+        assert_eq!(status.state, DeviceSyncState::AwaitingReply);
+        assert!(status.last_request_ns.is_some());
+        assert_eq!(status.last_reply_ns, None);
+
+        assert_eq!(
+            status.pending_requests,
+            vec![DeviceSyncKind::Consent, DeviceSyncKind::MessageHistory]
+        );
+        let last_request_ns = status.last_request_ns;
+
+        // Asking again while the requests are pending does not send new ones
+        amal.request_history_sync_now().await.unwrap();
+        assert_eq!(amal.device_sync_status().last_request_ns, last_request_ns);
+
+        amal.device_sync
+            .applied(DeviceSyncKind::Consent, &Err("archive unavailable"));
+        let status = amal.device_sync_status();
+        assert_eq!(status.state, DeviceSyncState::AwaitingReply);
+        assert_eq!(
+            status.pending_requests,
+            vec![DeviceSyncKind::MessageHistory]
+        );
+        assert_eq!(status.last_error.as_deref(), Some("archive unavailable"));
+
+        amal.device_sync
+            .applied::<String>(DeviceSyncKind::MessageHistory, &Ok(()));
+        let status = amal.device_sync_status();
+        assert_eq!(status.state, DeviceSyncState::Idle);
+        assert!(status.pending_requests.is_empty());
+        assert_eq!(status.last_error, None);
+        assert!(status.last_reply_ns.is_some());
+    }
+}
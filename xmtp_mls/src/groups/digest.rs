@@ -0,0 +1,219 @@
+//! Summaries of the messages received since a point in time, for periodic notifications.
+//!
+//! [`Client::notification_digest`] reads the unread messages of all groups in a single query and
+//! aggregates them per group, so apps can raise one summary notification instead of one per
+//! message. Messages that are not readable, muted by a mute rule, or in a denied conversation or
+//! from a denied inbox are left out, as for
+//! [`MlsGroup::unread_count`](super::MlsGroup::unread_count).
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxId};
+use xmtp_proto::{api_client::trait_impls::XmtpApi, xmtp::mls::message_contents::EncodedContent};
+
+use crate::{
+    client::ClientError,
+    configuration::DIGEST_TOP_SENDERS,
+    storage::group_message::{ContentType, StoredGroupMessage},
+    Client,
+};
+
+/// New messages of a group since the time given to [`Client::notification_digest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupDigest {
+    pub group_id: Vec<u8>,
+    pub new_message_count: u64,
+    /// The inboxes that sent the most messages, most messages first
+    pub top_senders: Vec<DigestSender>,
+    /// Whether a text message mentions the inbox with `@` followed by its inbox id or one of
+    /// its addresses
+    pub mentioned: bool,
+    pub last_message_ns: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestSender {
+    pub inbox_id: InboxId,
+    pub message_count: u64,
+}
+
+struct DigestBuilder {
+    digest: GroupDigest,
+    message_counts: HashMap<InboxId, u64>,
+}
+
+impl DigestBuilder {
+    fn new(group_id: Vec<u8>) -> Self {
+        Self {
+            digest: GroupDigest {
+                group_id,
+                new_message_count: 0,
+                top_senders: vec![],
+                mentioned: false,
+                last_message_ns: 0,
+            },
+            message_counts: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, message: StoredGroupMessage, mentions: &[String]) {
+        self.digest.new_message_count += 1;
+        self.digest.last_message_ns = self.digest.last_message_ns.max(message.sent_at_ns);
+        if !self.digest.mentioned && message.content_type == ContentType::Text {
+            self.digest.mentioned = decode_text(&message.decrypted_message_bytes)
+                .map(|text| text.to_lowercase())
+                .is_some_and(|text| mentions.iter().any(|mention| text.contains(mention)));
+        }
+        *self
+            .message_counts
+            .entry(message.sender_inbox_id)
+            .or_default() += 1;
+    }
+
+    fn build(self) -> GroupDigest {
+        let mut senders: Vec<_> = self
+            .message_counts
+            .into_iter()
+            .map(|(inbox_id, message_count)| DigestSender {
+                inbox_id,
+                message_count,
+            })
+            .collect();
+        senders.sort_by(|a, b| {
+            b.message_count
+                .cmp(&a.message_count)
+                .then_with(|| a.inbox_id.cmp(&b.inbox_id))
+        });
+        senders.truncate(DIGEST_TOP_SENDERS);
+        GroupDigest {
+            top_senders: senders,
+            ..self.digest
+        }
+    }
+}
+
+fn decode_text(content: &[u8]) -> Option<String> {
+    let content = EncodedContent::decode(content).ok()?;
+    TextCodec::decode(content).ok()
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Summarize, per group, the messages from other inboxes sent after `since_ns`. Groups
+    /// without new messages are left out, and the group with the latest message comes first.
+    pub async fn notification_digest(
+        &self,
+        since_ns: i64,
+    ) -> Result<Vec<GroupDigest>, ClientError> {
+        let conn = self.store().conn()?;
+        let association_state = self
+            .get_association_state(&conn, self.inbox_id(), None)
+            .await?;
+        let mentions: Vec<String> = std::iter::once(self.inbox_id().to_string())
+            .chain(association_state.account_addresses())
+            .map(|identifier| format!("@{}", identifier.to_lowercase()))
+            .collect();
+
+        let mut builders: Vec<DigestBuilder> = vec![];
+        for message in conn.unread_messages_since(since_ns)? {
+            match builders.last_mut() {
+                Some(builder) if builder.digest.group_id == message.group_id => {
+                    builder.add(message, &mentions)
+                }
+                _ => {
+                    let mut builder = DigestBuilder::new(message.group_id.clone());
+                    builder.add(message, &mentions);
+                    builders.push(builder);
+                }
+            }
+        }
+        let mut digests: Vec<_> = builders.into_iter().map(DigestBuilder::build).collect();
+        digests.sort_by(|a, b| b.last_message_ns.cmp(&a.last_message_ns));
+        Ok(digests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::time::now_ns;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    fn text(text: String) -> Vec<u8> {
+        encoded_content_to_bytes(TextCodec::encode(text).unwrap())
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_notification_digest() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let quiet_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        quiet_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let busy_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        busy_group
+            .add_members_by_inbox_id(&[bola.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        let since_ns = now_ns();
+
+        for client in [&bola, &caro] {
+            client
+                .sync_welcomes(&client.mls_provider().unwrap())
+                .await
+                .unwrap();
+        }
+        let bola_quiet = bola.group(quiet_group.group_id.clone()).unwrap();
+        bola_quiet.send_message(&text("hi".into())).await.unwrap();
+        let bola_busy = bola.group(busy_group.group_id.clone()).unwrap();
+        let caro_busy = caro.group(busy_group.group_id.clone()).unwrap();
+        bola_busy.send_message(&text("one".into())).await.unwrap();
+        caro_busy.send_message(&text("two".into())).await.unwrap();
+        bola_busy
+            .send_message(&text(format!("@{} three", amal.inbox_id())))
+            .await
+            .unwrap();
+        busy_group.send_message(&text("mine".into())).await.unwrap();
+
+        quiet_group.sync().await.unwrap();
+        busy_group.sync().await.unwrap();
+        let digests = amal.notification_digest(since_ns).await.unwrap();
+        assert_eq!(digests.len(), 2);
+
+        let busy = &digests[0];
+        assert_eq!(busy.group_id, busy_group.group_id);
+        assert_eq!(busy.new_message_count, 3);
+        assert!(busy.mentioned);
+        assert_eq!(busy.top_senders[0].inbox_id, bola.inbox_id());
+        assert_eq!(busy.top_senders[0].message_count, 2);
+        assert_eq!(busy.top_senders[1].inbox_id, caro.inbox_id());
+
+        let quiet = &digests[1];
+        assert_eq!(quiet.group_id, quiet_group.group_id);
+        assert_eq!(quiet.new_message_count, 1);
+        assert!(!quiet.mentioned);
+
+        assert!(amal
+            .notification_digest(busy.last_message_ns)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,9 +1,11 @@
 use crate::client::ClientError;
+use crate::diagnostics::WorkerKind;
 use crate::storage::StorageError;
 use crate::Client;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::OnceCell;
+use xmtp_common::time::now_ns;
 use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
 use xmtp_proto::api_client::trait_impls::XmtpApi;
 
@@ -38,6 +40,8 @@ where
         crate::spawn(None, async move {
             let inbox_id = self.client.inbox_id().to_string();
             let installation_id = hex::encode(self.client.installation_public_key());
+            let workers = self.client.workers.clone();
+            workers.started(WorkerKind::DisappearingMessages);
             while let Err(err) = self.run().await {
                 tracing::info!("Running worker..");
                 match err {
@@ -53,10 +57,13 @@ where
                     }
                     _ => {
                         tracing::error!(inbox_id, installation_id, "sync worker error {err}");
+                        workers.failed(WorkerKind::DisappearingMessages, &err);
                         xmtp_common::time::sleep(WORKER_RESTART_DELAY).await;
+                        workers.started(WorkerKind::DisappearingMessages);
                     }
                 }
             }
+            workers.stopped(WorkerKind::DisappearingMessages);
         });
     }
 }
@@ -66,7 +73,9 @@ where
     ApiClient: XmtpApi + Send + Sync + 'static,
     V: SmartContractSignatureVerifier + Send + Sync + 'static,
 {
-    /// Iterate on the list of groups and delete expired messages
+    /// Iterate on the list of groups and delete expired messages, the messages of expired groups
+    /// and of groups left longer than the retention, expire message requests, and delete expired
+    /// live locations
     async fn delete_expired_messages(&mut self) -> Result<(), DisappearingMessagesCleanerError> {
         let provider = self.client.mls_provider()?;
         match provider.conn_ref().delete_expired_messages() {
@@ -77,6 +86,45 @@ where
                 tracing::error!("Failed to delete expired messages, error: {:?}", e);
             }
         }
+        match provider.conn_ref().purge_expired_groups(now_ns()) {
+            Ok(purged_count) if purged_count > 0 => {
+                tracing::info!("Purged {} messages of expired groups", purged_count);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Failed to purge expired groups, error: {:?}", e);
+            }
+        }
+        if let Some(retention) = self.client.left_group_retention {
+            let left_before_ns = now_ns() - retention.as_nanos() as i64;
+            match provider.conn_ref().purge_left_groups(left_before_ns) {
+                Ok(purged_count) if purged_count > 0 => {
+                    tracing::info!("Purged {} messages of left groups", purged_count);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!("Failed to purge left groups, error: {:?}", e);
+                }
+            }
+        }
+        match self.client.expire_requests(now_ns()) {
+            Ok(expired) if !expired.is_empty() => {
+                tracing::info!("Expired {} message requests", expired.len());
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Failed to expire message requests, error: {:?}", e);
+            }
+        }
+        match provider.conn_ref().delete_expired_live_locations(now_ns()) {
+            Ok(expired_count) if expired_count > 0 => {
+                tracing::info!("Deleted {} expired live locations", expired_count);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Failed to delete expired live locations, error: {:?}", e);
+            }
+        }
         Ok(())
     }
     async fn run(&mut self) -> Result<(), DisappearingMessagesCleanerError> {
@@ -0,0 +1,337 @@
+//! Discoverable groups, which inboxes can ask to join without being invited.
+//!
+//! Admins make a group discoverable with [`MlsGroup::update_join_policy`] and publish its
+//! [`GroupListing`] outside of MLS, e.g. on a website or in a QR code. An inbox that reads the
+//! listing calls [`Client::request_to_join`], which sends a [`JoinRequest`] in a DM to each admin
+//! of the listing, since only members can write to the group itself. Admins find the requests
+//! with [`MlsGroup::join_requests`] and answer them with [`MlsGroup::approve_join_request`] or
+//! [`MlsGroup::reject_join_request`]. Approved inboxes are added to the group like any member,
+//! and receive a welcome.
+use prost::Message;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    join_request::{JoinRequest, JoinRequestCodec},
+    ContentCodec,
+};
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxId};
+use xmtp_proto::{api_client::trait_impls::XmtpApi, xmtp::mls::message_contents::EncodedContent};
+
+use super::{
+    group_mutable_metadata::{GroupMutableMetadata, MetadataField},
+    intents::UpdateMetadataIntentData,
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    client::ClientError,
+    storage::{
+        group::ConversationType,
+        group_intent::IntentKind,
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        join_request::{JoinRequestState, StoredJoinRequest},
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+        NotFound, StorageError,
+    },
+    Client,
+};
+
+/// Who can ask to join a group, see [`MetadataField::JoinPolicy`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JoinPolicy {
+    /// Members can only be invited by the members allowed to add members
+    #[default]
+    Closed,
+    /// The group can be listed, and anyone who reads its listing can ask the admins to join
+    RequestToJoin,
+}
+
+impl JoinPolicy {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            JoinPolicy::Closed => "closed",
+            JoinPolicy::RequestToJoin => "request_to_join",
+        }
+    }
+
+    /// The policy of a group. Groups without one, including groups created before the field
+    /// existed, are closed.
+    pub fn from_metadata(metadata: &GroupMutableMetadata) -> Self {
+        match metadata
+            .attributes
+            .get(MetadataField::JoinPolicy.as_str())
+            .map(String::as_str)
+        {
+            Some("request_to_join") => JoinPolicy::RequestToJoin,
+            _ => JoinPolicy::Closed,
+        }
+    }
+}
+
+/// What an inbox needs to know about a discoverable group to ask to join it. Apps publish the
+/// bytes of [`GroupListing::to_bytes`] wherever they list groups.
+#[derive(Clone, PartialEq, Message)]
+pub struct GroupListing {
+    #[prost(bytes = "vec", tag = "1")]
+    pub group_id: Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(string, tag = "3")]
+    pub description: String,
+    #[prost(string, tag = "4")]
+    pub image_url_square: String,
+    /// Inboxes the join requests are sent to
+    #[prost(string, repeated, tag = "5")]
+    pub admin_inbox_ids: Vec<InboxId>,
+}
+
+impl GroupListing {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GroupError> {
+        Self::decode(bytes).map_err(|e| GroupError::Generic(e.to_string()))
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    pub fn join_policy(&self, provider: &XmtpOpenMlsProvider) -> Result<JoinPolicy, GroupError> {
+        Ok(JoinPolicy::from_metadata(&self.mutable_metadata(provider)?))
+    }
+
+    /// Set who can ask to join the group. Only admins can update it.
+    pub async fn update_join_policy(&self, join_policy: JoinPolicy) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type != ConversationType::Group {
+            return Err(GroupError::NotDiscoverable);
+        }
+        let intent_data: Vec<u8> =
+            UpdateMetadataIntentData::new_update_join_policy(join_policy).into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The listing to publish for the group. Fails unless inboxes can ask to join the group.
+    pub fn listing(&self, provider: &XmtpOpenMlsProvider) -> Result<GroupListing, GroupError> {
+        let metadata = self.mutable_metadata(provider)?;
+        if JoinPolicy::from_metadata(&metadata) != JoinPolicy::RequestToJoin {
+            return Err(GroupError::NotDiscoverable);
+        }
+        let attribute = |field: MetadataField| {
+            metadata
+                .attributes
+                .get(field.as_str())
+                .cloned()
+                .unwrap_or_default()
+        };
+        Ok(GroupListing {
+            group_id: self.group_id.clone(),
+            name: attribute(MetadataField::GroupName),
+            description: attribute(MetadataField::Description),
+            image_url_square: attribute(MetadataField::GroupImageUrlSquare),
+            admin_inbox_ids: metadata
+                .super_admin_list
+                .iter()
+                .chain(&metadata.admin_list)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Join requests that no admin of this installation answered yet, oldest first
+    pub fn join_requests(&self) -> Result<Vec<StoredJoinRequest>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.join_requests(&self.group_id, JoinRequestState::Pending)?)
+    }
+
+    /// Add the inbox that asked to join the group
+    pub async fn approve_join_request(&self, inbox_id: &str) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        if self.join_policy(&provider)? != JoinPolicy::RequestToJoin {
+            return Err(GroupError::NotDiscoverable);
+        }
+        let conn = provider.conn_ref();
+        if !conn
+            .join_requests(&self.group_id, JoinRequestState::Pending)?
+            .iter()
+            .any(|request| request.inbox_id == inbox_id)
+        {
+            return Err(NotFound::JoinRequest(inbox_id.to_string()).into());
+        }
+        self.add_members_by_inbox_id_with_provider(&provider, &[inbox_id])
+            .await?;
+        conn.set_join_request_state(&self.group_id, inbox_id, JoinRequestState::Approved)?;
+        Ok(())
+    }
+
+    /// Decline the request of an inbox to join the group. Its later requests are ignored.
+    pub fn reject_join_request(&self, inbox_id: &str) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        if !conn.set_join_request_state(&self.group_id, inbox_id, JoinRequestState::Rejected)? {
+            return Err(NotFound::JoinRequest(inbox_id.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Record the join requests received in this conversation for discoverable groups this
+    /// inbox is an admin of, since only admins can act on them
+    pub(super) fn record_join_request(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        message: &StoredGroupMessage,
+    ) -> Result<(), StorageError> {
+        if message.content_type != ContentType::JoinRequest
+            || message.kind != GroupMessageKind::Application
+            || message.sender_inbox_id == self.client.inbox_id()
+        {
+            return Ok(());
+        }
+        let Some(request) = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+            .ok()
+            .and_then(|content| JoinRequestCodec::decode(content).ok())
+        else {
+            tracing::warn!("invalid join request from {}", message.sender_inbox_id);
+            return Ok(());
+        };
+        let conn = provider.conn_ref();
+        let Some(group) = conn.find_group(&request.group_id)? else {
+            return Ok(());
+        };
+        let group = MlsGroup::new_from_arc(self.client.clone(), group.id, group.created_at_ns);
+        let metadata = match group.mutable_metadata(provider) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                tracing::warn!("could not read the group of a join request: {err}");
+                return Ok(());
+            }
+        };
+        let inbox_id = self.client.inbox_id();
+        if JoinPolicy::from_metadata(&metadata) != JoinPolicy::RequestToJoin
+            || !metadata
+                .admin_list
+                .iter()
+                .chain(&metadata.super_admin_list)
+                .any(|admin| admin == inbox_id)
+        {
+            tracing::debug!(
+                "ignoring the join request of {}, the group is not discoverable or this inbox \
+                 is not one of its admins",
+                message.sender_inbox_id
+            );
+            return Ok(());
+        }
+        conn.record_join_request(&StoredJoinRequest {
+            group_id: request.group_id,
+            inbox_id: message.sender_inbox_id.clone(),
+            message: request.message,
+            requested_at_ns: message.sent_at_ns,
+            state: JoinRequestState::Pending,
+        })
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Ask the admins of a discoverable group to add this inbox, with a message for them.
+    /// Once an admin approves, the group arrives like any other group with a welcome.
+    pub async fn request_to_join(
+        &self,
+        listing: &GroupListing,
+        message: String,
+    ) -> Result<(), ClientError> {
+        let encoded = JoinRequestCodec::encode(JoinRequest {
+            group_id: listing.group_id.clone(),
+            message,
+        })
+        .map_err(|e| GroupError::Generic(e.to_string()))?;
+        let content = encoded_content_to_bytes(encoded);
+        for admin_inbox_id in &listing.admin_inbox_ids {
+            if admin_inbox_id == self.inbox_id() {
+                continue;
+            }
+            let dm = self
+                .find_or_create_dm_by_inbox_id(admin_inbox_id.clone())
+                .await?;
+            dm.send_message(&content).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_join_discoverable_group() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = amal
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    name: Some("chess club".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let amal_provider = amal.mls_provider().unwrap();
+        assert!(matches!(
+            group.listing(&amal_provider),
+            Err(GroupError::NotDiscoverable)
+        ));
+        group
+            .update_join_policy(JoinPolicy::RequestToJoin)
+            .await
+            .unwrap();
+        let listing = group.listing(&amal_provider).unwrap();
+        assert_eq!(listing.name, "chess club");
+        assert_eq!(listing.admin_inbox_ids, vec![amal.inbox_id().to_string()]);
+
+        let listing = GroupListing::from_bytes(&listing.to_bytes()).unwrap();
+        bola.request_to_join(&listing, "I play on tuesdays".to_string())
+            .await
+            .unwrap();
+        // Requests to join groups that are not discoverable are ignored
+        let closed = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let closed_listing = GroupListing {
+            group_id: closed.group_id.clone(),
+            ..listing.clone()
+        };
+        bola.request_to_join(&closed_listing, "me too".to_string())
+            .await
+            .unwrap();
+
+        amal.sync_welcomes(&amal_provider).await.unwrap();
+        let dm = amal
+            .find_or_create_dm_by_inbox_id(bola.inbox_id().to_string())
+            .await
+            .unwrap();
+        dm.sync().await.unwrap();
+        let requests = group.join_requests().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].inbox_id, bola.inbox_id());
+        assert_eq!(requests[0].message, "I play on tuesdays");
+        assert!(closed.join_requests().unwrap().is_empty());
+
+        group.approve_join_request(bola.inbox_id()).await.unwrap();
+        assert!(group.join_requests().unwrap().is_empty());
+        assert_eq!(group.members().await.unwrap().len(), 2);
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        assert!(bola.group(group.group_id.clone()).is_ok());
+    }
+}
@@ -0,0 +1,220 @@
+//! Starting a new group from an existing one, e.g. a new season of a community channel.
+//!
+//! [`MlsGroup::clone_group`] creates the group through [`Client::create_groups_bulk`], so the
+//! key packages of all the members are fetched in a single request and they are added in a
+//! single commit.
+//!
+//! The new group is [`GroupLifecycleState::Staged`] until its admins are queued, as for
+//! [`Client::create_group_with_message`]: if the members cannot be added it is deleted, and once
+//! they have the group it is kept, with the admin updates published by a later sync if they
+//! cannot be published right away.
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxId};
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{
+    bulk_create::GroupSpec,
+    group_mutable_metadata::MetadataField,
+    intents::{AdminListActionType, IntentKind, UpdateAdminListIntentData},
+    GroupError, GroupMetadataOptions, MlsGroup,
+};
+use crate::{
+    storage::group::{ConversationType, GroupLifecycleState},
+    subscriptions::LocalEvents,
+    Client,
+};
+
+/// Options of [`MlsGroup::clone_group`]
+#[derive(Debug, Clone, Default)]
+pub struct CloneGroupOptions {
+    /// Members of the group that are not added to the new group
+    pub excluded_inbox_ids: Vec<InboxId>,
+    /// Name of the new group, instead of the name of the group
+    pub name: Option<String>,
+}
+
+impl<ApiClient, V> MlsGroup<Client<ApiClient, V>>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Create a new group with the members, metadata, permissions and admins of this group.
+    ///
+    /// The expiration of the group is not copied. The admins are granted their role once they
+    /// are members of the new group, in a commit each. Fails without leaving a group behind if
+    /// the members cannot be added, see the [module docs](self).
+    pub async fn clone_group(
+        &self,
+        options: CloneGroupOptions,
+    ) -> Result<MlsGroup<Client<ApiClient, V>>, GroupError> {
+        let provider = self.mls_provider()?;
+        if self.conversation_type(&provider).await? != ConversationType::Group {
+            return Err(GroupError::Generic("only groups can be cloned".to_string()));
+        }
+        let mutable_metadata = self.mutable_metadata(&provider)?;
+        let attribute = |field: MetadataField| {
+            mutable_metadata
+                .attributes
+                .get(field.as_str())
+                .filter(|value| !value.is_empty())
+                .cloned()
+        };
+        let opts = GroupMetadataOptions {
+            name: options.name.or_else(|| attribute(MetadataField::GroupName)),
+            image_url_square: attribute(MetadataField::GroupImageUrlSquare),
+            description: attribute(MetadataField::Description),
+            pinned_frame_url: attribute(MetadataField::GroupPinnedFrameUrl),
+            message_disappearing_settings: self
+                .conversation_message_disappearing_settings(&provider)
+                .ok(),
+            expires_at_ns: None,
+        };
+        let is_included = |inbox_id: &String| {
+            inbox_id != self.client.inbox_id() && !options.excluded_inbox_ids.contains(inbox_id)
+        };
+        let member_inbox_ids: Vec<InboxId> = self
+            .members()
+            .await?
+            .into_iter()
+            .map(|member| member.inbox_id)
+            .filter(is_included)
+            .collect();
+        let spec = GroupSpec {
+            permissions_policy_set: Some(self.permissions()?.policies),
+            opts,
+            member_inbox_ids,
+        };
+        let group = self
+            .client
+            .create_groups_bulk_with_lifecycle(vec![spec], GroupLifecycleState::Staged)
+            .await?
+            .pop()
+            .ok_or_else(|| GroupError::Generic("group was not created".to_string()))??;
+
+        // The members have the group now, so it is kept whatever happens next
+        let admins = mutable_metadata
+            .admin_list
+            .into_iter()
+            .filter(is_included)
+            .map(|inbox_id| (AdminListActionType::Add, inbox_id))
+            .chain(
+                mutable_metadata
+                    .super_admin_list
+                    .into_iter()
+                    .filter(is_included)
+                    .map(|inbox_id| (AdminListActionType::AddSuper, inbox_id)),
+            );
+        for (action_type, inbox_id) in admins {
+            let intent_data = UpdateAdminListIntentData::new(action_type, inbox_id).into();
+            if let Err(err) =
+                group.queue_intent(&provider, IntentKind::UpdateAdminList, intent_data)
+            {
+                tracing::error!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to queue an admin of a cloned group: {err}"
+                );
+            }
+        }
+        provider.conn_ref().activate_staged_group(&group.group_id)?;
+        // notify streams of our new group
+        let _ = self
+            .client
+            .local_events
+            .send(LocalEvents::NewGroup(group.group_id.clone()));
+
+        // The admin updates are queued with the group, they are retried by the next sync
+        if let Err(err) = group.sync_until_last_intent_resolved(&provider).await {
+            tracing::warn!(
+                group_id = hex::encode(&group.group_id),
+                "admins of a cloned group are not granted yet: {err}"
+            );
+        }
+        Ok(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        api::NetworkSubsystem,
+        builder::ClientBuilder,
+        groups::{PreconfiguredPolicies, UpdateAdminListType},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_clone_group() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(
+                Some(PreconfiguredPolicies::AdminsOnly.to_policy_set()),
+                GroupMetadataOptions {
+                    name: Some("season 1".to_string()),
+                    description: Some("weekly games".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        group
+            .update_admin_list(UpdateAdminListType::Add, bo.inbox_id().to_string())
+            .await
+            .unwrap();
+
+        alix.reset_network_usage();
+        let season_2 = group
+            .clone_group(CloneGroupOptions {
+                excluded_inbox_ids: vec![caro.inbox_id().to_string()],
+                name: Some("season 2".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            alix.network_usage()
+                .subsystem(NetworkSubsystem::KeyPackages)
+                .requests,
+            1
+        );
+
+        let provider = alix.mls_provider().unwrap();
+        assert_ne!(season_2.group_id, group.group_id);
+        assert_eq!(
+            season_2.lifecycle_state().unwrap(),
+            GroupLifecycleState::Active
+        );
+        assert_eq!(season_2.group_name(&provider).unwrap(), "season 2");
+        assert_eq!(
+            season_2.group_description(&provider).unwrap(),
+            "weekly games"
+        );
+        assert_eq!(
+            season_2.permissions().unwrap().policies,
+            group.permissions().unwrap().policies
+        );
+        assert_eq!(
+            season_2.admin_list(&provider).unwrap(),
+            vec![bo.inbox_id().to_string()]
+        );
+        let mut members: Vec<_> = season_2
+            .members()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|member| member.inbox_id)
+            .collect();
+        members.sort();
+        let mut expected = vec![alix.inbox_id().to_string(), bo.inbox_id().to_string()];
+        expected.sort();
+        assert_eq!(members, expected);
+    }
+}
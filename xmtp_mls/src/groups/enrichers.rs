@@ -0,0 +1,163 @@
+//! Metadata computed for incoming messages as they are stored.
+//!
+//! Integrators register a [`MessageEnricher`] on the
+//! [`ClientBuilder`](crate::builder::ClientBuilder) to detect the language of a message, score
+//! its toxicity, and so on. Enrichers run just before an incoming application message is stored,
+//! and what they compute is stored as [`StoredMessageAnnotation`]s next to the message, in the
+//! same transaction, without touching its payload.
+use std::{collections::HashMap, sync::Arc};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_message::{MsgQueryArgs, StoredGroupMessage},
+    message_annotation::StoredMessageAnnotation,
+};
+
+/// Computes metadata for incoming messages.
+///
+/// Enrichers are called inline while the group is locked, so they should return quickly.
+pub trait MessageEnricher: Send + Sync {
+    /// The annotations of `message`, as `(key, value)` pairs such as `("language", "en")`. An
+    /// enricher that has nothing to say about a message returns no annotations.
+    fn enrich(&self, message: &StoredGroupMessage) -> Vec<(String, String)>;
+}
+
+/// The enrichers registered on a client, called in the order they were added. When two
+/// enrichers return the same key, the later one wins.
+#[derive(Clone, Default)]
+pub struct MessageEnrichers(Arc<Vec<Arc<dyn MessageEnricher>>>);
+
+impl std::fmt::Debug for MessageEnrichers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageEnrichers")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+impl MessageEnrichers {
+    pub fn push(&mut self, enricher: Arc<dyn MessageEnricher>) {
+        Arc::make_mut(&mut self.0).push(enricher);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn enrich(&self, message: &StoredGroupMessage) -> Vec<StoredMessageAnnotation> {
+        let mut annotations = HashMap::new();
+        for enricher in self.0.iter() {
+            annotations.extend(enricher.enrich(message));
+        }
+        annotations
+            .into_iter()
+            .map(|(key, value)| StoredMessageAnnotation {
+                message_id: message.id.clone(),
+                key,
+                value,
+                group_id: message.group_id.clone(),
+            })
+            .collect()
+    }
+}
+
+pub struct StoredGroupMessageWithAnnotations {
+    pub message: StoredGroupMessage,
+    pub annotations: Vec<StoredMessageAnnotation>,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Query the database for stored messages like [`MlsGroup::find_messages`], each with the
+    /// annotations computed by the message enrichers
+    pub fn find_messages_with_annotations(
+        &self,
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageWithAnnotations>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let messages = conn.get_group_messages(&self.group_id, args)?;
+        let message_ids: Vec<&[u8]> = messages.iter().map(|m| m.id.as_slice()).collect();
+        let mut annotations: HashMap<Vec<u8>, Vec<StoredMessageAnnotation>> = HashMap::new();
+        for annotation in conn.get_message_annotations(&message_ids)? {
+            annotations
+                .entry(annotation.message_id.clone())
+                .or_default()
+                .push(annotation);
+        }
+
+        Ok(messages
+            .into_iter()
+            .map(|message| StoredGroupMessageWithAnnotations {
+                annotations: annotations.remove(&message.id).unwrap_or_default(),
+                message,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group_message::ContentType,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    struct LengthEnricher;
+
+    impl MessageEnricher for LengthEnricher {
+        fn enrich(&self, message: &StoredGroupMessage) -> Vec<(String, String)> {
+            if message.content_type != ContentType::Text {
+                return vec![];
+            }
+            vec![(
+                "length".to_string(),
+                message.decrypted_message_bytes.len().to_string(),
+            )]
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_enrichers_annotate_incoming_messages() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let mut bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        bola.message_enrichers.push(Arc::new(LengthEnricher));
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let content = encoded_content_to_bytes(TextCodec::encode("hello".to_string()).unwrap());
+        let message_id = amal_group.send_message(&content).await.unwrap();
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+
+        let messages = bola_group
+            .find_messages_with_annotations(&MsgQueryArgs::default())
+            .unwrap();
+        let hello = messages
+            .iter()
+            .find(|m| m.message.id == message_id)
+            .unwrap();
+        assert_eq!(hello.annotations.len(), 1);
+        assert_eq!(hello.annotations[0].key, "length");
+        assert_eq!(hello.annotations[0].value, content.len().to_string());
+        // The payload is stored unchanged
+        assert_eq!(hello.message.decrypted_message_bytes, content);
+        // Messages that are not text have no annotations
+        assert!(messages
+            .iter()
+            .filter(|m| m.message.id != message_id)
+            .all(|m| m.annotations.is_empty()));
+    }
+}
@@ -0,0 +1,92 @@
+//! Forwarding messages to other conversations, see [`forward_content`].
+use prost::Message;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    forward::{forward_content, ForwardedFrom},
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::NotFound;
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Forward a message of this group to `to_group`, with its original sender, sent time and
+    /// conversation. Returns the id of the forwarded message.
+    pub async fn forward_message(
+        &self,
+        message_id: &[u8],
+        to_group: &MlsGroup<ScopedClient>,
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| NotFound::MessageById(message_id.to_vec()))?;
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+            .map_err(|e| GroupError::Generic(e.to_string()))?;
+        let forwarded = forward_content(
+            content,
+            ForwardedFrom {
+                sender_inbox_id: message.sender_inbox_id,
+                sent_at_ns: message.sent_at_ns,
+                conversation_id: message.group_id,
+            },
+        )
+        .map_err(GroupError::Forward)?;
+        to_group
+            .send_message(&encoded_content_to_bytes(forwarded))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use prost::Message;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{
+        encoded_content_to_bytes, forward::forwarded_from, text::TextCodec, ContentCodec,
+    };
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_forward_message() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let from_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let to_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let text = TextCodec::encode("hello".to_string()).unwrap();
+        let message_id = from_group
+            .send_message(&encoded_content_to_bytes(text))
+            .await
+            .unwrap();
+
+        let forwarded_id = from_group
+            .forward_message(&message_id, &to_group)
+            .await
+            .unwrap();
+        let conn = amal.store().conn().unwrap();
+        let original = conn.get_group_message(&message_id).unwrap().unwrap();
+        let forwarded = conn.get_group_message(&forwarded_id).unwrap().unwrap();
+        assert_eq!(forwarded.group_id, to_group.group_id);
+        let content = EncodedContent::decode(forwarded.decrypted_message_bytes.as_slice()).unwrap();
+        let provenance = forwarded_from(&content).unwrap();
+        assert_eq!(provenance.sender_inbox_id, amal.inbox_id());
+        assert_eq!(provenance.sent_at_ns, original.sent_at_ns);
+        assert_eq!(provenance.conversation_id, from_group.group_id);
+        assert_eq!(TextCodec::decode(content).unwrap(), "hello");
+
+        // A message can only be forwarded from its own group
+        assert!(to_group
+            .forward_message(&message_id, &from_group)
+            .await
+            .is_err());
+    }
+}
@@ -10,7 +10,7 @@ use xmtp_proto::xmtp::mls::message_contents::{
     GroupMutableMetadataV1 as GroupMutableMetadataProto, Inboxes as InboxesProto,
 };
 
-use super::GroupMetadataOptions;
+use super::{pins::parse_pinned_message_ids, GroupMetadataOptions};
 use crate::configuration::{
     DEFAULT_GROUP_DESCRIPTION, DEFAULT_GROUP_IMAGE_URL_SQUARE, DEFAULT_GROUP_NAME,
     DEFAULT_GROUP_PINNED_FRAME_URL, MUTABLE_METADATA_EXTENSION_ID,
@@ -47,6 +47,15 @@ pub enum MetadataField {
     GroupPinnedFrameUrl,
     MessageDisappearFromNS,
     MessageDisappearInNS,
+    /// Hex encoded ids of the pinned messages, comma separated, oldest pin first
+    PinnedMessages,
+    /// Not part of `supported_fields`: without a policy of its own, only admins can update it,
+    /// in every group including the ones created before the field existed.
+    GroupExpiresAtNS,
+    /// Not part of `supported_fields` either, so only admins can make a group discoverable
+    JoinPolicy,
+    /// Hex encoded id of the mega group the group is a shard of. Admin only, like `JoinPolicy`.
+    MegaGroupId,
 }
 
 impl MetadataField {
@@ -59,6 +68,10 @@ impl MetadataField {
             MetadataField::GroupPinnedFrameUrl => "group_pinned_frame_url",
             MetadataField::MessageDisappearFromNS => "message_disappear_from_ns",
             MetadataField::MessageDisappearInNS => "message_disappear_in_ns",
+            MetadataField::PinnedMessages => "pinned_message_ids",
+            MetadataField::GroupExpiresAtNS => "group_expires_at_ns",
+            MetadataField::JoinPolicy => "join_policy",
+            MetadataField::MegaGroupId => "mega_group_id",
         }
     }
 }
@@ -152,6 +165,12 @@ impl GroupMutableMetadata {
                 message_disappearing_settings.in_ns.to_string(),
             );
         }
+        if let Some(expires_at_ns) = opts.expires_at_ns {
+            attributes.insert(
+                MetadataField::GroupExpiresAtNS.to_string(),
+                expires_at_ns.to_string(),
+            );
+        }
 
         let admin_list = vec![];
         let super_admin_list = vec![creator_inbox_id.clone()];
@@ -202,9 +221,25 @@ impl GroupMutableMetadata {
             MetadataField::GroupPinnedFrameUrl,
             MetadataField::MessageDisappearFromNS,
             MetadataField::MessageDisappearInNS,
+            MetadataField::PinnedMessages,
         ]
     }
 
+    /// The expiration of the group, see [`MetadataField::GroupExpiresAtNS`]
+    pub fn expires_at_ns(&self) -> Option<i64> {
+        self.attributes
+            .get(MetadataField::GroupExpiresAtNS.as_str())
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// The ids of the pinned messages, see [`MetadataField::PinnedMessages`]
+    pub fn pinned_message_ids(&self) -> Vec<Vec<u8>> {
+        self.attributes
+            .get(MetadataField::PinnedMessages.as_str())
+            .map(|value| parse_pinned_message_ids(value))
+            .unwrap_or_default()
+    }
+
     /// Checks if the given inbox ID is an admin.
     pub fn is_admin(&self, inbox_id: &String) -> bool {
         self.admin_list.contains(inbox_id)
@@ -214,6 +249,16 @@ impl GroupMutableMetadata {
     pub fn is_super_admin(&self, inbox_id: &String) -> bool {
         self.super_admin_list.contains(inbox_id)
     }
+
+    /// Whether the group would be left without a super admin after `demoted` lose the role and
+    /// `removed` are removed from the group. Groups without super admins to begin with, like DMs,
+    /// cannot be orphaned.
+    pub fn would_orphan(&self, demoted: &[&str], removed: &[&str]) -> bool {
+        !self.super_admin_list.is_empty()
+            && self.super_admin_list.iter().all(|inbox_id| {
+                demoted.contains(&inbox_id.as_str()) || removed.contains(&inbox_id.as_str())
+            })
+    }
 }
 
 impl TryFrom<GroupMutableMetadata> for Vec<u8> {
@@ -26,6 +26,7 @@ use xmtp_proto::xmtp::mls::database::{
 };
 
 use super::{
+    discovery::JoinPolicy,
     group_membership::GroupMembership,
     group_mutable_metadata::MetadataField,
     group_permissions::{MembershipPolicies, MetadataPolicies, PermissionsPolicies},
@@ -33,9 +34,9 @@ use super::{
     GroupError, MlsGroup,
 };
 use crate::{
-    configuration::GROUP_KEY_ROTATION_INTERVAL_NS,
     storage::{
         db_connection::DbConnection,
+        group::GroupMembershipState,
         group_intent::{IntentKind, NewGroupIntent, StoredGroupIntent},
         ProviderTransactions,
     },
@@ -75,6 +76,9 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         intent_kind: IntentKind,
         intent_data: Vec<u8>,
     ) -> Result<StoredGroupIntent, GroupError> {
+        if conn.installation_retired_at_ns()?.is_some() {
+            return Err(GroupError::InstallationRetired);
+        }
         if intent_kind == IntentKind::SendMessage {
             self.maybe_insert_key_update_intent(conn)?;
         }
@@ -97,11 +101,42 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         let last_rotated_at_ns = conn.get_rotated_at_ns(self.group_id.clone())?;
         let now_ns = xmtp_common::time::now_ns();
         let elapsed_ns = now_ns - last_rotated_at_ns;
-        if elapsed_ns > GROUP_KEY_ROTATION_INTERVAL_NS {
+        if elapsed_ns > self.key_rotation_interval_ns(conn)? {
             self.queue_intent_with_conn(conn, IntentKind::KeyUpdate, vec![])?;
         }
         Ok(())
     }
+
+    /// Queue a key update if this installation's leaf key in the group is due for rotation, so
+    /// that groups this installation only reads rotate too. A key that was never rotated is as
+    /// old as the group on this installation.
+    pub(crate) fn maybe_rotate_key(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        provider.transaction(|provider| {
+            let conn = provider.conn_ref();
+            let Some(group) = conn.find_group(&self.group_id)? else {
+                return Ok(());
+            };
+            if group.membership_state != GroupMembershipState::Allowed {
+                return Ok(());
+            }
+            let rotated_at_ns = group.rotated_at_ns.max(group.created_at_ns);
+            if xmtp_common::time::now_ns() - rotated_at_ns > self.key_rotation_interval_ns(conn)? {
+                self.queue_intent_with_conn(conn, IntentKind::KeyUpdate, vec![])?;
+            }
+            Ok(())
+        })
+    }
+
+    /// The rotation interval set on the group, or else the one of the client
+    pub(crate) fn key_rotation_interval_ns(&self, conn: &DbConnection) -> Result<i64, GroupError> {
+        let interval_ns = conn.key_rotation_interval(&self.group_id)?;
+        Ok(interval_ns.unwrap_or_else(|| {
+            i64::try_from(self.client.key_rotation_interval().as_nanos()).unwrap_or(i64::MAX)
+        }))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -140,6 +175,108 @@ impl From<SendMessageIntentData> for Vec<u8> {
     }
 }
 
+/// Installations to remove from the group and add back with fresh key packages,
+/// because they never processed the welcome they were sent
+#[derive(Clone, PartialEq, Message)]
+pub struct ReaddInstallationsIntentData {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub installations: Vec<Vec<u8>>,
+}
+
+impl ReaddInstallationsIntentData {
+    pub fn new(installations: Vec<Vec<u8>>) -> Self {
+        Self { installations }
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, IntentError> {
+        Ok(Self::decode(data)?)
+    }
+}
+
+impl From<ReaddInstallationsIntentData> for Vec<u8> {
+    fn from(intent: ReaddInstallationsIntentData) -> Self {
+        intent.encode_to_vec()
+    }
+}
+
+/// Installations to remove from the group, while their inboxes stay members
+#[derive(Clone, PartialEq, Message)]
+pub struct RemoveInstallationsIntentData {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub installations: Vec<Vec<u8>>,
+}
+
+impl RemoveInstallationsIntentData {
+    pub fn new(installations: Vec<Vec<u8>>) -> Self {
+        Self { installations }
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, IntentError> {
+        Ok(Self::decode(data)?)
+    }
+}
+
+impl From<RemoveInstallationsIntentData> for Vec<u8> {
+    fn from(intent: RemoveInstallationsIntentData) -> Self {
+        intent.encode_to_vec()
+    }
+}
+
+/// Hand the super admin role from one inbox to another in a single commit. The previous super
+/// admin stays an admin.
+#[derive(Clone, PartialEq, Message)]
+pub struct TransferSuperAdminIntentData {
+    #[prost(string, tag = "1")]
+    pub from_inbox_id: String,
+    #[prost(string, tag = "2")]
+    pub to_inbox_id: String,
+}
+
+impl TransferSuperAdminIntentData {
+    pub fn new(from_inbox_id: String, to_inbox_id: String) -> Self {
+        Self {
+            from_inbox_id,
+            to_inbox_id,
+        }
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, IntentError> {
+        Ok(Self::decode(data)?)
+    }
+}
+
+impl From<TransferSuperAdminIntentData> for Vec<u8> {
+    fn from(intent: TransferSuperAdminIntentData) -> Self {
+        intent.encode_to_vec()
+    }
+}
+
+/// Pin or unpin one message. The pinned set is computed from the metadata of the group when the
+/// intent is published, so that pins of other members merged in the meantime are kept.
+#[derive(Clone, PartialEq, Message)]
+pub struct UpdatePinnedMessagesIntentData {
+    #[prost(bytes = "vec", tag = "1")]
+    pub message_id: Vec<u8>,
+    #[prost(bool, tag = "2")]
+    pub pin: bool,
+}
+
+impl UpdatePinnedMessagesIntentData {
+    pub fn new(message_id: Vec<u8>, pin: bool) -> Self {
+        Self { message_id, pin }
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, IntentError> {
+        Ok(Self::decode(data)?)
+    }
+}
+
+impl From<UpdatePinnedMessagesIntentData> for Vec<u8> {
+    fn from(intent: UpdatePinnedMessagesIntentData) -> Self {
+        intent.encode_to_vec()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum AddressesOrInstallationIds {
     AccountAddresses(Vec<String>),
@@ -253,6 +390,28 @@ impl UpdateMetadataIntentData {
             field_value: in_ns.to_string(),
         }
     }
+
+    /// An empty value removes the expiration
+    pub fn new_update_group_expires_at_ns(expires_at_ns: Option<i64>) -> Self {
+        Self {
+            field_name: MetadataField::GroupExpiresAtNS.to_string(),
+            field_value: expires_at_ns.map(|ns| ns.to_string()).unwrap_or_default(),
+        }
+    }
+
+    pub fn new_update_join_policy(join_policy: JoinPolicy) -> Self {
+        Self {
+            field_name: MetadataField::JoinPolicy.to_string(),
+            field_value: join_policy.as_str().to_string(),
+        }
+    }
+
+    pub fn new_update_mega_group_id(mega_group_id: &[u8]) -> Self {
+        Self {
+            field_name: MetadataField::MegaGroupId.to_string(),
+            field_value: hex::encode(mega_group_id),
+        }
+    }
 }
 
 impl From<UpdateMetadataIntentData> for Vec<u8> {
@@ -0,0 +1,303 @@
+//! Leaving groups.
+//!
+//! MLS does not let a member commit its own removal, so [`MlsGroup::leave`] sends a
+//! [`LeaveRequest`] to the group and waits in [`GroupMembershipState::PendingRemove`]. Members
+//! that the group's permissions allow to remove members commit the removal the next time they
+//! sync the group, from any of the sync paths. Requests received by a stream are handled by the
+//! next sync. Once the commit is received the group is [`GroupLifecycleState::Left`].
+//!
+//! The request is an application message rather than a self-remove proposal: the `SelfRemove`
+//! proposal type is an MLS extension that every member must support, and existing groups do not
+//! require it in their capabilities. A plain remove proposal is no alternative either, since
+//! the group's permissions are evaluated on commits and a proposal from a member without the
+//! remove permission would be rejected by every committer. The application message is checked
+//! against the same permissions by the members that commit the removal.
+//!
+//! [`GroupLifecycleState::Left`]: crate::storage::group::GroupLifecycleState::Left
+use std::collections::HashSet;
+
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    leave_request::{LeaveRequest, LeaveRequestCodec},
+    ContentCodec,
+};
+
+use super::{
+    group_metadata::GroupMetadata,
+    group_mutable_metadata::GroupMutableMetadata,
+    group_permissions::{GroupMutablePermissions, MembershipPolicy},
+    intents::UpdateGroupMembershipIntentData,
+    validated_commit::{extract_group_membership, CommitParticipant, Inbox},
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group::{ConversationType, GroupMembershipState},
+        group_intent::{IntentKind, IntentState},
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        leave_request::StoredLeaveRequest,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+        StorageError,
+    },
+    subscriptions::{GroupLeft, LocalEvents},
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Leave the group. The other members are asked to remove this inbox, and sending to the
+    /// group fails with [`GroupError::GroupLeft`] from now on.
+    ///
+    /// DMs cannot be left, and neither can groups by their super admins, since super admins
+    /// cannot be removed. Super admins leave with [`Self::leave_with_successor`].
+    pub async fn leave(&self) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type != ConversationType::Group {
+            return Err(GroupError::LeaveForbidden(
+                "only groups can be left".to_string(),
+            ));
+        }
+        if self.is_super_admin(self.client.inbox_id().to_string(), &provider)? {
+            return Err(GroupError::LeaveForbidden(
+                "super admins cannot be removed, give up the role first".to_string(),
+            ));
+        }
+        if self.has_left(&provider)? {
+            return Ok(());
+        }
+
+        let encoded = LeaveRequestCodec::encode(LeaveRequest {})
+            .map_err(|e| GroupError::Generic(e.to_string()))?;
+        self.send_message(&encoded_content_to_bytes(encoded))
+            .await?;
+        provider
+            .conn_ref()
+            .update_group_membership(&self.group_id, GroupMembershipState::PendingRemove)?;
+        let _ = self
+            .client
+            .local_events()
+            .send(LocalEvents::GroupLeft(GroupLeft {
+                group_id: self.group_id.clone(),
+                pending: true,
+            }));
+        Ok(())
+    }
+
+    /// Leave the group, first handing the super admin role of this inbox to `successor_inbox_id`
+    /// if it holds it, so that the group is not left without a super admin
+    pub async fn leave_with_successor(&self, successor_inbox_id: String) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        if self.is_super_admin(self.client.inbox_id().to_string(), &provider)? {
+            self.transfer_super_admin(successor_inbox_id).await?;
+        }
+        self.leave().await
+    }
+
+    /// Record the leave requests of other members. A leave request from another installation
+    /// of this inbox means that this installation is leaving too.
+    pub(super) fn record_leave_request(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) -> Result<(), StorageError> {
+        if message.content_type != ContentType::LeaveRequest
+            || message.kind != GroupMessageKind::Application
+        {
+            return Ok(());
+        }
+        if message.sender_inbox_id == self.client.inbox_id() {
+            return conn
+                .update_group_membership(&self.group_id, GroupMembershipState::PendingRemove);
+        }
+        conn.record_leave_request(&StoredLeaveRequest {
+            group_id: self.group_id.clone(),
+            inbox_id: message.sender_inbox_id.clone(),
+            requested_at_ns: message.sent_at_ns,
+        })
+    }
+
+    /// Remove the members that asked to leave the group, if this inbox is allowed to. Runs at
+    /// the end of every sync, and does nothing when no member asked to leave or a membership
+    /// change of this installation is still pending.
+    pub(super) async fn remove_leaving_members(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let conn = provider.conn_ref();
+        let requests = conn.leave_requests(&self.group_id)?;
+        if requests.is_empty() {
+            return Ok(());
+        }
+        let pending = conn.find_group_intents(
+            self.group_id.clone(),
+            Some(vec![IntentState::ToPublish, IntentState::Published]),
+            Some(vec![IntentKind::UpdateGroupMembership]),
+        )?;
+        if !pending.is_empty() {
+            return Ok(());
+        }
+
+        let (removable, gone) = self.load_mls_group_with_lock(provider, |mls_group| {
+            let extensions = mls_group.extensions();
+            let metadata = GroupMetadata::try_from(extensions)?;
+            let mutable_metadata = GroupMutableMetadata::try_from(extensions)?;
+            let permissions = GroupMutablePermissions::try_from(extensions)?;
+            let membership = extract_group_membership(extensions)?;
+            let members: HashSet<&str> = membership.inbox_ids().into_iter().collect();
+            let actor = CommitParticipant::build(
+                self.client.inbox_id().to_string(),
+                self.client.installation_id().to_vec(),
+                &metadata,
+                &mutable_metadata,
+            );
+
+            let (mut removable, mut gone) = (vec![], vec![]);
+            for StoredLeaveRequest { inbox_id, .. } in requests {
+                if !members.contains(inbox_id.as_str()) {
+                    gone.push(inbox_id);
+                    continue;
+                }
+                let inbox = Inbox {
+                    is_creator: inbox_id == metadata.creator_inbox_id,
+                    is_admin: mutable_metadata.is_admin(&inbox_id),
+                    is_super_admin: mutable_metadata.is_super_admin(&inbox_id),
+                    inbox_id,
+                };
+                if !inbox.is_super_admin
+                    && permissions
+                        .policies
+                        .remove_member_policy
+                        .evaluate(&actor, &inbox)
+                {
+                    removable.push(inbox.inbox_id);
+                }
+            }
+            Ok::<_, GroupError>((removable, gone))
+        })?;
+
+        conn.clear_leave_requests(&self.group_id, &gone)?;
+        if removable.is_empty() {
+            return Ok(());
+        }
+        tracing::info!(
+            inbox_id = self.client.inbox_id(),
+            group_id = hex::encode(&self.group_id),
+            "removing {} members that left the group",
+            removable.len()
+        );
+        let inbox_ids: Vec<&str> = removable.iter().map(String::as_str).collect();
+        let intent_data: UpdateGroupMembershipIntentData = self
+            .get_membership_update_intent(provider, &[], &inbox_ids)
+            .await?;
+        let intent = self.queue_intent(
+            provider,
+            IntentKind::UpdateGroupMembership,
+            intent_data.into(),
+        )?;
+        self.sync_until_intent_resolved(provider, intent.id).await?;
+        conn.clear_leave_requests(&self.group_id, &removable)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group::GroupLifecycleState,
+        InboxOwner,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_leave_group() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(&bola_wallet).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members(&[bola_wallet.get_address()])
+            .await
+            .unwrap();
+        // The creator is the super admin
+        assert!(matches!(
+            amal_group.leave().await,
+            Err(GroupError::LeaveForbidden(_))
+        ));
+
+        let bola_provider = bola.mls_provider().unwrap();
+        bola.sync_welcomes(&bola_provider).await.unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.leave().await.unwrap();
+        let stored = bola_provider
+            .conn_ref()
+            .find_group(&bola_group.group_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.membership_state, GroupMembershipState::PendingRemove);
+        assert!(matches!(
+            bola_group.send_message(b"one more thing").await,
+            Err(GroupError::GroupLeft)
+        ));
+
+        // Syncing the group commits the removal
+        amal_group.sync().await.unwrap();
+        assert_eq!(amal_group.members().await.unwrap().len(), 1);
+        assert!(amal
+            .store()
+            .conn()
+            .unwrap()
+            .leave_requests(&amal_group.group_id)
+            .unwrap()
+            .is_empty());
+
+        bola_group.sync().await.unwrap();
+        assert_eq!(
+            bola_group.lifecycle_state().unwrap(),
+            GroupLifecycleState::Left
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_leave_with_successor() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        // The only super admin can not be removed
+        assert!(matches!(
+            amal_group
+                .remove_members_by_inbox_id(&[amal.inbox_id()])
+                .await,
+            Err(GroupError::LastSuperAdmin)
+        ));
+
+        amal_group
+            .leave_with_successor(bola.inbox_id().to_string())
+            .await
+            .unwrap();
+        let provider = amal_group.mls_provider().unwrap();
+        assert_eq!(
+            amal_group.super_admin_list(&provider).unwrap(),
+            vec![bola.inbox_id().to_string()]
+        );
+        let stored = provider
+            .conn_ref()
+            .find_group(&amal_group.group_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.membership_state, GroupMembershipState::PendingRemove);
+    }
+}
@@ -0,0 +1,246 @@
+//! Locations sent with the [`LocationCodec`].
+//!
+//! A live share starts with a location that has `live_until_ns` set, and is followed by updates
+//! that reference the first message. Live shares are coalesced so that they do not flood the
+//! group's messages:
+//! - The latest position of each sender is kept in the `live_locations` table
+//! - Only the latest [`LIVE_LOCATION_HISTORY_LIMIT`] updates of a share are kept as messages
+//! - Once the share window ends, the position and all updates are deleted. The message that
+//!   started the share is kept.
+use prost::Message;
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    location::{Location, LocationCodec},
+    ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    configuration::LIVE_LOCATION_HISTORY_LIMIT,
+    storage::{
+        db_connection::DbConnection,
+        group_message::{ContentType, StoredGroupMessage},
+        live_location::StoredLiveLocation,
+        StorageError,
+    },
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send a location to the group once. Returns the id of the message.
+    pub async fn share_location(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        accuracy_meters: f64,
+    ) -> Result<Vec<u8>, GroupError> {
+        self.send_location(Location {
+            latitude,
+            longitude,
+            accuracy_meters,
+            live_until_ns: 0,
+            live_share_reference: String::new(),
+        })
+        .await
+    }
+
+    /// Start sharing a live location for `duration_ns`. Returns the id of the share, which
+    /// is used to send updates.
+    pub async fn start_live_location(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        accuracy_meters: f64,
+        duration_ns: i64,
+    ) -> Result<Vec<u8>, GroupError> {
+        if duration_ns <= 0 {
+            return Err(GroupError::Generic(
+                "a live location must be shared for a positive duration".to_string(),
+            ));
+        }
+        self.send_location(Location {
+            latitude,
+            longitude,
+            accuracy_meters,
+            live_until_ns: now_ns() + duration_ns,
+            live_share_reference: String::new(),
+        })
+        .await
+    }
+
+    /// Send a new position in a live share started by this inbox
+    pub async fn update_live_location(
+        &self,
+        share_id: &[u8],
+        latitude: f64,
+        longitude: f64,
+        accuracy_meters: f64,
+    ) -> Result<(), GroupError> {
+        let live_until_ns = self.own_live_location(share_id)?.expires_at_ns;
+        self.send_location(Location {
+            latitude,
+            longitude,
+            accuracy_meters,
+            live_until_ns,
+            live_share_reference: hex::encode(share_id),
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// End a live share started by this inbox before its share window ends
+    pub async fn stop_live_location(&self, share_id: &[u8]) -> Result<(), GroupError> {
+        let current = self.own_live_location(share_id)?;
+        self.send_location(Location {
+            latitude: current.latitude,
+            longitude: current.longitude,
+            accuracy_meters: current.accuracy_meters,
+            live_until_ns: now_ns(),
+            live_share_reference: hex::encode(share_id),
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// The latest position of every member that is sharing their live location in the group
+    pub fn live_locations(&self) -> Result<Vec<StoredLiveLocation>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.live_locations(&self.group_id, now_ns())?)
+    }
+
+    fn own_live_location(&self, share_id: &[u8]) -> Result<StoredLiveLocation, GroupError> {
+        self.live_locations()?
+            .into_iter()
+            .find(|location| {
+                location.share_id == share_id
+                    && location.sender_inbox_id == self.context().inbox_id()
+            })
+            .ok_or_else(|| {
+                GroupError::Generic(format!(
+                    "no live location share {} of this inbox",
+                    hex::encode(share_id)
+                ))
+            })
+    }
+
+    async fn send_location(&self, location: Location) -> Result<Vec<u8>, GroupError> {
+        let encoded =
+            LocationCodec::encode(location).map_err(|e| GroupError::Generic(e.to_string()))?;
+        self.send_message(&encoded_content_to_bytes(encoded)).await
+    }
+}
+
+/// Record the position of live location messages, and trim the updates of the share.
+/// Malformed locations are skipped, so that they do not block the rest of the group's messages.
+pub(crate) fn record_location_content(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<(), StorageError> {
+    if message.content_type != ContentType::Location {
+        return Ok(());
+    }
+    let Some(location) = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+        .ok()
+        .and_then(|content| LocationCodec::decode(content).ok())
+    else {
+        tracing::warn!("skipping malformed location");
+        return Ok(());
+    };
+    if !location.is_live() {
+        return Ok(());
+    }
+
+    let share_id = if location.live_share_reference.is_empty() {
+        message.id.clone()
+    } else {
+        let Ok(share_id) = hex::decode(&location.live_share_reference) else {
+            tracing::warn!("skipping live location update with a malformed reference");
+            return Ok(());
+        };
+        share_id
+    };
+    conn.record_live_location(&StoredLiveLocation {
+        group_id: message.group_id.clone(),
+        sender_inbox_id: message.sender_inbox_id.clone(),
+        share_id: share_id.clone(),
+        message_id: message.id.clone(),
+        latitude: location.latitude,
+        longitude: location.longitude,
+        accuracy_meters: location.accuracy_meters,
+        updated_at_ns: message.sent_at_ns,
+        expires_at_ns: location.live_until_ns,
+    })?;
+    if share_id != message.id {
+        conn.trim_live_location_history(
+            &message.group_id,
+            &message.sender_inbox_id,
+            &share_id,
+            LIVE_LOCATION_HISTORY_LIMIT,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        configuration::LIVE_LOCATION_HISTORY_LIMIT,
+        groups::GroupMetadataOptions,
+        storage::group_message::{ContentType, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_live_location_updates_are_coalesced() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        let share_id = amal_group
+            .start_live_location(52.0, 4.0, 5.0, 60 * 1_000_000_000)
+            .await
+            .unwrap();
+        let updates = LIVE_LOCATION_HISTORY_LIMIT + 5;
+        for i in 0..updates {
+            amal_group
+                .update_live_location(&share_id, 52.0, 4.0 + i as f64 / 1000.0, 5.0)
+                .await
+                .unwrap();
+        }
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+
+        let live = bola_group.live_locations().unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].share_id, share_id);
+        assert_eq!(live[0].longitude, 4.0 + (updates - 1) as f64 / 1000.0);
+        // The first message and the latest updates are kept
+        let messages = bola_group
+            .find_messages(&MsgQueryArgs {
+                content_types: Some(vec![ContentType::Location]),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages.len() as i64, LIVE_LOCATION_HISTORY_LIMIT + 1);
+
+        amal_group.stop_live_location(&share_id).await.unwrap();
+        bola_group.sync().await.unwrap();
+        assert!(bola_group.live_locations().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,531 @@
+//! Communities that are larger than a single MLS group can handle efficiently.
+//!
+//! A [`MegaGroup`] spreads its members across several regular groups ("shards") of at most
+//! [`ScopedGroupClient::max_group_size`] inboxes each. The installation that creates the mega
+//! group is a member of every shard and relays messages between them: every application message
+//! sent in a shard is forwarded to the other shards, with the original sender and sent time
+//! attached, when the creator syncs. Every shard is tagged with the id of the mega group in its
+//! metadata, so its members can read the messages of the whole mega group from their own shard,
+//! with relayed copies attributed to their original sender.
+use std::{collections::HashSet, sync::Arc};
+
+use prost::Message;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    forward::{forward_content, ForwardedFrom},
+};
+use xmtp_id::{InboxId, InboxIdRef};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{
+    group_mutable_metadata::MetadataField,
+    group_permissions::{extract_group_permissions, PolicySet},
+    intents::UpdateMetadataIntentData,
+    members::GroupMember,
+    validated_commit::extract_group_membership,
+    GroupError, GroupMetadataOptions, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    storage::{
+        group::GroupMembershipState,
+        group_intent::IntentKind,
+        group_message::{
+            DeliveryStatus, GroupMessageKind, MsgQueryArgs, SortDirection, StoredGroupMessage,
+        },
+        mega_group_shard::StoredMegaGroupShard,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+        NotFound,
+    },
+    subscriptions::LocalEvents,
+    Store,
+};
+
+/// Parameters of a relayed copy naming the member who sent the original message, and when
+const RELAYED_SENDER_INBOX_ID_KEY: &str = "megaGroup:senderInboxId";
+const RELAYED_SENT_AT_NS_KEY: &str = "megaGroup:sentAtNs";
+
+pub struct MegaGroup<ScopedClient> {
+    /// Id of the first shard
+    pub id: Vec<u8>,
+    client: Arc<ScopedClient>,
+    /// Every shard for the creator, the shard of this installation for other members
+    shards: Vec<MlsGroup<ScopedClient>>,
+    /// Inbox of the creator, which relays messages between the shards
+    relay_inbox_id: InboxId,
+    /// Whether this installation is the creator
+    relay: bool,
+}
+
+impl<ScopedClient: ScopedGroupClient> MegaGroup<ScopedClient> {
+    /// Create a mega group with a single, empty shard
+    pub async fn create(
+        client: Arc<ScopedClient>,
+        permissions_policy_set: Option<PolicySet>,
+        opts: GroupMetadataOptions,
+    ) -> Result<Self, GroupError> {
+        let provider = client.mls_provider()?;
+        let shard = MlsGroup::create_and_insert(
+            client.clone(),
+            &provider,
+            GroupMembershipState::Allowed,
+            permissions_policy_set.unwrap_or_default(),
+            opts,
+        )?;
+        let id = shard.group_id.clone();
+        StoredMegaGroupShard::new(id.clone(), id.clone(), 0).store(provider.conn_ref())?;
+        tag_shard(&shard, &provider, &id).await?;
+        let _ = client
+            .local_events()
+            .send(LocalEvents::NewGroup(id.clone()));
+
+        Ok(Self {
+            id,
+            relay_inbox_id: client.inbox_id().to_string(),
+            relay: true,
+            client,
+            shards: vec![shard],
+        })
+    }
+
+    /// Load a mega group created by this installation
+    pub fn load(client: Arc<ScopedClient>, id: Vec<u8>) -> Result<Self, GroupError> {
+        let conn = client.store().conn()?;
+        let mut shards = vec![];
+        for shard in conn.mega_group_shards(&id)? {
+            let group = conn
+                .find_group(&shard.group_id)?
+                .ok_or_else(|| NotFound::GroupById(shard.group_id.clone()))?;
+            shards.push(MlsGroup::new_from_arc(
+                client.clone(),
+                group.id,
+                group.created_at_ns,
+            ));
+        }
+        if shards.is_empty() {
+            return Err(NotFound::GroupById(id).into());
+        }
+
+        Ok(Self {
+            id,
+            relay_inbox_id: client.inbox_id().to_string(),
+            relay: true,
+            client,
+            shards,
+        })
+    }
+
+    /// The mega group the group `group_id` is a shard of
+    pub async fn from_shard(
+        client: Arc<ScopedClient>,
+        group_id: Vec<u8>,
+    ) -> Result<Self, GroupError> {
+        let provider = client.mls_provider()?;
+        let group = provider
+            .conn_ref()
+            .find_group(&group_id)?
+            .ok_or_else(|| NotFound::GroupById(group_id.clone()))?;
+        let shard = MlsGroup::new_from_arc(client.clone(), group.id, group.created_at_ns);
+        let id = shard
+            .mutable_metadata(&provider)?
+            .attributes
+            .get(MetadataField::MegaGroupId.as_str())
+            .and_then(|id| hex::decode(id).ok())
+            .ok_or_else(|| NotFound::MegaGroupShard(group_id))?;
+        if !provider.conn_ref().mega_group_shards(&id)?.is_empty() {
+            return Self::load(client, id);
+        }
+
+        let relay_inbox_id = shard.metadata(&provider).await?.creator_inbox_id;
+        Ok(Self {
+            id,
+            client,
+            shards: vec![shard],
+            relay_inbox_id,
+            relay: false,
+        })
+    }
+
+    pub fn shards(&self) -> &[MlsGroup<ScopedClient>] {
+        &self.shards
+    }
+
+    /// Add members to the shards that have room left, and create new shards for the rest.
+    /// Inboxes that are already a member of a shard are skipped.
+    pub async fn add_members_by_inbox_id<S: AsRef<str>>(
+        &mut self,
+        inbox_ids: &[S],
+    ) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        let max = self.client.max_group_size();
+
+        let mut shard_sizes = vec![];
+        let mut existing = HashSet::new();
+        for shard in &self.shards {
+            let members = shard_members(shard, &provider)?;
+            shard_sizes.push(members.len());
+            existing.extend(members);
+        }
+        let mut remaining: Vec<&str> = vec![];
+        for inbox_id in inbox_ids.iter().map(AsRef::as_ref) {
+            if existing.insert(inbox_id.to_string()) {
+                remaining.push(inbox_id);
+            }
+        }
+
+        for (shard, size) in self.shards.iter().zip(shard_sizes) {
+            let room = max.saturating_sub(size).min(remaining.len());
+            if room == 0 {
+                continue;
+            }
+            let batch: Vec<&str> = remaining.drain(..room).collect();
+            shard
+                .add_members_by_inbox_id_with_provider(&provider, &batch)
+                .await?;
+        }
+
+        while !remaining.is_empty() {
+            let shard = self.create_shard(&provider).await?;
+            // The new shard already holds this installation's inbox
+            let room = max.saturating_sub(1).min(remaining.len());
+            if room == 0 {
+                return Err(GroupError::UserLimitExceeded { size: 2, max });
+            }
+            let batch: Vec<&str> = remaining.drain(..room).collect();
+            shard
+                .add_members_by_inbox_id_with_provider(&provider, &batch)
+                .await?;
+            self.shards.push(shard);
+        }
+
+        Ok(())
+    }
+
+    /// Remove members from whichever shards they are in
+    pub async fn remove_members_by_inbox_id(
+        &self,
+        inbox_ids: &[InboxIdRef<'_>],
+    ) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        for shard in &self.shards {
+            let members = shard_members(shard, &provider)?;
+            let to_remove: Vec<InboxIdRef<'_>> = inbox_ids
+                .iter()
+                .copied()
+                .filter(|inbox_id| members.contains(*inbox_id))
+                .collect();
+            if !to_remove.is_empty() {
+                shard.remove_members_by_inbox_id(&to_remove).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send an encoded content to the shard of this installation, and return its id there. The
+    /// creator relays it to the other shards right away when it is the sender, and on its next
+    /// [`Self::sync`] otherwise.
+    pub async fn send_message(&self, message: &[u8]) -> Result<Vec<u8>, GroupError> {
+        let message_id = self.shards[0].send_message(message).await?;
+        if self.relay {
+            self.relay_messages().await?;
+        }
+        Ok(message_id)
+    }
+
+    /// Sync the shards, and relay their new messages if this installation is the creator
+    pub async fn sync(&self) -> Result<(), GroupError> {
+        for shard in &self.shards {
+            shard.sync().await?;
+        }
+        if self.relay {
+            self.relay_messages().await?;
+        }
+        Ok(())
+    }
+
+    /// Query the messages of the mega group, ordered by the time they were sent. The creator
+    /// reads the originals from every shard; other members read relayed copies from their shard,
+    /// with the sender and sent time of the original.
+    pub fn find_messages(
+        &self,
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessage>, GroupError> {
+        let mut messages = vec![];
+        for shard in &self.shards {
+            for mut message in shard.find_messages(args)? {
+                match self.relayed_from(&message) {
+                    Some(_) if self.relay => continue,
+                    Some((sender_inbox_id, sent_at_ns)) => {
+                        message.sender_inbox_id = sender_inbox_id;
+                        message.sent_at_ns = sent_at_ns;
+                    }
+                    None => {}
+                }
+                messages.push(message);
+            }
+        }
+
+        messages.sort_by_key(|message| message.sent_at_ns);
+        if let Some(SortDirection::Descending) = args.direction {
+            messages.reverse();
+        }
+        if let Some(limit) = args.limit {
+            messages.truncate(limit.max(0) as usize);
+        }
+        Ok(messages)
+    }
+
+    /// Members of all shards. This installation's inbox is listed once.
+    pub async fn members(&self) -> Result<Vec<GroupMember>, GroupError> {
+        let mut seen: HashSet<InboxId> = HashSet::new();
+        let mut members = vec![];
+        for shard in &self.shards {
+            for member in shard.members().await? {
+                if seen.insert(member.inbox_id.clone()) {
+                    members.push(member);
+                }
+            }
+        }
+        Ok(members)
+    }
+
+    /// Forward the application messages sent in each shard since the last relay to every other
+    /// shard. Content that cannot be forwarded, such as reactions to a message of one shard, is
+    /// not relayed.
+    async fn relay_messages(&self) -> Result<(), GroupError> {
+        let conn = self.client.store().conn()?;
+        for stored in conn.mega_group_shards(&self.id)? {
+            let Some(from) = self
+                .shards
+                .iter()
+                .find(|shard| shard.group_id == stored.group_id)
+            else {
+                continue;
+            };
+            let messages = from.find_messages(&MsgQueryArgs {
+                sent_after_ns: Some(stored.relayed_until_ns),
+                kind: Some(GroupMessageKind::Application),
+                delivery_status: Some(DeliveryStatus::Published),
+                direction: Some(SortDirection::Ascending),
+                ..Default::default()
+            })?;
+            for message in messages {
+                if let Some(content) = self.relay_content(&message) {
+                    let content = encoded_content_to_bytes(content);
+                    for to in self.shards.iter().filter(|to| to.group_id != from.group_id) {
+                        to.send_message(&content).await?;
+                    }
+                }
+                conn.set_mega_group_shard_relayed_until(&from.group_id, message.sent_at_ns)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The copy of `message` to send to the other shards, or `None` if it is a relayed copy
+    /// itself or cannot be forwarded
+    fn relay_content(&self, message: &StoredGroupMessage) -> Option<EncodedContent> {
+        if self.relayed_from(message).is_some() {
+            return None;
+        }
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice()).ok()?;
+        let mut content = forward_content(
+            content,
+            ForwardedFrom {
+                sender_inbox_id: message.sender_inbox_id.clone(),
+                sent_at_ns: message.sent_at_ns,
+                conversation_id: self.id.clone(),
+            },
+        )
+        .ok()?;
+        content.parameters.insert(
+            RELAYED_SENDER_INBOX_ID_KEY.to_string(),
+            message.sender_inbox_id.clone(),
+        );
+        content.parameters.insert(
+            RELAYED_SENT_AT_NS_KEY.to_string(),
+            message.sent_at_ns.to_string(),
+        );
+        Some(content)
+    }
+
+    /// The original sender and sent time of a copy relayed by the creator. Copies are only
+    /// trusted from the creator, so members cannot send messages in the name of others.
+    fn relayed_from(&self, message: &StoredGroupMessage) -> Option<(InboxId, i64)> {
+        if message.sender_inbox_id != self.relay_inbox_id {
+            return None;
+        }
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice()).ok()?;
+        let parameters = &content.parameters;
+        Some((
+            parameters.get(RELAYED_SENDER_INBOX_ID_KEY)?.clone(),
+            parameters.get(RELAYED_SENT_AT_NS_KEY)?.parse().ok()?,
+        ))
+    }
+
+    /// Create a new shard with the same metadata and permissions as the first one
+    async fn create_shard(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<MlsGroup<ScopedClient>, GroupError> {
+        let first = &self.shards[0];
+        let policies = first.load_mls_group_with_lock(provider, |mls_group| {
+            Ok(extract_group_permissions(&mls_group)?.policies)
+        })?;
+        let opts = GroupMetadataOptions {
+            name: Some(first.group_name(provider)?),
+            image_url_square: Some(first.group_image_url_square(provider)?),
+            description: Some(first.group_description(provider)?),
+            pinned_frame_url: Some(first.group_pinned_frame_url(provider)?),
+            message_disappearing_settings: None,
+            expires_at_ns: first.group_expiration(provider)?,
+        };
+
+        let shard = MlsGroup::create_and_insert(
+            self.client.clone(),
+            provider,
+            GroupMembershipState::Allowed,
+            policies,
+            opts,
+        )?;
+        StoredMegaGroupShard::new(
+            self.id.clone(),
+            shard.group_id.clone(),
+            self.shards.len() as i32,
+        )
+        .store(provider.conn_ref())?;
+        tag_shard(&shard, provider, &self.id).await?;
+        let _ = self
+            .client
+            .local_events()
+            .send(LocalEvents::NewGroup(shard.group_id.clone()));
+
+        Ok(shard)
+    }
+}
+
+/// Record the id of the mega group in the metadata of `shard`, so its members can find it
+async fn tag_shard<ScopedClient: ScopedGroupClient>(
+    shard: &MlsGroup<ScopedClient>,
+    provider: &XmtpOpenMlsProvider,
+    mega_group_id: &[u8],
+) -> Result<(), GroupError> {
+    let intent_data: Vec<u8> =
+        UpdateMetadataIntentData::new_update_mega_group_id(mega_group_id).into();
+    let intent = shard.queue_intent(provider, IntentKind::MetadataUpdate, intent_data)?;
+    shard.sync_until_intent_resolved(provider, intent.id).await
+}
+
+fn shard_members<ScopedClient: ScopedGroupClient>(
+    shard: &MlsGroup<ScopedClient>,
+    provider: &XmtpOpenMlsProvider,
+) -> Result<HashSet<String>, GroupError> {
+    let membership = shard.load_mls_group_with_lock(provider, |mls_group| {
+        Ok(extract_group_membership(mls_group.extensions())?)
+    })?;
+    Ok(membership.members.into_keys().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_mega_group_shards_members() {
+        let mut amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        amal.max_group_size = 2;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal = Arc::new(amal);
+
+        let mut mega_group = MegaGroup::create(amal.clone(), None, GroupMetadataOptions::default())
+            .await
+            .unwrap();
+        mega_group
+            .add_members_by_inbox_id(&[bola.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        assert_eq!(mega_group.shards().len(), 2);
+        assert_eq!(mega_group.members().await.unwrap().len(), 3);
+
+        let application = MsgQueryArgs {
+            kind: Some(GroupMessageKind::Application),
+            ..Default::default()
+        };
+        let text =
+            |text: &str| encoded_content_to_bytes(TextCodec::encode(text.to_string()).unwrap());
+        mega_group
+            .send_message(&text("hello everyone"))
+            .await
+            .unwrap();
+        assert_eq!(mega_group.find_messages(&application).unwrap().len(), 1);
+
+        // Caro is in the second shard, and gets the message relayed from the first one
+        caro.sync_welcomes(&caro.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let caro_view = caro
+            .mega_group_for_shard(mega_group.shards()[1].group_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(caro_view.id, mega_group.id);
+        caro_view.sync().await.unwrap();
+        let messages = caro_view.find_messages(&application).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender_inbox_id, amal.inbox_id());
+        caro_view.send_message(&text("hi from caro")).await.unwrap();
+
+        // The creator relays it to the first shard when it syncs, and reads it once
+        mega_group.sync().await.unwrap();
+        let messages = mega_group.find_messages(&application).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].sender_inbox_id, caro.inbox_id());
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_view = bola
+            .mega_group_for_shard(mega_group.shards()[0].group_id.clone())
+            .await
+            .unwrap();
+        bola_view.sync().await.unwrap();
+        let messages = bola_view.find_messages(&application).unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.sender_inbox_id.as_str())
+                .collect::<Vec<_>>(),
+            vec![amal.inbox_id(), caro.inbox_id()]
+        );
+
+        let loaded = MegaGroup::load(amal.clone(), mega_group.id.clone()).unwrap();
+        assert_eq!(
+            loaded
+                .shards()
+                .iter()
+                .map(|shard| shard.group_id.clone())
+                .collect::<Vec<_>>(),
+            mega_group
+                .shards()
+                .iter()
+                .map(|shard| shard.group_id.clone())
+                .collect::<Vec<_>>()
+        );
+
+        // Adding beyond the limit of a regular group is a typed error
+        let err = mega_group.shards()[0]
+            .add_members_by_inbox_id(&[caro.inbox_id()])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GroupError::UserLimitExceeded { size: 3, max: 2 }
+        ));
+    }
+}
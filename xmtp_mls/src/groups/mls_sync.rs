@@ -1,24 +1,32 @@
 use super::{
     build_extensions_for_admin_lists_update, build_extensions_for_metadata_update,
-    build_extensions_for_permissions_update, build_group_membership_extension,
+    build_extensions_for_permissions_update, build_extensions_for_super_admin_transfer,
+    build_group_membership_extension,
     intents::{
-        Installation, IntentError, PostCommitAction, SendMessageIntentData, SendWelcomesAction,
-        UpdateAdminListIntentData, UpdateGroupMembershipIntentData, UpdatePermissionIntentData,
+        Installation, IntentError, PostCommitAction, ReaddInstallationsIntentData,
+        RemoveInstallationsIntentData, SendMessageIntentData, SendWelcomesAction,
+        TransferSuperAdminIntentData, UpdateAdminListIntentData, UpdateGroupMembershipIntentData,
+        UpdatePermissionIntentData, UpdatePinnedMessagesIntentData,
     },
+    locations::record_location_content,
+    pins::{build_extensions_for_pinned_messages_update, parse_pinned_message_ids},
+    pipeline::{self, EnvelopeTrace, PipelineStage},
+    polls::record_poll_content,
     validated_commit::{extract_group_membership, CommitValidationError},
     GroupError, HmacKey, MlsGroup, ScopedGroupClient,
 };
-use crate::configuration::sync_update_installations_interval_ns;
 use crate::groups::group_mutable_metadata::MetadataField;
 use crate::storage::group_intent::IntentKind::MetadataUpdate;
 use crate::{
     configuration::{
-        GRPC_DATA_LIMIT, HMAC_SALT, MAX_GROUP_SIZE, MAX_INTENT_PUBLISH_ATTEMPTS, MAX_PAST_EPOCHS,
+        HMAC_SALT, MAX_INTENT_PUBLISH_ATTEMPTS, MAX_PAST_EPOCHS, MAX_POST_COMMIT_ACTION_ATTEMPTS,
+        MAX_WELCOME_READD_ATTEMPTS, MAX_WELCOME_SEND_ATTEMPTS, WELCOME_ACK_TIMEOUT_NS,
     },
     groups::{
         device_sync::{preference_sync::UserPreferenceUpdate, DeviceSyncContent},
         intents::UpdateMetadataIntentData,
         validated_commit::ValidatedCommit,
+        welcomes::{default_batches, is_payload_too_large, WelcomeSendReport},
     },
     hpke::{encrypt_welcome, HpkeError},
     identity::{parse_credential, IdentityError},
@@ -26,8 +34,10 @@ use crate::{
     intents::ProcessIntentError,
     storage::xmtp_openmls_provider::XmtpOpenMlsProvider,
     storage::{
+        cursor_store::SharedCursor,
         db_connection::DbConnection,
-        group_intent::{IntentKind, IntentState, StoredGroupIntent, ID},
+        failed_commit::StoredFailedCommit,
+        group_intent::{IntentKind, IntentState, NewGroupIntent, StoredGroupIntent, ID},
         group_message::{ContentType, DeliveryStatus, GroupMessageKind, StoredGroupMessage},
         refresh_state::EntityKind,
         serialization::{db_deserialize, db_serialize},
@@ -35,13 +45,16 @@ use crate::{
         user_preferences::StoredUserPreferences,
         ProviderTransactions, StorageError,
     },
-    subscriptions::{LocalEvents, SyncMessage},
+    subscriptions::{
+        GroupLeft, KeyRotated, LocalEvents, MessageDelivery, SyncMessage, UndeliveredWelcome,
+    },
     utils::{hash::sha256, id::calculate_message_id, time::hmac_epoch},
     Delete, Fetch, StoreOrIgnore,
 };
-use futures::future::try_join_all;
+use futures::future::join_all;
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
+use openmls::prelude::BasicCredentialError;
 use openmls::{
     credentials::BasicCredential,
     extensions::Extensions,
@@ -55,7 +68,6 @@ use openmls::{
     },
     treesync::LeafNodeParameters,
 };
-use openmls::{framing::WireFormat, prelude::BasicCredentialError};
 use openmls_traits::{signatures::Signer, OpenMlsProvider};
 use prost::bytes::Bytes;
 use prost::Message;
@@ -67,12 +79,12 @@ use std::{
 };
 use thiserror::Error;
 use tracing::debug;
-use xmtp_common::{retry_async, Retry, RetryableError};
+use xmtp_common::{retry_async, ErrorClass, RetryableError};
 use xmtp_content_types::{group_updated::GroupUpdatedCodec, CodecError, ContentCodec};
 use xmtp_id::{InboxId, InboxIdRef};
 use xmtp_proto::xmtp::mls::{
     api::v1::{
-        group_message::{Version as GroupMessageVersion, V1 as GroupMessageV1},
+        group_message::V1 as GroupMessageV1,
         group_message_input::{Version as GroupMessageInputVersion, V1 as GroupMessageInputV1},
         welcome_message_input::{
             Version as WelcomeMessageInputVersion, V1 as WelcomeMessageInputV1,
@@ -136,6 +148,17 @@ pub enum GroupMessageProcessingError {
     AssociationDeserialization(#[from] xmtp_id::associations::DeserializationError),
 }
 
+impl GroupMessageProcessingError {
+    /// Whether the envelope was skipped because it was processed before
+    pub(crate) fn is_already_processed(&self) -> bool {
+        matches!(
+            self,
+            Self::AlreadyProcessed(_)
+                | Self::ProcessIntent(ProcessIntentError::AlreadyProcessed(_))
+        )
+    }
+}
+
 impl RetryableError for GroupMessageProcessingError {
     fn is_retryable(&self) -> bool {
         match self {
@@ -163,6 +186,17 @@ impl RetryableError for GroupMessageProcessingError {
             | Self::UnsupportedMessageType(_) => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::Diesel(err) => err.error_class(),
+            Self::Storage(err) => err.error_class(),
+            Self::Identity(err) => err.error_class(),
+            Self::ProcessIntent(err) => err.error_class(),
+            Self::CommitValidation(err) => err.error_class(),
+            _ => Some(ErrorClass::Mls),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -195,14 +229,17 @@ where
             self.client.inbox_id(),
         );
         self.maybe_update_installations(&mls_provider, None).await?;
+        self.readd_stale_installations(&mls_provider)?;
+        self.maybe_rotate_key(&mls_provider)?;
 
-        self.sync_with_conn(&mls_provider).await
+        self.sync_with_conn(&mls_provider).await?;
+        self.check_verifications(&mls_provider).await
     }
 
     // TODO: Should probably be renamed to `sync_with_provider`
     #[tracing::instrument(skip_all)]
     pub async fn sync_with_conn(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
-        let _mutex = self.mutex.lock().await;
+        let mutex = self.mutex.lock().await;
         let mut errors: Vec<GroupError> = vec![];
 
         let conn = provider.conn_ref();
@@ -223,6 +260,12 @@ where
             // We don't return an error if receive fails, because it's possible this is caused
             // by malicious data sent over the network, or messages from before the user was
             // added to the group
+        } else if let Err(e) = conn.update_synced_at(
+            &self.group_id,
+            EntityKind::Group,
+            xmtp_common::time::now_ns(),
+        ) {
+            tracing::warn!(error = %e, "failed to record the sync time");
         }
 
         if let Err(post_commit_err) = self.post_commit(conn).await {
@@ -234,6 +277,19 @@ where
             errors.push(post_commit_err);
         }
 
+        if let Err(e) = conn.check_group_invariants() {
+            tracing::error!(error = %e, "failed to check group invariants");
+        }
+        drop(mutex);
+
+        // The removal is synced like any other intent, which needs the group mutex
+        if let Err(e) = Box::pin(self.remove_leaving_members(provider)).await {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                "failed to remove members that left the group: {e}"
+            );
+        }
+
         // Return a combination of publish and post_commit errors
         if !errors.is_empty() {
             return Err(GroupError::Sync(errors));
@@ -393,7 +449,11 @@ where
                 | IntentKind::UpdateGroupMembership
                 | IntentKind::UpdateAdminList
                 | IntentKind::MetadataUpdate
-                | IntentKind::UpdatePermission => {
+                | IntentKind::UpdatePermission
+                | IntentKind::ReaddInstallations
+                | IntentKind::RemoveInstallations
+                | IntentKind::TransferSuperAdmin
+                | IntentKind::UpdatePinnedMessages => {
                     if let Some(published_in_epoch) = intent.published_in_epoch {
                         let published_in_epoch_u64 = published_in_epoch as u64;
                         let group_epoch_u64 = group_epoch.as_u64();
@@ -457,11 +517,22 @@ where
                         return Ok(IntentState::ToPublish);
                     } else {
                         // If no error committing the change, write a transcript message
+                        self.mirror_pinned_messages(conn, &validated_commit)?;
                         self.save_transcript_message(
                             conn,
                             validated_commit,
                             envelope_timestamp_ns,
                         )?;
+                        if intent.kind == IntentKind::KeyUpdate {
+                            let _ = self.client.local_events().send(LocalEvents::KeyRotated(
+                                KeyRotated {
+                                    group_id: self.group_id.clone(),
+                                    installation_id: self.client.installation_id().to_vec(),
+                                    epoch: mls_group.epoch().as_u64(),
+                                    rotated_at_ns: envelope_timestamp_ns as i64,
+                                },
+                            ));
+                        }
                     }
                 }
                 IntentKind::SendMessage => {
@@ -475,7 +546,11 @@ where
                         return Ok(IntentState::ToPublish);
                     }
                     if let Some(id) = intent.message_id()? {
-                        conn.set_delivery_status_to_published(&id, envelope_timestamp_ns)?;
+                        let updated =
+                            conn.set_delivery_status_to_published(&id, envelope_timestamp_ns)?;
+                        if updated > 0 {
+                            self.report_delivery(conn, id, DeliveryStatus::Published);
+                        }
                     }
                 }
             };
@@ -485,6 +560,53 @@ where
         .await
     }
 
+    /// Broadcast the new delivery status of a message once it is committed
+    fn report_delivery(&self, conn: &DbConnection, message_id: Vec<u8>, status: DeliveryStatus) {
+        let local_events = self.client.local_events().clone();
+        let delivery = MessageDelivery {
+            group_id: self.group_id.clone(),
+            message_id,
+            status,
+        };
+        conn.after_commit(move || {
+            let _ = local_events.send(LocalEvents::MessageDelivery(delivery));
+        });
+    }
+
+    /// Whether `installation_id` belongs to `inbox_id` in the association state the group
+    /// membership refers to, built from the identity updates stored locally
+    async fn is_verified_sender(
+        &self,
+        conn: &DbConnection,
+        mls_group: &OpenMlsGroup,
+        inbox_id: InboxIdRef<'_>,
+        installation_id: &[u8],
+    ) -> bool {
+        let Ok(membership) = extract_group_membership(mls_group.extensions()) else {
+            return false;
+        };
+        let Some(sequence_id) = membership.get(inbox_id) else {
+            return false;
+        };
+        match self
+            .client
+            .get_association_state(conn, inbox_id, Some(*sequence_id as i64))
+            .await
+        {
+            Ok(state) => state
+                .installation_ids()
+                .iter()
+                .any(|id| id == installation_id),
+            Err(e) => {
+                tracing::warn!(
+                    inbox_id,
+                    "could not verify the association chain of the sender: {e}"
+                );
+                false
+            }
+        }
+    }
+
     #[tracing::instrument(level = "trace", skip_all)]
     async fn process_external_message(
         &self,
@@ -502,6 +624,31 @@ where
             let decrypted_message = mls_group.process_message(provider, message)?;
             let (sender_inbox_id, sender_installation_id) =
                 extract_message_sender(&mut mls_group, &decrypted_message, envelope_timestamp_ns)?;
+            // Anything sent by an installation we welcomed means the welcome was processed
+            provider
+                .conn_ref()
+                .clear_pending_welcome(&self.group_id, &sender_installation_id)?;
+            provider
+                .conn_ref()
+                .record_installation_activity(&sender_installation_id, envelope_timestamp_ns as i64)?;
+            provider.conn_ref().observe_installation_key_use(
+                &sender_inbox_id,
+                &sender_installation_id,
+                envelope_timestamp_ns as i64,
+            )?;
+            // Messages sent by our other installations are mirrored into this installation's
+            // history, so that sent messages show up on every device without history sync. The
+            // mirrored copy is only stored once the message is on the network, so it is already
+            // published and no delivery acknowledgement is needed from the sending installation.
+            let sent_by_me = sender_inbox_id == self.client.inbox_id();
+            let sender_verified = self
+                .is_verified_sender(
+                    provider.conn_ref(),
+                    &mls_group,
+                    &sender_inbox_id,
+                    &sender_installation_id,
+                )
+                .await;
 
             tracing::info!(
                 inbox_id = self.client.inbox_id(),
@@ -548,7 +695,7 @@ where
                             let message_id =
                                 calculate_message_id(&self.group_id, &content, &idempotency_key);
                             let queryable_content_fields = Self::extract_queryable_content_fields(&content);
-                            StoredGroupMessage {
+                            let message = StoredGroupMessage {
                                 id: message_id,
                                 group_id: self.group_id.clone(),
                                 decrypted_message_bytes: content,
@@ -562,8 +709,18 @@ where
                                 version_minor: queryable_content_fields.version_minor,
                                 authority_id: queryable_content_fields.authority_id,
                                 reference_id: queryable_content_fields.reference_id,
-                            }
-                                .store_or_ignore(provider.conn_ref())?
+                                sent_by_me,
+                                sender_verified,
+                            };
+                            let annotations = self.client.message_enrichers().enrich(&message);
+                            message.store_or_reconcile(provider.conn_ref())?;
+                            provider.conn_ref().insert_message_annotations(&annotations)?;
+                            record_poll_content(provider.conn_ref(), &message)?;
+                            record_location_content(provider.conn_ref(), &message)?;
+                            self.record_leave_request(provider.conn_ref(), &message)?;
+                            self.record_join_request(provider, &message)?;
+                            provider.conn_ref().record_reaction_message(&message)?;
+                            provider.conn_ref().filter_message(&message)?;
                         }
                         Some(Content::V2(V2 {
                                              idempotency_key,
@@ -595,6 +752,8 @@ where
                                         version_minor: 0,
                                         authority_id: "unknown".to_string(),
                                         reference_id: None,
+                                        sent_by_me,
+                                        sender_verified,
                                     }
                                         .store_or_ignore(provider.conn_ref())?;
 
@@ -629,6 +788,8 @@ where
                                         version_minor: 0,
                                         authority_id: "unknown".to_string(),
                                         reference_id: None,
+                                        sent_by_me,
+                                        sender_verified,
                                     }
                                         .store_or_ignore(provider.conn_ref())?;
 
@@ -702,7 +863,80 @@ where
                         self.context().inbox_id()
                     );
 
+                    // A commit with nothing but an update path only rotates the key of its sender
+                    let key_rotated =
+                        sc.queued_proposals().next().is_none() && sc.update_path_leaf_node().is_some();
                     mls_group.merge_staged_commit(provider, sc)?;
+                    if key_rotated {
+                        let _ = self.client.local_events().send(LocalEvents::KeyRotated(KeyRotated {
+                            group_id: self.group_id.clone(),
+                            installation_id: validated_commit.actor_installation_id(),
+                            epoch: mls_group.epoch().as_u64(),
+                            rotated_at_ns: envelope_timestamp_ns as i64,
+                        }));
+                    }
+                    if !mls_group.is_active() {
+                        tracing::info!(
+                            inbox_id = self.client.inbox_id(),
+                            group_id = hex::encode(&self.group_id),
+                            "removed from group, it will no longer be synced"
+                        );
+                        provider
+                            .conn_ref()
+                            .mark_group_left(&self.group_id, envelope_timestamp_ns as i64)?;
+                        let _ = self.client.local_events().send(LocalEvents::GroupLeft(GroupLeft {
+                            group_id: self.group_id.clone(),
+                            pending: false,
+                        }));
+                    }
+                    if !validated_commit.removed_inboxes.is_empty() {
+                        let removed: Vec<String> = validated_commit
+                            .removed_inboxes
+                            .iter()
+                            .map(|inbox| inbox.inbox_id.clone())
+                            .collect();
+                        provider
+                            .conn_ref()
+                            .clear_leave_requests(&self.group_id, &removed)?;
+                    }
+                    if let Some(change) = validated_commit
+                        .metadata_changes
+                        .metadata_field_changes
+                        .iter()
+                        .find(|change| {
+                            change.field_name == MetadataField::GroupExpiresAtNS.as_str()
+                        })
+                    {
+                        provider.conn_ref().update_group_expires_at_ns(
+                            self.group_id.clone(),
+                            change.new_value.as_deref().and_then(|value| value.parse().ok()),
+                        )?;
+                    }
+                    if let Some(change) = validated_commit
+                        .metadata_changes
+                        .metadata_field_changes
+                        .iter()
+                        .find(|change| change.field_name == MetadataField::GroupName.as_str())
+                    {
+                        provider.conn_ref().set_group_name(
+                            &self.group_id,
+                            change.new_value.as_deref().unwrap_or_default(),
+                        )?;
+                    }
+                    if let Some(change) = validated_commit
+                        .metadata_changes
+                        .metadata_field_changes
+                        .iter()
+                        .find(|change| {
+                            change.field_name == MetadataField::GroupImageUrlSquare.as_str()
+                        })
+                    {
+                        provider.conn_ref().set_group_avatar_url(
+                            &self.group_id,
+                            change.new_value.as_deref().unwrap_or_default(),
+                        )?;
+                    }
+                    self.mirror_pinned_messages(provider.conn_ref(), &validated_commit)?;
                     self.save_transcript_message(
                         provider.conn_ref(),
                         validated_commit,
@@ -802,6 +1036,26 @@ where
         }
     }
 
+    /// Mirror the pinned set in the `pinned_messages` table if `validated_commit` changed it
+    fn mirror_pinned_messages(
+        &self,
+        conn: &DbConnection,
+        validated_commit: &ValidatedCommit,
+    ) -> Result<(), StorageError> {
+        if let Some(change) = validated_commit
+            .metadata_changes
+            .metadata_field_changes
+            .iter()
+            .find(|change| change.field_name == MetadataField::PinnedMessages.as_str())
+        {
+            conn.set_pinned_messages(
+                &self.group_id,
+                &parse_pinned_message_ids(change.new_value.as_deref().unwrap_or_default()),
+            )?;
+        }
+        Ok(())
+    }
+
     /// In case of metadataUpdate will extract the updated fields and store them to the db
     fn handle_metadata_update(
         &self,
@@ -824,6 +1078,18 @@ where
                         data.field_value.parse::<i64>().ok(),
                     )?
                 }
+                field_name if field_name == MetadataField::GroupExpiresAtNS.as_str() => {
+                    provider.conn_ref().update_group_expires_at_ns(
+                        self.group_id.clone(),
+                        data.field_value.parse::<i64>().ok(),
+                    )?
+                }
+                field_name if field_name == MetadataField::GroupName.as_str() => provider
+                    .conn_ref()
+                    .set_group_name(&self.group_id, &data.field_value)?,
+                field_name if field_name == MetadataField::GroupImageUrlSquare.as_str() => provider
+                    .conn_ref()
+                    .set_group_avatar_url(&self.group_id, &data.field_value)?,
                 _ => {} // handle other metadata updates
             }
         }
@@ -831,54 +1097,56 @@ where
         Ok(())
     }
 
+    /// Run an envelope through the [`pipeline`](super::pipeline) stages
     #[tracing::instrument(level = "trace", skip_all)]
     async fn consume_message(
         &self,
         provider: &XmtpOpenMlsProvider,
         envelope: &GroupMessage,
     ) -> Result<(), GroupMessageProcessingError> {
-        let msgv1 = match &envelope.version {
-            Some(GroupMessageVersion::V1(value)) => value,
-            _ => return Err(GroupMessageProcessingError::InvalidPayload),
-        };
+        let mut trace = EnvelopeTrace::new(self.client.envelope_interceptors(), &self.group_id);
 
-        let mls_message_in = MlsMessageIn::tls_deserialize_exact(&msgv1.data)?;
-        let message_entity_kind = match mls_message_in.wire_format() {
-            WireFormat::Welcome => EntityKind::Welcome,
-            _ => EntityKind::Group,
-        };
-
-        let last_cursor = provider
-            .conn_ref()
-            .get_last_cursor_for_id(&self.group_id, message_entity_kind)?;
-        let should_skip_message = last_cursor > msgv1.id as i64;
-        if should_skip_message {
-            tracing::info!(
-                inbox_id = self.client.inbox_id(),
-                installation_id = %self.client.installation_id(),
-                group_id = hex::encode(&self.group_id),
-                "Message already processed: skipped msgId:[{}] entity kind:[{:?}] last cursor in db: [{}]",
-                msgv1.id,
-                message_entity_kind,
-                last_cursor
-            );
-            Err(GroupMessageProcessingError::AlreadyProcessed(msgv1.id))
-        } else {
-            let cursor = &msgv1.id;
-            // Download all unread welcome messages and convert to groups.
-            // In a database transaction, increment the cursor for a given entity and
-            // apply the update after the provided `ProcessingFn` has completed successfully.
-            provider.transaction_async(|provider| async move {
-                let is_updated =
-                    provider
-                        .conn_ref()
-                        .update_cursor(&msgv1.group_id, EntityKind::Group, *cursor as i64)?;
-                if !is_updated {
-                    return Err(ProcessIntentError::AlreadyProcessed(*cursor).into());
+        let decoded = trace.record(PipelineStage::Decode, pipeline::decode(envelope))?;
+        trace.decoded(&decoded);
+        trace.record(
+            PipelineStage::Dedupe,
+            pipeline::dedupe(provider.conn_ref(), &self.group_id, &decoded),
+        )?;
+        let consent_state = trace.record(
+            PipelineStage::ConsentCheck,
+            pipeline::consent_check(provider.conn_ref(), &self.group_id).map_err(Into::into),
+        )?;
+        trace.consent_checked(consent_state);
+
+        let msgv1 = decoded.message;
+        let cursor = &msgv1.id;
+        // In a database transaction, increment the cursor for a given entity and
+        // apply the update after the message has been validated successfully.
+        let trace_ref = &mut trace;
+        let result = provider
+            .transaction_async(|provider| async move {
+                let validated = async {
+                    let is_updated = provider.conn_ref().update_cursor(
+                        &msgv1.group_id,
+                        EntityKind::Group,
+                        *cursor as i64,
+                    )?;
+                    if !is_updated {
+                        return Err(ProcessIntentError::AlreadyProcessed(*cursor).into());
+                    }
+                    self.process_message(provider, msgv1, true).await
                 }
-                self.process_message(provider, msgv1, true).await?;
-                Ok::<_, GroupMessageProcessingError>(())
-            }).await
+                .await;
+                trace_ref.record(PipelineStage::MlsValidate, validated)
+            })
+            .await;
+        // Failures in the transaction body were already reported as validation failures
+        let result = if trace.has_failed() {
+            result
+        } else {
+            trace.record(PipelineStage::Store, result)
+        };
+        result
             .inspect(|_| {
                 tracing::info!(
                     "Transaction completed successfully: process for group [{}] envelope cursor[{}]",
@@ -894,8 +1162,12 @@ where
                     err
                 );
             })?;
-            Ok(())
-        }
+        // The message is stored whatever happens next, so a failed notify is only reported
+        let _ = trace.record(
+            PipelineStage::Notify,
+            pipeline::notify(provider.conn_ref(), self.client.local_events(), msgv1),
+        );
+        Ok(())
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
@@ -907,12 +1179,16 @@ where
         let mut receive_errors: Vec<GroupMessageProcessingError> = vec![];
         for message in messages.into_iter() {
             let result = retry_async!(
-                Retry::default(),
-                (async { self.consume_message(provider, &message).await })
+                self.client.retry_policies().scoped(ErrorClass::Mls),
+                (async { self.consume_message(provider, &message).await }),
+                LocalEvents::report_retries(self.client.local_events())
             );
             if let Err(e) = result {
                 let is_retryable = e.is_retryable();
                 let error_message = e.to_string();
+                if !is_retryable && !e.is_already_processed() {
+                    self.record_if_commit(provider, &message, &error_message);
+                }
                 receive_errors.push(e);
                 // If the error is retryable we cannot move on to the next message
                 // otherwise you can get into a forked group state.
@@ -941,13 +1217,126 @@ where
         }
     }
 
+    /// Record `message` as a failed commit if it holds a commit, which leaves this installation
+    /// behind the members that applied it
+    fn record_if_commit(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        message: &GroupMessage,
+        error: &str,
+    ) {
+        let Ok(decoded) = pipeline::decode(message) else {
+            return;
+        };
+        let is_commit = MlsMessageIn::tls_deserialize_exact(&decoded.message.data)
+            .map(|message| match message.extract() {
+                MlsMessageBodyIn::PrivateMessage(message) => {
+                    message.content_type() == MlsContentType::Commit
+                }
+                _ => false,
+            })
+            .unwrap_or(false);
+        if !is_commit {
+            return;
+        }
+        tracing::error!(
+            group_id = hex::encode(&self.group_id),
+            cursor = decoded.message.id,
+            "commit failed to process, the group may have forked: {error}"
+        );
+        let failed = StoredFailedCommit {
+            group_id: self.group_id.clone(),
+            cursor: decoded.message.id as i64,
+            error: error.to_string(),
+            failed_at_ns: xmtp_common::time::now_ns(),
+        };
+        if let Err(err) = provider.conn_ref().record_failed_commit(&failed) {
+            tracing::warn!("failed to record the failed commit: {err}");
+        }
+    }
+
     #[tracing::instrument(skip_all)]
     pub(super) async fn receive(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
+        if let Err(err) = self.adopt_shared_cursor(provider).await {
+            tracing::warn!("failed to adopt the shared group cursor: {err}");
+        }
         let messages = self
             .client
             .query_group_messages(&self.group_id, provider.conn_ref())
             .await?;
-        self.process_messages(messages, provider).await?;
+        let result = self.process_messages(messages, provider).await;
+        // Only committed cursors are shared with the other replicas
+        if let Err(err) = self.publish_shared_cursor(provider).await {
+            tracing::warn!("failed to publish the group cursor: {err}");
+        }
+        result
+    }
+
+    /// The epoch and epoch authenticator of the group, which identify its MLS state
+    fn mls_state_fingerprint(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(u64, Vec<u8>), GroupError> {
+        self.load_mls_group_with_lock(provider, |mls_group| {
+            Ok((
+                mls_group.epoch().as_u64(),
+                mls_group.epoch_authenticator().as_slice().to_vec(),
+            ))
+        })
+    }
+
+    /// Skip the messages another replica of this installation already processed, if this group
+    /// is at the MLS state the replica left it in. See [`cursor_store`](crate::storage::cursor_store).
+    async fn adopt_shared_cursor(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
+        let Some(cursor_store) = provider.conn_ref().cursor_store() else {
+            return Ok(());
+        };
+        let Some(shared) = cursor_store.get_cursor(&self.group_id).await? else {
+            return Ok(());
+        };
+        provider.transaction(|provider| {
+            let (epoch, epoch_authenticator) = self.mls_state_fingerprint(provider)?;
+            if !shared.describes(epoch, &epoch_authenticator) {
+                return Ok(());
+            }
+            let conn = provider.conn_ref();
+            conn.get_last_cursor_for_id(&self.group_id, EntityKind::Group)?;
+            if conn.update_cursor(&self.group_id, EntityKind::Group, shared.cursor)? {
+                tracing::info!(
+                    "adopted the cursor {} of another replica for group [{}]",
+                    shared.cursor,
+                    hex::encode(&self.group_id)
+                );
+            }
+            Ok(())
+        })
+    }
+
+    /// Share the committed cursor of the group, with the MLS state it was reached at
+    async fn publish_shared_cursor(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let Some(cursor_store) = provider.conn_ref().cursor_store() else {
+            return Ok(());
+        };
+        let shared = provider.transaction(|provider| {
+            let Some(state) = provider
+                .conn_ref()
+                .get_refresh_state(&self.group_id, EntityKind::Group)?
+            else {
+                return Ok(None);
+            };
+            let (epoch, epoch_authenticator) = self.mls_state_fingerprint(provider)?;
+            Ok::<_, GroupError>(Some(SharedCursor {
+                cursor: state.cursor,
+                epoch,
+                epoch_authenticator,
+            }))
+        })?;
+        if let Some(shared) = shared {
+            cursor_store.advance_cursor(&self.group_id, shared).await?;
+        }
         Ok(())
     }
 
@@ -995,6 +1384,7 @@ where
                 }
             }
         };
+        let sent_by_me = sender_inbox_id == self.context().inbox_id();
         let msg = StoredGroupMessage {
             id: message_id,
             group_id: group_id.to_vec(),
@@ -1009,6 +1399,9 @@ where
             version_minor: content_type.version_minor as i32,
             authority_id: content_type.authority_id.to_string(),
             reference_id: None,
+            sent_by_me,
+            // The actor of the commit was verified by `ValidatedCommit`
+            sender_verified: true,
         };
         msg.store_or_ignore(conn)?;
         Ok(Some(msg))
@@ -1019,6 +1412,10 @@ where
         &self,
         provider: &XmtpOpenMlsProvider,
     ) -> Result<(), GroupError> {
+        // Intents queued before the export must not fork the state the other device continues
+        if provider.conn_ref().installation_retired_at_ns()?.is_some() {
+            return Err(GroupError::InstallationRetired);
+        }
         self.load_mls_group_with_lock_async(provider, |mut mls_group| async move {
             let intents = provider.conn_ref().find_group_intents(
                 self.group_id.clone(),
@@ -1026,98 +1423,160 @@ where
                 None,
             )?;
 
+            // Payloads of the intents marked as published but not sent yet
+            let mut batched = vec![];
+            let mut result = Ok(());
             for intent in intents {
-                let result = retry_async!(
-                    Retry::default(),
-                    (async {
-                        self.get_publish_intent_data(provider, &mut mls_group, &intent)
-                            .await
-                    })
-                );
-
-                match result {
+                match self
+                    .publish_intent(provider, &mut mls_group, intent, &mut batched)
+                    .await
+                {
+                    Ok(false) => {}
+                    Ok(true) => break,
                     Err(err) => {
-                        tracing::error!(error = %err, "error getting publish intent data {:?}", err);
-                        if (intent.publish_attempts + 1) as usize >= MAX_INTENT_PUBLISH_ATTEMPTS {
-                            tracing::error!(
-                                intent.id,
-                                intent.kind = %intent.kind,
-                                inbox_id = self.client.inbox_id(),
-                                installation_id = %self.client.installation_id(),group_id = hex::encode(&self.group_id),
-                                "intent {} has reached max publish attempts", intent.id);
-                            // TODO: Eventually clean up errored attempts
-                            provider
-                                .conn_ref()
-                                .set_group_intent_error_and_fail_msg(&intent)?;
-                        } else {
-                            provider
-                                .conn_ref()
-                                .increment_intent_publish_attempt_count(intent.id)?;
-                        }
-
-                        return Err(err);
+                        result = Err(err);
+                        break;
                     }
-                    Ok(Some(PublishIntentData {
-                                payload_to_publish,
-                                post_commit_action,
-                                staged_commit,
-                            })) => {
-                        let payload_slice = payload_to_publish.as_slice();
-                        let has_staged_commit = staged_commit.is_some();
-                        provider.conn_ref().set_group_intent_published(
-                            intent.id,
-                            sha256(payload_slice),
-                            post_commit_action,
-                            staged_commit,
-                            mls_group.epoch().as_u64() as i64,
-                        )?;
-                        tracing::debug!(
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            intent.id,
-                            intent.kind = %intent.kind,
-                            group_id = hex::encode(&self.group_id),
-                            "client [{}] set stored intent [{}] to state `published`",
-                            self.client.inbox_id(),
-                            intent.id
-                        );
+                }
+            }
+            // The batched intents are already marked as published, so they are sent even if
+            // a later intent failed
+            self.send_batched_payloads(&mut batched).await?;
 
-                        let messages = self.prepare_group_messages(vec![payload_slice])?;
-                        self.client
-                            .api()
-                            .send_group_messages(messages)
-                            .await?;
+            result
+        })
+        .await
+    }
 
-                        tracing::info!(
-                            intent.id,
-                            intent.kind = %intent.kind,
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            group_id = hex::encode(&self.group_id),
-                            "[{}] published intent [{}] of type [{}]",
-                            self.client.inbox_id(),
-                            intent.id,
-                            intent.kind
-                        );
-                        if has_staged_commit {
-                            tracing::info!("Commit sent. Stopping further publishes for this round");
-                            return Ok(());
-                        }
-                    }
-                    Ok(None) => {
-                        tracing::info!(
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            "Skipping intent because no publish data returned"
+    /// Publish one intent. Returns true if it was a commit, which ends the publishing round.
+    ///
+    /// In [`crate::data_mode::DataMode::Low`] application messages are only added to `batched`, and sent with
+    /// the next commit or at the end of the round.
+    async fn publish_intent(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        mls_group: &mut OpenMlsGroup,
+        intent: StoredGroupIntent,
+        batched: &mut Vec<Vec<u8>>,
+    ) -> Result<bool, GroupError> {
+        let batch = self.client.data_mode().batch_publishes();
+        let annotations = self.client.outbound_interceptors().before_publish(&intent);
+        if !annotations.is_empty() {
+            tracing::info!(
+                intent.id,
+                intent.kind = %intent.kind,
+                group_id = hex::encode(&self.group_id),
+                ?annotations,
+                "intent [{}] annotated before publish",
+                intent.id
+            );
+        }
+        let result = retry_async!(
+            self.client.retry_policies().scoped(ErrorClass::Mls),
+            (async {
+                self.get_publish_intent_data(provider, mls_group, &intent)
+                    .await
+            }),
+            LocalEvents::report_retries(self.client.local_events())
+        );
+
+        match result {
+            Err(err) => {
+                tracing::error!(error = %err, "error getting publish intent data {:?}", err);
+                if (intent.publish_attempts + 1) as usize >= MAX_INTENT_PUBLISH_ATTEMPTS {
+                    tracing::error!(
+                        intent.id,
+                        intent.kind = %intent.kind,
+                        inbox_id = self.client.inbox_id(),
+                        installation_id = %self.client.installation_id(),group_id = hex::encode(&self.group_id),
+                        "intent {} has reached max publish attempts", intent.id);
+                    // TODO: Eventually clean up errored attempts
+                    provider
+                        .conn_ref()
+                        .set_group_intent_error_and_fail_msg(&intent)?;
+                    if let Ok(Some(message_id)) = intent.message_id() {
+                        self.report_delivery(
+                            provider.conn_ref(),
+                            message_id,
+                            DeliveryStatus::Failed,
                         );
-                        let deleter: &dyn Delete<StoredGroupIntent, Key = i32> = provider.conn_ref();
-                        deleter.delete(intent.id)?;
                     }
+                } else {
+                    provider
+                        .conn_ref()
+                        .increment_intent_publish_attempt_count(intent.id)?;
                 }
+
+                return Err(err);
             }
+            Ok(Some(PublishIntentData {
+                payload_to_publish,
+                post_commit_action,
+                staged_commit,
+            })) => {
+                let payload_slice = payload_to_publish.as_slice();
+                let has_staged_commit = staged_commit.is_some();
+                provider.conn_ref().set_group_intent_published(
+                    intent.id,
+                    sha256(payload_slice),
+                    post_commit_action,
+                    staged_commit,
+                    mls_group.epoch().as_u64() as i64,
+                )?;
+                tracing::debug!(
+                    inbox_id = self.client.inbox_id(),
+                    installation_id = %self.client.installation_id(),
+                    intent.id,
+                    intent.kind = %intent.kind,
+                    group_id = hex::encode(&self.group_id),
+                    "client [{}] set stored intent [{}] to state `published`",
+                    self.client.inbox_id(),
+                    intent.id
+                );
 
-            Ok(())
-        }).await
+                batched.push(payload_to_publish);
+                if batch && !has_staged_commit {
+                    return Ok(false);
+                }
+                self.send_batched_payloads(batched).await?;
+
+                tracing::info!(
+                    intent.id,
+                    intent.kind = %intent.kind,
+                    inbox_id = self.client.inbox_id(),
+                    installation_id = %self.client.installation_id(),
+                    group_id = hex::encode(&self.group_id),
+                    "[{}] published intent [{}] of type [{}]",
+                    self.client.inbox_id(),
+                    intent.id,
+                    intent.kind
+                );
+                if has_staged_commit {
+                    tracing::info!("Commit sent. Stopping further publishes for this round");
+                    return Ok(true);
+                }
+            }
+            Ok(None) => {
+                tracing::info!(
+                    inbox_id = self.client.inbox_id(),
+                    installation_id = %self.client.installation_id(),
+                    "Skipping intent because no publish data returned"
+                );
+                let deleter: &dyn Delete<StoredGroupIntent, Key = i32> = provider.conn_ref();
+                deleter.delete(intent.id)?;
+            }
+        }
+        Ok(false)
+    }
+
+    async fn send_batched_payloads(&self, batched: &mut Vec<Vec<u8>>) -> Result<(), GroupError> {
+        if batched.is_empty() {
+            return Ok(());
+        }
+        let payloads = std::mem::take(batched);
+        let messages = self.prepare_group_messages(payloads.iter().map(Vec::as_slice).collect())?;
+        self.client.api().send_group_messages(messages).await?;
+        Ok(())
     }
 
     // Takes a StoredGroupIntent and returns the payload and post commit data as a tuple
@@ -1215,6 +1674,45 @@ where
                     post_commit_action: None,
                 }))
             }
+            IntentKind::UpdatePinnedMessages => {
+                let pin_intent = UpdatePinnedMessagesIntentData::from_bytes(&intent.data)?;
+                let Some(mutable_metadata_extensions) =
+                    build_extensions_for_pinned_messages_update(openmls_group, pin_intent)?
+                else {
+                    return Ok(None);
+                };
+
+                let (commit, _, _) = openmls_group.update_group_context_extensions(
+                    provider,
+                    mutable_metadata_extensions,
+                    &self.context().identity.installation_keys,
+                )?;
+                let commit_bytes = commit.tls_serialize_detached()?;
+
+                Ok(Some(PublishIntentData {
+                    payload_to_publish: commit_bytes,
+                    staged_commit: get_and_clear_pending_commit(openmls_group, provider)?,
+                    post_commit_action: None,
+                }))
+            }
+            IntentKind::TransferSuperAdmin => {
+                let transfer_intent = TransferSuperAdminIntentData::from_bytes(&intent.data)?;
+                let mutable_metadata_extensions =
+                    build_extensions_for_super_admin_transfer(openmls_group, transfer_intent)?;
+
+                let (commit, _, _) = openmls_group.update_group_context_extensions(
+                    provider,
+                    mutable_metadata_extensions,
+                    &self.context().identity.installation_keys,
+                )?;
+                let commit_bytes = commit.tls_serialize_detached()?;
+
+                Ok(Some(PublishIntentData {
+                    payload_to_publish: commit_bytes,
+                    staged_commit: get_and_clear_pending_commit(openmls_group, provider)?,
+                    post_commit_action: None,
+                }))
+            }
             IntentKind::UpdatePermission => {
                 let update_permissions_intent =
                     UpdatePermissionIntentData::try_from(intent.data.clone())?;
@@ -1234,33 +1732,127 @@ where
                     post_commit_action: None,
                 }))
             }
+            IntentKind::ReaddInstallations => {
+                let intent_data = ReaddInstallationsIntentData::from_bytes(&intent.data)?;
+                let signer = &self.context().identity.installation_keys;
+                apply_readd_installations_intent(
+                    self.client.as_ref(),
+                    provider,
+                    openmls_group,
+                    intent_data,
+                    signer,
+                )
+                .await
+            }
+            IntentKind::RemoveInstallations => {
+                let intent_data = RemoveInstallationsIntentData::from_bytes(&intent.data)?;
+                let leaf_nodes_to_remove: Vec<LeafNodeIndex> = get_removed_leaf_nodes(
+                    openmls_group,
+                    &intent_data.installations.into_iter().collect(),
+                );
+                if leaf_nodes_to_remove.is_empty() {
+                    return Ok(None);
+                }
+
+                let extensions: Extensions = openmls_group.extensions().clone();
+                let (commit, _, _) = openmls_group.update_group_membership(
+                    provider,
+                    &self.context().identity.installation_keys,
+                    &[],
+                    &leaf_nodes_to_remove,
+                    extensions,
+                )?;
+
+                Ok(Some(PublishIntentData {
+                    payload_to_publish: commit.tls_serialize_detached()?,
+                    staged_commit: get_and_clear_pending_commit(openmls_group, provider)?,
+                    post_commit_action: None,
+                }))
+            }
         }
     }
 
+    /// Take the queued actions of committed intents, see
+    /// [`post_commit_action`](crate::storage::post_commit_action), and delete the committed intents
     #[tracing::instrument(skip_all)]
     pub(crate) async fn post_commit(&self, conn: &DbConnection) -> Result<(), GroupError> {
+        for pending in conn.pending_post_commit_actions(&self.group_id)? {
+            tracing::debug!(
+                inbox_id = self.client.inbox_id(),
+                installation_id = %self.client.installation_id(),
+                intent_id = pending.intent_id,
+                attempts = pending.attempts,
+                "taking post commit action"
+            );
+            if pending.attempts >= MAX_POST_COMMIT_ACTION_ATTEMPTS {
+                tracing::error!(
+                    group_id = hex::encode(&self.group_id),
+                    intent_id = pending.intent_id,
+                    attempts = pending.attempts,
+                    "post commit action keeps failing, giving up on it"
+                );
+                conn.dead_letter_post_commit_action(pending.id)?;
+                continue;
+            }
+            conn.increment_post_commit_action_attempts(pending.id)?;
+
+            let post_commit_action = match PostCommitAction::from_bytes(pending.action.as_slice()) {
+                Ok(action) => action,
+                Err(err) => {
+                    tracing::error!(
+                        group_id = hex::encode(&self.group_id),
+                        intent_id = pending.intent_id,
+                        "post commit action can't be decoded, giving up on it: {err}"
+                    );
+                    conn.dead_letter_post_commit_action(pending.id)?;
+                    continue;
+                }
+            };
+            match post_commit_action {
+                PostCommitAction::SendWelcomes(action) => {
+                    let welcome_message = action.welcome_message.clone();
+                    let mut installations = action.installations.clone();
+                    let report = self.send_welcomes(action).await?;
+                    conn.record_pending_welcomes(&self.group_id, &report.sent)?;
+                    // `pending.attempts` does not count the attempt just made
+                    let attempts = pending.attempts + 1;
+                    if !report.failed.is_empty() && attempts >= MAX_WELCOME_SEND_ATTEMPTS {
+                        tracing::error!(
+                            group_id = hex::encode(&self.group_id),
+                            attempts,
+                            "failed to send {} welcomes, giving up on them",
+                            report.failed.len()
+                        );
+                    } else if !report.failed.is_empty() {
+                        tracing::warn!(
+                            group_id = hex::encode(&self.group_id),
+                            attempts,
+                            "failed to send {} welcomes, queueing them again",
+                            report.failed.len()
+                        );
+                        installations.retain(|installation| {
+                            report.failed.iter().any(|failed| {
+                                failed.installation_id == installation.installation_key
+                            })
+                        });
+                        let retry = SendWelcomesAction::new(installations, welcome_message);
+                        conn.requeue_post_commit_action(pending.id, &retry.to_bytes())?;
+                    }
+                    let _ = self
+                        .client
+                        .local_events()
+                        .send(LocalEvents::WelcomesSent(report));
+                }
+            }
+            conn.complete_post_commit_action(pending.id)?;
+        }
+
         let intents = conn.find_group_intents(
             self.group_id.clone(),
             Some(vec![IntentState::Committed]),
             None,
         )?;
-
         for intent in intents {
-            if let Some(post_commit_data) = intent.post_commit_data {
-                tracing::debug!(
-                    inbox_id = self.client.inbox_id(),
-                    installation_id = %self.client.installation_id(),
-                    intent.id,
-                    intent.kind = %intent.kind, "taking post commit action"
-                );
-
-                let post_commit_action = PostCommitAction::from_bytes(post_commit_data.as_slice())?;
-                match post_commit_action {
-                    PostCommitAction::SendWelcomes(action) => {
-                        self.send_welcomes(action).await?;
-                    }
-                }
-            }
             let deleter: &dyn Delete<StoredGroupIntent, Key = i32> = conn;
             deleter.delete(intent.id)?;
         }
@@ -1268,13 +1860,76 @@ where
         Ok(())
     }
 
+    /// Take the post commit actions that were interrupted, e.g. by the process exiting between
+    /// merging a commit and sending its welcomes, and send the welcomes that failed again
+    pub async fn resume_post_commit_actions(&self) -> Result<(), GroupError> {
+        let _mutex = self.mutex.lock().await;
+        let conn = self.context().store().conn()?;
+        self.post_commit(&conn).await
+    }
+
+    /// Re-add installations that were sent a welcome but were never seen in the group since,
+    /// usually because the key package used for the welcome had already expired. A member that
+    /// joined but never sent anything is re-added once, and acknowledges the new welcome by
+    /// rotating its leaf, since it already has the group.
+    /// Once an installation has been re-added [`MAX_WELCOME_READD_ATTEMPTS`] times without
+    /// success, a [`LocalEvents::WelcomeUndelivered`] event is sent instead, so that the user
+    /// can step in.
+    pub(crate) fn readd_stale_installations(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let conn = provider.conn_ref();
+        let sent_before_ns = xmtp_common::time::now_ns() - WELCOME_ACK_TIMEOUT_NS;
+        let stale = conn.stale_pending_welcomes(&self.group_id, sent_before_ns)?;
+        if stale.is_empty() {
+            return Ok(());
+        }
+
+        let mut readd = vec![];
+        for pending in stale {
+            if pending.readd_attempts < MAX_WELCOME_READD_ATTEMPTS {
+                conn.increment_welcome_readd_attempts(&self.group_id, &pending.installation_id)?;
+                readd.push(pending.installation_id);
+                continue;
+            }
+
+            tracing::warn!(
+                inbox_id = self.client.inbox_id(),
+                group_id = hex::encode(&self.group_id),
+                installation_id = hex::encode(&pending.installation_id),
+                readd_attempts = pending.readd_attempts,
+                "installation never processed its welcome"
+            );
+            conn.clear_pending_welcome(&self.group_id, &pending.installation_id)?;
+            let _ = self
+                .client
+                .local_events()
+                .send(LocalEvents::WelcomeUndelivered(UndeliveredWelcome {
+                    group_id: self.group_id.clone(),
+                    installation_id: pending.installation_id,
+                    readd_attempts: pending.readd_attempts,
+                }));
+        }
+
+        if !readd.is_empty() {
+            conn.insert_group_intent(NewGroupIntent::new(
+                IntentKind::ReaddInstallations,
+                self.group_id.clone(),
+                ReaddInstallationsIntentData::new(readd).into(),
+            ))?;
+        }
+        Ok(())
+    }
+
     pub async fn maybe_update_installations(
         &self,
         provider: &XmtpOpenMlsProvider,
         update_interval_ns: Option<i64>,
     ) -> Result<(), GroupError> {
         // determine how long of an interval in time to use before updating list
-        let interval_ns = update_interval_ns.unwrap_or(sync_update_installations_interval_ns());
+        let interval_ns = update_interval_ns
+            .unwrap_or_else(|| self.client.data_mode().update_installations_interval_ns());
 
         let now_ns = xmtp_common::time::now_ns();
         let last_ns = provider
@@ -1399,10 +2054,14 @@ where
     /**
      * Sends welcome messages to the installations specified in the action
      *
-     * Internally, this breaks the request into chunks to avoid exceeding the GRPC max message size limits
+     * Internally, this batches the welcomes to stay under the GRPC max message size limits, and
+     * splits batches the server refuses as too large. See [`crate::groups::welcomes`]
      */
     #[tracing::instrument(level = "trace", skip_all)]
-    pub(super) async fn send_welcomes(&self, action: SendWelcomesAction) -> Result<(), GroupError> {
+    pub(super) async fn send_welcomes(
+        &self,
+        action: SendWelcomesAction,
+    ) -> Result<WelcomeSendReport, GroupError> {
         let welcomes = action
             .installations
             .into_iter()
@@ -1422,31 +2081,37 @@ where
             })
             .collect::<Result<Vec<WelcomeMessageInput>, HpkeError>>()?;
 
-        let welcome = welcomes
-            .first()
-            .ok_or(GroupError::Generic("No welcomes to send".to_string()))?;
-
-        let chunk_size = GRPC_DATA_LIMIT
-            / welcome
-                .version
-                .as_ref()
-                .map(|w| match w {
-                    WelcomeMessageInputVersion::V1(w) => {
-                        let w = w.installation_key.len() + w.data.len() + w.hpke_public_key.len();
-                        tracing::debug!("total welcome message proto bytes={w}");
-                        w
-                    }
-                })
-                .unwrap_or(GRPC_DATA_LIMIT / MAX_GROUP_SIZE);
+        if welcomes.is_empty() {
+            return Err(GroupError::Generic("No welcomes to send".to_string()));
+        }
 
-        tracing::debug!("welcome chunk_size={chunk_size}");
         let api = self.client.api();
-        let mut futures = vec![];
-        for welcomes in welcomes.chunks(chunk_size) {
-            futures.push(api.send_welcome_messages(welcomes));
+        let mut report = WelcomeSendReport::new(&self.group_id);
+        let mut batches = default_batches(welcomes);
+        while !batches.is_empty() {
+            tracing::debug!("sending {} welcome batches", batches.len());
+            let results = join_all(
+                batches
+                    .iter()
+                    .map(|batch| api.send_welcome_messages(batch.as_slice())),
+            )
+            .await;
+            let mut too_large = vec![];
+            for (mut batch, result) in batches.into_iter().zip(results) {
+                match result {
+                    Ok(()) => report.record_sent(&batch),
+                    Err(e) if batch.len() > 1 && is_payload_too_large(&e) => {
+                        tracing::debug!("welcome batch of {} too large, splitting", batch.len());
+                        let second_half = batch.split_off(batch.len() / 2);
+                        too_large.push(batch);
+                        too_large.push(second_half);
+                    }
+                    Err(e) => report.record_failed(&batch, &e.to_string()),
+                }
+            }
+            batches = too_large;
         }
-        try_join_all(futures).await?;
-        Ok(())
+        Ok(report)
     }
 
     /// Provides hmac keys for a range of epochs around current epoch
@@ -1634,6 +2299,76 @@ async fn apply_update_group_membership_intent(
     }))
 }
 
+// Removes installations that never processed their welcome, and adds them back
+// with a fresh key package
+#[tracing::instrument(level = "trace", skip_all)]
+async fn apply_readd_installations_intent(
+    client: impl ScopedGroupClient,
+    provider: &XmtpOpenMlsProvider,
+    openmls_group: &mut OpenMlsGroup,
+    intent_data: ReaddInstallationsIntentData,
+    signer: impl Signer,
+) -> Result<Option<PublishIntentData>, GroupError> {
+    // Installations that were removed from the group in the meantime are not re-added
+    let members: HashSet<Vec<u8>> = openmls_group
+        .members()
+        .map(|member| member.signature_key)
+        .collect();
+    let installation_ids: Vec<Vec<u8>> = intent_data
+        .installations
+        .into_iter()
+        .filter(|installation_id| members.contains(installation_id))
+        .collect();
+    if installation_ids.is_empty() {
+        return Ok(None);
+    }
+
+    // Installations without a fresh key package are left alone until the next attempt
+    let key_packages = client
+        .get_key_packages_for_installation_ids(installation_ids)
+        .await?;
+    if key_packages.is_empty() {
+        return Ok(None);
+    }
+
+    let mut new_installations: Vec<Installation> = vec![];
+    let mut new_key_packages: Vec<KeyPackage> = vec![];
+    let mut readded: HashSet<Vec<u8>> = HashSet::new();
+    for key_package in key_packages {
+        readded.insert(key_package.installation_id());
+        new_installations.push(Installation::from_verified_key_package(&key_package));
+        new_key_packages.push(key_package.inner);
+    }
+    let leaf_nodes_to_remove = get_removed_leaf_nodes(openmls_group, &readded);
+    // Group membership is unchanged, since the installations belong to the same inboxes
+    let extensions: Extensions = openmls_group.extensions().clone();
+
+    let (commit, maybe_welcome_message, _) = openmls_group.update_group_membership(
+        provider,
+        &signer,
+        &new_key_packages,
+        &leaf_nodes_to_remove,
+        extensions,
+    )?;
+
+    let post_commit_action = match maybe_welcome_message {
+        Some(welcome_message) => Some(PostCommitAction::from_welcome(
+            welcome_message,
+            new_installations,
+        )?),
+        None => None,
+    };
+
+    let staged_commit = get_and_clear_pending_commit(openmls_group, provider)?
+        .ok_or_else(|| GroupError::MissingPendingCommit)?;
+
+    Ok(Some(PublishIntentData {
+        payload_to_publish: commit.tls_serialize_detached()?,
+        post_commit_action: post_commit_action.map(|action| action.to_bytes()),
+        staged_commit: Some(staged_commit),
+    }))
+}
+
 fn get_removed_leaf_nodes(
     openmls_group: &mut OpenMlsGroup,
     removed_installations: &HashSet<Vec<u8>>,
@@ -1743,4 +2478,120 @@ pub(crate) mod tests {
         assert_eq!(hmac_keys[1].epoch, current_epoch);
         assert_eq!(hmac_keys[2].epoch, current_epoch + 1);
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test(flavor = "current_thread"))]
+    async fn shared_cursor_is_only_adopted_at_the_same_mls_state() {
+        use crate::{
+            identity::IdentityStrategy,
+            storage::{
+                cursor_store::{tests::InMemoryCursorStore, CursorStore},
+                group_message::MsgQueryArgs,
+                EncryptedMessageStore, StorageOption,
+            },
+            utils::test::{register_client, TestClient},
+            InboxOwner,
+        };
+        use xmtp_id::associations::{
+            generate_inbox_id, test_utils::MockSmartContractSignatureVerifier,
+        };
+        use xmtp_proto::{api_client::XmtpTestClient, xmtp::mls::api::v1::group_message::Version};
+
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let cursor_store = Arc::new(InMemoryCursorStore::default());
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap()
+        .with_cursor_store(cursor_store.clone());
+        let bola = ClientBuilder::new(IdentityStrategy::new(
+            generate_inbox_id(&bola_wallet.get_address(), &1).unwrap(),
+            bola_wallet.get_address(),
+            1,
+            None,
+        ))
+        .store(store)
+        .api_client(<TestClient as XmtpTestClient>::create_local().await)
+        .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+        .build_with_verifier()
+        .await
+        .unwrap();
+        register_client(&bola, &bola_wallet).await;
+
+        let amal_group = amal.create_group(None, Default::default()).unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let provider = bola.mls_provider().unwrap();
+        let bola_group = bola.sync_welcomes(&provider).await.unwrap().pop().unwrap();
+        // Publishes the key update of the welcome, before the MLS state is captured
+        bola_group.sync().await.unwrap();
+        amal_group.sync().await.unwrap();
+
+        let last_cursor = || async {
+            let envelopes = bola
+                .query_group_messages(&bola_group.group_id, provider.conn_ref())
+                .await
+                .unwrap();
+            match envelopes.last().unwrap().version {
+                Some(Version::V1(ref v1)) => v1.id as i64,
+                _ => panic!("expected a v1 envelope"),
+            }
+        };
+        let application_messages = || {
+            bola_group
+                .find_messages(&MsgQueryArgs {
+                    kind: Some(GroupMessageKind::Application),
+                    ..MsgQueryArgs::default()
+                })
+                .unwrap()
+                .len()
+        };
+        let (epoch, epoch_authenticator) = bola_group.mls_state_fingerprint(&provider).unwrap();
+
+        // Another replica processed the message at the current MLS state, it is skipped
+        amal_group.send_message(b"one").await.unwrap();
+        let shared = SharedCursor {
+            cursor: last_cursor().await,
+            epoch,
+            epoch_authenticator: epoch_authenticator.clone(),
+        };
+        cursor_store
+            .cursors
+            .lock()
+            .insert(bola_group.group_id.clone(), shared);
+        bola_group.sync().await.unwrap();
+        assert_eq!(application_messages(), 0);
+
+        // A cursor reached at another MLS state is not adopted
+        amal_group.send_message(b"two").await.unwrap();
+        let shared = SharedCursor {
+            cursor: last_cursor().await,
+            epoch,
+            epoch_authenticator: vec![0; epoch_authenticator.len()],
+        };
+        cursor_store
+            .cursors
+            .lock()
+            .insert(bola_group.group_id.clone(), shared);
+        bola_group.sync().await.unwrap();
+        assert_eq!(application_messages(), 1);
+
+        // Processed messages are shared with the state they were processed at
+        amal_group.send_message(b"three").await.unwrap();
+        let cursor = last_cursor().await;
+        bola_group.sync().await.unwrap();
+        assert_eq!(application_messages(), 2);
+        let shared = cursor_store
+            .get_cursor(&bola_group.group_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(shared.cursor, cursor);
+        assert!(shared.describes(epoch, &epoch_authenticator));
+    }
 }
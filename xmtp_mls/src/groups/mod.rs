@@ -1,11 +1,37 @@
+pub mod avatars;
+pub mod bulk_create;
+pub mod capabilities;
+pub mod debug_info;
 pub mod device_sync;
+pub mod digest;
+pub mod discovery;
+pub mod duplicate;
+pub mod enrichers;
+pub mod forward;
 pub mod group_membership;
 pub mod group_metadata;
 pub mod group_mutable_metadata;
 pub mod group_permissions;
 pub mod intents;
+pub mod leave;
+pub mod locations;
+pub mod mega_group;
 pub mod members;
+pub mod mute_rules;
+pub mod outbound;
+pub mod pins;
+pub mod pipeline;
+pub mod polls;
+pub mod post_commit;
+pub mod reactions;
+pub mod request_expiry;
 pub mod scoped_client;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod search;
+pub mod staged;
+pub mod transactions;
+pub mod verification;
+pub mod welcomes;
 
 mod disappearing_messages;
 pub(super) mod mls_sync;
@@ -36,10 +62,15 @@ use openmls_traits::OpenMlsProvider;
 use prost::Message;
 use thiserror::Error;
 use tokio::sync::Mutex;
+use xmtp_content_types::app_metadata::validate_app_metadata;
+use xmtp_content_types::location::{Location, LocationCodec};
+use xmtp_content_types::poll::{PollVote, PollVoteCodec};
 use xmtp_content_types::reaction::{LegacyReaction, ReactionCodec};
+use xmtp_content_types::CodecError;
 
 use self::device_sync::DeviceSyncError;
 pub use self::group_permissions::PreconfiguredPolicies;
+pub use self::mega_group::MegaGroup;
 use self::scoped_client::ScopedGroupClient;
 use self::{
     group_membership::GroupMembership,
@@ -50,7 +81,8 @@ use self::{
     },
     intents::{
         AdminListActionType, PermissionPolicyOption, PermissionUpdateType,
-        UpdateAdminListIntentData, UpdateMetadataIntentData, UpdatePermissionIntentData,
+        RemoveInstallationsIntentData, TransferSuperAdminIntentData, UpdateAdminListIntentData,
+        UpdateMetadataIntentData, UpdatePermissionIntentData,
     },
     validated_commit::extract_group_membership,
 };
@@ -76,7 +108,7 @@ use crate::{
     api::WrappedApiError,
     client::{deserialize_welcome, ClientError, XmtpMlsLocalContext},
     configuration::{
-        CIPHERSUITE, GROUP_MEMBERSHIP_EXTENSION_ID, GROUP_PERMISSIONS_EXTENSION_ID, MAX_GROUP_SIZE,
+        CIPHERSUITE, GROUP_MEMBERSHIP_EXTENSION_ID, GROUP_PERMISSIONS_EXTENSION_ID,
         MAX_PAST_EPOCHS, MUTABLE_METADATA_EXTENSION_ID,
         SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS,
     },
@@ -88,29 +120,32 @@ use crate::{
     storage::{
         consent_record::{ConsentState, ConsentType, StoredConsentRecord},
         db_connection::DbConnection,
-        group::{ConversationType, GroupMembershipState, StoredGroup},
-        group_intent::IntentKind,
+        group::{ConversationType, GroupLifecycleState, GroupMembershipState, StoredGroup},
+        group_intent::{IntentKind, IntentState, NewGroupIntent, StoredGroupIntent},
         group_message::{DeliveryStatus, GroupMessageKind, MsgQueryArgs, StoredGroupMessage},
+        local_day::LocalDay,
         sql_key_store,
     },
     subscriptions::{LocalEventError, LocalEvents},
     utils::id::calculate_message_id,
-    Store, MLS_COMMIT_LOCK,
+    Fetch, Store, MLS_COMMIT_LOCK,
 };
 use std::future::Future;
-use std::{collections::HashSet, sync::Arc};
-use xmtp_cryptography::signature::{sanitize_evm_addresses, AddressValidationError};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use xmtp_cryptography::{
+    account_id::sanitize_account_addresses, signature::AddressValidationError,
+};
 use xmtp_id::{InboxId, InboxIdRef};
 
 use crate::groups::group_mutable_metadata::MessageDisappearingSettings;
-use xmtp_common::retry::RetryableError;
+use xmtp_common::retry::{ErrorClass, RetryableError};
 
 #[derive(Debug, Error)]
 pub enum GroupError {
     #[error(transparent)]
     NotFound(#[from] NotFound),
-    #[error("Max user limit exceeded.")]
-    UserLimitExceeded,
+    #[error("group would have {size} members, more than the limit of {max}")]
+    UserLimitExceeded { size: usize, max: usize },
     #[error("api error: {0}")]
     Api(#[from] xmtp_proto::Error),
     #[error("api error: {0}")]
@@ -202,6 +237,28 @@ pub enum GroupError {
     SyncFailedToWait,
     #[error("cannot change metadata of DM")]
     DmGroupMetadataForbidden,
+    #[error("installations can only be pruned from groups by admins")]
+    PruneForbidden,
+    #[error("group expired, messages can no longer be sent")]
+    GroupExpired,
+    #[error("no longer a member of the group, messages can no longer be sent")]
+    GroupLeft,
+    #[error("cannot leave the group: {0}")]
+    LeaveForbidden(String),
+    #[error("cannot transfer the super admin role: {0}")]
+    TransferForbidden(String),
+    #[error("the group would be left without a super admin, transfer the role first")]
+    LastSuperAdmin,
+    #[error("key rotation interval is too long")]
+    KeyRotationIntervalTooLong,
+    #[error("inboxes cannot ask to join this group")]
+    NotDiscoverable,
+    #[error("at most {0} messages can be pinned")]
+    TooManyPinnedMessages(usize),
+    #[error("invalid app metadata: {0}")]
+    AppMetadata(CodecError),
+    #[error("message cannot be forwarded: {0}")]
+    Forward(CodecError),
     #[error("Missing pending commit")]
     MissingPendingCommit,
     #[error("Intent not committed")]
@@ -212,6 +269,8 @@ pub enum GroupError {
     LockUnavailable,
     #[error("Failed to acquire semaphore lock")]
     LockFailedToAcquire,
+    #[error("this installation was retired when its key store was exported to another device")]
+    InstallationRetired,
 }
 
 impl RetryableError for GroupError {
@@ -245,7 +304,7 @@ impl RetryableError for GroupError {
             | Self::GroupMetadata(_)
             | Self::GroupMutableMetadata(_)
             | Self::GroupMutablePermissions(_)
-            | Self::UserLimitExceeded
+            | Self::UserLimitExceeded { .. }
             | Self::InvalidGroupMembership
             | Self::Intent(_)
             | Self::CreateMessage(_)
@@ -258,6 +317,17 @@ impl RetryableError for GroupError {
             | Self::InvalidExtension(_)
             | Self::MissingMetadataField { .. }
             | Self::DmGroupMetadataForbidden
+            | Self::PruneForbidden
+            | Self::GroupExpired
+            | Self::GroupLeft
+            | Self::LeaveForbidden(_)
+            | Self::TransferForbidden(_)
+            | Self::LastSuperAdmin
+            | Self::KeyRotationIntervalTooLong
+            | Self::NotDiscoverable
+            | Self::TooManyPinnedMessages(_)
+            | Self::AppMetadata(_)
+            | Self::Forward(_)
             | Self::Signature(_)
             | Self::LeafNodeError(_)
             | Self::NoPSKSupport
@@ -269,6 +339,42 @@ impl RetryableError for GroupError {
             | Self::EncodeError(_) => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::Api(api_error) => api_error.error_class(),
+            Self::ReceiveErrors(errors) => errors
+                .iter()
+                .find(|e| e.is_retryable())
+                .and_then(|e| e.error_class()),
+            Self::Client(client_error) => client_error.error_class(),
+            Self::Diesel(diesel) => diesel.error_class(),
+            Self::Storage(storage) => storage.error_class(),
+            Self::NotFound(not_found) => not_found.error_class(),
+            Self::ReceiveError(msg) => msg.error_class(),
+            Self::Hpke(hpke) => hpke.error_class(),
+            Self::Identity(identity) => identity.error_class(),
+            Self::SqlKeyStore(sql) => sql.error_class(),
+            Self::Sync(errs) => errs
+                .iter()
+                .find(|e| e.is_retryable())
+                .and_then(|e| e.error_class()),
+            Self::InstallationDiff(diff) => diff.error_class(),
+            Self::CommitValidation(err) => err.error_class(),
+            Self::WrappedApi(err) => err.error_class(),
+            Self::MessageHistory(err) => err.error_class(),
+            Self::ProcessIntent(err) => err.error_class(),
+            Self::UpdateGroupMembership(_)
+            | Self::GroupCreate(_)
+            | Self::SelfUpdate(_)
+            | Self::WelcomeError(_)
+            | Self::CreateGroupContextExtProposalError(_)
+            | Self::LockUnavailable
+            | Self::LockFailedToAcquire
+            | Self::SyncFailedToWait => Some(ErrorClass::Mls),
+            _ => None,
+        }
+    }
 }
 
 pub struct MlsGroup<C> {
@@ -290,6 +396,8 @@ pub struct GroupMetadataOptions {
     pub description: Option<String>,
     pub pinned_frame_url: Option<String>,
     pub message_disappearing_settings: Option<MessageDisappearingSettings>,
+    /// Time in NS after which the group can no longer be used, see [`MlsGroup::group_expiration`]
+    pub expires_at_ns: Option<i64>,
 }
 
 impl<C> Clone for MlsGroup<C> {
@@ -354,6 +462,13 @@ impl TryFrom<EncodedContent> for QueryableContentFields {
             }
             (ReactionCodec::TYPE_ID, _) => LegacyReaction::decode(&content.content)
                 .and_then(|legacy_reaction| hex::decode(legacy_reaction.reference).ok()),
+            (LocationCodec::TYPE_ID, _) => Location::decode(content.content.as_slice())
+                .ok()
+                .filter(|location| !location.live_share_reference.is_empty())
+                .and_then(|location| hex::decode(location.live_share_reference).ok()),
+            (PollVoteCodec::TYPE_ID, _) => PollVote::decode(content.content.as_slice())
+                .ok()
+                .and_then(|vote| hex::decode(vote.poll_reference).ok()),
             _ => None,
         };
 
@@ -367,7 +482,8 @@ impl TryFrom<EncodedContent> for QueryableContentFields {
     }
 }
 
-/// Represents a group, which can contain anywhere from 1 to MAX_GROUP_SIZE inboxes.
+/// Represents a group, which can contain anywhere from 1 to [`ScopedGroupClient::max_group_size`]
+/// inboxes. Larger communities can be split across several groups with a [`MegaGroup`].
 ///
 /// This is a wrapper around OpenMLS's `MlsGroup` that handles our application-level configuration
 /// and validations.
@@ -448,6 +564,24 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         operation(mls_group)
     }
 
+    /// Delete the group from this installation with everything stored for it, including its
+    /// OpenMLS state, except its consent record
+    pub(crate) fn delete_locally(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
+        provider.conn_ref().delete_group(&self.group_id)?;
+        match self.delete_mls_group(provider) {
+            Err(GroupError::NotFound(NotFound::MlsGroup)) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Delete the stored OpenMLS group from the OpenMLS provider's keystore
+    fn delete_mls_group(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
+        self.load_mls_group_with_lock(provider, |mut mls_group| {
+            mls_group.delete(provider.storage())?;
+            Ok(())
+        })
+    }
+
     // Load the stored OpenMLS group from the OpenMLS provider's keystore
     #[tracing::instrument(level = "trace", skip_all)]
     pub(crate) async fn load_mls_group_with_lock_async<F, E, R, Fut>(
@@ -485,11 +619,33 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         membership_state: GroupMembershipState,
         permissions_policy_set: PolicySet,
         opts: GroupMetadataOptions,
+    ) -> Result<Self, GroupError> {
+        Self::create_and_insert_with_lifecycle(
+            client,
+            provider,
+            membership_state,
+            GroupLifecycleState::Active,
+            permissions_policy_set,
+            opts,
+        )
+    }
+
+    /// [`Self::create_and_insert`] a group starting in `lifecycle_state`
+    pub(crate) fn create_and_insert_with_lifecycle(
+        client: Arc<ScopedClient>,
+        provider: &XmtpOpenMlsProvider,
+        membership_state: GroupMembershipState,
+        lifecycle_state: GroupLifecycleState,
+        permissions_policy_set: PolicySet,
+        opts: GroupMetadataOptions,
     ) -> Result<Self, GroupError> {
         let context = client.context();
         let creator_inbox_id = context.inbox_id();
         let protected_metadata =
             build_protected_metadata_extension(creator_inbox_id, ConversationType::Group)?;
+        let expires_at_ns = opts.expires_at_ns;
+        let name = opts.name.clone();
+        let image_url_square = opts.image_url_square.clone();
         let mutable_metadata = build_mutable_metadata_extension_default(creator_inbox_id, opts)?;
         let group_membership = build_starting_group_membership_extension(creator_inbox_id, 0);
         let mutable_permissions = build_mutable_permissions_extension(permissions_policy_set)?;
@@ -511,15 +667,25 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         )?;
 
         let group_id = mls_group.group_id().to_vec();
-        let stored_group = StoredGroup::new(
-            group_id.clone(),
-            now_ns(),
-            membership_state,
-            context.inbox_id().to_string(),
-            None,
-        );
+        let stored_group = StoredGroup {
+            expires_at_ns,
+            lifecycle_state,
+            ..StoredGroup::new(
+                group_id.clone(),
+                now_ns(),
+                membership_state,
+                context.inbox_id().to_string(),
+                None,
+            )
+        };
 
         stored_group.store(provider.conn_ref())?;
+        if let Some(name) = name {
+            provider.conn_ref().set_group_name(&group_id, &name)?;
+        }
+        if let Some(url) = image_url_square {
+            provider.conn_ref().set_group_avatar_url(&group_id, &url)?;
+        }
         let new_group = Self::new_from_arc(client.clone(), group_id, stored_group.created_at_ns);
 
         // Consent state defaults to allowed when the user creates the group
@@ -603,7 +769,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
 
         let conversation_type = metadata.conversation_type;
 
-        let to_store = match conversation_type {
+        let mut to_store = match conversation_type {
             ConversationType::Group => StoredGroup::new_from_welcome(
                 group_id.clone(),
                 now_ns(),
@@ -635,6 +801,8 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
                 dm_members,
             ),
         };
+        let mutable_metadata = GroupMutableMetadata::try_from(&mls_group)?;
+        to_store.expires_at_ns = mutable_metadata.expires_at_ns();
 
         // Ensure that the list of members in the group's MLS tree matches the list of inboxes specified
         // in the `GroupMembership` extension.
@@ -642,7 +810,54 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
 
         // Insert or replace the group in the database.
         // Replacement can happen in the case that the user has been removed from and subsequently re-added to the group.
+        let readded = provider.conn_ref().find_group(&group_id)?.is_some();
         let stored_group = provider.conn_ref().insert_or_replace_group(to_store)?;
+        if stored_group.has_left() {
+            // Added back to a group that we were removed from
+            provider.conn_ref().mark_group_rejoined(&stored_group.id)?;
+            if stored_group.membership_state == GroupMembershipState::PendingRemove {
+                provider
+                    .conn_ref()
+                    .update_group_membership(&stored_group.id, GroupMembershipState::Allowed)?;
+            }
+        }
+        if let Some(name) = mutable_metadata
+            .attributes
+            .get(&MetadataField::GroupName.to_string())
+        {
+            provider.conn_ref().set_group_name(&stored_group.id, name)?;
+        }
+        if let Some(url) = mutable_metadata
+            .attributes
+            .get(&MetadataField::GroupImageUrlSquare.to_string())
+        {
+            provider
+                .conn_ref()
+                .set_group_avatar_url(&stored_group.id, url)?;
+        }
+        provider
+            .conn_ref()
+            .set_pinned_messages(&stored_group.id, &mutable_metadata.pinned_message_ids())?;
+        Self::apply_consent_policy(
+            client,
+            provider.conn_ref(),
+            &stored_group.id,
+            conversation_type,
+            &stored_group.added_by_inbox_id,
+        )?;
+        // Members that already had the group may have been re-added because the member who
+        // added them never heard from them. Rotating our leaf right away tells it that this
+        // welcome was processed, so that it stops re-adding us. A first join is acknowledged by
+        // the first message or commit we send instead, to avoid a commit per join.
+        if readded {
+            provider
+                .conn_ref()
+                .insert_group_intent(NewGroupIntent::new(
+                    IntentKind::KeyUpdate,
+                    stored_group.id.clone(),
+                    vec![],
+                ))?;
+        }
 
         Ok(Self::new(
             client.clone(),
@@ -651,6 +866,41 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         ))
     }
 
+    /// Give a conversation joined from a welcome the consent of the user's
+    /// [`ConsentPolicy`](crate::storage::consent_policy::ConsentPolicy), unless it already has a
+    /// consent record
+    fn apply_consent_policy(
+        client: &ScopedClient,
+        conn: &DbConnection,
+        group_id: &[u8],
+        conversation_type: ConversationType,
+        added_by_inbox_id: &str,
+    ) -> Result<(), GroupError> {
+        let entity = hex::encode(group_id);
+        if conn
+            .get_consent_record(entity.clone(), ConsentType::ConversationId)?
+            .is_some()
+        {
+            return Ok(());
+        }
+        let Some(state) =
+            conn.consent_from_policy(conversation_type, client.inbox_id(), added_by_inbox_id)?
+        else {
+            return Ok(());
+        };
+        tracing::info!(
+            group_id = entity,
+            ?state,
+            "setting consent of joined conversation from the consent policy"
+        );
+        conn.insert_or_replace_consent_records(&[StoredConsentRecord::new(
+            ConsentType::ConversationId,
+            state,
+            entity,
+        )])?;
+        Ok(())
+    }
+
     /// Decrypt a welcome message using HPKE and then create and save a group from the stored message
     pub async fn create_from_encrypted_welcome(
         client: &ScopedClient,
@@ -783,7 +1033,77 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         Ok(())
     }
 
+    /// Remove installations that have not sent a message or rotated their key package for longer
+    /// than `inactive_for_ns`, while keeping their inboxes in the group. This keeps the MLS tree
+    /// of old groups small, which makes commits cheaper.
+    ///
+    /// Installations are only tracked from the first time this installation sees them, so
+    /// an installation is never pruned before `inactive_for_ns` has passed since then.
+    /// Activity is only known locally, so the other members accept the removal from any admin
+    /// and reject it from anyone else. Returns the installations that were removed.
+    pub async fn prune_inactive_installations(
+        &self,
+        inactive_for_ns: i64,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        let provider = self.client.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type != ConversationType::Group {
+            return Err(GroupError::PruneForbidden);
+        }
+        let inbox_id = self.client.inbox_id().to_string();
+        if !self.is_admin(inbox_id.clone(), &provider)?
+            && !self.is_super_admin(inbox_id, &provider)?
+        {
+            return Err(GroupError::PruneForbidden);
+        }
+
+        let my_installation_id = self.context().installation_public_key().to_vec();
+        let installation_ids: Vec<Vec<u8>> = self.load_mls_group_with_lock(&provider, |group| {
+            Ok(group
+                .members()
+                .map(|member| member.signature_key)
+                .filter(|installation_id| *installation_id != my_installation_id)
+                .collect())
+        })?;
+
+        let conn = provider.conn_ref();
+        let now = now_ns();
+        let last_active = conn.installations_last_active(&installation_ids)?;
+        let mut pruned = vec![];
+        for installation_id in installation_ids {
+            match last_active.get(&installation_id) {
+                Some(last_active_ns) if now - last_active_ns > inactive_for_ns => {
+                    pruned.push(installation_id);
+                }
+                Some(_) => {}
+                // Start tracking installations we have never seen
+                None => conn.record_installation_activity(&installation_id, now)?,
+            }
+        }
+        if pruned.is_empty() {
+            return Ok(pruned);
+        }
+
+        tracing::info!(
+            inbox_id = self.client.inbox_id(),
+            group_id = hex::encode(&self.group_id),
+            "pruning {} inactive installations",
+            pruned.len()
+        );
+        let intent_data: Vec<u8> = RemoveInstallationsIntentData::new(pruned.clone()).into();
+        let intent = self.queue_intent(&provider, IntentKind::RemoveInstallations, intent_data)?;
+        self.sync_until_intent_resolved(&provider, intent.id)
+            .await?;
+        Ok(pruned)
+    }
+
     /// Send a message, optimistically returning the ID of the message before the result of a message publish.
+    ///
+    /// The message is stored locally as [`DeliveryStatus::Unpublished`] and published on the next
+    /// call to [`Self::publish_messages`] or sync. The returned ID is derived from the same
+    /// idempotency key that is published, so it stays the ID of the message once it has been
+    /// read back from the network. Each status change is broadcast as a
+    /// [`MessageDelivery`](crate::subscriptions::MessageDelivery) event, which can be streamed
+    /// with [`Client::stream_message_deliveries_with_callback`].
     pub fn send_message_optimistic(&self, message: &[u8]) -> Result<Vec<u8>, GroupError> {
         let provider = self.mls_provider()?;
         let message_id =
@@ -825,7 +1145,17 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
     where
         F: FnOnce(i64) -> PlaintextEnvelope,
     {
+        if let Ok(content) = EncodedContent::decode(message) {
+            validate_app_metadata(&content).map_err(GroupError::AppMetadata)?;
+        }
+
         let now = now_ns();
+        if self.has_left(provider)? {
+            return Err(GroupError::GroupLeft);
+        }
+        if self.is_expired(provider, now)? {
+            return Err(GroupError::GroupExpired);
+        }
         let plain_envelope = envelope(now);
         let mut encoded_envelope = vec![];
         plain_envelope
@@ -852,8 +1182,15 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
             version_minor: queryable_content_fields.version_minor,
             authority_id: queryable_content_fields.authority_id,
             reference_id: queryable_content_fields.reference_id,
+            sent_by_me: true,
+            sender_verified: true,
         };
         group_message.store(provider.conn_ref())?;
+        polls::record_poll_content(provider.conn_ref(), &group_message)?;
+        locations::record_location_content(provider.conn_ref(), &group_message)?;
+        provider
+            .conn_ref()
+            .record_reaction_message(&group_message)?;
 
         Ok(message_id)
     }
@@ -878,6 +1215,17 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         Ok(messages)
     }
 
+    /// Number of application messages on each day from `from` to `to` that has any, for
+    /// date separators
+    pub fn count_messages_by_day(
+        &self,
+        from: LocalDay,
+        to: LocalDay,
+    ) -> Result<Vec<(LocalDay, i64)>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.count_messages_by_day(&self.group_id, from, to)?)
+    }
+
     /// Query the database for stored messages. Optionally filtered by time, kind, delivery_status
     /// and limit
     pub fn find_messages_with_reactions(
@@ -896,18 +1244,13 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
     /// group membership will be updated to include those changes as well.
     #[tracing::instrument(level = "trace", skip_all)]
     pub async fn add_members(&self, account_addresses_to_add: &[String]) -> Result<(), GroupError> {
-        let account_addresses = sanitize_evm_addresses(account_addresses_to_add)?;
+        let account_addresses = sanitize_account_addresses(account_addresses_to_add)?;
         let inbox_id_map = self
             .client
             .api()
             .get_inbox_ids(account_addresses.clone())
             .await?;
         let provider = self.mls_provider()?;
-        // get current number of users in group
-        let member_count = self.members_with_provider(&provider).await?.len();
-        if member_count + inbox_id_map.len() > MAX_GROUP_SIZE {
-            return Err(GroupError::UserLimitExceeded);
-        }
 
         if inbox_id_map.len() != account_addresses.len() {
             let found_addresses: HashSet<&String> = inbox_id_map.keys().collect();
@@ -942,6 +1285,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         inbox_ids: &[S],
     ) -> Result<(), GroupError> {
         let ids = inbox_ids.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        self.check_group_size(provider, &ids)?;
         let intent_data = self
             .get_membership_update_intent(provider, ids.as_slice(), &[])
             .await?;
@@ -963,6 +1307,28 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         self.sync_until_intent_resolved(provider, intent.id).await
     }
 
+    /// Make sure that adding `inbox_ids` keeps the group within the client's maximum group size
+    fn check_group_size(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        inbox_ids: &[&str],
+    ) -> Result<(), GroupError> {
+        let membership = self.load_mls_group_with_lock(provider, |mls_group| {
+            Ok(extract_group_membership(mls_group.extensions())?)
+        })?;
+        let new_members: HashSet<&str> = inbox_ids
+            .iter()
+            .copied()
+            .filter(|inbox_id| membership.get(inbox_id).is_none())
+            .collect();
+        let size = membership.members.len() + new_members.len();
+        let max = self.client.max_group_size();
+        if size > max {
+            return Err(GroupError::UserLimitExceeded { size, max });
+        }
+        Ok(())
+    }
+
     /// Removes members from the group by their account addresses.
     ///
     /// # Arguments
@@ -975,7 +1341,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         &self,
         account_addresses_to_remove: &[InboxId],
     ) -> Result<(), GroupError> {
-        let account_addresses = sanitize_evm_addresses(account_addresses_to_remove)?;
+        let account_addresses = sanitize_account_addresses(account_addresses_to_remove)?;
         let inbox_id_map = self.client.api().get_inbox_ids(account_addresses).await?;
 
         let ids = inbox_id_map
@@ -998,6 +1364,13 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         inbox_ids: &[InboxIdRef<'_>],
     ) -> Result<(), GroupError> {
         let provider = self.client.store().conn()?.into();
+        // The commit would be refused by the permission policy with a generic error
+        if self
+            .mutable_metadata(&provider)?
+            .would_orphan(&[], inbox_ids)
+        {
+            return Err(GroupError::LastSuperAdmin);
+        }
 
         let intent_data = self
             .get_membership_update_intent(&provider, &[], inbox_ids)
@@ -1237,6 +1610,32 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         }
     }
 
+    /// Set the time in NS after which the group expires, or remove the expiration with `None`.
+    /// Once expired, messages can no longer be sent, the group is no longer active and its
+    /// messages are purged by the disappearing messages worker. Only admins can update it.
+    pub async fn update_group_expiration(
+        &self,
+        expires_at_ns: Option<i64>,
+    ) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type == ConversationType::Dm {
+            return Err(GroupError::DmGroupMetadataForbidden);
+        }
+        let intent_data: Vec<u8> =
+            UpdateMetadataIntentData::new_update_group_expires_at_ns(expires_at_ns).into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The time in NS after which the group expires, if any
+    pub fn group_expiration(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Option<i64>, GroupError> {
+        let mutable_metadata = self.mutable_metadata(provider)?;
+        Ok(mutable_metadata.expires_at_ns())
+    }
+
     /// Retrieves the admin list of the group from the group's mutable metadata extension.
     pub fn admin_list(&self, provider: &XmtpOpenMlsProvider) -> Result<Vec<String>, GroupError> {
         let mutable_metadata = self.mutable_metadata(provider)?;
@@ -1297,6 +1696,14 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
             UpdateAdminListType::AddSuper => AdminListActionType::AddSuper,
             UpdateAdminListType::RemoveSuper => AdminListActionType::RemoveSuper,
         };
+        // The commit would be refused by the permission policy with a generic error
+        if intent_action_type == AdminListActionType::RemoveSuper
+            && self
+                .mutable_metadata(&provider)?
+                .would_orphan(&[&inbox_id], &[])
+        {
+            return Err(GroupError::LastSuperAdmin);
+        }
         let intent_data: Vec<u8> =
             UpdateAdminListIntentData::new(intent_action_type, inbox_id).into();
         let intent = self.queue_intent(&provider, IntentKind::UpdateAdminList, intent_data)?;
@@ -1304,6 +1711,53 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         self.sync_until_intent_resolved(&provider, intent.id).await
     }
 
+    /// Hand the super admin role of this inbox to `to_inbox_id`, which must be a member of the
+    /// group. This inbox stays an admin. Both admin lists change in a single commit, so the group
+    /// is never left with both or neither inbox as super admin. If the commit cannot be
+    /// published, the intent is dropped rather than retried on a later sync.
+    pub async fn transfer_super_admin(&self, to_inbox_id: String) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type == ConversationType::Dm {
+            return Err(GroupError::DmGroupMetadataForbidden);
+        }
+        let from_inbox_id = self.client.inbox_id().to_string();
+        if to_inbox_id == from_inbox_id {
+            return Err(GroupError::TransferForbidden(
+                "the role is already held by this inbox".to_string(),
+            ));
+        }
+        if !self.is_super_admin(from_inbox_id.clone(), &provider)? {
+            return Err(GroupError::TransferForbidden(
+                "only super admins can transfer the role".to_string(),
+            ));
+        }
+        let is_member = self.load_mls_group_with_lock(&provider, |mls_group| {
+            let membership = extract_group_membership(mls_group.extensions())?;
+            Ok::<_, GroupError>(membership.get(&to_inbox_id).is_some())
+        })?;
+        if !is_member {
+            return Err(GroupError::TransferForbidden(format!(
+                "{to_inbox_id} is not a member of the group"
+            )));
+        }
+
+        let intent_data: Vec<u8> =
+            TransferSuperAdminIntentData::new(from_inbox_id, to_inbox_id).into();
+        let intent = self.queue_intent(&provider, IntentKind::TransferSuperAdmin, intent_data)?;
+        let result = self.sync_until_intent_resolved(&provider, intent.id).await;
+        if result.is_err() {
+            let conn = provider.conn_ref();
+            if let Some(StoredGroupIntent {
+                state: IntentState::ToPublish,
+                ..
+            }) = Fetch::<StoredGroupIntent>::fetch(conn, &intent.id)?
+            {
+                conn.set_group_intent_error(intent.id)?;
+            }
+        }
+        result
+    }
+
     /// Find the `inbox_id` of the group member who added the member to the group
     pub fn added_by_inbox_id(&self) -> Result<String, GroupError> {
         let conn = self.context().store().conn()?;
@@ -1326,6 +1780,16 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         Ok(dm_id.other_inbox_id(inbox_id))
     }
 
+    /// A stable, URL-safe id of this conversation, see
+    /// [`conversation_public_id`](crate::utils::id::conversation_public_id)
+    pub fn public_id(&self) -> Result<String, GroupError> {
+        let conn = self.context().store().conn()?;
+        let group = conn
+            .find_group(&self.group_id)?
+            .ok_or_else(|| NotFound::GroupById(self.group_id.clone()))?;
+        Ok(group.public_id())
+    }
+
     /// Find the `consent_state` of the group
     pub fn consent_state(&self) -> Result<ConsentState, GroupError> {
         let conn = self.context().store().conn()?;
@@ -1365,20 +1829,72 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         Ok(())
     }
 
-    /// Update this installation's leaf key in the group by creating a key update commit
+    /// Update this installation's leaf key in the group by creating a key update commit, right
+    /// away instead of waiting for the [rotation interval](Self::key_rotation_interval) to pass
     pub async fn key_update(&self) -> Result<(), GroupError> {
         let provider = self.client.mls_provider()?;
         let intent = self.queue_intent(&provider, IntentKind::KeyUpdate, vec![])?;
         self.sync_until_intent_resolved(&provider, intent.id).await
     }
 
+    /// Rotate this installation's leaf key in the group at least every `interval`, instead of
+    /// the interval of the client. `None` goes back to the interval of the client.
+    pub fn set_key_rotation_interval(&self, interval: Option<Duration>) -> Result<(), GroupError> {
+        let interval_ns = interval
+            .map(|interval| i64::try_from(interval.as_nanos()))
+            .transpose()
+            .map_err(|_| GroupError::KeyRotationIntervalTooLong)?;
+        let conn = self.context().store().conn()?;
+        conn.set_key_rotation_interval(&self.group_id, interval_ns)?;
+        Ok(())
+    }
+
+    /// How long this installation's leaf key in the group may go without rotation. The key is
+    /// rotated with the next message sent or sync after that.
+    pub fn key_rotation_interval(&self) -> Result<Duration, GroupError> {
+        let conn = self.context().store().conn()?;
+        let interval_ns = self.key_rotation_interval_ns(&conn)?;
+        Ok(Duration::from_nanos(interval_ns.max(0) as u64))
+    }
+
     /// Checks if the current user is active in the group.
     ///
-    /// If the current user has been kicked out of the group, `is_active` will return `false`
+    /// If the current user has been kicked out of the group, or the group expired, `is_active`
+    /// will return `false`
     pub fn is_active(&self, provider: &XmtpOpenMlsProvider) -> Result<bool, GroupError> {
+        if self.has_left(provider)? || self.is_expired(provider, now_ns())? {
+            return Ok(false);
+        }
         self.load_mls_group_with_lock(provider, |mls_group| Ok(mls_group.is_active()))
     }
 
+    /// Where the group is in its lifecycle, see [`GroupLifecycleState`]. Groups whose history
+    /// was purged are still listed, so they can be shown as left.
+    pub fn lifecycle_state(&self) -> Result<GroupLifecycleState, GroupError> {
+        let conn = self.context().store().conn()?;
+        let group = conn
+            .find_group(&self.group_id)?
+            .ok_or_else(|| NotFound::GroupById(self.group_id.clone()))?;
+        Ok(group.lifecycle_state)
+    }
+
+    /// Whether this installation left the group, or asked to leave it
+    fn has_left(&self, provider: &XmtpOpenMlsProvider) -> Result<bool, GroupError> {
+        Ok(provider
+            .conn_ref()
+            .find_group(&self.group_id)?
+            .is_some_and(|group| {
+                group.has_left() || group.membership_state == GroupMembershipState::PendingRemove
+            }))
+    }
+
+    fn is_expired(&self, provider: &XmtpOpenMlsProvider, now_ns: i64) -> Result<bool, GroupError> {
+        Ok(provider
+            .conn_ref()
+            .find_group(&self.group_id)?
+            .is_some_and(|group| group.is_expired(now_ns)))
+    }
+
     /// Get the `GroupMetadata` of the group.
     pub async fn metadata(
         &self,
@@ -1668,6 +2184,31 @@ pub fn build_extensions_for_admin_lists_update(
     Ok(extensions)
 }
 
+pub fn build_extensions_for_super_admin_transfer(
+    group: &OpenMlsGroup,
+    transfer: TransferSuperAdminIntentData,
+) -> Result<Extensions, GroupError> {
+    let existing_metadata: GroupMutableMetadata = group.try_into()?;
+    let attributes = existing_metadata.attributes.clone();
+    let mut admin_list = existing_metadata.admin_list;
+    let mut super_admin_list = existing_metadata.super_admin_list;
+    super_admin_list.retain(|x| x != &transfer.from_inbox_id);
+    if !super_admin_list.contains(&transfer.to_inbox_id) {
+        super_admin_list.push(transfer.to_inbox_id.clone());
+    }
+    admin_list.retain(|x| x != &transfer.to_inbox_id);
+    if !admin_list.contains(&transfer.from_inbox_id) {
+        admin_list.push(transfer.from_inbox_id);
+    }
+    let new_mutable_metadata: Vec<u8> =
+        GroupMutableMetadata::new(attributes, admin_list, super_admin_list).try_into()?;
+    let unknown_gc_extension = UnknownExtension(new_mutable_metadata);
+    let extension = Extension::Unknown(MUTABLE_METADATA_EXTENSION_ID, unknown_gc_extension);
+    let mut extensions = group.extensions().clone();
+    extensions.add_or_replace(extension);
+    Ok(extensions)
+}
+
 pub fn build_starting_group_membership_extension(inbox_id: &str, sequence_id: u64) -> Extension {
     let mut group_membership = GroupMembership::new();
     group_membership.add(inbox_id.to_string(), sequence_id);
@@ -1697,10 +2238,15 @@ fn build_group_config(
 
     let required_proposal_types = &[ProposalType::GroupContextExtensions];
 
+    let supported_extension_types: Vec<ExtensionType> = required_extension_types
+        .iter()
+        .copied()
+        .chain(capabilities::supported_extension_types())
+        .collect();
     let capabilities = Capabilities::new(
         None,
         None,
-        Some(required_extension_types),
+        Some(&supported_extension_types),
         Some(required_proposal_types),
         None,
     );
@@ -1749,15 +2295,14 @@ async fn validate_initial_group_membership(
 
     let mut expected_installation_ids = HashSet::<Vec<u8>>::new();
 
-    let futures: Vec<_> = membership
+    let identifiers: Vec<_> = membership
         .members
         .iter()
-        .map(|(inbox_id, sequence_id)| {
-            client.get_association_state(conn, inbox_id, Some(*sequence_id as i64))
-        })
+        .map(|(inbox_id, sequence_id)| (inbox_id.as_str(), Some(*sequence_id as i64)))
         .collect();
-
-    let results = futures::future::try_join_all(futures).await?;
+    let results = client
+        .batch_get_association_state(conn, &identifiers)
+        .await?;
 
     for association_state in results {
         expected_installation_ids.extend(association_state.installation_ids());
@@ -1878,25 +2423,33 @@ pub(crate) mod tests {
     use crate::storage::group::StoredGroup;
     use crate::storage::schema::groups;
     use crate::{
+        api::NetworkSubsystem,
         builder::ClientBuilder,
+        configuration::GROUP_KEY_ROTATION_INTERVAL_NS,
+        data_mode::DataMode,
         groups::{
             build_dm_protected_metadata_extension, build_mutable_metadata_extension_default,
             build_protected_metadata_extension,
             group_metadata::GroupMetadata,
             group_mutable_metadata::MetadataField,
-            intents::{PermissionPolicyOption, PermissionUpdateType},
+            intents::{
+                PermissionPolicyOption, PermissionUpdateType, ReaddInstallationsIntentData,
+                RemoveInstallationsIntentData,
+            },
             members::{GroupMember, PermissionLevel},
             mls_sync::GroupMessageProcessingError,
             validate_dm_group, DeliveryStatus, GroupError, GroupMetadataOptions,
             PreconfiguredPolicies, UpdateAdminListType,
         },
         storage::{
+            consent_policy::{ConsentPolicy, RequestPolicy},
             consent_record::ConsentState,
-            group::{ConversationType, GroupQueryArgs},
+            group::{ConversationType, GroupLifecycleState, GroupQueryArgs},
             group_intent::{IntentKind, IntentState},
             group_message::{GroupMessageKind, MsgQueryArgs, StoredGroupMessage},
             xmtp_openmls_provider::XmtpOpenMlsProvider,
         },
+        subscriptions::{LocalEvents, MessageDelivery},
         utils::test::FullXmtpClient,
         InboxOwner, StreamHandle as _,
     };
@@ -1983,6 +2536,80 @@ pub(crate) mod tests {
         assert_eq!(messages.len(), 2);
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_send_message_with_app_metadata() {
+        use xmtp_content_types::{
+            app_metadata::{set_app_metadata, MAX_APP_METADATA_BYTES},
+            encoded_content_to_bytes,
+            text::TextCodec,
+        };
+
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        let mut content = TextCodec::encode("hello".to_string()).unwrap();
+        set_app_metadata(&mut content, "com.example", "thread", "42").unwrap();
+        amal_group
+            .send_message(&encoded_content_to_bytes(content.clone()))
+            .await
+            .unwrap();
+
+        // Metadata over the limit is rejected before it is sent
+        content.parameters.insert(
+            "app:com.example:blob".to_string(),
+            "x".repeat(MAX_APP_METADATA_BYTES),
+        );
+        let err = amal_group
+            .send_message(&encoded_content_to_bytes(content))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GroupError::AppMetadata(_)));
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        let messages = bola_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].app_metadata()["com.example"]["thread"], "42");
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_received_messages_have_verified_senders() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        amal_group.send_message(b"hello").await.unwrap();
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        let messages = bola_group.find_messages(&MsgQueryArgs::default()).unwrap();
+        assert!(!messages.is_empty());
+        assert!(messages.iter().all(|message| message.sender_verified));
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
     async fn test_receive_self_message() {
         let wallet = generate_local_wallet();
@@ -2035,22 +2662,122 @@ pub(crate) mod tests {
         assert_eq!(message.decrypted_message_bytes, bo_message);
     }
 
-    // Test members function from non group creator
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
-    async fn test_members_func_from_non_creator() {
-        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
-        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
-
-        let amal_group = amal
+    async fn test_low_data_mode_batches_publishes() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
             .create_group(None, GroupMetadataOptions::default())
             .unwrap();
-        amal_group
-            .add_members_by_inbox_id(&[bola.inbox_id()])
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
             .await
             .unwrap();
 
-        // Get bola's version of the same group
-        let bola_groups = bola
+        alix.set_data_mode(DataMode::Low);
+        alix_group.send_message_optimistic(b"one").unwrap();
+        alix_group.send_message_optimistic(b"two").unwrap();
+        alix_group.send_message_optimistic(b"three").unwrap();
+        alix.reset_network_usage();
+        alix_group.publish_messages().await.unwrap();
+        assert_eq!(
+            alix.network_usage()
+                .subsystem(NetworkSubsystem::Publish)
+                .requests,
+            1
+        );
+
+        let bo_group = receive_group_invite(&bo).await;
+        bo_group.sync().await.unwrap();
+        let messages = bo_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        let contents: Vec<_> = messages
+            .iter()
+            .map(|m| m.decrypted_message_bytes.as_slice())
+            .collect();
+        assert_eq!(contents, vec![&b"one"[..], b"two", b"three"]);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_group_expiration() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let expires_at_ns = now_ns() + 3_600_000_000_000;
+        let alix_group = alix
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    expires_at_ns: Some(expires_at_ns),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        alix_group.send_message(b"hello").await.unwrap();
+
+        let bo_provider = bo.mls_provider().unwrap();
+        let bo_group = receive_group_invite(&bo).await;
+        bo_group.sync().await.unwrap();
+        assert_eq!(
+            bo_group.group_expiration(&bo_provider).unwrap(),
+            Some(expires_at_ns)
+        );
+        assert!(bo_group.is_active(&bo_provider).unwrap());
+        // Only admins can change the expiration
+        assert!(bo_group.update_group_expiration(None).await.is_err());
+
+        alix_group
+            .update_group_expiration(Some(now_ns() - 1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            alix_group.send_message(b"too late").await,
+            Err(GroupError::GroupExpired)
+        ));
+        assert!(!alix_group
+            .is_active(&alix_group.mls_provider().unwrap())
+            .unwrap());
+
+        bo_group.sync().await.unwrap();
+        assert!(!bo_group.is_active(&bo_provider).unwrap());
+        assert!(matches!(
+            bo_group.send_message_optimistic(b"too late"),
+            Err(GroupError::GroupExpired)
+        ));
+
+        bo_provider
+            .conn_ref()
+            .purge_expired_groups(now_ns())
+            .unwrap();
+        assert!(bo_group
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    // Test members function from non group creator
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_members_func_from_non_creator() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        // Get bola's version of the same group
+        let bola_groups = bola
             .sync_welcomes(&bola.mls_provider().unwrap())
             .await
             .unwrap();
@@ -2437,6 +3164,83 @@ pub(crate) mod tests {
         assert_eq!(bola_messages.len(), 1);
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_key_rotation_interval() {
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = client
+            .create_group(None, GroupMetadataOptions::default())
+            .expect("create group");
+        assert_eq!(
+            group.key_rotation_interval().unwrap().as_nanos() as i64,
+            GROUP_KEY_ROTATION_INTERVAL_NS
+        );
+
+        // Any time since the last rotation is too long, so the next message rotates the key
+        group
+            .set_key_rotation_interval(Some(std::time::Duration::ZERO))
+            .unwrap();
+        let mut events = client.local_events.subscribe();
+        group.send_message(b"hello").await.unwrap();
+        let rotated = loop {
+            if let LocalEvents::KeyRotated(rotated) = events.recv().await.unwrap() {
+                break rotated;
+            }
+        };
+        assert_eq!(rotated.group_id, group.group_id);
+        let epoch = group
+            .load_mls_group_with_lock(&client.mls_provider().unwrap(), |mls_group| {
+                Ok(mls_group.epoch().as_u64())
+            })
+            .unwrap();
+        assert_eq!(rotated.epoch, epoch);
+
+        group.set_key_rotation_interval(None).unwrap();
+        assert_eq!(
+            group.key_rotation_interval().unwrap().as_nanos() as i64,
+            GROUP_KEY_ROTATION_INTERVAL_NS
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_key_rotation_on_sync() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .expect("create group");
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+
+        // Bola only reads the group, the key is rotated when syncing
+        bola_group
+            .set_key_rotation_interval(Some(std::time::Duration::ZERO))
+            .unwrap();
+        let mut events = amal.local_events.subscribe();
+        bola_group.sync().await.unwrap();
+        amal_group.sync().await.unwrap();
+        let rotated = loop {
+            if let LocalEvents::KeyRotated(rotated) = events.recv().await.unwrap() {
+                break rotated;
+            }
+        };
+        assert_eq!(rotated.group_id, amal_group.group_id);
+        assert_eq!(
+            rotated.installation_id,
+            bola.installation_public_key().to_vec()
+        );
+
+        assert!(matches!(
+            amal_group.set_key_rotation_interval(Some(std::time::Duration::MAX)),
+            Err(GroupError::KeyRotationIntervalTooLong)
+        ));
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
     async fn test_post_commit() {
         let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
@@ -2572,6 +3376,68 @@ pub(crate) mod tests {
         assert!(amal_messages.is_empty());
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_removed_members_keep_a_tombstone() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = &generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(bola_wallet).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members(&[bola_wallet.get_address()])
+            .await
+            .unwrap();
+        let bola_provider = bola.mls_provider().unwrap();
+        bola.sync_welcomes(&bola_provider).await.unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.send_message(b"hello").await.unwrap();
+        assert_eq!(
+            bola_group.lifecycle_state().unwrap(),
+            GroupLifecycleState::Active
+        );
+
+        amal_group
+            .remove_members(&[bola_wallet.get_address()])
+            .await
+            .unwrap();
+        bola_group.sync().await.unwrap();
+        assert_eq!(
+            bola_group.lifecycle_state().unwrap(),
+            GroupLifecycleState::Left
+        );
+        assert!(!bola_group.is_active(&bola_provider).unwrap());
+        assert!(matches!(
+            bola_group.send_message(b"still here?").await,
+            Err(GroupError::GroupLeft)
+        ));
+        assert_eq!(
+            bola.sync_all_welcomes_and_groups(&bola_provider, None)
+                .await
+                .unwrap(),
+            0
+        );
+
+        bola_provider
+            .conn_ref()
+            .purge_left_groups(now_ns())
+            .unwrap();
+        assert_eq!(
+            bola_group.lifecycle_state().unwrap(),
+            GroupLifecycleState::Purged
+        );
+        assert!(bola_group
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+        // The tombstone is still listed
+        assert_eq!(
+            bola.find_groups(GroupQueryArgs::default()).unwrap().len(),
+            1
+        );
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
     async fn test_add_missing_installations() {
         // Setup for test
@@ -2703,6 +3569,7 @@ pub(crate) mod tests {
                     message_disappearing_settings: Some(
                         expected_group_message_disappearing_settings.clone(),
                     ),
+                    expires_at_ns: None,
                 },
             )
             .unwrap();
@@ -3383,13 +4250,73 @@ pub(crate) mod tests {
         drop(provider); // allow connection to be re-added to pool
 
         // Verify that amal can NOT remove themself as a super admin because they are the only remaining
-        amal_group
+        let result = amal_group
             .update_admin_list(
                 UpdateAdminListType::RemoveSuper,
                 amal.inbox_id().to_string(),
             )
+            .await;
+        assert!(matches!(result, Err(GroupError::LastSuperAdmin)));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_transfer_super_admin() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
             .await
-            .expect_err("expected err");
+            .unwrap();
+        assert!(matches!(
+            amal_group
+                .transfer_super_admin(caro.inbox_id().to_string())
+                .await,
+            Err(GroupError::TransferForbidden(_))
+        ));
+
+        amal_group
+            .transfer_super_admin(bola.inbox_id().to_string())
+            .await
+            .unwrap();
+        let provider = amal_group.mls_provider().unwrap();
+        assert_eq!(
+            amal_group.super_admin_list(&provider).unwrap(),
+            vec![bola.inbox_id().to_string()]
+        );
+        assert_eq!(
+            amal_group.admin_list(&provider).unwrap(),
+            vec![amal.inbox_id().to_string()]
+        );
+        // A single commit was published
+        let intents = provider
+            .conn_ref()
+            .find_group_intents(amal_group.group_id.clone(), None, None)
+            .unwrap();
+        assert!(intents.is_empty());
+        drop(provider); // allow connection to be re-added to pool
+
+        // Amal is no longer a super admin
+        assert!(matches!(
+            amal_group
+                .transfer_super_admin(bola.inbox_id().to_string())
+                .await,
+            Err(GroupError::TransferForbidden(_))
+        ));
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        let provider = bola_group.mls_provider().unwrap();
+        assert!(bola_group
+            .is_super_admin(bola.inbox_id().to_string(), &provider)
+            .unwrap());
     }
 
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
@@ -3782,6 +4709,249 @@ pub(crate) mod tests {
         );
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_optimistic_send_reports_delivery() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group.sync().await.unwrap();
+
+        let mut events = amal.local_events.subscribe();
+        let message_id = amal_group.send_message_optimistic(b"hello").unwrap();
+        let stored = amal.store().conn().unwrap();
+        assert_eq!(
+            stored
+                .get_group_message(&message_id)
+                .unwrap()
+                .unwrap()
+                .delivery_status,
+            DeliveryStatus::Unpublished
+        );
+
+        amal_group.publish_messages().await.unwrap();
+
+        let delivery = loop {
+            if let LocalEvents::MessageDelivery(delivery) = events.recv().await.unwrap() {
+                break delivery;
+            }
+        };
+        assert_eq!(
+            delivery,
+            MessageDelivery {
+                group_id: amal_group.group_id.clone(),
+                message_id: message_id.clone(),
+                status: DeliveryStatus::Published,
+            }
+        );
+        let messages = amal_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, message_id);
+        assert_eq!(messages[0].delivery_status, DeliveryStatus::Published);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_prune_inactive_installations() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola_a = ClientBuilder::new_test_client(&bola_wallet).await;
+        let bola_b = ClientBuilder::new_test_client(&bola_wallet).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola_a.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        let mut groups = vec![(&amal, amal_group.clone())];
+        for client in [&bola_a, &caro] {
+            client
+                .sync_welcomes(&client.mls_provider().unwrap())
+                .await
+                .unwrap();
+            groups.push((client, client.group(amal_group.group_id.clone()).unwrap()));
+        }
+        // The installations in the tree of each member, once synced
+        async fn synced_trees(
+            groups: &[(&FullXmtpClient, MlsGroup<FullXmtpClient>)],
+        ) -> Vec<Vec<Vec<u8>>> {
+            let mut trees = vec![];
+            for (client, group) in groups {
+                group.sync().await.unwrap();
+                let mut installations: Vec<Vec<u8>> = group
+                    .load_mls_group_with_lock(client.mls_provider().unwrap(), |group| {
+                        Ok(group.members().map(|member| member.signature_key).collect())
+                    })
+                    .unwrap();
+                installations.sort();
+                trees.push(installations);
+            }
+            trees
+        }
+        let bola_b_id = bola_b.installation_public_key().to_vec();
+        let assert_converged = |trees: Vec<Vec<Vec<u8>>>| {
+            assert!(trees.iter().all(|tree| *tree == trees[0]));
+            assert_eq!(trees[0].len(), 3);
+            assert!(!trees[0].contains(&bola_b_id));
+        };
+
+        // The key packages were just fetched, so the installations are active
+        let pruned = amal_group
+            .prune_inactive_installations(crate::configuration::NS_IN_HOUR)
+            .await
+            .unwrap();
+        assert!(pruned.is_empty());
+
+        // Bola's second installation goes quiet, the others keep sending messages
+        let quiet_since = now_ns();
+        xmtp_common::time::sleep(std::time::Duration::from_millis(100)).await;
+        for (_, group) in groups.iter().skip(1) {
+            group.send_message(b"still here").await.unwrap();
+        }
+        amal_group.sync().await.unwrap();
+        let pruned = amal_group
+            .prune_inactive_installations(now_ns() - quiet_since)
+            .await
+            .unwrap();
+        assert_eq!(pruned, vec![bola_b_id.clone()]);
+
+        // The other members accept the removal, and Bola is still a member
+        assert_converged(synced_trees(&groups).await);
+        assert!(amal_group
+            .members()
+            .await
+            .unwrap()
+            .iter()
+            .any(|member| member.inbox_id == bola_a.inbox_id()));
+
+        // Updating the installations doesn't add the pruned installation back
+        amal_group.update_installations().await.unwrap();
+        assert_converged(synced_trees(&groups).await);
+
+        // Only admins can prune installations
+        let (_, caro_group) = &groups[2];
+        let intent_data: Vec<u8> =
+            RemoveInstallationsIntentData::new(vec![bola_a.installation_public_key().to_vec()])
+                .into();
+        let provider = caro.mls_provider().unwrap();
+        let intent = caro_group
+            .queue_intent(&provider, IntentKind::RemoveInstallations, intent_data)
+            .unwrap();
+        caro_group
+            .sync_until_intent_resolved(&provider, intent.id)
+            .await
+            .unwrap_err();
+        assert_converged(synced_trees(&groups).await);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_joiner_acknowledges_welcome() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        let bola_installation_id = bola.installation_public_key().to_vec();
+        let amal_conn = amal.store().conn().unwrap();
+        let pending = amal_conn
+            .stale_pending_welcomes(&amal_group.group_id, now_ns())
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].installation_id, bola_installation_id);
+
+        // Joining does not commit anything, the first message of bola is the acknowledgement
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        let bola_conn = bola.store().conn().unwrap();
+        assert!(bola_conn
+            .find_group_intents(
+                amal_group.group_id.clone(),
+                None,
+                Some(vec![IntentKind::KeyUpdate])
+            )
+            .unwrap()
+            .is_empty());
+        bola_group.send_message(b"hi").await.unwrap();
+        amal_group.sync().await.unwrap();
+
+        assert!(amal_conn
+            .stale_pending_welcomes(&amal_group.group_id, now_ns())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_readded_joiner_acknowledges_welcome() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+
+        // Amal never heard from bola, and re-adds it with a fresh key package
+        let bola_installation_id = bola.installation_public_key().to_vec();
+        let amal_provider = amal.mls_provider().unwrap();
+        amal_group
+            .queue_intent(
+                &amal_provider,
+                IntentKind::ReaddInstallations,
+                ReaddInstallationsIntentData::new(vec![bola_installation_id.clone()]).into(),
+            )
+            .unwrap();
+        amal_group.sync().await.unwrap();
+        let amal_conn = amal.store().conn().unwrap();
+        let pending = amal_conn
+            .stale_pending_welcomes(&amal_group.group_id, now_ns())
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].installation_id, bola_installation_id);
+
+        // Bola already had the group, so it rotates its leaf on the new welcome
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_conn = bola.store().conn().unwrap();
+        assert_eq!(
+            bola_conn
+                .find_group_intents(
+                    amal_group.group_id.clone(),
+                    None,
+                    Some(vec![IntentKind::KeyUpdate])
+                )
+                .unwrap()
+                .len(),
+            1
+        );
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        amal_group.sync().await.unwrap();
+
+        assert!(amal_conn
+            .stale_pending_welcomes(&amal_group.group_id, now_ns())
+            .unwrap()
+            .is_empty());
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
     async fn test_dm_creation() {
         let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
@@ -4232,6 +5402,49 @@ pub(crate) mod tests {
         assert_eq!(caro_group.consent_state().unwrap(), ConsentState::Allowed);
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_consent_policy_applies_to_welcomes() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        bola.set_consent_policy(ConsentPolicy {
+            dms: RequestPolicy::AllowContacts,
+            groups: RequestPolicy::DenyUnknown,
+        })
+        .unwrap();
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(alix_group.group_id.clone()).unwrap();
+        assert_eq!(bola_group.consent_state().unwrap(), ConsentState::Denied);
+
+        // Once bola has messaged alix, alix is a contact
+        let bola_dm = bola
+            .find_or_create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        bola_dm.send_message(b"hi").await.unwrap();
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(alix_group.group_id.clone()).unwrap();
+        assert_eq!(bola_group.consent_state().unwrap(), ConsentState::Unknown);
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     // TODO(rich): Generalize the test once fixed - test messages that are 0, 1, 2, 3, 4, 5 epochs behind
     async fn test_max_past_epochs() {
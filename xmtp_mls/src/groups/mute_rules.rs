@@ -0,0 +1,164 @@
+//! Keyword and regex rules that mute incoming messages, see [`MuteRule`].
+//!
+//! Rules are kept in the user preferences and synced to the other installations. Incoming
+//! messages that match a rule are stored as usual and can be listed with
+//! [`MsgQueryArgs::filtered`], but are left out of [`MlsGroup::unread_count`] and
+//! [`MlsGroup::should_notify`].
+//!
+//! [`MsgQueryArgs::filtered`]: crate::storage::group_message::MsgQueryArgs::filtered
+use regex::Regex;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    groups::device_sync::preference_sync::UserPreferenceUpdate,
+    storage::{
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        mute_rule::MuteRule,
+    },
+    subscriptions::LocalEvents,
+    Client,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Mute incoming text messages that contain `pattern`, ignoring case, or that match it as a
+    /// regular expression if `is_regex` is set. The rule applies to all conversations unless
+    /// `group_id` is given. Only messages received after the rule is added are muted. The rule
+    /// is synced to the other installations.
+    pub fn add_mute_rule(
+        &self,
+        pattern: &str,
+        is_regex: bool,
+        group_id: Option<&[u8]>,
+    ) -> Result<MuteRule, ClientError> {
+        if pattern.is_empty() {
+            return Err(ClientError::Generic(
+                "mute rule pattern is empty".to_string(),
+            ));
+        }
+        if is_regex {
+            Regex::new(pattern)
+                .map_err(|e| ClientError::Generic(format!("invalid mute rule: {e}")))?;
+        }
+        let rule = MuteRule::new(pattern, is_regex, group_id);
+        self.store().conn()?.add_mute_rule(&rule)?;
+        self.sync_mute_rule_update(UserPreferenceUpdate::MuteRuleAdded(rule.clone()));
+        Ok(rule)
+    }
+
+    /// Remove a mute rule on every installation, returning `true` if there was one
+    pub fn remove_mute_rule(&self, id: &str) -> Result<bool, ClientError> {
+        if !self.store().conn()?.remove_mute_rule(id)? {
+            return Ok(false);
+        }
+        self.sync_mute_rule_update(UserPreferenceUpdate::MuteRuleRemoved { id: id.to_string() });
+        Ok(true)
+    }
+
+    fn sync_mute_rule_update(&self, update: UserPreferenceUpdate) {
+        if self.history_sync_url.is_some() {
+            let _ = self
+                .local_events
+                .send(LocalEvents::OutgoingPreferenceUpdates(vec![update]));
+        }
+    }
+
+    pub fn mute_rules(&self) -> Result<Vec<MuteRule>, ClientError> {
+        let conn = self.store().conn()?;
+        Ok(conn.mute_rules()?)
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Number of messages from other members sent after `read_at_ns`, leaving out muted messages
+    pub fn unread_count(&self, read_at_ns: i64) -> Result<i64, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.count_unread_messages(&self.group_id, read_at_ns)?)
+    }
+
+    /// Whether a message of this group should raise a notification: it is a readable
+    /// application message from another member that no mute rule filtered, and neither the
+    /// conversation nor the sender is denied
+    pub fn should_notify(&self, message: &StoredGroupMessage) -> Result<bool, GroupError> {
+        if message.group_id != self.group_id
+            || message.sent_by_me
+            || message.kind != GroupMessageKind::Application
+            || !ContentType::READABLE.contains(&message.content_type)
+        {
+            return Ok(false);
+        }
+        let conn = self.context().store().conn()?;
+        Ok(!conn.is_message_filtered(&message.id)?
+            && !conn.is_consent_denied(&message.group_id, &message.sender_inbox_id)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{ContentType, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_mute_rules_filter_incoming_messages() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert!(bola.add_mute_rule("(unclosed", true, None).is_err());
+        bola.add_mute_rule("spoiler", false, None).unwrap();
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        for text in ["Spoiler: it was a dream", "hello"] {
+            let content = TextCodec::encode(text.to_string()).unwrap();
+            amal_group
+                .send_message(&encoded_content_to_bytes(content))
+                .await
+                .unwrap();
+        }
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+
+        let filtered = bola_group
+            .find_messages(&MsgQueryArgs {
+                filtered: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert!(!bola_group.should_notify(&filtered[0]).unwrap());
+        assert_eq!(bola_group.unread_count(0).unwrap(), 1);
+
+        let shown = bola_group
+            .find_messages(&MsgQueryArgs {
+                filtered: Some(false),
+                content_types: Some(vec![ContentType::Text]),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(shown.len(), 1);
+        assert!(bola_group.should_notify(&shown[0]).unwrap());
+    }
+}
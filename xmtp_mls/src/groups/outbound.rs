@@ -0,0 +1,166 @@
+//! Hooks that run before an intent is published.
+//!
+//! An [`OutboundInterceptor`] sees every intent right before it is turned into an MLS message,
+//! for audit logging, DLP scanning or metrics. Interceptors only get a read-only view of the
+//! intent and cannot change what is encrypted or published. They can attach
+//! [`OutboundAnnotations`], which are logged alongside the publish.
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::storage::group_intent::{IntentKind, StoredGroupIntent};
+
+use super::intents::SendMessageIntentData;
+
+/// A read-only view of an intent about to be published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboundIntent<'a> {
+    pub group_id: &'a [u8],
+    pub intent_id: i32,
+    pub kind: IntentKind,
+    /// Number of earlier attempts to publish this intent
+    pub publish_attempts: i32,
+    /// The encoded message content, for [`IntentKind::SendMessage`] intents
+    pub message: Option<&'a [u8]>,
+}
+
+/// Key-value annotations attached to an intent by [`OutboundInterceptor`]s.
+/// Later interceptors see the annotations of earlier ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutboundAnnotations(BTreeMap<String, String>);
+
+impl OutboundAnnotations {
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Observes intents before they are published.
+///
+/// Interceptors run every time an intent is published, including retries, while the group is
+/// locked. They should return quickly and hand any expensive work off to another task.
+pub trait OutboundInterceptor: Send + Sync {
+    fn before_publish(&self, intent: &OutboundIntent<'_>, annotations: &mut OutboundAnnotations);
+}
+
+/// The interceptors registered on a client, called in the order they were added
+#[derive(Clone, Default)]
+pub struct OutboundInterceptors(Arc<Vec<Arc<dyn OutboundInterceptor>>>);
+
+impl std::fmt::Debug for OutboundInterceptors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutboundInterceptors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+impl OutboundInterceptors {
+    pub fn push(&mut self, interceptor: Arc<dyn OutboundInterceptor>) {
+        Arc::make_mut(&mut self.0).push(interceptor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Run every interceptor on `intent`, and collect their annotations
+    pub(crate) fn before_publish(&self, intent: &StoredGroupIntent) -> OutboundAnnotations {
+        let mut annotations = OutboundAnnotations::default();
+        if self.is_empty() {
+            return annotations;
+        }
+
+        let message = match intent.kind {
+            IntentKind::SendMessage => SendMessageIntentData::from_bytes(&intent.data)
+                .map(|data| data.message)
+                .ok(),
+            _ => None,
+        };
+        let outbound = OutboundIntent {
+            group_id: &intent.group_id,
+            intent_id: intent.id,
+            kind: intent.kind,
+            publish_attempts: intent.publish_attempts,
+            message: message.as_deref(),
+        };
+        for interceptor in self.0.iter() {
+            interceptor.before_publish(&outbound, &mut annotations);
+        }
+        annotations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use parking_lot::Mutex;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[derive(Default)]
+    struct AuditLog {
+        seen: Mutex<Vec<(IntentKind, Option<Vec<u8>>, Option<String>)>>,
+    }
+
+    impl OutboundInterceptor for AuditLog {
+        fn before_publish(
+            &self,
+            intent: &OutboundIntent<'_>,
+            annotations: &mut OutboundAnnotations,
+        ) {
+            self.seen.lock().push((
+                intent.kind,
+                intent.message.map(<[u8]>::to_vec),
+                annotations.get("policy").map(str::to_string),
+            ));
+        }
+    }
+
+    struct Policy;
+
+    impl OutboundInterceptor for Policy {
+        fn before_publish(
+            &self,
+            _intent: &OutboundIntent<'_>,
+            annotations: &mut OutboundAnnotations,
+        ) {
+            annotations.insert("policy", "reviewed");
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn interceptors_see_intents_before_publish() {
+        let mut amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let audit = Arc::new(AuditLog::default());
+        amal.outbound_interceptors.push(Arc::new(Policy));
+        amal.outbound_interceptors.push(audit.clone());
+
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group.send_message(b"hello").await.unwrap();
+
+        let seen = audit.seen.lock();
+        let (_, message, policy) = seen
+            .iter()
+            .find(|(kind, _, _)| *kind == IntentKind::SendMessage)
+            .expect("send message intent was intercepted");
+        assert!(message.as_ref().is_some_and(|m| !m.is_empty()));
+        assert_eq!(policy.as_deref(), Some("reviewed"));
+    }
+}
@@ -0,0 +1,260 @@
+//! Pinned messages.
+//!
+//! The pinned set is part of the group metadata, see
+//! [`super::group_mutable_metadata::MetadataField::PinnedMessages`], so it
+//! changes through commits that the metadata policy of the field gates, and every member ends
+//! up with the same set. Each installation mirrors it in the `pinned_messages` table when the
+//! field changes.
+//!
+//! An intent only holds the message to pin or unpin. The new set is computed from the metadata
+//! of the group when the intent is published, and the sender enforces [`MAX_PINNED_MESSAGES`]
+//! there. Receivers accept any set a commit carries, like they do for other metadata fields.
+use openmls::{extensions::Extensions, group::MlsGroup as OpenMlsGroup};
+
+use super::{
+    build_extensions_for_metadata_update, group_mutable_metadata::GroupMutableMetadata,
+    group_mutable_metadata::MetadataField, intents::UpdatePinnedMessagesIntentData, GroupError,
+    MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    configuration::MAX_PINNED_MESSAGES,
+    storage::{
+        group_intent::IntentKind,
+        group_message::{GroupMessageKind, StoredGroupMessage},
+        NotFound,
+    },
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Pin a message of the group for every member. Pinning a pinned message does nothing.
+    pub async fn pin_message(&self, message_id: &[u8]) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        provider
+            .conn_ref()
+            .get_group_message(message_id)?
+            .filter(|message| {
+                message.group_id == self.group_id && message.kind == GroupMessageKind::Application
+            })
+            .ok_or_else(|| NotFound::MessageById(message_id.to_vec()))?;
+
+        self.update_pinned_messages(UpdatePinnedMessagesIntentData::new(
+            message_id.to_vec(),
+            true,
+        ))
+        .await
+    }
+
+    /// Unpin a message for every member. Unpinning a message that is not pinned does nothing.
+    pub async fn unpin_message(&self, message_id: &[u8]) -> Result<(), GroupError> {
+        self.update_pinned_messages(UpdatePinnedMessagesIntentData::new(
+            message_id.to_vec(),
+            false,
+        ))
+        .await
+    }
+
+    async fn update_pinned_messages(
+        &self,
+        intent_data: UpdatePinnedMessagesIntentData,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let intent = self.queue_intent(
+            &provider,
+            IntentKind::UpdatePinnedMessages,
+            intent_data.into(),
+        )?;
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The pinned messages of the group that are stored locally, oldest pin first
+    pub fn pinned_messages(&self) -> Result<Vec<StoredGroupMessage>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.pinned_group_messages(&self.group_id)?)
+    }
+}
+
+/// Parse the value of [`MetadataField::PinnedMessages`]
+pub(super) fn parse_pinned_message_ids(value: &str) -> Vec<Vec<u8>> {
+    value
+        .split(',')
+        .filter(|id| !id.is_empty())
+        .filter_map(|id| hex::decode(id).ok())
+        .collect()
+}
+
+fn encode_pinned_message_ids(message_ids: &[Vec<u8>]) -> String {
+    message_ids
+        .iter()
+        .map(hex::encode)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Apply `intent` to the pinned set of `group`. Returns `None` when the set would not change.
+pub(super) fn build_extensions_for_pinned_messages_update(
+    group: &OpenMlsGroup,
+    intent: UpdatePinnedMessagesIntentData,
+) -> Result<Option<Extensions>, GroupError> {
+    let existing_metadata: GroupMutableMetadata = group.try_into()?;
+    let mut pinned = existing_metadata.pinned_message_ids();
+    let is_pinned = pinned.contains(&intent.message_id);
+    match (intent.pin, is_pinned) {
+        (true, true) | (false, false) => return Ok(None),
+        (true, false) if pinned.len() >= MAX_PINNED_MESSAGES => {
+            return Err(GroupError::TooManyPinnedMessages(MAX_PINNED_MESSAGES))
+        }
+        (true, false) => pinned.push(intent.message_id),
+        (false, true) => pinned.retain(|id| id != &intent.message_id),
+    }
+    build_extensions_for_metadata_update(
+        group,
+        MetadataField::PinnedMessages.to_string(),
+        encode_pinned_message_ids(&pinned),
+    )
+    .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        configuration::MAX_PINNED_MESSAGES,
+        groups::{
+            intents::UpdatePinnedMessagesIntentData, GroupMetadataOptions, PreconfiguredPolicies,
+        },
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_pin_messages() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(
+                Some(PreconfiguredPolicies::AdminsOnly.to_policy_set()),
+                GroupMetadataOptions::default(),
+            )
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let first = amal_group.send_message(b"rules").await.unwrap();
+        let second = amal_group.send_message(b"schedule").await.unwrap();
+
+        amal_group.pin_message(&first).await.unwrap();
+        amal_group.pin_message(&second).await.unwrap();
+        amal_group.pin_message(&first).await.unwrap();
+        let pinned: Vec<_> = amal_group
+            .pinned_messages()
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(pinned, vec![first.clone(), second.clone()]);
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        assert_eq!(bola_group.pinned_messages().unwrap().len(), 2);
+
+        // Only admins can pin messages in admin only groups
+        bola_group
+            .unpin_message(&first)
+            .await
+            .expect_err("expected err");
+
+        amal_group.unpin_message(&first).await.unwrap();
+        bola_group.sync().await.unwrap();
+        let pinned: Vec<_> = bola_group
+            .pinned_messages()
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(pinned, vec![second]);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_pins_apply_to_the_current_set() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        let amal_message = amal_group.send_message(b"from amal").await.unwrap();
+        let bola_message = bola_group.send_message(b"from bola").await.unwrap();
+
+        // Amal pins without having seen the pin of Bola
+        bola_group.pin_message(&bola_message).await.unwrap();
+        amal_group.pin_message(&amal_message).await.unwrap();
+
+        bola_group.sync().await.unwrap();
+        for (client, group) in [(&amal, &amal_group), (&bola, &bola_group)] {
+            let provider = client.mls_provider().unwrap();
+            assert_eq!(
+                group
+                    .mutable_metadata(&provider)
+                    .unwrap()
+                    .pinned_message_ids(),
+                vec![bola_message.clone(), amal_message.clone()]
+            );
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_senders_enforce_the_pin_limit() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+
+        for id in 0..MAX_PINNED_MESSAGES as u8 {
+            amal_group
+                .update_pinned_messages(UpdatePinnedMessagesIntentData::new(vec![id], true))
+                .await
+                .unwrap();
+        }
+        // Bola pins against a full set, its intent fails before anything is published
+        bola_group.sync().await.unwrap();
+        let extra = bola_group.send_message(b"one too many").await.unwrap();
+        bola_group.pin_message(&extra).await.unwrap_err();
+
+        amal_group.sync().await.unwrap();
+        for (client, group) in [(&amal, &amal_group), (&bola, &bola_group)] {
+            let provider = client.mls_provider().unwrap();
+            let pinned = group
+                .mutable_metadata(&provider)
+                .unwrap()
+                .pinned_message_ids();
+            assert_eq!(pinned.len(), MAX_PINNED_MESSAGES);
+            assert!(!pinned.contains(&extra));
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Incoming group messages pass through a fixed sequence of stages:
+//!
+//! decode → dedupe → consent check → MLS validate → store → notify
+//!
+//! Envelopes received by a sync and by a stream go through the same stages. The notify stage
+//! sends a [`LocalEvents::MessageStored`] event for the message the envelope stored, and a
+//! stream then yields the stored message.
+//!
+//! Each stage is a separate function so that it can be tested on its own. Integrators can
+//! observe every stage with an [`EnvelopeInterceptor`], for instance to collect analytics or
+//! run custom spam heuristics. Interceptors are read-only, and cannot change the outcome of
+//! a stage.
+use std::sync::Arc;
+
+use openmls::{
+    framing::WireFormat,
+    prelude::{tls_codec::Deserialize, MlsMessageIn},
+};
+use xmtp_proto::xmtp::mls::api::v1::{
+    group_message::{Version as GroupMessageVersion, V1 as GroupMessageV1},
+    GroupMessage,
+};
+
+use super::mls_sync::GroupMessageProcessingError;
+use crate::{
+    storage::{
+        consent_record::{ConsentState, ConsentType},
+        group_message::StoredGroupMessage,
+        refresh_state::EntityKind,
+        DbConnection, StorageError,
+    },
+    subscriptions::{LocalEvents, MessageStored},
+};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    /// The envelope is parsed into an MLS message
+    Decode,
+    /// Envelopes at or before the group's cursor are skipped
+    Dedupe,
+    /// The consent state of the conversation is looked up
+    ConsentCheck,
+    /// The MLS message is decrypted and validated, and applied to the group
+    MlsValidate,
+    /// The message and the updated group state are committed to the database
+    Store,
+    /// Local listeners are told about the stored message
+    Notify,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageOutcome {
+    Passed,
+    /// The envelope was already processed, and the remaining stages are skipped
+    Skipped,
+    Failed(String),
+}
+
+impl StageOutcome {
+    fn from_result<T>(result: &Result<T, GroupMessageProcessingError>) -> Self {
+        match result {
+            Ok(_) => Self::Passed,
+            Err(GroupMessageProcessingError::AlreadyProcessed(_)) => Self::Skipped,
+            Err(e) => Self::Failed(e.to_string()),
+        }
+    }
+}
+
+/// What an [`EnvelopeInterceptor`] sees after each stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeEvent<'a> {
+    pub group_id: &'a [u8],
+    /// Cursor of the envelope, once it is decoded
+    pub cursor: Option<u64>,
+    /// Time in nanoseconds the envelope was received by the network, once it is decoded
+    pub created_ns: Option<u64>,
+    /// Consent state of the conversation, once it is checked
+    pub consent_state: Option<ConsentState>,
+    pub stage: PipelineStage,
+    pub outcome: StageOutcome,
+}
+
+/// Observes incoming envelopes as they move through the pipeline.
+///
+/// Interceptors are called inline while the envelope is processed, so they should return
+/// quickly and hand any expensive work off to another task.
+pub trait EnvelopeInterceptor: Send + Sync {
+    fn on_stage(&self, event: &EnvelopeEvent<'_>);
+}
+
+/// The interceptors registered on a client, called in the order they were added
+#[derive(Clone, Default)]
+pub struct EnvelopeInterceptors(Arc<Vec<Arc<dyn EnvelopeInterceptor>>>);
+
+impl std::fmt::Debug for EnvelopeInterceptors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvelopeInterceptors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+impl EnvelopeInterceptors {
+    pub fn push(&mut self, interceptor: Arc<dyn EnvelopeInterceptor>) {
+        Arc::make_mut(&mut self.0).push(interceptor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn notify(&self, event: &EnvelopeEvent<'_>) {
+        for interceptor in self.0.iter() {
+            interceptor.on_stage(event);
+        }
+    }
+}
+
+/// Tracks what is known about one envelope, and reports each stage to the interceptors
+pub(crate) struct EnvelopeTrace<'a> {
+    interceptors: &'a EnvelopeInterceptors,
+    group_id: &'a [u8],
+    cursor: Option<u64>,
+    created_ns: Option<u64>,
+    consent_state: Option<ConsentState>,
+    failed: bool,
+}
+
+impl<'a> EnvelopeTrace<'a> {
+    pub(crate) fn new(interceptors: &'a EnvelopeInterceptors, group_id: &'a [u8]) -> Self {
+        Self {
+            interceptors,
+            group_id,
+            cursor: None,
+            created_ns: None,
+            consent_state: None,
+            failed: false,
+        }
+    }
+
+    /// Report the result of `stage`, and pass the result through
+    pub(crate) fn record<T>(
+        &mut self,
+        stage: PipelineStage,
+        result: Result<T, GroupMessageProcessingError>,
+    ) -> Result<T, GroupMessageProcessingError> {
+        self.failed |= result.is_err();
+        if self.interceptors.is_empty() {
+            return result;
+        }
+        self.interceptors.notify(&EnvelopeEvent {
+            group_id: self.group_id,
+            cursor: self.cursor,
+            created_ns: self.created_ns,
+            consent_state: self.consent_state,
+            stage,
+            outcome: StageOutcome::from_result(&result),
+        });
+        result
+    }
+
+    /// Whether any stage recorded so far failed or was skipped
+    pub(crate) fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    pub(crate) fn decoded(&mut self, envelope: &DecodedEnvelope<'_>) {
+        self.cursor = Some(envelope.message.id);
+        self.created_ns = Some(envelope.message.created_ns);
+    }
+
+    pub(crate) fn consent_checked(&mut self, state: ConsentState) {
+        self.consent_state = Some(state);
+    }
+}
+
+pub(crate) struct DecodedEnvelope<'a> {
+    pub(crate) message: &'a GroupMessageV1,
+    pub(crate) entity_kind: EntityKind,
+}
+
+/// Decode stage: parse the envelope and check that it holds a supported MLS message
+pub(crate) fn decode(
+    envelope: &GroupMessage,
+) -> Result<DecodedEnvelope<'_>, GroupMessageProcessingError> {
+    match &envelope.version {
+        Some(GroupMessageVersion::V1(message)) => decode_v1(message),
+        _ => Err(GroupMessageProcessingError::InvalidPayload),
+    }
+}
+
+/// Decode stage for an envelope whose version was already unwrapped, as streams do
+pub(crate) fn decode_v1(
+    message: &GroupMessageV1,
+) -> Result<DecodedEnvelope<'_>, GroupMessageProcessingError> {
+    let mls_message_in = MlsMessageIn::tls_deserialize_exact(&message.data)?;
+    let entity_kind = match mls_message_in.wire_format() {
+        WireFormat::Welcome => EntityKind::Welcome,
+        _ => EntityKind::Group,
+    };
+
+    Ok(DecodedEnvelope {
+        message,
+        entity_kind,
+    })
+}
+
+/// Dedupe stage: skip envelopes at or before the last one processed for the group
+pub(crate) fn dedupe(
+    conn: &DbConnection,
+    group_id: &[u8],
+    envelope: &DecodedEnvelope<'_>,
+) -> Result<(), GroupMessageProcessingError> {
+    let last_cursor = conn.get_last_cursor_for_id(group_id, envelope.entity_kind)?;
+    if last_cursor >= envelope.message.id as i64 {
+        tracing::info!(
+            group_id = hex::encode(group_id),
+            "Message already processed: skipped msgId:[{}] entity kind:[{:?}] last cursor in db: [{}]",
+            envelope.message.id,
+            envelope.entity_kind,
+            last_cursor
+        );
+        return Err(GroupMessageProcessingError::AlreadyProcessed(
+            envelope.message.id,
+        ));
+    }
+    Ok(())
+}
+
+/// Consent check stage: look up whether the user has allowed or denied the conversation.
+///
+/// Envelopes are processed regardless of consent, since skipping commits would leave the
+/// group's MLS state behind. The consent state is passed on to interceptors instead.
+pub(crate) fn consent_check(
+    conn: &DbConnection,
+    group_id: &[u8],
+) -> Result<ConsentState, StorageError> {
+    let record = conn.get_consent_record(hex::encode(group_id), ConsentType::ConversationId)?;
+    Ok(record
+        .map(|record| record.state)
+        .unwrap_or(ConsentState::Unknown))
+}
+
+/// Notify stage: send a [`LocalEvents::MessageStored`] event for the message stored from the
+/// envelope, and return it. Commits without a transcript message store nothing to notify about.
+pub(crate) fn notify(
+    conn: &DbConnection,
+    local_events: &broadcast::Sender<LocalEvents>,
+    envelope: &GroupMessageV1,
+) -> Result<Option<StoredGroupMessage>, GroupMessageProcessingError> {
+    let stored =
+        conn.get_group_message_by_timestamp(&envelope.group_id, envelope.created_ns as i64)?;
+    if let Some(message) = &stored {
+        // nobody listening is fine
+        let _ = local_events.send(LocalEvents::MessageStored(MessageStored {
+            group_id: envelope.group_id.clone(),
+            message_id: message.id.clone(),
+            cursor: envelope.id,
+        }));
+    }
+    Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::{consent_record::StoredConsentRecord, encrypted_store::tests::with_connection},
+    };
+    use parking_lot::Mutex;
+    use prost::Message;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[derive(Default)]
+    struct RecordingInterceptor {
+        stages: Mutex<Vec<(PipelineStage, StageOutcome)>>,
+    }
+
+    impl EnvelopeInterceptor for RecordingInterceptor {
+        fn on_stage(&self, event: &EnvelopeEvent<'_>) {
+            self.stages
+                .lock()
+                .push((event.stage, event.outcome.clone()));
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decode_rejects_empty_envelopes() {
+        let result = decode(&GroupMessage { version: None });
+        assert!(matches!(
+            result,
+            Err(GroupMessageProcessingError::InvalidPayload)
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn consent_check_defaults_to_unknown() {
+        with_connection(|conn| {
+            let group_id = vec![1, 2, 3];
+            assert_eq!(
+                consent_check(conn, &group_id).unwrap(),
+                ConsentState::Unknown
+            );
+
+            conn.insert_or_replace_consent_records(&[StoredConsentRecord::new(
+                ConsentType::ConversationId,
+                ConsentState::Denied,
+                hex::encode(&group_id),
+            )])
+            .unwrap();
+            assert_eq!(
+                consent_check(conn, &group_id).unwrap(),
+                ConsentState::Denied
+            );
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn interceptors_observe_every_stage() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let mut bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let interceptor = Arc::new(RecordingInterceptor::default());
+        bola.envelope_interceptors.push(interceptor.clone());
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.find_groups(Default::default()).unwrap().remove(0);
+
+        let mut events = bola.local_events().subscribe();
+        amal_group.send_message(b"hello").await.unwrap();
+        bola_group.sync().await.unwrap();
+
+        let stages = interceptor.stages.lock();
+        let expected = [
+            PipelineStage::Decode,
+            PipelineStage::Dedupe,
+            PipelineStage::ConsentCheck,
+            PipelineStage::MlsValidate,
+            PipelineStage::Store,
+            PipelineStage::Notify,
+        ];
+        assert_eq!(
+            stages
+                .iter()
+                .rev()
+                .take(expected.len())
+                .rev()
+                .map(|(stage, _)| *stage)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(stages
+            .iter()
+            .rev()
+            .take(expected.len())
+            .all(|(_, outcome)| *outcome == StageOutcome::Passed));
+        drop(stages);
+        let mut stored_ids = || {
+            std::iter::from_fn(|| events.try_recv().ok())
+                .filter_map(|event| match event {
+                    LocalEvents::MessageStored(stored) => Some(stored.message_id),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let hello = bola_group
+            .find_messages(&Default::default())
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert!(stored_ids().contains(&hello.id));
+
+        // streamed envelopes go through the same stages
+        amal_group.send_message(b"streamed").await.unwrap();
+        let envelope = bola
+            .api_client
+            .query_group_messages(bola_group.group_id.clone(), None)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        interceptor.stages.lock().clear();
+        let streamed = bola_group
+            .process_streamed_group_message(envelope.encode_to_vec())
+            .await
+            .unwrap();
+        assert_eq!(stored_ids(), vec![streamed.id]);
+        let stages = interceptor.stages.lock();
+        assert_eq!(
+            stages.iter().map(|(stage, _)| *stage).collect::<Vec<_>>(),
+            expected
+        );
+        assert!(stages
+            .iter()
+            .all(|(_, outcome)| *outcome == StageOutcome::Passed));
+    }
+}
@@ -0,0 +1,324 @@
+//! Polls sent with the [`PollCodec`] and voted on with the [`PollVoteCodec`].
+//!
+//! Polls and votes are written to their own tables as the messages are stored, so that results
+//! can be read without decoding every vote. Every installation applies the same rules, so all
+//! SDKs show identical results:
+//! - Only the latest vote of each inbox counts, ordered by sent time and then message id
+//! - A vote without any options withdraws the inbox's vote
+//! - Votes for options that do not exist, or for several options on a single choice poll,
+//!   are ignored
+//! - Votes that arrive before their poll are kept aside and checked by the same rules once the
+//!   poll arrives
+use prost::Message;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    poll::{Poll, PollCodec, PollVote, PollVoteCodec},
+    ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group_message::{ContentType, StoredGroupMessage},
+        poll::{StoredOrphanPollVote, StoredPoll, StoredPollVote},
+        NotFound, StorageError,
+    },
+    StoreOrIgnore,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollOptionResult {
+    pub option: String,
+    pub votes: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollResults {
+    pub poll_id: Vec<u8>,
+    pub question: String,
+    pub allow_multiple_choices: bool,
+    /// Results of each option, in the order the options were listed in the poll
+    pub options: Vec<PollOptionResult>,
+    /// Number of inboxes with a counted vote
+    pub voters: u32,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send a poll to the group. Returns the id of the poll message.
+    pub async fn create_poll(
+        &self,
+        question: String,
+        options: Vec<String>,
+        allow_multiple_choices: bool,
+    ) -> Result<Vec<u8>, GroupError> {
+        if options.is_empty() {
+            return Err(GroupError::Generic(
+                "a poll needs at least one option".to_string(),
+            ));
+        }
+        let encoded = PollCodec::encode(Poll {
+            question,
+            options,
+            allow_multiple_choices,
+        })
+        .map_err(|e| GroupError::Generic(e.to_string()))?;
+        self.send_message(&encoded_content_to_bytes(encoded)).await
+    }
+
+    /// Vote on a poll, replacing any earlier vote of this inbox.
+    /// An empty list of options withdraws the vote.
+    pub async fn vote(&self, poll_id: &[u8], option_indexes: Vec<u32>) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        let poll = conn
+            .get_poll(poll_id)?
+            .filter(|poll| poll.group_id == self.group_id)
+            .ok_or_else(|| NotFound::PollById(poll_id.to_vec()))?;
+        if !poll.accepts(&option_indexes) {
+            return Err(GroupError::Generic(format!(
+                "invalid vote {:?} on poll {}",
+                option_indexes,
+                hex::encode(poll_id)
+            )));
+        }
+
+        let encoded = PollVoteCodec::encode(PollVote {
+            poll_reference: hex::encode(poll_id),
+            option_indexes,
+        })
+        .map_err(|e| GroupError::Generic(e.to_string()))?;
+        self.send_message(&encoded_content_to_bytes(encoded))
+            .await?;
+        Ok(())
+    }
+
+    /// Count the votes on a poll in this group
+    pub fn poll_results(&self, poll_id: &[u8]) -> Result<PollResults, GroupError> {
+        let conn = self.context().store().conn()?;
+        let poll = conn
+            .get_poll(poll_id)?
+            .filter(|poll| poll.group_id == self.group_id)
+            .ok_or_else(|| NotFound::PollById(poll_id.to_vec()))?;
+
+        let mut options: Vec<PollOptionResult> = poll
+            .options()
+            .into_iter()
+            .map(|option| PollOptionResult { option, votes: 0 })
+            .collect();
+        let mut voters = 0;
+        for vote in conn.get_poll_votes(poll_id)? {
+            let option_indexes = vote.option_indexes();
+            // A vote without options is a withdrawn vote
+            if option_indexes.is_empty() || !poll.accepts(&option_indexes) {
+                continue;
+            }
+            voters += 1;
+            for index in option_indexes {
+                options[index as usize].votes += 1;
+            }
+        }
+
+        Ok(PollResults {
+            poll_id: poll.id,
+            question: poll.question,
+            allow_multiple_choices: poll.allow_multiple_choices,
+            options,
+            voters,
+        })
+    }
+}
+
+/// Record polls and votes from a stored message. Malformed polls and votes are skipped, so
+/// that they do not block the rest of the group's messages.
+pub(crate) fn record_poll_content(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<(), StorageError> {
+    if !matches!(
+        message.content_type,
+        ContentType::Poll | ContentType::PollVote
+    ) {
+        return Ok(());
+    }
+    let Ok(content) = EncodedContent::decode(message.decrypted_message_bytes.as_slice()) else {
+        return Ok(());
+    };
+
+    match message.content_type {
+        ContentType::Poll => {
+            let Ok(poll) = PollCodec::decode(content) else {
+                tracing::warn!("skipping malformed poll");
+                return Ok(());
+            };
+            if poll.options.is_empty() {
+                return Ok(());
+            }
+            let poll = StoredPoll {
+                id: message.id.clone(),
+                group_id: message.group_id.clone(),
+                creator_inbox_id: message.sender_inbox_id.clone(),
+                question: poll.question,
+                options: serde_json::to_string(&poll.options).unwrap_or_default(),
+                allow_multiple_choices: poll.allow_multiple_choices,
+                created_at_ns: message.sent_at_ns,
+            };
+            poll.store_or_ignore(conn)?;
+            // Votes delivered before the poll are validated now, by the same rules as votes
+            // that arrive after it
+            for orphan in conn.take_orphan_poll_votes(&poll.id)? {
+                let vote = StoredPollVote::from(orphan.clone());
+                if orphan.group_id == poll.group_id && poll.accepts(&vote.option_indexes()) {
+                    conn.record_poll_vote(&vote)?;
+                }
+            }
+            Ok(())
+        }
+        ContentType::PollVote => {
+            let Some((poll_id, vote)) = PollVoteCodec::decode(content)
+                .ok()
+                .and_then(|vote| Some((hex::decode(&vote.poll_reference).ok()?, vote)))
+            else {
+                tracing::warn!("skipping malformed poll vote");
+                return Ok(());
+            };
+            let stored_vote = StoredPollVote::new(
+                poll_id,
+                message.sender_inbox_id.clone(),
+                message.id.clone(),
+                message.sent_at_ns,
+                &vote.option_indexes,
+            );
+            match conn.get_poll(&stored_vote.poll_id)? {
+                Some(poll) => {
+                    if poll.group_id == message.group_id && poll.accepts(&vote.option_indexes) {
+                        conn.record_poll_vote(&stored_vote)?;
+                    }
+                }
+                // Out of order delivery, kept until the poll arrives
+                None => StoredOrphanPollVote::new(stored_vote, message.group_id.clone())
+                    .store_or_ignore(conn)?,
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_poll_votes_are_tallied() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        let poll_id = amal_group
+            .create_poll(
+                "Lunch?".to_string(),
+                vec!["Pizza".to_string(), "Sushi".to_string()],
+                false,
+            )
+            .await
+            .unwrap();
+        amal_group.vote(&poll_id, vec![0]).await.unwrap();
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        // Only one option can be picked
+        assert!(bola_group.vote(&poll_id, vec![0, 1]).await.is_err());
+        bola_group.vote(&poll_id, vec![0]).await.unwrap();
+        // Bola changes their mind
+        bola_group.vote(&poll_id, vec![1]).await.unwrap();
+
+        amal_group.sync().await.unwrap();
+        let results = amal_group.poll_results(&poll_id).unwrap();
+        assert_eq!(results.question, "Lunch?");
+        assert_eq!(results.voters, 2);
+        assert_eq!(
+            results
+                .options
+                .iter()
+                .map(|option| option.votes)
+                .collect::<Vec<_>>(),
+            vec![1, 1]
+        );
+        assert_eq!(bola_group.poll_results(&poll_id).unwrap(), results);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_votes_before_the_poll_are_counted() {
+        use super::record_poll_content;
+        use crate::storage::{
+            encrypted_store::{group::tests::generate_group, tests::with_connection},
+            group_message::{tests::generate_message, ContentType},
+        };
+        use crate::Store;
+        use xmtp_content_types::{
+            encoded_content_to_bytes,
+            poll::{Poll, PollCodec, PollVote, PollVoteCodec},
+            ContentCodec,
+        };
+
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let mut poll =
+                generate_message(None, Some(&group.id), Some(100), Some(ContentType::Poll));
+            poll.decrypted_message_bytes = encoded_content_to_bytes(
+                PollCodec::encode(Poll {
+                    question: "Lunch?".to_string(),
+                    options: vec!["Pizza".to_string(), "Sushi".to_string()],
+                    allow_multiple_choices: false,
+                })
+                .unwrap(),
+            );
+            let vote = |voter: &str, option_indexes: Vec<u32>| {
+                let mut vote = generate_message(
+                    None,
+                    Some(&group.id),
+                    Some(200),
+                    Some(ContentType::PollVote),
+                );
+                vote.sender_inbox_id = voter.to_string();
+                vote.decrypted_message_bytes = encoded_content_to_bytes(
+                    PollVoteCodec::encode(PollVote {
+                        poll_reference: hex::encode(&poll.id),
+                        option_indexes,
+                    })
+                    .unwrap(),
+                );
+                vote
+            };
+
+            // Both votes are delivered before the poll, and only one of them is valid
+            record_poll_content(conn, &vote("bola", vec![1])).unwrap();
+            record_poll_content(conn, &vote("caro", vec![0, 1])).unwrap();
+            assert!(conn.get_poll_votes(&poll.id).unwrap().is_empty());
+
+            record_poll_content(conn, &poll).unwrap();
+            let votes = conn.get_poll_votes(&poll.id).unwrap();
+            assert_eq!(votes.len(), 1);
+            assert_eq!(votes[0].voter_inbox_id, "bola");
+            assert_eq!(votes[0].option_indexes(), vec![1]);
+            assert!(conn.take_orphan_poll_votes(&poll.id).unwrap().is_empty());
+        })
+        .await
+    }
+}
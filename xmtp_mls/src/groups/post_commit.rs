@@ -0,0 +1,93 @@
+//! Worker taking the post commit actions left pending by a previous session, see
+//! [`crate::storage::post_commit_action`].
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::disappearing_messages::WORKER_RESTART_DELAY;
+use crate::{
+    client::ClientError,
+    diagnostics::WorkerKind,
+    storage::{post_commit_action::COMPLETED_ACTION_RETENTION_NS, StorageError},
+    Client,
+};
+
+pub struct PostCommitActionsWorker<ApiClient, V> {
+    client: Client<ApiClient, V>,
+}
+
+impl<ApiClient, V> PostCommitActionsWorker<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    pub fn new(client: Client<ApiClient, V>) -> Self {
+        Self { client }
+    }
+
+    pub(crate) fn spawn_worker(self) {
+        crate::spawn(None, async move {
+            let inbox_id = self.client.inbox_id().to_string();
+            let installation_id = hex::encode(self.client.installation_public_key());
+            let workers = self.client.workers.clone();
+            workers.started(WorkerKind::PostCommitActions);
+            while let Err(err) = self.run().await {
+                match err {
+                    ClientError::Storage(StorageError::PoolNeedsConnection) => {
+                        tracing::warn!(
+                            inbox_id,
+                            installation_id,
+                            "Pool disconnected. task will restart on reconnect"
+                        );
+                        break;
+                    }
+                    _ => {
+                        tracing::error!(
+                            inbox_id,
+                            installation_id,
+                            "post commit action error {err}"
+                        );
+                        workers.failed(WorkerKind::PostCommitActions, &err);
+                        xmtp_common::time::sleep(WORKER_RESTART_DELAY).await;
+                        workers.started(WorkerKind::PostCommitActions);
+                    }
+                }
+            }
+            workers.stopped(WorkerKind::PostCommitActions);
+        });
+    }
+
+    /// Take the pending actions of every group, then prune the old completed ones
+    async fn run(&self) -> Result<(), ClientError> {
+        let group_ids = self
+            .client
+            .store()
+            .conn()?
+            .groups_with_pending_post_commit_actions()?;
+        for group_id in group_ids {
+            let group = self.client.group(group_id)?;
+            group.resume_post_commit_actions().await?;
+        }
+        let conn = self.client.store().conn()?;
+        let deleted =
+            conn.delete_completed_post_commit_actions(now_ns() - COMPLETED_ACTION_RETENTION_NS)?;
+        tracing::debug!("deleted {deleted} completed post commit actions");
+        Ok(())
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Start the worker resuming interrupted post commit actions. It exits once they are all taken.
+    pub fn start_post_commit_actions_worker(&self) {
+        tracing::trace!(
+            inbox_id = self.inbox_id(),
+            installation_id = hex::encode(self.installation_public_key()),
+            "starting post commit actions worker"
+        );
+        PostCommitActionsWorker::new(self.clone()).spawn_worker();
+    }
+}
@@ -0,0 +1,119 @@
+//! Reactions sent with the [`ReactionCodec`].
+//!
+//! Reactions are written to their own table as the messages are stored, see
+//! [`StoredReaction`]. Only the latest reaction of each inbox with each content on a message
+//! counts, so a reaction can be removed by sending the same content with
+//! [`ReactionAction::Removed`], and added again later.
+use xmtp_content_types::{encoded_content_to_bytes, reaction::ReactionCodec, ContentCodec};
+use xmtp_proto::xmtp::mls::message_contents::content_types::{
+    ReactionAction, ReactionSchema, ReactionV2,
+};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{message_reaction::StoredReaction, NotFound};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Add a reaction with `content` to a message of this group, or remove it if this inbox
+    /// already reacted with `content`. Returns whether the reaction is now added.
+    pub async fn toggle_reaction(
+        &self,
+        message_id: &[u8],
+        content: String,
+    ) -> Result<bool, GroupError> {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| NotFound::MessageById(message_id.to_vec()))?;
+        let added = conn
+            .get_reaction(message_id, self.client.inbox_id(), &content)?
+            .is_some_and(|reaction| !reaction.removed);
+
+        let action = if added {
+            ReactionAction::Removed
+        } else {
+            ReactionAction::Added
+        };
+        let encoded = ReactionCodec::encode(ReactionV2 {
+            reference: hex::encode(message_id),
+            reference_inbox_id: message.sender_inbox_id,
+            action: action as i32,
+            content,
+            schema: ReactionSchema::Unicode as i32,
+        })
+        .map_err(|e| GroupError::Generic(e.to_string()))?;
+        self.send_message(&encoded_content_to_bytes(encoded))
+            .await?;
+        Ok(!added)
+    }
+
+    /// The reactions currently on a message of this group, oldest first
+    pub fn reactions(&self, message_id: &[u8]) -> Result<Vec<StoredReaction>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_reactions(&[message_id])?
+            .into_iter()
+            .filter(|reaction| reaction.group_id == self.group_id)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group_message::MsgQueryArgs,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_toggle_reaction() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        let message_id = amal_group.send_message(b"hello").await.unwrap();
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        assert!(bola_group
+            .toggle_reaction(&message_id, "👍".to_string())
+            .await
+            .unwrap());
+        assert!(amal_group
+            .toggle_reaction(&message_id, "👍".to_string())
+            .await
+            .unwrap());
+        // Bola takes their reaction back
+        assert!(!bola_group
+            .toggle_reaction(&message_id, "👍".to_string())
+            .await
+            .unwrap());
+
+        amal_group.sync().await.unwrap();
+        let reactions = amal_group.reactions(&message_id).unwrap();
+        assert_eq!(reactions.len(), 1);
+        assert_eq!(reactions[0].reactor_inbox_id, amal.inbox_id());
+
+        let messages = amal_group
+            .find_messages_with_reactions(&MsgQueryArgs::default())
+            .unwrap();
+        let hello = messages
+            .iter()
+            .find(|m| m.message.id == message_id)
+            .unwrap();
+        assert_eq!(hello.reactions.len(), 1);
+        assert_eq!(hello.reactions[0].sender_inbox_id, amal.inbox_id());
+    }
+}
@@ -0,0 +1,164 @@
+//! Automatic expiry of message requests, see [`RequestExpiry`].
+//!
+//! Conversations that are still waiting for the user's consent after
+//! [`RequestExpiry::after`] are denied, and with [`RequestExpiryAction::Delete`] they are deleted
+//! from this installation, so that abandoned spam does not pile up in the requests inbox. The new consent
+//! is emitted on the consent stream and synced to the other installations like any other
+//! change.
+use std::time::Duration;
+
+use crate::{
+    client::ClientError,
+    groups::{device_sync::preference_sync::UserPreferenceUpdate, MlsGroup},
+    storage::consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+    subscriptions::LocalEvents,
+    Client,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+/// What happens to a request once it expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RequestExpiryAction {
+    /// Deny the conversation
+    #[default]
+    Deny,
+    /// Deny the conversation and delete it with everything stored for it, keeping the denied
+    /// consent
+    Delete,
+}
+
+/// How long conversations may wait for the user's consent, configured with
+/// [`crate::builder::ClientBuilder::request_expiry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestExpiry {
+    pub after: Duration,
+    pub action: RequestExpiryAction,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Apply the configured [`RequestExpiry`] to the conversations that were created before
+    /// `now_ns` minus [`RequestExpiry::after`] and are still waiting for consent. Returns the ids
+    /// of the expired conversations. Does nothing if request expiry is not configured.
+    pub fn expire_requests(&self, now_ns: i64) -> Result<Vec<Vec<u8>>, ClientError> {
+        let Some(expiry) = self.request_expiry else {
+            return Ok(vec![]);
+        };
+        let conn = self.store().conn()?;
+        let requests = conn.stale_requests(now_ns - expiry.after.as_nanos() as i64)?;
+        if requests.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let records: Vec<_> = requests
+            .iter()
+            .map(|group| {
+                StoredConsentRecord::new(
+                    ConsentType::ConversationId,
+                    ConsentState::Denied,
+                    hex::encode(&group.id),
+                )
+            })
+            .collect();
+        conn.insert_or_replace_consent_records(&records)?;
+        if expiry.action == RequestExpiryAction::Delete {
+            let provider = self.mls_provider()?;
+            for group in &requests {
+                MlsGroup::new(self.clone(), group.id.clone(), group.created_at_ns)
+                    .delete_locally(&provider)?;
+            }
+        }
+        tracing::info!(
+            count = requests.len(),
+            action = ?expiry.action,
+            "expired message requests"
+        );
+
+        let _ = self
+            .local_events
+            .send(LocalEvents::OutgoingPreferenceUpdates(
+                records
+                    .into_iter()
+                    .map(UserPreferenceUpdate::ConsentUpdate)
+                    .collect(),
+            ));
+        Ok(requests.into_iter().map(|group| group.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        storage::encrypted_store::{
+            group::tests::{generate_group, generate_group_with_created_at},
+            group_message::tests::generate_message,
+        },
+        Store,
+    };
+    use xmtp_common::time::now_ns;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_stale_requests_are_denied_and_deleted() {
+        let mut alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        alix.request_expiry = Some(RequestExpiry {
+            after: Duration::from_secs(60),
+            action: RequestExpiryAction::Delete,
+        });
+        let conn = alix.store().conn().unwrap();
+        let now = now_ns();
+        let stale = generate_group_with_created_at(None, now - 120_000_000_000);
+        let fresh = generate_group(None);
+        let allowed = generate_group_with_created_at(None, now - 120_000_000_000);
+        for group in [&stale, &fresh, &allowed] {
+            group.store(&conn).unwrap();
+            generate_message(None, Some(&group.id), None, None)
+                .store(&conn)
+                .unwrap();
+        }
+        StoredConsentRecord::new(
+            ConsentType::ConversationId,
+            ConsentState::Allowed,
+            hex::encode(&allowed.id),
+        )
+        .store(&conn)
+        .unwrap();
+        let mut events = alix.local_events.subscribe();
+
+        assert_eq!(alix.expire_requests(now).unwrap(), vec![stale.id.clone()]);
+        assert!(alix.expire_requests(now).unwrap().is_empty());
+
+        let consent = conn
+            .get_consent_record(hex::encode(&stale.id), ConsentType::ConversationId)
+            .unwrap()
+            .unwrap();
+        assert_eq!(consent.state, ConsentState::Denied);
+        assert!(conn.find_group(&stale.id).unwrap().is_none());
+        for (group, messages) in [(&stale, 0), (&fresh, 1), (&allowed, 1)] {
+            assert_eq!(
+                conn.get_group_messages(&group.id, &Default::default())
+                    .unwrap()
+                    .len(),
+                messages
+            );
+        }
+        let Ok(LocalEvents::OutgoingPreferenceUpdates(updates)) = events.try_recv() else {
+            panic!("expected the denied request to be emitted");
+        };
+        assert!(matches!(
+            &updates[..],
+            [UserPreferenceUpdate::ConsentUpdate(record)]
+                if record.entity == hex::encode(&stale.id) && record.state == ConsentState::Denied
+        ));
+    }
+}
@@ -1,19 +1,24 @@
+use super::enrichers::MessageEnrichers;
 use super::group_membership::{GroupMembership, MembershipDiff};
+use super::outbound::OutboundInterceptors;
+use super::pipeline::EnvelopeInterceptors;
 use crate::{
     api::ApiClientWrapper,
     client::{ClientError, XmtpMlsLocalContext},
+    data_mode::DataMode,
     identity_updates::{InstallationDiff, InstallationDiffError},
     storage::{
         xmtp_openmls_provider::XmtpOpenMlsProvider, DbConnection, EncryptedMessageStore,
         StorageError,
     },
-    subscriptions::LocalEvents,
+    subscriptions::{burst::BurstLimiter, dedupe::StreamDedupe, LocalEvents},
     types::InstallationId,
     verified_key_package_v2::VerifiedKeyPackageV2,
     Client,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::sync::broadcast;
+use xmtp_common::RetryPolicies;
 use xmtp_id::{
     associations::AssociationState, scw_verifier::SmartContractSignatureVerifier, InboxIdRef,
 };
@@ -35,6 +40,26 @@ pub trait LocalScopedGroupClient: Send + Sync + Sized {
 
     fn history_sync_url(&self) -> &Option<String>;
 
+    fn retry_policies(&self) -> &RetryPolicies;
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors;
+
+    fn message_enrichers(&self) -> &MessageEnrichers;
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors;
+
+    fn max_group_size(&self) -> usize;
+
+    fn data_mode(&self) -> DataMode;
+
+    fn stream_dedupe(&self) -> &StreamDedupe;
+
+    fn stream_bursts(&self) -> &BurstLimiter;
+
+    fn stream_silence_window(&self) -> Option<Duration>;
+
+    fn key_rotation_interval(&self) -> Duration;
+
     fn inbox_id(&self) -> InboxIdRef<'_> {
         self.context_ref().inbox_id()
     }
@@ -101,6 +126,26 @@ pub trait ScopedGroupClient: Sized {
 
     fn history_sync_url(&self) -> &Option<String>;
 
+    fn retry_policies(&self) -> &RetryPolicies;
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors;
+
+    fn message_enrichers(&self) -> &MessageEnrichers;
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors;
+
+    fn max_group_size(&self) -> usize;
+
+    fn data_mode(&self) -> DataMode;
+
+    fn stream_dedupe(&self) -> &StreamDedupe;
+
+    fn stream_bursts(&self) -> &BurstLimiter;
+
+    fn stream_silence_window(&self) -> Option<Duration>;
+
+    fn key_rotation_interval(&self) -> Duration;
+
     fn inbox_id(&self) -> InboxIdRef<'_> {
         self.context_ref().inbox_id()
     }
@@ -175,6 +220,46 @@ where
         &self.history_sync_url
     }
 
+    fn retry_policies(&self) -> &RetryPolicies {
+        &self.retry_policies
+    }
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors {
+        &self.envelope_interceptors
+    }
+
+    fn message_enrichers(&self) -> &MessageEnrichers {
+        &self.message_enrichers
+    }
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors {
+        &self.outbound_interceptors
+    }
+
+    fn max_group_size(&self) -> usize {
+        self.max_group_size
+    }
+
+    fn data_mode(&self) -> DataMode {
+        self.data_mode.get()
+    }
+
+    fn stream_dedupe(&self) -> &StreamDedupe {
+        &self.stream_dedupe
+    }
+
+    fn stream_bursts(&self) -> &BurstLimiter {
+        &self.stream_bursts
+    }
+
+    fn stream_silence_window(&self) -> Option<Duration> {
+        self.stream_silence_window
+    }
+
+    fn key_rotation_interval(&self) -> Duration {
+        self.key_rotation_interval
+    }
+
     async fn get_installation_diff(
         &self,
         conn: &DbConnection,
@@ -254,6 +339,46 @@ where
         (**self).history_sync_url()
     }
 
+    fn retry_policies(&self) -> &RetryPolicies {
+        (**self).retry_policies()
+    }
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors {
+        (**self).envelope_interceptors()
+    }
+
+    fn message_enrichers(&self) -> &MessageEnrichers {
+        (**self).message_enrichers()
+    }
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors {
+        (**self).outbound_interceptors()
+    }
+
+    fn max_group_size(&self) -> usize {
+        (**self).max_group_size()
+    }
+
+    fn data_mode(&self) -> DataMode {
+        (**self).data_mode()
+    }
+
+    fn stream_dedupe(&self) -> &StreamDedupe {
+        (**self).stream_dedupe()
+    }
+
+    fn stream_bursts(&self) -> &BurstLimiter {
+        (**self).stream_bursts()
+    }
+
+    fn stream_silence_window(&self) -> Option<Duration> {
+        (**self).stream_silence_window()
+    }
+
+    fn key_rotation_interval(&self) -> Duration {
+        (**self).key_rotation_interval()
+    }
+
     fn store(&self) -> &EncryptedMessageStore {
         (**self).store()
     }
@@ -348,6 +473,46 @@ where
         (**self).history_sync_url()
     }
 
+    fn retry_policies(&self) -> &RetryPolicies {
+        (**self).retry_policies()
+    }
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors {
+        (**self).envelope_interceptors()
+    }
+
+    fn message_enrichers(&self) -> &MessageEnrichers {
+        (**self).message_enrichers()
+    }
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors {
+        (**self).outbound_interceptors()
+    }
+
+    fn max_group_size(&self) -> usize {
+        (**self).max_group_size()
+    }
+
+    fn data_mode(&self) -> DataMode {
+        (**self).data_mode()
+    }
+
+    fn stream_dedupe(&self) -> &StreamDedupe {
+        (**self).stream_dedupe()
+    }
+
+    fn stream_bursts(&self) -> &BurstLimiter {
+        (**self).stream_bursts()
+    }
+
+    fn stream_silence_window(&self) -> Option<Duration> {
+        (**self).stream_silence_window()
+    }
+
+    fn key_rotation_interval(&self) -> Duration {
+        (**self).key_rotation_interval()
+    }
+
     fn inbox_id(&self) -> InboxIdRef<'_> {
         (**self).inbox_id()
     }
@@ -439,6 +604,46 @@ where
         (**self).history_sync_url()
     }
 
+    fn retry_policies(&self) -> &RetryPolicies {
+        (**self).retry_policies()
+    }
+
+    fn envelope_interceptors(&self) -> &EnvelopeInterceptors {
+        (**self).envelope_interceptors()
+    }
+
+    fn message_enrichers(&self) -> &MessageEnrichers {
+        (**self).message_enrichers()
+    }
+
+    fn outbound_interceptors(&self) -> &OutboundInterceptors {
+        (**self).outbound_interceptors()
+    }
+
+    fn max_group_size(&self) -> usize {
+        (**self).max_group_size()
+    }
+
+    fn data_mode(&self) -> DataMode {
+        (**self).data_mode()
+    }
+
+    fn stream_dedupe(&self) -> &StreamDedupe {
+        (**self).stream_dedupe()
+    }
+
+    fn stream_bursts(&self) -> &BurstLimiter {
+        (**self).stream_bursts()
+    }
+
+    fn stream_silence_window(&self) -> Option<Duration> {
+        (**self).stream_silence_window()
+    }
+
+    fn key_rotation_interval(&self) -> Duration {
+        (**self).key_rotation_interval()
+    }
+
     fn inbox_id(&self) -> InboxIdRef<'_> {
         (**self).inbox_id()
     }
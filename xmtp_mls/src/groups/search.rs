@@ -0,0 +1,87 @@
+//! Searching messages and sorting conversations by name, ignoring case and accents, see
+//! [`collation`](crate::storage::encrypted_store::collation) and
+//! [`group_name`](crate::storage::encrypted_store::group_name).
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    storage::{
+        group::{GroupQueryArgs, GroupSortKey},
+        group_message::StoredGroupMessage,
+    },
+    Client,
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Text messages of this group that contain `query`, ignoring case and accents, newest
+    /// first
+    pub fn search_messages(
+        &self,
+        query: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<StoredGroupMessage>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.search_text_messages(Some(&self.group_id), query, limit)?)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Like [`Client::find_groups`], sorted by group name ignoring case and accents. Groups
+    /// without a name come last, oldest first. The sort and the limit are applied by the store.
+    pub fn find_groups_by_name(
+        &self,
+        args: GroupQueryArgs,
+    ) -> Result<Vec<MlsGroup<Self>>, ClientError> {
+        self.find_groups(args.sort_by(GroupSortKey::Name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group::GroupQueryArgs,
+    };
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_search_and_sort_ignore_accents() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let mut groups = Vec::new();
+        for name in ["zoo", "Élan", "apple"] {
+            let group = amal
+                .create_group(
+                    None,
+                    GroupMetadataOptions {
+                        name: Some(name.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            groups.push(group);
+        }
+
+        let provider = amal.mls_provider().unwrap();
+        let names: Vec<_> = amal
+            .find_groups_by_name(GroupQueryArgs::default())
+            .unwrap()
+            .iter()
+            .map(|group| group.group_name(&provider).unwrap())
+            .collect();
+        assert_eq!(names, ["apple", "Élan", "zoo"]);
+
+        let content = TextCodec::encode("Déjà vu".to_string()).unwrap();
+        groups[0]
+            .send_message(&encoded_content_to_bytes(content))
+            .await
+            .unwrap();
+        assert_eq!(groups[0].search_messages("DEJA", None).unwrap().len(), 1);
+        assert!(groups[1].search_messages("deja", None).unwrap().is_empty());
+    }
+}
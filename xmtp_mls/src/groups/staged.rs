@@ -0,0 +1,286 @@
+//! Creation of a group together with its first message.
+//!
+//! [`Client::create_group_with_message`] stores the group as [`GroupLifecycleState::Staged`], so
+//! it is neither listed nor announced to streams while its members are added and the message is
+//! queued. The group becomes `Active` once both succeeded. If adding the members fails before
+//! their commit is published, the group is deleted with everything stored for it, including its
+//! OpenMLS state, so that observers never see an empty group.
+//!
+//! Once the commit is published the invitees have the group, so it is kept whatever happens
+//! next. The first message is queued before the group is activated, and a message that fails to
+//! publish stays queued and is sent by the next sync of the group, like any other unpublished
+//! message. Groups left staged by a crash are recovered the same way when the client is built,
+//! see [`Client::recover_staged_groups`].
+use std::sync::Arc;
+
+use openmls_traits::OpenMlsProvider;
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxId};
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{
+    group_permissions::PolicySet, GroupError, GroupMetadataOptions, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    client::ClientError,
+    storage::{
+        group::{GroupLifecycleState, GroupMembershipState},
+        group_intent::IntentState,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    subscriptions::LocalEvents,
+    Client,
+};
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Create a group with `inbox_ids` as members and `first_message` sent to it. The group is
+    /// only kept if both succeed, see the [module docs](self).
+    pub async fn create_group_with_message(
+        &self,
+        inbox_ids: &[InboxId],
+        permissions_policy_set: Option<PolicySet>,
+        opts: GroupMetadataOptions,
+        first_message: &[u8],
+    ) -> Result<MlsGroup<Self>, ClientError> {
+        tracing::info!("creating group with a first message");
+        let provider = self.mls_provider()?;
+        let group = MlsGroup::create_and_insert_with_lifecycle(
+            Arc::new(self.clone()),
+            &provider,
+            GroupMembershipState::Allowed,
+            GroupLifecycleState::Staged,
+            permissions_policy_set.unwrap_or_default(),
+            opts,
+        )?;
+
+        if !inbox_ids.is_empty() {
+            if let Err(err) = group
+                .add_members_by_inbox_id_with_provider(&provider, inbox_ids)
+                .await
+            {
+                if !group.commit_published(&provider)? {
+                    group.discard_staged_or_log(&provider);
+                    return Err(err.into());
+                }
+                tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "adding members failed after their commit was published, keeping the group: {err}"
+                );
+            }
+        }
+
+        if let Err(err) = group.prepare_message(first_message, &provider, |now| {
+            MlsGroup::<Self>::into_envelope(first_message, now)
+        }) {
+            if !group.commit_published(&provider)? {
+                group.discard_staged_or_log(&provider);
+                return Err(err.into());
+            }
+            // the invitees already have the group, an empty group is better than none
+            tracing::error!(
+                group_id = hex::encode(&group.group_id),
+                "failed to queue the first message of a group with published members: {err}"
+            );
+        }
+        provider.conn_ref().activate_staged_group(&group.group_id)?;
+        // notify streams of our new group
+        let _ = self
+            .local_events
+            .send(LocalEvents::NewGroup(group.group_id.clone()));
+
+        // The message is queued with the group, it is retried by the next sync if this fails
+        if let Err(err) = group.sync_until_last_intent_resolved(&provider).await {
+            tracing::warn!(
+                group_id = hex::encode(&group.group_id),
+                "first message of a new group is not published yet: {err}"
+            );
+        }
+
+        Ok(group)
+    }
+
+    /// Resolve the groups left [`GroupLifecycleState::Staged`] by an interrupted
+    /// [`Self::create_group_with_message`] or [`MlsGroup::clone_group`]. Groups whose commit was published are activated, and
+    /// their queued message is sent by the next sync. The others are discarded.
+    pub(crate) fn recover_staged_groups(&self) -> Result<(), ClientError> {
+        let provider = self.mls_provider()?;
+        for group_id in provider.conn_ref().find_staged_group_ids()? {
+            let (group, _) = MlsGroup::new_validated(self.clone(), group_id, &provider)?;
+            if group.commit_published(&provider)? {
+                provider.conn_ref().activate_staged_group(&group.group_id)?;
+                let _ = self
+                    .local_events
+                    .send(LocalEvents::NewGroup(group.group_id.clone()));
+            } else {
+                group.discard_staged_or_log(&provider);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Delete a [`GroupLifecycleState::Staged`] group from this installation. Does nothing if the
+    /// group is not staged.
+    pub(crate) fn discard_staged(&self, provider: &XmtpOpenMlsProvider) -> Result<(), GroupError> {
+        if !provider.conn_ref().delete_staged_group(&self.group_id)? {
+            return Ok(());
+        }
+        self.delete_mls_group(provider)
+    }
+
+    pub(crate) fn discard_staged_or_log(&self, provider: &XmtpOpenMlsProvider) {
+        if let Err(err) = self.discard_staged(provider) {
+            tracing::error!(
+                group_id = hex::encode(&self.group_id),
+                "failed to discard staged group: {err}"
+            );
+        }
+    }
+
+    /// Whether a commit of this group left this installation, in which case other members may
+    /// have the group
+    pub(crate) fn commit_published(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<bool, GroupError> {
+        let published = provider.conn_ref().find_group_intents(
+            self.group_id.clone(),
+            Some(vec![IntentState::Published, IntentState::Committed]),
+            None,
+        )?;
+        Ok(!published.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        storage::group::{GroupQueryArgs, StoredGroup},
+        storage::group_message::MsgQueryArgs,
+        Fetch,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_create_group_with_message() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = amal
+            .create_group_with_message(
+                &[bola.inbox_id().to_string()],
+                None,
+                GroupMetadataOptions::default(),
+                b"hello",
+            )
+            .await
+            .unwrap();
+        let groups = amal.find_groups(GroupQueryArgs::default()).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            group.lifecycle_state().unwrap(),
+            GroupLifecycleState::Active
+        );
+        let messages = group.find_messages(&MsgQueryArgs::default()).unwrap();
+        assert!(messages
+            .iter()
+            .any(|m| m.decrypted_message_bytes == b"hello"));
+
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(group.group_id.clone()).unwrap();
+        bola_group.sync().await.unwrap();
+        let messages = bola_group.find_messages(&MsgQueryArgs::default()).unwrap();
+        assert!(messages
+            .iter()
+            .any(|m| m.decrypted_message_bytes == b"hello"));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_create_group_with_message_rolls_back() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let result = amal
+            .create_group_with_message(
+                &["not an inbox".to_string()],
+                None,
+                GroupMetadataOptions::default(),
+                b"hello",
+            )
+            .await;
+        assert!(result.is_err());
+        assert!(amal
+            .find_groups(GroupQueryArgs::default())
+            .unwrap()
+            .is_empty());
+
+        // nothing is left of a group discarded while staged
+        let provider = amal.mls_provider().unwrap();
+        let group = MlsGroup::create_and_insert_with_lifecycle(
+            Arc::new(amal.clone()),
+            &provider,
+            GroupMembershipState::Allowed,
+            GroupLifecycleState::Staged,
+            PolicySet::default(),
+            GroupMetadataOptions::default(),
+        )
+        .unwrap();
+        assert!(amal
+            .find_groups(GroupQueryArgs::default())
+            .unwrap()
+            .is_empty());
+        group.discard_staged(&provider).unwrap();
+        let stored: Option<StoredGroup> = provider.conn_ref().fetch(&group.group_id).unwrap();
+        assert!(stored.is_none());
+        assert!(group
+            .load_mls_group_with_lock(&provider, |_| Ok(()))
+            .is_err());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_recover_staged_groups() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let provider = amal.mls_provider().unwrap();
+        let stage = || {
+            MlsGroup::create_and_insert_with_lifecycle(
+                Arc::new(amal.clone()),
+                &provider,
+                GroupMembershipState::Allowed,
+                GroupLifecycleState::Staged,
+                PolicySet::default(),
+                GroupMetadataOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // interrupted once the invitees had the group, and before anything was published
+        let announced = stage();
+        announced
+            .add_members_by_inbox_id_with_provider(&provider, &[bola.inbox_id().to_string()])
+            .await
+            .unwrap();
+        let unannounced = stage();
+
+        amal.recover_staged_groups().unwrap();
+        assert_eq!(
+            announced.lifecycle_state().unwrap(),
+            GroupLifecycleState::Active
+        );
+        let stored: Option<StoredGroup> = provider.conn_ref().fetch(&unannounced.group_id).unwrap();
+        assert!(stored.is_none());
+        let groups = amal.find_groups(GroupQueryArgs::default()).unwrap();
+        assert_eq!(groups.len(), 1);
+    }
+}
@@ -1,4 +1,6 @@
-use futures::{Stream, StreamExt};
+use std::sync::Arc;
+
+use futures::Stream;
 
 use prost::Message;
 use tokio::sync::oneshot;
@@ -8,10 +10,12 @@ use crate::{
     groups::ScopedGroupClient,
     storage::group_message::StoredGroupMessage,
     subscriptions::{
+        delivery,
         stream_messages::{ProcessMessageFuture, StreamGroupMessages},
         Result, SubscribeError,
     },
     types::GroupId,
+    StreamControl,
 };
 use xmtp_proto::api_client::{trait_impls::XmtpApi, XmtpMlsStreams};
 use xmtp_proto::xmtp::mls::api::v1::GroupMessage;
@@ -77,15 +81,28 @@ where
     <ScopedClient as ScopedGroupClient>::ApiClient: XmtpApi + XmtpMlsStreams + 'static,
 {
     let (tx, rx) = oneshot::channel();
+    let control = Arc::new(StreamControl::default());
 
-    crate::spawn(Some(rx), async move {
+    crate::spawn_controlled(Some(rx), Some(control.clone()), async move {
         let client_ref = &client;
-        let stream = StreamGroupMessages::new(client_ref, active_conversations.collect()).await?;
-        futures::pin_mut!(stream);
+        let group_ids: Vec<GroupId> = active_conversations.collect();
+        let stream = StreamGroupMessages::new(client_ref, group_ids.clone()).await?;
         let _ = tx.send(());
-        while let Some(message) = stream.next().await {
-            callback(message)
-        }
+        let replay = |cursor_ns| {
+            let conn = client_ref.store().conn()?;
+            Ok(delivery::stored_messages_after(
+                &conn, &group_ids, cursor_ns,
+            )?)
+        };
+        delivery::deliver_messages(
+            stream,
+            &control,
+            client_ref.stream_dedupe(),
+            client_ref.stream_bursts(),
+            replay,
+            &mut callback,
+        )
+        .await;
         tracing::debug!("`stream_messages` stream ended, dropping stream");
         Ok::<_, SubscribeError>(())
     })
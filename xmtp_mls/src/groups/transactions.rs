@@ -0,0 +1,178 @@
+//! On-chain status of transaction reference messages.
+//!
+//! Apps check transactions with their own chain access by implementing
+//! [`TransactionVerifier`] and registering it with
+//! [`crate::builder::ClientBuilder::transaction_verifier`]. Statuses are cached in storage, and
+//! final statuses are never checked again.
+use std::sync::Arc;
+
+use prost::Message;
+use thiserror::Error;
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{
+    transaction_reference::{TransactionReference, TransactionReferenceCodec},
+    ContentCodec,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::{api_client::trait_impls::XmtpApi, xmtp::mls::message_contents::EncodedContent};
+
+use crate::{
+    client::ClientError,
+    storage::{
+        group_message::ContentType,
+        transaction_verification::{StoredTransactionVerification, TransactionStatus},
+        NotFound,
+    },
+    Client,
+};
+
+#[derive(Debug, Error)]
+#[error("transaction verification failed: {0}")]
+pub struct TransactionVerifierError(pub String);
+
+/// Checks the status of a transaction on chain
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait TransactionVerifier: Send + Sync {
+    /// Only called with references that passed [`TransactionReference::validate`]
+    async fn transaction_status(
+        &self,
+        reference: &TransactionReference,
+    ) -> Result<TransactionStatus, TransactionVerifierError>;
+}
+
+/// The verifier registered on a client
+pub(crate) type SharedTransactionVerifier = Option<Arc<dyn TransactionVerifier>>;
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Status of the transaction referenced by a message. Returns the cached status if it is
+    /// final, and asks the [`TransactionVerifier`] otherwise.
+    pub async fn transaction_status(
+        &self,
+        message_id: &[u8],
+    ) -> Result<TransactionStatus, ClientError> {
+        let conn = self.store().conn()?;
+        if let Some(cached) = conn.get_transaction_verification(message_id)? {
+            if cached.status.is_final() {
+                return Ok(cached.status);
+            }
+        }
+
+        let message = conn
+            .get_group_message(message_id)?
+            .ok_or_else(|| NotFound::MessageById(message_id.to_vec()))?;
+        if message.content_type != ContentType::TransactionReference {
+            return Err(ClientError::Generic(format!(
+                "message {} is not a transaction reference",
+                hex::encode(message_id)
+            )));
+        }
+        let reference = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+            .ok()
+            .and_then(|content| TransactionReferenceCodec::decode(content).ok());
+        let (reference, status) = match reference {
+            Some(reference) if reference.validate().is_ok() => {
+                let status = match &self.transaction_verifier {
+                    Some(verifier) => verifier
+                        .transaction_status(&reference)
+                        .await
+                        .map_err(|e| ClientError::Generic(e.to_string()))?,
+                    None => return Ok(TransactionStatus::Unverified),
+                };
+                (Some(reference), status)
+            }
+            reference => (reference, TransactionStatus::Invalid),
+        };
+
+        conn.set_transaction_verification(&StoredTransactionVerification {
+            message_id: message_id.to_vec(),
+            network_id: reference
+                .as_ref()
+                .map(|r| r.network_id.clone())
+                .unwrap_or_default(),
+            reference: reference.map(|r| r.reference).unwrap_or_default(),
+            status,
+            checked_at_ns: now_ns(),
+        })?;
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::encoded_content_to_bytes;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[derive(Default)]
+    struct CountingVerifier(AtomicUsize);
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl TransactionVerifier for CountingVerifier {
+        async fn transaction_status(
+            &self,
+            _reference: &TransactionReference,
+        ) -> Result<TransactionStatus, TransactionVerifierError> {
+            // Pending the first time it is checked
+            Ok(match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => TransactionStatus::Pending,
+                _ => TransactionStatus::Confirmed,
+            })
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_transaction_status_is_cached() {
+        let mut amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let verifier = Arc::new(CountingVerifier::default());
+        amal.transaction_verifier = Some(verifier.clone() as Arc<dyn TransactionVerifier>);
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let encoded = TransactionReferenceCodec::encode(TransactionReference {
+            namespace: None,
+            network_id: "1".to_string(),
+            reference: format!("0x{}", "ab".repeat(32)),
+            metadata: None,
+        })
+        .unwrap();
+        let message_id = group
+            .send_message(&encoded_content_to_bytes(encoded))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            amal.transaction_status(&message_id).await.unwrap(),
+            TransactionStatus::Pending
+        );
+        assert_eq!(
+            amal.transaction_status(&message_id).await.unwrap(),
+            TransactionStatus::Confirmed
+        );
+        // Final statuses are read from storage
+        assert_eq!(
+            amal.transaction_status(&message_id).await.unwrap(),
+            TransactionStatus::Confirmed
+        );
+        assert_eq!(verifier.0.load(Ordering::SeqCst), 2);
+
+        let text_id = group.send_message(b"hello").await.unwrap();
+        assert!(amal.transaction_status(&text_id).await.is_err());
+    }
+}
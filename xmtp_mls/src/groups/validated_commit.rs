@@ -26,7 +26,10 @@ use crate::{
     identity_updates::{InstallationDiff, InstallationDiffError},
     storage::db_connection::DbConnection,
 };
-use xmtp_common::{retry::RetryableError, retryable};
+use xmtp_common::{
+    retry::{ErrorClass, RetryableError},
+    retryable,
+};
 
 use super::{
     group_membership::{GroupMembership, MembershipDiff},
@@ -92,6 +95,13 @@ impl RetryableError for CommitValidationError {
             _ => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            CommitValidationError::InstallationDiff(diff_error) => diff_error.error_class(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Hash)]
@@ -193,7 +203,6 @@ pub struct MetadataFieldChange {
     pub field_name: String,
     #[allow(dead_code)]
     pub old_value: Option<String>,
-    #[allow(dead_code)]
     pub new_value: Option<String>,
 }
 
@@ -308,6 +317,7 @@ impl ValidatedCommit {
             added_installations,
             removed_installations,
             current_group_members,
+            &actor,
         )?;
         credentials_to_verify.push(actor.clone());
 
@@ -530,17 +540,19 @@ fn expected_diff_matches_commit(
     added_installations: HashSet<Vec<u8>>,
     removed_installations: HashSet<Vec<u8>>,
     existing_installation_ids: HashSet<Vec<u8>>,
+    actor: &CommitParticipant,
 ) -> Result<(), CommitValidationError> {
     // Check and make sure that any added installations are either:
     // 1. In the expected diff
     // 2. Already a member of the group (for example, the group creator is already a member on the first commit)
 
     let unknown_adds = added_installations
-        .into_iter()
+        .iter()
         .filter(|installation_id| {
-            !expected_diff.added_installations.contains(installation_id)
-                && !existing_installation_ids.contains(installation_id)
+            !expected_diff.added_installations.contains(*installation_id)
+                && !existing_installation_ids.contains(*installation_id)
         })
+        .cloned()
         .collect::<Vec<Vec<u8>>>();
     if !unknown_adds.is_empty() {
         return Err(CommitValidationError::UnexpectedInstallationAdded(
@@ -548,12 +560,30 @@ fn expected_diff_matches_commit(
         ));
     }
 
-    if removed_installations.ne(&expected_diff.removed_installations) {
+    // An installation that is removed and added in the same commit is being re-added with a
+    // fresh key package, because it never processed its welcome. It stays in the group.
+    let removed_installations: HashSet<Vec<u8>> = removed_installations
+        .difference(&added_installations)
+        .cloned()
+        .collect();
+    // Installations that were already pruned are not in the tree anymore, and can't be removed again
+    let expected_removals: HashSet<Vec<u8>> = expected_diff
+        .removed_installations
+        .intersection(&existing_installation_ids)
+        .cloned()
+        .collect();
+    // Admins can also prune inactive installations of inboxes that stay in the group. Activity
+    // is only known to the sender, see `MlsGroup::prune_inactive_installations`.
+    let unexpected_removals = removed_installations
+        .difference(&expected_removals)
+        .cloned()
+        .collect::<Vec<Vec<u8>>>();
+    let can_prune = actor.is_admin || actor.is_super_admin;
+    if !expected_removals.is_subset(&removed_installations)
+        || (!unexpected_removals.is_empty() && !can_prune)
+    {
         return Err(CommitValidationError::UnexpectedInstallationsRemoved(
-            removed_installations
-                .difference(&expected_diff.removed_installations)
-                .cloned()
-                .collect::<Vec<Vec<u8>>>(),
+            unexpected_removals,
         ));
     }
 
@@ -0,0 +1,218 @@
+//! Verification codes (safety numbers) for comparing identity keys out of band.
+//!
+//! The code for a pair of members is derived from the installation keys of both inboxes, so
+//! both sides compute the same code, and the code changes whenever either side adds or
+//! removes an installation. Once the user has compared the code with their peer, the peer
+//! can be marked as verified. The verification is stored with the code it was made for, and
+//! is reset with a [`LocalEvents::VerificationReset`] event when the code changes.
+use xmtp_common::time::now_ns;
+
+use super::{members::GroupMember, GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        conversation_verification::StoredConversationVerification,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    subscriptions::{LocalEvents, VerificationReset},
+    utils::hash::sha256,
+};
+
+/// Number of digits in a group of the verification code
+const CODE_GROUP_DIGITS: u32 = 5;
+/// Number of groups derived from the fingerprint of each party
+const CODE_GROUPS_PER_PARTY: usize = 6;
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// The code to compare with `peer_inbox_id` out of band, as 12 groups of 5 digits
+    pub async fn verification_code(&self, peer_inbox_id: &str) -> Result<String, GroupError> {
+        let members = self.members().await?;
+        verification_code(self.client.inbox_id(), peer_inbox_id, &members)
+    }
+
+    /// Record that the user compared the current verification code with `peer_inbox_id`
+    pub async fn mark_verified(&self, peer_inbox_id: &str) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let members = self.members_with_provider(&provider).await?;
+        let code = verification_code(self.client.inbox_id(), peer_inbox_id, &members)?;
+        provider
+            .conn_ref()
+            .set_conversation_verification(&StoredConversationVerification {
+                group_id: self.group_id.clone(),
+                peer_inbox_id: peer_inbox_id.to_string(),
+                verification_code: code,
+                verified_at_ns: now_ns(),
+            })?;
+        Ok(())
+    }
+
+    /// Forget that `peer_inbox_id` was verified
+    pub fn unmark_verified(&self, peer_inbox_id: &str) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        conn.delete_conversation_verification(&self.group_id, peer_inbox_id)?;
+        Ok(())
+    }
+
+    /// Whether `peer_inbox_id` was verified and their keys did not change since
+    pub async fn is_verified(&self, peer_inbox_id: &str) -> Result<bool, GroupError> {
+        let provider = self.mls_provider()?;
+        self.check_verifications(&provider).await?;
+        Ok(provider
+            .conn_ref()
+            .get_conversation_verification(&self.group_id, peer_inbox_id)?
+            .is_some())
+    }
+
+    /// Reset the verification of peers whose code changed, or who left the group
+    pub(crate) async fn check_verifications(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let conn = provider.conn_ref();
+        let verifications = conn.conversation_verifications(&self.group_id)?;
+        if verifications.is_empty() {
+            return Ok(());
+        }
+
+        let members = self.members_with_provider(provider).await?;
+        for verification in verifications {
+            let current = verification_code(
+                self.client.inbox_id(),
+                &verification.peer_inbox_id,
+                &members,
+            )
+            .ok();
+            if current.as_ref() == Some(&verification.verification_code) {
+                continue;
+            }
+
+            tracing::info!(
+                inbox_id = self.client.inbox_id(),
+                group_id = hex::encode(&self.group_id),
+                peer_inbox_id = verification.peer_inbox_id,
+                "keys of a verified peer changed, resetting verification"
+            );
+            conn.delete_conversation_verification(&self.group_id, &verification.peer_inbox_id)?;
+            let _ = self
+                .client
+                .local_events()
+                .send(LocalEvents::VerificationReset(VerificationReset {
+                    group_id: self.group_id.clone(),
+                    peer_inbox_id: verification.peer_inbox_id,
+                }));
+        }
+        Ok(())
+    }
+}
+
+fn verification_code(
+    own_inbox_id: &str,
+    peer_inbox_id: &str,
+    members: &[GroupMember],
+) -> Result<String, GroupError> {
+    if own_inbox_id == peer_inbox_id {
+        return Err(GroupError::Generic(
+            "a verification code needs two different members".to_string(),
+        ));
+    }
+    let fingerprint = |inbox_id: &str| {
+        members
+            .iter()
+            .find(|member| member.inbox_id == inbox_id)
+            .map(fingerprint)
+            .ok_or_else(|| GroupError::Generic(format!("{inbox_id} is not a member of the group")))
+    };
+    let mut fingerprints = [fingerprint(own_inbox_id)?, fingerprint(peer_inbox_id)?];
+    // Both sides have to compute the same code
+    fingerprints.sort();
+
+    let modulus = 10u64.pow(CODE_GROUP_DIGITS);
+    let groups: Vec<String> = fingerprints
+        .iter()
+        .flat_map(|fingerprint| {
+            fingerprint
+                .chunks(5)
+                .take(CODE_GROUPS_PER_PARTY)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+                })
+        })
+        .map(|value| {
+            format!(
+                "{:0width$}",
+                value % modulus,
+                width = CODE_GROUP_DIGITS as usize
+            )
+        })
+        .collect();
+    Ok(groups.join(" "))
+}
+
+/// Hash of the inbox id and all its installation keys
+fn fingerprint(member: &GroupMember) -> Vec<u8> {
+    let mut installation_ids = member.installation_ids.clone();
+    installation_ids.sort();
+    let mut bytes = member.inbox_id.as_bytes().to_vec();
+    for installation_id in installation_ids {
+        bytes.extend(installation_id);
+    }
+    sha256(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::LocalEvents};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_verification_resets_when_keys_change() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(&bola_wallet).await;
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+        bola.sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bola_group = bola.group(amal_group.group_id.clone()).unwrap();
+
+        let code = amal_group.verification_code(bola.inbox_id()).await.unwrap();
+        assert_eq!(code.split(' ').count(), 12);
+        assert_eq!(
+            bola_group.verification_code(amal.inbox_id()).await.unwrap(),
+            code
+        );
+        assert!(amal_group.verification_code(amal.inbox_id()).await.is_err());
+
+        amal_group.mark_verified(bola.inbox_id()).await.unwrap();
+        assert!(amal_group.is_verified(bola.inbox_id()).await.unwrap());
+
+        // Bola adds an installation, which changes the code
+        let mut events = amal.local_events.subscribe();
+        let _bola2 = ClientBuilder::new_test_client(&bola_wallet).await;
+        amal_group.update_installations().await.unwrap();
+        amal_group.sync().await.unwrap();
+        assert_ne!(
+            amal_group.verification_code(bola.inbox_id()).await.unwrap(),
+            code
+        );
+
+        let reset = loop {
+            if let LocalEvents::VerificationReset(reset) = events.recv().await.unwrap() {
+                break reset;
+            }
+        };
+        assert_eq!(reset.peer_inbox_id, bola.inbox_id());
+        assert!(!amal_group.is_verified(bola.inbox_id()).await.unwrap());
+    }
+}
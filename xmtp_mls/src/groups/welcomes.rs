@@ -0,0 +1,160 @@
+//! Batching of the welcomes sent when members are added.
+//!
+//! Welcomes are packed into as few requests as fit under [`GRPC_DATA_LIMIT`] and
+//! [`MAX_WELCOMES_PER_BATCH`], using their encoded size. A batch the server still refuses as too
+//! large is split in half and sent again, down to single welcomes. The outcome of every
+//! installation is collected in a [`WelcomeSendReport`], so that only the failed ones are retried.
+use prost::Message;
+use xmtp_proto::xmtp::mls::api::v1::{
+    welcome_message_input::Version as WelcomeMessageInputVersion, WelcomeMessageInput,
+};
+
+use crate::configuration::{GRPC_DATA_LIMIT, MAX_WELCOMES_PER_BATCH};
+
+/// The outcome of sending the welcomes of a commit
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WelcomeSendReport {
+    pub group_id: Vec<u8>,
+    /// Installations the welcome was sent to
+    pub sent: Vec<Vec<u8>>,
+    /// Installations the welcome could not be sent to. They are queued to be retried, until the
+    /// welcome was attempted [`MAX_WELCOME_SEND_ATTEMPTS`](crate::configuration::MAX_WELCOME_SEND_ATTEMPTS)
+    /// times.
+    pub failed: Vec<FailedWelcome>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedWelcome {
+    pub installation_id: Vec<u8>,
+    pub error: String,
+}
+
+impl WelcomeSendReport {
+    pub(crate) fn new(group_id: &[u8]) -> Self {
+        Self {
+            group_id: group_id.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn record_sent(&mut self, batch: &[WelcomeMessageInput]) {
+        self.sent.extend(batch.iter().map(installation_key));
+    }
+
+    pub(crate) fn record_failed(&mut self, batch: &[WelcomeMessageInput], error: &str) {
+        self.failed
+            .extend(batch.iter().map(|welcome| FailedWelcome {
+                installation_id: installation_key(welcome),
+                error: error.to_string(),
+            }));
+    }
+}
+
+/// The installation a welcome is addressed to
+pub(crate) fn installation_key(welcome: &WelcomeMessageInput) -> Vec<u8> {
+    match &welcome.version {
+        Some(WelcomeMessageInputVersion::V1(welcome)) => welcome.installation_key.clone(),
+        None => vec![],
+    }
+}
+
+/// Pack `welcomes` in order into batches of at most `max_count` welcomes and `max_bytes` encoded
+/// bytes. A welcome larger than `max_bytes` gets a batch of its own.
+pub(crate) fn batch_welcomes(
+    welcomes: Vec<WelcomeMessageInput>,
+    max_bytes: usize,
+    max_count: usize,
+) -> Vec<Vec<WelcomeMessageInput>> {
+    let mut batches = vec![];
+    let mut batch: Vec<WelcomeMessageInput> = vec![];
+    let mut batch_bytes = 0;
+    for welcome in welcomes {
+        // each welcome is a length delimited field of the request
+        let bytes = welcome.encoded_len() + prost::length_delimiter_len(welcome.encoded_len()) + 1;
+        if !batch.is_empty() && (batch.len() >= max_count || batch_bytes + bytes > max_bytes) {
+            batches.push(std::mem::take(&mut batch));
+            batch_bytes = 0;
+        }
+        batch_bytes += bytes;
+        batch.push(welcome);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// [`batch_welcomes`] with the default limits
+pub(crate) fn default_batches(welcomes: Vec<WelcomeMessageInput>) -> Vec<Vec<WelcomeMessageInput>> {
+    batch_welcomes(welcomes, GRPC_DATA_LIMIT, MAX_WELCOMES_PER_BATCH)
+}
+
+/// Whether the server refused a request because of its size
+pub(crate) fn is_payload_too_large(error: &impl std::fmt::Display) -> bool {
+    // ResourceExhausted alone is also the status of rate limited requests
+    let message = error.to_string().to_lowercase();
+    ["message too large", "larger than max", "payload too large"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmtp_proto::xmtp::mls::api::v1::welcome_message_input::V1 as WelcomeMessageInputV1;
+
+    fn welcome(installation: u8, data_len: usize) -> WelcomeMessageInput {
+        WelcomeMessageInput {
+            version: Some(WelcomeMessageInputVersion::V1(WelcomeMessageInputV1 {
+                installation_key: vec![installation; 32],
+                data: vec![0; data_len],
+                hpke_public_key: vec![1; 32],
+            })),
+        }
+    }
+
+    #[test]
+    fn batches_by_count_and_size() {
+        let welcomes: Vec<_> = (0..10).map(|i| welcome(i, 100)).collect();
+        let by_count = batch_welcomes(welcomes.clone(), usize::MAX, 4);
+        assert_eq!(
+            by_count.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![4, 4, 2]
+        );
+
+        let size = welcomes[0].encoded_len() + 3;
+        let by_size = batch_welcomes(welcomes.clone(), size * 3, 100);
+        assert_eq!(
+            by_size.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 3, 1]
+        );
+        // order is kept
+        assert_eq!(by_size.concat(), welcomes);
+
+        // welcomes over the limit are sent alone
+        let oversized = batch_welcomes(vec![welcome(0, 10), welcome(1, 1000)], 500, 100);
+        assert_eq!(oversized.len(), 2);
+    }
+
+    #[test]
+    fn reports_recipients() {
+        let mut report = WelcomeSendReport::new(&[1]);
+        report.record_sent(&[welcome(2, 1)]);
+        report.record_failed(&[welcome(3, 1)], "down");
+        assert_eq!(report.sent, vec![vec![2; 32]]);
+        assert_eq!(
+            report.failed,
+            vec![FailedWelcome {
+                installation_id: vec![3; 32],
+                error: "down".to_string()
+            }]
+        );
+        assert!(is_payload_too_large(
+            &"status: ResourceExhausted, message: \"grpc: received message larger than max\""
+        ));
+        assert!(!is_payload_too_large(&"connection reset"));
+        assert!(!is_payload_too_large(
+            &"status: ResourceExhausted, message: \"rate limit exceeded\""
+        ));
+    }
+}
@@ -17,7 +17,7 @@ use openmls_rust_crypto::RustCrypto;
 use openmls_traits::OpenMlsProvider;
 use openmls_traits::{storage::StorageProvider, types::HpkeCiphertext};
 use thiserror::Error;
-use xmtp_common::{retryable, RetryableError};
+use xmtp_common::{retryable, ErrorClass, RetryableError};
 
 #[derive(Debug, Error)]
 pub enum HpkeError {
@@ -38,6 +38,13 @@ impl RetryableError for HpkeError {
             _ => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::StorageError(storage) => storage.error_class(),
+            _ => None,
+        }
+    }
 }
 
 /// Encrypt a welcome message using the provided HPKE private key
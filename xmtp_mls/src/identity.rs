@@ -1,8 +1,10 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::configuration::GROUP_PERMISSIONS_EXTENSION_ID;
+use crate::groups::capabilities::supported_extension_types;
 use crate::storage::db_connection::DbConnection;
 use crate::storage::identity::StoredIdentity;
+use crate::storage::key_store_backend::generate_installation_credential;
 use crate::storage::sql_key_store::{SqlKeyStore, SqlKeyStoreError, KEY_PACKAGE_REFERENCES};
 use crate::{
     api::{ApiClientWrapper, WrappedApiError},
@@ -27,7 +29,7 @@ use prost::Message;
 use thiserror::Error;
 use tracing::debug;
 use tracing::info;
-use xmtp_common::{retryable, RetryableError};
+use xmtp_common::{retryable, ErrorClass, RetryableError};
 use xmtp_cryptography::{CredentialSign, XmtpInstallationCredential};
 use xmtp_id::associations::unverified::UnverifiedSignature;
 use xmtp_id::associations::{AssociationError, InstallationKeyContext, PublicContext};
@@ -116,10 +118,10 @@ impl IdentityStrategy {
         use IdentityStrategy::*;
 
         info!("Initializing identity");
-        let stored_identity: Option<Identity> = provider
-            .conn_ref()
+        let conn = provider.conn_ref();
+        let stored_identity: Option<Identity> = conn
             .fetch(&())?
-            .map(|i: StoredIdentity| i.try_into())
+            .map(|i: StoredIdentity| i.into_identity(conn.key_store_backend()))
             .transpose()?;
 
         debug!("identity in store: {:?}", stored_identity);
@@ -232,6 +234,17 @@ impl RetryableError for IdentityError {
             _ => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::Api(_) => Some(ErrorClass::Network),
+            Self::WrappedApi(err) => err.error_class(),
+            Self::StorageError(err) => err.error_class(),
+            Self::OpenMlsStorageError(err) => err.error_class(),
+            Self::DieselResult(err) => err.error_class(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -276,11 +289,12 @@ impl Identity {
         scw_signature_verifier: impl SmartContractSignatureVerifier,
     ) -> Result<Self, IdentityError> {
         // check if address is already associated with an inbox_id
-        let address = address.to_lowercase();
+        let member_identifier: MemberIdentifier = address.into();
+        let address = member_identifier.to_string();
         let inbox_ids = api_client.get_inbox_ids(vec![address.clone()]).await?;
         let associated_inbox_id = inbox_ids.get(&address);
-        let installation_keys = XmtpInstallationCredential::new();
-        let member_identifier: MemberIdentifier = address.clone().to_lowercase().into();
+        let installation_keys =
+            generate_installation_credential(provider.conn_ref().key_store_backend())?;
 
         if let Some(associated_inbox_id) = associated_inbox_id {
             // If an inbox is associated with address, we'd use it to create Identity and ignore the nonce.
@@ -479,17 +493,20 @@ impl Identity {
             Extension::ApplicationId(ApplicationIdExtension::new(self.inbox_id().as_bytes()));
         let leaf_node_extensions = Extensions::single(application_id);
 
+        let mut extension_types = vec![
+            ExtensionType::LastResort,
+            ExtensionType::ApplicationId,
+            ExtensionType::Unknown(GROUP_PERMISSIONS_EXTENSION_ID),
+            ExtensionType::Unknown(MUTABLE_METADATA_EXTENSION_ID),
+            ExtensionType::Unknown(GROUP_MEMBERSHIP_EXTENSION_ID),
+            ExtensionType::ImmutableMetadata,
+        ];
+        // Advertise the optional features this installation supports
+        extension_types.extend(supported_extension_types());
         let capabilities = Capabilities::new(
             None,
             Some(&[CIPHERSUITE]),
-            Some(&[
-                ExtensionType::LastResort,
-                ExtensionType::ApplicationId,
-                ExtensionType::Unknown(GROUP_PERMISSIONS_EXTENSION_ID),
-                ExtensionType::Unknown(MUTABLE_METADATA_EXTENSION_ID),
-                ExtensionType::Unknown(GROUP_MEMBERSHIP_EXTENSION_ID),
-                ExtensionType::ImmutableMetadata,
-            ]),
+            Some(&extension_types),
             Some(&[ProposalType::GroupContextExtensions]),
             None,
         );
@@ -1,10 +1,14 @@
 use crate::storage::{
-    association_state::StoredAssociationState, user_preferences::StoredUserPreferences,
+    association_state::StoredAssociationState, installation_key_log::StoredInstallationKey,
+    user_preferences::StoredUserPreferences,
+};
+use futures::{
+    future::{join_all, try_join_all},
+    stream, StreamExt, TryStreamExt,
 };
-use futures::future::try_join_all;
 use std::collections::{HashMap, HashSet};
 use thiserror::Error;
-use xmtp_common::{retry_async, retryable, Retry, RetryableError};
+use xmtp_common::{retry_async, retryable, time::now_ns, ErrorClass, RetryableError};
 use xmtp_cryptography::CredentialSign;
 use xmtp_id::{
     associations::{
@@ -15,9 +19,11 @@ use xmtp_id::{
             UnverifiedIdentityUpdate, UnverifiedInstallationKeySignature, UnverifiedSignature,
         },
         AssociationError, AssociationState, AssociationStateDiff, IdentityAction, IdentityUpdate,
-        InstallationKeyContext, MemberIdentifier, SignatureError,
+        InstallationKeyContext, MemberIdentifier,
+    },
+    scw_verifier::{
+        OfflineSignatureVerifier, RemoteSignatureVerifier, SmartContractSignatureVerifier,
     },
-    scw_verifier::{RemoteSignatureVerifier, SmartContractSignatureVerifier},
     InboxIdRef,
 };
 use xmtp_proto::api_client::{ClientWithMetadata, XmtpIdentityClient, XmtpMlsClient};
@@ -25,8 +31,10 @@ use xmtp_proto::api_client::{ClientWithMetadata, XmtpIdentityClient, XmtpMlsClie
 use crate::{
     api::{ApiClientWrapper, GetIdentityUpdatesV2Filter, InboxUpdate},
     client::ClientError,
+    configuration::MAX_CONCURRENT_IDENTITY_VALIDATIONS,
     groups::group_membership::{GroupMembership, MembershipDiff},
     storage::{db_connection::DbConnection, identity_update::StoredIdentityUpdate},
+    subscriptions::LocalEvents,
     Client, XmtpApi,
 };
 
@@ -36,12 +44,133 @@ pub enum IdentityUpdateError {
     InvalidSignatureRequest(#[from] SignatureRequestError),
 }
 
+/// The outcome of [`Client::apply_signature_requests`]
+#[derive(Debug)]
+pub struct IdentityUpdateBatch {
+    /// Positions of the requests that were published, in the order they were given
+    pub published: Vec<usize>,
+    /// The requests that were not published, in the order they were given
+    pub unpublished: Vec<SignatureRequest>,
+    /// Whether the identity updates of the inboxes were loaded once published
+    pub loaded: bool,
+    /// The first error met publishing the updates, or loading them once published
+    pub error: Option<ClientError>,
+}
+
+impl IdentityUpdateBatch {
+    /// `Ok` if every update was published and loaded, or else the first error
+    pub fn into_result(self) -> Result<(), ClientError> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
 #[derive(Debug)]
 pub struct InstallationDiff {
     pub added_installations: HashSet<Vec<u8>>,
     pub removed_installations: HashSet<Vec<u8>>,
 }
 
+/// A change to the identity of a member of one of the user's conversations, found while
+/// processing the member's identity updates. The same change is reported once for every
+/// conversation shared with the member, with the same `sequence_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityChange {
+    pub inbox_id: String,
+    /// Sequence id of the identity update the change was computed up to
+    pub sequence_id: i64,
+    pub kind: IdentityChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdentityChangeKind {
+    WalletAdded(String),
+    WalletRemoved(String),
+    InstallationAdded(Vec<u8>),
+    InstallationRevoked(Vec<u8>),
+}
+
+impl IdentityChange {
+    fn from_diff(inbox_id: &str, sequence_id: i64, diff: &AssociationStateDiff) -> Vec<Self> {
+        let added = diff.new_members.iter().map(|member| match member {
+            MemberIdentifier::Address(address) => IdentityChangeKind::WalletAdded(address.clone()),
+            MemberIdentifier::Installation(id) => IdentityChangeKind::InstallationAdded(id.clone()),
+        });
+        let removed = diff.removed_members.iter().map(|member| match member {
+            MemberIdentifier::Address(address) => {
+                IdentityChangeKind::WalletRemoved(address.clone())
+            }
+            MemberIdentifier::Installation(id) => {
+                IdentityChangeKind::InstallationRevoked(id.clone())
+            }
+        });
+        added
+            .chain(removed)
+            .map(|kind| IdentityChange {
+                inbox_id: inbox_id.to_string(),
+                sequence_id,
+                kind,
+            })
+            .collect()
+    }
+}
+
+/// Members added to and removed from an inbox between two sequence ids
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssociationDiff {
+    pub inbox_id: String,
+    /// `None` when the diff starts before the inbox was created
+    pub from_sequence_id: Option<i64>,
+    pub to_sequence_id: i64,
+    pub added_wallets: Vec<String>,
+    pub removed_wallets: Vec<String>,
+    pub added_installations: Vec<Vec<u8>>,
+    pub removed_installations: Vec<Vec<u8>>,
+}
+
+impl AssociationDiff {
+    fn new(
+        inbox_id: &str,
+        from_sequence_id: Option<i64>,
+        to_sequence_id: i64,
+        diff: AssociationStateDiff,
+    ) -> Self {
+        let wallets = |members: &[MemberIdentifier]| {
+            members
+                .iter()
+                .filter_map(|member| member.address().map(str::to_string))
+                .collect()
+        };
+        Self {
+            inbox_id: inbox_id.to_string(),
+            from_sequence_id,
+            to_sequence_id,
+            added_wallets: wallets(&diff.new_members),
+            removed_wallets: wallets(&diff.removed_members),
+            added_installations: diff.new_installations(),
+            removed_installations: diff.removed_installations(),
+        }
+    }
+}
+
+/// Changes to the installation keys of an inbox that the user has not acknowledged yet,
+/// similar to a "safety number changed" warning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderConsistency {
+    pub inbox_id: String,
+    /// Keys that appeared after the inbox was first seen
+    pub added: Vec<StoredInstallationKey>,
+    /// Keys that are no longer part of the inbox
+    pub revoked: Vec<StoredInstallationKey>,
+    /// Keys that are part of the inbox again after they were revoked
+    pub reappeared: Vec<StoredInstallationKey>,
+}
+
+impl SenderConsistency {
+    pub fn is_consistent(&self) -> bool {
+        self.added.is_empty() && self.revoked.is_empty() && self.reappeared.is_empty()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum InstallationDiffError {
     #[error(transparent)]
@@ -54,6 +183,12 @@ impl RetryableError for InstallationDiffError {
             InstallationDiffError::Client(client_error) => retryable!(client_error),
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            InstallationDiffError::Client(client_error) => client_error.error_class(),
+        }
+    }
 }
 
 impl DbConnection {
@@ -97,17 +232,79 @@ where
         conn: &DbConnection,
         identifiers: &[(InboxIdRef<'a>, Option<i64>)],
     ) -> Result<Vec<AssociationState>, ClientError> {
-        let association_states = try_join_all(
-            identifiers
-                .iter()
-                .map(|(inbox_id, to_sequence_id)| {
-                    self.get_association_state(conn, inbox_id, *to_sequence_id)
-                })
-                .collect::<Vec<_>>(),
-        )
-        .await?;
+        let loaded: Vec<LoadedAssociationState> =
+            stream::iter(identifiers.iter().map(|(inbox_id, to_sequence_id)| {
+                self.load_association_state(conn, inbox_id, *to_sequence_id)
+            }))
+            .buffered(MAX_CONCURRENT_IDENTITY_VALIDATIONS)
+            .try_collect()
+            .await?;
+
+        // Write all the states that were computed at once
+        let to_cache = loaded
+            .iter()
+            .filter(|loaded| !loaded.cached)
+            .map(|loaded| {
+                (
+                    loaded.state.inbox_id().to_string(),
+                    loaded.sequence_id,
+                    loaded.state.clone(),
+                )
+            })
+            .collect();
+        StoredAssociationState::batch_write_to_cache(conn, to_cache)?;
+
+        Ok(loaded.into_iter().map(|loaded| loaded.state).collect())
+    }
+
+    /// Compare the installations of `inbox_id` on the network with the keys this installation
+    /// observed before, and log any new or revoked keys. Keys of an inbox that has not been
+    /// seen before are trusted on first use.
+    pub async fn verify_sender_consistency(
+        &self,
+        inbox_id: InboxIdRef<'a>,
+    ) -> Result<SenderConsistency, ClientError> {
+        let conn = self.store().conn()?;
+        let state = self.get_latest_association_state(&conn, inbox_id).await?;
+        let current = state.installation_ids();
+        let now = now_ns();
+        conn.observe_installation_keys(inbox_id, &current, now)?;
+        let missing: Vec<Vec<u8>> = conn
+            .installation_key_log(inbox_id)?
+            .into_iter()
+            .filter(|key| key.is_active() && !current.contains(&key.installation_id))
+            .map(|key| key.installation_id)
+            .collect();
+        conn.revoke_installation_keys(inbox_id, &missing, now)?;
 
-        Ok(association_states)
+        let mut consistency = SenderConsistency {
+            inbox_id: inbox_id.to_string(),
+            added: vec![],
+            revoked: vec![],
+            reappeared: vec![],
+        };
+        for key in conn.installation_key_log(inbox_id)? {
+            if key.acknowledged {
+                continue;
+            }
+            if !key.is_active() {
+                consistency.revoked.push(key);
+            } else if key.reappeared() {
+                consistency.reappeared.push(key);
+            } else {
+                consistency.added.push(key);
+            }
+        }
+        Ok(consistency)
+    }
+
+    /// Accept the current installation keys of `inbox_id`, clearing the changes reported by
+    /// [`Self::verify_sender_consistency`]
+    pub fn acknowledge_sender_keys(&self, inbox_id: InboxIdRef<'a>) -> Result<(), ClientError> {
+        self.store()
+            .conn()?
+            .acknowledge_installation_keys(inbox_id)?;
+        Ok(())
     }
 
     /// Get the latest association state available on the network for the given `inbox_id`
@@ -129,6 +326,29 @@ where
         inbox_id: InboxIdRef<'a>,
         to_sequence_id: Option<i64>,
     ) -> Result<AssociationState, ClientError> {
+        let loaded = self
+            .load_association_state(conn, inbox_id, to_sequence_id)
+            .await?;
+        if !loaded.cached {
+            StoredAssociationState::write_to_cache(
+                conn,
+                inbox_id.to_string(),
+                loaded.sequence_id,
+                loaded.state.clone(),
+            )?;
+        }
+
+        Ok(loaded.state)
+    }
+
+    /// Read the association state from the cache, or compute it from the stored identity
+    /// updates without writing it to the cache
+    async fn load_association_state(
+        &self,
+        conn: &DbConnection,
+        inbox_id: InboxIdRef<'a>,
+        to_sequence_id: Option<i64>,
+    ) -> Result<LoadedAssociationState, ClientError> {
         let updates = conn.get_identity_updates(inbox_id, None, to_sequence_id)?;
         let last_sequence_id = updates
             .last()
@@ -140,28 +360,57 @@ where
             }
         }
 
-        if let Some(association_state) =
+        if let Some(state) =
             StoredAssociationState::read_from_cache(conn, inbox_id.to_string(), last_sequence_id)?
         {
-            return Ok(association_state);
+            return Ok(LoadedAssociationState {
+                state,
+                sequence_id: last_sequence_id,
+                cached: true,
+            });
         }
 
-        let unverified_updates = updates
-            .into_iter()
-            .map(UnverifiedIdentityUpdate::try_from)
-            .collect::<Result<Vec<UnverifiedIdentityUpdate>, AssociationError>>()?;
+        let unverified_updates = offload(move || {
+            updates
+                .into_iter()
+                .map(UnverifiedIdentityUpdate::try_from)
+                .collect::<Result<Vec<UnverifiedIdentityUpdate>, AssociationError>>()
+        })
+        .await??;
         let updates = verify_updates(unverified_updates, &self.scw_verifier).await?;
+        let state = offload(move || get_state(updates)).await??;
 
-        let association_state = get_state(updates)?;
-
-        StoredAssociationState::write_to_cache(
-            conn,
-            inbox_id.to_string(),
-            last_sequence_id,
-            association_state.clone(),
-        )?;
+        Ok(LoadedAssociationState {
+            state,
+            sequence_id: last_sequence_id,
+            cached: false,
+        })
+    }
 
-        Ok(association_state)
+    /// Members added to and removed from `inbox_id` between two sequence ids, from the cached
+    /// association states when available. The diff starts from an empty inbox if
+    /// `from_sequence_id` is `None`, and ends at the latest known update if `to_sequence_id` is
+    /// `None`. Identity updates are not fetched from the network.
+    pub async fn compute_association_diff(
+        &self,
+        inbox_id: InboxIdRef<'a>,
+        from_sequence_id: Option<i64>,
+        to_sequence_id: Option<i64>,
+    ) -> Result<AssociationDiff, ClientError> {
+        let conn = self.store().conn()?;
+        let to_sequence_id = match to_sequence_id {
+            Some(sequence_id) => sequence_id,
+            None => conn.get_latest_sequence_id_for_inbox(inbox_id)?,
+        };
+        let diff = self
+            .get_association_state_diff(&conn, inbox_id, from_sequence_id, Some(to_sequence_id))
+            .await?;
+        Ok(AssociationDiff::new(
+            inbox_id,
+            from_sequence_id,
+            to_sequence_id,
+            diff,
+        ))
     }
 
     /// Calculate the changes between the `starting_sequence_id` and `ending_sequence_id` for the
@@ -187,6 +436,25 @@ where
                 .as_diff());
         }
 
+        // Both states are usually cached already
+        if let (Some(starting_sequence_id), Some(ending_sequence_id)) =
+            (starting_sequence_id, ending_sequence_id)
+        {
+            let initial_state = StoredAssociationState::read_from_cache(
+                conn,
+                inbox_id.to_string(),
+                starting_sequence_id,
+            )?;
+            let final_state = StoredAssociationState::read_from_cache(
+                conn,
+                inbox_id.to_string(),
+                ending_sequence_id,
+            )?;
+            if let (Some(initial_state), Some(final_state)) = (initial_state, final_state) {
+                return Ok(initial_state.diff(&final_state));
+            }
+        }
+
         // Get the initial state to compare against
         let initial_state = self
             .get_association_state(conn, inbox_id, starting_sequence_id)
@@ -245,7 +513,7 @@ where
         let nonce = maybe_nonce.unwrap_or(0);
         let inbox_id = generate_inbox_id(&wallet_address, &nonce)?;
         let installation_public_key = self.identity().installation_keys.verifying_key();
-        let member_identifier: MemberIdentifier = wallet_address.to_lowercase().into();
+        let member_identifier: MemberIdentifier = wallet_address.into();
 
         let builder = SignatureRequestBuilder::new(inbox_id);
         let mut signature_request = builder
@@ -283,7 +551,7 @@ where
         let inbox_id = self.inbox_id();
         let builder = SignatureRequestBuilder::new(inbox_id);
         let installation_public_key = self.identity().installation_keys.verifying_key();
-        let new_member_identifier = MemberIdentifier::Address(new_wallet_address);
+        let new_member_identifier: MemberIdentifier = new_wallet_address.into();
 
         let mut signature_request = builder
             .add_association(new_member_identifier, installation_public_key.into())
@@ -303,6 +571,39 @@ where
         Ok(signature_request)
     }
 
+    /// Combine signature requests of the client's inbox, such as the registration of the
+    /// installation and the association of another wallet during onboarding, into one request.
+    /// Each wallet then signs once, and the actions are published as a single identity update.
+    ///
+    /// The signatures of the requests are dropped, and the installation key signs the combined
+    /// request again if it needs to.
+    pub async fn combine_signature_requests(
+        &self,
+        signature_requests: Vec<SignatureRequest>,
+    ) -> Result<SignatureRequest, ClientError> {
+        let mut signature_request =
+            SignatureRequest::combine(signature_requests).map_err(IdentityUpdateError::from)?;
+        let installation_public_key = self.identity().installation_keys.verifying_key();
+        let installation_member: MemberIdentifier = installation_public_key.into();
+        if signature_request
+            .missing_signatures()
+            .contains(&&installation_member)
+        {
+            let signature = self
+                .identity()
+                .installation_keys
+                .credential_sign::<InstallationKeyContext>(signature_request.signature_text())?;
+            signature_request
+                .add_signature(
+                    UnverifiedSignature::new_installation_key(signature, installation_public_key),
+                    &self.scw_verifier,
+                )
+                .await?;
+        }
+
+        Ok(signature_request)
+    }
+
     /// Revoke the given wallets from the association state for the client's inbox
     pub async fn revoke_wallets(
         &self,
@@ -310,11 +611,12 @@ where
     ) -> Result<SignatureRequest, ClientError> {
         let inbox_id = self.inbox_id();
         let current_state = retry_async!(
-            Retry::default(),
+            self.retry_policies.scoped(ErrorClass::Network),
             (async {
                 self.get_association_state(&self.store().conn()?, inbox_id, None)
                     .await
-            })
+            }),
+            LocalEvents::report_retries(&self.local_events)
         )?;
         let mut builder = SignatureRequestBuilder::new(inbox_id);
 
@@ -336,11 +638,12 @@ where
         let inbox_id = self.inbox_id();
 
         let current_state = retry_async!(
-            Retry::default(),
+            self.retry_policies.scoped(ErrorClass::Network),
             (async {
                 self.get_association_state(&self.store().conn()?, inbox_id, None)
                     .await
-            })
+            }),
+            LocalEvents::report_retries(&self.local_events)
         )?;
 
         let mut builder = SignatureRequestBuilder::new(inbox_id);
@@ -369,33 +672,119 @@ where
         &self,
         signature_request: SignatureRequest,
     ) -> Result<(), ClientError> {
-        let inbox_id = signature_request.inbox_id().to_string();
-        // If the signature request isn't completed, this will error
-        let identity_update = signature_request
-            .build_identity_update()
-            .map_err(IdentityUpdateError::from)?;
+        self.apply_signature_requests(vec![signature_request])
+            .await?
+            .into_result()
+    }
 
-        identity_update.to_verified(self.scw_verifier()).await?;
+    /// Apply several signature requests at once, such as adding a wallet right after
+    /// registering a new installation.
+    ///
+    /// Every request is verified before anything is published. The updates of an inbox are
+    /// published one after the other in the order given, the updates of different inboxes
+    /// concurrently, and the identity updates of all the inboxes are loaded once at the end. When
+    /// an update can not be published, the later updates of the same inbox are held back since
+    /// they may depend on it, and all of them are returned in [`IdentityUpdateBatch::unpublished`]
+    /// to be applied again.
+    ///
+    /// Each request is one publish. Requests of the same inbox that are not signed yet can be
+    /// published at once by combining them first with [`Self::combine_signature_requests`].
+    ///
+    /// Publishing is not retried, as an update published by a request that timed out would be
+    /// rejected when published again. Apply the unpublished requests again instead.
+    pub async fn apply_signature_requests(
+        &self,
+        signature_requests: Vec<SignatureRequest>,
+    ) -> Result<IdentityUpdateBatch, ClientError> {
+        let updates = try_join_all(
+            signature_requests
+                .iter()
+                .cloned()
+                .map(|request| async move {
+                    // If the signature request isn't completed, this will error
+                    let identity_update = request
+                        .build_identity_update()
+                        .map_err(IdentityUpdateError::from)?;
+                    identity_update.to_verified(self.scw_verifier()).await?;
+                    Ok::<_, ClientError>(identity_update)
+                }),
+        )
+        .await?;
 
-        // We don't need to validate the update, since the server will do this for us
-        self.api_client
-            .publish_identity_update(identity_update)
-            .await?;
+        let mut by_inbox: Vec<(String, Vec<(usize, UnverifiedIdentityUpdate)>)> = vec![];
+        for (index, update) in updates.into_iter().enumerate() {
+            match by_inbox
+                .iter_mut()
+                .find(|(inbox_id, _)| *inbox_id == update.inbox_id)
+            {
+                Some((_, inbox_updates)) => inbox_updates.push((index, update)),
+                None => by_inbox.push((update.inbox_id.clone(), vec![(index, update)])),
+            }
+        }
 
-        // Load the identity updates for the inbox so that we have a record in our DB
-        retry_async!(
-            Retry::default(),
-            (async {
-                load_identity_updates(
-                    &self.api_client,
-                    &self.store().conn()?,
-                    &[inbox_id.as_str()],
-                )
-                .await
-            })
-        )?;
+        // We don't need to validate the updates, since the server will do this for us
+        let outcomes = join_all(by_inbox.into_iter().map(|(inbox_id, updates)| async move {
+            let mut published = vec![];
+            for (index, update) in updates {
+                let result = self.api_client.publish_identity_update(update).await;
+                if let Err(e) = result {
+                    tracing::warn!(inbox_id, index, "failed to publish identity update: {e}");
+                    return (inbox_id, published, Some((index, ClientError::from(e))));
+                }
+                published.push(index);
+            }
+            (inbox_id, published, None)
+        }))
+        .await;
+
+        let mut published = vec![];
+        let mut published_inboxes = vec![];
+        let mut first_failure: Option<(usize, ClientError)> = None;
+        for (inbox_id, inbox_published, failure) in outcomes {
+            if !inbox_published.is_empty() {
+                published_inboxes.push(inbox_id);
+            }
+            published.extend(inbox_published);
+            if let Some((index, e)) = failure {
+                if first_failure
+                    .as_ref()
+                    .is_none_or(|(first, _)| index < *first)
+                {
+                    first_failure = Some((index, e));
+                }
+            }
+        }
+        published.sort_unstable();
+        let unpublished = signature_requests
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !published.contains(index))
+            .map(|(_, request)| request)
+            .collect();
+        let mut batch = IdentityUpdateBatch {
+            published,
+            unpublished,
+            loaded: true,
+            error: first_failure.map(|(_, e)| e),
+        };
+
+        if !published_inboxes.is_empty() {
+            let inbox_ids: Vec<&str> = published_inboxes.iter().map(String::as_str).collect();
+            // Load the identity updates for the inboxes so that we have a record in our DB
+            let loaded = retry_async!(
+                self.retry_policies.scoped(ErrorClass::Network),
+                (async {
+                    load_identity_updates(&self.api_client, &self.store().conn()?, &inbox_ids).await
+                }),
+                LocalEvents::report_retries(&self.local_events)
+            );
+            if let Err(e) = loaded {
+                batch.loaded = false;
+                batch.error.get_or_insert(e);
+            }
+        }
 
-        Ok(())
+        Ok(batch)
     }
 
     /// Given two group memberships and the diff, get the list of installations that were added or removed
@@ -436,6 +825,7 @@ where
 
         let mut added_installations: HashSet<Vec<u8>> = HashSet::new();
         let mut removed_installations: HashSet<Vec<u8>> = HashSet::new();
+        let mut identity_changes = vec![];
 
         // TODO: Do all of this in parallel
         for inbox_id in added_and_updated_members {
@@ -444,15 +834,26 @@ where
                 Some(i) => Some(*i as i64),
                 None => None,
             };
+            let ending_sequence_id = new_group_membership.get(inbox_id).map(|i| *i as i64);
             let state_diff = self
                 .get_association_state_diff(
                     conn,
                     inbox_id.as_str(),
                     starting_sequence_id,
-                    new_group_membership.get(inbox_id).map(|i| *i as i64),
+                    ending_sequence_id,
                 )
                 .await?;
 
+            // Members that were already in the group changed their identity
+            if let (Some(_), Some(sequence_id)) = (starting_sequence_id, ending_sequence_id) {
+                if inbox_id.as_str() != self.inbox_id() {
+                    identity_changes.extend(IdentityChange::from_diff(
+                        inbox_id,
+                        sequence_id,
+                        &state_diff,
+                    ));
+                }
+            }
             added_installations.extend(state_diff.new_installations());
             removed_installations.extend(state_diff.removed_installations());
         }
@@ -471,6 +872,12 @@ where
             removed_installations.extend(state_diff.new_installations());
         }
 
+        if !identity_changes.is_empty() {
+            let _ = self
+                .local_events
+                .send(LocalEvents::IdentityChanges(identity_changes));
+        }
+
         Ok(InstallationDiff {
             added_installations,
             removed_installations,
@@ -518,19 +925,65 @@ pub async fn load_identity_updates<ApiClient: XmtpApi>(
     Ok(updates)
 }
 
-/// Convert a list of unverified updates to verified updates using the given smart contract verifier
+struct LoadedAssociationState {
+    state: AssociationState,
+    /// Sequence id of the last update in the state
+    sequence_id: i64,
+    /// Whether the state was read from the cache
+    cached: bool,
+}
+
+/// Run CPU heavy work on the blocking thread pool, so that it does not hold up other tasks
+#[cfg(not(target_arch = "wasm32"))]
+async fn offload<T, F>(work: F) -> Result<T, ClientError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| ClientError::Generic(e.to_string()))
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn offload<T, F>(work: F) -> Result<T, ClientError>
+where
+    F: FnOnce() -> T,
+{
+    Ok(work())
+}
+
+/// Convert a list of unverified updates to verified updates using the given smart contract verifier.
+/// Updates signed only with installation and wallet keys are verified on the blocking thread
+/// pool, a bounded number at a time. Updates with a smart contract wallet signature wait on the
+/// network and are verified on the calling task.
 async fn verify_updates(
     updates: Vec<UnverifiedIdentityUpdate>,
     scw_verifier: impl SmartContractSignatureVerifier,
-) -> Result<Vec<IdentityUpdate>, SignatureError> {
-    try_join_all(
-        updates
-            .iter()
-            .map(|update| update.to_verified(&scw_verifier)),
-    )
+) -> Result<Vec<IdentityUpdate>, ClientError> {
+    let scw_verifier = &scw_verifier;
+    stream::iter(updates.into_iter().map(|update| async move {
+        if update.has_smart_contract_wallet_signature() {
+            return Ok(update.to_verified(scw_verifier).await?);
+        }
+        verify_offline(update).await
+    }))
+    .buffered(MAX_CONCURRENT_IDENTITY_VALIDATIONS)
+    .try_collect()
     .await
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+async fn verify_offline(update: UnverifiedIdentityUpdate) -> Result<IdentityUpdate, ClientError> {
+    let runtime = tokio::runtime::Handle::current();
+    Ok(offload(move || runtime.block_on(update.to_verified(OfflineSignatureVerifier))).await??)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn verify_offline(update: UnverifiedIdentityUpdate) -> Result<IdentityUpdate, ClientError> {
+    Ok(update.to_verified(OfflineSignatureVerifier).await?)
+}
+
 /// A static lookup method to verify if an identity is a member of an inbox
 pub async fn is_member_of_association_state<Client>(
     api_client: &ApiClientWrapper<Client>,
@@ -568,8 +1021,11 @@ where
     let updates = try_join_all(updates).await?;
 
     for update in updates {
-        association_state =
-            Some(update.update_state(association_state, update.client_timestamp_ns)?);
+        association_state = Some(update.update_state(
+            association_state,
+            update.client_timestamp_ns,
+            update.address_rules,
+        )?);
     }
     let association_state = association_state.ok_or(ClientError::Generic(
         "Unable to create association state".to_string(),
@@ -585,8 +1041,9 @@ pub(crate) mod tests {
     use xmtp_cryptography::utils::generate_local_wallet;
     use xmtp_id::{
         associations::{
-            builder::SignatureRequest, test_utils::add_wallet_signature, AssociationState,
-            MemberIdentifier,
+            builder::{SignatureRequest, SignatureRequestBuilder},
+            test_utils::add_wallet_signature,
+            AssociationState, MemberIdentifier,
         },
         scw_verifier::SmartContractSignatureVerifier,
         InboxOwner,
@@ -594,14 +1051,15 @@ pub(crate) mod tests {
 
     use crate::{
         builder::ClientBuilder,
-        groups::group_membership::GroupMembership,
+        groups::{group_membership::GroupMembership, GroupMetadataOptions},
         storage::{db_connection::DbConnection, identity_update::StoredIdentityUpdate},
+        subscriptions::LocalEvents,
         utils::test::FullXmtpClient,
         Client, XmtpApi,
     };
     use xmtp_common::rand_vec;
 
-    use super::{is_member_of_association_state, load_identity_updates};
+    use super::{is_member_of_association_state, load_identity_updates, IdentityChangeKind};
 
     async fn get_association_state<ApiClient, Verifier>(
         client: &Client<ApiClient, Verifier>,
@@ -669,6 +1127,71 @@ pub(crate) mod tests {
         assert!(is_member);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_verify_sender_consistency() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(&bola_wallet).await;
+
+        // Keys are trusted on first use
+        let consistency = amal
+            .verify_sender_consistency(bola.inbox_id())
+            .await
+            .unwrap();
+        assert!(consistency.is_consistent());
+
+        let bola2 = ClientBuilder::new_test_client(&bola_wallet).await;
+        let consistency = amal
+            .verify_sender_consistency(bola.inbox_id())
+            .await
+            .unwrap();
+        assert!(!consistency.is_consistent());
+        assert_eq!(consistency.added.len(), 1);
+        assert_eq!(
+            consistency.added[0].installation_id,
+            bola2.installation_public_key().to_vec()
+        );
+
+        amal.acknowledge_sender_keys(bola.inbox_id()).unwrap();
+        assert!(amal
+            .verify_sender_consistency(bola.inbox_id())
+            .await
+            .unwrap()
+            .is_consistent());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_identity_changes_of_members() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(&bola_wallet).await;
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bola.inbox_id()])
+            .await
+            .unwrap();
+
+        let mut events = amal.local_events.subscribe();
+        let bola2 = ClientBuilder::new_test_client(&bola_wallet).await;
+        group.update_installations().await.unwrap();
+
+        let changes = loop {
+            if let LocalEvents::IdentityChanges(changes) = events.recv().await.unwrap() {
+                break changes;
+            }
+        };
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].inbox_id, bola.inbox_id());
+        assert_eq!(
+            changes[0].kind,
+            IdentityChangeKind::InstallationAdded(bola2.installation_public_key().to_vec())
+        );
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
     async fn create_inbox_round_trip() {
@@ -731,6 +1254,175 @@ pub(crate) mod tests {
         assert!(association_state.get(&wallet_2_address.into()).is_some());
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn apply_signature_requests_in_a_batch() {
+        let wallet = generate_local_wallet();
+        let wallet_2 = generate_local_wallet();
+        let wallet_3 = generate_local_wallet();
+        let client = ClientBuilder::new_test_client(&wallet).await;
+
+        let mut add_wallet_2 = client
+            .associate_wallet(wallet_2.get_address())
+            .await
+            .unwrap();
+        add_wallet_signature(&mut add_wallet_2, &wallet_2).await;
+        let add_wallet_3 = client
+            .associate_wallet(wallet_3.get_address())
+            .await
+            .unwrap();
+
+        // Nothing is published while a request is missing signatures
+        assert!(client
+            .apply_signature_requests(vec![add_wallet_2.clone(), add_wallet_3.clone()])
+            .await
+            .is_err());
+        let association_state = get_association_state(&client, client.inbox_id()).await;
+        assert_eq!(association_state.members().len(), 2);
+
+        let mut add_wallet_3 = add_wallet_3;
+        add_wallet_signature(&mut add_wallet_3, &wallet_3).await;
+        let batch = client
+            .apply_signature_requests(vec![add_wallet_2, add_wallet_3])
+            .await
+            .unwrap();
+        assert_eq!(batch.published, vec![0, 1]);
+        assert!(batch.unpublished.is_empty());
+        assert!(batch.error.is_none());
+
+        let association_state = get_association_state(&client, client.inbox_id()).await;
+        assert_eq!(association_state.members().len(), 4);
+        assert!(association_state
+            .get(&wallet_2.get_address().into())
+            .is_some());
+        assert!(association_state
+            .get(&wallet_3.get_address().into())
+            .is_some());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn apply_signature_requests_holds_back_updates_after_a_failure() {
+        let wallet = generate_local_wallet();
+        let wallet_2 = generate_local_wallet();
+        let wallet_3 = generate_local_wallet();
+        let stranger = generate_local_wallet();
+        let client = ClientBuilder::new_test_client(&wallet).await;
+
+        // Signed by a wallet that is not a member of the inbox, so the network rejects it
+        let mut rejected = SignatureRequestBuilder::new(client.inbox_id())
+            .add_association(wallet_2.get_address().into(), stranger.get_address().into())
+            .build();
+        add_wallet_signature(&mut rejected, &stranger).await;
+        add_wallet_signature(&mut rejected, &wallet_2).await;
+        let mut add_wallet_3 = client
+            .associate_wallet(wallet_3.get_address())
+            .await
+            .unwrap();
+        add_wallet_signature(&mut add_wallet_3, &wallet_3).await;
+
+        let batch = client
+            .apply_signature_requests(vec![rejected, add_wallet_3])
+            .await
+            .unwrap();
+        assert!(batch.published.is_empty());
+        assert_eq!(batch.unpublished.len(), 2);
+        assert!(batch.error.is_some());
+        let association_state = get_association_state(&client, client.inbox_id()).await;
+        assert_eq!(association_state.members().len(), 2);
+
+        // The update held back is published once applied again
+        let held_back = batch.unpublished.into_iter().nth(1).unwrap();
+        let batch = client
+            .apply_signature_requests(vec![held_back])
+            .await
+            .unwrap();
+        assert_eq!(batch.published, vec![0]);
+        assert!(batch.loaded);
+        let association_state = get_association_state(&client, client.inbox_id()).await;
+        assert!(association_state
+            .get(&wallet_3.get_address().into())
+            .is_some());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn combined_signature_requests_are_published_once() {
+        let wallet = generate_local_wallet();
+        let wallet_2 = generate_local_wallet();
+        let wallet_3 = generate_local_wallet();
+        let client = ClientBuilder::new_test_client(&wallet).await;
+        let conn = client.store().conn().unwrap();
+        let sequence_id = conn
+            .get_latest_sequence_id_for_inbox(client.inbox_id())
+            .unwrap();
+
+        let add_wallet_2 = client
+            .associate_wallet(wallet_2.get_address())
+            .await
+            .unwrap();
+        let add_wallet_3 = client
+            .associate_wallet(wallet_3.get_address())
+            .await
+            .unwrap();
+        let mut combined = client
+            .combine_signature_requests(vec![add_wallet_2, add_wallet_3])
+            .await
+            .unwrap();
+        add_wallet_signature(&mut combined, &wallet_2).await;
+        add_wallet_signature(&mut combined, &wallet_3).await;
+        client.apply_signature_request(combined).await.unwrap();
+
+        let association_state = get_association_state(&client, client.inbox_id()).await;
+        assert_eq!(association_state.members().len(), 4);
+        let updates = conn
+            .get_identity_updates(client.inbox_id(), Some(sequence_id), None)
+            .unwrap();
+        assert_eq!(updates.len(), 1);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn compute_association_diff() {
+        let wallet = generate_local_wallet();
+        let wallet_2 = generate_local_wallet();
+        let client = ClientBuilder::new_test_client(&wallet).await;
+        let conn = client.store().conn().unwrap();
+        let first_sequence_id = conn
+            .get_latest_sequence_id_for_inbox(client.inbox_id())
+            .unwrap();
+
+        let mut add_association_request = client
+            .associate_wallet(wallet_2.get_address())
+            .await
+            .unwrap();
+        add_wallet_signature(&mut add_association_request, &wallet_2).await;
+        client
+            .apply_signature_request(add_association_request)
+            .await
+            .unwrap();
+
+        let diff = client
+            .compute_association_diff(client.inbox_id(), Some(first_sequence_id), None)
+            .await
+            .unwrap();
+        assert!(diff.to_sequence_id > first_sequence_id);
+        assert_eq!(diff.added_wallets, vec![wallet_2.get_address()]);
+        assert!(diff.removed_wallets.is_empty());
+        assert!(diff.added_installations.is_empty());
+
+        // From the creation of the inbox
+        let diff = client
+            .compute_association_diff(client.inbox_id(), None, Some(first_sequence_id))
+            .await
+            .unwrap();
+        assert_eq!(diff.added_wallets, vec![wallet.get_address()]);
+        assert_eq!(
+            diff.added_installations,
+            vec![client.installation_public_key().to_vec()]
+        );
+    }
+
     #[cfg_attr(not(target_arch = "wasm32"), test)]
     #[cfg(not(target_arch = "wasm32"))]
     fn cache_association_state() {
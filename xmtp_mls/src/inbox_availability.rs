@@ -0,0 +1,173 @@
+//! Working out the inbox id an address will create, before asking its wallet for signatures.
+//!
+//! [`check_inbox_availability`] is a dry run of creating a client: it generates the inbox id for
+//! an address and nonce, and asks the network whether the address already belongs to an inbox,
+//! and whether the inbox id was already created. Apps can show the resulting inbox id, and pick
+//! another nonce on a collision, without publishing anything.
+//!
+//! Results are cached in memory for [`INBOX_AVAILABILITY_CACHE_TTL`], since apps tend to check
+//! the same address again while the user goes through onboarding. The cache is kept per network,
+//! and cleared whenever this process registers an identity.
+use std::{collections::HashMap, sync::LazyLock};
+
+use parking_lot::Mutex;
+use xmtp_common::time::now_ns;
+use xmtp_cryptography::account_id::normalize_account_address;
+use xmtp_id::associations::generate_inbox_id;
+
+use crate::{
+    api::{ApiClientWrapper, GetIdentityUpdatesV2Filter},
+    client::ClientError,
+    configuration::INBOX_AVAILABILITY_CACHE_TTL,
+    XmtpApi,
+};
+
+/// Checks by network, address and nonce, with the time they were made
+type CheckKey = (String, String, u64);
+
+static CHECKS: LazyLock<Mutex<HashMap<CheckKey, (InboxIdCheck, i64)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Whether creating a client for an address with a nonce would create a new inbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InboxAvailability {
+    /// Nobody created the inbox yet, creating a client creates it
+    Available,
+    /// The address already created the inbox, creating a client adds an installation to it
+    Registered,
+    /// The address belongs to another inbox, created with another nonce or added as a wallet
+    AddressTaken { inbox_id: String },
+    /// The inbox was created, but the address was since removed from it. Another nonce is needed
+    /// to create a new inbox.
+    InboxTaken,
+}
+
+/// The outcome of [`check_inbox_availability`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxIdCheck {
+    /// The address, normalized
+    pub address: String,
+    pub nonce: u64,
+    /// The inbox id the address and nonce generate
+    pub inbox_id: String,
+    pub availability: InboxAvailability,
+}
+
+/// The inbox id that `address` creates with `nonce`, the same as
+/// [`xmtp_id::associations::generate_inbox_id`] after normalizing the address
+pub fn inbox_id_for(address: &str, nonce: u64) -> Result<String, ClientError> {
+    let address = normalize_account_address(address)?;
+    Ok(generate_inbox_id(&address, &nonce)?)
+}
+
+/// Find out which inbox creating a client for `address` with `nonce` would use, without
+/// publishing anything, see the [module docs](self).
+///
+/// `network` identifies the network `api_client` connects to, such as its host, so that answers
+/// from one network are not served for another.
+pub async fn check_inbox_availability<ApiClient>(
+    api_client: &ApiClientWrapper<ApiClient>,
+    network: &str,
+    address: &str,
+    nonce: u64,
+) -> Result<InboxIdCheck, ClientError>
+where
+    ApiClient: XmtpApi,
+{
+    let address = normalize_account_address(address)?;
+    let key = (network.to_lowercase(), address.clone(), nonce);
+    if let Some(check) = cached(&key) {
+        return Ok(check);
+    }
+
+    let inbox_id = generate_inbox_id(&address, &nonce)?;
+    let mut existing = api_client.get_inbox_ids(vec![address.clone()]).await?;
+    let availability = match existing.remove(&address) {
+        Some(existing) if existing == inbox_id => InboxAvailability::Registered,
+        Some(existing) => InboxAvailability::AddressTaken { inbox_id: existing },
+        None => {
+            let mut updates = api_client
+                .get_identity_updates_v2(vec![GetIdentityUpdatesV2Filter {
+                    inbox_id: inbox_id.clone(),
+                    sequence_id: None,
+                }])
+                .await?;
+            if updates
+                .remove(&inbox_id)
+                .is_some_and(|updates| !updates.is_empty())
+            {
+                InboxAvailability::InboxTaken
+            } else {
+                InboxAvailability::Available
+            }
+        }
+    };
+
+    let check = InboxIdCheck {
+        address,
+        nonce,
+        inbox_id,
+        availability,
+    };
+    CHECKS.lock().insert(key, (check.clone(), now_ns()));
+    Ok(check)
+}
+
+/// Forget the cached checks, since the answers change once a client was created. Called when an
+/// identity is registered.
+pub fn clear_inbox_availability_cache() {
+    CHECKS.lock().clear();
+}
+
+fn cached(key: &CheckKey) -> Option<InboxIdCheck> {
+    let mut checks = CHECKS.lock();
+    let expired_before = now_ns() - INBOX_AVAILABILITY_CACHE_TTL.as_nanos() as i64;
+    checks.retain(|_, (_, checked_at_ns)| *checked_at_ns >= expired_before);
+    checks.get(key).map(|(check, _)| check.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::InboxOwner;
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn checks_inbox_availability() {
+        let wallet = generate_local_wallet();
+        let address = wallet.get_address();
+        let inbox_id = inbox_id_for(&address, 1).unwrap();
+        assert_eq!(inbox_id, generate_inbox_id(&address, &1).unwrap());
+
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let check = check_inbox_availability(&client.api_client, "local", &address, 1)
+            .await
+            .unwrap();
+        assert_eq!(check.inbox_id, inbox_id);
+        assert_eq!(check.availability, InboxAvailability::Available);
+
+        // Registering an identity clears the cached answers
+        let registered = ClientBuilder::new_test_client(&wallet).await;
+        let check = check_inbox_availability(&client.api_client, "local", &address, 1)
+            .await
+            .unwrap();
+        assert_eq!(check.inbox_id, registered.inbox_id());
+        assert_eq!(check.availability, InboxAvailability::Registered);
+
+        let check = check_inbox_availability(&client.api_client, "local", &address, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            check.availability,
+            InboxAvailability::AddressTaken {
+                inbox_id: registered.inbox_id().to_string()
+            }
+        );
+    }
+}
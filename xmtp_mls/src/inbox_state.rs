@@ -0,0 +1,170 @@
+//! A snapshot of an inbox for apps to display, combining the association state with what the
+//! client knows about each installation.
+use std::collections::HashMap;
+
+use xmtp_id::{
+    associations::{AssociationState, MemberIdentifier},
+    scw_verifier::SmartContractSignatureVerifier,
+    InboxId,
+};
+
+use crate::{
+    client::ClientError, identity_updates::load_identity_updates,
+    storage::xmtp_openmls_provider::XmtpOpenMlsProvider, utils::hash::sha256,
+    verified_key_package_v2::VerifiedKeyPackageV2, Client, XmtpApi,
+};
+
+/// Whether an installation has a usable key package on the network
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyPackageStatus {
+    /// The key packages were not fetched, see [`Client::inbox_state`]
+    Unknown,
+    Valid,
+    /// The installation has no key package, so it cannot be added to groups
+    Missing,
+    /// The key package failed validation, for example because it expired
+    Invalid(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstallationInfo {
+    pub id: Vec<u8>,
+    /// Time the installation was associated with the inbox, as claimed by the client
+    pub client_timestamp_ns: Option<u64>,
+    /// Last time the installation was seen sending a message or rotating its key package
+    pub last_active_ns: Option<i64>,
+    pub key_package_status: KeyPackageStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InboxState {
+    pub inbox_id: InboxId,
+    pub recovery_address: String,
+    pub account_addresses: Vec<String>,
+    pub installations: Vec<InstallationInfo>,
+}
+
+impl InboxState {
+    pub fn installation_ids(&self) -> Vec<Vec<u8>> {
+        self.installations
+            .iter()
+            .map(|installation| installation.id.clone())
+            .collect()
+    }
+
+    fn new(state: &AssociationState, last_active: &HashMap<Vec<u8>, i64>) -> Self {
+        let installations = state
+            .members()
+            .into_iter()
+            .filter_map(|member| match member.identifier {
+                MemberIdentifier::Installation(id) => Some(InstallationInfo {
+                    last_active_ns: last_active.get(&id).copied(),
+                    id,
+                    client_timestamp_ns: member.client_timestamp_ns,
+                    key_package_status: KeyPackageStatus::Unknown,
+                }),
+                MemberIdentifier::Address(_) => None,
+            })
+            .collect();
+        Self {
+            inbox_id: state.inbox_id().to_string(),
+            recovery_address: state.recovery_address().to_string(),
+            account_addresses: state.account_addresses(),
+            installations,
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Get the [`InboxState`] of `inbox_id`.
+    ///
+    /// If `refresh_from_network` is true, the identity updates and the key packages of the
+    /// installations are fetched from the network first. Otherwise the state is read from the
+    /// local database, and the key package status is [`KeyPackageStatus::Unknown`].
+    pub async fn inbox_state(
+        &self,
+        inbox_id: &str,
+        refresh_from_network: bool,
+    ) -> Result<InboxState, ClientError> {
+        let conn = self.store().conn()?;
+        if refresh_from_network {
+            load_identity_updates(&self.api_client, &conn, &[inbox_id]).await?;
+        }
+        let association_state = self.get_association_state(&conn, inbox_id, None).await?;
+        let last_active = conn.installations_last_active(&association_state.installation_ids())?;
+        let mut state = InboxState::new(&association_state, &last_active);
+        if refresh_from_network && !state.installations.is_empty() {
+            let key_packages = self
+                .api_client
+                .fetch_key_packages(state.installation_ids())
+                .await?;
+            self.apply_key_package_status(&mut state, key_packages)?;
+        }
+        Ok(state)
+    }
+
+    fn apply_key_package_status(
+        &self,
+        state: &mut InboxState,
+        key_packages: HashMap<Vec<u8>, Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let conn = self.store().conn()?;
+        let crypto_provider = XmtpOpenMlsProvider::new_crypto();
+        for installation in state.installations.iter_mut() {
+            installation.key_package_status = match key_packages.get(&installation.id) {
+                Some(bytes) if !bytes.is_empty() => {
+                    match VerifiedKeyPackageV2::from_bytes(&crypto_provider, bytes) {
+                        Ok(_) => {
+                            conn.record_installation_key_package(&installation.id, &sha256(bytes))?;
+                            KeyPackageStatus::Valid
+                        }
+                        Err(e) => KeyPackageStatus::Invalid(e.to_string()),
+                    }
+                }
+                _ => KeyPackageStatus::Missing,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::InboxOwner;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_inbox_state_of_other_inbox() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola_wallet = generate_local_wallet();
+        let bola = ClientBuilder::new_test_client(&bola_wallet).await;
+
+        let state = amal.inbox_state(bola.inbox_id(), true).await.unwrap();
+        assert_eq!(state.inbox_id, bola.inbox_id());
+        assert_eq!(state.recovery_address, bola_wallet.get_address());
+        assert_eq!(state.account_addresses, vec![bola_wallet.get_address()]);
+        assert_eq!(state.installations.len(), 1);
+        let installation = &state.installations[0];
+        assert_eq!(installation.id, bola.installation_public_key().to_vec());
+        assert!(installation.client_timestamp_ns.is_some());
+        assert_eq!(installation.key_package_status, KeyPackageStatus::Valid);
+
+        // Without a refresh the key packages are not checked
+        let cached = amal.inbox_state(bola.inbox_id(), false).await.unwrap();
+        assert_eq!(
+            cached.installations[0].key_package_status,
+            KeyPackageStatus::Unknown
+        );
+        assert!(cached.installations[0].last_active_ns.is_some());
+    }
+}
@@ -9,7 +9,7 @@
 //! intent is fully resolved (success or failure) once it
 
 use thiserror::Error;
-use xmtp_common::RetryableError;
+use xmtp_common::{ErrorClass, RetryableError};
 
 #[derive(Debug, Error)]
 pub enum ProcessIntentError {
@@ -29,4 +29,12 @@ impl RetryableError for ProcessIntentError {
             Self::Storage(err) => err.is_retryable(),
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        match self {
+            Self::AlreadyProcessed(_) => None,
+            Self::Diesel(err) => err.error_class(),
+            Self::Storage(err) => err.error_class(),
+        }
+    }
 }
@@ -2,18 +2,30 @@
 #![warn(clippy::unwrap_used)]
 
 pub mod api;
+mod background_migrations;
 pub mod builder;
 pub mod client;
+pub mod cold_restore;
 pub mod configuration;
+pub mod data_mode;
+pub mod diagnostics;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod groups;
 mod hpke;
 pub mod identity;
 pub mod identity_updates;
+pub mod inbox_availability;
+pub mod inbox_state;
 mod intents;
 mod mutex_registry;
+pub mod signing_session;
 pub mod storage;
 mod stream_handles;
 pub mod subscriptions;
+pub mod sync_progress;
+#[cfg(all(test, feature = "test-vectors", not(target_arch = "wasm32")))]
+mod test_vectors;
 pub mod types;
 pub mod utils;
 pub mod verified_key_package_v2;
@@ -152,7 +164,8 @@ pub trait Delete<Model> {
 
 use crate::groups::GroupError;
 pub use stream_handles::{
-    spawn, AbortHandle, GenericStreamHandle, StreamHandle, StreamHandleError,
+    spawn, spawn_controlled, AbortHandle, GenericStreamHandle, StreamControl, StreamHandle,
+    StreamHandleError,
 };
 
 #[cfg(test)]
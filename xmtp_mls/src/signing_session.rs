@@ -0,0 +1,186 @@
+//! Delegated signing for identities whose wallet key is held by a backend.
+//!
+//! The device only holds its installation keys. It prepares the identity update, adds the
+//! installation key signatures, and hands the [`SigningSession::signature_text`] to the backend.
+//! The session can be serialized with [`SigningSession::to_bytes`] and stored while waiting, so
+//! that the flow survives the app being closed. Once the wallet signature arrives the session
+//! is restored, the signature is added with [`Client::add_session_signature`] and the update is
+//! published with [`Client::complete_signing_session`].
+use serde::{Deserialize, Serialize};
+use xmtp_id::{
+    associations::{
+        builder::{SignatureRequest, SignatureRequestError},
+        unverified::UnverifiedSignature,
+        MemberIdentifier,
+    },
+    scw_verifier::SmartContractSignatureVerifier,
+};
+
+use crate::{client::ClientError, identity_updates::IdentityUpdateError, Client, XmtpApi};
+
+/// What completing the session does
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SigningSessionKind {
+    /// Register the installation, see [`Client::register_identity`]
+    Registration,
+    /// Publish an identity update, see [`Client::apply_signature_request`]
+    IdentityUpdate,
+}
+
+/// An identity update waiting for signatures from another party
+#[derive(Clone, Debug)]
+pub struct SigningSession {
+    kind: SigningSessionKind,
+    request: SignatureRequest,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedSigningSession {
+    kind: SigningSessionKind,
+    request: Vec<u8>,
+}
+
+impl SigningSession {
+    pub fn new(kind: SigningSessionKind, request: SignatureRequest) -> Self {
+        Self { kind, request }
+    }
+
+    pub fn kind(&self) -> SigningSessionKind {
+        self.kind
+    }
+
+    pub fn inbox_id(&self) -> &str {
+        self.request.inbox_id()
+    }
+
+    /// The text the missing signers have to sign
+    pub fn signature_text(&self) -> String {
+        self.request.signature_text()
+    }
+
+    pub fn missing_signers(&self) -> Vec<MemberIdentifier> {
+        self.request
+            .missing_signatures()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether all the signatures were added and the session can be completed
+    pub fn is_ready(&self) -> bool {
+        self.request.is_ready()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SignatureRequestError> {
+        serde_json::to_vec(&SerializedSigningSession {
+            kind: self.kind,
+            request: self.request.to_bytes()?,
+        })
+        .map_err(|e| SignatureRequestError::Serialization(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureRequestError> {
+        let session: SerializedSigningSession = serde_json::from_slice(bytes)
+            .map_err(|e| SignatureRequestError::Serialization(e.to_string()))?;
+        Ok(Self {
+            kind: session.kind,
+            request: SignatureRequest::from_bytes(&session.request)?,
+        })
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// A session to register this installation, if it still needs a wallet signature
+    pub fn registration_session(&self) -> Option<SigningSession> {
+        self.signature_request()
+            .map(|request| SigningSession::new(SigningSessionKind::Registration, request))
+    }
+
+    /// Verify and add a signature, such as the one returned by the backend holding the wallet
+    pub async fn add_session_signature(
+        &self,
+        session: &mut SigningSession,
+        signature: UnverifiedSignature,
+    ) -> Result<(), ClientError> {
+        session
+            .request
+            .add_signature(signature, self.scw_verifier())
+            .await
+            .map_err(IdentityUpdateError::from)?;
+        Ok(())
+    }
+
+    /// Publish the identity update of a session that has all its signatures
+    pub async fn complete_signing_session(
+        &self,
+        session: SigningSession,
+    ) -> Result<(), ClientError> {
+        if !session.is_ready() {
+            return Err(IdentityUpdateError::from(SignatureRequestError::MissingSigner).into());
+        }
+        match session.kind {
+            SigningSessionKind::Registration => self.register_identity(session.request).await,
+            SigningSessionKind::IdentityUpdate => {
+                self.apply_signature_request(session.request).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+    use ethers::signers::{LocalWallet, Signer};
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::InboxOwner;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_resume_session_with_backend_signature() {
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let backend_wallet: LocalWallet = generate_local_wallet();
+        let request = client
+            .associate_wallet(backend_wallet.get_address())
+            .await
+            .unwrap();
+        let session = SigningSession::new(SigningSessionKind::IdentityUpdate, request);
+        assert_eq!(
+            session.missing_signers(),
+            vec![MemberIdentifier::Address(backend_wallet.get_address())]
+        );
+
+        // The session is stored while the backend signs
+        let bytes = session.to_bytes().unwrap();
+        let text = session.signature_text();
+        let signature = backend_wallet.sign_message(&text).await.unwrap().to_vec();
+
+        let mut session = SigningSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session.signature_text(), text);
+        client
+            .add_session_signature(
+                &mut session,
+                UnverifiedSignature::new_recoverable_ecdsa(signature),
+            )
+            .await
+            .unwrap();
+        assert!(session.is_ready());
+        client.complete_signing_session(session).await.unwrap();
+
+        let conn = client.store().conn().unwrap();
+        let state = client
+            .get_latest_association_state(&conn, client.inbox_id())
+            .await
+            .unwrap();
+        assert!(state
+            .account_addresses()
+            .contains(&backend_wallet.get_address()));
+    }
+}
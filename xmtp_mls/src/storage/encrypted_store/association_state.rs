@@ -89,6 +89,33 @@ impl StoredAssociationState {
         result
     }
 
+    /// Write several states in a single statement, ignoring the ones that are cached already
+    pub fn batch_write_to_cache(
+        conn: &DbConnection,
+        states: Vec<(InboxId, i64, AssociationState)>,
+    ) -> Result<(), StorageError> {
+        if states.is_empty() {
+            return Ok(());
+        }
+
+        let stored_states: Vec<StoredAssociationState> = states
+            .into_iter()
+            .map(|(inbox_id, sequence_id, state)| StoredAssociationState {
+                inbox_id,
+                sequence_id,
+                state: AssociationStateProto::from(state).encode_to_vec(),
+            })
+            .collect();
+        let written = conn.raw_query(|query_conn| {
+            diesel::insert_or_ignore_into(dsl::association_state)
+                .values(&stored_states)
+                .execute(query_conn)
+        })?;
+        tracing::debug!("Wrote {written} association states to cache");
+
+        Ok(())
+    }
+
     pub fn batch_read_from_cache(
         conn: &DbConnection,
         identifiers: Vec<(InboxId, i64)>,
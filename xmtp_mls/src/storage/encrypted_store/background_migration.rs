@@ -0,0 +1,339 @@
+//! Migrations that backfill data in bounded batches after startup.
+//!
+//! Schema migrations run before the store can be used, so a migration that rewrites a large
+//! table delays client initialization. Instead, the schema migration only adds the column, and
+//! a [`BackgroundMigration`] fills it in batches while the client is running. The rowid of the
+//! last migrated row is kept in the `migration_progress` table, so the backfill resumes where
+//! it stopped after a restart. The worker writes each batch in the same transaction as the
+//! progress past it, so a batch is never skipped nor applied twice.
+use diesel::{dsl::sql, prelude::*, sql_query, sql_types};
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    group::record_dm_public_id,
+    group_message::{ContentType, StoredGroupMessage},
+    message_reaction::StoredReaction,
+    schema::{
+        group_messages::dsl as messages_dsl,
+        groups::dsl as groups_dsl,
+        migration_progress::{self, dsl},
+    },
+    RawDbConnection,
+};
+use crate::storage::StorageError;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = migration_progress)]
+#[diesel(primary_key(name))]
+pub struct StoredMigrationProgress {
+    pub name: String,
+    /// Rowid of the last row migrated
+    pub cursor: i64,
+    pub completed_at_ns: Option<i64>,
+}
+
+/// A backfill run in batches by the background migration worker
+pub trait BackgroundMigration: Send + Sync {
+    /// Unique name, used as the key of the progress
+    fn name(&self) -> &'static str;
+
+    /// Migrate at most `batch_size` rows after the rowid `cursor`. Returns the rowid of the last
+    /// row migrated, or `None` once there is nothing left to migrate.
+    fn migrate_batch(
+        &self,
+        conn: &DbConnection,
+        cursor: i64,
+        batch_size: i64,
+    ) -> Result<Option<i64>, StorageError>;
+}
+
+/// Every background migration, in the order they run
+pub(crate) const BACKGROUND_MIGRATIONS: &[&dyn BackgroundMigration] =
+    &[&BackfillSentByMe, &BackfillReactions, &BackfillDmPublicIds];
+
+/// Name of [`BackfillDmPublicIds`]
+pub(super) const BACKFILL_DM_PUBLIC_IDS: &str = "backfill_dm_public_ids";
+
+/// Messages stored before `sent_by_me` was added were all marked as not sent by this inbox
+struct BackfillSentByMe;
+
+#[derive(QueryableByName)]
+struct LastRowid {
+    #[diesel(sql_type = sql_types::Nullable<sql_types::BigInt>)]
+    last_rowid: Option<i64>,
+}
+
+/// Rowid of the last message of the batch of `batch_size` messages after `cursor`
+fn last_message_rowid(
+    conn: &mut RawDbConnection,
+    cursor: i64,
+    batch_size: i64,
+) -> Result<Option<i64>, diesel::result::Error> {
+    Ok(sql_query(
+        "SELECT MAX(rowid) AS last_rowid FROM \
+         (SELECT rowid FROM group_messages WHERE rowid > ? ORDER BY rowid LIMIT ?)",
+    )
+    .bind::<sql_types::BigInt, _>(cursor)
+    .bind::<sql_types::BigInt, _>(batch_size)
+    .get_result::<LastRowid>(conn)?
+    .last_rowid)
+}
+
+impl BackgroundMigration for BackfillSentByMe {
+    fn name(&self) -> &'static str {
+        "backfill_sent_by_me"
+    }
+
+    fn migrate_batch(
+        &self,
+        conn: &DbConnection,
+        cursor: i64,
+        batch_size: i64,
+    ) -> Result<Option<i64>, StorageError> {
+        Ok(conn.raw_query(|conn| {
+            let last_rowid = last_message_rowid(conn, cursor, batch_size)?;
+            if let Some(last_rowid) = last_rowid {
+                sql_query(
+                    "UPDATE group_messages SET sent_by_me = 1 \
+                     WHERE rowid > ? AND rowid <= ? AND sent_by_me = 0 \
+                     AND sender_inbox_id = (SELECT inbox_id FROM identity)",
+                )
+                .bind::<sql_types::BigInt, _>(cursor)
+                .bind::<sql_types::BigInt, _>(last_rowid)
+                .execute(conn)?;
+            }
+            Ok::<_, diesel::result::Error>(last_rowid)
+        })?)
+    }
+}
+
+/// Reactions stored before `message_reactions` was added were never recorded there
+struct BackfillReactions;
+
+impl BackgroundMigration for BackfillReactions {
+    fn name(&self) -> &'static str {
+        "backfill_reactions"
+    }
+
+    fn migrate_batch(
+        &self,
+        conn: &DbConnection,
+        cursor: i64,
+        batch_size: i64,
+    ) -> Result<Option<i64>, StorageError> {
+        let batch = conn.raw_query(|conn| {
+            let Some(last_rowid) = last_message_rowid(conn, cursor, batch_size)? else {
+                return Ok(None);
+            };
+            let reactions: Vec<StoredGroupMessage> = messages_dsl::group_messages
+                .filter(messages_dsl::content_type.eq(ContentType::Reaction))
+                .filter(
+                    sql::<sql_types::Bool>("rowid > ")
+                        .bind::<sql_types::BigInt, _>(cursor)
+                        .sql(" AND rowid <= ")
+                        .bind::<sql_types::BigInt, _>(last_rowid),
+                )
+                .load(conn)?;
+            Ok::<_, diesel::result::Error>(Some((last_rowid, reactions)))
+        })?;
+        let Some((last_rowid, reactions)) = batch else {
+            return Ok(None);
+        };
+        let reactions = conn.open_messages(reactions)?;
+        for reaction in reactions.iter().filter_map(StoredReaction::from_message) {
+            conn.record_reaction(&reaction)?;
+        }
+        Ok(Some(last_rowid))
+    }
+}
+
+/// DMs stored before `dm_public_ids` was added have no recorded hash
+struct BackfillDmPublicIds;
+
+impl BackgroundMigration for BackfillDmPublicIds {
+    fn name(&self) -> &'static str {
+        BACKFILL_DM_PUBLIC_IDS
+    }
+
+    fn migrate_batch(
+        &self,
+        conn: &DbConnection,
+        cursor: i64,
+        batch_size: i64,
+    ) -> Result<Option<i64>, StorageError> {
+        Ok(conn.raw_query(|conn| {
+            let batch: Vec<(i64, Option<String>)> = groups_dsl::groups
+                .select((sql::<sql_types::BigInt>("rowid"), groups_dsl::dm_id))
+                .filter(sql::<sql_types::Bool>("rowid > ").bind::<sql_types::BigInt, _>(cursor))
+                .order(sql::<sql_types::BigInt>("rowid"))
+                .limit(batch_size)
+                .load(conn)?;
+            for (_, dm_id) in &batch {
+                record_dm_public_id(conn, dm_id.as_deref())?;
+            }
+            Ok::<_, diesel::result::Error>(batch.last().map(|(rowid, _)| *rowid))
+        })?)
+    }
+}
+
+impl DbConnection {
+    pub fn get_migration_progress(
+        &self,
+        name: &str,
+    ) -> Result<Option<StoredMigrationProgress>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::migration_progress.find(name).first(conn).optional())?)
+    }
+
+    /// Whether the migration `name` is complete
+    pub fn background_migration_complete(&self, name: &str) -> Result<bool, StorageError> {
+        Ok(self
+            .get_migration_progress(name)?
+            .is_some_and(|progress| progress.completed_at_ns.is_some()))
+    }
+
+    fn set_migration_progress(
+        &self,
+        progress: &StoredMigrationProgress,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::migration_progress)
+                .values(progress)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Mark the migration `name` complete, for backfills that need more than the store and are
+    /// run by the worker itself
+    pub fn complete_migration(&self, name: &str) -> Result<(), StorageError> {
+        self.set_migration_progress(&StoredMigrationProgress {
+            name: name.to_string(),
+            cursor: 0,
+            completed_at_ns: Some(now_ns()),
+        })
+    }
+
+    /// Run the next batch of `migration`. Returns `true` once the migration is complete.
+    ///
+    /// Run it in a transaction, so that the rows of the batch and the progress past them are
+    /// written together.
+    pub fn run_background_migration_batch(
+        &self,
+        migration: &dyn BackgroundMigration,
+        batch_size: i64,
+    ) -> Result<bool, StorageError> {
+        let mut progress = self
+            .get_migration_progress(migration.name())?
+            .unwrap_or_else(|| StoredMigrationProgress {
+                name: migration.name().to_string(),
+                cursor: 0,
+                completed_at_ns: None,
+            });
+        if progress.completed_at_ns.is_some() {
+            return Ok(true);
+        }
+        match migration.migrate_batch(self, progress.cursor, batch_size)? {
+            Some(cursor) => progress.cursor = cursor,
+            None => progress.completed_at_ns = Some(now_ns()),
+        }
+        self.set_migration_progress(&progress)?;
+        Ok(progress.completed_at_ns.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::{
+        group::tests::{generate_dm, generate_group},
+        group_message::tests::generate_message,
+        identity::StoredIdentity,
+        schema::{dm_public_ids::dsl as dm_public_ids_dsl, groups},
+        tests::with_connection,
+    };
+    use crate::{Fetch, Store};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_backfills_sent_by_me_in_batches() {
+        with_connection(|conn| {
+            StoredIdentity::new("me".to_string(), vec![1], vec![2])
+                .store(conn)
+                .unwrap();
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let mut ids = vec![];
+            for i in 0..5 {
+                let mut message = generate_message(None, Some(&group.id), None, None);
+                message.sender_inbox_id = if i % 2 == 0 { "me" } else { "peer" }.to_string();
+                message.sent_by_me = false;
+                message.store(conn).unwrap();
+                ids.push((message.id, i % 2 == 0));
+            }
+
+            let mut batches = 0;
+            while !conn
+                .run_background_migration_batch(&BackfillSentByMe, 2)
+                .unwrap()
+            {
+                batches += 1;
+            }
+            // 3 batches of messages, then the one that finds nothing left
+            assert_eq!(batches, 3);
+            assert!(conn
+                .get_migration_progress("backfill_sent_by_me")
+                .unwrap()
+                .unwrap()
+                .completed_at_ns
+                .is_some());
+
+            for (id, sent_by_me) in ids {
+                let message: crate::storage::group_message::StoredGroupMessage =
+                    conn.fetch(&id).unwrap().unwrap();
+                assert_eq!(message.sent_by_me, sent_by_me);
+            }
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_backfills_dm_public_ids() {
+        with_connection(|conn| {
+            // A DM stored before the hashes were recorded
+            let dm = generate_dm(None);
+            conn.raw_query(|conn| diesel::insert_into(groups::table).values(&dm).execute(conn))
+                .unwrap();
+            generate_group(None).store(conn).unwrap();
+            // found by hashing every dm_id until the backfill completes
+            assert!(conn
+                .find_group_by_public_id(&dm.public_id())
+                .unwrap()
+                .is_some());
+
+            while !conn
+                .run_background_migration_batch(&BackfillDmPublicIds, 1)
+                .unwrap()
+            {}
+            let recorded: Vec<String> = conn
+                .raw_query(|conn| {
+                    dm_public_ids_dsl::dm_public_ids
+                        .select(dm_public_ids_dsl::dm_id)
+                        .load(conn)
+                })
+                .unwrap();
+            assert_eq!(recorded, vec![dm.dm_id.clone().unwrap()]);
+            assert_eq!(
+                conn.find_group_by_public_id(&dm.public_id())
+                    .unwrap()
+                    .unwrap()
+                    .id,
+                dm.id
+            );
+        })
+        .await
+    }
+}
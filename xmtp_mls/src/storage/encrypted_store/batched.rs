@@ -0,0 +1,111 @@
+//! Iterators over large query results, loaded from the database in bounded batches.
+//!
+//! Each batch is a separate query that continues after the key of the last row of the previous
+//! batch, so that at most one batch of rows is held in memory at a time and rows written while
+//! iterating are picked up if they sort after the cursor. The connection is only held while a
+//! batch is loaded. Paginated queries use [`Batched`] rather than their own cursor, e.g.
+//! [`DbConnection::messages_in_window`](super::db_connection::DbConnection::messages_in_window).
+use crate::storage::StorageError;
+
+/// Rows loaded per query unless the caller sets a batch size
+pub const DEFAULT_BATCH_SIZE: i64 = 1_000;
+
+/// Iterator over the rows of a query, see the [module docs](self)
+pub struct Batched<T, K, F> {
+    next_batch: F,
+    key: fn(&T) -> K,
+    batch_size: i64,
+    batch: std::vec::IntoIter<T>,
+    cursor: Option<K>,
+    done: bool,
+}
+
+impl<T, K, F> Batched<T, K, F>
+where
+    F: FnMut(Option<&K>, i64) -> Result<Vec<T>, StorageError>,
+{
+    /// Iterate over the rows returned by `next_batch`, called with the key of the last row loaded
+    /// so far and the number of rows to load. `key` must follow the order of the query.
+    pub fn new(batch_size: i64, key: fn(&T) -> K, next_batch: F) -> Self {
+        Self {
+            next_batch,
+            key,
+            batch_size: batch_size.max(1),
+            batch: Vec::new().into_iter(),
+            cursor: None,
+            done: false,
+        }
+    }
+
+    /// Load `batch_size` rows per query instead, if no batch was loaded yet
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl<T, K, F> Iterator for Batched<T, K, F>
+where
+    F: FnMut(Option<&K>, i64) -> Result<Vec<T>, StorageError>,
+{
+    type Item = Result<T, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(row) = self.batch.next() {
+            return Some(Ok(row));
+        }
+        if self.done {
+            return None;
+        }
+        let batch = match (self.next_batch)(self.cursor.as_ref(), self.batch_size) {
+            Ok(batch) => batch,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        self.done = (batch.len() as i64) < self.batch_size;
+        if let Some(last) = batch.last() {
+            self.cursor = Some((self.key)(last));
+        }
+        self.batch = batch.into_iter();
+        self.batch.next().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_rows_in_batches() {
+        let rows: Vec<i64> = (0..7).collect();
+        let mut queries = 0;
+        let batched = Batched::new(
+            3,
+            |row: &i64| *row,
+            |cursor: Option<&i64>, limit| {
+                queries += 1;
+                Ok(rows
+                    .iter()
+                    .copied()
+                    .filter(|row| cursor.map_or(true, |cursor| row > cursor))
+                    .take(limit as usize)
+                    .collect())
+            },
+        );
+        assert_eq!(batched.collect::<Result<Vec<_>, _>>().unwrap(), rows);
+        assert_eq!(queries, 3);
+    }
+
+    #[test]
+    fn stops_after_an_error() {
+        let mut batched = Batched::new(
+            2,
+            |row: &i64| *row,
+            |_: Option<&i64>, _| Err(StorageError::PoolNeedsConnection),
+        );
+        assert!(batched.next().unwrap().is_err());
+        assert!(batched.next().is_none());
+    }
+}
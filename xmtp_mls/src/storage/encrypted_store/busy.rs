@@ -0,0 +1,172 @@
+//! Waiting on a locked database without blocking the async runtime.
+//!
+//! SQLite blocks the calling thread for up to `busy_timeout` when another connection holds the
+//! write lock, which stalls every task sharing that runtime thread. Connections only wait
+//! [`BUSY_TIMEOUT_MS`], enough to ride out the short writes that make up most contention.
+//! Transactions that still find the database busy, typically behind a long-running write, go
+//! through [`retry_while_busy`] when they run on the async runtime. It sleeps on the runtime
+//! between attempts with an increasing backoff, so that other tasks can make progress, until
+//! [`BUSY_DEADLINE`] has passed since the first attempt.
+use std::{error::Error as StdError, future::Future, time::Duration};
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use xmtp_common::time::{now_ns, sleep};
+
+/// How long a single statement waits on a lock before SQLite reports the database as busy
+pub const BUSY_TIMEOUT_MS: u32 = 50;
+/// How long [`retry_while_busy`] keeps retrying an operation that found the database busy
+pub const BUSY_DEADLINE: Duration = Duration::from_secs(10);
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_millis(250);
+
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+/// The `PRAGMA` every native connection is customized with
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) fn busy_timeout_pragma() -> String {
+    format!("PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};")
+}
+
+/// The message SQLite reports for the primary result `code`.
+///
+/// Diesel maps every result code besides constraint violations to
+/// [`DatabaseErrorKind::Unknown`], keeping only the message SQLite attached to it. For
+/// `SQLITE_BUSY` and `SQLITE_LOCKED` that is always the generic message of the code.
+#[cfg(not(target_arch = "wasm32"))]
+fn result_code_message(code: i32) -> std::borrow::Cow<'static, str> {
+    // SAFETY: sqlite3_errstr returns a pointer to a static, nul-terminated string
+    unsafe { std::ffi::CStr::from_ptr(libsqlite3_sys::sqlite3_errstr(code)) }.to_string_lossy()
+}
+
+/// The messages of sqlite3ErrStr, which the browser build links without exposing
+#[cfg(target_arch = "wasm32")]
+fn result_code_message(code: i32) -> std::borrow::Cow<'static, str> {
+    match code {
+        SQLITE_BUSY => "database is locked".into(),
+        SQLITE_LOCKED => "database table is locked".into(),
+        _ => "unknown error".into(),
+    }
+}
+
+/// Whether `error` is SQLite failing with `SQLITE_BUSY` or `SQLITE_LOCKED`
+pub fn is_busy_result(error: &DieselError) -> bool {
+    match error {
+        DieselError::DatabaseError(DatabaseErrorKind::Unknown, info) => {
+            [SQLITE_BUSY, SQLITE_LOCKED]
+                .into_iter()
+                .any(|code| info.message() == result_code_message(code))
+        }
+        _ => false,
+    }
+}
+
+/// Whether `error`, or any error it was caused by, is a [busy](is_busy_result) database
+pub fn is_busy(error: &(dyn StdError + 'static)) -> bool {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        if error
+            .downcast_ref::<DieselError>()
+            .is_some_and(is_busy_result)
+        {
+            return true;
+        }
+        next = error.source();
+    }
+    false
+}
+
+/// Run `op` until it does not fail with a [busy](is_busy) database, yielding to the runtime
+/// between attempts. The last error is returned once [`BUSY_DEADLINE`] has passed.
+pub async fn retry_while_busy<T, E, F, Fut>(mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: StdError + 'static,
+{
+    let deadline = now_ns() + BUSY_DEADLINE.as_nanos() as i64;
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match op().await {
+            Err(e) if is_busy(&e) && now_ns() + (backoff.as_nanos() as i64) < deadline => {
+                tracing::debug!("database is busy, retrying in {}ms", backoff.as_millis());
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::StorageError;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn database_error(kind: DatabaseErrorKind, message: &str) -> StorageError {
+        DieselError::DatabaseError(kind, Box::new(message.to_string())).into()
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn retries_busy_operations() {
+        let mut attempts = 0;
+        let result = retry_while_busy(|| {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    Err(database_error(
+                        DatabaseErrorKind::Unknown,
+                        &result_code_message(SQLITE_BUSY),
+                    ))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        // other errors are returned right away
+        let mut attempts = 0;
+        let result: Result<(), _> = retry_while_busy(|| {
+            attempts += 1;
+            async {
+                Err(database_error(
+                    DatabaseErrorKind::UniqueViolation,
+                    "UNIQUE constraint failed",
+                ))
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn classifies_a_locked_database() {
+        use diesel::{connection::SimpleConnection, Connection, SqliteConnection};
+
+        let path = xmtp_common::tmp_path();
+        let mut writer = SqliteConnection::establish(&path).unwrap();
+        writer
+            .batch_execute("CREATE TABLE t (x INTEGER); BEGIN IMMEDIATE; INSERT INTO t VALUES (1);")
+            .unwrap();
+        let mut other = SqliteConnection::establish(&path).unwrap();
+        let error = other
+            .batch_execute("PRAGMA busy_timeout = 0; INSERT INTO t VALUES (2);")
+            .unwrap_err();
+        assert!(is_busy_result(&error));
+        assert!(is_busy(&StorageError::from(error)));
+
+        let error = other.batch_execute("SELECT * FROM missing;").unwrap_err();
+        assert!(!is_busy_result(&error));
+        writer.batch_execute("COMMIT;").unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+}
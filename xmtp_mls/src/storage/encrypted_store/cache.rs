@@ -0,0 +1,432 @@
+//! Optional in-memory cache for rows that are read far more often than they are written.
+//!
+//! The cache is only filled by reads made outside of a transaction, so it never holds a value
+//! that a transaction wrote and later rolled back. Writes don't go through the cache: every
+//! connection of the store reports the statements it runs through a
+//! [`CacheInstrumentation`], and a statement writing to a cached table drops that table from the
+//! cache when it starts, when it finishes, and again when its transaction commits or rolls back.
+//! Reads that were running meanwhile are not cached, see [`StoreCache::generation`].
+//!
+//! Invalidation works on whole tables, from the SQL of the statement, so it covers writes made
+//! through any query, including the writes of triggers listed in [`CachedTable::written_by`].
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use parking_lot::RwLock;
+
+use super::{
+    consent_record::{ConsentType, StoredConsentRecord},
+    group::StoredGroup,
+    identity::StoredIdentity,
+};
+
+/// Upper bound on the number of groups kept in the cache.
+/// Once reached, the group cache is cleared and refilled from subsequent reads.
+const MAX_CACHED_GROUPS: usize = 1024;
+
+/// Length of the start of a statement read to find the table it writes to
+const STATEMENT_HEAD_LEN: usize = 96;
+
+/// A table with rows kept in the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CachedTable {
+    Identity,
+    Groups,
+    ConsentRecords,
+}
+
+impl CachedTable {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "identity" => Some(Self::Identity),
+            "groups" => Some(Self::Groups),
+            "consent_records" => Some(Self::ConsentRecords),
+            _ => None,
+        }
+    }
+
+    /// The cached tables written by a statement writing to `table`, directly or through a
+    /// trigger
+    pub fn written_by(table: &str) -> Vec<Self> {
+        match table {
+            // The `msg_inserted` trigger bumps `groups.last_message_ns`
+            "group_messages" => vec![Self::Groups],
+            table => Self::from_name(table).into_iter().collect(),
+        }
+    }
+
+    /// The cached tables that `sql` writes to, read from the start of the statement
+    pub fn written_by_statement(sql: &str) -> Vec<Self> {
+        let mut tokens = sql
+            .split(|c: char| c.is_whitespace() || c == '(')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.trim_matches(|c| c == '`' || c == '"' || c == '\''));
+        let Some(verb) = tokens.next() else {
+            return vec![];
+        };
+        let table = if verb.eq_ignore_ascii_case("update") {
+            tokens.find(|token| !token.eq_ignore_ascii_case("or") && !is_conflict_clause(token))
+        } else if verb.eq_ignore_ascii_case("insert")
+            || verb.eq_ignore_ascii_case("replace")
+            || verb.eq_ignore_ascii_case("delete")
+        {
+            tokens
+                .by_ref()
+                .find(|token| {
+                    token.eq_ignore_ascii_case("into") || token.eq_ignore_ascii_case("from")
+                })
+                .and_then(|_| tokens.next())
+        } else {
+            None
+        };
+        table.map(Self::written_by).unwrap_or_default()
+    }
+}
+
+fn is_conflict_clause(token: &str) -> bool {
+    ["rollback", "abort", "replace", "fail", "ignore"]
+        .iter()
+        .any(|clause| token.eq_ignore_ascii_case(clause))
+}
+
+type ConsentKey = (ConsentType, String);
+
+#[derive(Debug, Default)]
+pub struct StoreCache {
+    /// Incremented every time a table is dropped from the cache
+    generation: AtomicU64,
+    identity: RwLock<Option<StoredIdentity>>,
+    groups: RwLock<HashMap<Vec<u8>, StoredGroup>>,
+    /// `None` caches the absence of a consent record, which is the common case
+    consent: RwLock<HashMap<ConsentKey, Option<StoredConsentRecord>>>,
+}
+
+impl StoreCache {
+    /// Taken before reading a row from the database, and passed back when caching it. If a
+    /// table was dropped from the cache meanwhile, the row may be outdated and isn't cached.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn invalidate(&self, table: CachedTable) {
+        match table {
+            CachedTable::Identity => {
+                let mut identity = self.identity.write();
+                self.generation.fetch_add(1, Ordering::SeqCst);
+                identity.take();
+            }
+            CachedTable::Groups => {
+                let mut groups = self.groups.write();
+                self.generation.fetch_add(1, Ordering::SeqCst);
+                groups.clear();
+            }
+            CachedTable::ConsentRecords => {
+                let mut consent = self.consent.write();
+                self.generation.fetch_add(1, Ordering::SeqCst);
+                consent.clear();
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.invalidate(CachedTable::Identity);
+        self.invalidate(CachedTable::Groups);
+        self.invalidate(CachedTable::ConsentRecords);
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        self.generation() == generation
+    }
+
+    pub fn identity(&self) -> Option<StoredIdentity> {
+        self.identity.read().clone()
+    }
+
+    pub fn put_identity(&self, generation: u64, identity: &StoredIdentity) {
+        let mut cached = self.identity.write();
+        if self.is_current(generation) {
+            *cached = Some(identity.clone());
+        }
+    }
+
+    pub fn group(&self, id: &[u8]) -> Option<StoredGroup> {
+        self.groups.read().get(id).cloned()
+    }
+
+    pub fn put_group(&self, generation: u64, group: &StoredGroup) {
+        let mut groups = self.groups.write();
+        if !self.is_current(generation) {
+            return;
+        }
+        if groups.len() >= MAX_CACHED_GROUPS && !groups.contains_key(&group.id) {
+            groups.clear();
+        }
+        groups.insert(group.id.clone(), group.clone());
+    }
+
+    /// `Some(None)` if the record is known not to exist
+    pub fn consent_record(
+        &self,
+        entity_type: ConsentType,
+        entity: &str,
+    ) -> Option<Option<StoredConsentRecord>> {
+        self.consent
+            .read()
+            .get(&(entity_type, entity.to_string()))
+            .cloned()
+    }
+
+    pub fn put_consent_record(
+        &self,
+        generation: u64,
+        entity_type: ConsentType,
+        entity: &str,
+        record: Option<&StoredConsentRecord>,
+    ) {
+        let mut consent = self.consent.write();
+        if self.is_current(generation) {
+            consent.insert((entity_type, entity.to_string()), record.cloned());
+        }
+    }
+}
+
+/// Collects the start of a statement, stopping the formatting of the rest of it
+struct StatementHead(String);
+
+impl Write for StatementHead {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = STATEMENT_HEAD_LEN.saturating_sub(self.0.len());
+        if s.len() <= room {
+            self.0.push_str(s);
+            return Ok(());
+        }
+        let mut end = room;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.0.push_str(&s[..end]);
+        Err(fmt::Error)
+    }
+}
+
+fn statement_head(query: &(impl fmt::Display + ?Sized)) -> String {
+    let mut head = StatementHead(String::with_capacity(STATEMENT_HEAD_LEN));
+    // Stopping early is reported as an error
+    let _ = write!(head, "{query}");
+    head.0
+}
+
+/// Drops the tables a connection writes to from the cache, see the [module docs](self)
+pub struct CacheInstrumentation {
+    cache: Arc<StoreCache>,
+    /// Tables written by the statement running
+    writing: Vec<CachedTable>,
+    /// Tables written by the open transaction
+    written: Vec<CachedTable>,
+    in_transaction: bool,
+    ending_transaction: bool,
+}
+
+impl CacheInstrumentation {
+    pub fn new(cache: Arc<StoreCache>) -> Self {
+        Self {
+            cache,
+            writing: vec![],
+            written: vec![],
+            in_transaction: false,
+            ending_transaction: false,
+        }
+    }
+
+    fn start(&mut self, sql: &str) {
+        let sql = sql.trim_start();
+        let verb = sql.split_whitespace().next().unwrap_or_default();
+        if verb.eq_ignore_ascii_case("begin") {
+            self.in_transaction = true;
+            return;
+        }
+        // `ROLLBACK TO SAVEPOINT` and `RELEASE SAVEPOINT` end nested transactions only
+        let ends_transaction = (verb.eq_ignore_ascii_case("commit")
+            || verb.eq_ignore_ascii_case("end")
+            || verb.eq_ignore_ascii_case("rollback"))
+            && !sql.to_ascii_lowercase().contains(" to ");
+        if ends_transaction {
+            self.ending_transaction = true;
+            return;
+        }
+        self.writing = CachedTable::written_by_statement(sql);
+        for table in &self.writing {
+            self.cache.invalidate(*table);
+        }
+    }
+
+    fn finish(&mut self) {
+        for table in std::mem::take(&mut self.writing) {
+            self.cache.invalidate(table);
+            if self.in_transaction && !self.written.contains(&table) {
+                self.written.push(table);
+            }
+        }
+        if self.ending_transaction {
+            // Reads made before the transaction ended may have seen the previous rows
+            for table in std::mem::take(&mut self.written) {
+                self.cache.invalidate(table);
+            }
+            self.in_transaction = false;
+            self.ending_transaction = false;
+        }
+    }
+}
+
+impl Instrumentation for CacheInstrumentation {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { query, .. } => self.start(&statement_head(query)),
+            InstrumentationEvent::FinishQuery { .. } => self.finish(),
+            InstrumentationEvent::BeginTransaction { depth, .. } if depth.get() == 1 => {
+                self.in_transaction = true;
+            }
+            InstrumentationEvent::CommitTransaction { depth, .. }
+            | InstrumentationEvent::RollbackTransaction { depth, .. }
+                if depth.get() == 1 =>
+            {
+                self.ending_transaction = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::{
+            consent_record::ConsentState, group::tests::generate_group, EncryptedMessageStore,
+            ProviderTransactions, StorageError, StorageOption,
+        },
+        Fetch, Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::rand_vec;
+
+    async fn cached_store() -> EncryptedMessageStore {
+        EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap()
+        .with_cache()
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn finds_the_tables_written_by_statements() {
+        use CachedTable::*;
+        let cases = [
+            ("INSERT INTO `groups` (`id`) VALUES (?)", vec![Groups]),
+            (
+                "INSERT OR IGNORE INTO `consent_records` (",
+                vec![ConsentRecords],
+            ),
+            ("UPDATE `groups` SET `rotated_at_ns` = ?", vec![Groups]),
+            ("UPDATE OR REPLACE identity SET", vec![Identity]),
+            ("DELETE FROM `identity`", vec![Identity]),
+            ("REPLACE INTO consent_records", vec![ConsentRecords]),
+            ("INSERT INTO `group_messages` (`id`)", vec![Groups]),
+            ("SELECT * FROM `groups`", vec![]),
+            ("UPDATE `group_intents` SET", vec![]),
+        ];
+        for (sql, tables) in cases {
+            assert_eq!(CachedTable::written_by_statement(sql), tables, "{sql}");
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn reads_fill_the_cache_and_writes_drop_it() {
+        let store = cached_store().await;
+        let conn = store.conn().unwrap();
+        let cache = conn.cache().unwrap();
+
+        let identity = StoredIdentity::new("inbox".to_string(), rand_vec::<24>(), rand_vec::<24>());
+        identity.store(&conn).unwrap();
+        assert!(cache.identity().is_none());
+        let fetched: StoredIdentity = conn.fetch(&()).unwrap().unwrap();
+        assert_eq!(fetched.inbox_id, "inbox");
+        assert_eq!(cache.identity().unwrap().inbox_id, "inbox");
+
+        let group = generate_group(None);
+        group.store(&conn).unwrap();
+        assert!(conn.find_group(&group.id).unwrap().is_some());
+        assert!(cache.group(&group.id).is_some());
+        // No explicit invalidation, the update statement drops the cached groups
+        conn.update_rotated_at_ns(group.id.clone()).unwrap();
+        assert!(cache.group(&group.id).is_none());
+        assert!(conn.find_group(&group.id).unwrap().is_some());
+        assert!(cache.group(&group.id).is_some());
+
+        assert!(conn
+            .get_consent_record("entity".to_string(), ConsentType::InboxId)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            cache.consent_record(ConsentType::InboxId, "entity"),
+            Some(None)
+        );
+        let record = StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Allowed,
+            "entity".to_string(),
+        );
+        conn.insert_or_replace_consent_records(&[record.clone()])
+            .unwrap();
+        assert_eq!(cache.consent_record(ConsentType::InboxId, "entity"), None);
+        assert_eq!(
+            conn.get_consent_record("entity".to_string(), ConsentType::InboxId)
+                .unwrap(),
+            Some(record)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn rolled_back_writes_are_never_cached() {
+        let store = cached_store().await;
+        let provider = store.mls_provider().unwrap();
+        let cache = provider.conn_ref().cache().unwrap();
+        let group = generate_group(None);
+
+        let result: Result<(), StorageError> = provider.transaction(|provider| {
+            let conn = provider.conn_ref();
+            group.store(conn)?;
+            // Reads in a transaction may see uncommitted rows, and don't fill the cache
+            assert!(conn.find_group(&group.id)?.is_some());
+            assert!(cache.group(&group.id).is_none());
+            Err(StorageError::Deserialization("roll back".to_string()))
+        });
+        assert!(result.is_err());
+        assert!(cache.group(&group.id).is_none());
+        assert!(provider.conn_ref().find_group(&group.id).unwrap().is_none());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn outdated_reads_are_not_cached() {
+        let cache = StoreCache::default();
+        let group = generate_group(None);
+        let generation = cache.generation();
+        cache.invalidate(CachedTable::Groups);
+        cache.put_group(generation, &group);
+        assert!(cache.group(&group.id).is_none());
+        cache.put_group(cache.generation(), &group);
+        assert!(cache.group(&group.id).is_some());
+    }
+}
@@ -0,0 +1,128 @@
+//! SQLCipher tuning parameters
+//!
+//! Most of these parameters are baked into the database file when it is created,
+//! so a database must always be opened with the same values it was created with.
+//! On native platforms the resolved parameters are persisted next to the database
+//! and re-used on subsequent opens.
+use serde::{Deserialize, Serialize};
+
+use crate::storage::SqlCipherError;
+
+/// The number of plaintext header bytes we leave unencrypted by default.
+/// This is what allows iOS to identify the file as a SQLite database
+/// and keep the file lock while the app is suspended.
+pub const DEFAULT_PLAINTEXT_HEADER_SIZE: u32 = 32;
+
+/// SQLCipher tuning parameters. `None` uses the SQLCipher default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherOptions {
+    /// Number of PBKDF2 iterations used to derive the page key (`PRAGMA kdf_iter`)
+    pub kdf_iter: Option<u32>,
+    /// Database page size in bytes (`PRAGMA cipher_page_size`)
+    pub page_size: Option<u32>,
+    /// Wipe memory allocated by SQLCipher on free (`PRAGMA cipher_memory_security`)
+    pub memory_security: Option<bool>,
+    /// Number of database header bytes left unencrypted (`PRAGMA cipher_plaintext_header_size`).
+    /// `0` encrypts the whole header, which prevents iOS from opening the database
+    /// while the device is locked.
+    pub plaintext_header_size: u32,
+}
+
+impl Default for CipherOptions {
+    fn default() -> Self {
+        Self {
+            kdf_iter: None,
+            page_size: None,
+            memory_security: None,
+            plaintext_header_size: DEFAULT_PLAINTEXT_HEADER_SIZE,
+        }
+    }
+}
+
+impl CipherOptions {
+    /// Check that these options are accepted by SQLCipher
+    pub fn validate(&self) -> Result<(), SqlCipherError> {
+        let invalid = |reason: String| Err(SqlCipherError::InvalidCipherOptions(reason));
+
+        if let Some(0) = self.kdf_iter {
+            return invalid("kdf_iter must be at least 1".into());
+        }
+        let page_size = self.page_size.unwrap_or(4096);
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return invalid(format!(
+                "page_size must be a power of two between 512 and 65536, got {page_size}"
+            ));
+        }
+        let header = self.plaintext_header_size;
+        if header % 16 != 0 || header >= page_size {
+            return invalid(format!(
+                "plaintext_header_size must be a multiple of 16 smaller than the page size, got {header}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `true` if the database header is left unencrypted.
+    /// The salt is then kept in a file next to the database instead of in the header.
+    pub fn has_plaintext_header(&self) -> bool {
+        self.plaintext_header_size > 0
+    }
+
+    /// PRAGMAs to run after `PRAGMA key` and before the first database access
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn pragmas(&self) -> String {
+        format!(
+            "{}\nPRAGMA cipher_plaintext_header_size = {};",
+            self.settings_pragmas(),
+            self.plaintext_header_size
+        )
+    }
+
+    /// PRAGMAs for every parameter except the plaintext header size
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn settings_pragmas(&self) -> String {
+        let mut pragmas = Vec::new();
+        if let Some(kdf_iter) = self.kdf_iter {
+            pragmas.push(format!("PRAGMA kdf_iter = {kdf_iter};"));
+        }
+        if let Some(page_size) = self.page_size {
+            pragmas.push(format!("PRAGMA cipher_page_size = {page_size};"));
+        }
+        if let Some(memory_security) = self.memory_security {
+            let value = if memory_security { "ON" } else { "OFF" };
+            pragmas.push(format!("PRAGMA cipher_memory_security = {value};"));
+        }
+        pragmas.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_options_are_valid() {
+        CipherOptions::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let page_size = CipherOptions {
+            page_size: Some(1000),
+            ..Default::default()
+        };
+        assert!(page_size.validate().is_err());
+
+        let header = CipherOptions {
+            plaintext_header_size: 20,
+            ..Default::default()
+        };
+        assert!(header.validate().is_err());
+
+        let kdf = CipherOptions {
+            kdf_iter: Some(0),
+            ..Default::default()
+        };
+        assert!(kdf.validate().is_err());
+    }
+}
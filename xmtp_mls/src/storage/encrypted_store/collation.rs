@@ -0,0 +1,191 @@
+//! Unicode-aware comparison of text, so that sorting and searching match what users expect
+//! outside of English: `Éclair` sorts next to `eclair`, and searching for `cafe` finds `Café`.
+//!
+//! Text is folded by decomposing it (NFKD), dropping the combining marks such as accents, and
+//! lowercasing it. The folding is registered on every connection as the [`UNICODE_COLLATION`]
+//! collation and the `unicode_fold` SQL function, so queries order and match text the same way
+//! as [`unicode_cmp`] does in Rust.
+use std::cmp::Ordering;
+
+use diesel::{
+    prelude::*,
+    sql_types::{Binary, Nullable, Text},
+    sqlite::SqliteConnection,
+};
+use prost::Message;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{
+    db_connection::DbConnection,
+    extensions::SqliteExtension,
+    group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+    schema::group_messages::dsl,
+};
+use crate::storage::StorageError;
+
+/// Name of the collation comparing folded text, e.g. `ORDER BY name COLLATE XMTP_UNICODE`
+pub const UNICODE_COLLATION: &str = "XMTP_UNICODE";
+
+define_sql_function! {
+    /// [`fold`] in SQL
+    fn unicode_fold(text: Nullable<Text>) -> Nullable<Text>;
+}
+
+define_sql_function! {
+    /// The text of the encoded content of a text message, or `NULL` for other content
+    fn message_text(content: Binary) -> Nullable<Text>;
+}
+
+/// Fold `text` for comparison: NFKD decomposition, without combining marks, lowercased
+pub fn fold(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Compare folded text. Strings that fold to the same text are ordered as-is, so the order is
+/// stable.
+pub fn unicode_cmp(a: &str, b: &str) -> Ordering {
+    fold(a).cmp(&fold(b)).then_with(|| a.cmp(b))
+}
+
+fn decode_text(content: &[u8]) -> Option<String> {
+    let content = EncodedContent::decode(content).ok()?;
+    TextCodec::decode(content).ok()
+}
+
+/// Registers the collation and SQL functions of this module
+pub(super) struct UnicodeFolding;
+
+impl SqliteExtension for UnicodeFolding {
+    fn name(&self) -> &str {
+        "unicode folding"
+    }
+
+    fn load(&self, conn: &mut SqliteConnection) -> QueryResult<()> {
+        conn.register_collation(UNICODE_COLLATION, unicode_cmp)?;
+        unicode_fold_utils::register_impl(conn, |text: Option<String>| {
+            text.map(|text| fold(&text))
+        })?;
+        message_text_utils::register_impl(conn, |content: Vec<u8>| decode_text(&content))
+    }
+}
+
+/// Escape the `LIKE` wildcards of `text`, with `\` as the escape character
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl DbConnection {
+    /// Text messages that contain `query`, ignoring case and accents, newest first. Searches
+    /// all conversations unless `group_id` is given.
+    pub fn search_text_messages(
+        &self,
+        group_id: Option<&[u8]>,
+        query: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let pattern = format!("%{}%", escape_like(&fold(query)));
+        let mut search = dsl::group_messages
+            .filter(dsl::kind.eq(GroupMessageKind::Application))
+            .filter(dsl::content_type.eq(ContentType::Text))
+            .filter(
+                unicode_fold(message_text(dsl::decrypted_message_bytes))
+                    .like(pattern)
+                    .escape('\\'),
+            )
+            .order(dsl::sent_at_ns.desc())
+            .into_boxed();
+        if let Some(group_id) = group_id {
+            search = search.filter(dsl::group_id.eq(group_id));
+        }
+        if let Some(limit) = limit {
+            search = search.limit(limit);
+        }
+        let messages = self.raw_query(|conn| search.load(conn))?;
+        self.open_messages(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{sql_query, sql_types::Text as SqlText};
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use xmtp_content_types::encoded_content_to_bytes;
+
+    #[derive(QueryableByName)]
+    struct Name {
+        #[diesel(sql_type = SqlText)]
+        name: String,
+    }
+
+    #[test]
+    fn folds_case_accents_and_compatibility_forms() {
+        assert_eq!(fold("Crème BRÛLÉE"), "creme brulee");
+        assert_eq!(fold("ﬁancé"), "fiance");
+        assert_eq!(fold("Straße"), "straße");
+        assert_eq!(unicode_cmp("Éclair", "eclair"), Ordering::Greater);
+        assert_eq!(unicode_cmp("éclair", "Zebra"), Ordering::Less);
+        assert_eq!(escape_like("50%_off\\"), r"50\%\_off\\");
+    }
+
+    #[tokio::test]
+    async fn sorts_and_searches_with_folding() {
+        with_connection(|conn| {
+            let names: Vec<Name> = conn
+                .raw_query(|conn| {
+                    sql_query(format!(
+                        "SELECT column1 AS name FROM (VALUES ('zebra'), ('Éclair'), ('apple'))
+                        ORDER BY name COLLATE {UNICODE_COLLATION}"
+                    ))
+                    .load(conn)
+                })
+                .unwrap();
+            let names: Vec<_> = names.into_iter().map(|n| n.name).collect();
+            assert_eq!(names, ["apple", "Éclair", "zebra"]);
+
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            for (sent_at_ns, text) in [(1, "Rendez-vous au Café"), (2, "coffee"), (3, "CAFE?")] {
+                let mut message = generate_message(
+                    None,
+                    Some(&group.id),
+                    Some(sent_at_ns),
+                    Some(ContentType::Text),
+                );
+                message.decrypted_message_bytes =
+                    encoded_content_to_bytes(TextCodec::encode(text.to_string()).unwrap());
+                message.store(conn).unwrap();
+            }
+
+            let found = conn
+                .search_text_messages(Some(&group.id), "café", None)
+                .unwrap();
+            let sent_at: Vec<_> = found.iter().map(|m| m.sent_at_ns).collect();
+            assert_eq!(sent_at, [3, 1]);
+            assert!(conn
+                .search_text_messages(None, "caf%", None)
+                .unwrap()
+                .is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,577 @@
+//! Application-layer encryption of sensitive columns, for databases that SQLCipher does not
+//! protect, e.g. the OPFS database on wasm.
+//!
+//! Each table gets its own keys, derived from the column encryption key with HKDF salted with
+//! the table name. Values are sealed with AES-256-GCM under a random nonce, so equal values
+//! have different ciphertexts. The ids of the row are bound to the ciphertext as associated
+//! data, see [`row_aad`], so a value moved to another row or group fails to open.
+//!
+//! Encryption is enabled with
+//! [`StorageOption::with_column_encryption`](super::StorageOption::with_column_encryption).
+//! The encrypted columns are:
+//! - `group_messages.decrypted_message_bytes`, bound to the group id and the message id
+//! - `drafts.encoded_content`, bound to the group id
+//! - `group_avatars.url`, bound to the group id, looked up by `group_avatars.url_lookup`
+//! - `group_names.name`, bound to the group id
+//!
+//! When the store is opened with encryption enabled, the values written before are sealed in
+//! place and the column is recorded in `encrypted_columns`. From then on every value of the
+//! column must be sealed, and the store refuses to open without a column encryption key.
+//!
+//! Sealed values can't be compared in SQL. Columns that are only ever compared for equality
+//! get a lookup column next to them, holding a [`ColumnCipher::lookup_hash`], an HMAC-SHA256 of
+//! the value that is equal for equal values. Columns that queries filter or join on otherwise,
+//! like ids and inbox ids, stay in plaintext. Groups sorted by name are sorted once their names
+//! are opened. Searching messages by text runs in SQL on the stored bytes, so it does not match
+//! encrypted messages.
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use diesel::{
+    connection::LoadConnection,
+    prelude::*,
+    sql_types::{BigInt, Binary, Text},
+    sqlite::Sqlite,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{schema::encrypted_columns::dsl, EncryptionKey};
+use crate::storage::StorageError;
+
+/// Marks an encrypted value, followed by the nonce and the ciphertext
+const SEALED_PREFIX: &[u8] = b"xmtp-col-v1";
+/// [`SEALED_PREFIX`] of a sealed value of a text column, which is hex encoded
+const SEALED_TEXT_PREFIX: &str = "786d74702d636f6c2d7631";
+const NONCE_SIZE: usize = 12;
+const VALUE_KEY_INFO: &[u8] = b"column value";
+const LOOKUP_KEY_INFO: &[u8] = b"column lookup";
+
+/// A sealed column, see the [module docs](self)
+struct EncryptedColumn {
+    table: &'static str,
+    column: &'static str,
+    /// The columns of the ids the values are bound to, in the order of [`row_aad`]
+    row_ids: &'static [&'static str],
+    /// Whether the column is text, whose sealed values are hex encoded
+    text: bool,
+    /// The column holding the [lookup hash](ColumnCipher::lookup_hash) of the value
+    lookup: Option<&'static str>,
+}
+
+const ENCRYPTED_COLUMNS: &[EncryptedColumn] = &[
+    EncryptedColumn {
+        table: super::group_message::GROUP_MESSAGES_TABLE,
+        column: "decrypted_message_bytes",
+        row_ids: &["group_id", "id"],
+        text: false,
+        lookup: None,
+    },
+    EncryptedColumn {
+        table: super::draft::DRAFTS_TABLE,
+        column: "encoded_content",
+        row_ids: &["group_id"],
+        text: false,
+        lookup: None,
+    },
+    EncryptedColumn {
+        table: super::group_avatar::GROUP_AVATARS_TABLE,
+        column: "url",
+        row_ids: &["group_id"],
+        text: true,
+        lookup: Some("url_lookup"),
+    },
+    EncryptedColumn {
+        table: super::group_name::GROUP_NAMES_TABLE,
+        column: "name",
+        row_ids: &["group_id"],
+        text: true,
+        lookup: None,
+    },
+];
+
+#[derive(QueryableByName)]
+struct ColumnValue {
+    #[diesel(sql_type = BigInt)]
+    row_id: i64,
+    #[diesel(sql_type = Binary)]
+    first_id: Vec<u8>,
+    #[diesel(sql_type = Binary)]
+    second_id: Vec<u8>,
+    #[diesel(sql_type = Binary)]
+    value: Vec<u8>,
+}
+
+impl EncryptedColumn {
+    fn is_sealed(&self, value: &[u8]) -> bool {
+        if self.text {
+            value.starts_with(SEALED_TEXT_PREFIX.as_bytes())
+        } else {
+            value.starts_with(SEALED_PREFIX)
+        }
+    }
+
+    /// Seal the values of the column that were written before encryption was enabled
+    fn seal_existing<C>(&self, conn: &mut C, cipher: &ColumnCipher) -> Result<usize, StorageError>
+    where
+        C: Connection<Backend = Sqlite> + LoadConnection,
+    {
+        let Self {
+            table,
+            column,
+            row_ids,
+            ..
+        } = self;
+        let second_id = row_ids.get(1).copied().unwrap_or("x''");
+        let rows = diesel::sql_query(format!(
+            "SELECT rowid AS row_id, {first_id} AS first_id, {second_id} AS second_id, \
+            CAST({column} AS BLOB) AS value FROM {table}",
+            first_id = row_ids[0],
+        ))
+        .load::<ColumnValue>(conn)?;
+
+        let mut sealed = 0;
+        for row in rows.into_iter().filter(|row| !self.is_sealed(&row.value)) {
+            let ids: &[&[u8]] = if row_ids.len() > 1 {
+                &[&row.first_id, &row.second_id]
+            } else {
+                &[&row.first_id]
+            };
+            let aad = row_aad(ids);
+            let update = match self.lookup {
+                Some(lookup) => {
+                    format!("UPDATE {table} SET {column} = ?, {lookup} = ? WHERE rowid = ?")
+                }
+                None => format!("UPDATE {table} SET {column} = ? WHERE rowid = ?"),
+            };
+            let mut query = diesel::sql_query(update).into_boxed::<Sqlite>();
+            query = if self.text {
+                let plaintext = std::str::from_utf8(&row.value)
+                    .map_err(|e| StorageError::ColumnEncryption(e.to_string()))?;
+                query.bind::<Text, _>(cipher.seal_text(table, &aad, plaintext)?)
+            } else {
+                query.bind::<Binary, _>(cipher.seal(table, &aad, &row.value)?)
+            };
+            if self.lookup.is_some() {
+                query = query.bind::<Binary, _>(cipher.lookup_hash(table, &row.value));
+            }
+            query.bind::<BigInt, _>(row.row_id).execute(conn)?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+}
+
+/// Seal the values of the encrypted columns that were written before encryption was enabled,
+/// and record the columns as encrypted. Without a `cipher`, fails if any column was recorded
+/// as encrypted.
+pub(super) fn seal_existing_columns<C>(
+    conn: &mut C,
+    cipher: Option<&ColumnCipher>,
+) -> Result<(), StorageError>
+where
+    C: Connection<Backend = Sqlite> + LoadConnection,
+{
+    let encrypted: Vec<(String, String)> = dsl::encrypted_columns.load(conn)?;
+    let Some(cipher) = cipher else {
+        if encrypted.is_empty() {
+            return Ok(());
+        }
+        return Err(StorageError::ColumnEncryption(
+            "the database has encrypted columns, but no column encryption key was given".into(),
+        ));
+    };
+    conn.transaction(|conn| {
+        for column in ENCRYPTED_COLUMNS {
+            if encrypted
+                .iter()
+                .any(|(table, name)| table == column.table && name == column.column)
+            {
+                continue;
+            }
+            let sealed = column.seal_existing(conn, cipher)?;
+            tracing::info!(
+                "encrypted {sealed} existing values of {}.{}",
+                column.table,
+                column.column
+            );
+            diesel::insert_into(dsl::encrypted_columns)
+                .values((
+                    dsl::table_name.eq(column.table),
+                    dsl::column_name.eq(column.column),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// The associated data binding a sealed value to the ids of its row. Each id is prefixed with
+/// its length, so that the boundaries between ids are part of the data.
+pub fn row_aad(ids: &[&[u8]]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(ids.iter().map(|id| id.len() + 4).sum());
+    for id in ids {
+        aad.extend_from_slice(&(id.len() as u32).to_be_bytes());
+        aad.extend_from_slice(id);
+    }
+    aad
+}
+
+/// Encrypts and decrypts column values, see the [module docs](self)
+#[derive(Clone, zeroize::ZeroizeOnDrop)]
+pub struct ColumnCipher {
+    key: EncryptionKey,
+}
+
+impl std::fmt::Debug for ColumnCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColumnCipher").finish_non_exhaustive()
+    }
+}
+
+impl ColumnCipher {
+    pub fn new(key: EncryptionKey) -> Self {
+        Self { key }
+    }
+
+    fn table_key(&self, table: &str, info: &[u8]) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(table.as_bytes()), &self.key)
+            .expand(info, &mut key)
+            .expect("Length is correct");
+        key
+    }
+
+    /// Encrypt a value of a column of `table`, bound to the [ids of its row](row_aad)
+    pub fn seal(&self, table: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let key = self.table_key(table, VALUE_KEY_INFO);
+        let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+        let nonce = xmtp_common::rand_array::<NONCE_SIZE>();
+        let ciphertext = cipher
+            .encrypt(
+                GenericArray::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|e| StorageError::ColumnEncryption(e.to_string()))?;
+
+        let mut sealed = Vec::with_capacity(SEALED_PREFIX.len() + NONCE_SIZE + ciphertext.len());
+        sealed.extend_from_slice(SEALED_PREFIX);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt a value sealed with [`Self::seal`] for the same row
+    pub fn open(&self, table: &str, aad: &[u8], value: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        let Some(sealed) = value.strip_prefix(SEALED_PREFIX) else {
+            return Err(StorageError::ColumnEncryption(format!(
+                "value of encrypted table {table} is not sealed"
+            )));
+        };
+        if sealed.len() < NONCE_SIZE {
+            return Err(StorageError::ColumnEncryption(format!(
+                "sealed value of {table} is truncated"
+            )));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let key = self.table_key(table, VALUE_KEY_INFO);
+        Aes256Gcm::new(GenericArray::from_slice(&key))
+            .decrypt(
+                GenericArray::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|e| StorageError::ColumnEncryption(e.to_string()))
+    }
+
+    /// [`Self::seal`] for a text column, the sealed value is hex encoded
+    pub fn seal_text(
+        &self,
+        table: &str,
+        aad: &[u8],
+        plaintext: &str,
+    ) -> Result<String, StorageError> {
+        Ok(hex::encode(self.seal(table, aad, plaintext.as_bytes())?))
+    }
+
+    /// [`Self::open`] for a text column sealed with [`Self::seal_text`]
+    pub fn open_text(
+        &self,
+        table: &str,
+        aad: &[u8],
+        value: String,
+    ) -> Result<String, StorageError> {
+        if !value.starts_with(SEALED_TEXT_PREFIX) {
+            return Err(StorageError::ColumnEncryption(format!(
+                "value of encrypted table {table} is not sealed"
+            )));
+        }
+        let sealed =
+            hex::decode(&value).map_err(|e| StorageError::ColumnEncryption(e.to_string()))?;
+        String::from_utf8(self.open(table, aad, sealed)?)
+            .map_err(|e| StorageError::ColumnEncryption(e.to_string()))
+    }
+
+    /// A keyed hash of a value of a lookup column of `table`, equal for equal values
+    pub fn lookup_hash(&self, table: &str, value: &[u8]) -> Vec<u8> {
+        let key = self.table_key(table, LOOKUP_KEY_INFO);
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts any key size");
+        mac.update(value);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::{
+            encrypted_store::{
+                group::{tests::generate_group, GroupQueryArgs, GroupSortKey},
+                group_message::tests::generate_message,
+                schema::{
+                    group_avatars::dsl as avatars_dsl, group_messages::dsl,
+                    group_names::dsl as names_dsl,
+                },
+            },
+            EncryptedMessageStore, StorageOption,
+        },
+        Store,
+    };
+    use diesel::prelude::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[test]
+    fn seals_and_opens_values() {
+        let cipher = ColumnCipher::new([1; 32]);
+        let aad = row_aad(&[b"group", b"message"]);
+        let sealed = cipher.seal("group_messages", &aad, b"gm").unwrap();
+        assert_ne!(sealed, cipher.seal("group_messages", &aad, b"gm").unwrap());
+        assert_eq!(
+            cipher.open("group_messages", &aad, sealed.clone()).unwrap(),
+            b"gm"
+        );
+
+        // keys are per table and per column encryption key
+        assert!(cipher.open("drafts", &aad, sealed.clone()).is_err());
+        assert!(ColumnCipher::new([2; 32])
+            .open("group_messages", &aad, sealed.clone())
+            .is_err());
+
+        // values are bound to their row
+        let other_row = row_aad(&[b"group", b"other message"]);
+        assert!(cipher
+            .open("group_messages", &other_row, sealed.clone())
+            .is_err());
+        let shifted = row_aad(&[b"groupmessage", b""]);
+        assert!(cipher.open("group_messages", &shifted, sealed).is_err());
+
+        // values that were not sealed are refused
+        assert!(cipher.open("drafts", &aad, b"plain".to_vec()).is_err());
+
+        let sealed = cipher
+            .seal_text("group_avatars", &aad, "https://a/1.png")
+            .unwrap();
+        assert!(sealed.starts_with(SEALED_TEXT_PREFIX));
+        assert_eq!(
+            cipher.open_text("group_avatars", &aad, sealed).unwrap(),
+            "https://a/1.png"
+        );
+        assert!(cipher
+            .open_text("group_avatars", &aad, "https://a/1.png".into())
+            .is_err());
+    }
+
+    #[test]
+    fn lookup_hashes_are_deterministic_per_table() {
+        let cipher = ColumnCipher::new([1; 32]);
+        let hash = cipher.lookup_hash("group_avatars", b"https://a/1.png");
+        assert_eq!(
+            hash,
+            cipher.lookup_hash("group_avatars", b"https://a/1.png")
+        );
+        assert_ne!(
+            hash,
+            cipher.lookup_hash("group_avatars", b"https://a/2.png")
+        );
+        assert_ne!(hash, cipher.lookup_hash("group_names", b"https://a/1.png"));
+        assert_ne!(
+            hash,
+            ColumnCipher::new([2; 32]).lookup_hash("group_avatars", b"https://a/1.png")
+        );
+    }
+
+    #[test]
+    fn sealed_text_prefix_is_the_hex_of_the_sealed_prefix() {
+        assert_eq!(SEALED_TEXT_PREFIX, hex::encode(SEALED_PREFIX));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn encrypts_message_bytes() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral.with_column_encryption([3; 32]),
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        let conn = store.conn().unwrap();
+        let group = generate_group(None);
+        group.store(&conn).unwrap();
+        let message = generate_message(None, Some(&group.id), Some(1), None);
+        message.store(&conn).unwrap();
+
+        let stored: Vec<u8> = conn
+            .raw_query(|conn| {
+                dsl::group_messages
+                    .find(message.id.as_slice())
+                    .select(dsl::decrypted_message_bytes)
+                    .first(conn)
+            })
+            .unwrap();
+        assert_ne!(stored, message.decrypted_message_bytes);
+        assert_eq!(
+            conn.get_group_message(&message.id).unwrap(),
+            Some(message.clone())
+        );
+
+        // a sealed value copied to another message does not open
+        let other = generate_message(None, Some(&group.id), Some(2), None);
+        other.store(&conn).unwrap();
+        conn.raw_query(|conn| {
+            diesel::update(dsl::group_messages.find(other.id.as_slice()))
+                .set(dsl::decrypted_message_bytes.eq(&stored))
+                .execute(conn)
+        })
+        .unwrap();
+        assert!(matches!(
+            conn.get_group_message(&other.id),
+            Err(StorageError::ColumnEncryption(_))
+        ));
+
+        conn.set_draft(&group.id, b"draft").unwrap();
+        assert_eq!(
+            conn.get_draft(&group.id).unwrap().unwrap().encoded_content,
+            b"draft"
+        );
+
+        conn.set_group_avatar_url(&group.id, "https://a/1.png")
+            .unwrap();
+        let stored_url: String = conn
+            .raw_query(|conn| {
+                avatars_dsl::group_avatars
+                    .find(group.id.as_slice())
+                    .select(avatars_dsl::url)
+                    .first(conn)
+            })
+            .unwrap();
+        assert!(!stored_url.contains("https"));
+        // the url is compared through its lookup hash
+        assert!(!conn
+            .store_group_avatar(&group.id, "https://a/0.png", b"image")
+            .unwrap());
+        assert_eq!(
+            conn.pending_group_avatars().unwrap()[0].url,
+            "https://a/1.png"
+        );
+        assert!(conn
+            .store_group_avatar(&group.id, "https://a/1.png", b"image")
+            .unwrap());
+        assert_eq!(conn.group_avatar(&group.id).unwrap().unwrap(), b"image");
+
+        // sealed names are sorted once opened
+        conn.set_group_name(&group.id, "zoo").unwrap();
+        let named = generate_group(None);
+        named.store(&conn).unwrap();
+        conn.set_group_name(&named.id, "apple").unwrap();
+        let stored_name: String = conn
+            .raw_query(|conn| {
+                names_dsl::group_names
+                    .find(named.id.as_slice())
+                    .select(names_dsl::name)
+                    .first(conn)
+            })
+            .unwrap();
+        assert_ne!(stored_name, "apple");
+        let first = conn
+            .find_groups(
+                GroupQueryArgs::default()
+                    .sort_by(GroupSortKey::Name)
+                    .limit(1),
+            )
+            .unwrap();
+        assert_eq!(first[0].id, named.id);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn seals_existing_values_when_enabled() {
+        let path = xmtp_common::tmp_path();
+        let key = EncryptedMessageStore::generate_enc_key();
+        let group = generate_group(None);
+        {
+            let store = EncryptedMessageStore::new(StorageOption::Persistent(path.clone()), key)
+                .await
+                .unwrap();
+            let conn = store.conn().unwrap();
+            group.store(&conn).unwrap();
+            conn.set_draft(&group.id, b"draft").unwrap();
+            conn.set_group_name(&group.id, "plain").unwrap();
+            conn.set_group_avatar_url(&group.id, "https://a/1.png")
+                .unwrap();
+        }
+
+        let encrypted = StorageOption::Persistent(path.clone()).with_column_encryption([3; 32]);
+        {
+            let store = EncryptedMessageStore::new(encrypted.clone(), key)
+                .await
+                .unwrap();
+            let conn = store.conn().unwrap();
+            let stored_name: String = conn
+                .raw_query(|conn| {
+                    names_dsl::group_names
+                        .find(group.id.as_slice())
+                        .select(names_dsl::name)
+                        .first(conn)
+                })
+                .unwrap();
+            assert!(stored_name.starts_with(SEALED_TEXT_PREFIX));
+            assert_eq!(
+                conn.get_draft(&group.id).unwrap().unwrap().encoded_content,
+                b"draft"
+            );
+            assert!(conn
+                .store_group_avatar(&group.id, "https://a/1.png", b"image")
+                .unwrap());
+
+            // a value written without the key is refused
+            conn.raw_query(|conn| {
+                diesel::update(names_dsl::group_names.find(group.id.as_slice()))
+                    .set(names_dsl::name.eq("tampered"))
+                    .execute(conn)
+            })
+            .unwrap();
+            assert!(matches!(
+                conn.find_groups(GroupQueryArgs::default().sort_by(GroupSortKey::Name)),
+                Err(StorageError::ColumnEncryption(_))
+            ));
+        }
+
+        // once encrypted, the store does not open without the key
+        assert!(matches!(
+            EncryptedMessageStore::new(StorageOption::Persistent(path.clone()), key).await,
+            Err(StorageError::ColumnEncryption(_))
+        ));
+        EncryptedMessageStore::new(encrypted, key).await.unwrap();
+        EncryptedMessageStore::remove_db_files(path)
+    }
+}
@@ -0,0 +1,224 @@
+//! Account-level defaults for the consent of new conversations.
+//!
+//! When a welcome adds this installation to a conversation without a consent record, the
+//! [`RequestPolicy`] for its type decides whether it starts allowed, denied, or unknown for the
+//! user to decide. The policy is a user preference, synced to the other installations.
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    consent_record::{ConsentState, ConsentType},
+    db_connection::DbConnection,
+    group::ConversationType,
+    group_message::GroupMessageKind,
+    schema::{
+        group_messages::dsl as messages_dsl, groups::dsl as groups_dsl,
+        user_preferences::dsl as preferences_dsl,
+    },
+    user_preferences::{NewStoredUserPreferences, StoredUserPreferences},
+};
+use crate::{groups::group_metadata::DmMembers, storage::StorageError};
+
+/// What to do with conversations started by another inbox.
+///
+/// An inbox is a contact if it is allowed, or if this user sent a message in a DM with it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RequestPolicy {
+    /// Leave new conversations unknown
+    #[default]
+    Ask,
+    /// Allow conversations from contacts, leave the others unknown
+    AllowContacts,
+    /// Deny conversations from inboxes that are not contacts
+    DenyUnknown,
+    /// Allow conversations from contacts and deny the others
+    ContactsOnly,
+}
+
+impl RequestPolicy {
+    /// The consent of a new conversation, or `None` to leave it unknown
+    pub fn consent(&self, from_contact: bool) -> Option<ConsentState> {
+        use RequestPolicy::*;
+        match (self, from_contact) {
+            (AllowContacts | ContactsOnly, true) => Some(ConsentState::Allowed),
+            (DenyUnknown | ContactsOnly, false) => Some(ConsentState::Denied),
+            _ => None,
+        }
+    }
+}
+
+/// The [`RequestPolicy`] of each conversation type
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConsentPolicy {
+    pub dms: RequestPolicy,
+    pub groups: RequestPolicy,
+}
+
+impl ConsentPolicy {
+    pub fn for_conversation_type(&self, conversation_type: ConversationType) -> RequestPolicy {
+        match conversation_type {
+            ConversationType::Dm => self.dms,
+            ConversationType::Group => self.groups,
+            // Sync groups only have this user's installations
+            ConversationType::Sync => RequestPolicy::Ask,
+        }
+    }
+}
+
+impl DbConnection {
+    /// The current consent policy, the default one if it was never set
+    pub fn consent_policy(&self) -> Result<ConsentPolicy, StorageError> {
+        match StoredUserPreferences::load(self)?.consent_policy {
+            Some(policy) => serde_json::from_str(&policy)
+                .map_err(|e| StorageError::Deserialization(e.to_string())),
+            None => Ok(ConsentPolicy::default()),
+        }
+    }
+
+    /// Replace the consent policy. It applies to conversations joined from now on.
+    pub fn set_consent_policy(&self, policy: &ConsentPolicy) -> Result<(), StorageError> {
+        let mut preferences = StoredUserPreferences::load(self)?;
+        preferences.consent_policy = Some(
+            serde_json::to_string(policy)
+                .map_err(|e| StorageError::Serialization(e.to_string()))?,
+        );
+        let to_insert: NewStoredUserPreferences = (&preferences).into();
+        self.raw_query(|conn| {
+            diesel::insert_into(preferences_dsl::user_preferences)
+                .values(to_insert)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Whether `inbox_id` is a contact of `my_inbox_id`, see [`RequestPolicy`]
+    pub fn is_contact(&self, my_inbox_id: &str, inbox_id: &str) -> Result<bool, StorageError> {
+        let inbox_consent = self.get_consent_record(inbox_id.to_string(), ConsentType::InboxId)?;
+        if inbox_consent.is_some_and(|record| record.state == ConsentState::Allowed) {
+            return Ok(true);
+        }
+        let dm_id = String::from(DmMembers {
+            member_one_inbox_id: my_inbox_id,
+            member_two_inbox_id: inbox_id,
+        });
+        let sent: i64 = self.raw_query(|conn| {
+            messages_dsl::group_messages
+                .filter(messages_dsl::sent_by_me.eq(true))
+                .filter(messages_dsl::kind.eq(GroupMessageKind::Application))
+                .filter(
+                    messages_dsl::group_id.eq_any(
+                        groups_dsl::groups
+                            .filter(groups_dsl::dm_id.eq(dm_id))
+                            .select(groups_dsl::id),
+                    ),
+                )
+                .count()
+                .get_result(conn)
+        })?;
+        Ok(sent > 0)
+    }
+
+    /// The consent the policy gives a conversation of `conversation_type` that `inviter_inbox_id`
+    /// added `my_inbox_id` to, or `None` to leave it unknown
+    pub fn consent_from_policy(
+        &self,
+        conversation_type: ConversationType,
+        my_inbox_id: &str,
+        inviter_inbox_id: &str,
+    ) -> Result<Option<ConsentState>, StorageError> {
+        let policy = self
+            .consent_policy()?
+            .for_conversation_type(conversation_type);
+        if policy == RequestPolicy::Ask {
+            return Ok(None);
+        }
+        let from_contact = self.is_contact(my_inbox_id, inviter_inbox_id)?;
+        Ok(policy.consent(from_contact))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            consent_record::StoredConsentRecord,
+            group::tests::{generate_dm, generate_group},
+            group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn policy_decides_consent_of_requests() {
+        with_connection(|conn| {
+            assert_eq!(conn.consent_policy().unwrap(), ConsentPolicy::default());
+            assert_eq!(
+                conn.consent_from_policy(ConversationType::Group, "me", "stranger")
+                    .unwrap(),
+                None
+            );
+
+            conn.set_consent_policy(&ConsentPolicy {
+                dms: RequestPolicy::AllowContacts,
+                groups: RequestPolicy::DenyUnknown,
+            })
+            .unwrap();
+            conn.insert_or_replace_consent_records(&[StoredConsentRecord::new(
+                ConsentType::InboxId,
+                ConsentState::Allowed,
+                "friend".to_string(),
+            )])
+            .unwrap();
+
+            assert_eq!(
+                conn.consent_from_policy(ConversationType::Group, "me", "stranger")
+                    .unwrap(),
+                Some(ConsentState::Denied)
+            );
+            assert_eq!(
+                conn.consent_from_policy(ConversationType::Group, "me", "friend")
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                conn.consent_from_policy(ConversationType::Dm, "me", "friend")
+                    .unwrap(),
+                Some(ConsentState::Allowed)
+            );
+            assert_eq!(
+                conn.consent_from_policy(ConversationType::Dm, "me", "stranger")
+                    .unwrap(),
+                None
+            );
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn inboxes_messaged_in_a_dm_are_contacts() {
+        with_connection(|conn| {
+            let mut dm = generate_dm(None);
+            let dm_id = String::from(DmMembers {
+                member_one_inbox_id: "me",
+                member_two_inbox_id: "pen_pal",
+            });
+            dm.dm_id = Some(dm_id);
+            dm.store(conn).unwrap();
+            generate_group(None).store(conn).unwrap();
+            assert!(!conn.is_contact("me", "pen_pal").unwrap());
+
+            let mut message = generate_message(None, Some(&dm.id), None, None);
+            message.sent_by_me = true;
+            message.store(conn).unwrap();
+            assert!(conn.is_contact("me", "pen_pal").unwrap());
+            assert!(!conn.is_contact("me", "stranger").unwrap());
+        })
+        .await
+    }
+}
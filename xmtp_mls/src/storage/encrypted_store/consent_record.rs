@@ -1,4 +1,4 @@
-use crate::{impl_store, storage::StorageError};
+use crate::{storage::StorageError, Store};
 
 use super::Sqlite;
 use super::{
@@ -15,6 +15,7 @@ use diesel::{
     upsert::excluded,
 };
 use serde::{Deserialize, Serialize};
+use xmtp_common::time::now_ns;
 
 /// StoredConsentRecord holds a serialized ConsentRecord
 #[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -27,6 +28,12 @@ pub struct StoredConsentRecord {
     pub state: ConsentState,
     /// The entity of what was consented (0x00 etc..)
     pub entity: String,
+    /// Time in NS after which the record reverts to [`ConsentState::Unknown`], e.g. for
+    /// "allow for 30 days" or a temporary mute. Expiry is local: it is not part of the
+    /// preference updates synced to other installations, which receive the revert instead, and
+    /// records synced back that keep the state leave it in place.
+    #[serde(skip)]
+    pub expires_at_ns: Option<i64>,
 }
 
 impl StoredConsentRecord {
@@ -35,11 +42,47 @@ impl StoredConsentRecord {
             entity_type,
             state,
             entity,
+            expires_at_ns: None,
+        }
+    }
+
+    /// The same record, reverting to [`ConsentState::Unknown`] at `expires_at_ns`
+    pub fn expiring_at(self, expires_at_ns: i64) -> Self {
+        Self {
+            expires_at_ns: Some(expires_at_ns),
+            ..self
+        }
+    }
+
+    pub fn is_expired(&self, now_ns: i64) -> bool {
+        self.expires_at_ns
+            .is_some_and(|expires_at_ns| expires_at_ns <= now_ns)
+    }
+
+    /// The record as it reads at `now_ns`: [`ConsentState::Unknown`] once expired, even if
+    /// [`DbConnection::expire_consent_records`] has not reverted it yet
+    fn at(self, now_ns: i64) -> Self {
+        if !self.is_expired(now_ns) {
+            return self;
+        }
+        Self {
+            state: ConsentState::Unknown,
+            expires_at_ns: None,
+            ..self
         }
     }
 }
 
-impl_store!(StoredConsentRecord, consent_records);
+impl Store<DbConnection> for StoredConsentRecord {
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::insert_into(consent_records::table)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
 
 impl DbConnection {
     /// Returns the consent_records for the given entity up
@@ -48,13 +91,23 @@ impl DbConnection {
         entity: String,
         entity_type: ConsentType,
     ) -> Result<Option<StoredConsentRecord>, StorageError> {
-        Ok(self.raw_query(|conn| -> diesel::QueryResult<_> {
+        let now = now_ns();
+        let cache = self.readable_cache();
+        if let Some(record) = cache.and_then(|c| c.consent_record(entity_type, &entity)) {
+            return Ok(record.map(|r| r.at(now)));
+        }
+        let generation = cache.map_or(0, |c| c.generation());
+        let record = self.raw_query(|conn| -> diesel::QueryResult<_> {
             dsl::consent_records
-                .filter(dsl::entity.eq(entity))
+                .filter(dsl::entity.eq(&entity))
                 .filter(dsl::entity_type.eq(entity_type))
                 .first(conn)
                 .optional()
-        })?)
+        })?;
+        if let Some(cache) = cache {
+            cache.put_consent_record(generation, entity_type, &entity, record.as_ref());
+        }
+        Ok(record.map(|r| r.at(now)))
     }
 
     /// Insert consent_records, and replace existing entries, returns records that are new or changed
@@ -91,7 +144,10 @@ impl DbConnection {
                         .values(record)
                         .on_conflict((dsl::entity_type, dsl::entity))
                         .do_update()
-                        .set(dsl::state.eq(excluded(dsl::state)))
+                        .set((
+                            dsl::state.eq(excluded(dsl::state)),
+                            dsl::expires_at_ns.eq(excluded(dsl::expires_at_ns)),
+                        ))
                         .execute(conn)?;
                 }
                 Ok(())
@@ -103,6 +159,34 @@ impl DbConnection {
         Ok(changed)
     }
 
+    /// Like [`Self::insert_or_replace_consent_records`] for records synced from other
+    /// installations. Synced records carry no expiry, so a record that leaves the state
+    /// unchanged keeps the local expiry instead of clearing it.
+    pub fn insert_or_replace_synced_consent_records(
+        &self,
+        records: &[StoredConsentRecord],
+    ) -> Result<Vec<StoredConsentRecord>, StorageError> {
+        let records = self.raw_query(|conn| -> diesel::QueryResult<_> {
+            records
+                .iter()
+                .map(|record| {
+                    let existing: Option<StoredConsentRecord> = dsl::consent_records
+                        .find((&record.entity_type, &record.entity))
+                        .first(conn)
+                        .optional()?;
+                    Ok(match existing {
+                        Some(existing) if existing.state == record.state => StoredConsentRecord {
+                            expires_at_ns: existing.expires_at_ns,
+                            ..record.clone()
+                        },
+                        _ => record.clone(),
+                    })
+                })
+                .collect::<diesel::QueryResult<Vec<_>>>()
+        })?;
+        self.insert_or_replace_consent_records(&records)
+    }
+
     pub fn maybe_insert_consent_record_return_existing(
         &self,
         record: &StoredConsentRecord,
@@ -126,10 +210,35 @@ impl DbConnection {
             Ok(None)
         })
     }
+
+    /// Revert the records that expired by `now_ns` to [`ConsentState::Unknown`], returning
+    /// them as they are now
+    pub fn expire_consent_records(
+        &self,
+        now_ns: i64,
+    ) -> Result<Vec<StoredConsentRecord>, StorageError> {
+        let expired = self.raw_query(|conn| {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                let expired: Vec<StoredConsentRecord> = dsl::consent_records
+                    .filter(dsl::expires_at_ns.le(now_ns))
+                    .load(conn)?;
+                diesel::update(dsl::consent_records.filter(dsl::expires_at_ns.le(now_ns)))
+                    .set((
+                        dsl::state.eq(ConsentState::Unknown),
+                        dsl::expires_at_ns.eq(None::<i64>),
+                    ))
+                    .execute(conn)?;
+                Ok(expired)
+            })
+        })?;
+        Ok(expired.into_iter().map(|r| r.at(now_ns)).collect())
+    }
 }
 
 #[repr(i32)]
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, AsExpression, FromSqlRow)]
+#[derive(
+    Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, AsExpression, FromSqlRow,
+)]
 #[diesel(sql_type = Integer)]
 /// Type of consent record stored
 pub enum ConsentType {
@@ -215,11 +324,7 @@ mod tests {
         state: ConsentState,
         entity: String,
     ) -> StoredConsentRecord {
-        StoredConsentRecord {
-            entity_type,
-            state,
-            entity,
-        }
+        StoredConsentRecord::new(entity_type, state, entity)
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
@@ -287,4 +392,94 @@ mod tests {
         })
         .await;
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn expired_records_revert_to_unknown() {
+        with_connection(|conn| {
+            let temporary =
+                generate_consent_record(ConsentType::InboxId, ConsentState::Denied, "muted".into())
+                    .expiring_at(100);
+            let permanent = generate_consent_record(
+                ConsentType::InboxId,
+                ConsentState::Allowed,
+                "friend".into(),
+            );
+            conn.insert_or_replace_consent_records(&[temporary.clone(), permanent])
+                .unwrap();
+
+            // Reads see the expiry before the sweep
+            let record = conn
+                .get_consent_record("muted".into(), ConsentType::InboxId)
+                .unwrap()
+                .unwrap();
+            assert_eq!(record.state, ConsentState::Unknown);
+
+            assert!(conn.expire_consent_records(99).unwrap().is_empty());
+            let expired = conn.expire_consent_records(100).unwrap();
+            assert_eq!(expired.len(), 1);
+            assert_eq!(expired[0].entity, "muted");
+            assert_eq!(expired[0].state, ConsentState::Unknown);
+            assert!(conn.expire_consent_records(200).unwrap().is_empty());
+
+            let stored: StoredConsentRecord = conn
+                .raw_query(|conn| {
+                    dsl::consent_records
+                        .find((ConsentType::InboxId, "muted"))
+                        .first(conn)
+                })
+                .unwrap();
+            assert_eq!(stored.state, ConsentState::Unknown);
+            assert_eq!(stored.expires_at_ns, None);
+        })
+        .await;
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn synced_records_keep_the_local_expiry() {
+        with_connection(|conn| {
+            let temporary =
+                generate_consent_record(ConsentType::InboxId, ConsentState::Allowed, "temp".into())
+                    .expiring_at(i64::MAX);
+            conn.insert_or_replace_consent_records(&[temporary.clone()])
+                .unwrap();
+
+            // The same state synced back keeps the expiry
+            let changed = conn
+                .insert_or_replace_synced_consent_records(&[generate_consent_record(
+                    ConsentType::InboxId,
+                    ConsentState::Allowed,
+                    "temp".into(),
+                )])
+                .unwrap();
+            assert!(changed.is_empty());
+            let stored: StoredConsentRecord = conn
+                .raw_query(|conn| {
+                    dsl::consent_records
+                        .find((ConsentType::InboxId, "temp"))
+                        .first(conn)
+                })
+                .unwrap();
+            assert_eq!(stored.expires_at_ns, Some(i64::MAX));
+
+            // A different state replaces it
+            conn.insert_or_replace_synced_consent_records(&[generate_consent_record(
+                ConsentType::InboxId,
+                ConsentState::Denied,
+                "temp".into(),
+            )])
+            .unwrap();
+            let stored: StoredConsentRecord = conn
+                .raw_query(|conn| {
+                    dsl::consent_records
+                        .find((ConsentType::InboxId, "temp"))
+                        .first(conn)
+                })
+                .unwrap();
+            assert_eq!(stored.state, ConsentState::Denied);
+            assert_eq!(stored.expires_at_ns, None);
+        })
+        .await;
+    }
 }
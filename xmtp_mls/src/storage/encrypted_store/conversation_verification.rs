@@ -0,0 +1,121 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::conversation_verifications::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// A peer of a conversation that the user verified out of band. The code is kept so that the
+/// verification can be dropped once the peer's keys change.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = conversation_verifications)]
+#[diesel(primary_key(group_id, peer_inbox_id))]
+pub struct StoredConversationVerification {
+    pub group_id: Vec<u8>,
+    pub peer_inbox_id: String,
+    /// The code that was compared when the peer was verified
+    pub verification_code: String,
+    pub verified_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Insert or replace the verification of a peer
+    pub fn set_conversation_verification(
+        &self,
+        verification: &StoredConversationVerification,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::conversation_verifications)
+                .values(verification)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn get_conversation_verification<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        peer_inbox_id: &str,
+    ) -> Result<Option<StoredConversationVerification>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::conversation_verifications
+                .find((group_id.as_ref(), peer_inbox_id))
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    /// All the verified peers of a group
+    pub fn conversation_verifications<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredConversationVerification>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::conversation_verifications
+                .filter(dsl::group_id.eq(group_id.as_ref()))
+                .order(dsl::peer_inbox_id.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// Returns whether the peer was verified
+    pub fn delete_conversation_verification<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        peer_inbox_id: &str,
+    ) -> Result<bool, StorageError> {
+        let deleted = self.raw_query(|conn| {
+            diesel::delete(dsl::conversation_verifications.find((group_id.as_ref(), peer_inbox_id)))
+                .execute(conn)
+        })?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_replaces_and_deletes_verifications() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let mut verification = StoredConversationVerification {
+                group_id: group.id.clone(),
+                peer_inbox_id: "bola".to_string(),
+                verification_code: "00001".to_string(),
+                verified_at_ns: 1,
+            };
+            conn.set_conversation_verification(&verification).unwrap();
+            verification.verification_code = "00002".to_string();
+            conn.set_conversation_verification(&verification).unwrap();
+
+            assert_eq!(
+                conn.conversation_verifications(&group.id).unwrap(),
+                vec![verification.clone()]
+            );
+            assert!(conn
+                .delete_conversation_verification(&group.id, "bola")
+                .unwrap());
+            assert!(!conn
+                .delete_conversation_verification(&group.id, "bola")
+                .unwrap());
+            assert_eq!(
+                conn.get_conversation_verification(&group.id, "bola")
+                    .unwrap(),
+                None
+            );
+        })
+        .await
+    }
+}
@@ -0,0 +1,92 @@
+//! Optional external storage for the group cursors of the `refresh_state` table.
+//!
+//! Bots and other server-side deployments run several replicas of the same installation. With a
+//! [`CursorStore`] attached to the [`EncryptedMessageStore`](super::EncryptedMessageStore), a
+//! replica skips the messages of a group that another replica already processed.
+//!
+//! A cursor is only meaningful together with the MLS state it was reached at, so it is shared
+//! as a [`SharedCursor`] that records the epoch and the epoch authenticator of the group after
+//! the envelope at the cursor was processed. A replica only adopts a shared cursor if its own
+//! group is at that exact state, which means the envelopes it skips are application messages of
+//! the current epoch. Commits are always processed by every replica, and a replica that does
+//! not have the group yet joins it from the welcome like any other installation. Welcome
+//! cursors are not shared for the same reason.
+//!
+//! The local `refresh_state` table stays the source of truth inside a transaction. The store is
+//! only read before a group receives its messages, and written once they were committed, never
+//! while a database connection is held.
+use std::fmt::Debug;
+
+use crate::storage::StorageError;
+
+/// A group cursor, with the MLS state of the group once the envelope at the cursor was processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedCursor {
+    pub cursor: i64,
+    pub epoch: u64,
+    pub epoch_authenticator: Vec<u8>,
+}
+
+impl SharedCursor {
+    /// Whether the cursor was reached at the given MLS state, and can be adopted by a group at
+    /// that state.
+    pub fn describes(&self, epoch: u64, epoch_authenticator: &[u8]) -> bool {
+        self.epoch == epoch && self.epoch_authenticator == epoch_authenticator
+    }
+}
+
+/// External storage for group cursors, shared by every replica of an installation.
+///
+/// Errors should be reported as [`StorageError::CursorStore`].
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait CursorStore: Send + Sync + Debug {
+    /// The shared cursor of the group, or `None` if it was never stored
+    async fn get_cursor(&self, group_id: &[u8]) -> Result<Option<SharedCursor>, StorageError>;
+
+    /// Store `cursor` if it is greater than the stored cursor, atomically with respect to the
+    /// other replicas. Returns whether the cursor was stored.
+    async fn advance_cursor(
+        &self,
+        group_id: &[u8],
+        cursor: SharedCursor,
+    ) -> Result<bool, StorageError>;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    /// A [`CursorStore`] kept in memory, standing in for an external store
+    #[derive(Debug, Default)]
+    pub(crate) struct InMemoryCursorStore {
+        pub(crate) cursors: Mutex<HashMap<Vec<u8>, SharedCursor>>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl CursorStore for InMemoryCursorStore {
+        async fn get_cursor(&self, group_id: &[u8]) -> Result<Option<SharedCursor>, StorageError> {
+            Ok(self.cursors.lock().get(group_id).cloned())
+        }
+
+        async fn advance_cursor(
+            &self,
+            group_id: &[u8],
+            cursor: SharedCursor,
+        ) -> Result<bool, StorageError> {
+            let mut cursors = self.cursors.lock();
+            if let Some(stored) = cursors.get(group_id) {
+                if stored.cursor >= cursor.cursor {
+                    return Ok(false);
+                }
+            }
+            cursors.insert(group_id.to_vec(), cursor);
+            Ok(true)
+        }
+    }
+}
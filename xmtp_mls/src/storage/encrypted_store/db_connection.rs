@@ -2,6 +2,11 @@ use parking_lot::Mutex;
 use std::fmt;
 use std::sync::Arc;
 
+use super::cache::{CacheInstrumentation, StoreCache};
+use super::column_encryption::ColumnCipher;
+use super::cursor_store::CursorStore;
+use super::group_invariants::GroupInvariantChecker;
+use crate::storage::key_store_backend::KeyStoreBackend;
 use crate::storage::xmtp_openmls_provider::XmtpOpenMlsProvider;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -20,13 +25,83 @@ pub type DbConnection = DbConnectionPrivate<sqlite_web::connection::WasmSqliteCo
 #[doc(hidden)]
 pub struct DbConnectionPrivate<C> {
     inner: Arc<Mutex<C>>,
+    cache: Option<Arc<StoreCache>>,
+    cursor_store: Option<Arc<dyn CursorStore>>,
+    key_store_backend: Option<Arc<dyn KeyStoreBackend>>,
+    invariant_checker: Option<Arc<GroupInvariantChecker>>,
+    column_cipher: Option<Arc<ColumnCipher>>,
+    /// Callbacks to run once the current transaction commits, see [`Self::after_commit`]
+    after_commit: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
 }
 
 /// Owned DBConnection Methods
 impl<C> DbConnectionPrivate<C> {
     /// Create a new [`DbConnectionPrivate`] from an existing Arc<Mutex<C>>
     pub(super) fn from_arc_mutex(conn: Arc<Mutex<C>>) -> Self {
-        Self { inner: conn }
+        Self {
+            inner: conn,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+            column_cipher: None,
+            after_commit: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Attach the store-wide external cursor store to this connection
+    pub(super) fn with_cursor_store(mut self, cursor_store: Option<Arc<dyn CursorStore>>) -> Self {
+        self.cursor_store = cursor_store;
+        self
+    }
+
+    /// The external cursor store, if one is attached
+    pub(crate) fn cursor_store(&self) -> Option<&dyn CursorStore> {
+        self.cursor_store.as_deref()
+    }
+
+    /// Attach the store-wide key store backend to this connection
+    pub(super) fn with_key_store_backend(
+        mut self,
+        key_store_backend: Option<Arc<dyn KeyStoreBackend>>,
+    ) -> Self {
+        self.key_store_backend = key_store_backend;
+        self
+    }
+
+    /// The host-provided key store backend, if one is attached
+    pub(crate) fn key_store_backend(&self) -> Option<&Arc<dyn KeyStoreBackend>> {
+        self.key_store_backend.as_ref()
+    }
+
+    /// Attach the store-wide group invariant checker to this connection
+    pub(super) fn with_invariant_checker(
+        mut self,
+        invariant_checker: Option<Arc<GroupInvariantChecker>>,
+    ) -> Self {
+        self.invariant_checker = invariant_checker;
+        self
+    }
+
+    /// The group invariant checker, if invariant checks are enabled
+    pub(crate) fn invariant_checker(&self) -> Option<&GroupInvariantChecker> {
+        self.invariant_checker.as_deref()
+    }
+
+    /// Attach the store-wide column cipher to this connection
+    pub(super) fn with_column_cipher(mut self, column_cipher: Option<Arc<ColumnCipher>>) -> Self {
+        self.column_cipher = column_cipher;
+        self
+    }
+
+    /// The column cipher, if column encryption is enabled
+    pub(crate) fn column_cipher(&self) -> Option<&ColumnCipher> {
+        self.column_cipher.as_deref()
+    }
+
+    /// The store-wide cache, if caching is enabled
+    pub(crate) fn cache(&self) -> Option<&StoreCache> {
+        self.cache.as_deref()
     }
 }
 
@@ -34,6 +109,69 @@ impl<C> DbConnectionPrivate<C>
 where
     C: diesel::Connection,
 {
+    /// Attach the store-wide cache to this connection, and report the writes of the connection
+    /// to it
+    pub(super) fn with_cache(mut self, cache: Option<Arc<StoreCache>>) -> Self {
+        if let Some(cache) = &cache {
+            self.inner
+                .lock()
+                .set_instrumentation(CacheInstrumentation::new(cache.clone()));
+        }
+        self.cache = cache;
+        self
+    }
+
+    /// The cache to read rows from and fill, or `None` in a transaction, which may see rows
+    /// that are not committed yet
+    pub(crate) fn readable_cache(&self) -> Option<&StoreCache> {
+        let cache = self.cache.as_deref()?;
+        (!self.in_transaction()).then_some(cache)
+    }
+
+    fn in_transaction(&self) -> bool {
+        use diesel::connection::TransactionManager;
+
+        let mut conn = self.inner.lock();
+        let depth =
+            <C::TransactionManager as TransactionManager<C>>::transaction_manager_status_mut(
+                &mut *conn,
+            )
+            .transaction_depth();
+        !matches!(depth, Ok(None))
+    }
+
+    /// Run `callback` once the current transaction commits, e.g. to tell subscribers about a
+    /// change only once it is visible, or right away outside of a transaction. The callback is
+    /// dropped if the transaction rolls back.
+    pub(crate) fn after_commit(&self, callback: impl FnOnce() + Send + 'static) {
+        if self.in_transaction() {
+            self.after_commit.lock().push(Box::new(callback));
+        } else {
+            callback();
+        }
+    }
+
+    /// The number of callbacks waiting for a commit, marking where a transaction starts
+    pub(super) fn after_commit_mark(&self) -> usize {
+        self.after_commit.lock().len()
+    }
+
+    /// Run the callbacks registered for the transaction that began at `mark` once the outermost
+    /// transaction committed, or drop them if it rolled back
+    pub(super) fn finish_after_commit(&self, mark: usize, committed: bool) {
+        if !committed {
+            self.after_commit.lock().truncate(mark);
+            return;
+        }
+        if self.in_transaction() {
+            return;
+        }
+        let callbacks = std::mem::take(&mut *self.after_commit.lock());
+        for callback in callbacks {
+            callback();
+        }
+    }
+
     /// Do a scoped query with a mutable [`diesel::Connection`]
     /// reference
     pub(crate) fn raw_query<T, E, F>(&self, fun: F) -> Result<T, E>
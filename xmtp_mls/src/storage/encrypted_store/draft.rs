@@ -0,0 +1,116 @@
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+use super::{
+    column_encryption::row_aad,
+    db_connection::DbConnection,
+    schema::drafts::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// Table name the key of the encrypted content of drafts is derived from, see
+/// [`super::column_encryption`]
+pub(super) const DRAFTS_TABLE: &str = "drafts";
+
+/// A partially composed message, saved so it survives app restarts
+/// and is shared between windows of the same client.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = drafts)]
+#[diesel(primary_key(group_id))]
+pub struct StoredDraft {
+    /// Id of the conversation the draft belongs to
+    pub group_id: Vec<u8>,
+    /// Encoded content of the draft
+    pub encoded_content: Vec<u8>,
+    /// Time in nanoseconds the draft was last saved
+    pub updated_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Save the draft for a conversation, replacing any existing draft
+    pub fn set_draft<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        encoded_content: &[u8],
+    ) -> Result<(), StorageError> {
+        let encoded_content = match self.column_cipher() {
+            Some(cipher) => cipher.seal(
+                DRAFTS_TABLE,
+                &row_aad(&[group_id.as_ref()]),
+                encoded_content,
+            )?,
+            None => encoded_content.to_vec(),
+        };
+        let draft = StoredDraft {
+            group_id: group_id.as_ref().to_vec(),
+            encoded_content,
+            updated_at_ns: now_ns(),
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::drafts)
+                .values(&draft)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn get_draft<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredDraft>, StorageError> {
+        let draft: Option<StoredDraft> =
+            self.raw_query(|conn| dsl::drafts.find(group_id.as_ref()).first(conn).optional())?;
+        match (draft, self.column_cipher()) {
+            (Some(mut draft), Some(cipher)) => {
+                draft.encoded_content = cipher.open(
+                    DRAFTS_TABLE,
+                    &row_aad(&[&draft.group_id]),
+                    draft.encoded_content,
+                )?;
+                Ok(Some(draft))
+            }
+            (draft, _) => Ok(draft),
+        }
+    }
+
+    /// Remove the draft for a conversation, returning `true` if there was one
+    pub fn clear_draft<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<bool, StorageError> {
+        let deleted = self
+            .raw_query(|conn| diesel::delete(dsl::drafts.find(group_id.as_ref())).execute(conn))?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_saves_and_clears_drafts() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            assert!(conn.get_draft(&group.id).unwrap().is_none());
+
+            conn.set_draft(&group.id, b"hel").unwrap();
+            conn.set_draft(&group.id, b"hello").unwrap();
+            let draft = conn.get_draft(&group.id).unwrap().unwrap();
+            assert_eq!(draft.encoded_content, b"hello");
+
+            assert!(conn.clear_draft(&group.id).unwrap());
+            assert!(!conn.clear_draft(&group.id).unwrap());
+            assert!(conn.get_draft(&group.id).unwrap().is_none());
+        })
+        .await
+    }
+}
@@ -0,0 +1,122 @@
+//! Trusted SQLite extensions registered by the host app, such as custom collations or full-text
+//! search tokenizers.
+//!
+//! Extensions are passed to [`EncryptedMessageStore::new_with_extensions`] and loaded into every
+//! connection of the pool when it is opened, after the SQLCipher key is set, including the
+//! connections opened when the store reconnects.
+//!
+//! [`EncryptedMessageStore::new_with_extensions`]: super::EncryptedMessageStore::new_with_extensions
+use std::sync::Arc;
+
+use diesel::{r2d2, sqlite::SqliteConnection};
+
+/// An extension loaded into each connection to the database.
+///
+/// Extensions run with the same access to the database as the client, so only register code
+/// the app trusts.
+pub trait SqliteExtension: Send + Sync {
+    /// Name of the extension, for logs
+    fn name(&self) -> &str;
+
+    /// Register the extension on a newly opened connection, e.g. with
+    /// [`SqliteConnection::register_collation`]
+    fn load(&self, conn: &mut SqliteConnection) -> diesel::QueryResult<()>;
+}
+
+/// The extensions of a store, loaded in the order they were added
+#[derive(Clone, Default)]
+pub struct SqliteExtensions(Arc<Vec<Arc<dyn SqliteExtension>>>);
+
+impl std::fmt::Debug for SqliteExtensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|extension| extension.name()))
+            .finish()
+    }
+}
+
+impl SqliteExtensions {
+    pub fn push(&mut self, extension: Arc<dyn SqliteExtension>) {
+        Arc::make_mut(&mut self.0).push(extension);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(super) fn extend(&mut self, other: &SqliteExtensions) {
+        Arc::make_mut(&mut self.0).extend(other.0.iter().cloned());
+    }
+
+    pub(super) fn load(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        for extension in self.0.iter() {
+            extension.load(conn).map_err(|e| {
+                tracing::error!("failed to load SQLite extension {}: {e}", extension.name());
+                r2d2::Error::QueryError(e)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use diesel::{sql_query, RunQueryDsl};
+    use xmtp_common::tmp_path;
+
+    use super::*;
+    use crate::storage::{EncryptedMessageStore, StorageOption};
+
+    #[derive(Default)]
+    struct ReverseCollation {
+        loads: AtomicUsize,
+    }
+
+    impl SqliteExtension for ReverseCollation {
+        fn name(&self) -> &str {
+            "reverse collation"
+        }
+
+        fn load(&self, conn: &mut SqliteConnection) -> diesel::QueryResult<()> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            conn.register_collation("REVERSE", |a: &str, b: &str| b.cmp(a))
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_extensions_on_every_connection() {
+        let db_path = tmp_path();
+        let collation = Arc::new(ReverseCollation::default());
+        let mut extensions = SqliteExtensions::default();
+        extensions.push(collation.clone());
+        {
+            let store = EncryptedMessageStore::new_with_extensions(
+                StorageOption::Persistent(db_path.clone()),
+                EncryptedMessageStore::generate_enc_key(),
+                extensions,
+            )
+            .await
+            .unwrap();
+            let uses_collation = |store: &EncryptedMessageStore| {
+                store
+                    .conn()
+                    .unwrap()
+                    .raw_query(|conn| {
+                        sql_query("SELECT 'a' AS letter ORDER BY letter COLLATE REVERSE")
+                            .execute(conn)
+                    })
+                    .is_ok()
+            };
+            assert!(uses_collation(&store));
+
+            store.release_connection().unwrap();
+            let loads = collation.loads.load(Ordering::SeqCst);
+            store.reconnect().unwrap();
+            assert!(uses_collation(&store));
+            assert!(collation.loads.load(Ordering::SeqCst) > loads);
+        }
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+}
@@ -0,0 +1,76 @@
+//! Commits from the network that failed to process for good.
+//!
+//! Other members applied these commits, so a group with failed commits has most likely forked
+//! on this installation, see [`GroupDebugInfo::maybe_forked`](crate::groups::debug_info::GroupDebugInfo::maybe_forked).
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::failed_commits::{self, dsl},
+};
+use crate::storage::StorageError;
+
+#[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = failed_commits)]
+pub struct StoredFailedCommit {
+    pub group_id: Vec<u8>,
+    /// Cursor of the envelope the commit was received in
+    pub cursor: i64,
+    pub error: String,
+    pub failed_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Record that the commit received at `cursor` failed to process. Recording the same commit
+    /// again does nothing.
+    pub fn record_failed_commit(&self, commit: &StoredFailedCommit) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::failed_commits)
+                .values(commit)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The commits of `group_id` that failed to process, oldest first
+    pub fn failed_commits(&self, group_id: &[u8]) -> Result<Vec<StoredFailedCommit>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::failed_commits
+                .filter(dsl::group_id.eq(group_id))
+                .order(dsl::cursor.asc())
+                .load(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn records_each_commit_once() {
+        with_connection(|conn| {
+            let failed = |cursor| StoredFailedCommit {
+                group_id: vec![1],
+                cursor,
+                error: "wrong epoch".into(),
+                failed_at_ns: 10,
+            };
+            conn.record_failed_commit(&failed(2)).unwrap();
+            conn.record_failed_commit(&failed(1)).unwrap();
+            conn.record_failed_commit(&failed(2)).unwrap();
+
+            assert_eq!(
+                conn.failed_commits(&[1]).unwrap(),
+                vec![failed(1), failed(2)]
+            );
+            assert!(conn.failed_commits(&[2]).unwrap().is_empty());
+        })
+        .await
+    }
+}
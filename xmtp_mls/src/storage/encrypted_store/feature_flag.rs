@@ -0,0 +1,108 @@
+//! Feature flags set at runtime to gate experimental behaviors.
+//!
+//! Flags set on this installation are persisted in the user preferences, and override the
+//! defaults the client was built with. A flag can be synced, in which case it is sent to the
+//! other installations as a preference update and set there as well.
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    db_connection::DbConnection,
+    schema::user_preferences::dsl,
+    user_preferences::{NewStoredUserPreferences, StoredUserPreferences},
+};
+use crate::storage::StorageError;
+
+/// A flag set on this installation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureFlag {
+    pub enabled: bool,
+    /// Whether the flag is shared with the other installations
+    pub synced: bool,
+}
+
+/// The flags set on this installation, by name
+pub type FeatureFlags = BTreeMap<String, FeatureFlag>;
+
+impl DbConnection {
+    /// The flags set on this installation
+    pub fn feature_flags(&self) -> Result<FeatureFlags, StorageError> {
+        match StoredUserPreferences::load(self)?.feature_flags {
+            Some(flags) => serde_json::from_str(&flags)
+                .map_err(|e| StorageError::Deserialization(e.to_string())),
+            None => Ok(FeatureFlags::new()),
+        }
+    }
+
+    pub fn feature_flag(&self, name: &str) -> Result<Option<FeatureFlag>, StorageError> {
+        Ok(self.feature_flags()?.remove(name))
+    }
+
+    /// Set a flag, or unset it with `None` so that the default of the client applies again.
+    /// Returns whether the flag changed.
+    pub fn set_feature_flag(
+        &self,
+        name: &str,
+        flag: Option<FeatureFlag>,
+    ) -> Result<bool, StorageError> {
+        let mut preferences = StoredUserPreferences::load(self)?;
+        let mut flags = self.feature_flags()?;
+        let previous = match flag {
+            Some(flag) => flags.insert(name.to_string(), flag),
+            None => flags.remove(name),
+        };
+        if previous == flag {
+            return Ok(false);
+        }
+        preferences.feature_flags = Some(
+            serde_json::to_string(&flags)
+                .map_err(|e| StorageError::Serialization(e.to_string()))?,
+        );
+        let to_insert: NewStoredUserPreferences = (&preferences).into();
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::user_preferences)
+                .values(to_insert)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn flags_are_persisted() {
+        with_connection(|conn| {
+            assert!(conn.feature_flags().unwrap().is_empty());
+            let flag = FeatureFlag {
+                enabled: true,
+                synced: false,
+            };
+            assert!(conn
+                .set_feature_flag("new_sync_engine", Some(flag))
+                .unwrap());
+            assert!(!conn
+                .set_feature_flag("new_sync_engine", Some(flag))
+                .unwrap());
+            assert_eq!(conn.feature_flag("new_sync_engine").unwrap(), Some(flag));
+
+            // the other preferences are kept
+            let policy = conn.consent_policy().unwrap();
+            conn.set_consent_policy(&policy).unwrap();
+            assert_eq!(conn.feature_flags().unwrap().len(), 1);
+
+            assert!(conn.set_feature_flag("new_sync_engine", None).unwrap());
+            assert_eq!(conn.feature_flag("new_sync_engine").unwrap(), None);
+        })
+        .await
+    }
+}
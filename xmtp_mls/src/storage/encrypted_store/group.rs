@@ -1,13 +1,25 @@
 //! The Group database table. Stored information surrounding group membership and ID's.
 use super::{
-    consent_record::{ConsentState, StoredConsentRecord},
+    background_migration::BACKFILL_DM_PUBLIC_IDS,
+    consent_record::{ConsentState, ConsentType, StoredConsentRecord},
     db_connection::DbConnection,
-    schema::groups::{self, dsl},
-    Sqlite,
+    group_message::SortDirection,
+    group_name::order_by_name_sql,
+    schema::{
+        dm_public_ids::dsl as dm_public_ids_dsl,
+        groups::{self, dsl},
+    },
+    RawDbConnection, Sqlite,
 };
 
 use crate::{
-    groups::group_metadata::DmMembers, impl_fetch, impl_store, DuplicateItem, StorageError,
+    groups::group_metadata::DmMembers,
+    impl_fetch,
+    utils::{
+        hash::sha256,
+        id::{conversation_public_id, parse_conversation_public_id, ConversationPublicId},
+    },
+    DuplicateItem, StorageError, Store,
 };
 
 use crate::storage::NotFound;
@@ -55,10 +67,157 @@ pub struct StoredGroup {
     pub message_disappear_from_ns: Option<i64>,
     /// How long a message in the group can live in NS
     pub message_disappear_in_ns: Option<i64>,
+    /// The time in NS after which the group can no longer be used and its messages are purged
+    pub expires_at_ns: Option<i64>,
+    /// Enum, [`GroupLifecycleState`] tracking whether this installation left the group
+    pub lifecycle_state: GroupLifecycleState,
+    /// The time in NS at which this installation was removed from the group
+    pub left_at_ns: Option<i64>,
 }
 
 impl_fetch!(StoredGroup, groups, Vec<u8>);
-impl_store!(StoredGroup, groups);
+
+impl Store<DbConnection> for StoredGroup {
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::insert_into(groups::table)
+                .values(self)
+                .execute(conn)?;
+            record_dm_public_id(conn, self.dm_id.as_deref())
+        })?;
+        Ok(())
+    }
+}
+
+/// Record the hash that the [`conversation_public_id`] of the DM `dm_id` is made of, so that
+/// [`DbConnection::find_group_by_public_id`] finds the DM without hashing every `dm_id`
+pub(super) fn record_dm_public_id(
+    conn: &mut RawDbConnection,
+    dm_id: Option<&str>,
+) -> QueryResult<()> {
+    if let Some(dm_id) = dm_id {
+        diesel::insert_or_ignore_into(dm_public_ids_dsl::dm_public_ids)
+            .values((
+                dm_public_ids_dsl::dm_id_hash.eq(sha256(dm_id.as_bytes())),
+                dm_public_ids_dsl::dm_id.eq(dm_id),
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Delete every message of `group_ids`, along with everything attached to those messages
+/// (reactions, annotations, polls and votes, pins, live locations, drafts, filtered messages and
+/// join or leave requests). Returns the number of messages deleted.
+fn delete_messages_of_groups(
+    conn: &mut RawDbConnection,
+    group_ids: &[Vec<u8>],
+) -> QueryResult<usize> {
+    use super::schema::{
+        drafts::dsl as drafts_dsl, filtered_messages::dsl as filtered_dsl,
+        group_messages::dsl as messages_dsl, join_requests::dsl as join_requests_dsl,
+        leave_requests::dsl as leave_requests_dsl, live_locations::dsl as live_locations_dsl,
+        message_annotations::dsl as annotations_dsl, message_reactions::dsl as reactions_dsl,
+        orphan_poll_votes::dsl as orphan_votes_dsl, pinned_messages::dsl as pins_dsl,
+        poll_votes::dsl as votes_dsl, polls::dsl as polls_dsl,
+    };
+
+    let poll_ids: Vec<Vec<u8>> = polls_dsl::polls
+        .filter(polls_dsl::group_id.eq_any(group_ids))
+        .select(polls_dsl::id)
+        .load(conn)?;
+    diesel::delete(votes_dsl::poll_votes.filter(votes_dsl::poll_id.eq_any(&poll_ids)))
+        .execute(conn)?;
+    diesel::delete(polls_dsl::polls.filter(polls_dsl::group_id.eq_any(group_ids))).execute(conn)?;
+    diesel::delete(
+        orphan_votes_dsl::orphan_poll_votes.filter(orphan_votes_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        reactions_dsl::message_reactions.filter(reactions_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        annotations_dsl::message_annotations.filter(annotations_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(pins_dsl::pinned_messages.filter(pins_dsl::group_id.eq_any(group_ids)))
+        .execute(conn)?;
+    diesel::delete(
+        live_locations_dsl::live_locations.filter(live_locations_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(drafts_dsl::drafts.filter(drafts_dsl::group_id.eq_any(group_ids)))
+        .execute(conn)?;
+    diesel::delete(
+        filtered_dsl::filtered_messages.filter(filtered_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        join_requests_dsl::join_requests.filter(join_requests_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        leave_requests_dsl::leave_requests.filter(leave_requests_dsl::group_id.eq_any(group_ids)),
+    )
+    .execute(conn)?;
+    diesel::delete(messages_dsl::group_messages.filter(messages_dsl::group_id.eq_any(group_ids)))
+        .execute(conn)
+}
+
+/// Delete the group `group_id` with everything stored for it, except its consent record
+fn delete_group_rows(conn: &mut RawDbConnection, group_id: &[u8]) -> QueryResult<()> {
+    use super::schema::{
+        conversation_verifications::dsl as verifications_dsl,
+        failed_commits::dsl as failed_commits_dsl, group_avatars::dsl as avatars_dsl,
+        group_burst_budgets::dsl as burst_budgets_dsl, group_intents::dsl as intents_dsl,
+        group_key_rotation_policies::dsl as rotation_policies_dsl, group_names::dsl as names_dsl,
+        mega_group_shards::dsl as shards_dsl, pending_welcomes::dsl as pending_welcomes_dsl,
+        post_commit_actions::dsl as post_commit_dsl, refresh_state::dsl as refresh_state_dsl,
+    };
+
+    delete_messages_of_groups(conn, &[group_id.to_vec()])?;
+    diesel::delete(intents_dsl::group_intents.filter(intents_dsl::group_id.eq(group_id)))
+        .execute(conn)?;
+    diesel::delete(
+        post_commit_dsl::post_commit_actions.filter(post_commit_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        pending_welcomes_dsl::pending_welcomes.filter(pending_welcomes_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(avatars_dsl::group_avatars.filter(avatars_dsl::group_id.eq(group_id)))
+        .execute(conn)?;
+    diesel::delete(names_dsl::group_names.filter(names_dsl::group_id.eq(group_id)))
+        .execute(conn)?;
+    diesel::delete(
+        failed_commits_dsl::failed_commits.filter(failed_commits_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        verifications_dsl::conversation_verifications
+            .filter(verifications_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        burst_budgets_dsl::group_burst_budgets.filter(burst_budgets_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        rotation_policies_dsl::group_key_rotation_policies
+            .filter(rotation_policies_dsl::group_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(shards_dsl::mega_group_shards.filter(shards_dsl::group_id.eq(group_id)))
+        .execute(conn)?;
+    diesel::delete(
+        refresh_state_dsl::refresh_state.filter(refresh_state_dsl::entity_id.eq(group_id)),
+    )
+    .execute(conn)?;
+    diesel::delete(dsl::groups.find(group_id)).execute(conn)?;
+    Ok(())
+}
 
 impl StoredGroup {
     /// Create a new group from a welcome message
@@ -84,6 +243,9 @@ impl StoredGroup {
             last_message_ns: Some(now_ns()),
             message_disappear_from_ns: None,
             message_disappear_in_ns: None,
+            expires_at_ns: None,
+            lifecycle_state: GroupLifecycleState::Active,
+            left_at_ns: None,
         }
     }
 
@@ -111,6 +273,9 @@ impl StoredGroup {
             last_message_ns: Some(now_ns()),
             message_disappear_from_ns: None,
             message_disappear_in_ns: None,
+            expires_at_ns: None,
+            lifecycle_state: GroupLifecycleState::Active,
+            left_at_ns: None,
         }
     }
 
@@ -134,8 +299,42 @@ impl StoredGroup {
             last_message_ns: Some(now_ns()),
             message_disappear_from_ns: None,
             message_disappear_in_ns: None,
+            expires_at_ns: None,
+            lifecycle_state: GroupLifecycleState::Active,
+            left_at_ns: None,
         }
     }
+
+    /// Whether the group passed its expiration time. Expired groups are inactive.
+    pub fn is_expired(&self, now_ns: i64) -> bool {
+        self.expires_at_ns
+            .is_some_and(|expires_at_ns| expires_at_ns <= now_ns)
+    }
+
+    /// Whether this installation was removed from the group. Left groups are inactive.
+    pub fn has_left(&self) -> bool {
+        matches!(
+            self.lifecycle_state,
+            GroupLifecycleState::Left | GroupLifecycleState::Purged
+        )
+    }
+
+    /// See [`conversation_public_id`]
+    pub fn public_id(&self) -> String {
+        conversation_public_id(&self.id, self.dm_id.as_deref())
+    }
+}
+
+/// What the groups found with [`GroupQueryArgs`] are sorted by
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GroupSortKey {
+    #[default]
+    CreatedAt,
+    /// Time of the last message, or of the creation for groups without messages
+    LastMessage,
+    /// Group name, ignoring case and accents, see [`super::group_name`]. Groups without a name
+    /// come last.
+    Name,
 }
 
 #[derive(Debug, Default)]
@@ -148,6 +347,8 @@ pub struct GroupQueryArgs {
     pub consent_states: Option<Vec<ConsentState>>,
     pub include_sync_groups: bool,
     pub include_duplicate_dms: bool,
+    pub sort_by: GroupSortKey,
+    pub direction: Option<SortDirection>,
 }
 
 impl AsRef<GroupQueryArgs> for GroupQueryArgs {
@@ -217,6 +418,45 @@ impl GroupQueryArgs {
         self.include_sync_groups = true;
         self
     }
+
+    pub fn include_duplicate_dms(mut self) -> Self {
+        self.include_duplicate_dms = true;
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: GroupSortKey) -> Self {
+        self.sort_by = sort_by;
+        self
+    }
+
+    pub fn direction(self, direction: SortDirection) -> Self {
+        self.maybe_direction(Some(direction))
+    }
+
+    pub fn maybe_direction(mut self, direction: Option<SortDirection>) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// The `ORDER BY` clause of the query, for a table or view with the columns of `groups`
+    /// named `table`, where the time of the last message is `last_message_column`
+    pub(super) fn order_by_sql(&self, table: &str, last_message_column: &str) -> String {
+        let direction = match self.direction.as_ref().unwrap_or(&SortDirection::Ascending) {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        };
+        match self.sort_by {
+            GroupSortKey::CreatedAt => format!("{table}.created_at_ns {direction}"),
+            GroupSortKey::Name => format!(
+                "{}, {table}.created_at_ns {direction}",
+                order_by_name_sql(table, direction)
+            ),
+            GroupSortKey::LastMessage => format!(
+                "COALESCE({table}.{last_message_column}, {table}.created_at_ns) {direction}, \
+                 {table}.created_at_ns {direction}"
+            ),
+        }
+    }
 }
 
 impl DbConnection {
@@ -227,6 +467,7 @@ impl DbConnection {
     ) -> Result<Vec<StoredGroup>, StorageError> {
         use crate::storage::schema::consent_records::dsl as consent_dsl;
         use crate::storage::schema::groups::dsl as groups_dsl;
+        let args = args.as_ref();
         let GroupQueryArgs {
             allowed_states,
             created_after_ns,
@@ -236,11 +477,18 @@ impl DbConnection {
             consent_states,
             include_sync_groups,
             include_duplicate_dms,
-        } = args.as_ref();
+            sort_by: _,
+            direction: _,
+        } = args;
+        let order =
+            sql::<diesel::sql_types::BigInt>(&args.order_by_sql("groups", "last_message_ns"));
+        // Sealed names are sorted once opened, before the limit applies
+        let sort_opened_names = args.sort_by == GroupSortKey::Name && self.group_names_sealed();
 
         let mut query = groups_dsl::groups
             .filter(groups_dsl::conversation_type.ne(ConversationType::Sync))
-            .order(groups_dsl::created_at_ns.asc())
+            .filter(groups_dsl::lifecycle_state.ne(GroupLifecycleState::Staged))
+            .order(order.clone())
             .into_boxed();
 
         if !include_duplicate_dms {
@@ -255,8 +503,8 @@ impl DbConnection {
             ));
         }
 
-        if let Some(limit) = limit {
-            query = query.limit(*limit);
+        if let Some(limit) = limit.filter(|_| !sort_opened_names) {
+            query = query.limit(limit);
         }
 
         if let Some(allowed_states) = allowed_states {
@@ -275,12 +523,13 @@ impl DbConnection {
             query = query.filter(groups_dsl::conversation_type.eq(conversation_type));
         }
 
+        let now = xmtp_common::time::now_ns();
         let mut groups = if let Some(consent_states) = consent_states {
             if consent_states
                 .iter()
                 .any(|state| *state == ConsentState::Unknown)
             {
-                // Include both `Unknown`, `null`, and other specified states
+                // Include both `Unknown`, `null`, expired, and other specified states
                 let query = query
                     .left_join(
                         consent_dsl::consent_records
@@ -291,6 +540,7 @@ impl DbConnection {
                         consent_dsl::state
                             .is_null()
                             .or(consent_dsl::state.eq(ConsentState::Unknown))
+                            .or(consent_dsl::expires_at_ns.le(now))
                             .or(consent_dsl::state.eq_any(
                                 consent_states
                                     .iter()
@@ -300,11 +550,11 @@ impl DbConnection {
                             )),
                     )
                     .select(groups_dsl::groups::all_columns())
-                    .order(groups_dsl::created_at_ns.asc());
+                    .order(order.clone());
 
                 self.raw_query(|conn| query.load::<StoredGroup>(conn))?
             } else {
-                // Only include the specified states
+                // Only include the specified states that have not expired
                 let query = query
                     .inner_join(
                         consent_dsl::consent_records
@@ -312,8 +562,13 @@ impl DbConnection {
                                 .eq(consent_dsl::entity)),
                     )
                     .filter(consent_dsl::state.eq_any(consent_states.clone()))
+                    .filter(
+                        consent_dsl::expires_at_ns
+                            .is_null()
+                            .or(consent_dsl::expires_at_ns.gt(now)),
+                    )
                     .select(groups_dsl::groups::all_columns())
-                    .order(groups_dsl::created_at_ns.asc());
+                    .order(order.clone());
 
                 self.raw_query(|conn| query.load::<StoredGroup>(conn))?
             }
@@ -321,6 +576,14 @@ impl DbConnection {
             // Handle the case where `consent_states` is `None`
             self.raw_query(|conn| query.load::<StoredGroup>(conn))?
         };
+        if sort_opened_names {
+            groups = self.sort_by_opened_names(
+                groups,
+                |group| (&group.id, group.created_at_ns),
+                args.direction.clone().unwrap_or(SortDirection::Ascending),
+                *limit,
+            )?;
+        }
 
         // Were sync groups explicitly asked for? Was the include_sync_groups flag set to true?
         // Then query for those separately
@@ -357,12 +620,21 @@ impl DbConnection {
 
     /// Return a single group that matches the given ID
     pub fn find_group(&self, id: &[u8]) -> Result<Option<StoredGroup>, StorageError> {
+        let cache = self.readable_cache();
+        if let Some(group) = cache.and_then(|c| c.group(id)) {
+            return Ok(Some(group));
+        }
+        let generation = cache.map_or(0, |c| c.generation());
         let query = dsl::groups
             .order(dsl::created_at_ns.asc())
             .limit(1)
             .filter(dsl::id.eq(id));
         let groups = self.raw_query(|conn| query.load(conn))?;
-        Ok(groups.into_iter().next())
+        let group = groups.into_iter().next();
+        if let (Some(cache), Some(group)) = (cache, &group) {
+            cache.put_group(generation, group);
+        }
+        Ok(group)
     }
 
     /// Return a single group that matches the given welcome ID
@@ -402,6 +674,58 @@ impl DbConnection {
         Ok(groups.into_iter().next())
     }
 
+    /// Find the conversation with a [`conversation_public_id`]. For a DM, this is the DM group
+    /// with the latest message, as with [`Self::find_dm_group`].
+    ///
+    /// DMs are looked up by the hash recorded when they are stored. DMs stored before the hashes
+    /// were recorded are hashed one by one until their backfill completes, see
+    /// [`super::background_migration`].
+    pub fn find_group_by_public_id(
+        &self,
+        public_id: &str,
+    ) -> Result<Option<StoredGroup>, StorageError> {
+        let dm_id_hash = match parse_conversation_public_id(public_id) {
+            Some(ConversationPublicId::Group(group_id)) => return self.find_group(&group_id),
+            Some(ConversationPublicId::Dm(dm_id_hash)) => dm_id_hash,
+            None => return Ok(None),
+        };
+        let recorded: Option<String> = self.raw_query(|conn| {
+            dm_public_ids_dsl::dm_public_ids
+                .find(&dm_id_hash)
+                .select(dm_public_ids_dsl::dm_id)
+                .first(conn)
+                .optional()
+        })?;
+        let dm_id = match recorded {
+            Some(dm_id) => dm_id,
+            None if !self.background_migration_complete(BACKFILL_DM_PUBLIC_IDS)? => {
+                let dm_ids: Vec<Option<String>> = self.raw_query(|conn| {
+                    dsl::groups
+                        .filter(dsl::dm_id.is_not_null())
+                        .select(dsl::dm_id)
+                        .distinct()
+                        .load(conn)
+                })?;
+                let Some(dm_id) = dm_ids
+                    .into_iter()
+                    .flatten()
+                    .find(|dm_id| sha256(dm_id.as_bytes()) == dm_id_hash)
+                else {
+                    return Ok(None);
+                };
+                dm_id
+            }
+            None => return Ok(None),
+        };
+        Ok(self.raw_query(|conn| {
+            dsl::groups
+                .filter(dsl::dm_id.eq(Some(dm_id)))
+                .order(dsl::last_message_ns.desc())
+                .first(conn)
+                .optional()
+        })?)
+    }
+
     /// Updates group membership state
     pub fn update_group_membership<GroupId: AsRef<[u8]>>(
         &self,
@@ -497,6 +821,174 @@ impl DbConnection {
         Ok(())
     }
 
+    pub fn update_group_expires_at_ns(
+        &self,
+        group_id: Vec<u8>,
+        expires_at_ns: Option<i64>,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::groups.find(&group_id))
+                .set(dsl::expires_at_ns.eq(expires_at_ns))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+
+    /// Delete every message of the groups that expired before `now_ns`.
+    /// Returns the number of messages deleted.
+    pub fn purge_expired_groups(&self, now_ns: i64) -> Result<usize, StorageError> {
+        use super::schema::group_messages::dsl as messages_dsl;
+
+        Ok(self.raw_query(|conn| {
+            let expired_group_ids = dsl::groups
+                .filter(dsl::expires_at_ns.le(now_ns))
+                .select(dsl::id);
+            diesel::delete(
+                messages_dsl::group_messages
+                    .filter(messages_dsl::group_id.eq_any(expired_group_ids)),
+            )
+            .execute(conn)
+        })?)
+    }
+
+    /// Record that this installation was removed from a group at `left_at_ns`. Does nothing if
+    /// it already left.
+    pub fn mark_group_left(&self, group_id: &[u8], left_at_ns: i64) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::groups.find(group_id))
+                .filter(dsl::lifecycle_state.eq(GroupLifecycleState::Active))
+                .set((
+                    dsl::lifecycle_state.eq(GroupLifecycleState::Left),
+                    dsl::left_at_ns.eq(left_at_ns),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+
+    /// Make a group that this installation left active again, after it was added back
+    pub fn mark_group_rejoined(&self, group_id: &[u8]) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::groups.find(group_id))
+                .set((
+                    dsl::lifecycle_state.eq(GroupLifecycleState::Active),
+                    dsl::left_at_ns.eq(None::<i64>),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+
+    /// Make a [`GroupLifecycleState::Staged`] group active. Returns `false` if it was not staged.
+    pub fn activate_staged_group(&self, group_id: &[u8]) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::groups.find(group_id))
+                .filter(dsl::lifecycle_state.eq(GroupLifecycleState::Staged))
+                .set(dsl::lifecycle_state.eq(GroupLifecycleState::Active))
+                .execute(conn)
+        })?;
+
+        Ok(updated > 0)
+    }
+
+    /// Delete a [`GroupLifecycleState::Staged`] group with everything stored for it while it was
+    /// staged. Returns `false` if the group was not staged.
+    pub fn delete_staged_group(&self, group_id: &[u8]) -> Result<bool, StorageError> {
+        use super::schema::consent_records::dsl as consent_dsl;
+
+        let deleted = self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                let staged = dsl::groups
+                    .find(group_id)
+                    .filter(dsl::lifecycle_state.eq(GroupLifecycleState::Staged))
+                    .select(dsl::id)
+                    .first::<Vec<u8>>(conn)
+                    .optional()?;
+                if staged.is_none() {
+                    return Ok(false);
+                }
+                delete_group_rows(conn, group_id)?;
+                diesel::delete(
+                    consent_dsl::consent_records
+                        .filter(consent_dsl::entity_type.eq(ConsentType::ConversationId))
+                        .filter(consent_dsl::entity.eq(hex::encode(group_id))),
+                )
+                .execute(conn)?;
+                Ok::<_, diesel::result::Error>(true)
+            })
+        })?;
+
+        Ok(deleted)
+    }
+
+    /// Delete a group with everything stored for it, except its consent record, which is kept so
+    /// that the conversation keeps its consent if this installation is added back
+    pub fn delete_group(&self, group_id: &[u8]) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                delete_group_rows(conn, group_id)
+            })
+        })?;
+
+        Ok(())
+    }
+
+    /// The ids of every [`GroupLifecycleState::Staged`] group
+    pub fn find_staged_group_ids(&self) -> Result<Vec<Vec<u8>>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::groups
+                .filter(dsl::lifecycle_state.eq(GroupLifecycleState::Staged))
+                .select(dsl::id)
+                .load(conn)
+        })?)
+    }
+
+    /// Delete every message of the groups left before `left_before_ns`, along with everything
+    /// attached to those messages (reactions, annotations, polls and votes, pins, live locations,
+    /// drafts, filtered messages and join or leave requests), keeping the groups as
+    /// [`GroupLifecycleState::Purged`] tombstones. Returns the number of messages deleted.
+    pub fn purge_left_groups(&self, left_before_ns: i64) -> Result<usize, StorageError> {
+        let deleted = self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                let purged_group_ids: Vec<Vec<u8>> = dsl::groups
+                    .filter(dsl::lifecycle_state.eq(GroupLifecycleState::Left))
+                    .filter(dsl::left_at_ns.le(left_before_ns))
+                    .select(dsl::id)
+                    .load(conn)?;
+                if purged_group_ids.is_empty() {
+                    return Ok(0);
+                }
+                let deleted = delete_messages_of_groups(conn, &purged_group_ids)?;
+                diesel::update(dsl::groups.filter(dsl::id.eq_any(&purged_group_ids)))
+                    .set(dsl::lifecycle_state.eq(GroupLifecycleState::Purged))
+                    .execute(conn)?;
+                Ok::<_, diesel::result::Error>(deleted)
+            })
+        })?;
+
+        Ok(deleted)
+    }
+
+    /// Active conversations created before `created_before_ns` that are still waiting for the
+    /// user's consent, oldest first
+    pub fn stale_requests(&self, created_before_ns: i64) -> Result<Vec<StoredGroup>, StorageError> {
+        let requests = self.find_groups(
+            GroupQueryArgs {
+                include_duplicate_dms: true,
+                ..Default::default()
+            }
+            .created_before_ns(created_before_ns)
+            .consent_states(vec![ConsentState::Unknown]),
+        )?;
+        Ok(requests
+            .into_iter()
+            .filter(|group| group.lifecycle_state == GroupLifecycleState::Active)
+            .collect())
+    }
+
     pub fn insert_or_replace_group(&self, group: StoredGroup) -> Result<StoredGroup, StorageError> {
         tracing::info!("Trying to insert group");
         let stored_group = self.raw_query(|conn| {
@@ -505,6 +997,7 @@ impl DbConnection {
                 .on_conflict_do_nothing()
                 .get_result(conn)
                 .optional()?;
+            record_dm_public_id(conn, group.dm_id.as_deref())?;
 
             if maybe_inserted_group.is_none() {
                 let existing_group: StoredGroup = dsl::groups.find(group.id).first(conn)?;
@@ -559,6 +1052,8 @@ pub enum GroupMembershipState {
     Rejected = 2,
     /// User is Pending acceptance to the Group
     Pending = 3,
+    /// User asked to leave the Group, and waits for a member to remove them
+    PendingRemove = 4,
 }
 
 impl ToSql<Integer, Sqlite> for GroupMembershipState
@@ -580,6 +1075,52 @@ where
             1 => Ok(GroupMembershipState::Allowed),
             2 => Ok(GroupMembershipState::Rejected),
             3 => Ok(GroupMembershipState::Pending),
+            4 => Ok(GroupMembershipState::PendingRemove),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+/// Lifecycle of a group once this installation is removed from it.
+///
+/// `Active` groups become `Left` when a commit removes this installation. They are no longer
+/// synced and sending to them fails. After the grace period configured with
+/// [`crate::builder::ClientBuilder::left_group_retention`] their messages are deleted and they
+/// are kept as `Purged` tombstones, so apps can still show the conversation. Being added back
+/// makes a group `Active` again.
+///
+/// `Staged` groups are being created by [`crate::Client::create_group_with_message`], and are
+/// not listed until they become `Active` or are deleted.
+pub enum GroupLifecycleState {
+    Active = 1,
+    Left = 2,
+    Purged = 3,
+    Staged = 4,
+}
+
+impl ToSql<Integer, Sqlite> for GroupLifecycleState
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for GroupLifecycleState
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(GroupLifecycleState::Active),
+            2 => Ok(GroupLifecycleState::Left),
+            3 => Ok(GroupLifecycleState::Purged),
+            4 => Ok(GroupLifecycleState::Staged),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -712,11 +1253,7 @@ pub(crate) mod tests {
         state: ConsentState,
         entity: String,
     ) -> StoredConsentRecord {
-        StoredConsentRecord {
-            entity_type,
-            state,
-            entity,
-        }
+        StoredConsentRecord::new(entity_type, state, entity)
     }
 
     static TARGET_INBOX_ID: AtomicU16 = AtomicU16::new(2);
@@ -1054,6 +1591,23 @@ pub(crate) mod tests {
                 .unwrap();
             assert_eq!(unknown_results.len(), 1);
             assert_eq!(unknown_results[0].id, test_group_4.id);
+
+            // Expired consent reads as unknown before the sweep reverts it
+            conn.insert_or_replace_consent_records(&[test_group_1_consent.expiring_at(1)])
+                .unwrap();
+            let allowed_results = conn
+                .find_groups(
+                    GroupQueryArgs::default().consent_states([ConsentState::Allowed].to_vec()),
+                )
+                .unwrap();
+            assert_eq!(allowed_results.len(), 1);
+            assert_eq!(allowed_results[0].id, test_group_3.id);
+            let unknown_results = conn
+                .find_groups(
+                    GroupQueryArgs::default().consent_states([ConsentState::Unknown].to_vec()),
+                )
+                .unwrap();
+            assert_eq!(unknown_results.len(), 2);
         })
         .await
     }
@@ -1075,4 +1629,186 @@ pub(crate) mod tests {
         })
         .await
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_purge_expired_groups() {
+        use crate::storage::encrypted_store::group_message::tests::generate_message;
+
+        with_connection(|conn| {
+            let expired = generate_group(None);
+            let active = generate_group(None);
+            expired.store(conn).unwrap();
+            active.store(conn).unwrap();
+            for group in [&expired, &active] {
+                for _ in 0..2 {
+                    generate_message(None, Some(&group.id), None, None)
+                        .store(conn)
+                        .unwrap();
+                }
+            }
+
+            let now = now_ns();
+            conn.update_group_expires_at_ns(expired.id.clone(), Some(now - 1))
+                .unwrap();
+            conn.update_group_expires_at_ns(active.id.clone(), Some(now + 1_000_000_000))
+                .unwrap();
+
+            assert_eq!(conn.purge_expired_groups(now).unwrap(), 2);
+            assert_eq!(conn.purge_expired_groups(now).unwrap(), 0);
+
+            let expired: StoredGroup = conn.fetch(&expired.id).unwrap().unwrap();
+            assert!(expired.is_expired(now));
+            assert!(!conn
+                .find_group(&active.id)
+                .unwrap()
+                .unwrap()
+                .is_expired(now));
+            assert_eq!(
+                conn.get_group_messages(&active.id, &Default::default())
+                    .unwrap()
+                    .len(),
+                2
+            );
+        })
+        .await
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_find_groups_sorted() {
+        with_connection(|conn| {
+            let mut oldest = generate_group_with_created_at(None, 1);
+            oldest.last_message_ns = Some(30);
+            let mut middle = generate_group_with_created_at(None, 2);
+            middle.last_message_ns = Some(10);
+            // no messages, so sorted by its creation time
+            let newest = generate_group_with_created_at(None, 20);
+            for group in [&oldest, &middle, &newest] {
+                group.store(conn).unwrap();
+            }
+            let ids = |args: GroupQueryArgs| {
+                conn.find_groups(args)
+                    .unwrap()
+                    .into_iter()
+                    .map(|group| group.id)
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(
+                ids(GroupQueryArgs::default()),
+                vec![oldest.id.clone(), middle.id.clone(), newest.id.clone()]
+            );
+            assert_eq!(
+                ids(GroupQueryArgs::default()
+                    .direction(SortDirection::Descending)
+                    .limit(2)),
+                vec![newest.id.clone(), middle.id.clone()]
+            );
+            assert_eq!(
+                ids(GroupQueryArgs::default()
+                    .sort_by(GroupSortKey::LastMessage)
+                    .direction(SortDirection::Descending)),
+                vec![oldest.id.clone(), newest.id.clone(), middle.id.clone()]
+            );
+            assert_eq!(
+                ids(GroupQueryArgs::default()
+                    .sort_by(GroupSortKey::LastMessage)
+                    .consent_states(vec![ConsentState::Unknown])),
+                vec![middle.id.clone(), newest.id.clone(), oldest.id.clone()]
+            );
+        })
+        .await
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_find_group_by_public_id() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let dm = generate_dm(None);
+            dm.store(conn).unwrap();
+            // a duplicate of the DM, with a later message
+            let mut duplicate = generate_dm(None);
+            duplicate.dm_id = dm.dm_id.clone();
+            duplicate.last_message_ns = Some(dm.last_message_ns.unwrap_or_default() + 1);
+            duplicate.store(conn).unwrap();
+
+            let group_public_id = group.public_id();
+            assert_eq!(group_public_id, format!("g-{}", hex::encode(&group.id)));
+            assert_eq!(
+                conn.find_group_by_public_id(&group_public_id)
+                    .unwrap()
+                    .unwrap()
+                    .id,
+                group.id
+            );
+
+            assert_eq!(dm.public_id(), duplicate.public_id());
+            assert!(!dm.public_id().contains("placeholder"));
+            assert_eq!(
+                conn.find_group_by_public_id(&dm.public_id())
+                    .unwrap()
+                    .unwrap()
+                    .id,
+                duplicate.id
+            );
+
+            for unknown in ["", "g-", "g-zz", "dm-00", &generate_dm(None).public_id()] {
+                assert_eq!(conn.find_group_by_public_id(unknown).unwrap(), None);
+            }
+        })
+        .await
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_purge_left_groups_keeps_tombstones() {
+        use crate::storage::encrypted_store::group_message::tests::generate_message;
+
+        with_connection(|conn| {
+            let left = generate_group(None);
+            let left_recently = generate_group(None);
+            left.store(conn).unwrap();
+            left_recently.store(conn).unwrap();
+            for group in [&left, &left_recently] {
+                let message = generate_message(None, Some(&group.id), None, None);
+                message.store(conn).unwrap();
+                conn.set_pinned_messages(&group.id, &[message.id]).unwrap();
+                conn.set_draft(&group.id, b"draft").unwrap();
+            }
+
+            conn.mark_group_left(&left.id, 100).unwrap();
+            conn.mark_group_left(&left_recently.id, 300).unwrap();
+            // Leaving again keeps the first time
+            conn.mark_group_left(&left.id, 200).unwrap();
+            let stored: StoredGroup = conn.fetch(&left.id).unwrap().unwrap();
+            assert_eq!(stored.lifecycle_state, GroupLifecycleState::Left);
+            assert_eq!(stored.left_at_ns, Some(100));
+
+            assert_eq!(conn.purge_left_groups(200).unwrap(), 1);
+            assert_eq!(conn.purge_left_groups(200).unwrap(), 0);
+            let stored: StoredGroup = conn.fetch(&left.id).unwrap().unwrap();
+            assert_eq!(stored.lifecycle_state, GroupLifecycleState::Purged);
+            assert!(stored.has_left());
+            // Everything attached to the purged messages goes with them
+            assert!(conn.pinned_message_ids(&left.id).unwrap().is_empty());
+            assert!(conn.get_draft(&left.id).unwrap().is_none());
+            assert_eq!(
+                conn.get_group_messages(&left_recently.id, &Default::default())
+                    .unwrap()
+                    .len(),
+                1
+            );
+            assert_eq!(conn.pinned_message_ids(&left_recently.id).unwrap().len(), 1);
+            assert!(conn.get_draft(&left_recently.id).unwrap().is_some());
+
+            conn.mark_group_rejoined(&left.id).unwrap();
+            let stored: StoredGroup = conn.fetch(&left.id).unwrap().unwrap();
+            assert!(!stored.has_left());
+            assert_eq!(stored.left_at_ns, None);
+        })
+        .await
+    }
 }
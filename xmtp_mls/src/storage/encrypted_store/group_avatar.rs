@@ -0,0 +1,353 @@
+use diesel::{
+    prelude::*,
+    sql_types::{Bool, Nullable},
+    sqlite::Sqlite,
+};
+use xmtp_common::time::now_ns;
+
+use super::{
+    column_encryption::row_aad,
+    db_connection::DbConnection,
+    schema::{
+        avatar_blobs::{self, dsl as blobs_dsl},
+        group_avatars::{self, dsl},
+    },
+};
+use crate::{storage::StorageError, utils::hash::sha256};
+
+/// Table name the key of the encrypted url is derived from, see [`super::column_encryption`]
+pub(super) const GROUP_AVATARS_TABLE: &str = "group_avatars";
+
+/// The image url of a group, and the digest of the image once it is fetched.
+///
+/// Images are stored once in `avatar_blobs` however many groups use them, and are evicted by
+/// the database when no group uses them anymore, including when a group is deleted.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_avatars)]
+#[diesel(primary_key(group_id))]
+pub struct StoredGroupAvatar {
+    pub group_id: Vec<u8>,
+    pub url: String,
+    /// SHA-256 of the image, or `None` until it is fetched
+    pub digest: Option<Vec<u8>>,
+    pub updated_at_ns: i64,
+    /// Fetches of the url that failed
+    pub failed_attempts: i32,
+    /// Time in NS before which the url is not fetched again
+    pub retry_at_ns: i64,
+    /// Whether the image is too large to be stored. It is not fetched again until the url changes.
+    pub skipped: bool,
+    /// Keyed hash of the url when it is encrypted, see [`super::column_encryption`]
+    pub url_lookup: Option<Vec<u8>>,
+}
+
+/// A filter on the url of an avatar
+type UrlFilter = Box<dyn BoxableExpression<group_avatars::table, Sqlite, SqlType = Nullable<Bool>>>;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = avatar_blobs)]
+struct AvatarBlob {
+    digest: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl DbConnection {
+    /// The url as it is written and its lookup hash, encrypted if column encryption is enabled
+    fn seal_avatar_url(
+        &self,
+        group_id: &[u8],
+        url: &str,
+    ) -> Result<(String, Option<Vec<u8>>), StorageError> {
+        match self.column_cipher() {
+            Some(cipher) => Ok((
+                cipher.seal_text(GROUP_AVATARS_TABLE, &row_aad(&[group_id]), url)?,
+                Some(cipher.lookup_hash(GROUP_AVATARS_TABLE, url.as_bytes())),
+            )),
+            None => Ok((url.to_string(), None)),
+        }
+    }
+
+    /// Matches the avatars whose url is `url`. A sealed url is matched by its lookup hash.
+    fn avatar_url_is(&self, url: &str) -> UrlFilter {
+        match self.column_cipher() {
+            Some(cipher) => Box::new(
+                dsl::url_lookup.eq(cipher.lookup_hash(GROUP_AVATARS_TABLE, url.as_bytes())),
+            ),
+            None => Box::new(dsl::url.eq(url.to_string()).nullable()),
+        }
+    }
+
+    fn open_avatar(
+        &self,
+        mut avatar: StoredGroupAvatar,
+    ) -> Result<StoredGroupAvatar, StorageError> {
+        if let Some(cipher) = self.column_cipher() {
+            let url = std::mem::take(&mut avatar.url);
+            avatar.url =
+                cipher.open_text(GROUP_AVATARS_TABLE, &row_aad(&[&avatar.group_id]), url)?;
+        }
+        Ok(avatar)
+    }
+
+    /// Record the image url of a group. The image is fetched again if the url changed. An empty
+    /// url removes the avatar.
+    pub fn set_group_avatar_url(&self, group_id: &[u8], url: &str) -> Result<(), StorageError> {
+        if url.is_empty() {
+            self.raw_query(|conn| diesel::delete(dsl::group_avatars.find(group_id)).execute(conn))?;
+            return Ok(());
+        }
+        if self
+            .group_avatar_entry(group_id)?
+            .is_some_and(|avatar| avatar.url == url)
+        {
+            return Ok(());
+        }
+
+        let (sealed_url, url_lookup) = self.seal_avatar_url(group_id, url)?;
+        let avatar = StoredGroupAvatar {
+            group_id: group_id.to_vec(),
+            url: sealed_url,
+            digest: None,
+            updated_at_ns: now_ns(),
+            failed_attempts: 0,
+            retry_at_ns: 0,
+            skipped: false,
+            url_lookup,
+        };
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::group_avatars)
+                .values(&avatar)
+                .on_conflict(dsl::group_id)
+                .do_update()
+                .set((
+                    dsl::url.eq(&avatar.url),
+                    dsl::url_lookup.eq(&avatar.url_lookup),
+                    dsl::digest.eq(None::<Vec<u8>>),
+                    dsl::updated_at_ns.eq(avatar.updated_at_ns),
+                    dsl::failed_attempts.eq(0),
+                    dsl::retry_at_ns.eq(0),
+                    dsl::skipped.eq(false),
+                ))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn group_avatar_entry(
+        &self,
+        group_id: &[u8],
+    ) -> Result<Option<StoredGroupAvatar>, StorageError> {
+        let avatar =
+            self.raw_query(|conn| dsl::group_avatars.find(group_id).first(conn).optional())?;
+        avatar.map(|avatar| self.open_avatar(avatar)).transpose()
+    }
+
+    /// Avatars whose image has not been fetched yet and is due to be fetched, oldest first.
+    /// Skipped images and images waiting for their next retry are left out.
+    pub fn pending_group_avatars(&self) -> Result<Vec<StoredGroupAvatar>, StorageError> {
+        let avatars = self.raw_query(|conn| {
+            dsl::group_avatars
+                .filter(dsl::digest.is_null())
+                .filter(dsl::skipped.eq(false))
+                .filter(dsl::retry_at_ns.le(now_ns()))
+                .order(dsl::updated_at_ns.asc())
+                .load(conn)
+        })?;
+        avatars
+            .into_iter()
+            .map(|avatar| self.open_avatar(avatar))
+            .collect()
+    }
+
+    /// Record a failed fetch of the image at `url`, which is not fetched again before
+    /// `retry_at_ns`. Returns `false` if the url of the group changed in the meantime.
+    pub fn record_group_avatar_failure(
+        &self,
+        group_id: &[u8],
+        url: &str,
+        retry_at_ns: i64,
+    ) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::group_avatars.find(group_id))
+                .filter(self.avatar_url_is(url))
+                .set((
+                    dsl::failed_attempts.eq(dsl::failed_attempts + 1),
+                    dsl::retry_at_ns.eq(retry_at_ns),
+                ))
+                .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// Stop fetching the image at `url`, until the url of the group changes. Returns `false` if
+    /// the url changed in the meantime.
+    pub fn skip_group_avatar(&self, group_id: &[u8], url: &str) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::group_avatars.find(group_id))
+                .filter(self.avatar_url_is(url))
+                .set(dsl::skipped.eq(true))
+                .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// Store the image fetched from `url` for a group. Returns `false` without storing anything
+    /// if the url of the group changed in the meantime.
+    pub fn store_group_avatar(
+        &self,
+        group_id: &[u8],
+        url: &str,
+        data: &[u8],
+    ) -> Result<bool, StorageError> {
+        let blob = AvatarBlob {
+            digest: sha256(data),
+            data: data.to_vec(),
+        };
+        let updated = self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                diesel::insert_or_ignore_into(blobs_dsl::avatar_blobs)
+                    .values(&blob)
+                    .execute(conn)?;
+                let updated = diesel::update(dsl::group_avatars.find(group_id))
+                    .filter(self.avatar_url_is(url))
+                    .set(dsl::digest.eq(&blob.digest))
+                    .execute(conn)?;
+                if updated == 0 {
+                    // Nothing references the image, don't keep it around
+                    diesel::delete(
+                        blobs_dsl::avatar_blobs
+                            .filter(blobs_dsl::digest.eq(&blob.digest))
+                            .filter(diesel::dsl::not(diesel::dsl::exists(
+                                dsl::group_avatars.filter(dsl::digest.eq(&blob.digest)),
+                            ))),
+                    )
+                    .execute(conn)?;
+                }
+                Ok::<_, diesel::result::Error>(updated)
+            })
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// The image of a group, if it was fetched
+    pub fn group_avatar(&self, group_id: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::group_avatars
+                .inner_join(
+                    blobs_dsl::avatar_blobs.on(dsl::digest.eq(blobs_dsl::digest.nullable())),
+                )
+                .filter(dsl::group_id.eq(group_id))
+                .select(blobs_dsl::data)
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    #[cfg(test)]
+    fn count_avatar_blobs(&self) -> Result<i64, StorageError> {
+        Ok(self.raw_query(|conn| blobs_dsl::avatar_blobs.count().get_result(conn))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_deduplicates_and_evicts_avatars() {
+        with_connection(|conn| {
+            let first = generate_group(None);
+            first.store(conn).unwrap();
+            let second = generate_group(None);
+            second.store(conn).unwrap();
+            let image = b"png".to_vec();
+
+            conn.set_group_avatar_url(&first.id, "https://a/1.png")
+                .unwrap();
+            conn.set_group_avatar_url(&second.id, "https://b/1.png")
+                .unwrap();
+            assert_eq!(conn.pending_group_avatars().unwrap().len(), 2);
+            assert!(conn
+                .store_group_avatar(&first.id, "https://a/1.png", &image)
+                .unwrap());
+            assert!(conn
+                .store_group_avatar(&second.id, "https://b/1.png", &image)
+                .unwrap());
+            // The url changed while the image was fetched
+            assert!(!conn
+                .store_group_avatar(&first.id, "https://a/0.png", b"old")
+                .unwrap());
+            assert!(conn.pending_group_avatars().unwrap().is_empty());
+            assert_eq!(conn.count_avatar_blobs().unwrap(), 1);
+            assert_eq!(conn.group_avatar(&first.id).unwrap(), Some(image.clone()));
+
+            // Setting the same url again keeps the image
+            conn.set_group_avatar_url(&first.id, "https://a/1.png")
+                .unwrap();
+            assert_eq!(conn.group_avatar(&first.id).unwrap(), Some(image.clone()));
+
+            conn.set_group_avatar_url(&first.id, "").unwrap();
+            assert_eq!(conn.group_avatar(&first.id).unwrap(), None);
+            assert_eq!(conn.count_avatar_blobs().unwrap(), 1);
+
+            conn.raw_query(|conn| {
+                use crate::storage::schema::groups::dsl as groups_dsl;
+                diesel::delete(groups_dsl::groups.find(&second.id)).execute(conn)
+            })
+            .unwrap();
+            assert_eq!(conn.group_avatar_entry(&second.id).unwrap(), None);
+            assert_eq!(conn.count_avatar_blobs().unwrap(), 0);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_waits_after_failures_and_skips_until_the_url_changes() {
+        with_connection(|conn| {
+            let failing = generate_group(None);
+            failing.store(conn).unwrap();
+            let large = generate_group(None);
+            large.store(conn).unwrap();
+            conn.set_group_avatar_url(&failing.id, "https://a/1.png")
+                .unwrap();
+            conn.set_group_avatar_url(&large.id, "https://b/1.png")
+                .unwrap();
+
+            let later = now_ns() + 1_000_000_000_000;
+            assert!(conn
+                .record_group_avatar_failure(&failing.id, "https://a/1.png", later)
+                .unwrap());
+            assert!(conn
+                .skip_group_avatar(&large.id, "https://b/1.png")
+                .unwrap());
+            // The url changed while the image was fetched
+            assert!(!conn
+                .record_group_avatar_failure(&failing.id, "https://a/0.png", later)
+                .unwrap());
+            assert!(conn.pending_group_avatars().unwrap().is_empty());
+            let entry = conn.group_avatar_entry(&failing.id).unwrap().unwrap();
+            assert_eq!(entry.failed_attempts, 1);
+            assert_eq!(entry.retry_at_ns, later);
+
+            // A new url is fetched right away
+            conn.set_group_avatar_url(&failing.id, "https://a/2.png")
+                .unwrap();
+            conn.set_group_avatar_url(&large.id, "https://b/2.png")
+                .unwrap();
+            let pending = conn.pending_group_avatars().unwrap();
+            assert_eq!(pending.len(), 2);
+            assert!(pending
+                .iter()
+                .all(|avatar| avatar.failed_attempts == 0 && !avatar.skipped));
+        })
+        .await
+    }
+}
@@ -0,0 +1,85 @@
+//! Per-group burst budgets of the message streams, see
+//! [`BurstBudget`](crate::subscriptions::burst::BurstBudget).
+//!
+//! Budgets are kept here so that they survive restarts. The messages counted in the current
+//! window are only kept in memory.
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::group_burst_budgets::{self, dsl},
+};
+use crate::storage::StorageError;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_burst_budgets)]
+#[diesel(primary_key(group_id))]
+pub struct StoredGroupBurstBudget {
+    pub group_id: Vec<u8>,
+    /// Messages delivered per window
+    pub max_messages: i32,
+    pub window_ms: i64,
+}
+
+impl DbConnection {
+    /// Set the burst budget of `group_id`, or remove it with `None`
+    pub fn set_group_burst_budget(
+        &self,
+        group_id: &[u8],
+        budget: Option<StoredGroupBurstBudget>,
+    ) -> Result<(), StorageError> {
+        let Some(budget) = budget else {
+            self.raw_query(|conn| {
+                diesel::delete(dsl::group_burst_budgets.find(group_id)).execute(conn)
+            })?;
+            return Ok(());
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::group_burst_budgets)
+                .values(&budget)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The burst budgets of every group
+    pub fn group_burst_budgets(&self) -> Result<Vec<StoredGroupBurstBudget>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::group_burst_budgets.load(conn))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn sets_and_clears_the_budget() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let budget = |max_messages| StoredGroupBurstBudget {
+                group_id: group.id.clone(),
+                max_messages,
+                window_ms: 1000,
+            };
+
+            conn.set_group_burst_budget(&group.id, Some(budget(10)))
+                .unwrap();
+            conn.set_group_burst_budget(&group.id, Some(budget(20)))
+                .unwrap();
+            assert_eq!(conn.group_burst_budgets().unwrap(), vec![budget(20)]);
+
+            conn.set_group_burst_budget(&group.id, None).unwrap();
+            assert!(conn.group_burst_budgets().unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -8,6 +8,7 @@ use diesel::{
 };
 use prost::Message;
 
+pub use super::intent_state::{IntentState, IntentTransition};
 use super::{
     db_connection::DbConnection,
     group,
@@ -19,7 +20,7 @@ use crate::{
     impl_fetch, impl_store,
     storage::{NotFound, StorageError},
     utils::id::calculate_message_id,
-    Delete,
+    Delete, Fetch,
 };
 use xmtp_proto::xmtp::mls::message_contents::{
     plaintext_envelope::{Content, V1},
@@ -37,6 +38,10 @@ pub enum IntentKind {
     UpdateGroupMembership = 4,
     UpdateAdminList = 5,
     UpdatePermission = 6,
+    ReaddInstallations = 7,
+    RemoveInstallations = 8,
+    TransferSuperAdmin = 9,
+    UpdatePinnedMessages = 10,
 }
 
 impl std::fmt::Display for IntentKind {
@@ -48,21 +53,15 @@ impl std::fmt::Display for IntentKind {
             IntentKind::UpdateGroupMembership => "UpdateGroupMembership",
             IntentKind::UpdateAdminList => "UpdateAdminList",
             IntentKind::UpdatePermission => "UpdatePermission",
+            IntentKind::ReaddInstallations => "ReaddInstallations",
+            IntentKind::RemoveInstallations => "RemoveInstallations",
+            IntentKind::TransferSuperAdmin => "TransferSuperAdmin",
+            IntentKind::UpdatePinnedMessages => "UpdatePinnedMessages",
         };
         write!(f, "{}", description)
     }
 }
 
-#[repr(i32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
-#[diesel(sql_type = Integer)]
-pub enum IntentState {
-    ToPublish = 1,
-    Published = 2,
-    Committed = 3,
-    Error = 4,
-}
-
 #[derive(Queryable, Identifiable, Debug, PartialEq, Clone)]
 #[diesel(table_name = group_intents)]
 #[diesel(primary_key(id))]
@@ -200,11 +199,9 @@ impl DbConnection {
         let rows_changed = self.raw_query(|conn| {
             diesel::update(dsl::group_intents)
                 .filter(dsl::id.eq(intent_id))
-                // State machine requires that the only valid state transition to Published is from
-                // ToPublish
-                .filter(dsl::state.eq(IntentState::ToPublish))
+                .filter(dsl::state.eq_any(IntentTransition::Publish.sources()))
                 .set((
-                    dsl::state.eq(IntentState::Published),
+                    dsl::state.eq(IntentTransition::Publish.target()),
                     dsl::payload_hash.eq(payload_hash),
                     dsl::post_commit_data.eq(post_commit_data),
                     dsl::staged_commit.eq(staged_commit),
@@ -229,15 +226,13 @@ impl DbConnection {
         Ok(())
     }
 
-    // Set the intent with the given ID to `Committed`
+    // Set the intent with the given ID to `Committed`, and queue its post commit action
     pub fn set_group_intent_committed(&self, intent_id: ID) -> Result<(), StorageError> {
         let rows_changed = self.raw_query(|conn| {
             diesel::update(dsl::group_intents)
                 .filter(dsl::id.eq(intent_id))
-                // State machine requires that the only valid state transition to Committed is from
-                // Published
-                .filter(dsl::state.eq(IntentState::Published))
-                .set(dsl::state.eq(IntentState::Committed))
+                .filter(dsl::state.eq_any(IntentTransition::Commit.sources()))
+                .set(dsl::state.eq(IntentTransition::Commit.target()))
                 .execute(conn)
         })?;
 
@@ -246,6 +241,16 @@ impl DbConnection {
             return Err(NotFound::IntentForCommitted(intent_id).into());
         }
 
+        let intent: Option<StoredGroupIntent> = self.fetch(&intent_id)?;
+        if let Some(StoredGroupIntent {
+            group_id,
+            post_commit_data: Some(action),
+            ..
+        }) = intent
+        {
+            self.enqueue_post_commit_action(&group_id, intent_id, &action)?;
+        }
+
         Ok(())
     }
 
@@ -255,11 +260,9 @@ impl DbConnection {
         let rows_changed = self.raw_query(|conn| {
             diesel::update(dsl::group_intents)
                 .filter(dsl::id.eq(intent_id))
-                // State machine requires that the only valid state transition to ToPublish is from
-                // Published
-                .filter(dsl::state.eq(IntentState::Published))
+                .filter(dsl::state.eq_any(IntentTransition::Republish.sources()))
                 .set((
-                    dsl::state.eq(IntentState::ToPublish),
+                    dsl::state.eq(IntentTransition::Republish.target()),
                     // When moving to ToPublish, clear the payload hash and post commit data
                     dsl::payload_hash.eq(None::<Vec<u8>>),
                     dsl::post_commit_data.eq(None::<Vec<u8>>),
@@ -281,7 +284,8 @@ impl DbConnection {
         let rows_changed = self.raw_query(|conn| {
             diesel::update(dsl::group_intents)
                 .filter(dsl::id.eq(intent_id))
-                .set(dsl::state.eq(IntentState::Error))
+                .filter(dsl::state.eq_any(IntentTransition::Fail.sources()))
+                .set(dsl::state.eq(IntentTransition::Fail.target()))
                 .execute(conn)
         })?;
 
@@ -356,31 +360,10 @@ where
             4 => Ok(IntentKind::UpdateGroupMembership),
             5 => Ok(IntentKind::UpdateAdminList),
             6 => Ok(IntentKind::UpdatePermission),
-            x => Err(format!("Unrecognized variant {}", x).into()),
-        }
-    }
-}
-
-impl ToSql<Integer, Sqlite> for IntentState
-where
-    i32: ToSql<Integer, Sqlite>,
-{
-    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
-        out.set_value(*self as i32);
-        Ok(IsNull::No)
-    }
-}
-
-impl FromSql<Integer, Sqlite> for IntentState
-where
-    i32: FromSql<Integer, Sqlite>,
-{
-    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
-        match i32::from_sql(bytes)? {
-            1 => Ok(IntentState::ToPublish),
-            2 => Ok(IntentState::Published),
-            3 => Ok(IntentState::Committed),
-            4 => Ok(IntentState::Error),
+            7 => Ok(IntentKind::ReaddInstallations),
+            8 => Ok(IntentKind::RemoveInstallations),
+            9 => Ok(IntentKind::TransferSuperAdmin),
+            10 => Ok(IntentKind::UpdatePinnedMessages),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -0,0 +1,321 @@
+//! Checks of invariants the stored group state must uphold across operations.
+//!
+//! The [`GroupInvariantChecker`] compares the database to what it saw on its previous check, so
+//! it also catches invariants that span several operations, such as cursors moving backwards. It
+//! is used by tests, and can be enabled at runtime with
+//! [`EncryptedMessageStore::with_invariant_checks`](super::EncryptedMessageStore::with_invariant_checks),
+//! in which case every group sync logs the violations it finds.
+use std::collections::HashMap;
+
+use diesel::{dsl::not, prelude::*};
+use parking_lot::Mutex;
+
+use super::{
+    db_connection::DbConnection,
+    group_intent::IntentState,
+    refresh_state::{EntityKind, RefreshState},
+    schema::{group_intents, group_messages, groups, refresh_state},
+};
+use crate::storage::StorageError;
+
+/// A violated invariant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The cursor of an entity is lower than on the previous check
+    CursorRegressed {
+        entity_id: Vec<u8>,
+        entity_kind: EntityKind,
+        previous: i64,
+        current: i64,
+    },
+    /// A message is stored for a group that does not exist
+    MessageForUnknownGroup {
+        message_id: Vec<u8>,
+        group_id: Vec<u8>,
+    },
+    /// An intent is in a state it cannot reach from its state on the previous check
+    IllegalIntentTransition {
+        intent_id: i32,
+        previous: IntentState,
+        current: IntentState,
+    },
+}
+
+impl std::fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use InvariantViolation::*;
+        match self {
+            CursorRegressed {
+                entity_id,
+                entity_kind,
+                previous,
+                current,
+            } => write!(
+                f,
+                "{entity_kind} cursor of {} went from {previous} back to {current}",
+                hex::encode(entity_id)
+            ),
+            MessageForUnknownGroup {
+                message_id,
+                group_id,
+            } => write!(
+                f,
+                "message {} is stored for unknown group {}",
+                hex::encode(message_id),
+                hex::encode(group_id)
+            ),
+            IllegalIntentTransition {
+                intent_id,
+                previous,
+                current,
+            } => write!(
+                f,
+                "intent {intent_id} moved from {previous:?} to {current:?}"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Snapshot {
+    cursors: HashMap<(Vec<u8>, EntityKind), i64>,
+    intents: HashMap<i32, IntentState>,
+}
+
+/// Checks the stored group state against its invariants, and against its previous check
+#[derive(Debug, Default)]
+pub struct GroupInvariantChecker {
+    previous: Mutex<Snapshot>,
+}
+
+impl GroupInvariantChecker {
+    /// Check the invariants, returning every violation found since the previous check
+    pub fn check(&self, conn: &DbConnection) -> Result<Vec<InvariantViolation>, StorageError> {
+        let cursors: Vec<RefreshState> = conn.raw_query(|conn| refresh_state::table.load(conn))?;
+        let intents: Vec<(i32, IntentState)> = conn.raw_query(|conn| {
+            group_intents::table
+                .select((group_intents::id, group_intents::state))
+                .load(conn)
+        })?;
+        let orphan_messages: Vec<(Vec<u8>, Vec<u8>)> = conn.raw_query(|conn| {
+            group_messages::table
+                .filter(not(
+                    group_messages::group_id.eq_any(groups::table.select(groups::id))
+                ))
+                .select((group_messages::id, group_messages::group_id))
+                .load(conn)
+        })?;
+
+        let current = Snapshot {
+            cursors: cursors
+                .into_iter()
+                .map(|state| ((state.entity_id, state.entity_kind), state.cursor))
+                .collect(),
+            intents: intents.into_iter().collect(),
+        };
+        let mut previous = self.previous.lock();
+        let mut violations = vec![];
+        for ((entity_id, entity_kind), cursor) in &current.cursors {
+            let key = (entity_id.clone(), *entity_kind);
+            if let Some(previous_cursor) = previous.cursors.get(&key) {
+                if cursor < previous_cursor {
+                    violations.push(InvariantViolation::CursorRegressed {
+                        entity_id: entity_id.clone(),
+                        entity_kind: *entity_kind,
+                        previous: *previous_cursor,
+                        current: *cursor,
+                    });
+                }
+            }
+        }
+        for (intent_id, state) in &current.intents {
+            if let Some(previous_state) = previous.intents.get(intent_id) {
+                if !previous_state.can_reach(*state) {
+                    violations.push(InvariantViolation::IllegalIntentTransition {
+                        intent_id: *intent_id,
+                        previous: *previous_state,
+                        current: *state,
+                    });
+                }
+            }
+        }
+        violations.extend(orphan_messages.into_iter().map(|(message_id, group_id)| {
+            InvariantViolation::MessageForUnknownGroup {
+                message_id,
+                group_id,
+            }
+        }));
+        *previous = current;
+
+        Ok(violations)
+    }
+}
+
+impl DbConnection {
+    /// Check the group invariants if the store was opened with invariant checks, logging the
+    /// violations found
+    pub fn check_group_invariants(&self) -> Result<(), StorageError> {
+        let Some(checker) = self.invariant_checker() else {
+            return Ok(());
+        };
+        for violation in checker.check(self)? {
+            tracing::error!("group invariant violated: {violation}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use proptest::{collection::vec, prelude::*, test_runner::TestRunner};
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group,
+            group_intent::{IntentKind, NewGroupIntent},
+            group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        QueueIntent,
+        PublishIntent(usize),
+        CommitIntent(usize),
+        RetryIntent(usize),
+        FailIntent(usize),
+        AdvanceCursor(i64),
+        StoreMessage,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::QueueIntent),
+            any::<usize>().prop_map(Op::PublishIntent),
+            any::<usize>().prop_map(Op::CommitIntent),
+            any::<usize>().prop_map(Op::RetryIntent),
+            any::<usize>().prop_map(Op::FailIntent),
+            (0..1_000i64).prop_map(Op::AdvanceCursor),
+            Just(Op::StoreMessage),
+        ]
+    }
+
+    /// Apply `op` through the storage API, ignoring the transitions it refuses
+    fn apply(conn: &DbConnection, group_id: &[u8], intents: &mut Vec<i32>, op: Op) {
+        let pick = |i: usize| intents.get(i % intents.len().max(1)).copied();
+        let _ = match op {
+            Op::QueueIntent => {
+                let intent = conn
+                    .insert_group_intent(NewGroupIntent::new(
+                        IntentKind::SendMessage,
+                        group_id.to_vec(),
+                        vec![],
+                    ))
+                    .unwrap();
+                intents.push(intent.id);
+                Ok(())
+            }
+            Op::PublishIntent(i) => match pick(i) {
+                Some(id) => {
+                    conn.set_group_intent_published(id, id.to_be_bytes().to_vec(), None, None, 0)
+                }
+                None => Ok(()),
+            },
+            Op::CommitIntent(i) => pick(i).map_or(Ok(()), |id| conn.set_group_intent_committed(id)),
+            Op::RetryIntent(i) => pick(i).map_or(Ok(()), |id| conn.set_group_intent_to_publish(id)),
+            Op::FailIntent(i) => pick(i).map_or(Ok(()), |id| conn.set_group_intent_error(id)),
+            Op::AdvanceCursor(cursor) => {
+                conn.get_last_cursor_for_id(group_id, EntityKind::Group)
+                    .unwrap();
+                conn.update_cursor(group_id, EntityKind::Group, cursor)
+                    .map(|_| ())
+            }
+            Op::StoreMessage => generate_message(None, Some(group_id), None, None).store(conn),
+        };
+    }
+
+    #[tokio::test]
+    async fn storage_operations_uphold_invariants() {
+        with_connection(|conn| {
+            let checker = GroupInvariantChecker::default();
+            TestRunner::default()
+                .run(&vec(op(), 1..50), |ops| {
+                    let group = generate_group(None);
+                    group.store(conn).unwrap();
+                    let mut intents = vec![];
+                    for op in ops {
+                        apply(conn, &group.id, &mut intents, op);
+                        prop_assert_eq!(
+                            checker.check(conn).unwrap(),
+                            Vec::<InvariantViolation>::new()
+                        );
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn reports_violations() {
+        with_connection(|conn| {
+            let checker = GroupInvariantChecker::default();
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            conn.get_last_cursor_for_id(&group.id, EntityKind::Group)
+                .unwrap();
+            conn.update_cursor(&group.id, EntityKind::Group, 10)
+                .unwrap();
+            let intent = conn
+                .insert_group_intent(NewGroupIntent::new(
+                    IntentKind::SendMessage,
+                    group.id.clone(),
+                    vec![],
+                ))
+                .unwrap();
+            conn.set_group_intent_error(intent.id).unwrap();
+            assert!(checker.check(conn).unwrap().is_empty());
+
+            // Bypass the storage API and its guards, which refuse these writes
+            conn.raw_query(|conn| {
+                diesel::sql_query("DROP TRIGGER group_intents_state_guard").execute(conn)?;
+                diesel::update(refresh_state::table)
+                    .set(refresh_state::cursor.eq(5))
+                    .execute(conn)?;
+                diesel::update(group_intents::table)
+                    .set(group_intents::state.eq(IntentState::ToPublish))
+                    .execute(conn)
+            })
+            .unwrap();
+            let message = generate_message(None, None, None, None);
+            message.store(conn).unwrap();
+
+            let violations = checker.check(conn).unwrap();
+            assert_eq!(
+                violations,
+                vec![
+                    InvariantViolation::CursorRegressed {
+                        entity_id: group.id.clone(),
+                        entity_kind: EntityKind::Group,
+                        previous: 10,
+                        current: 5,
+                    },
+                    InvariantViolation::IllegalIntentTransition {
+                        intent_id: intent.id,
+                        previous: IntentState::Error,
+                        current: IntentState::ToPublish,
+                    },
+                    InvariantViolation::MessageForUnknownGroup {
+                        message_id: message.id,
+                        group_id: message.group_id,
+                    },
+                ]
+            );
+        })
+        .await
+    }
+}
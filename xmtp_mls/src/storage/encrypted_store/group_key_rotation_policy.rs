@@ -0,0 +1,96 @@
+//! Per-group overrides of how often this installation rotates its leaf key.
+//!
+//! Groups without a policy use the interval the client is built with, see
+//! [`ClientBuilder::key_rotation_interval`](crate::builder::ClientBuilder::key_rotation_interval).
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    schema::group_key_rotation_policies::{self, dsl},
+};
+use crate::storage::StorageError;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_key_rotation_policies)]
+#[diesel(primary_key(group_id))]
+pub struct StoredKeyRotationPolicy {
+    pub group_id: Vec<u8>,
+    /// Longest time in nanoseconds the leaf key may go without rotation
+    pub interval_ns: i64,
+    pub updated_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Set the rotation interval of `group_id`, or remove it with `None` so that the client
+    /// interval applies again
+    pub fn set_key_rotation_interval(
+        &self,
+        group_id: &[u8],
+        interval_ns: Option<i64>,
+    ) -> Result<(), StorageError> {
+        let Some(interval_ns) = interval_ns else {
+            self.raw_query(|conn| {
+                diesel::delete(dsl::group_key_rotation_policies.find(group_id)).execute(conn)
+            })?;
+            return Ok(());
+        };
+        let policy = StoredKeyRotationPolicy {
+            group_id: group_id.to_vec(),
+            interval_ns,
+            updated_at_ns: now_ns(),
+        };
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::group_key_rotation_policies)
+                .values(&policy)
+                .on_conflict(dsl::group_id)
+                .do_update()
+                .set((
+                    dsl::interval_ns.eq(policy.interval_ns),
+                    dsl::updated_at_ns.eq(policy.updated_at_ns),
+                ))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The rotation interval set on `group_id`, if any
+    pub fn key_rotation_interval(&self, group_id: &[u8]) -> Result<Option<i64>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::group_key_rotation_policies
+                .find(group_id)
+                .select(dsl::interval_ns)
+                .first(conn)
+                .optional()
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn sets_and_clears_the_interval() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            assert_eq!(conn.key_rotation_interval(&group.id).unwrap(), None);
+
+            conn.set_key_rotation_interval(&group.id, Some(10)).unwrap();
+            conn.set_key_rotation_interval(&group.id, Some(20)).unwrap();
+            assert_eq!(conn.key_rotation_interval(&group.id).unwrap(), Some(20));
+
+            conn.set_key_rotation_interval(&group.id, None).unwrap();
+            assert_eq!(conn.key_rotation_interval(&group.id).unwrap(), None);
+        })
+        .await
+    }
+}
@@ -8,24 +8,36 @@ use diesel::{
     serialize::{self, IsNull, Output, ToSql},
     sql_types::Integer,
 };
+use prost::Message;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ops::Sub;
 use xmtp_common::time::now_ns;
 use xmtp_content_types::{
-    attachment, group_updated, membership_change, reaction, read_receipt, remote_attachment, reply,
-    text, transaction_reference,
+    app_metadata::{app_metadata, validate_app_metadata, AppMetadata},
+    attachment, group_updated, join_request, leave_request, location, membership_change, poll,
+    reaction, read_receipt, remote_attachment, reply, text, transaction_reference,
 };
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
 
 use super::{
+    batched::{Batched, DEFAULT_BATCH_SIZE},
+    column_encryption::row_aad,
     db_connection::DbConnection,
     schema::{
+        filtered_messages::dsl as filtered_dsl,
         group_messages::{self, dsl},
         groups::dsl as groups_dsl,
+        message_reactions::dsl as reactions_dsl,
     },
     Sqlite,
 };
-use crate::{impl_fetch, impl_store, impl_store_or_ignore, StorageError};
+use crate::{Fetch, StorageError, Store, StoreOrIgnore};
+
+/// Table name the keys of the encrypted columns of messages are derived from, see
+/// [`super::column_encryption`]
+pub(super) const GROUP_MESSAGES_TABLE: &str = "group_messages";
 
 #[derive(
     Debug, Clone, Serialize, Deserialize, Insertable, Identifiable, Queryable, Eq, PartialEq,
@@ -60,6 +72,11 @@ pub struct StoredGroupMessage {
     pub authority_id: String,
     /// The ID of a referenced message
     pub reference_id: Option<Vec<u8>>,
+    /// Whether this message was sent by this inbox, from this or any other installation
+    pub sent_by_me: bool,
+    /// Whether the sender installation was a member with a verified association chain when the
+    /// message was processed. Messages from installations revoked since then stay verified.
+    pub sender_verified: bool,
 }
 
 pub struct StoredGroupMessageWithReactions {
@@ -120,6 +137,24 @@ pub enum ContentType {
     Attachment = 7,
     RemoteAttachment = 8,
     TransactionReference = 9,
+    Poll = 10,
+    PollVote = 11,
+    Location = 12,
+    LeaveRequest = 13,
+    JoinRequest = 14,
+}
+
+impl ContentType {
+    /// Content types shown to the user, or with a text fallback. The `conversation_list` view
+    /// shows the latest message of the same types.
+    pub const READABLE: [ContentType; 6] = [
+        Self::Text,
+        Self::Reaction,
+        Self::Reply,
+        Self::Attachment,
+        Self::RemoteAttachment,
+        Self::TransactionReference,
+    ];
 }
 
 impl std::fmt::Display for ContentType {
@@ -135,6 +170,11 @@ impl std::fmt::Display for ContentType {
             Self::RemoteAttachment => remote_attachment::RemoteAttachmentCodec::TYPE_ID,
             Self::Reply => reply::ReplyCodec::TYPE_ID,
             Self::TransactionReference => transaction_reference::TransactionReferenceCodec::TYPE_ID,
+            Self::Poll => poll::PollCodec::TYPE_ID,
+            Self::PollVote => poll::PollVoteCodec::TYPE_ID,
+            Self::Location => location::LocationCodec::TYPE_ID,
+            Self::LeaveRequest => leave_request::LeaveRequestCodec::TYPE_ID,
+            Self::JoinRequest => join_request::JoinRequestCodec::TYPE_ID,
         };
 
         write!(f, "{}", as_string)
@@ -153,6 +193,11 @@ impl From<String> for ContentType {
             attachment::AttachmentCodec::TYPE_ID => Self::Attachment,
             remote_attachment::RemoteAttachmentCodec::TYPE_ID => Self::RemoteAttachment,
             transaction_reference::TransactionReferenceCodec::TYPE_ID => Self::TransactionReference,
+            poll::PollCodec::TYPE_ID => Self::Poll,
+            poll::PollVoteCodec::TYPE_ID => Self::PollVote,
+            location::LocationCodec::TYPE_ID => Self::Location,
+            leave_request::LeaveRequestCodec::TYPE_ID => Self::LeaveRequest,
+            join_request::JoinRequestCodec::TYPE_ID => Self::JoinRequest,
             _ => Self::Unknown,
         }
     }
@@ -184,6 +229,11 @@ where
             7 => Ok(ContentType::Attachment),
             8 => Ok(ContentType::RemoteAttachment),
             9 => Ok(ContentType::TransactionReference),
+            10 => Ok(ContentType::Poll),
+            11 => Ok(ContentType::PollVote),
+            12 => Ok(ContentType::Location),
+            13 => Ok(ContentType::LeaveRequest),
+            14 => Ok(ContentType::JoinRequest),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -222,9 +272,73 @@ where
     }
 }
 
-impl_fetch!(StoredGroupMessage, group_messages, Vec<u8>);
-impl_store!(StoredGroupMessage, group_messages);
-impl_store_or_ignore!(StoredGroupMessage, group_messages);
+impl Fetch<StoredGroupMessage> for DbConnection {
+    type Key = Vec<u8>;
+    fn fetch(&self, key: &Self::Key) -> Result<Option<StoredGroupMessage>, StorageError> {
+        self.get_group_message(key)
+    }
+}
+
+impl Store<DbConnection> for StoredGroupMessage {
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        let message = into.seal_message(self)?;
+        into.raw_query(|conn| {
+            diesel::insert_into(group_messages::table)
+                .values(&*message)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl StoreOrIgnore<DbConnection> for StoredGroupMessage {
+    fn store_or_ignore(&self, into: &DbConnection) -> Result<(), StorageError> {
+        let message = into.seal_message(self)?;
+        into.raw_query(|conn| {
+            diesel::insert_or_ignore_into(group_messages::table)
+                .values(&*message)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl StoredGroupMessage {
+    /// Store a message received from the network.
+    ///
+    /// Messages sent by this inbox may already have a local row, written optimistically
+    /// before the message was published. Instead of being ignored, that row is marked as
+    /// published with the network timestamp.
+    pub fn store_or_reconcile(&self, into: &DbConnection) -> Result<(), StorageError> {
+        if !self.sent_by_me {
+            return self.store_or_ignore(into);
+        }
+        let message = into.seal_message(self)?;
+        into.raw_query(|conn| {
+            diesel::insert_into(group_messages::table)
+                .values(&*message)
+                .on_conflict(dsl::id)
+                .do_update()
+                .set((
+                    dsl::delivery_status.eq(DeliveryStatus::Published),
+                    dsl::sent_at_ns.eq(self.sent_at_ns),
+                    dsl::sent_by_me.eq(true),
+                ))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Metadata the sending app attached to the message. Metadata over the size limits is
+    /// dropped.
+    pub fn app_metadata(&self) -> AppMetadata {
+        EncodedContent::decode(self.decrypted_message_bytes.as_slice())
+            .ok()
+            .filter(|content| validate_app_metadata(content).is_ok())
+            .map(|content| app_metadata(&content))
+            .unwrap_or_default()
+    }
+}
 
 #[derive(Default, Clone)]
 pub struct MsgQueryArgs {
@@ -235,14 +349,96 @@ pub struct MsgQueryArgs {
     pub limit: Option<i64>,
     pub direction: Option<SortDirection>,
     pub content_types: Option<Vec<ContentType>>,
+    /// Only messages that were, or were not, filtered by a mute rule. `None` includes both.
+    pub filtered: Option<bool>,
 }
 
 impl DbConnection {
+    /// The message as it is written, with its sensitive columns encrypted if column
+    /// encryption is enabled
+    fn seal_message<'a>(
+        &self,
+        message: &'a StoredGroupMessage,
+    ) -> Result<Cow<'a, StoredGroupMessage>, StorageError> {
+        let Some(cipher) = self.column_cipher() else {
+            return Ok(Cow::Borrowed(message));
+        };
+        let mut sealed = message.clone();
+        sealed.decrypted_message_bytes = cipher.seal(
+            GROUP_MESSAGES_TABLE,
+            &row_aad(&[&message.group_id, &message.id]),
+            &message.decrypted_message_bytes,
+        )?;
+        Ok(Cow::Owned(sealed))
+    }
+
+    /// Decrypt the sensitive columns of messages read from the database
+    pub(crate) fn open_messages(
+        &self,
+        mut messages: Vec<StoredGroupMessage>,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        if let Some(cipher) = self.column_cipher() {
+            for message in &mut messages {
+                let bytes = std::mem::take(&mut message.decrypted_message_bytes);
+                message.decrypted_message_bytes = cipher.open(
+                    GROUP_MESSAGES_TABLE,
+                    &row_aad(&[&message.group_id, &message.id]),
+                    bytes,
+                )?;
+            }
+        }
+        Ok(messages)
+    }
+
+    pub(crate) fn open_message(
+        &self,
+        message: Option<StoredGroupMessage>,
+    ) -> Result<Option<StoredGroupMessage>, StorageError> {
+        Ok(self
+            .open_messages(message.into_iter().collect())?
+            .into_iter()
+            .next())
+    }
+
     /// Query for group messages
     pub fn get_group_messages(
         &self,
         group_id: &[u8],
         args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        self.query_group_messages(group_id, args, &[], None)
+    }
+
+    /// Iterate over the group messages matching `args`, loading `batch_size` messages at a time
+    /// (by default [`DEFAULT_BATCH_SIZE`]). The limit of `args` applies to the whole iteration.
+    pub fn stream_group_messages<'a>(
+        &'a self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+        batch_size: Option<i64>,
+    ) -> impl Iterator<Item = Result<StoredGroupMessage, StorageError>> + 'a {
+        let group_id = group_id.to_vec();
+        let total = args.limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+        let mut args = args.clone();
+        Batched::new(
+            batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+            |message: &StoredGroupMessage| (message.sent_at_ns, message.id.clone()),
+            move |cursor, limit| {
+                args.limit = Some(limit);
+                self.query_group_messages(&group_id, &args, &[], cursor)
+            },
+        )
+        .take(total)
+    }
+
+    /// Query for group messages, leaving out `excluded_content_types` before the limit applies.
+    /// With a `cursor`, only the messages sorting after that sent time and id are returned.
+    fn query_group_messages(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+        excluded_content_types: &[ContentType],
+        cursor: Option<&(i64, Vec<u8>)>,
     ) -> Result<Vec<StoredGroupMessage>, StorageError> {
         // Get all messages that have a group with an id equal the provided id,
         // or a dm_id equal to the dm_id that belongs to the loaded group with the provided id.
@@ -283,57 +479,89 @@ impl DbConnection {
             query = query.filter(dsl::content_type.eq_any(content_types));
         }
 
-        query = match args.direction.as_ref().unwrap_or(&SortDirection::Ascending) {
-            SortDirection::Ascending => query.order(dsl::sent_at_ns.asc()),
-            SortDirection::Descending => query.order(dsl::sent_at_ns.desc()),
+        if !excluded_content_types.is_empty() {
+            query = query.filter(dsl::content_type.ne_all(excluded_content_types));
+        }
+
+        if let Some(filtered) = args.filtered {
+            let filtered_ids = filtered_dsl::filtered_messages.select(filtered_dsl::message_id);
+            query = if filtered {
+                query.filter(dsl::id.eq_any(filtered_ids))
+            } else {
+                query.filter(dsl::id.ne_all(filtered_ids))
+            };
+        }
+
+        let direction = args.direction.as_ref().unwrap_or(&SortDirection::Ascending);
+        if let Some((sent_at_ns, id)) = cursor {
+            let (sent_at_ns, id) = (*sent_at_ns, id.as_slice());
+            query = match direction {
+                SortDirection::Ascending => query.filter(
+                    dsl::sent_at_ns
+                        .gt(sent_at_ns)
+                        .or(dsl::sent_at_ns.eq(sent_at_ns).and(dsl::id.gt(id))),
+                ),
+                SortDirection::Descending => query.filter(
+                    dsl::sent_at_ns
+                        .lt(sent_at_ns)
+                        .or(dsl::sent_at_ns.eq(sent_at_ns).and(dsl::id.lt(id))),
+                ),
+            };
+        }
+
+        query = match direction {
+            SortDirection::Ascending => query.order((dsl::sent_at_ns.asc(), dsl::id.asc())),
+            SortDirection::Descending => query.order((dsl::sent_at_ns.desc(), dsl::id.desc())),
         };
 
         if let Some(limit) = args.limit {
             query = query.limit(limit);
         }
 
-        Ok(self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?)
+        let messages = self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?;
+        self.open_messages(messages)
     }
 
-    /// Query for group messages with their reactions
-    #[allow(clippy::too_many_arguments)]
+    /// Query for group messages with their reactions.
+    ///
+    /// Reactions are left out of the messages and attached to the messages they react to
+    /// instead, keeping only the current reactions recorded in the reactions table. The reactions
+    /// of all the messages are loaded in one query.
     pub fn get_group_messages_with_reactions(
         &self,
         group_id: &[u8],
         args: &MsgQueryArgs,
     ) -> Result<Vec<StoredGroupMessageWithReactions>, StorageError> {
-        // First get all the main messages
-        let mut modified_args = args.clone();
-        // filter out reactions from the main query so we don't get them twice
-        let content_types = match modified_args.content_types.clone() {
-            Some(content_types) => {
-                let mut content_types = content_types.clone();
-                content_types.retain(|content_type| *content_type != ContentType::Reaction);
-                Some(content_types)
-            }
-            None => Some(vec![
-                ContentType::Text,
-                ContentType::GroupMembershipChange,
-                ContentType::GroupUpdated,
-                ContentType::ReadReceipt,
-                ContentType::Reply,
-                ContentType::Attachment,
-                ContentType::RemoteAttachment,
-                ContentType::TransactionReference,
-                ContentType::Unknown,
-            ]),
-        };
-
-        modified_args.content_types = content_types;
-        let messages = self.get_group_messages(group_id, &modified_args)?;
-
-        // Then get all reactions for these messages in a single query
+        let messages = self.query_group_messages(group_id, args, &[ContentType::Reaction], None)?;
         let message_ids: Vec<&[u8]> = messages.iter().map(|m| m.id.as_slice()).collect();
 
+        // Reactions that were replaced or removed are not in the reactions table
+        let current_reactions = reactions_dsl::message_reactions
+            .filter(reactions_dsl::reference_id.eq_any(message_ids.clone()))
+            .filter(reactions_dsl::removed.eq(false))
+            .select(reactions_dsl::message_id);
+        // Only reactions sent to this conversation count, including the stitched DM groups
         let mut reactions_query = dsl::group_messages
-            .filter(dsl::group_id.eq(group_id))
-            .filter(dsl::reference_id.is_not_null())
+            .filter(
+                dsl::group_id.eq_any(
+                    groups_dsl::groups
+                        .filter(
+                            groups_dsl::id.eq(group_id).or(groups_dsl::dm_id.eq_any(
+                                groups_dsl::groups
+                                    .select(groups_dsl::dm_id)
+                                    .filter(groups_dsl::id.eq(group_id))
+                                    .into_boxed(),
+                            )),
+                        )
+                        .select(groups_dsl::id),
+                ),
+            )
             .filter(dsl::reference_id.eq_any(message_ids))
+            .filter(
+                dsl::content_type
+                    .ne(ContentType::Reaction)
+                    .or(dsl::id.eq_any(current_reactions)),
+            )
             .into_boxed();
 
         // Apply the same sorting as the main messages
@@ -342,12 +570,11 @@ impl DbConnection {
             SortDirection::Descending => reactions_query.order(dsl::sent_at_ns.desc()),
         };
 
-        let reactions: Vec<StoredGroupMessage> =
-            self.raw_query(|conn| reactions_query.load(conn))?;
+        let reactions = self.raw_query(|conn| reactions_query.load(conn))?;
+        let reactions = self.open_messages(reactions)?;
 
         // Group reactions by parent message id
         let mut reactions_by_reference: HashMap<Vec<u8>, Vec<StoredGroupMessage>> = HashMap::new();
-
         for reaction in reactions {
             if let Some(reference_id) = &reaction.reference_id {
                 reactions_by_reference
@@ -357,21 +584,15 @@ impl DbConnection {
             }
         }
 
-        // Combine messages with their reactions
-        let messages_with_reactions: Vec<StoredGroupMessageWithReactions> = messages
+        Ok(messages
             .into_iter()
-            .map(|message| {
-                let message_clone = message.clone();
-                StoredGroupMessageWithReactions {
-                    message,
-                    reactions: reactions_by_reference
-                        .remove(&message_clone.id)
-                        .unwrap_or_default(),
-                }
+            .map(|message| StoredGroupMessageWithReactions {
+                reactions: reactions_by_reference
+                    .remove(&message.id)
+                    .unwrap_or_default(),
+                message,
             })
-            .collect();
-
-        Ok(messages_with_reactions)
+            .collect())
     }
 
     /// Get a particular group message
@@ -379,12 +600,13 @@ impl DbConnection {
         &self,
         id: MessageId,
     ) -> Result<Option<StoredGroupMessage>, StorageError> {
-        Ok(self.raw_query(|conn| {
+        let message = self.raw_query(|conn| {
             dsl::group_messages
                 .filter(dsl::id.eq(id.as_ref()))
                 .first(conn)
                 .optional()
-        })?)
+        })?;
+        self.open_message(message)
     }
 
     pub fn get_group_message_by_timestamp<GroupId: AsRef<[u8]>>(
@@ -392,13 +614,79 @@ impl DbConnection {
         group_id: GroupId,
         timestamp: i64,
     ) -> Result<Option<StoredGroupMessage>, StorageError> {
-        Ok(self.raw_query(|conn| {
+        let message = self.raw_query(|conn| {
             dsl::group_messages
                 .filter(dsl::group_id.eq(group_id.as_ref()))
                 .filter(dsl::sent_at_ns.eq(timestamp))
                 .first(conn)
                 .optional()
-        })?)
+        })?;
+        self.open_message(message)
+    }
+
+    /// Messages sent in a group between `start_ns` (inclusive) and `end_ns` (exclusive),
+    /// oldest first, optionally limited to the given sender inbox ids.
+    ///
+    /// Messages are loaded lazily, one page at a time, so that long windows can be reviewed
+    /// without loading the whole history into memory.
+    pub fn messages_in_window(
+        &self,
+        group_id: &[u8],
+        start_ns: i64,
+        end_ns: i64,
+        senders: &[String],
+    ) -> MessagesInWindow<'_> {
+        let group_id = group_id.to_vec();
+        let senders = senders.to_vec();
+        let next_page: WindowPage<'_> =
+            Box::new(move |after: Option<&(i64, Vec<u8>)>, page_size: i64| {
+                self.messages_in_window_page(
+                    &group_id, start_ns, end_ns, &senders, after, page_size,
+                )
+            });
+        MessagesInWindow(Batched::new(
+            MESSAGES_IN_WINDOW_PAGE_SIZE,
+            |message: &StoredGroupMessage| (message.sent_at_ns, message.id.clone()),
+            next_page,
+        ))
+    }
+
+    /// The next `page_size` messages of [`Self::messages_in_window`] after the sent time and id
+    /// `after` of the last message loaded
+    fn messages_in_window_page(
+        &self,
+        group_id: &[u8],
+        start_ns: i64,
+        end_ns: i64,
+        senders: &[String],
+        after: Option<&(i64, Vec<u8>)>,
+        page_size: i64,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let mut query = dsl::group_messages
+            .filter(dsl::group_id.eq(group_id))
+            .filter(dsl::sent_at_ns.lt(end_ns))
+            .into_boxed();
+
+        query = match after {
+            None => query.filter(dsl::sent_at_ns.ge(start_ns)),
+            Some((sent_at_ns, id)) => query.filter(
+                dsl::sent_at_ns
+                    .gt(*sent_at_ns)
+                    .or(dsl::sent_at_ns.eq(*sent_at_ns).and(dsl::id.gt(id.clone()))),
+            ),
+        };
+
+        if !senders.is_empty() {
+            query = query.filter(dsl::sender_inbox_id.eq_any(senders));
+        }
+
+        let page = self.raw_query(|conn| {
+            query
+                .order((dsl::sent_at_ns.asc(), dsl::id.asc()))
+                .limit(page_size)
+                .load::<StoredGroupMessage>(conn)
+        })?;
+        self.open_messages(page)
     }
 
     pub fn set_delivery_status_to_published<MessageId: AsRef<[u8]>>(
@@ -476,6 +764,33 @@ impl DbConnection {
     }
 }
 
+/// Number of messages loaded at a time by [`MessagesInWindow`]
+const MESSAGES_IN_WINDOW_PAGE_SIZE: i64 = 100;
+
+/// Loads a page of [`MessagesInWindow`] after the sent time and id of the last message
+type WindowPage<'a> = Box<
+    dyn FnMut(Option<&(i64, Vec<u8>)>, i64) -> Result<Vec<StoredGroupMessage>, StorageError> + 'a,
+>;
+
+/// Iterator over the messages in a time window, see [`DbConnection::messages_in_window`].
+/// Pages continue after the sent time and id of the last message, see [`Batched`].
+pub struct MessagesInWindow<'a>(Batched<StoredGroupMessage, (i64, Vec<u8>), WindowPage<'a>>);
+
+impl MessagesInWindow<'_> {
+    /// Load `page_size` messages at a time instead of the default
+    pub fn page_size(self, page_size: i64) -> Self {
+        Self(self.0.batch_size(page_size))
+    }
+}
+
+impl Iterator for MessagesInWindow<'_> {
+    type Item = Result<StoredGroupMessage, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     #[cfg(target_arch = "wasm32")]
@@ -509,9 +824,59 @@ pub(crate) mod tests {
             version_minor: 0,
             authority_id: "unknown".to_string(),
             reference_id: None,
+            sent_by_me: false,
+            sender_verified: true,
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_reconciles_sent_messages_with_local_copy() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let local = StoredGroupMessage {
+                delivery_status: DeliveryStatus::Unpublished,
+                sent_by_me: true,
+                ..generate_message(None, Some(&group.id), Some(1_000), None)
+            };
+            local.store(conn).unwrap();
+
+            let mirrored = StoredGroupMessage {
+                delivery_status: DeliveryStatus::Published,
+                sent_at_ns: 2_000,
+                ..local.clone()
+            };
+            mirrored.store_or_reconcile(conn).unwrap();
+
+            let stored = conn.get_group_message(&local.id).unwrap().unwrap();
+            assert_eq!(stored.delivery_status, DeliveryStatus::Published);
+            assert_eq!(stored.sent_at_ns, 2_000);
+            assert!(stored.sent_by_me);
+
+            // Messages from other inboxes are never overwritten
+            let other = StoredGroupMessage {
+                sent_by_me: false,
+                ..generate_message(None, Some(&group.id), Some(3_000), None)
+            };
+            other.store(conn).unwrap();
+            StoredGroupMessage {
+                sent_at_ns: 4_000,
+                ..other.clone()
+            }
+            .store_or_reconcile(conn)
+            .unwrap();
+            assert_eq!(
+                conn.get_group_message(&other.id)
+                    .unwrap()
+                    .unwrap()
+                    .sent_at_ns,
+                3_000
+            );
+        })
+        .await
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn it_does_not_error_on_empty_messages() {
         with_connection(|conn| {
@@ -637,6 +1002,44 @@ pub(crate) mod tests {
         .await
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_pages_through_messages_in_window() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let mut messages: Vec<_> = (0..10)
+                .map(|i| generate_message(None, Some(&group.id), Some(1_000 + i * 10), None))
+                .collect();
+            // Two messages sent at the same time must both be returned across a page boundary
+            messages[3].sent_at_ns = messages[2].sent_at_ns;
+            messages[1].sender_inbox_id = "bola".to_string();
+            messages[5].sender_inbox_id = "bola".to_string();
+            assert_ok!(messages.store(conn));
+
+            let window: Vec<_> = conn
+                .messages_in_window(&group.id, 1_010, 1_080, &[])
+                .page_size(2)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(window.len(), 7);
+            assert!(window
+                .windows(2)
+                .all(|w| w[0].sent_at_ns <= w[1].sent_at_ns));
+            assert!(window
+                .iter()
+                .all(|m| (1_010..1_080).contains(&m.sent_at_ns)));
+
+            let from_bola: Vec<_> = conn
+                .messages_in_window(&group.id, 0, i64::MAX, &["bola".to_string()])
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(from_bola.len(), 2);
+            assert!(from_bola.iter().all(|m| m.sender_inbox_id == "bola"));
+        })
+        .await
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn it_deletes_middle_message_by_expiration_time() {
         with_connection(|conn| {
@@ -783,6 +1186,42 @@ pub(crate) mod tests {
         .await
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_streams_messages_in_batches() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            // messages sent at the same time are not skipped between batches
+            let messages: Vec<_> = [1, 2, 2, 2, 3, 4, 5]
+                .into_iter()
+                .map(|sent_at_ns| generate_message(None, Some(&group.id), Some(sent_at_ns), None))
+                .collect();
+            assert_ok!(messages.store(conn));
+
+            let expected = conn
+                .get_group_messages(&group.id, &MsgQueryArgs::default())
+                .unwrap();
+            let streamed = conn
+                .stream_group_messages(&group.id, &MsgQueryArgs::default(), Some(2))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(streamed, expected);
+
+            let args = MsgQueryArgs {
+                direction: Some(SortDirection::Descending),
+                limit: Some(5),
+                ..Default::default()
+            };
+            let streamed = conn
+                .stream_group_messages(&group.id, &args, Some(2))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(streamed, conn.get_group_messages(&group.id, &args).unwrap());
+        })
+        .await
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn it_gets_messages_by_content_type() {
         with_connection(|conn| {
@@ -853,4 +1292,77 @@ pub(crate) mod tests {
         })
         .await
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_gets_messages_with_their_reactions() {
+        use crate::storage::message_reaction::StoredReaction;
+
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let text =
+                generate_message(None, Some(&group.id), Some(1_000), Some(ContentType::Text));
+            let poll =
+                generate_message(None, Some(&group.id), Some(2_000), Some(ContentType::Poll));
+            let reaction = |sent_at_ns: i64, removed: bool| {
+                let mut message = generate_message(
+                    None,
+                    Some(&group.id),
+                    Some(sent_at_ns),
+                    Some(ContentType::Reaction),
+                );
+                message.reference_id = Some(text.id.clone());
+                message.sender_inbox_id = format!("reactor_{sent_at_ns}");
+                conn.record_reaction(&StoredReaction {
+                    reference_id: text.id.clone(),
+                    reactor_inbox_id: message.sender_inbox_id.clone(),
+                    content: "👍".to_string(),
+                    group_id: group.id.clone(),
+                    message_id: message.id.clone(),
+                    reacted_at_ns: sent_at_ns,
+                    removed,
+                })
+                .unwrap();
+                message
+            };
+            let current = reaction(3_000, false);
+            let removed = reaction(4_000, true);
+            assert_ok!(vec![text.clone(), poll.clone(), current.clone(), removed].store(conn));
+            // a message of another conversation referencing the same id is not attached
+            let other_group = generate_group(None);
+            other_group.store(conn).unwrap();
+            let mut elsewhere = generate_message(
+                None,
+                Some(&other_group.id),
+                Some(5_000),
+                Some(ContentType::Text),
+            );
+            elsewhere.reference_id = Some(text.id.clone());
+            elsewhere.store(conn).unwrap();
+
+            let messages = conn
+                .get_group_messages_with_reactions(&group.id, &MsgQueryArgs::default())
+                .unwrap();
+            assert_eq!(messages.len(), 2);
+            assert_eq!(messages[0].message.id, text.id);
+            assert_eq!(messages[0].reactions, vec![current]);
+            assert_eq!(messages[1].message.id, poll.id);
+            assert!(messages[1].reactions.is_empty());
+
+            // the limit only counts the messages reacted to
+            let limited = conn
+                .get_group_messages_with_reactions(
+                    &group.id,
+                    &MsgQueryArgs {
+                        direction: Some(SortDirection::Descending),
+                        limit: Some(1),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            assert_eq!(limited.len(), 1);
+            assert_eq!(limited[0].message.id, poll.id);
+        })
+        .await
+    }
 }
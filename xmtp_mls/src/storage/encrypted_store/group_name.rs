@@ -0,0 +1,180 @@
+//! The names of groups, copied from their mutable metadata whenever it changes, so that groups
+//! are sorted by name in SQL without loading their MLS state, see
+//! [`GroupSortKey::Name`](super::group::GroupSortKey::Name).
+//!
+//! Names are sealed when column encryption is enabled, see [`super::column_encryption`]. Sealed
+//! names can't be compared in SQL, so the groups are then sorted once their names are opened.
+use std::{cmp::Ordering, collections::HashMap};
+
+use diesel::prelude::*;
+
+use super::{
+    collation::{unicode_cmp, UNICODE_COLLATION},
+    column_encryption::row_aad,
+    db_connection::DbConnection,
+    group_message::SortDirection,
+    schema::{
+        group_names::{self, dsl},
+        groups::dsl as groups_dsl,
+    },
+};
+use crate::storage::StorageError;
+
+/// Table name the key of the encrypted names is derived from, see [`super::column_encryption`]
+pub(super) const GROUP_NAMES_TABLE: &str = "group_names";
+
+#[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_names)]
+struct StoredGroupName {
+    group_id: Vec<u8>,
+    name: String,
+}
+
+/// `ORDER BY` terms sorting the rows of `table`, a table or view with the `id` of groups, by
+/// group name ignoring case and accents. Groups without a name come last.
+pub(super) fn order_by_name_sql(table: &str, direction: &str) -> String {
+    let name = format!("(SELECT name FROM group_names WHERE group_names.group_id = {table}.id)");
+    format!("{name} IS NULL, {name} COLLATE {UNICODE_COLLATION} {direction}")
+}
+
+impl DbConnection {
+    /// Record the name of a group. An empty name removes it.
+    pub fn set_group_name(&self, group_id: &[u8], name: &str) -> Result<(), StorageError> {
+        if name.is_empty() {
+            self.raw_query(|conn| diesel::delete(dsl::group_names.find(group_id)).execute(conn))?;
+            return Ok(());
+        }
+        let name = match self.column_cipher() {
+            Some(cipher) => cipher.seal_text(GROUP_NAMES_TABLE, &row_aad(&[group_id]), name)?,
+            None => name.to_string(),
+        };
+        let row = StoredGroupName {
+            group_id: group_id.to_vec(),
+            name,
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::group_names)
+                .values(&row)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The recorded names of every group, by group id
+    fn group_names(&self) -> Result<HashMap<Vec<u8>, String>, StorageError> {
+        let rows: Vec<StoredGroupName> = self.raw_query(|conn| dsl::group_names.load(conn))?;
+        rows.into_iter()
+            .map(|StoredGroupName { group_id, name }| {
+                let name = match self.column_cipher() {
+                    Some(cipher) => {
+                        cipher.open_text(GROUP_NAMES_TABLE, &row_aad(&[&group_id]), name)?
+                    }
+                    None => name,
+                };
+                Ok((group_id, name))
+            })
+            .collect()
+    }
+
+    /// The groups without a recorded name, including the groups created before names were
+    /// recorded
+    pub fn groups_without_name(&self) -> Result<Vec<Vec<u8>>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            groups_dsl::groups
+                .select(groups_dsl::id)
+                .filter(groups_dsl::id.ne_all(dsl::group_names.select(dsl::group_id)))
+                .load(conn)
+        })?)
+    }
+
+    /// Whether names are sealed and can't be sorted in SQL
+    pub(super) fn group_names_sealed(&self) -> bool {
+        self.column_cipher().is_some()
+    }
+
+    /// Sort `items` by the opened names of their groups, in the order of [`order_by_name_sql`]
+    /// followed by the creation time, then keep the first `limit`. `key` returns the group id
+    /// and creation time of an item.
+    pub(super) fn sort_by_opened_names<T>(
+        &self,
+        items: Vec<T>,
+        key: impl Fn(&T) -> (&[u8], i64),
+        direction: SortDirection,
+        limit: Option<i64>,
+    ) -> Result<Vec<T>, StorageError> {
+        let names = self.group_names()?;
+        let mut items: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let (group_id, created_at_ns) = key(&item);
+                (names.get(group_id).cloned(), created_at_ns, item)
+            })
+            .collect();
+        items.sort_by(|(a_name, a_created, _), (b_name, b_created, _)| {
+            let ordering = match (a_name, b_name) {
+                (Some(a), Some(b)) => unicode_cmp(a, b),
+                _ => Ordering::Equal,
+            }
+            .then(a_created.cmp(b_created));
+            let ordering = match direction {
+                SortDirection::Ascending => ordering,
+                SortDirection::Descending => ordering.reverse(),
+            };
+            // Groups without a name come last either way
+            a_name.is_none().cmp(&b_name.is_none()).then(ordering)
+        });
+        let items = items.into_iter().map(|(_, _, item)| item);
+        Ok(match limit {
+            Some(limit) => items.take(limit.max(0) as usize).collect(),
+            None => items.collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::{tests::generate_group_with_created_at, GroupQueryArgs, GroupSortKey},
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn sorts_groups_by_name_in_sql() {
+        with_connection(|conn| {
+            let mut ids = vec![];
+            for (created_at_ns, name) in [(1, "zoo"), (2, ""), (3, "Élan"), (4, "apple")] {
+                let group = generate_group_with_created_at(None, created_at_ns);
+                group.store(conn).unwrap();
+                conn.set_group_name(&group.id, name).unwrap();
+                ids.push(group.id);
+            }
+            assert_eq!(conn.groups_without_name().unwrap(), vec![ids[1].clone()]);
+
+            let sorted = |args: GroupQueryArgs| {
+                conn.find_groups(args.sort_by(GroupSortKey::Name))
+                    .unwrap()
+                    .into_iter()
+                    .map(|group| group.created_at_ns)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(sorted(GroupQueryArgs::default()), vec![4, 3, 1, 2]);
+            assert_eq!(
+                sorted(
+                    GroupQueryArgs::default()
+                        .direction(SortDirection::Descending)
+                        .limit(2)
+                ),
+                vec![1, 3]
+            );
+        })
+        .await
+    }
+}
@@ -0,0 +1,204 @@
+//! Sync requests of other installations that this installation answered or refused, so that
+//! the user can review which installations received an archive of their history.
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+use xmtp_common::time::now_ns;
+use xmtp_proto::xmtp::mls::message_contents::DeviceSyncKind;
+
+use super::{
+    db_connection::DbConnection,
+    schema::history_sync_log::{self, dsl},
+    Sqlite,
+};
+use crate::storage::StorageError;
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+pub enum HistorySyncOutcome {
+    /// An archive was sent to the requester
+    Provided = 1,
+    /// The requester is not an installation of this inbox
+    UnknownInstallation = 2,
+    /// The requester already received too many archives recently
+    RateLimited = 3,
+}
+
+impl ToSql<Integer, Sqlite> for HistorySyncOutcome
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for HistorySyncOutcome
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(HistorySyncOutcome::Provided),
+            2 => Ok(HistorySyncOutcome::UnknownInstallation),
+            3 => Ok(HistorySyncOutcome::RateLimited),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = history_sync_log)]
+#[diesel(primary_key(id))]
+pub struct HistorySyncLogEntry {
+    pub id: i32,
+    pub request_id: String,
+    pub requester_inbox_id: String,
+    pub requester_installation_id: Vec<u8>,
+    /// The [`DeviceSyncKind`] requested, see [`Self::kind`]
+    pub kind: i32,
+    pub outcome: HistorySyncOutcome,
+    pub created_at_ns: i64,
+}
+
+impl HistorySyncLogEntry {
+    pub fn kind(&self) -> DeviceSyncKind {
+        DeviceSyncKind::try_from(self.kind).unwrap_or(DeviceSyncKind::Unspecified)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = history_sync_log)]
+struct NewHistorySyncLogEntry<'a> {
+    request_id: &'a str,
+    requester_inbox_id: &'a str,
+    requester_installation_id: &'a [u8],
+    kind: i32,
+    outcome: HistorySyncOutcome,
+    created_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Record how a sync request of `requester_installation_id` was handled
+    pub fn log_history_sync(
+        &self,
+        request_id: &str,
+        requester_inbox_id: &str,
+        requester_installation_id: &[u8],
+        kind: DeviceSyncKind,
+        outcome: HistorySyncOutcome,
+    ) -> Result<HistorySyncLogEntry, StorageError> {
+        let entry = NewHistorySyncLogEntry {
+            request_id,
+            requester_inbox_id,
+            requester_installation_id,
+            kind: kind as i32,
+            outcome,
+            created_at_ns: now_ns(),
+        };
+        Ok(self.raw_query(|conn| {
+            diesel::insert_into(dsl::history_sync_log)
+                .values(&entry)
+                .get_result(conn)
+        })?)
+    }
+
+    /// The logged sync requests, newest first
+    pub fn history_sync_log(
+        &self,
+        limit: Option<i64>,
+    ) -> Result<Vec<HistorySyncLogEntry>, StorageError> {
+        let mut query = dsl::history_sync_log
+            .order((dsl::created_at_ns.desc(), dsl::id.desc()))
+            .into_boxed();
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Number of archives of `kind` provided to `installation_id` since `since_ns`
+    pub fn history_syncs_provided_since(
+        &self,
+        installation_id: &[u8],
+        kind: DeviceSyncKind,
+        since_ns: i64,
+    ) -> Result<i64, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::history_sync_log
+                .filter(dsl::requester_installation_id.eq(installation_id))
+                .filter(dsl::kind.eq(kind as i32))
+                .filter(dsl::outcome.eq(HistorySyncOutcome::Provided))
+                .filter(dsl::created_at_ns.ge(since_ns))
+                .count()
+                .get_result(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_logs_history_syncs() {
+        with_connection(|conn| {
+            let start = now_ns();
+            let installation_id = vec![1; 32];
+            let log = |outcome| {
+                conn.log_history_sync(
+                    "request",
+                    "inbox",
+                    &installation_id,
+                    DeviceSyncKind::MessageHistory,
+                    outcome,
+                )
+                .unwrap()
+            };
+            log(HistorySyncOutcome::Provided);
+            log(HistorySyncOutcome::RateLimited);
+            conn.log_history_sync(
+                "other",
+                "inbox",
+                &[2; 32],
+                DeviceSyncKind::Consent,
+                HistorySyncOutcome::Provided,
+            )
+            .unwrap();
+
+            assert_eq!(
+                conn.history_syncs_provided_since(
+                    &installation_id,
+                    DeviceSyncKind::MessageHistory,
+                    start
+                )
+                .unwrap(),
+                1
+            );
+            assert_eq!(
+                conn.history_syncs_provided_since(&installation_id, DeviceSyncKind::Consent, start)
+                    .unwrap(),
+                0
+            );
+
+            let entries = conn.history_sync_log(Some(2)).unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].request_id, "other");
+            assert_eq!(entries[0].kind(), DeviceSyncKind::Consent);
+            assert_eq!(entries[1].outcome, HistorySyncOutcome::RateLimited);
+        })
+        .await
+    }
+}
@@ -1,13 +1,18 @@
-use std::sync::atomic::AtomicBool;
+use std::sync::{atomic::AtomicBool, Arc};
 
-use crate::storage::{encrypted_store::schema::identity, StorageError};
+use crate::storage::{
+    encrypted_store::schema::identity,
+    key_store_backend::{installation_credential, KeyStoreBackend},
+    StorageError,
+};
 use diesel::prelude::*;
 use xmtp_id::InboxId;
 
+use super::db_connection::DbConnection;
 use crate::{
     identity::Identity,
-    impl_fetch, impl_store,
     storage::serialization::{db_deserialize, db_serialize},
+    Fetch, Store,
 };
 
 /// Identity of this installation
@@ -16,13 +21,39 @@ use crate::{
 #[diesel(table_name = identity)]
 pub struct StoredIdentity {
     pub inbox_id: InboxId,
+    /// The serialized installation key, or its handle if it is held by a [`KeyStoreBackend`]
     pub installation_keys: Vec<u8>,
     pub credential_bytes: Vec<u8>,
     rowid: Option<i32>,
 }
 
-impl_fetch!(StoredIdentity, identity);
-impl_store!(StoredIdentity, identity);
+impl Fetch<StoredIdentity> for DbConnection {
+    type Key = ();
+    fn fetch(&self, _key: &Self::Key) -> Result<Option<StoredIdentity>, StorageError> {
+        let cache = self.readable_cache();
+        if let Some(identity) = cache.and_then(|c| c.identity()) {
+            return Ok(Some(identity));
+        }
+        let generation = cache.map_or(0, |c| c.generation());
+        let stored: Option<StoredIdentity> =
+            self.raw_query(|conn| identity::table.first(conn).optional())?;
+        if let (Some(cache), Some(stored)) = (cache, &stored) {
+            cache.put_identity(generation, stored);
+        }
+        Ok(stored)
+    }
+}
+
+impl Store<DbConnection> for StoredIdentity {
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::insert_into(identity::table)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
 
 impl StoredIdentity {
     pub fn new(inbox_id: InboxId, installation_keys: Vec<u8>, credential_bytes: Vec<u8>) -> Self {
@@ -39,23 +70,33 @@ impl TryFrom<&Identity> for StoredIdentity {
     type Error = StorageError;
 
     fn try_from(identity: &Identity) -> Result<Self, Self::Error> {
+        let installation_keys = match identity.installation_keys.external_handle() {
+            Some(handle) => handle.to_vec(),
+            None => db_serialize(&identity.installation_keys)?,
+        };
         Ok(StoredIdentity {
             inbox_id: identity.inbox_id.clone(),
-            installation_keys: db_serialize(&identity.installation_keys)?,
+            installation_keys,
             credential_bytes: db_serialize(&identity.credential())?,
             rowid: None,
         })
     }
 }
 
-impl TryFrom<StoredIdentity> for Identity {
-    type Error = StorageError;
-
-    fn try_from(identity: StoredIdentity) -> Result<Self, Self::Error> {
+impl StoredIdentity {
+    /// The identity, signing through `backend` if the installation key is held by one
+    pub(crate) fn into_identity(
+        self,
+        backend: Option<&Arc<dyn KeyStoreBackend>>,
+    ) -> Result<Identity, StorageError> {
+        let installation_keys = match backend {
+            Some(backend) => installation_credential(backend, self.installation_keys)?,
+            None => db_deserialize(&self.installation_keys)?,
+        };
         Ok(Identity {
-            inbox_id: identity.inbox_id.clone(),
-            installation_keys: db_deserialize(&identity.installation_keys)?,
-            credential: db_deserialize(&identity.credential_bytes)?,
+            inbox_id: self.inbox_id,
+            installation_keys,
+            credential: db_deserialize(&self.credential_bytes)?,
             signature_request: None,
             is_ready: AtomicBool::new(true),
         })
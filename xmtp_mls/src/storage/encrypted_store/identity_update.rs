@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use crate::{impl_store, storage::StorageError};
 
 use super::{
+    batched::{Batched, DEFAULT_BATCH_SIZE},
     db_connection::DbConnection,
     schema::identity_updates::{self, dsl},
 };
@@ -75,6 +76,32 @@ impl DbConnection {
         Ok(self.raw_query(|conn| query.load::<StoredIdentityUpdate>(conn))?)
     }
 
+    /// Iterate over the identity updates of `inbox_id` after `from_sequence_id`, loading
+    /// `batch_size` updates at a time (by default [`DEFAULT_BATCH_SIZE`])
+    pub fn stream_identity_updates<'a>(
+        &'a self,
+        inbox_id: &str,
+        from_sequence_id: Option<i64>,
+        batch_size: Option<i64>,
+    ) -> impl Iterator<Item = Result<StoredIdentityUpdate, StorageError>> + 'a {
+        let inbox_id = inbox_id.to_string();
+        Batched::new(
+            batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+            |update: &StoredIdentityUpdate| update.sequence_id,
+            move |cursor, limit| {
+                let mut query = dsl::identity_updates
+                    .order(dsl::sequence_id.asc())
+                    .filter(dsl::inbox_id.eq(&inbox_id))
+                    .limit(limit)
+                    .into_boxed();
+                if let Some(sequence_id) = cursor.or(from_sequence_id.as_ref()) {
+                    query = query.filter(dsl::sequence_id.gt(*sequence_id));
+                }
+                Ok(self.raw_query(|conn| query.load::<StoredIdentityUpdate>(conn))?)
+            },
+        )
+    }
+
     /// Batch insert identity updates, ignoring duplicates.
     #[tracing::instrument(level = "trace", skip(updates))]
     pub fn insert_or_ignore_identity_updates(
@@ -204,6 +231,12 @@ pub(crate) mod tests {
 
             assert_eq!(only_update_2.len(), 1);
             assert_eq!(only_update_2[0].sequence_id, 2);
+
+            let streamed = conn
+                .stream_identity_updates(inbox_id, Some(1), Some(1))
+                .map(|update| update.unwrap().sequence_id)
+                .collect::<Vec<_>>();
+            assert_eq!(streamed, vec![2, 3]);
         })
         .await
     }
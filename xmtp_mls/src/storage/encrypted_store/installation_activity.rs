@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    schema::installation_activity::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// The last time an installation was seen doing something
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = installation_activity)]
+#[diesel(primary_key(installation_id))]
+pub struct StoredInstallationActivity {
+    pub installation_id: Vec<u8>,
+    /// Time in nanoseconds the installation was last seen sending a message or
+    /// rotating its key package
+    pub last_active_ns: i64,
+    /// Hash of the last key package fetched for the installation
+    pub key_package_hash: Option<Vec<u8>>,
+}
+
+impl DbConnection {
+    fn get_installation_activity(
+        &self,
+        installation_id: &[u8],
+    ) -> Result<Option<StoredInstallationActivity>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::installation_activity
+                .find(installation_id)
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    fn replace_installation_activity(
+        &self,
+        activity: &StoredInstallationActivity,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::installation_activity)
+                .values(activity)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Record that the installation was active at `active_ns`. Activity older than what is
+    /// already recorded is ignored.
+    pub fn record_installation_activity(
+        &self,
+        installation_id: &[u8],
+        active_ns: i64,
+    ) -> Result<(), StorageError> {
+        let activity = match self.get_installation_activity(installation_id)? {
+            Some(activity) if activity.last_active_ns >= active_ns => return Ok(()),
+            Some(activity) => StoredInstallationActivity {
+                last_active_ns: active_ns,
+                ..activity
+            },
+            None => StoredInstallationActivity {
+                installation_id: installation_id.to_vec(),
+                last_active_ns: active_ns,
+                key_package_hash: None,
+            },
+        };
+        self.replace_installation_activity(&activity)
+    }
+
+    /// Record the key package fetched for the installation. A key package that is different
+    /// from the one fetched last time means the installation rotated it, and is still alive.
+    pub fn record_installation_key_package(
+        &self,
+        installation_id: &[u8],
+        key_package_hash: &[u8],
+    ) -> Result<(), StorageError> {
+        let activity = match self.get_installation_activity(installation_id)? {
+            Some(activity) if activity.key_package_hash.as_deref() == Some(key_package_hash) => {
+                return Ok(())
+            }
+            Some(activity) => StoredInstallationActivity {
+                last_active_ns: activity.last_active_ns.max(now_ns()),
+                key_package_hash: Some(key_package_hash.to_vec()),
+                ..activity
+            },
+            None => StoredInstallationActivity {
+                installation_id: installation_id.to_vec(),
+                last_active_ns: now_ns(),
+                key_package_hash: Some(key_package_hash.to_vec()),
+            },
+        };
+        self.replace_installation_activity(&activity)
+    }
+
+    /// The last time each of `installation_ids` was active.
+    /// Installations that were never seen are missing from the result.
+    pub fn installations_last_active(
+        &self,
+        installation_ids: &[Vec<u8>],
+    ) -> Result<HashMap<Vec<u8>, i64>, StorageError> {
+        let activity: Vec<(Vec<u8>, i64)> = self.raw_query(|conn| {
+            dsl::installation_activity
+                .filter(dsl::installation_id.eq_any(installation_ids))
+                .select((dsl::installation_id, dsl::last_active_ns))
+                .load(conn)
+        })?;
+        Ok(activity.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tracks_installation_activity() {
+        with_connection(|conn| {
+            let (bola, caro, davon) = (vec![1; 32], vec![2; 32], vec![3; 32]);
+
+            conn.record_installation_activity(&bola, 100).unwrap();
+            // Older activity does not move the last activity back
+            conn.record_installation_activity(&bola, 50).unwrap();
+
+            conn.record_installation_activity(&caro, 100).unwrap();
+            conn.record_installation_key_package(&caro, b"first")
+                .unwrap();
+            let rotated_at = conn.installations_last_active(&[caro.clone()]).unwrap()[&caro];
+            assert!(rotated_at > 100);
+            // The same key package again is not a rotation
+            conn.record_installation_key_package(&caro, b"first")
+                .unwrap();
+            assert_eq!(
+                conn.installations_last_active(&[caro.clone()]).unwrap()[&caro],
+                rotated_at
+            );
+
+            let last_active = conn
+                .installations_last_active(&[bola.clone(), caro.clone(), davon.clone()])
+                .unwrap();
+            assert_eq!(last_active.len(), 2);
+            assert_eq!(last_active[&bola], 100);
+            assert!(!last_active.contains_key(&davon));
+        })
+        .await
+    }
+}
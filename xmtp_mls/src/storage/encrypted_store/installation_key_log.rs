@@ -0,0 +1,330 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::installation_key_log::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// An installation key observed for an inbox. The log is append-only, enforced by triggers:
+/// entries are never deleted and their times only move forward, so that a key that disappears
+/// and comes back is noticed.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = installation_key_log)]
+#[diesel(primary_key(inbox_id, installation_id))]
+pub struct StoredInstallationKey {
+    pub inbox_id: String,
+    pub installation_id: Vec<u8>,
+    pub first_seen_ns: i64,
+    pub last_seen_ns: i64,
+    /// Time the installation was last found to no longer be part of the inbox
+    pub revoked_at_ns: Option<i64>,
+    /// Whether the user accepted this key, or the latest change to it
+    pub acknowledged: bool,
+    /// Time the installation was last seen again after it was revoked
+    pub reappeared_at_ns: Option<i64>,
+}
+
+impl StoredInstallationKey {
+    /// Whether the installation is part of the inbox, as far as this installation knows
+    pub fn is_active(&self) -> bool {
+        match (self.revoked_at_ns, self.reappeared_at_ns) {
+            (None, _) => true,
+            (Some(revoked_at_ns), Some(reappeared_at_ns)) => reappeared_at_ns > revoked_at_ns,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Whether the installation is back after it was revoked
+    pub fn reappeared(&self) -> bool {
+        self.revoked_at_ns.is_some() && self.is_active()
+    }
+}
+
+impl DbConnection {
+    /// Record that `installation_ids` are all the installations of `inbox_id`.
+    ///
+    /// The keys of an inbox that has not been seen before are trusted on first use. Keys that
+    /// show up later, and revoked keys that show up again, are not acknowledged until
+    /// [`Self::acknowledge_installation_keys`]. Returns the number of keys that were new.
+    pub fn observe_installation_keys(
+        &self,
+        inbox_id: &str,
+        installation_ids: &[Vec<u8>],
+        seen_ns: i64,
+    ) -> Result<usize, StorageError> {
+        let known: Vec<StoredInstallationKey> = self.installation_key_log(inbox_id)?;
+        let first_use = known.is_empty();
+        let mut new_keys = vec![];
+        for installation_id in installation_ids {
+            match known
+                .iter()
+                .find(|key| &key.installation_id == installation_id)
+            {
+                Some(key) => self.touch_installation_key(key, seen_ns)?,
+                None => new_keys.push(StoredInstallationKey {
+                    inbox_id: inbox_id.to_string(),
+                    installation_id: installation_id.clone(),
+                    first_seen_ns: seen_ns,
+                    last_seen_ns: seen_ns,
+                    revoked_at_ns: None,
+                    acknowledged: first_use,
+                    reappeared_at_ns: None,
+                }),
+            }
+        }
+
+        if new_keys.is_empty() {
+            return Ok(0);
+        }
+        Ok(self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::installation_key_log)
+                .values(&new_keys)
+                .execute(conn)
+        })?)
+    }
+
+    /// Record that a single installation of `inbox_id` was seen acting, for instance sending a
+    /// message. Keys that are not logged yet are left for [`Self::observe_installation_keys`],
+    /// which sees every key of the inbox at once: logging a key seen on its own first would flag
+    /// the keys the inbox already had as new.
+    pub fn observe_installation_key_use(
+        &self,
+        inbox_id: &str,
+        installation_id: &[u8],
+        seen_ns: i64,
+    ) -> Result<(), StorageError> {
+        let key: Option<StoredInstallationKey> = self.raw_query(|conn| {
+            dsl::installation_key_log
+                .find((inbox_id, installation_id))
+                .first(conn)
+                .optional()
+        })?;
+        if let Some(key) = key {
+            self.touch_installation_key(&key, seen_ns)?;
+        }
+        Ok(())
+    }
+
+    /// Move the last time `key` was seen forward, flagging it if it was revoked before
+    fn touch_installation_key(
+        &self,
+        key: &StoredInstallationKey,
+        seen_ns: i64,
+    ) -> Result<(), StorageError> {
+        let last_seen_ns = key.last_seen_ns.max(seen_ns);
+        let reappeared = !key.is_active() && key.revoked_at_ns.is_some_and(|at| seen_ns > at);
+        let target = dsl::installation_key_log.find((&key.inbox_id, &key.installation_id));
+        if reappeared {
+            self.raw_query(|conn| {
+                diesel::update(target)
+                    .set((
+                        dsl::last_seen_ns.eq(last_seen_ns),
+                        dsl::reappeared_at_ns.eq(seen_ns),
+                        dsl::acknowledged.eq(false),
+                    ))
+                    .execute(conn)
+            })?;
+        } else if last_seen_ns > key.last_seen_ns {
+            self.raw_query(|conn| {
+                diesel::update(target)
+                    .set(dsl::last_seen_ns.eq(last_seen_ns))
+                    .execute(conn)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Every installation key observed for the inbox, in the order they were first seen
+    pub fn installation_key_log(
+        &self,
+        inbox_id: &str,
+    ) -> Result<Vec<StoredInstallationKey>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::installation_key_log
+                .filter(dsl::inbox_id.eq(inbox_id))
+                .order((dsl::first_seen_ns.asc(), dsl::installation_id.asc()))
+                .load(conn)
+        })?)
+    }
+
+    /// Mark keys that are no longer part of the inbox as revoked. Revocations need to be
+    /// acknowledged again. Returns the number of keys revoked.
+    pub fn revoke_installation_keys(
+        &self,
+        inbox_id: &str,
+        installation_ids: &[Vec<u8>],
+        revoked_at_ns: i64,
+    ) -> Result<usize, StorageError> {
+        let active: Vec<Vec<u8>> = self
+            .installation_key_log(inbox_id)?
+            .into_iter()
+            .filter(|key| key.is_active() && installation_ids.contains(&key.installation_id))
+            .map(|key| key.installation_id)
+            .collect();
+        Ok(self.raw_query(|conn| {
+            diesel::update(
+                dsl::installation_key_log
+                    .filter(dsl::inbox_id.eq(inbox_id))
+                    .filter(dsl::installation_id.eq_any(&active)),
+            )
+            .set((
+                dsl::revoked_at_ns.eq(revoked_at_ns),
+                dsl::acknowledged.eq(false),
+            ))
+            .execute(conn)
+        })?)
+    }
+
+    /// Accept the current keys of the inbox. Returns the number of keys acknowledged.
+    pub fn acknowledge_installation_keys(&self, inbox_id: &str) -> Result<usize, StorageError> {
+        Ok(self.raw_query(|conn| {
+            diesel::update(
+                dsl::installation_key_log
+                    .filter(dsl::inbox_id.eq(inbox_id))
+                    .filter(dsl::acknowledged.eq(false)),
+            )
+            .set(dsl::acknowledged.eq(true))
+            .execute(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_trusts_keys_on_first_use() {
+        with_connection(|conn| {
+            let acknowledged = |conn: &DbConnection| {
+                conn.installation_key_log("bola")
+                    .unwrap()
+                    .into_iter()
+                    .map(|key| key.acknowledged)
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(
+                conn.observe_installation_keys("bola", &[vec![1], vec![2]], 100)
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                conn.observe_installation_keys("bola", &[vec![1]], 200)
+                    .unwrap(),
+                0
+            );
+            assert_eq!(
+                conn.installation_key_log("bola").unwrap()[0].last_seen_ns,
+                200
+            );
+
+            // A key that shows up later is flagged
+            assert_eq!(
+                conn.observe_installation_keys("bola", &[vec![3]], 300)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(acknowledged(conn), vec![true, true, false]);
+            assert_eq!(conn.acknowledge_installation_keys("bola").unwrap(), 1);
+
+            assert_eq!(
+                conn.revoke_installation_keys("bola", &[vec![1]], 400)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(acknowledged(conn), vec![false, true, true]);
+            assert_eq!(
+                conn.installation_key_log("bola").unwrap()[0].revoked_at_ns,
+                Some(400)
+            );
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_flags_revoked_keys_that_reappear() {
+        with_connection(|conn| {
+            conn.observe_installation_keys("bola", &[vec![1], vec![2]], 100)
+                .unwrap();
+            conn.revoke_installation_keys("bola", &[vec![1]], 200)
+                .unwrap();
+            conn.acknowledge_installation_keys("bola").unwrap();
+            // Seen acting before it was revoked
+            conn.observe_installation_key_use("bola", &[1], 150)
+                .unwrap();
+            assert!(!conn.installation_key_log("bola").unwrap()[0].is_active());
+
+            conn.observe_installation_key_use("bola", &[1], 300)
+                .unwrap();
+            let key = conn.installation_key_log("bola").unwrap().remove(0);
+            assert!(key.reappeared());
+            assert!(!key.acknowledged);
+            assert_eq!(key.reappeared_at_ns, Some(300));
+
+            // It can be revoked again
+            assert_eq!(
+                conn.revoke_installation_keys("bola", &[vec![1]], 400)
+                    .unwrap(),
+                1
+            );
+            assert!(!conn.installation_key_log("bola").unwrap()[0].is_active());
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_does_not_flag_keys_seen_one_at_a_time() {
+        with_connection(|conn| {
+            // A message from one installation is seen before the inbox is checked
+            conn.observe_installation_key_use("bola", &[2], 100)
+                .unwrap();
+            assert!(conn.installation_key_log("bola").unwrap().is_empty());
+
+            conn.observe_installation_keys("bola", &[vec![1], vec![2]], 200)
+                .unwrap();
+            assert!(conn
+                .installation_key_log("bola")
+                .unwrap()
+                .iter()
+                .all(|key| key.acknowledged));
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_rejects_rewriting_the_log() {
+        with_connection(|conn| {
+            conn.observe_installation_keys("bola", &[vec![1]], 100)
+                .unwrap();
+            conn.revoke_installation_keys("bola", &[vec![1]], 200)
+                .unwrap();
+
+            let delete = conn.raw_query(|conn| {
+                diesel::delete(dsl::installation_key_log.filter(dsl::inbox_id.eq("bola")))
+                    .execute(conn)
+            });
+            assert!(delete.is_err());
+            let unrevoke = conn.raw_query(|conn| {
+                diesel::update(dsl::installation_key_log.filter(dsl::inbox_id.eq("bola")))
+                    .set(dsl::revoked_at_ns.eq(None::<i64>))
+                    .execute(conn)
+            });
+            assert!(unrevoke.is_err());
+            let backdate = conn.raw_query(|conn| {
+                diesel::update(dsl::installation_key_log.filter(dsl::inbox_id.eq("bola")))
+                    .set(dsl::first_seen_ns.eq(0))
+                    .execute(conn)
+            });
+            assert!(backdate.is_err());
+            assert_eq!(conn.installation_key_log("bola").unwrap().len(), 1);
+        })
+        .await
+    }
+}
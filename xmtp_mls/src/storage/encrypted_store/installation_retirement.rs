@@ -0,0 +1,58 @@
+//! Retirement of an installation whose MLS state moved to another device.
+//!
+//! Once the key store is exported with `export_key_store`, the new device continues the groups
+//! from the exported state. If the old device kept publishing commits, both devices would advance
+//! the same leaf and the group state would fork. The export therefore retires the installation
+//! in the same transaction it reads the state in, once the state is sealed and attested, and a
+//! retired installation no longer queues or publishes intents. Importing an export clears the retirement, since the database of the new
+//! device is usually restored from a backup of the old one.
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::installation_retirement::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// The only row of the table
+const ROW_ID: i32 = 1;
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = installation_retirement)]
+struct StoredRetirement {
+    id: i32,
+    retired_at_ns: i64,
+}
+
+impl DbConnection {
+    /// When this installation was retired, if it was
+    pub fn installation_retired_at_ns(&self) -> Result<Option<i64>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::installation_retirement
+                .find(ROW_ID)
+                .select(dsl::retired_at_ns)
+                .first(conn)
+                .optional()
+        })?)
+    }
+}
+
+/// Retire the installation, keeping the time of the first retirement
+#[cfg_attr(not(feature = "key-store-export"), allow(dead_code))]
+pub(super) fn retire_installation(
+    conn: &mut super::RawDbConnection,
+    retired_at_ns: i64,
+) -> QueryResult<usize> {
+    diesel::insert_or_ignore_into(dsl::installation_retirement)
+        .values(&StoredRetirement {
+            id: ROW_ID,
+            retired_at_ns,
+        })
+        .execute(conn)
+}
+
+/// Clear the retirement, once the state of the installation was imported
+#[cfg_attr(not(feature = "key-store-export"), allow(dead_code))]
+pub(super) fn clear_retirement(conn: &mut super::RawDbConnection) -> QueryResult<usize> {
+    diesel::delete(dsl::installation_retirement).execute(conn)
+}
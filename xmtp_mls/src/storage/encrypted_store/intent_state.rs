@@ -0,0 +1,226 @@
+//! The lifecycle of a group intent, as a state machine with typed transitions.
+//!
+//! ```text
+//!            Publish            Commit
+//! ToPublish ─────────▶ Published ──────▶ Committed
+//!     ▲                   │
+//!     └───── Republish ───┘
+//!
+//! any state ── Fail ──▶ Error
+//! ```
+//!
+//! The storage API only moves intents through an [`IntentTransition`], and the database refuses
+//! any other change of `group_intents.state` with a trigger, so that no code path can write a
+//! state the intent could not have reached.
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+use thiserror::Error;
+
+use super::Sqlite;
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+pub enum IntentState {
+    ToPublish = 1,
+    Published = 2,
+    Committed = 3,
+    Error = 4,
+}
+
+/// A change of the state of an intent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntentTransition {
+    /// The intent was sent to the network
+    Publish,
+    /// The commit of the intent was merged
+    Commit,
+    /// The published intent must be sent again, e.g. because its commit was not accepted
+    Republish,
+    /// The intent can not be completed
+    Fail,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("intent in state {from:?} can not {transition:?}")]
+pub struct InvalidIntentTransition {
+    pub from: IntentState,
+    pub transition: IntentTransition,
+}
+
+impl IntentTransition {
+    /// The states the transition can start from
+    pub fn sources(self) -> &'static [IntentState] {
+        use IntentState::*;
+        match self {
+            Self::Publish => &[ToPublish],
+            Self::Commit | Self::Republish => &[Published],
+            Self::Fail => &[ToPublish, Published, Committed, Error],
+        }
+    }
+
+    /// The state the transition ends in
+    pub fn target(self) -> IntentState {
+        match self {
+            Self::Publish => IntentState::Published,
+            Self::Commit => IntentState::Committed,
+            Self::Republish => IntentState::ToPublish,
+            Self::Fail => IntentState::Error,
+        }
+    }
+}
+
+impl IntentState {
+    /// The state after `transition`
+    pub fn apply(self, transition: IntentTransition) -> Result<Self, InvalidIntentTransition> {
+        if transition.sources().contains(&self) {
+            Ok(transition.target())
+        } else {
+            Err(InvalidIntentTransition {
+                from: self,
+                transition,
+            })
+        }
+    }
+
+    /// Whether the state can be written over this one: staying in the same state, or a single
+    /// transition. This is what the storage trigger enforces.
+    pub fn can_become(self, to: Self) -> bool {
+        use IntentState::*;
+        match (self, to) {
+            (from, to) if from == to => true,
+            (_, Error) => true,
+            (ToPublish, Published) => true,
+            (Published, ToPublish | Committed) => true,
+            (ToPublish, ToPublish | Committed)
+            | (Published, Published)
+            | (Committed, ToPublish | Published | Committed)
+            | (Error, ToPublish | Published | Committed) => false,
+        }
+    }
+
+    /// Whether an intent in this state can be in state `to` after any number of transitions.
+    ///
+    /// `ToPublish` and `Published` move back and forth until the intent is `Committed`, and any
+    /// intent can fail with `Error`, which is final.
+    pub fn can_reach(self, to: Self) -> bool {
+        use IntentState::*;
+        match self {
+            ToPublish | Published => true,
+            Committed => matches!(to, Committed | Error),
+            Error => to == Error,
+        }
+    }
+}
+
+impl ToSql<Integer, Sqlite> for IntentState
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for IntentState
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(IntentState::ToPublish),
+            2 => Ok(IntentState::Published),
+            3 => Ok(IntentState::Committed),
+            4 => Ok(IntentState::Error),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use diesel::prelude::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group,
+            group_intent::{IntentKind, NewGroupIntent},
+            schema::group_intents::dsl,
+            tests::with_connection,
+        },
+        Store,
+    };
+
+    const STATES: [IntentState; 4] = [
+        IntentState::ToPublish,
+        IntentState::Published,
+        IntentState::Committed,
+        IntentState::Error,
+    ];
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn transitions_follow_the_state_machine() {
+        use IntentState::*;
+        use IntentTransition::*;
+        assert_eq!(ToPublish.apply(Publish), Ok(Published));
+        assert_eq!(Published.apply(Commit), Ok(Committed));
+        assert_eq!(Published.apply(Republish), Ok(ToPublish));
+        assert_eq!(Committed.apply(Fail), Ok(Error));
+        assert_eq!(
+            Published.apply(Publish),
+            Err(InvalidIntentTransition {
+                from: Published,
+                transition: Publish
+            })
+        );
+        assert!(Error.apply(Republish).is_err());
+        assert!(ToPublish.apply(Commit).is_err());
+
+        for transition in [Publish, Commit, Republish, Fail] {
+            for from in STATES {
+                if let Ok(to) = from.apply(transition) {
+                    assert!(from.can_become(to));
+                    assert!(from.can_reach(to));
+                }
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn storage_refuses_illegal_transitions() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            for from in STATES {
+                for to in STATES {
+                    let intent = conn
+                        .insert_group_intent(NewGroupIntent::new_test(
+                            IntentKind::SendMessage,
+                            group.id.clone(),
+                            vec![],
+                            from,
+                        ))
+                        .unwrap();
+                    let result = conn.raw_query(|conn| {
+                        diesel::update(dsl::group_intents.find(intent.id))
+                            .set(dsl::state.eq(to))
+                            .execute(conn)
+                    });
+                    assert_eq!(result.is_ok(), from.can_become(to), "{from:?} to {to:?}");
+                }
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,157 @@
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+
+use super::{
+    db_connection::DbConnection,
+    schema::join_requests::{self, dsl},
+    Sqlite,
+};
+use crate::storage::StorageError;
+
+/// An inbox that asked to join a discoverable group, see [`crate::groups::discovery`]
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = join_requests)]
+#[diesel(primary_key(group_id, inbox_id))]
+pub struct StoredJoinRequest {
+    pub group_id: Vec<u8>,
+    pub inbox_id: String,
+    /// Shown to the admins with the request
+    pub message: String,
+    /// Time in nanoseconds the join request was sent
+    pub requested_at_ns: i64,
+    pub state: JoinRequestState,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+/// Whether an admin answered a join request
+pub enum JoinRequestState {
+    Pending = 1,
+    Approved = 2,
+    Rejected = 3,
+}
+
+impl ToSql<Integer, Sqlite> for JoinRequestState
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for JoinRequestState
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(JoinRequestState::Pending),
+            2 => Ok(JoinRequestState::Approved),
+            3 => Ok(JoinRequestState::Rejected),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+impl DbConnection {
+    /// Record a join request. An inbox that asks again keeps its first request, so a rejected
+    /// inbox cannot ask again.
+    pub fn record_join_request(&self, request: &StoredJoinRequest) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::join_requests)
+                .values(request)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Join requests of `group_id` in `state`, oldest first
+    pub fn join_requests<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        state: JoinRequestState,
+    ) -> Result<Vec<StoredJoinRequest>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::join_requests
+                .filter(dsl::group_id.eq(group_id.as_ref()))
+                .filter(dsl::state.eq(state))
+                .order(dsl::requested_at_ns.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// Record the answer to a join request. Returns `false` if there is no such request.
+    pub fn set_join_request_state<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        inbox_id: &str,
+        state: JoinRequestState,
+    ) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::join_requests.find((group_id.as_ref(), inbox_id)))
+                .set(dsl::state.eq(state))
+                .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tracks_join_requests() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let request = |inbox_id: &str, requested_at_ns| StoredJoinRequest {
+                group_id: group.id.clone(),
+                inbox_id: inbox_id.to_string(),
+                message: format!("{inbox_id} would like to join"),
+                requested_at_ns,
+                state: JoinRequestState::Pending,
+            };
+
+            conn.record_join_request(&request("bola", 2)).unwrap();
+            conn.record_join_request(&request("caro", 1)).unwrap();
+            assert!(conn
+                .set_join_request_state(&group.id, "bola", JoinRequestState::Rejected)
+                .unwrap());
+            // Asking again does not reset the answer
+            conn.record_join_request(&request("bola", 3)).unwrap();
+            assert_eq!(
+                conn.join_requests(&group.id, JoinRequestState::Pending)
+                    .unwrap(),
+                vec![request("caro", 1)]
+            );
+            assert_eq!(
+                conn.join_requests(&group.id, JoinRequestState::Rejected)
+                    .unwrap()
+                    .len(),
+                1
+            );
+            assert!(!conn
+                .set_join_request_state(&group.id, "dave", JoinRequestState::Approved)
+                .unwrap());
+        })
+        .await
+    }
+}
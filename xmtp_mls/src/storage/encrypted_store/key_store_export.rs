@@ -0,0 +1,486 @@
+//! Export and import of the MLS state of an installation, to move it to another device.
+//!
+//! **The export contains every MLS secret of the installation.** Anyone holding it together with
+//! the export key can decrypt the messages of every group the installation is a member of, until
+//! the groups move to a new epoch. It must only be handed to a platform that moves state between
+//! devices of the same user securely, such as an encrypted iCloud or Android backup.
+//!
+//! The export holds the OpenMLS key store together with the rows that must match it: the
+//! `groups`, the `refresh_state` cursors and the `group_intents`, read in a single transaction.
+//! Two devices using the same MLS state would fork the groups, so once the state is sealed and
+//! attested, the same transaction retires the installation, see
+//! [`installation_retirement`](super::installation_retirement). A retired installation no longer
+//! queues or publishes intents. If sealing or attesting fails, nothing is exported and the
+//! installation keeps working.
+//!
+//! The state is sealed with AES-256-GCM under the export key, and the digest of the sealed bytes
+//! is attested by a [`KeyStoreAttestation`] of the host, e.g. App Attest or Android Key
+//! Attestation. The import verifies the attestation before anything is decrypted, then replaces
+//! the MLS state and clears the retirement in a single transaction. The identity of the
+//! installation is not part of the export, it is restored with the backup of the database.
+//!
+//! With a [`KeyStoreBackend`] attached, the MLS secrets are unsealed into the export, and sealed
+//! again by the backend of the importing store, if any. The installation signing key of a backend
+//! is not part of the export, the identity only holds its handle.
+//!
+//! Only available with the `key-store-export` feature.
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    db_connection::DbConnection,
+    group::{record_dm_public_id, StoredGroup},
+    installation_retirement::{clear_retirement, retire_installation},
+    schema::{group_intents, groups, openmls_key_value, refresh_state},
+    EncryptionKey,
+};
+use crate::storage::{
+    key_store_backend::KeyStoreBackend,
+    sql_key_store::{is_secret_entry, sealing_key, sealing_key_storage_key},
+    StorageError,
+};
+
+/// Marks a key store export, followed by the nonce, the ciphertext and the attestation
+const EXPORT_PREFIX: &[u8] = b"xmtp-key-store-v2";
+const NONCE_SIZE: usize = 12;
+
+/// Attests exports of the key store with a hardware-backed key of the host
+pub trait KeyStoreAttestation: Send + Sync {
+    /// An attestation of `digest`, the SHA-256 of the sealed key store
+    fn attest(&self, digest: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Check that `attestation` was produced by [`Self::attest`] for `digest` on a device of this
+    /// user
+    fn verify(&self, digest: &[u8], attestation: &[u8]) -> Result<(), String>;
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = openmls_key_value)]
+struct KeyValueEntry {
+    version: i32,
+    key_bytes: Vec<u8>,
+    value_bytes: Vec<u8>,
+}
+
+/// A `refresh_state` row, as stored
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = refresh_state)]
+struct RefreshStateEntry {
+    entity_id: Vec<u8>,
+    entity_kind: i32,
+    cursor: i64,
+}
+
+/// A `group_intents` row, as stored. The id is kept since the staged commits of published
+/// intents are found by it.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_intents)]
+struct GroupIntentEntry {
+    id: i32,
+    kind: i32,
+    group_id: Vec<u8>,
+    data: Vec<u8>,
+    state: i32,
+    payload_hash: Option<Vec<u8>>,
+    post_commit_data: Option<Vec<u8>>,
+    publish_attempts: i32,
+    staged_commit: Option<Vec<u8>>,
+    published_in_epoch: Option<i64>,
+}
+
+/// The MLS state of an installation, as sealed in an export
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct MlsStateExport {
+    key_values: Vec<KeyValueEntry>,
+    groups: Vec<StoredGroup>,
+    refresh_state: Vec<RefreshStateEntry>,
+    intents: Vec<GroupIntentEntry>,
+}
+
+fn export_error(message: impl std::fmt::Display) -> StorageError {
+    StorageError::KeyStoreExport(message.to_string())
+}
+
+/// The digest the host attests, of the nonce and ciphertext of an export
+fn sealed_digest(sealed: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update(EXPORT_PREFIX)
+        .chain_update(sealed)
+        .finalize()
+        .to_vec()
+}
+
+/// Unseal the secrets of `entries` sealed by `backend`, and drop the handle of its sealing key
+fn unseal_entries(
+    backend: &dyn KeyStoreBackend,
+    entries: &mut Vec<KeyValueEntry>,
+) -> Result<(), StorageError> {
+    let sealing_key = sealing_key_storage_key();
+    let Some(position) = entries.iter().position(|e| e.key_bytes == sealing_key) else {
+        // Nothing was sealed yet
+        return Ok(());
+    };
+    let handle = entries.remove(position).value_bytes;
+    for entry in entries.iter_mut().filter(|e| is_secret_entry(&e.key_bytes)) {
+        entry.value_bytes = backend.unseal(&handle, &entry.key_bytes, &entry.value_bytes)?;
+    }
+    Ok(())
+}
+
+/// Seal the secrets of `entries` with the sealing key of `backend`
+fn seal_entries(
+    conn: &mut super::RawDbConnection,
+    backend: &dyn KeyStoreBackend,
+    entries: &mut [KeyValueEntry],
+) -> Result<(), StorageError> {
+    let handle = sealing_key(conn, backend)?;
+    for entry in entries.iter_mut().filter(|e| is_secret_entry(&e.key_bytes)) {
+        entry.value_bytes = backend.seal(&handle, &entry.key_bytes, &entry.value_bytes)?;
+    }
+    Ok(())
+}
+
+/// Seal `state` under `export_key` and attest the sealed bytes
+fn seal(
+    state: &MlsStateExport,
+    export_key: &EncryptionKey,
+    attestation: &dyn KeyStoreAttestation,
+) -> Result<Vec<u8>, StorageError> {
+    let plaintext =
+        bincode::serialize(state).map_err(|e| StorageError::Serialization(e.to_string()))?;
+
+    let nonce = xmtp_common::rand_array::<NONCE_SIZE>();
+    let ciphertext = Aes256Gcm::new(GenericArray::from_slice(export_key))
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: EXPORT_PREFIX,
+            },
+        )
+        .map_err(export_error)?;
+    let mut sealed = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    let attested = attestation
+        .attest(&sealed_digest(&sealed))
+        .map_err(export_error)?;
+
+    let mut export = Vec::with_capacity(EXPORT_PREFIX.len() + 4 + sealed.len() + attested.len());
+    export.extend_from_slice(EXPORT_PREFIX);
+    export.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+    export.extend_from_slice(&sealed);
+    export.extend_from_slice(&attested);
+    Ok(export)
+}
+
+impl DbConnection {
+    /// Seal the MLS state under `export_key`, attest it with `attestation` and retire this
+    /// installation, see the [module docs](self) before using this.
+    pub fn export_key_store(
+        &self,
+        export_key: &EncryptionKey,
+        attestation: &dyn KeyStoreAttestation,
+    ) -> Result<Vec<u8>, StorageError> {
+        let backend = self.key_store_backend();
+        let (state, export) = self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                let mut state = MlsStateExport {
+                    key_values: openmls_key_value::table.load(conn)?,
+                    groups: groups::table.load(conn)?,
+                    refresh_state: refresh_state::table.load(conn)?,
+                    intents: group_intents::table.load(conn)?,
+                };
+                if let Some(backend) = backend {
+                    unseal_entries(backend.as_ref(), &mut state.key_values)?;
+                }
+                // The state cannot change until the transaction ends, and the installation is
+                // only retired once there is an export to continue from
+                let export = seal(&state, export_key, attestation)?;
+                retire_installation(conn, xmtp_common::time::now_ns())?;
+                Ok::<_, StorageError>((state, export))
+            })
+        })?;
+        tracing::warn!(
+            entries = state.key_values.len(),
+            groups = state.groups.len(),
+            "exported the MLS state of this installation, it is now retired"
+        );
+        Ok(export)
+    }
+
+    /// Replace the MLS state with an export of [`Self::export_key_store`], once its attestation
+    /// is verified. Returns the number of imported key store entries.
+    pub fn import_key_store(
+        &self,
+        export: &[u8],
+        export_key: &EncryptionKey,
+        attestation: &dyn KeyStoreAttestation,
+    ) -> Result<usize, StorageError> {
+        let truncated = || export_error("key store export is truncated");
+        let rest = export
+            .strip_prefix(EXPORT_PREFIX)
+            .ok_or_else(|| export_error("not a key store export"))?;
+        let (len, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len || len < NONCE_SIZE {
+            return Err(truncated());
+        }
+        let (sealed, attested) = rest.split_at(len);
+        attestation
+            .verify(&sealed_digest(sealed), attested)
+            .map_err(export_error)?;
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let plaintext = Aes256Gcm::new(GenericArray::from_slice(export_key))
+            .decrypt(
+                GenericArray::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: EXPORT_PREFIX,
+                },
+            )
+            .map_err(export_error)?;
+        let mut state: MlsStateExport = bincode::deserialize(&plaintext)
+            .map_err(|e| StorageError::Deserialization(e.to_string()))?;
+
+        let backend = self.key_store_backend();
+        self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                // The handle of the sealing key of this store is kept
+                diesel::delete(
+                    openmls_key_value::table
+                        .filter(openmls_key_value::key_bytes.ne(sealing_key_storage_key())),
+                )
+                .execute(conn)?;
+                if let Some(backend) = backend {
+                    seal_entries(conn, backend.as_ref(), &mut state.key_values)?;
+                }
+                diesel::insert_into(openmls_key_value::table)
+                    .values(&state.key_values)
+                    .execute(conn)?;
+                diesel::delete(refresh_state::table).execute(conn)?;
+                diesel::insert_into(refresh_state::table)
+                    .values(&state.refresh_state)
+                    .execute(conn)?;
+                diesel::delete(group_intents::table).execute(conn)?;
+                diesel::insert_into(group_intents::table)
+                    .values(&state.intents)
+                    .execute(conn)?;
+                // Messages and other rows of the groups are kept, only the MLS columns must match
+                for group in &state.groups {
+                    diesel::replace_into(groups::table)
+                        .values(group)
+                        .execute(conn)?;
+                    record_dm_public_id(conn, group.dm_id.as_deref())?;
+                }
+                clear_retirement(conn)?;
+                Ok::<_, StorageError>(())
+            })
+        })?;
+        tracing::warn!(
+            entries = state.key_values.len(),
+            groups = state.groups.len(),
+            "replaced the MLS state of this installation with an import"
+        );
+        Ok(state.key_values.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::configuration::CIPHERSUITE;
+    use crate::storage::encrypted_store::{group::tests::generate_group, tests::with_connection};
+    use crate::storage::{
+        key_store_backend::tests::InMemoryKeyStoreBackend,
+        xmtp_openmls_provider::XmtpOpenMlsProvider, EncryptedMessageStore, StorageOption,
+    };
+    use crate::Store;
+    use openmls_basic_credential::{SignatureKeyPair, StorageId};
+    use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+    use std::sync::Arc;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    /// Attests with a keyed digest, standing in for the hardware of the host
+    struct TestAttestation(u8);
+
+    impl KeyStoreAttestation for TestAttestation {
+        fn attest(&self, digest: &[u8]) -> Result<Vec<u8>, String> {
+            Ok(Sha256::new()
+                .chain_update([self.0])
+                .chain_update(digest)
+                .finalize()
+                .to_vec())
+        }
+
+        fn verify(&self, digest: &[u8], attestation: &[u8]) -> Result<(), String> {
+            if self.attest(digest)? == attestation {
+                Ok(())
+            } else {
+                Err("attestation does not match".to_string())
+            }
+        }
+    }
+
+    fn entries(conn: &DbConnection) -> Vec<KeyValueEntry> {
+        conn.raw_query(|conn| openmls_key_value::table.load(conn))
+            .unwrap()
+    }
+
+    struct FailingAttestation;
+
+    impl KeyStoreAttestation for FailingAttestation {
+        fn attest(&self, _digest: &[u8]) -> Result<Vec<u8>, String> {
+            Err("attestation unavailable".to_string())
+        }
+
+        fn verify(&self, _digest: &[u8], _attestation: &[u8]) -> Result<(), String> {
+            Err("attestation unavailable".to_string())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn failed_attestation_keeps_the_installation() {
+        with_connection(|conn| {
+            generate_group(None).store(conn).unwrap();
+            let err = conn
+                .export_key_store(&[7; 32], &FailingAttestation)
+                .unwrap_err();
+            assert!(matches!(err, StorageError::KeyStoreExport(_)));
+            assert_eq!(conn.installation_retired_at_ns().unwrap(), None);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_exports_and_imports_the_key_store() {
+        let entry = KeyValueEntry {
+            version: 1,
+            key_bytes: vec![1, 2],
+            value_bytes: vec![3, 4],
+        };
+        let group = generate_group(None);
+        let cursor = RefreshStateEntry {
+            entity_id: group.id.clone(),
+            entity_kind: 2,
+            cursor: 42,
+        };
+        let export = with_connection(|conn| {
+            conn.raw_query(|conn| {
+                diesel::insert_into(openmls_key_value::table)
+                    .values(&entry)
+                    .execute(conn)?;
+                diesel::insert_into(refresh_state::table)
+                    .values(&cursor)
+                    .execute(conn)
+            })
+            .unwrap();
+            group.store(conn).unwrap();
+            assert_eq!(conn.installation_retired_at_ns().unwrap(), None);
+            let export = conn
+                .export_key_store(&[7; 32], &TestAttestation(1))
+                .unwrap();
+            // the source installation must not continue from the exported state
+            assert!(conn.installation_retired_at_ns().unwrap().is_some());
+            export
+        })
+        .await;
+
+        with_connection(|conn| {
+            // the attestation is checked before anything is decrypted
+            let err = conn
+                .import_key_store(&export, &[7; 32], &TestAttestation(2))
+                .unwrap_err();
+            assert!(matches!(err, StorageError::KeyStoreExport(_)));
+            assert!(conn
+                .import_key_store(&export, &[8; 32], &TestAttestation(1))
+                .is_err());
+            assert!(conn
+                .import_key_store(&export[..20], &[7; 32], &TestAttestation(1))
+                .is_err());
+            assert!(entries(conn).is_empty());
+
+            assert_eq!(
+                conn.import_key_store(&export, &[7; 32], &TestAttestation(1))
+                    .unwrap(),
+                1
+            );
+            assert_eq!(entries(conn), vec![entry.clone()]);
+            assert_eq!(conn.find_group(&group.id).unwrap(), Some(group.clone()));
+            let cursors: Vec<RefreshStateEntry> = conn
+                .raw_query(|conn| refresh_state::table.load(conn))
+                .unwrap();
+            assert_eq!(cursors, vec![cursor.clone()]);
+            assert_eq!(conn.installation_retired_at_ns().unwrap(), None);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn secrets_move_between_key_store_backends() {
+        let new_store = || async {
+            EncryptedMessageStore::new(
+                StorageOption::Persistent(xmtp_common::tmp_path()),
+                EncryptedMessageStore::generate_enc_key(),
+            )
+            .await
+            .unwrap()
+        };
+        let signature_keys = SignatureKeyPair::new(CIPHERSUITE.signature_algorithm()).unwrap();
+        let public_key = StorageId::from(signature_keys.to_public_vec());
+        let read_signature_keys = |provider: &XmtpOpenMlsProvider| {
+            provider
+                .storage()
+                .signature_key_pair::<StorageId, SignatureKeyPair>(&public_key)
+                .unwrap()
+                .map(|keys| keys.to_public_vec())
+        };
+
+        let source = new_store()
+            .await
+            .with_key_store_backend(Arc::new(InMemoryKeyStoreBackend::default()));
+        let provider = XmtpOpenMlsProvider::new(source.conn().unwrap());
+        provider
+            .storage()
+            .write_signature_key_pair::<StorageId, SignatureKeyPair>(&public_key, &signature_keys)
+            .unwrap();
+        let export = provider
+            .conn_ref()
+            .export_key_store(&[7; 32], &TestAttestation(1))
+            .unwrap();
+
+        // sealed again by the backend of the importing store
+        let backend = Arc::new(InMemoryKeyStoreBackend::default());
+        let target = new_store().await.with_key_store_backend(backend.clone());
+        let provider = XmtpOpenMlsProvider::new(target.conn().unwrap());
+        provider
+            .conn_ref()
+            .import_key_store(&export, &[7; 32], &TestAttestation(1))
+            .unwrap();
+        assert_eq!(backend.sealing_keys.lock().len(), 1);
+        assert_eq!(
+            read_signature_keys(&provider),
+            Some(signature_keys.to_public_vec())
+        );
+
+        // or kept in the database of a store without one
+        let provider = XmtpOpenMlsProvider::new(new_store().await.conn().unwrap());
+        provider
+            .conn_ref()
+            .import_key_store(&export, &[7; 32], &TestAttestation(1))
+            .unwrap();
+        assert_eq!(
+            read_signature_keys(&provider),
+            Some(signature_keys.to_public_vec())
+        );
+    }
+}
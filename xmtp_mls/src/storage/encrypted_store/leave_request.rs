@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::leave_requests::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// A member that asked to leave a group, see [`crate::groups::MlsGroup::leave`]
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = leave_requests)]
+#[diesel(primary_key(group_id, inbox_id))]
+pub struct StoredLeaveRequest {
+    pub group_id: Vec<u8>,
+    pub inbox_id: String,
+    /// Time in nanoseconds the leave request was sent
+    pub requested_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Record a leave request. A member that asks again keeps its first request.
+    pub fn record_leave_request(&self, request: &StoredLeaveRequest) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::leave_requests)
+                .values(request)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Members of `group_id` waiting to be removed, oldest request first
+    pub fn leave_requests<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredLeaveRequest>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::leave_requests
+                .filter(dsl::group_id.eq(group_id.as_ref()))
+                .order(dsl::requested_at_ns.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// The members were removed from the group
+    pub fn clear_leave_requests<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        inbox_ids: &[String],
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::delete(
+                dsl::leave_requests
+                    .filter(dsl::group_id.eq(group_id.as_ref()))
+                    .filter(dsl::inbox_id.eq_any(inbox_ids)),
+            )
+            .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_first_leave_request() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let request = |inbox_id: &str, requested_at_ns| StoredLeaveRequest {
+                group_id: group.id.clone(),
+                inbox_id: inbox_id.to_string(),
+                requested_at_ns,
+            };
+
+            conn.record_leave_request(&request("bola", 2)).unwrap();
+            conn.record_leave_request(&request("caro", 1)).unwrap();
+            conn.record_leave_request(&request("bola", 3)).unwrap();
+            assert_eq!(
+                conn.leave_requests(&group.id).unwrap(),
+                vec![request("caro", 1), request("bola", 2)]
+            );
+
+            conn.clear_leave_requests(&group.id, &["bola".to_string()])
+                .unwrap();
+            assert_eq!(
+                conn.leave_requests(&group.id).unwrap(),
+                vec![request("caro", 1)]
+            );
+        })
+        .await
+    }
+}
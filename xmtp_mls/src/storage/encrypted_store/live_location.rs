@@ -0,0 +1,200 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    group_message::ContentType,
+    schema::{
+        group_messages::dsl as messages_dsl,
+        live_locations::{self, dsl},
+    },
+};
+use crate::storage::StorageError;
+
+/// The latest position of a sender that is sharing their live location in a group
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq)]
+#[diesel(table_name = live_locations)]
+#[diesel(primary_key(group_id, sender_inbox_id))]
+pub struct StoredLiveLocation {
+    pub group_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    /// Id of the message that started the live share
+    pub share_id: Vec<u8>,
+    /// Id of the message with the latest position
+    pub message_id: Vec<u8>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy_meters: f64,
+    pub updated_at_ns: i64,
+    pub expires_at_ns: i64,
+}
+
+impl StoredLiveLocation {
+    /// Positions are ordered by the time they were sent, and then by message id, so that every
+    /// installation keeps the same position when two arrive with the same timestamp
+    fn replaces(&self, other: &StoredLiveLocation) -> bool {
+        (self.updated_at_ns, &self.message_id) >= (other.updated_at_ns, &other.message_id)
+    }
+}
+
+impl DbConnection {
+    /// Record the position of a sender, unless a later position of the sender is already known.
+    /// Returns whether the position was recorded.
+    pub fn record_live_location(
+        &self,
+        location: &StoredLiveLocation,
+    ) -> Result<bool, StorageError> {
+        let existing: Option<StoredLiveLocation> = self.raw_query(|conn| {
+            dsl::live_locations
+                .find((&location.group_id, &location.sender_inbox_id))
+                .first(conn)
+                .optional()
+        })?;
+        if existing.is_some_and(|existing| !location.replaces(&existing)) {
+            return Ok(false);
+        }
+
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::live_locations)
+                .values(location)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    /// The live locations in a group whose share window has not ended at `now_ns`
+    pub fn live_locations<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        now_ns: i64,
+    ) -> Result<Vec<StoredLiveLocation>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::live_locations
+                .filter(dsl::group_id.eq(group_id.as_ref()))
+                .filter(dsl::expires_at_ns.gt(now_ns))
+                .order(dsl::sender_inbox_id.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// Delete all but the latest `keep` update messages that a sender sent in a live share.
+    /// The message that started the share is never deleted.
+    /// Returns the number of messages deleted.
+    pub fn trim_live_location_history(
+        &self,
+        group_id: &[u8],
+        sender_inbox_id: &str,
+        share_id: &[u8],
+        keep: i64,
+    ) -> Result<usize, StorageError> {
+        let stale_ids: Vec<Vec<u8>> = self.raw_query(|conn| {
+            messages_dsl::group_messages
+                .select(messages_dsl::id)
+                .filter(messages_dsl::group_id.eq(group_id))
+                .filter(messages_dsl::sender_inbox_id.eq(sender_inbox_id))
+                .filter(messages_dsl::content_type.eq(ContentType::Location))
+                .filter(messages_dsl::reference_id.eq(share_id))
+                .order((messages_dsl::sent_at_ns.desc(), messages_dsl::id.desc()))
+                .offset(keep)
+                .limit(i64::MAX)
+                .load(conn)
+        })?;
+        if stale_ids.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(self.raw_query(|conn| {
+            diesel::delete(messages_dsl::group_messages.filter(messages_dsl::id.eq_any(stale_ids)))
+                .execute(conn)
+        })?)
+    }
+
+    /// Delete the live locations whose share window ended before `now_ns`, together with the
+    /// update messages of those shares. Returns the number of shares that expired.
+    pub fn delete_expired_live_locations(&self, now_ns: i64) -> Result<usize, StorageError> {
+        let expired: Vec<StoredLiveLocation> = self.raw_query(|conn| {
+            dsl::live_locations
+                .filter(dsl::expires_at_ns.le(now_ns))
+                .load(conn)
+        })?;
+        for location in &expired {
+            self.trim_live_location_history(
+                &location.group_id,
+                &location.sender_inbox_id,
+                &location.share_id,
+                0,
+            )?;
+        }
+
+        Ok(self.raw_query(|conn| {
+            diesel::delete(dsl::live_locations.filter(dsl::expires_at_ns.le(now_ns))).execute(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_coalesces_live_locations() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let share_id = vec![1; 32];
+            let location = |message_id: u8, updated_at_ns: i64| StoredLiveLocation {
+                group_id: group.id.clone(),
+                sender_inbox_id: "bola".to_string(),
+                share_id: share_id.clone(),
+                message_id: vec![message_id; 32],
+                latitude: 52.0,
+                longitude: 4.0 + updated_at_ns as f64 / 1000.0,
+                accuracy_meters: 0.0,
+                updated_at_ns,
+                expires_at_ns: 1_000,
+            };
+
+            assert!(conn.record_live_location(&location(2, 200)).unwrap());
+            // An update that arrives late does not replace a newer one
+            assert!(!conn.record_live_location(&location(3, 100)).unwrap());
+            let live = conn.live_locations(&group.id, 500).unwrap();
+            assert_eq!(live.len(), 1);
+            assert_eq!(live[0].message_id, vec![2; 32]);
+
+            for sent_at_ns in 0..5 {
+                let mut message = generate_message(
+                    None,
+                    Some(&group.id),
+                    Some(sent_at_ns),
+                    Some(ContentType::Location),
+                );
+                message.sender_inbox_id = "bola".to_string();
+                message.reference_id = Some(share_id.clone());
+                message.store(conn).unwrap();
+            }
+            assert_eq!(
+                conn.trim_live_location_history(&group.id, "bola", &share_id, 2)
+                    .unwrap(),
+                3
+            );
+
+            assert!(conn.live_locations(&group.id, 1_000).unwrap().is_empty());
+            assert_eq!(conn.delete_expired_live_locations(1_000).unwrap(), 1);
+            assert!(conn
+                .get_group_messages(&group.id, &Default::default())
+                .unwrap()
+                .is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,201 @@
+//! Calendar days in the user's time zone, for date separators and exports.
+//!
+//! A time zone is given by its offset from UTC, which the app gets from the platform for the
+//! day it is interested in. Day boundaries are turned into `sent_at_ns` ranges, so queries stay
+//! in SQL and use the message indexes.
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Binary, Integer},
+};
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    group_message::{GroupMessageKind, MsgQueryArgs},
+};
+use crate::storage::StorageError;
+
+const NS_IN_SEC: i64 = 1_000_000_000;
+const NS_IN_DAY: i64 = 24 * 60 * 60 * NS_IN_SEC;
+
+/// A calendar day in a time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalDay {
+    /// Days since 1970-01-01 in the time zone
+    pub days_since_epoch: i64,
+    /// Offset of the time zone from UTC, e.g. `3600` for UTC+1
+    pub utc_offset_secs: i32,
+}
+
+impl LocalDay {
+    /// The day `timestamp_ns` falls on in the time zone
+    pub fn containing(timestamp_ns: i64, utc_offset_secs: i32) -> Self {
+        Self {
+            days_since_epoch: (timestamp_ns + offset_ns(utc_offset_secs)).div_euclid(NS_IN_DAY),
+            utc_offset_secs,
+        }
+    }
+
+    pub fn today(utc_offset_secs: i32) -> Self {
+        Self::containing(now_ns(), utc_offset_secs)
+    }
+
+    /// The day `days` days later, or earlier if negative
+    pub fn add_days(&self, days: i64) -> Self {
+        Self {
+            days_since_epoch: self.days_since_epoch + days,
+            ..*self
+        }
+    }
+
+    /// First nanosecond of the day, since the Unix epoch
+    pub fn start_ns(&self) -> i64 {
+        self.days_since_epoch * NS_IN_DAY - offset_ns(self.utc_offset_secs)
+    }
+
+    /// First nanosecond of the next day, since the Unix epoch
+    pub fn end_ns(&self) -> i64 {
+        self.add_days(1).start_ns()
+    }
+}
+
+fn offset_ns(utc_offset_secs: i32) -> i64 {
+    i64::from(utc_offset_secs) * NS_IN_SEC
+}
+
+impl MsgQueryArgs {
+    /// Only messages sent from the start of `from` to the end of `to`, both included
+    pub fn between_days(mut self, from: LocalDay, to: LocalDay) -> Self {
+        // `sent_after_ns` is exclusive
+        self.sent_after_ns = Some(from.start_ns() - 1);
+        self.sent_before_ns = Some(to.end_ns());
+        self
+    }
+
+    /// Only messages sent on `day`
+    pub fn on_day(self, day: LocalDay) -> Self {
+        self.between_days(day, day)
+    }
+}
+
+#[derive(QueryableByName)]
+struct DayCount {
+    #[diesel(sql_type = BigInt)]
+    day: i64,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+impl DbConnection {
+    /// Number of application messages of a group on each day from `from` to `to` that has
+    /// any, in order. The days are counted in the time zone of `from`.
+    pub fn count_messages_by_day(
+        &self,
+        group_id: &[u8],
+        from: LocalDay,
+        to: LocalDay,
+    ) -> Result<Vec<(LocalDay, i64)>, StorageError> {
+        let offset_ns = offset_ns(from.utc_offset_secs);
+        let counts: Vec<DayCount> = self.raw_query(|conn| {
+            sql_query(
+                "SELECT (sent_at_ns + ?) / ? AS day, COUNT(*) AS count
+                FROM group_messages
+                WHERE group_id = ? AND kind = ? AND sent_at_ns >= ? AND sent_at_ns < ?
+                GROUP BY day
+                ORDER BY day ASC",
+            )
+            .bind::<BigInt, _>(offset_ns)
+            .bind::<BigInt, _>(NS_IN_DAY)
+            .bind::<Binary, _>(group_id)
+            .bind::<Integer, _>(GroupMessageKind::Application)
+            .bind::<BigInt, _>(from.start_ns())
+            .bind::<BigInt, _>(to.end_ns())
+            .load(conn)
+        })?;
+        Ok(counts
+            .into_iter()
+            .map(|DayCount { day, count }| {
+                let day = LocalDay {
+                    days_since_epoch: day,
+                    utc_offset_secs: from.utc_offset_secs,
+                };
+                (day, count)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const HOUR_NS: i64 = 60 * 60 * NS_IN_SEC;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn day_boundaries_follow_the_offset() {
+        // 2024-01-02 at 23:30 UTC is already 2024-01-03 in UTC+1
+        let timestamp_ns = 19_724 * NS_IN_DAY + 23 * HOUR_NS + HOUR_NS / 2;
+        let utc = LocalDay::containing(timestamp_ns, 0);
+        let paris = LocalDay::containing(timestamp_ns, 3600);
+        let new_york = LocalDay::containing(timestamp_ns, -5 * 3600);
+        assert_eq!(utc.days_since_epoch, 19_724);
+        assert_eq!(paris.days_since_epoch, 19_725);
+        assert_eq!(new_york.days_since_epoch, 19_724);
+        assert_eq!(paris.start_ns(), 19_725 * NS_IN_DAY - HOUR_NS);
+        assert_eq!(paris.end_ns() - paris.start_ns(), NS_IN_DAY);
+        assert_eq!(LocalDay::containing(paris.start_ns(), 3600), paris);
+        assert_eq!(
+            LocalDay::containing(paris.start_ns() - 1, 3600),
+            paris.add_days(-1)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_queries_messages_by_local_day() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let day = LocalDay {
+                days_since_epoch: 19_724,
+                utc_offset_secs: 3600,
+            };
+            // The last hour of the previous day, two messages on the day and one the day after
+            for sent_at_ns in [
+                day.start_ns() - HOUR_NS,
+                day.start_ns(),
+                day.end_ns() - 1,
+                day.end_ns() + HOUR_NS,
+            ] {
+                generate_message(None, Some(&group.id), Some(sent_at_ns), None)
+                    .store(conn)
+                    .unwrap();
+            }
+
+            let messages = conn
+                .get_group_messages(&group.id, &MsgQueryArgs::default().on_day(day))
+                .unwrap();
+            assert_eq!(messages.len(), 2);
+
+            let counts = conn
+                .count_messages_by_day(&group.id, day.add_days(-1), day.add_days(1))
+                .unwrap();
+            assert_eq!(
+                counts,
+                vec![(day.add_days(-1), 1), (day, 2), (day.add_days(1), 1)]
+            );
+        })
+        .await
+    }
+}
@@ -0,0 +1,333 @@
+//! Database maintenance routines meant to be run while the app is idle.
+//!
+//! Each step is cheap on its own, but together they keep query plans fresh
+//! and return free pages to the filesystem. Steps are ordered from cheapest
+//! to most expensive, and the run stops early once the time budget is spent
+//! so that mobile apps can call this opportunistically.
+use std::collections::BTreeMap;
+
+use diesel::{connection::SimpleConnection, prelude::*, sql_query};
+use serde::Serialize;
+use xmtp_common::time::{Duration, Instant};
+
+use super::{private::EncryptedMessageStore, StorageError, XmtpDb};
+
+/// Number of free pages released per `PRAGMA incremental_vacuum` step.
+const VACUUM_PAGES_PER_STEP: i64 = 256;
+/// Number of pages merged per FTS5 `merge` step.
+const FTS_MERGE_PAGES_PER_STEP: i64 = 500;
+
+/// How much work [`EncryptedMessageStore::optimize`] is allowed to do.
+/// Each level includes the steps of the levels below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptimizeLevel {
+    /// Only run `PRAGMA optimize`, which is cheap and recommended by SQLite
+    /// to be run periodically on long-lived connections.
+    #[default]
+    Light,
+    /// Additionally run `ANALYZE` and merge FTS index segments.
+    Standard,
+    /// Additionally release free pages with `PRAGMA incremental_vacuum`.
+    Full,
+}
+
+/// A maintenance step run by [`EncryptedMessageStore::optimize`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptimizeStep {
+    PragmaOptimize,
+    Analyze,
+    FtsMerge(String),
+    IncrementalVacuum,
+}
+
+/// Summary of an [`EncryptedMessageStore::optimize`] run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// Steps that ran to completion
+    pub completed: Vec<OptimizeStep>,
+    /// Number of pages returned to the filesystem
+    pub pages_freed: i64,
+    /// `true` if the run stopped early because the time budget ran out
+    pub budget_exhausted: bool,
+}
+
+/// Size and row counts of the database, for diagnostics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StorageStats {
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    /// Number of rows in each table, by table name
+    pub row_counts: BTreeMap<String, i64>,
+}
+
+#[derive(QueryableByName, Debug)]
+struct PageSize {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    page_size: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct PageCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    page_count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct RowCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct FreelistCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    freelist_count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct AutoVacuum {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    auto_vacuum: i32,
+}
+
+#[derive(QueryableByName, Debug)]
+struct TableName {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    name: String,
+}
+
+/// `PRAGMA auto_vacuum` value for `INCREMENTAL` mode
+const AUTO_VACUUM_INCREMENTAL: i32 = 2;
+
+/// Put the database in incremental auto_vacuum mode, so that [`OptimizeLevel::Full`] can return
+/// free pages to the filesystem. The mode only applies to a database created with it, so an
+/// existing database is rebuilt once with `VACUUM`, which must run outside of a transaction.
+pub(super) fn enable_incremental_vacuum<C>(conn: &mut C) -> Result<(), StorageError>
+where
+    C: diesel::Connection<Backend = diesel::sqlite::Sqlite>
+        + diesel::connection::LoadConnection
+        + SimpleConnection,
+{
+    let AutoVacuum { auto_vacuum } =
+        sql_query("PRAGMA auto_vacuum").get_result::<AutoVacuum>(conn)?;
+    if auto_vacuum == AUTO_VACUUM_INCREMENTAL {
+        return Ok(());
+    }
+    tracing::info!(auto_vacuum, "enabling incremental auto_vacuum");
+    conn.batch_execute("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")?;
+    Ok(())
+}
+
+impl<Db> EncryptedMessageStore<Db>
+where
+    Db: XmtpDb,
+{
+    /// Run database maintenance, spending at most roughly `budget` of wall-clock time.
+    ///
+    /// The budget is checked between steps, so a single step may overrun it.
+    /// Returns which steps completed, so callers can decide whether to schedule another run.
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn optimize(
+        &self,
+        level: OptimizeLevel,
+        budget: Duration,
+    ) -> Result<OptimizeReport, StorageError> {
+        let start = Instant::now();
+        let out_of_time = || start.elapsed() >= budget;
+        let mut report = OptimizeReport::default();
+
+        self.conn()?.raw_query(|conn| {
+            conn.batch_execute("PRAGMA optimize;")?;
+            report.completed.push(OptimizeStep::PragmaOptimize);
+            if level < OptimizeLevel::Standard {
+                return Ok::<_, StorageError>(());
+            }
+
+            if out_of_time() {
+                report.budget_exhausted = true;
+                return Ok(());
+            }
+            conn.batch_execute("ANALYZE;")?;
+            report.completed.push(OptimizeStep::Analyze);
+
+            let fts_tables = sql_query(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND sql LIKE 'CREATE VIRTUAL TABLE%USING fts5%'",
+            )
+            .load::<TableName>(conn)?;
+            for TableName { name } in fts_tables {
+                if out_of_time() {
+                    report.budget_exhausted = true;
+                    return Ok(());
+                }
+                conn.batch_execute(&format!(
+                    r#"INSERT INTO "{name}"("{name}", rank) VALUES ('merge', {FTS_MERGE_PAGES_PER_STEP});"#
+                ))?;
+                report.completed.push(OptimizeStep::FtsMerge(name));
+            }
+            if level < OptimizeLevel::Full {
+                return Ok(());
+            }
+
+            let AutoVacuum { auto_vacuum } =
+                sql_query("PRAGMA auto_vacuum").get_result::<AutoVacuum>(conn)?;
+            if auto_vacuum != AUTO_VACUUM_INCREMENTAL {
+                tracing::debug!(
+                    auto_vacuum,
+                    "database is not in incremental auto_vacuum mode, skipping incremental vacuum"
+                );
+                return Ok(());
+            }
+            loop {
+                let FreelistCount { freelist_count } =
+                    sql_query("PRAGMA freelist_count").get_result::<FreelistCount>(conn)?;
+                if freelist_count == 0 {
+                    break;
+                }
+                if out_of_time() {
+                    report.budget_exhausted = true;
+                    return Ok(());
+                }
+                let step = freelist_count.min(VACUUM_PAGES_PER_STEP);
+                conn.batch_execute(&format!("PRAGMA incremental_vacuum({step});"))?;
+                report.pages_freed += step;
+            }
+            report.completed.push(OptimizeStep::IncrementalVacuum);
+            Ok(())
+        })?;
+
+        tracing::info!(
+            ?level,
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            pages_freed = report.pages_freed,
+            budget_exhausted = report.budget_exhausted,
+            "database maintenance finished"
+        );
+        Ok(report)
+    }
+
+    /// Collect the size of the database and the number of rows in each table
+    pub fn stats(&self) -> Result<StorageStats, StorageError> {
+        self.conn()?.raw_query(|conn| {
+            let PageSize { page_size } =
+                sql_query("PRAGMA page_size").get_result::<PageSize>(conn)?;
+            let PageCount { page_count } =
+                sql_query("PRAGMA page_count").get_result::<PageCount>(conn)?;
+            let FreelistCount { freelist_count } =
+                sql_query("PRAGMA freelist_count").get_result::<FreelistCount>(conn)?;
+
+            let tables = sql_query(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .load::<TableName>(conn)?;
+            let mut row_counts = BTreeMap::new();
+            for TableName { name } in tables {
+                let RowCount { count } =
+                    sql_query(format!(r#"SELECT COUNT(*) AS count FROM "{name}""#))
+                        .get_result::<RowCount>(conn)?;
+                row_counts.insert(name, count);
+            }
+
+            Ok::<_, StorageError>(StorageStats {
+                page_size,
+                page_count,
+                freelist_count,
+                row_counts,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::{EncryptedMessageStore, StorageOption},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn light_optimize_only_runs_pragma_optimize() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+
+        let report = store
+            .optimize(OptimizeLevel::Light, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(report.completed, vec![OptimizeStep::PragmaOptimize]);
+        assert!(!report.budget_exhausted);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn stats_count_rows() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        crate::storage::group::tests::generate_group(None)
+            .store(&store.conn().unwrap())
+            .unwrap();
+
+        let stats = store.stats().unwrap();
+        assert!(stats.page_count > 0);
+        assert_eq!(stats.row_counts.get("groups"), Some(&1));
+        assert_eq!(stats.row_counts.get("group_messages"), Some(&0));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn optimize_stops_when_budget_is_spent() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+
+        let report = store.optimize(OptimizeLevel::Full, Duration::ZERO).unwrap();
+        assert_eq!(report.completed, vec![OptimizeStep::PragmaOptimize]);
+        assert!(report.budget_exhausted);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn full_optimize_frees_pages() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        store
+            .conn()
+            .unwrap()
+            .raw_query(|conn| {
+                conn.batch_execute(
+                    "CREATE TABLE junk(x);
+                    WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+                    INSERT INTO junk SELECT randomblob(4096) FROM n;
+                    DROP TABLE junk;",
+                )
+            })
+            .unwrap();
+        let before = store.stats().unwrap();
+        assert!(before.freelist_count > 0);
+
+        let report = store
+            .optimize(OptimizeLevel::Full, Duration::from_secs(60))
+            .unwrap();
+        assert!(report.completed.contains(&OptimizeStep::IncrementalVacuum));
+        assert_eq!(report.pages_freed, before.freelist_count);
+        let after = store.stats().unwrap();
+        assert_eq!(after.freelist_count, 0);
+        assert!(after.page_count < before.page_count);
+    }
+}
@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::mega_group_shards::{self, dsl},
+};
+use crate::{impl_store, storage::StorageError};
+
+/// A group that holds part of the members of a mega group
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = mega_group_shards)]
+#[diesel(primary_key(group_id))]
+pub struct StoredMegaGroupShard {
+    pub group_id: Vec<u8>,
+    /// Id of the first shard of the mega group
+    pub mega_group_id: Vec<u8>,
+    pub shard_index: i32,
+    /// Sent time of the last message of this shard relayed to the other shards
+    pub relayed_until_ns: i64,
+}
+
+impl_store!(StoredMegaGroupShard, mega_group_shards);
+
+impl StoredMegaGroupShard {
+    pub fn new(mega_group_id: Vec<u8>, group_id: Vec<u8>, shard_index: i32) -> Self {
+        Self {
+            group_id,
+            mega_group_id,
+            shard_index,
+            relayed_until_ns: 0,
+        }
+    }
+}
+
+impl DbConnection {
+    /// The shards of a mega group, in order
+    pub fn mega_group_shards<MegaGroupId: AsRef<[u8]>>(
+        &self,
+        mega_group_id: MegaGroupId,
+    ) -> Result<Vec<StoredMegaGroupShard>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::mega_group_shards
+                .filter(dsl::mega_group_id.eq(mega_group_id.as_ref()))
+                .order(dsl::shard_index.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// Record that the messages of the shard `group_id` were relayed up to `relayed_until_ns`
+    pub fn set_mega_group_shard_relayed_until<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        relayed_until_ns: i64,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::mega_group_shards.find(group_id.as_ref()))
+                .set(dsl::relayed_until_ns.eq(relayed_until_ns))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_lists_shards_in_order() {
+        with_connection(|conn| {
+            let (first, second) = (generate_group(None), generate_group(None));
+            first.store(conn).unwrap();
+            second.store(conn).unwrap();
+
+            StoredMegaGroupShard::new(first.id.clone(), second.id.clone(), 1)
+                .store(conn)
+                .unwrap();
+            StoredMegaGroupShard::new(first.id.clone(), first.id.clone(), 0)
+                .store(conn)
+                .unwrap();
+
+            let shards = conn.mega_group_shards(&first.id).unwrap();
+            assert_eq!(
+                shards
+                    .iter()
+                    .map(|shard| shard.group_id.clone())
+                    .collect::<Vec<_>>(),
+                vec![first.id.clone(), second.id.clone()]
+            );
+            assert!(conn.mega_group_shards(&second.id).unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,98 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::message_annotations::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// Metadata computed for a message by a
+/// [`MessageEnricher`](crate::groups::enrichers::MessageEnricher), such as its language.
+/// Annotations are kept apart from the message, so its payload is never changed.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = message_annotations)]
+#[diesel(primary_key(message_id, key))]
+pub struct StoredMessageAnnotation {
+    pub message_id: Vec<u8>,
+    pub key: String,
+    pub value: String,
+    pub group_id: Vec<u8>,
+}
+
+impl DbConnection {
+    /// Store annotations, replacing the earlier values of the same keys
+    pub fn insert_message_annotations(
+        &self,
+        annotations: &[StoredMessageAnnotation],
+    ) -> Result<(), StorageError> {
+        if annotations.is_empty() {
+            return Ok(());
+        }
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::message_annotations)
+                .values(annotations)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The annotations of the messages `message_ids`, ordered by message and key
+    pub fn get_message_annotations<MessageId: AsRef<[u8]>>(
+        &self,
+        message_ids: &[MessageId],
+    ) -> Result<Vec<StoredMessageAnnotation>, StorageError> {
+        let message_ids: Vec<&[u8]> = message_ids.iter().map(AsRef::as_ref).collect();
+        Ok(self.raw_query(|conn| {
+            dsl::message_annotations
+                .filter(dsl::message_id.eq_any(message_ids))
+                .order((dsl::message_id.asc(), dsl::key.asc()))
+                .load(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_replaces_annotations_by_key() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let annotation = |message_id: u8, key: &str, value: &str| StoredMessageAnnotation {
+                message_id: vec![message_id; 32],
+                key: key.to_string(),
+                value: value.to_string(),
+                group_id: group.id.clone(),
+            };
+
+            conn.insert_message_annotations(&[
+                annotation(1, "language", "fr"),
+                annotation(1, "toxicity", "0.1"),
+                annotation(2, "language", "en"),
+            ])
+            .unwrap();
+            conn.insert_message_annotations(&[annotation(1, "language", "en")])
+                .unwrap();
+
+            let annotations = conn.get_message_annotations(&[vec![1; 32]]).unwrap();
+            assert_eq!(
+                annotations,
+                vec![
+                    annotation(1, "language", "en"),
+                    annotation(1, "toxicity", "0.1")
+                ]
+            );
+        })
+        .await
+    }
+}
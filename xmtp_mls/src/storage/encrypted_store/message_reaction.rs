@@ -0,0 +1,181 @@
+use diesel::prelude::*;
+use prost::Message;
+use xmtp_content_types::reaction::LegacyReaction;
+use xmtp_proto::xmtp::mls::message_contents::{
+    content_types::{ReactionAction, ReactionV2},
+    EncodedContent,
+};
+
+use super::{
+    db_connection::DbConnection,
+    group_message::{ContentType, StoredGroupMessage},
+    schema::message_reactions::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// The latest reaction of an inbox with some content on a message.
+///
+/// Adding and removing a reaction are both messages. Only the latest one of each
+/// (message, reactor, content) is kept, ordered by sent time and then message id, so that every
+/// installation ends up with the same reactions whatever order the messages arrive in.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = message_reactions)]
+#[diesel(primary_key(reference_id, reactor_inbox_id, content))]
+pub struct StoredReaction {
+    /// Id of the message reacted to
+    pub reference_id: Vec<u8>,
+    pub reactor_inbox_id: String,
+    pub content: String,
+    pub group_id: Vec<u8>,
+    /// Id of the message of the latest reaction
+    pub message_id: Vec<u8>,
+    pub reacted_at_ns: i64,
+    /// Whether the latest reaction removed the earlier ones
+    pub removed: bool,
+}
+
+impl StoredReaction {
+    /// The reaction sent in `message`, if it is a well formed reaction
+    pub fn from_message(message: &StoredGroupMessage) -> Option<Self> {
+        if message.content_type != ContentType::Reaction {
+            return None;
+        }
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice()).ok()?;
+        // Reactions before version 2 are JSON encoded
+        let (reference, content, removed) = if message.version_major >= 2 {
+            let reaction = ReactionV2::decode(content.content.as_slice()).ok()?;
+            let removed = reaction.action == ReactionAction::Removed as i32;
+            (reaction.reference, reaction.content, removed)
+        } else {
+            let reaction = LegacyReaction::decode(&content.content)?;
+            let removed = reaction.action == "removed";
+            (reaction.reference, reaction.content, removed)
+        };
+        Some(Self {
+            reference_id: hex::decode(reference).ok()?,
+            reactor_inbox_id: message.sender_inbox_id.clone(),
+            content,
+            group_id: message.group_id.clone(),
+            message_id: message.id.clone(),
+            reacted_at_ns: message.sent_at_ns,
+            removed,
+        })
+    }
+
+    fn replaces(&self, other: &StoredReaction) -> bool {
+        if self.message_id == other.message_id {
+            return true;
+        }
+        (self.reacted_at_ns, &self.message_id) > (other.reacted_at_ns, &other.message_id)
+    }
+}
+
+impl DbConnection {
+    /// Record a reaction, unless the reactor already has a later reaction with the same content
+    /// on the message. Returns whether the reaction was recorded.
+    pub fn record_reaction(&self, reaction: &StoredReaction) -> Result<bool, StorageError> {
+        let existing = self.get_reaction(
+            &reaction.reference_id,
+            &reaction.reactor_inbox_id,
+            &reaction.content,
+        )?;
+        if existing.is_some_and(|existing| !reaction.replaces(&existing)) {
+            return Ok(false);
+        }
+
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::message_reactions)
+                .values(reaction)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    /// Record the reaction sent in `message`, if it is one. Malformed reactions are skipped, so
+    /// that they do not block the rest of the group's messages.
+    pub fn record_reaction_message(
+        &self,
+        message: &StoredGroupMessage,
+    ) -> Result<(), StorageError> {
+        if message.content_type != ContentType::Reaction {
+            return Ok(());
+        }
+        match StoredReaction::from_message(message) {
+            Some(reaction) => {
+                self.record_reaction(&reaction)?;
+            }
+            None => tracing::warn!("skipping malformed reaction"),
+        }
+        Ok(())
+    }
+
+    pub fn get_reaction(
+        &self,
+        reference_id: &[u8],
+        reactor_inbox_id: &str,
+        content: &str,
+    ) -> Result<Option<StoredReaction>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::message_reactions
+                .find((reference_id, reactor_inbox_id, content))
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    /// The reactions currently on the messages `reference_ids`, leaving out removed reactions,
+    /// oldest first
+    pub fn get_reactions<ReferenceId: AsRef<[u8]>>(
+        &self,
+        reference_ids: &[ReferenceId],
+    ) -> Result<Vec<StoredReaction>, StorageError> {
+        let reference_ids: Vec<&[u8]> = reference_ids.iter().map(AsRef::as_ref).collect();
+        Ok(self.raw_query(|conn| {
+            dsl::message_reactions
+                .filter(dsl::reference_id.eq_any(reference_ids))
+                .filter(dsl::removed.eq(false))
+                .order((dsl::reacted_at_ns.asc(), dsl::message_id.asc()))
+                .load(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_reaction() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let reference_id = vec![1; 32];
+            let reaction = |message_id: u8, reacted_at_ns: i64, removed: bool| StoredReaction {
+                reference_id: reference_id.clone(),
+                reactor_inbox_id: "bola".to_string(),
+                content: "👍".to_string(),
+                group_id: group.id.clone(),
+                message_id: vec![message_id; 32],
+                reacted_at_ns,
+                removed,
+            };
+
+            assert!(conn.record_reaction(&reaction(1, 100, false)).unwrap());
+            assert_eq!(conn.get_reactions(&[&reference_id]).unwrap().len(), 1);
+            assert!(conn.record_reaction(&reaction(2, 200, true)).unwrap());
+            assert!(conn.get_reactions(&[&reference_id]).unwrap().is_empty());
+            // An add that arrives late does not undo the removal
+            assert!(!conn.record_reaction(&reaction(3, 150, false)).unwrap());
+            assert!(conn.get_reactions(&[&reference_id]).unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,211 @@
+//! Inspecting and running the pending database migrations.
+//!
+//! Most migrations only change the schema and are instant, but some rewrite or index tables
+//! and take time proportional to their size. Hosts can check [`PendingMigration`]s before
+//! opening the store and report [`MigrationProgress`] while it migrates, instead of looking
+//! hung to the user or to a watchdog.
+use diesel::{connection::LoadConnection, prelude::*, sql_query, sql_types};
+use diesel_migrations::MigrationHarness;
+
+use super::{Sqlite, MIGRATIONS};
+use crate::storage::StorageError;
+
+/// Migrations that rewrite or index a table, keyed by version, with that table.
+///
+/// `it_lists_every_expensive_migration` fails when a migration indexes or updates `groups` or
+/// `group_messages` without being listed here.
+const EXPENSIVE_MIGRATIONS: &[(&str, &str)] = &[
+    ("2024-09-09-231735", "groups"),
+    ("2024-11-13-145830", "groups"),
+    ("2024-12-20-214747", "groups"),
+    ("2025-01-03-002434", "group_messages"),
+    ("2025-01-24-101500", "group_messages"),
+    ("2025-02-03-090000", "groups"),
+    ("2025-02-08-090000", "groups"),
+    ("2025-02-21-090000", "group_messages"),
+];
+
+/// A migration that will run when the store is opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingMigration {
+    pub name: String,
+    /// Number of rows the migration rewrites or indexes, 0 for schema only migrations
+    pub estimated_rows: i64,
+}
+
+/// Reported before and after each migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationProgress {
+    pub name: String,
+    /// Position of the migration among the pending ones, starting at 0
+    pub index: usize,
+    pub total: usize,
+    pub finished: bool,
+}
+
+pub type MigrationProgressCallback = dyn Fn(&MigrationProgress) + Send + Sync;
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = sql_types::BigInt)]
+    count: i64,
+}
+
+pub(super) fn migration_plan<C>(conn: &mut C) -> Result<Vec<PendingMigration>, StorageError>
+where
+    C: MigrationHarness<Sqlite> + LoadConnection<Backend = Sqlite>,
+{
+    let pending = conn.pending_migrations(MIGRATIONS)?;
+    pending
+        .iter()
+        .map(|migration| {
+            let version = migration.name().version().to_string();
+            let estimated_rows = match EXPENSIVE_MIGRATIONS
+                .iter()
+                .find(|(expensive, _)| version.starts_with(expensive))
+            {
+                Some((_, table)) => count_rows(conn, table)?,
+                None => 0,
+            };
+            Ok(PendingMigration {
+                name: migration.name().to_string(),
+                estimated_rows,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn run_migrations<C>(
+    conn: &mut C,
+    on_progress: Option<&MigrationProgressCallback>,
+) -> Result<(), StorageError>
+where
+    C: MigrationHarness<Sqlite>,
+{
+    let Some(on_progress) = on_progress else {
+        conn.run_pending_migrations(MIGRATIONS)?;
+        return Ok(());
+    };
+    let pending = conn.pending_migrations(MIGRATIONS)?;
+    let total = pending.len();
+    for (index, migration) in pending.iter().enumerate() {
+        let mut progress = MigrationProgress {
+            name: migration.name().to_string(),
+            index,
+            total,
+            finished: false,
+        };
+        on_progress(&progress);
+        conn.run_migration(migration.as_ref())?;
+        progress.finished = true;
+        on_progress(&progress);
+    }
+    Ok(())
+}
+
+/// Rows of `table`, or 0 if it does not exist yet
+fn count_rows<C>(conn: &mut C, table: &str) -> Result<i64, StorageError>
+where
+    C: LoadConnection<Backend = Sqlite>,
+{
+    let exists =
+        sql_query("SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind::<sql_types::Text, _>(table)
+            .get_result::<Count>(conn)?
+            .count
+            > 0;
+    if !exists {
+        return Ok(0);
+    }
+    // The table names come from EXPENSIVE_MIGRATIONS
+    Ok(sql_query(format!("SELECT COUNT(*) AS count FROM {table}"))
+        .get_result::<Count>(conn)?
+        .count)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use std::sync::{Arc, Mutex};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_reports_migration_progress() {
+        with_connection(|conn| {
+            conn.raw_query(|conn| {
+                for _ in 0..3 {
+                    conn.revert_last_migration(MIGRATIONS)?;
+                }
+                let plan = migration_plan(conn)?;
+                assert_eq!(plan.len(), 3);
+
+                let reported = Arc::new(Mutex::new(vec![]));
+                let report = reported.clone();
+                let on_progress = move |progress: &MigrationProgress| {
+                    report.lock().unwrap().push(progress.clone());
+                };
+                run_migrations(conn, Some(&on_progress))?;
+                assert!(migration_plan(conn)?.is_empty());
+
+                let reported = reported.lock().unwrap();
+                assert_eq!(reported.len(), 6);
+                assert_eq!(reported[0].name, plan[0].name);
+                assert!(!reported[0].finished);
+                assert!(reported[5].finished);
+                assert_eq!(reported[5].index, 2);
+                assert_eq!(reported[5].total, 3);
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        })
+        .await
+    }
+
+    /// Tables that grow with the user's history
+    #[cfg(not(target_arch = "wasm32"))]
+    const LARGE_TABLES: &[&str] = &["groups", "group_messages"];
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn it_lists_every_expensive_migration() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let comments = regex::Regex::new(r"--[^\n]*").unwrap();
+        let mut missing = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let Ok(up) = std::fs::read_to_string(path.join("up.sql")) else {
+                continue;
+            };
+            let up = comments.replace_all(&up, "").to_lowercase();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            for table in LARGE_TABLES {
+                // Tables created by the migration are empty while it runs
+                let created = regex::Regex::new(&format!(
+                    r#"create table (if not exists )?"?{table}"?\s*\("#
+                ))
+                .unwrap();
+                let expensive = regex::Regex::new(&format!(
+                    r#"(create (unique )?index [^;]* on "?{table}"?\s*\(|update "?{table}"?\s)"#
+                ))
+                .unwrap();
+                if created.is_match(&up) || !expensive.is_match(&up) {
+                    continue;
+                }
+                let listed = EXPENSIVE_MIGRATIONS
+                    .iter()
+                    .any(|(version, _)| name.starts_with(version));
+                if !listed {
+                    missing.push(format!("{name} ({table})"));
+                }
+            }
+        }
+        assert!(
+            missing.is_empty(),
+            "add these migrations to EXPENSIVE_MIGRATIONS: {missing:?}"
+        );
+    }
+}
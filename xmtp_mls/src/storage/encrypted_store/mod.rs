@@ -11,32 +11,89 @@
 //! `diesel print-schema` or use `cargo run update-schema` which will update the files for you.
 
 pub mod association_state;
+pub mod background_migration;
+pub mod batched;
+pub mod busy;
+pub mod cache;
+pub mod cipher_options;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod collation;
+pub mod column_encryption;
+pub mod consent_policy;
 pub mod consent_record;
 mod conversation_list;
+pub mod conversation_verification;
+pub mod cursor_store;
 pub mod db_connection;
+pub mod draft;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod extensions;
+pub mod failed_commit;
+pub mod feature_flag;
 pub mod group;
+pub mod group_avatar;
+pub mod group_burst_budget;
 pub mod group_intent;
+pub mod group_invariants;
+pub mod group_key_rotation_policy;
 pub mod group_message;
+pub mod group_name;
+pub mod history_sync_log;
 pub mod identity;
 pub mod identity_update;
+pub mod installation_activity;
+pub mod installation_key_log;
+pub mod installation_retirement;
+pub mod intent_state;
+pub mod join_request;
 pub mod key_package_history;
 pub mod key_store_entry;
+#[cfg(feature = "key-store-export")]
+pub mod key_store_export;
+pub mod leave_request;
+pub mod live_location;
+pub mod local_day;
+pub mod maintenance;
+pub mod mega_group_shard;
+pub mod message_annotation;
+pub mod message_reaction;
+pub mod migrations;
+pub mod mute_rule;
 #[cfg(not(target_arch = "wasm32"))]
 pub(super) mod native;
+pub mod network_environment;
+pub mod passphrase;
+pub mod pending_welcome;
+pub mod pinned_message;
+pub mod poll;
+pub mod post_commit_action;
 pub mod refresh_state;
 pub mod schema;
 mod schema_gen;
 #[cfg(not(target_arch = "wasm32"))]
+mod snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod split_key;
+#[cfg(not(target_arch = "wasm32"))]
 mod sqlcipher_connection;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod store_paths;
+pub mod transaction_monitor;
+pub mod transaction_verification;
 pub mod user_preferences;
 pub mod wallet_addresses;
 #[cfg(target_arch = "wasm32")]
 pub(super) mod wasm;
 
+pub use self::cipher_options::CipherOptions;
 pub use self::db_connection::DbConnection;
+pub use self::migrations::{MigrationProgress, MigrationProgressCallback, PendingMigration};
+pub use self::passphrase::{PassphraseKdfParams, PassphraseKey};
 #[cfg(not(target_arch = "wasm32"))]
 pub use diesel::sqlite::{Sqlite, SqliteConnection};
 #[cfg(not(target_arch = "wasm32"))]
+pub use extensions::{SqliteExtension, SqliteExtensions};
+#[cfg(not(target_arch = "wasm32"))]
 pub use native::RawDbConnection;
 #[cfg(not(target_arch = "wasm32"))]
 pub use sqlcipher_connection::EncryptedConnection;
@@ -57,6 +114,7 @@ use diesel::{
     sql_query,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use transaction_monitor::TransactionTimer;
 use xmtp_common::{retry_async, Retry, RetryableError};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/");
@@ -74,7 +132,57 @@ struct SqliteVersion {
 pub enum StorageOption {
     #[default]
     Ephemeral,
+    /// A database at the given path. Opens with the SQLCipher parameters it was created with,
+    /// or the default parameters for a new database.
     Persistent(String),
+    /// A database at the given path, with explicit SQLCipher parameters.
+    /// Opening fails if the database was created with different parameters.
+    PersistentWithCipher(String, CipherOptions),
+    /// The database of the wrapped option, with its sensitive columns encrypted, see
+    /// [`StorageOption::with_column_encryption`].
+    ColumnEncrypted(
+        Box<StorageOption>,
+        std::sync::Arc<column_encryption::ColumnCipher>,
+    ),
+}
+
+impl StorageOption {
+    pub(super) fn path(&self) -> Option<&String> {
+        use StorageOption::*;
+        match self {
+            Persistent(path) | PersistentWithCipher(path, _) => Some(path),
+            Ephemeral => None,
+            ColumnEncrypted(inner, _) => inner.path(),
+        }
+    }
+
+    /// SQLCipher parameters requested by the caller, if any
+    pub fn cipher_options(&self) -> Option<&CipherOptions> {
+        match self {
+            StorageOption::PersistentWithCipher(_, cipher) => Some(cipher),
+            StorageOption::ColumnEncrypted(inner, _) => inner.cipher_options(),
+            _ => None,
+        }
+    }
+
+    /// Encrypt the sensitive columns of the database with `key` before they are written,
+    /// see [`column_encryption`]. For databases that SQLCipher does not encrypt.
+    ///
+    /// Values written before are encrypted when the store is opened. From then on, the store
+    /// must be opened with column encryption, with the same key.
+    pub fn with_column_encryption(self, key: EncryptionKey) -> Self {
+        StorageOption::ColumnEncrypted(
+            Box::new(self),
+            std::sync::Arc::new(column_encryption::ColumnCipher::new(key)),
+        )
+    }
+
+    fn column_cipher(&self) -> Option<&std::sync::Arc<column_encryption::ColumnCipher>> {
+        match self {
+            StorageOption::ColumnEncrypted(_, cipher) => Some(cipher),
+            _ => None,
+        }
+    }
 }
 
 #[allow(async_fn_in_trait)]
@@ -110,12 +218,97 @@ impl EncryptedMessageStore {
     /// Created a new store
     #[tracing::instrument(level = "trace", skip_all)]
     pub async fn new(opts: StorageOption, enc_key: EncryptionKey) -> Result<Self, StorageError> {
-        Self::new_database(opts, Some(enc_key))
+        Self::new_database(opts, Some(enc_key), None, SqliteExtensions::default())
+    }
+
+    /// Like [`Self::new`], loading `extensions` into every connection to the database
+    pub async fn new_with_extensions(
+        opts: StorageOption,
+        enc_key: EncryptionKey,
+        extensions: SqliteExtensions,
+    ) -> Result<Self, StorageError> {
+        Self::new_database(opts, Some(enc_key), None, extensions)
     }
 
     /// Create a new, unencrypted database
     pub async fn new_unencrypted(opts: StorageOption) -> Result<Self, StorageError> {
-        Self::new_database(opts, None)
+        Self::new_database(opts, None, None, SqliteExtensions::default())
+    }
+
+    /// Derive the encryption key of the database at `db_path` from a passphrase.
+    ///
+    /// The salt and Argon2id parameters are read from the file next to the database. For a
+    /// new database a random salt is generated and persisted with `params`, or the default
+    /// parameters. `params` must match the persisted parameters if given.
+    ///
+    /// The salt file must be kept with the database: the key cannot be derived without it.
+    pub fn key_from_passphrase(
+        db_path: &str,
+        passphrase: &str,
+        params: Option<PassphraseKdfParams>,
+    ) -> Result<EncryptionKey, StorageError> {
+        let kdf = match (
+            EncryptedConnection::persisted_passphrase_kdf(db_path)?,
+            params,
+        ) {
+            (Some(persisted), Some(params)) if persisted.params != params => {
+                return Err(super::SqlCipherError::KeyDerivation(
+                    "parameters differ from the ones the key was derived with".into(),
+                )
+                .into());
+            }
+            (Some(persisted), _) => persisted,
+            (None, params) => {
+                let kdf = passphrase::PassphraseKdf::generate(params.unwrap_or_default());
+                EncryptedConnection::persist_passphrase_kdf(db_path, &kdf)?;
+                kdf
+            }
+        };
+        Ok(kdf.derive_key(passphrase)?)
+    }
+
+    /// Like [`Self::new`], calling `on_progress` before and after each pending migration
+    pub async fn new_with_migration_progress(
+        opts: StorageOption,
+        enc_key: EncryptionKey,
+        on_progress: &MigrationProgressCallback,
+    ) -> Result<Self, StorageError> {
+        Self::new_database(
+            opts,
+            Some(enc_key),
+            Some(on_progress),
+            SqliteExtensions::default(),
+        )
+    }
+
+    /// The migrations that opening the database would run, without running them
+    pub fn migration_plan(
+        opts: StorageOption,
+        enc_key: EncryptionKey,
+    ) -> Result<Vec<PendingMigration>, StorageError> {
+        let db = native::NativeDb::new(&opts, Some(enc_key), SqliteExtensions::default())?;
+        let store = Self {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
+        store.pending_migrations()
+    }
+
+    /// Move an existing database with an encrypted header to a plaintext header of
+    /// `plaintext_header_size` bytes, so that iOS can keep it open while the app is in the
+    /// background. Databases created with a plaintext header do not need this.
+    ///
+    /// Must be called before the store is opened.
+    pub fn migrate_to_plaintext_header(
+        path: &str,
+        enc_key: EncryptionKey,
+        plaintext_header_size: u32,
+    ) -> Result<(), StorageError> {
+        EncryptedConnection::migrate_to_plaintext_header(path, enc_key, plaintext_header_size)
     }
 
     /// This function is private so that an unencrypted database cannot be created by accident
@@ -123,11 +316,23 @@ impl EncryptedMessageStore {
     fn new_database(
         opts: StorageOption,
         enc_key: Option<EncryptionKey>,
+        on_progress: Option<&MigrationProgressCallback>,
+        extensions: SqliteExtensions,
     ) -> Result<Self, StorageError> {
+        if let Some(path) = opts.path() {
+            Self::apply_pending_restore(path, enc_key)?;
+        }
         tracing::info!("Setting up DB connection pool");
-        let db = native::NativeDb::new(&opts, enc_key)?;
-        let mut store = Self { db, opts };
-        store.init_db()?;
+        let db = native::NativeDb::new(&opts, enc_key, extensions)?;
+        let mut store = Self {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
+        store.init_db(on_progress)?;
         Ok(store)
     }
 }
@@ -138,38 +343,79 @@ pub type EncryptedMessageStore = self::private::EncryptedMessageStore<wasm::Wasm
 #[cfg(target_arch = "wasm32")]
 impl EncryptedMessageStore {
     pub async fn new(opts: StorageOption, enc_key: EncryptionKey) -> Result<Self, StorageError> {
-        Self::new_database(opts, Some(enc_key)).await
+        Self::new_database(opts, Some(enc_key), None).await
     }
 
     pub async fn new_unencrypted(opts: StorageOption) -> Result<Self, StorageError> {
-        Self::new_database(opts, None).await
+        Self::new_database(opts, None, None).await
+    }
+
+    /// Like [`Self::new`], calling `on_progress` before and after each pending migration
+    pub async fn new_with_migration_progress(
+        opts: StorageOption,
+        enc_key: EncryptionKey,
+        on_progress: &MigrationProgressCallback,
+    ) -> Result<Self, StorageError> {
+        Self::new_database(opts, Some(enc_key), Some(on_progress)).await
+    }
+
+    /// The migrations that opening the database would run, without running them
+    pub async fn migration_plan(
+        opts: StorageOption,
+        _enc_key: EncryptionKey,
+    ) -> Result<Vec<PendingMigration>, StorageError> {
+        let db = wasm::WasmDb::new(&opts).await?;
+        let store = Self {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
+        store.pending_migrations()
     }
 
     /// This function is private so that an unencrypted database cannot be created by accident
     async fn new_database(
         opts: StorageOption,
         _enc_key: Option<EncryptionKey>,
+        on_progress: Option<&MigrationProgressCallback>,
     ) -> Result<Self, StorageError> {
         let db = wasm::WasmDb::new(&opts).await?;
-        let mut this = Self { db, opts };
-        this.init_db()?;
+        let mut this = Self {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
+        this.init_db(on_progress)?;
         Ok(this)
     }
 }
 
 /// Shared Code between WebAssembly and Native using the `XmtpDb` trait
 pub mod private {
-    use crate::storage::xmtp_openmls_provider::XmtpOpenMlsProviderPrivate;
+    use crate::storage::{
+        key_store_backend::KeyStoreBackend, xmtp_openmls_provider::XmtpOpenMlsProviderPrivate,
+    };
 
     use super::*;
     use diesel::connection::SimpleConnection;
     use diesel_migrations::MigrationHarness;
+    use std::sync::Arc;
 
     #[derive(Clone, Debug)]
     /// Manages a Sqlite db for persisting messages and other objects.
     pub struct EncryptedMessageStore<Db> {
         pub(super) opts: StorageOption,
         pub(super) db: Db,
+        pub(super) cache: Option<Arc<cache::StoreCache>>,
+        pub(super) cursor_store: Option<Arc<dyn cursor_store::CursorStore>>,
+        pub(super) key_store_backend: Option<Arc<dyn KeyStoreBackend>>,
+        pub(super) invariant_checker: Option<Arc<group_invariants::GroupInvariantChecker>>,
     }
 
     impl<Db> EncryptedMessageStore<Db>
@@ -177,12 +423,20 @@ pub mod private {
         Db: XmtpDb,
     {
         #[tracing::instrument(level = "trace", skip_all)]
-        pub(super) fn init_db(&mut self) -> Result<(), StorageError> {
+        pub(super) fn init_db(
+            &mut self,
+            on_progress: Option<&MigrationProgressCallback>,
+        ) -> Result<(), StorageError> {
             self.db.validate(&self.opts)?;
             self.db.conn()?.raw_query(|conn| {
                 conn.batch_execute("PRAGMA journal_mode = WAL;")?;
+                maintenance::enable_incremental_vacuum(conn)?;
                 tracing::info!("Running DB migrations");
-                conn.run_pending_migrations(MIGRATIONS)?;
+                migrations::run_migrations(conn, on_progress)?;
+                column_encryption::seal_existing_columns(
+                    conn,
+                    self.opts.column_cipher().map(|cipher| &**cipher),
+                )?;
 
                 let sqlite_version =
                     sql_query("SELECT sqlite_version() AS version").load::<SqliteVersion>(conn)?;
@@ -195,6 +449,11 @@ pub mod private {
             Ok::<_, StorageError>(())
         }
 
+        pub(super) fn pending_migrations(&self) -> Result<Vec<PendingMigration>, StorageError> {
+            self.db.validate(&self.opts)?;
+            self.db.conn()?.raw_query(migrations::migration_plan)
+        }
+
         pub fn mls_provider(
             &self,
         ) -> Result<XmtpOpenMlsProviderPrivate<Db, Db::Connection>, StorageError> {
@@ -206,7 +465,50 @@ pub mod private {
         pub fn conn(
             &self,
         ) -> Result<DbConnectionPrivate<<Db as XmtpDb>::Connection>, StorageError> {
-            self.db.conn()
+            Ok(self
+                .db
+                .conn()?
+                .with_cache(self.cache.clone())
+                .with_cursor_store(self.cursor_store.clone())
+                .with_key_store_backend(self.key_store_backend.clone())
+                .with_invariant_checker(self.invariant_checker.clone())
+                .with_column_cipher(self.opts.column_cipher().cloned()))
+        }
+
+        /// Keep identity, groups and consent records in an in-memory cache, shared by every
+        /// connection pulled from this store, see [`cache`].
+        pub fn with_cache(mut self) -> Self {
+            self.cache = Some(Default::default());
+            self
+        }
+
+        /// Share the cursors of group messages with the other replicas of this installation
+        /// through an external store, see [`cursor_store`].
+        pub fn with_cursor_store(
+            mut self,
+            cursor_store: Arc<dyn cursor_store::CursorStore>,
+        ) -> Self {
+            self.cursor_store = Some(cursor_store);
+            self
+        }
+
+        /// Keep the private keys of the installation in a secure module of the host, see
+        /// [`key_store_backend`](crate::storage::key_store_backend).
+        ///
+        /// Must be attached before the identity is created, and every time the store is opened.
+        pub fn with_key_store_backend(
+            mut self,
+            key_store_backend: Arc<dyn KeyStoreBackend>,
+        ) -> Self {
+            self.key_store_backend = Some(key_store_backend);
+            self
+        }
+
+        /// Check the group invariants after every group sync, logging the violations found.
+        /// Meant for debugging, since every check reads the cursors and intents of all groups.
+        pub fn with_invariant_checks(mut self) -> Self {
+            self.invariant_checker = Some(Default::default());
+            self
         }
 
         /// Release connection to the database, closing it
@@ -374,7 +676,11 @@ where
     where
         F: Copy + FnMut(&'a XmtpOpenMlsProviderPrivate<Db, <Db as XmtpDb>::Connection>) -> Fut,
         Fut: futures::Future<Output = Result<T, E>>,
-        E: From<diesel::result::Error> + From<StorageError> + RetryableError,
+        E: From<diesel::result::Error>
+            + From<StorageError>
+            + RetryableError
+            + std::error::Error
+            + 'static,
         Db: 'a;
 }
 
@@ -396,6 +702,7 @@ where
     ///     provider.conn().db_operation()?;
     /// })
     /// ```
+    #[track_caller]
     fn transaction<T, F, E>(&self, fun: F) -> Result<T, E>
     where
         F: FnOnce(&XmtpOpenMlsProviderPrivate<Db, <Db as XmtpDb>::Connection>) -> Result<T, E>,
@@ -407,18 +714,27 @@ where
             let mut connection = connection.inner_mut_ref();
             <Db as XmtpDb>::TransactionManager::begin_transaction(&mut *connection)?;
         }
+        let timer = TransactionTimer::begin(transaction_monitor::origin(Some(
+            std::panic::Location::caller(),
+        )));
 
         let conn = self.conn_ref();
+        let mark = conn.after_commit_mark();
 
         match fun(self) {
             Ok(value) => {
-                conn.raw_query(|conn| {
+                let committed = conn.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::commit_transaction(&mut *conn)
-                })?;
+                });
+                conn.finish_after_commit(mark, committed.is_ok());
+                committed?;
+                timer.end(true);
                 tracing::debug!("Transaction being committed");
                 Ok(value)
             }
             Err(err) => {
+                conn.finish_after_commit(mark, false);
+                timer.end(false);
                 tracing::debug!("Transaction being rolled back");
                 match conn.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::rollback_transaction(&mut *conn)
@@ -458,6 +774,8 @@ where
             let mut connection = connection.inner_mut_ref();
             <Db as XmtpDb>::TransactionManager::begin_transaction(&mut *connection)?;
         }
+        let timer = TransactionTimer::begin(transaction_monitor::origin(None));
+        let mark = self.conn_ref().after_commit_mark();
 
         // ensuring we have only one strong reference
         let result = fun(self).await;
@@ -468,13 +786,19 @@ where
         let local_connection = DbConnectionPrivate::from_arc_mutex(local_connection);
         match result {
             Ok(value) => {
-                local_connection.raw_query(|conn| {
+                let committed = local_connection.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::commit_transaction(&mut *conn)
-                })?;
+                });
+                // Callbacks are registered on the connection of the provider
+                self.conn_ref().finish_after_commit(mark, committed.is_ok());
+                committed?;
+                timer.end(true);
                 tracing::debug!("Transaction async being committed");
                 Ok(value)
             }
             Err(err) => {
+                self.conn_ref().finish_after_commit(mark, false);
+                timer.end(false);
                 tracing::debug!("Transaction async being rolled back");
                 match local_connection.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::rollback_transaction(&mut *conn)
@@ -495,11 +819,16 @@ where
     where
         F: Copy + FnMut(&'a XmtpOpenMlsProviderPrivate<Db, <Db as XmtpDb>::Connection>) -> Fut,
         Fut: futures::Future<Output = Result<T, E>>,
-        E: From<diesel::result::Error> + From<StorageError> + RetryableError,
+        E: From<diesel::result::Error>
+            + From<StorageError>
+            + RetryableError
+            + std::error::Error
+            + 'static,
     {
+        // a busy database is waited on before the retry policy counts an attempt
         retry_async!(
             retry.unwrap_or_default(),
-            (async { self.transaction_async(fun).await })
+            (async { busy::retry_while_busy(|| self.transaction_async(fun)).await })
         )
     }
 }
@@ -518,6 +847,7 @@ pub(crate) mod tests {
         storage::{
             group::{GroupMembershipState, StoredGroup},
             identity::StoredIdentity,
+            SqlCipherError,
         },
         Fetch, Store, StreamHandle as _, XmtpOpenMlsProvider,
     };
@@ -630,12 +960,23 @@ pub(crate) mod tests {
         let opts = StorageOption::Persistent(db_path.clone());
 
         #[cfg(not(target_arch = "wasm32"))]
-        let db =
-            native::NativeDb::new(&opts, Some(EncryptedMessageStore::generate_enc_key())).unwrap();
+        let db = native::NativeDb::new(
+            &opts,
+            Some(EncryptedMessageStore::generate_enc_key()),
+            Default::default(),
+        )
+        .unwrap();
         #[cfg(target_arch = "wasm32")]
         let db = wasm::WasmDb::new(&opts).await.unwrap();
 
-        let store = EncryptedMessageStore { db, opts };
+        let store = EncryptedMessageStore {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
         store.db.validate(&store.opts).unwrap();
 
         store
@@ -728,8 +1069,11 @@ pub(crate) mod tests {
 
         // Ensure it fails
         assert!(
-            matches!(res.err(), Some(StorageError::SqlCipherKeyIncorrect)),
-            "Expected SqlCipherKeyIncorrect error"
+            matches!(
+                res.err(),
+                Some(StorageError::SqlCipher(SqlCipherError::KeyIncorrect))
+            ),
+            "Expected SqlCipherError::KeyIncorrect error"
         );
         EncryptedMessageStore::remove_db_files(db_path)
     }
@@ -759,6 +1103,55 @@ pub(crate) mod tests {
         EncryptedMessageStore::remove_db_files(db_path)
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn after_commit_callbacks_wait_for_the_commit() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let store = EncryptedMessageStore::new(
+            StorageOption::Ephemeral,
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        let provider = XmtpOpenMlsProvider::new(store.conn().unwrap());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let count = |runs: &Arc<AtomicUsize>| {
+            let runs = runs.clone();
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+
+        // dropped on rollback
+        let _ = provider.transaction(|provider| {
+            provider.conn_ref().after_commit(count(&runs));
+            Err::<(), _>(StorageError::PoolNeedsConnection)
+        });
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        // run once the outermost transaction commits
+        provider
+            .transaction(|provider| {
+                provider
+                    .transaction(|provider| {
+                        provider.conn_ref().after_commit(count(&runs));
+                        Ok::<_, StorageError>(())
+                    })
+                    .unwrap();
+                assert_eq!(runs.load(Ordering::SeqCst), 0);
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // run right away outside of a transaction
+        provider.conn_ref().after_commit(count(&runs));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
     // get two connections
     // start a transaction
     // try to write with second connection
@@ -0,0 +1,400 @@
+use diesel::{dsl::sql, expression::SqlLiteral, prelude::*, sql_types::Bool};
+use prost::Message;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{
+    consent_record::{ConsentState, ConsentType},
+    db_connection::DbConnection,
+    group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+    schema::{
+        filtered_messages::{self, dsl as filtered_dsl},
+        group_messages::dsl as messages_dsl,
+        user_preferences::dsl as preferences_dsl,
+    },
+    user_preferences::{NewStoredUserPreferences, StoredUserPreferences},
+};
+use crate::storage::StorageError;
+
+/// A rule that filters incoming text messages that match it.
+///
+/// Rules are kept in the user preferences and synced to the other installations. Filtered
+/// messages are still stored, but are flagged in [`FilteredMessage`] so that they are left out of
+/// unread counts and notifications.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MuteRule {
+    /// Random id, the same on every installation
+    pub id: String,
+    /// A keyword, matched anywhere in the text ignoring case, or a regular expression
+    pub pattern: String,
+    pub is_regex: bool,
+    /// The hex-encoded id of the conversation the rule applies to, or `None` for all
+    /// conversations
+    pub group_id: Option<String>,
+    pub created_at_ns: i64,
+}
+
+impl MuteRule {
+    /// A new rule. Regular expressions must already be valid.
+    pub fn new(pattern: &str, is_regex: bool, group_id: Option<&[u8]>) -> Self {
+        Self {
+            id: xmtp_common::rand_string::<16>(),
+            pattern: pattern.to_string(),
+            is_regex,
+            group_id: group_id.map(hex::encode),
+            created_at_ns: now_ns(),
+        }
+    }
+
+    /// The id of the conversation the rule applies to
+    pub fn decoded_group_id(&self) -> Option<Vec<u8>> {
+        self.group_id.as_ref().and_then(|id| hex::decode(id).ok())
+    }
+}
+
+/// An incoming message that matched a mute rule when it was stored
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = filtered_messages)]
+#[diesel(primary_key(message_id))]
+pub struct FilteredMessage {
+    pub message_id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    /// The first rule that matched
+    pub rule_id: String,
+}
+
+/// A mute rule ready to be matched against messages
+struct MuteMatcher {
+    rule: MuteRule,
+    regex: Option<Regex>,
+}
+
+impl MuteMatcher {
+    fn new(rule: MuteRule) -> Option<Self> {
+        let regex = if rule.is_regex {
+            // Rules are checked when they are added, so this only skips rules stored by a
+            // newer version with a different regex syntax
+            Some(Regex::new(&rule.pattern).ok()?)
+        } else {
+            None
+        };
+        Some(Self { rule, regex })
+    }
+
+    fn applies_to(&self, group_id: &[u8]) -> bool {
+        self.rule
+            .group_id
+            .as_ref()
+            .is_none_or(|rule_group_id| *rule_group_id == hex::encode(group_id))
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(text),
+            None => text
+                .to_lowercase()
+                .contains(&self.rule.pattern.to_lowercase()),
+        }
+    }
+}
+
+/// The text of `message`, if it is a text message
+fn message_text(message: &StoredGroupMessage) -> Option<String> {
+    if message.content_type != ContentType::Text {
+        return None;
+    }
+    let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice()).ok()?;
+    TextCodec::decode(content).ok()
+}
+
+/// Leaves out the messages of denied conversations and of denied inboxes, which never count as
+/// unread. Expired records read as unknown, as in [`DbConnection::get_consent_record`].
+fn not_denied() -> SqlLiteral<Bool> {
+    let denied = |entity_type: ConsentType| {
+        format!(
+            "SELECT entity FROM consent_records WHERE entity_type = {} AND state = {} \
+             AND (expires_at_ns IS NULL OR expires_at_ns > {})",
+            entity_type as i32,
+            ConsentState::Denied as i32,
+            now_ns()
+        )
+    };
+    sql::<Bool>(&format!(
+        "lower(hex(group_messages.group_id)) NOT IN ({}) \
+         AND group_messages.sender_inbox_id NOT IN ({})",
+        denied(ConsentType::ConversationId),
+        denied(ConsentType::InboxId)
+    ))
+}
+
+impl DbConnection {
+    /// All mute rules, oldest first
+    pub fn mute_rules(&self) -> Result<Vec<MuteRule>, StorageError> {
+        match StoredUserPreferences::load(self)?.mute_rules {
+            Some(rules) => serde_json::from_str(&rules)
+                .map_err(|e| StorageError::Deserialization(e.to_string())),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Add a mute rule, returning `false` if a rule with the same id was already there
+    pub fn add_mute_rule(&self, rule: &MuteRule) -> Result<bool, StorageError> {
+        let mut rules = self.mute_rules()?;
+        if rules.iter().any(|r| r.id == rule.id) {
+            return Ok(false);
+        }
+        rules.push(rule.clone());
+        self.set_mute_rules(&rules)?;
+        Ok(true)
+    }
+
+    /// Remove a mute rule, returning `true` if there was one. Messages it already filtered stay
+    /// filtered.
+    pub fn remove_mute_rule(&self, id: &str) -> Result<bool, StorageError> {
+        let mut rules = self.mute_rules()?;
+        let count = rules.len();
+        rules.retain(|rule| rule.id != id);
+        if rules.len() == count {
+            return Ok(false);
+        }
+        self.set_mute_rules(&rules)?;
+        Ok(true)
+    }
+
+    fn set_mute_rules(&self, rules: &[MuteRule]) -> Result<(), StorageError> {
+        let mut preferences = StoredUserPreferences::load(self)?;
+        preferences.mute_rules = Some(
+            serde_json::to_string(rules).map_err(|e| StorageError::Serialization(e.to_string()))?,
+        );
+        let to_insert: NewStoredUserPreferences = (&preferences).into();
+        self.raw_query(|conn| {
+            diesel::insert_into(preferences_dsl::user_preferences)
+                .values(to_insert)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Flag `message` as filtered if it is an incoming text message that matches a mute rule.
+    /// Returns whether it was filtered.
+    pub fn filter_message(&self, message: &StoredGroupMessage) -> Result<bool, StorageError> {
+        if message.sent_by_me || message.kind != GroupMessageKind::Application {
+            return Ok(false);
+        }
+        let Some(text) = message_text(message) else {
+            return Ok(false);
+        };
+        let matched = self
+            .mute_rules()?
+            .into_iter()
+            .filter_map(MuteMatcher::new)
+            .find(|matcher| matcher.applies_to(&message.group_id) && matcher.matches(&text));
+        let Some(matcher) = matched else {
+            return Ok(false);
+        };
+
+        let filtered = FilteredMessage {
+            message_id: message.id.clone(),
+            group_id: message.group_id.clone(),
+            rule_id: matcher.rule.id,
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(filtered_dsl::filtered_messages)
+                .values(&filtered)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    pub fn is_message_filtered(&self, message_id: &[u8]) -> Result<bool, StorageError> {
+        let count: i64 = self.raw_query(|conn| {
+            filtered_dsl::filtered_messages
+                .filter(filtered_dsl::message_id.eq(message_id))
+                .count()
+                .get_result(conn)
+        })?;
+        Ok(count > 0)
+    }
+
+    /// Whether the conversation or the sender of a message is denied
+    pub fn is_consent_denied(
+        &self,
+        group_id: &[u8],
+        sender_inbox_id: &str,
+    ) -> Result<bool, StorageError> {
+        for (entity, entity_type) in [
+            (hex::encode(group_id), ConsentType::ConversationId),
+            (sender_inbox_id.to_string(), ConsentType::InboxId),
+        ] {
+            let record = self.get_consent_record(entity, entity_type)?;
+            if record.is_some_and(|record| record.state == ConsentState::Denied) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Number of readable application messages of a group sent by other inboxes after
+    /// `read_at_ns`, leaving out filtered messages and denied conversations or senders
+    pub fn count_unread_messages(
+        &self,
+        group_id: &[u8],
+        read_at_ns: i64,
+    ) -> Result<i64, StorageError> {
+        Ok(self.raw_query(|conn| {
+            messages_dsl::group_messages
+                .filter(messages_dsl::group_id.eq(group_id))
+                .filter(messages_dsl::kind.eq(GroupMessageKind::Application))
+                .filter(messages_dsl::sent_by_me.eq(false))
+                .filter(messages_dsl::sent_at_ns.gt(read_at_ns))
+                .filter(messages_dsl::content_type.eq_any(ContentType::READABLE))
+                .filter(not_denied())
+                .filter(
+                    messages_dsl::id
+                        .ne_all(filtered_dsl::filtered_messages.select(filtered_dsl::message_id)),
+                )
+                .count()
+                .get_result(conn)
+        })?)
+    }
+
+    /// Readable application messages of all groups sent by other inboxes after `since_ns`,
+    /// leaving out filtered messages and denied conversations or senders, grouped by group and
+    /// oldest first within a group
+    pub fn unread_messages_since(
+        &self,
+        since_ns: i64,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let messages = self.raw_query(|conn| {
+            messages_dsl::group_messages
+                .filter(messages_dsl::kind.eq(GroupMessageKind::Application))
+                .filter(messages_dsl::sent_by_me.eq(false))
+                .filter(messages_dsl::sent_at_ns.gt(since_ns))
+                .filter(messages_dsl::content_type.eq_any(ContentType::READABLE))
+                .filter(not_denied())
+                .filter(
+                    messages_dsl::id
+                        .ne_all(filtered_dsl::filtered_messages.select(filtered_dsl::message_id)),
+                )
+                .order((messages_dsl::group_id.asc(), messages_dsl::sent_at_ns.asc()))
+                .load(conn)
+        })?;
+        self.open_messages(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::encoded_content_to_bytes;
+
+    fn text_message(group_id: &[u8], text: &str) -> StoredGroupMessage {
+        let content = TextCodec::encode(text.to_string()).unwrap();
+        let mut message =
+            generate_message(None, Some(group_id), Some(100), Some(ContentType::Text));
+        message.decrypted_message_bytes = encoded_content_to_bytes(content);
+        message
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_filters_matching_messages() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let other_group = generate_group(None);
+            other_group.store(conn).unwrap();
+            let rule = MuteRule::new("Spoiler", false, None);
+            assert!(conn.add_mute_rule(&rule).unwrap());
+            assert!(!conn.add_mute_rule(&rule).unwrap());
+            conn.add_mute_rule(&MuteRule::new(r"^buy \d+", true, Some(&group.id)))
+                .unwrap();
+
+            let spoiler = text_message(&group.id, "big spoiler ahead");
+            let offer = text_message(&group.id, "buy 2 now");
+            let other_offer = text_message(&other_group.id, "buy 2 now");
+            let hello = text_message(&group.id, "hello");
+            for message in [&spoiler, &offer, &other_offer, &hello] {
+                message.store(conn).unwrap();
+            }
+
+            assert!(conn.filter_message(&spoiler).unwrap());
+            assert!(conn.filter_message(&offer).unwrap());
+            // The regex rule only applies to the first group
+            assert!(!conn.filter_message(&other_offer).unwrap());
+            assert!(!conn.filter_message(&hello).unwrap());
+            assert!(conn.is_message_filtered(&spoiler.id).unwrap());
+            assert_eq!(conn.count_unread_messages(&group.id, 0).unwrap(), 1);
+
+            // Rules are kept with the other preferences
+            assert_eq!(conn.mute_rules().unwrap().len(), 2);
+            assert!(conn.remove_mute_rule(&rule.id).unwrap());
+            assert!(!conn.remove_mute_rule(&rule.id).unwrap());
+            assert_eq!(conn.mute_rules().unwrap().len(), 1);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_leaves_out_unreadable_and_denied_messages() {
+        use crate::storage::consent_record::StoredConsentRecord;
+
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let denied_group = generate_group(None);
+            denied_group.store(conn).unwrap();
+
+            text_message(&group.id, "hello").store(conn).unwrap();
+            generate_message(
+                None,
+                Some(&group.id),
+                Some(100),
+                Some(ContentType::ReadReceipt),
+            )
+            .store(conn)
+            .unwrap();
+            let mut spam = text_message(&group.id, "spam");
+            spam.sender_inbox_id = "spammer".to_string();
+            spam.store(conn).unwrap();
+            text_message(&denied_group.id, "hello").store(conn).unwrap();
+
+            conn.insert_or_replace_consent_records(&[
+                StoredConsentRecord::new(
+                    ConsentType::ConversationId,
+                    ConsentState::Denied,
+                    hex::encode(&denied_group.id),
+                ),
+                StoredConsentRecord::new(
+                    ConsentType::InboxId,
+                    ConsentState::Denied,
+                    "spammer".to_string(),
+                ),
+            ])
+            .unwrap();
+
+            assert_eq!(conn.count_unread_messages(&group.id, 0).unwrap(), 1);
+            assert_eq!(conn.count_unread_messages(&denied_group.id, 0).unwrap(), 0);
+            let unread = conn.unread_messages_since(0).unwrap();
+            assert_eq!(unread.len(), 1);
+            assert_eq!(unread[0].group_id, group.id);
+            assert!(conn.is_consent_denied(&group.id, "spammer").unwrap());
+            assert!(conn.is_consent_denied(&denied_group.id, "0x0").unwrap());
+            assert!(!conn.is_consent_denied(&group.id, "0x0").unwrap());
+        })
+        .await
+    }
+}
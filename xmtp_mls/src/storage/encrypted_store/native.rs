@@ -14,7 +14,10 @@ pub type ConnectionManager = r2d2::ConnectionManager<SqliteConnection>;
 pub type Pool = r2d2::Pool<ConnectionManager>;
 pub type RawDbConnection = PooledConnection<ConnectionManager>;
 
-use super::{sqlcipher_connection::EncryptedConnection, EncryptionKey, StorageOption, XmtpDb};
+use super::{
+    busy::busy_timeout_pragma, collation::UnicodeFolding, extensions::SqliteExtensions,
+    sqlcipher_connection::EncryptedConnection, EncryptionKey, StorageOption, XmtpDb,
+};
 
 trait XmtpConnection:
     ValidatedConnection
@@ -55,7 +58,7 @@ impl<T: CustomizeConnection<SqliteConnection, r2d2::Error>>
 
 /// An Unencrypted Connection
 /// Creates a Sqlite3 Database/Connection in WAL mode.
-/// Sets `busy_timeout` on each connection.
+/// Sets `busy_timeout` on each connection, see [`busy`](super::busy).
 /// _*NOTE:*_Unencrypted Connections are not validated and mostly meant for testing.
 /// It is not recommended to use an unencrypted connection in production.
 #[derive(Clone, Debug)]
@@ -64,27 +67,43 @@ impl ValidatedConnection for UnencryptedConnection {}
 
 impl CustomizeConnection<SqliteConnection, r2d2::Error> for UnencryptedConnection {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
-        conn.batch_execute("PRAGMA busy_timeout = 5000;")
+        conn.batch_execute(&busy_timeout_pragma())
             .map_err(r2d2::Error::QueryError)?;
         Ok(())
     }
 }
 
-impl StorageOption {
-    // create a completely new standalone connection
-    pub(super) fn conn(&self) -> Result<SqliteConnection, diesel::ConnectionError> {
-        use StorageOption::*;
-        match self {
-            Persistent(path) => SqliteConnection::establish(path),
-            Ephemeral => SqliteConnection::establish(":memory:"),
+/// Customizes connections like `inner`, then loads the host's extensions
+#[derive(Clone, Debug)]
+struct ExtendedConnection {
+    inner: Option<Box<dyn XmtpConnection>>,
+    extensions: SqliteExtensions,
+}
+
+impl ValidatedConnection for ExtendedConnection {
+    fn validate(&self, opts: &StorageOption) -> Result<(), StorageError> {
+        match &self.inner {
+            Some(inner) => inner.validate(opts),
+            None => Ok(()),
+        }
+    }
+}
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ExtendedConnection {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        if let Some(inner) = &self.inner {
+            inner.on_acquire(conn)?;
         }
+        self.extensions.load(conn)
     }
+}
 
-    pub(super) fn path(&self) -> Option<&String> {
-        use StorageOption::*;
-        match self {
-            Persistent(path) => Some(path),
-            _ => None,
+impl StorageOption {
+    // create a completely new standalone connection
+    pub(super) fn conn(&self) -> Result<SqliteConnection, diesel::ConnectionError> {
+        match self.path() {
+            Some(path) => SqliteConnection::establish(path),
+            None => SqliteConnection::establish(":memory:"),
         }
     }
 }
@@ -102,32 +121,40 @@ impl NativeDb {
     pub(super) fn new(
         opts: &StorageOption,
         enc_key: Option<EncryptionKey>,
+        extensions: SqliteExtensions,
     ) -> Result<Self, StorageError> {
         let mut builder = Pool::builder();
 
-        let customizer = if let Some(key) = enc_key {
+        let inner = if let Some(key) = enc_key {
             let enc_opts = EncryptedConnection::new(key, opts)?;
-            builder = builder.connection_customizer(Box::new(enc_opts.clone()));
             Some(Box::new(enc_opts) as Box<dyn XmtpConnection>)
-        } else if matches!(opts, StorageOption::Persistent(_)) {
-            builder = builder.connection_customizer(Box::new(UnencryptedConnection));
+        } else if opts.path().is_some() {
             Some(Box::new(UnencryptedConnection) as Box<dyn XmtpConnection>)
         } else {
             None
         };
+        // The built-in extensions are loaded first, so the host's can rely on them
+        let mut all_extensions = SqliteExtensions::default();
+        all_extensions.push(Arc::new(UnicodeFolding));
+        all_extensions.extend(&extensions);
+        let customizer = ExtendedConnection {
+            inner,
+            extensions: all_extensions,
+        };
+        builder = builder.connection_customizer(Box::new(customizer.clone()));
 
-        let pool = match opts {
-            StorageOption::Ephemeral => builder
+        let pool = match opts.path() {
+            None => builder
                 .max_size(1)
                 .build(ConnectionManager::new(":memory:"))?,
-            StorageOption::Persistent(ref path) => builder
+            Some(path) => builder
                 .max_size(crate::configuration::MAX_DB_POOL_SIZE)
                 .build(ConnectionManager::new(path))?,
         };
 
         Ok(Self {
             pool: Arc::new(Some(pool).into()),
-            customizer,
+            customizer: Some(Box::new(customizer)),
             opts: opts.clone(),
         })
     }
@@ -147,6 +174,19 @@ impl NativeDb {
 
         Ok(pool.get()?)
     }
+
+    /// A connection to the database on disk that is not part of the pool, customized like
+    /// the pooled ones
+    pub(super) fn dedicated_conn(&self) -> Result<SqliteConnection, StorageError> {
+        if self.opts.path().is_none() {
+            return Err(StorageError::SnapshotEphemeral);
+        }
+        let mut conn = self.opts.conn()?;
+        if let Some(customizer) = &self.customizer {
+            customizer.on_acquire(&mut conn)?;
+        }
+        Ok(conn)
+    }
 }
 
 impl XmtpDb for NativeDb {
@@ -182,11 +222,11 @@ impl XmtpDb for NativeDb {
             builder = builder.connection_customizer(opts.clone().into_super());
         }
 
-        let pool = match self.opts {
-            StorageOption::Ephemeral => builder
+        let pool = match self.opts.path() {
+            None => builder
                 .max_size(1)
                 .build(ConnectionManager::new(":memory:"))?,
-            StorageOption::Persistent(ref path) => builder
+            Some(path) => builder
                 .max_size(crate::configuration::MAX_DB_POOL_SIZE)
                 .build(ConnectionManager::new(path))?,
         };
@@ -0,0 +1,165 @@
+//! The network environment a database belongs to.
+//!
+//! Inboxes, groups and key packages only exist on the network they were created on, so a
+//! database used against another environment fails in confusing ways, such as groups that can
+//! not be found. The environment is recorded the first time the database is used, or for
+//! databases created before, once their identity is found on the network. It is checked every
+//! time a client is built, see
+//! [`ClientBuilder::network_environment`](crate::builder::ClientBuilder::network_environment).
+use std::{fmt, str::FromStr};
+
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    schema::network_environment::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// The only row of the table
+const ROW_ID: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkEnvironment {
+    Local,
+    Dev,
+    Production,
+}
+
+impl NetworkEnvironment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Dev => "dev",
+            Self::Production => "production",
+        }
+    }
+
+    /// The environment of the XMTP nodes at `host`, or `None` for hosts of other deployments
+    pub fn from_host(host: &str) -> Option<Self> {
+        let host = host.to_lowercase();
+        if host.contains("grpc.production.xmtp.network") {
+            Some(Self::Production)
+        } else if host.contains("grpc.dev.xmtp.network") {
+            Some(Self::Dev)
+        } else if host.contains("localhost") || host.contains("127.0.0.1") {
+            Some(Self::Local)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for NetworkEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NetworkEnvironment {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Self::Local),
+            "dev" => Ok(Self::Dev),
+            "production" => Ok(Self::Production),
+            _ => Err(StorageError::Deserialization(format!(
+                "unknown network environment {s:?}"
+            ))),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[diesel(table_name = network_environment)]
+struct StoredNetworkEnvironment {
+    id: i32,
+    environment: String,
+    recorded_at_ns: i64,
+}
+
+impl DbConnection {
+    /// The environment the database was first used with, if it was recorded
+    pub fn network_environment(&self) -> Result<Option<NetworkEnvironment>, StorageError> {
+        let stored: Option<String> = self.raw_query(|conn| {
+            dsl::network_environment
+                .find(ROW_ID)
+                .select(dsl::environment)
+                .first(conn)
+                .optional()
+        })?;
+        stored.map(|env| env.parse()).transpose()
+    }
+
+    /// Record `env` unless an environment was already recorded, and return the recorded one
+    pub fn record_network_environment(
+        &self,
+        env: NetworkEnvironment,
+    ) -> Result<NetworkEnvironment, StorageError> {
+        let stored = StoredNetworkEnvironment {
+            id: ROW_ID,
+            environment: env.as_str().to_string(),
+            recorded_at_ns: now_ns(),
+        };
+        self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::network_environment)
+                .values(&stored)
+                .execute(conn)
+        })?;
+        Ok(self.network_environment()?.unwrap_or(env))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn records_the_first_environment() {
+        with_connection(|conn| {
+            assert_eq!(conn.network_environment().unwrap(), None);
+            assert_eq!(
+                conn.record_network_environment(NetworkEnvironment::Dev)
+                    .unwrap(),
+                NetworkEnvironment::Dev
+            );
+            assert_eq!(
+                conn.record_network_environment(NetworkEnvironment::Production)
+                    .unwrap(),
+                NetworkEnvironment::Dev
+            );
+            assert_eq!(
+                conn.network_environment().unwrap(),
+                Some(NetworkEnvironment::Dev)
+            );
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn environments_of_hosts() {
+        assert_eq!(
+            NetworkEnvironment::from_host("https://grpc.production.xmtp.network:443"),
+            Some(NetworkEnvironment::Production)
+        );
+        assert_eq!(
+            NetworkEnvironment::from_host("https://grpc.dev.xmtp.network:443"),
+            Some(NetworkEnvironment::Dev)
+        );
+        assert_eq!(
+            NetworkEnvironment::from_host("http://localhost:5556"),
+            Some(NetworkEnvironment::Local)
+        );
+        assert_eq!(
+            NetworkEnvironment::from_host("https://xmtp.example.com"),
+            None
+        );
+    }
+}
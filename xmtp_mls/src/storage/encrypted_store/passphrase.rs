@@ -0,0 +1,144 @@
+//! Database encryption keys derived from a user passphrase with Argon2id
+//!
+//! Apps without a platform keystore can protect the database with a passphrase instead.
+//! The salt and the Argon2id parameters are not secret, and on native platforms they are
+//! persisted next to the database so that the same passphrase derives the same key on later
+//! opens, see [`super::EncryptedMessageStore::key_from_passphrase`].
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+
+use super::EncryptionKey;
+use crate::storage::SqlCipherError;
+
+/// Shortest salt accepted by [`PassphraseKey::derive_from_passphrase`], in bytes
+pub const MIN_PASSPHRASE_SALT_SIZE: usize = 16;
+
+/// Argon2id cost parameters
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassphraseKdfParams {
+    /// Memory used, in KiB
+    pub memory_kib: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Number of lanes
+    pub parallelism: u32,
+}
+
+impl Default for PassphraseKdfParams {
+    /// The Argon2id parameters OWASP recommends for a 19 MiB memory budget
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Derive an [`EncryptionKey`] from a passphrase
+pub trait PassphraseKey: Sized {
+    fn derive_from_passphrase(
+        passphrase: &str,
+        salt: &[u8],
+        params: &PassphraseKdfParams,
+    ) -> Result<Self, SqlCipherError>;
+}
+
+impl PassphraseKey for EncryptionKey {
+    fn derive_from_passphrase(
+        passphrase: &str,
+        salt: &[u8],
+        params: &PassphraseKdfParams,
+    ) -> Result<Self, SqlCipherError> {
+        if passphrase.is_empty() {
+            return Err(SqlCipherError::KeyDerivation("passphrase is empty".into()));
+        }
+        if salt.len() < MIN_PASSPHRASE_SALT_SIZE {
+            return Err(SqlCipherError::KeyDerivation(format!(
+                "salt must be at least {MIN_PASSPHRASE_SALT_SIZE} bytes, got {}",
+                salt.len()
+            )));
+        }
+        let argon2_params = Params::new(
+            params.memory_kib,
+            params.iterations,
+            params.parallelism,
+            Some(std::mem::size_of::<EncryptionKey>()),
+        )
+        .map_err(|e| SqlCipherError::KeyDerivation(e.to_string()))?;
+
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| SqlCipherError::KeyDerivation(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// The salt and parameters a database key was derived with
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassphraseKdf {
+    /// Hex-encoded salt
+    pub salt: String,
+    pub params: PassphraseKdfParams,
+}
+
+impl PassphraseKdf {
+    /// A random salt with `params`
+    pub fn generate(params: PassphraseKdfParams) -> Self {
+        Self {
+            salt: hex::encode(xmtp_common::rand_array::<MIN_PASSPHRASE_SALT_SIZE>()),
+            params,
+        }
+    }
+
+    pub fn derive_key(&self, passphrase: &str) -> Result<EncryptionKey, SqlCipherError> {
+        let salt = hex::decode(&self.salt)
+            .map_err(|e| SqlCipherError::KeyDerivation(format!("invalid salt: {e}")))?;
+        EncryptionKey::derive_from_passphrase(passphrase, &salt, &self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_params() -> PassphraseKdfParams {
+        PassphraseKdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn derives_the_same_key_from_the_same_inputs() {
+        let salt = [7u8; 16];
+        let key = EncryptionKey::derive_from_passphrase("hunter2", &salt, &fast_params()).unwrap();
+        assert_eq!(
+            key,
+            EncryptionKey::derive_from_passphrase("hunter2", &salt, &fast_params()).unwrap()
+        );
+        assert_ne!(
+            key,
+            EncryptionKey::derive_from_passphrase("hunter3", &salt, &fast_params()).unwrap()
+        );
+        assert_ne!(
+            key,
+            EncryptionKey::derive_from_passphrase("hunter2", &[8u8; 16], &fast_params()).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        assert!(EncryptionKey::derive_from_passphrase("", &[7u8; 16], &fast_params()).is_err());
+        assert!(
+            EncryptionKey::derive_from_passphrase("hunter2", &[7u8; 8], &fast_params()).is_err()
+        );
+        let params = PassphraseKdfParams {
+            iterations: 0,
+            ..fast_params()
+        };
+        assert!(EncryptionKey::derive_from_passphrase("hunter2", &[7u8; 16], &params).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use diesel::{prelude::*, upsert::excluded};
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    schema::pending_welcomes::{self, dsl},
+};
+use crate::storage::StorageError;
+
+/// An installation that was sent a welcome to a group, but has not been seen in the group since
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = pending_welcomes)]
+#[diesel(primary_key(group_id, installation_id))]
+pub struct StoredPendingWelcome {
+    pub group_id: Vec<u8>,
+    pub installation_id: Vec<u8>,
+    /// Time in nanoseconds the latest welcome was sent
+    pub sent_at_ns: i64,
+    /// Number of times the installation was re-added with a fresh key package
+    pub readd_attempts: i32,
+}
+
+impl DbConnection {
+    /// Record that welcomes to `group_id` were sent to `installation_ids`.
+    /// Installations that were already pending keep their re-add attempts.
+    pub fn record_pending_welcomes<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        installation_ids: &[Vec<u8>],
+    ) -> Result<(), StorageError> {
+        let sent_at_ns = now_ns();
+        let pending: Vec<StoredPendingWelcome> = installation_ids
+            .iter()
+            .map(|installation_id| StoredPendingWelcome {
+                group_id: group_id.as_ref().to_vec(),
+                installation_id: installation_id.clone(),
+                sent_at_ns,
+                readd_attempts: 0,
+            })
+            .collect();
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::pending_welcomes)
+                .values(&pending)
+                .on_conflict((dsl::group_id, dsl::installation_id))
+                .do_update()
+                .set(dsl::sent_at_ns.eq(excluded(dsl::sent_at_ns)))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The installation was seen in the group, so it must have processed its welcome.
+    /// Returns `true` if the installation was pending.
+    pub fn clear_pending_welcome<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        installation_id: &[u8],
+    ) -> Result<bool, StorageError> {
+        let deleted = self.raw_query(|conn| {
+            diesel::delete(dsl::pending_welcomes.find((group_id.as_ref(), installation_id)))
+                .execute(conn)
+        })?;
+        Ok(deleted > 0)
+    }
+
+    /// Installations in `group_id` whose latest welcome was sent before `sent_before_ns`
+    pub fn stale_pending_welcomes<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        sent_before_ns: i64,
+    ) -> Result<Vec<StoredPendingWelcome>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::pending_welcomes
+                .filter(dsl::group_id.eq(group_id.as_ref()))
+                .filter(dsl::sent_at_ns.lt(sent_before_ns))
+                .load(conn)
+        })?)
+    }
+
+    pub fn increment_welcome_readd_attempts<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        installation_id: &[u8],
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::pending_welcomes.find((group_id.as_ref(), installation_id)))
+                .set(dsl::readd_attempts.eq(dsl::readd_attempts + 1))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::time::now_ns;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tracks_pending_welcomes() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let (bola, caro) = (vec![1; 32], vec![2; 32]);
+
+            conn.record_pending_welcomes(&group.id, &[bola.clone(), caro.clone()])
+                .unwrap();
+            conn.increment_welcome_readd_attempts(&group.id, &bola)
+                .unwrap();
+            assert!(conn.clear_pending_welcome(&group.id, &caro).unwrap());
+            assert!(!conn.clear_pending_welcome(&group.id, &caro).unwrap());
+
+            // Sending another welcome keeps the number of attempts
+            conn.record_pending_welcomes(&group.id, &[bola.clone()])
+                .unwrap();
+            let stale = conn.stale_pending_welcomes(&group.id, now_ns()).unwrap();
+            assert_eq!(stale.len(), 1);
+            assert_eq!(stale[0].installation_id, bola);
+            assert_eq!(stale[0].readd_attempts, 1);
+
+            assert!(conn
+                .stale_pending_welcomes(&group.id, stale[0].sent_at_ns)
+                .unwrap()
+                .is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,132 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    group_message::StoredGroupMessage,
+    schema::{
+        group_messages::dsl as messages_dsl,
+        pinned_messages::{self, dsl},
+    },
+};
+use crate::storage::StorageError;
+
+/// A message pinned in a group, mirrored from
+/// [`crate::groups::group_mutable_metadata::MetadataField::PinnedMessages`]
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = pinned_messages)]
+#[diesel(primary_key(group_id, message_id))]
+pub struct StoredPinnedMessage {
+    pub group_id: Vec<u8>,
+    pub message_id: Vec<u8>,
+    /// Order of the pin in the group, oldest pin first
+    pub position: i32,
+}
+
+impl DbConnection {
+    /// Replace the pinned messages of a group with `message_ids`, in order
+    pub fn set_pinned_messages(
+        &self,
+        group_id: &[u8],
+        message_ids: &[Vec<u8>],
+    ) -> Result<(), StorageError> {
+        let pins: Vec<_> = message_ids
+            .iter()
+            .enumerate()
+            .map(|(position, message_id)| StoredPinnedMessage {
+                group_id: group_id.to_vec(),
+                message_id: message_id.clone(),
+                position: position as i32,
+            })
+            .collect();
+        self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                diesel::delete(dsl::pinned_messages.filter(dsl::group_id.eq(group_id)))
+                    .execute(conn)?;
+                diesel::insert_or_ignore_into(dsl::pinned_messages)
+                    .values(&pins)
+                    .execute(conn)
+            })
+        })?;
+        Ok(())
+    }
+
+    /// Ids of the pinned messages of a group, oldest pin first
+    pub fn pinned_message_ids(&self, group_id: &[u8]) -> Result<Vec<Vec<u8>>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::pinned_messages
+                .filter(dsl::group_id.eq(group_id))
+                .order(dsl::position.asc())
+                .select(dsl::message_id)
+                .load(conn)
+        })?)
+    }
+
+    /// The pinned messages of a group that are stored locally, oldest pin first
+    pub fn pinned_group_messages(
+        &self,
+        group_id: &[u8],
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let messages = self.raw_query(|conn| {
+            dsl::pinned_messages
+                .inner_join(messages_dsl::group_messages.on(messages_dsl::id.eq(dsl::message_id)))
+                .filter(dsl::group_id.eq(group_id))
+                .filter(messages_dsl::group_id.eq(group_id))
+                .order(dsl::position.asc())
+                .select(messages_dsl::group_messages::all_columns())
+                .load(conn)
+        })?;
+        self.open_messages(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_replaces_pinned_messages() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let first = generate_message(None, Some(&group.id), Some(1), None);
+            first.store(conn).unwrap();
+            let second = generate_message(None, Some(&group.id), Some(2), None);
+            second.store(conn).unwrap();
+            let missing = vec![7; 32];
+
+            conn.set_pinned_messages(
+                &group.id,
+                &[second.id.clone(), missing.clone(), first.id.clone()],
+            )
+            .unwrap();
+            assert_eq!(
+                conn.pinned_message_ids(&group.id).unwrap(),
+                vec![second.id.clone(), missing, first.id.clone()]
+            );
+            // Messages that are not stored locally are skipped
+            let messages = conn.pinned_group_messages(&group.id).unwrap();
+            assert_eq!(messages, vec![second.clone(), first.clone()]);
+
+            conn.set_pinned_messages(&group.id, &[first.id.clone()])
+                .unwrap();
+            assert_eq!(
+                conn.pinned_message_ids(&group.id).unwrap(),
+                vec![first.id.clone()]
+            );
+            conn.set_pinned_messages(&group.id, &[]).unwrap();
+            assert!(conn.pinned_message_ids(&group.id).unwrap().is_empty());
+        })
+        .await
+    }
+}
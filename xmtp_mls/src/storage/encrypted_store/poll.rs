@@ -0,0 +1,280 @@
+use diesel::prelude::*;
+
+use super::{
+    db_connection::DbConnection,
+    schema::{
+        orphan_poll_votes::{self, dsl as orphans_dsl},
+        poll_votes::{self, dsl as votes_dsl},
+        polls::{self, dsl},
+    },
+};
+use crate::{impl_store_or_ignore, storage::StorageError};
+
+/// A poll created in a group
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = polls)]
+#[diesel(primary_key(id))]
+pub struct StoredPoll {
+    /// Id of the message that created the poll
+    pub id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    pub creator_inbox_id: String,
+    pub question: String,
+    /// JSON array of the answers that can be voted for
+    pub options: String,
+    pub allow_multiple_choices: bool,
+    pub created_at_ns: i64,
+}
+
+impl_store_or_ignore!(StoredPoll, polls);
+
+impl StoredPoll {
+    pub fn options(&self) -> Vec<String> {
+        serde_json::from_str(&self.options).unwrap_or_default()
+    }
+
+    /// Whether `option_indexes` is a valid vote on this poll
+    pub fn accepts(&self, option_indexes: &[u32]) -> bool {
+        let option_count = self.options().len();
+        if !self.allow_multiple_choices && option_indexes.len() > 1 {
+            return false;
+        }
+        option_indexes
+            .iter()
+            .all(|index| (*index as usize) < option_count)
+    }
+}
+
+/// The latest vote of an inbox on a poll
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = poll_votes)]
+#[diesel(primary_key(poll_id, voter_inbox_id))]
+pub struct StoredPollVote {
+    pub poll_id: Vec<u8>,
+    pub voter_inbox_id: String,
+    /// Id of the message the vote was cast in
+    pub message_id: Vec<u8>,
+    pub voted_at_ns: i64,
+    /// JSON array of the indexes of the options voted for
+    pub option_indexes: String,
+}
+
+impl StoredPollVote {
+    pub fn new(
+        poll_id: Vec<u8>,
+        voter_inbox_id: String,
+        message_id: Vec<u8>,
+        voted_at_ns: i64,
+        option_indexes: &[u32],
+    ) -> Self {
+        let mut option_indexes = option_indexes.to_vec();
+        option_indexes.sort_unstable();
+        option_indexes.dedup();
+        Self {
+            poll_id,
+            voter_inbox_id,
+            message_id,
+            voted_at_ns,
+            option_indexes: serde_json::to_string(&option_indexes).unwrap_or_default(),
+        }
+    }
+
+    pub fn option_indexes(&self) -> Vec<u32> {
+        serde_json::from_str(&self.option_indexes).unwrap_or_default()
+    }
+
+    /// Votes are ordered by the time they were sent, and then by message id, so that every
+    /// installation keeps the same vote when two arrive with the same timestamp
+    fn replaces(&self, other: &StoredPollVote) -> bool {
+        if self.message_id == other.message_id {
+            return true;
+        }
+        (self.voted_at_ns, &self.message_id) > (other.voted_at_ns, &other.message_id)
+    }
+}
+
+/// A vote that arrived before its poll. It is validated against the poll and recorded as a
+/// [`StoredPollVote`] once the poll arrives.
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = orphan_poll_votes)]
+#[diesel(primary_key(message_id))]
+pub struct StoredOrphanPollVote {
+    /// Id of the message the vote was cast in
+    pub message_id: Vec<u8>,
+    pub poll_id: Vec<u8>,
+    /// Id of the group the vote was sent to
+    pub group_id: Vec<u8>,
+    pub voter_inbox_id: String,
+    pub voted_at_ns: i64,
+    /// JSON array of the indexes of the options voted for
+    pub option_indexes: String,
+}
+
+impl_store_or_ignore!(StoredOrphanPollVote, orphan_poll_votes);
+
+impl StoredOrphanPollVote {
+    pub fn new(vote: StoredPollVote, group_id: Vec<u8>) -> Self {
+        Self {
+            message_id: vote.message_id,
+            poll_id: vote.poll_id,
+            group_id,
+            voter_inbox_id: vote.voter_inbox_id,
+            voted_at_ns: vote.voted_at_ns,
+            option_indexes: vote.option_indexes,
+        }
+    }
+}
+
+impl From<StoredOrphanPollVote> for StoredPollVote {
+    fn from(orphan: StoredOrphanPollVote) -> Self {
+        Self {
+            poll_id: orphan.poll_id,
+            voter_inbox_id: orphan.voter_inbox_id,
+            message_id: orphan.message_id,
+            voted_at_ns: orphan.voted_at_ns,
+            option_indexes: orphan.option_indexes,
+        }
+    }
+}
+
+impl DbConnection {
+    pub fn get_poll<PollId: AsRef<[u8]>>(
+        &self,
+        poll_id: PollId,
+    ) -> Result<Option<StoredPoll>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::polls.find(poll_id.as_ref()).first(conn).optional())?)
+    }
+
+    /// Record a vote, unless the voter already has a later vote on the poll.
+    /// Returns whether the vote was recorded.
+    pub fn record_poll_vote(&self, vote: &StoredPollVote) -> Result<bool, StorageError> {
+        let existing: Option<StoredPollVote> = self.raw_query(|conn| {
+            votes_dsl::poll_votes
+                .find((&vote.poll_id, &vote.voter_inbox_id))
+                .first(conn)
+                .optional()
+        })?;
+        if existing.is_some_and(|existing| !vote.replaces(&existing)) {
+            return Ok(false);
+        }
+
+        self.raw_query(|conn| {
+            diesel::replace_into(votes_dsl::poll_votes)
+                .values(vote)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    /// Remove and return the votes that arrived before the poll, oldest first
+    pub fn take_orphan_poll_votes<PollId: AsRef<[u8]>>(
+        &self,
+        poll_id: PollId,
+    ) -> Result<Vec<StoredOrphanPollVote>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            conn.transaction(|conn| {
+                let orphans = orphans_dsl::orphan_poll_votes
+                    .filter(orphans_dsl::poll_id.eq(poll_id.as_ref()))
+                    .order((
+                        orphans_dsl::voted_at_ns.asc(),
+                        orphans_dsl::message_id.asc(),
+                    ))
+                    .load(conn)?;
+                diesel::delete(
+                    orphans_dsl::orphan_poll_votes
+                        .filter(orphans_dsl::poll_id.eq(poll_id.as_ref())),
+                )
+                .execute(conn)?;
+                Ok::<_, diesel::result::Error>(orphans)
+            })
+        })?)
+    }
+
+    /// The latest vote of every inbox that voted on the poll
+    pub fn get_poll_votes<PollId: AsRef<[u8]>>(
+        &self,
+        poll_id: PollId,
+    ) -> Result<Vec<StoredPollVote>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            votes_dsl::poll_votes
+                .filter(votes_dsl::poll_id.eq(poll_id.as_ref()))
+                .order(votes_dsl::voter_inbox_id.asc())
+                .load(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_vote() {
+        with_connection(|conn| {
+            let poll_id = vec![1; 32];
+            let vote = |message_id: u8, voted_at_ns: i64, option: u32| {
+                StoredPollVote::new(
+                    poll_id.clone(),
+                    "bola".to_string(),
+                    vec![message_id; 32],
+                    voted_at_ns,
+                    &[option],
+                )
+            };
+
+            assert!(conn.record_poll_vote(&vote(1, 100, 0)).unwrap());
+            assert!(conn.record_poll_vote(&vote(2, 200, 1)).unwrap());
+            // A vote that arrives late does not replace a newer one
+            assert!(!conn.record_poll_vote(&vote(3, 150, 2)).unwrap());
+
+            let votes = conn.get_poll_votes(&poll_id).unwrap();
+            assert_eq!(votes.len(), 1);
+            assert_eq!(votes[0].option_indexes(), vec![1]);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_takes_orphan_votes_once() {
+        use crate::{storage::encrypted_store::group::tests::generate_group, Store, StoreOrIgnore};
+
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let poll_id = vec![1; 32];
+            let orphan = |message_id: u8, voted_at_ns: i64| {
+                StoredOrphanPollVote::new(
+                    StoredPollVote::new(
+                        poll_id.clone(),
+                        format!("voter_{message_id}"),
+                        vec![message_id; 32],
+                        voted_at_ns,
+                        &[0],
+                    ),
+                    group.id.clone(),
+                )
+            };
+
+            orphan(1, 200).store_or_ignore(conn).unwrap();
+            orphan(2, 100).store_or_ignore(conn).unwrap();
+            // The same vote received twice is kept once
+            orphan(2, 100).store_or_ignore(conn).unwrap();
+
+            let orphans = conn.take_orphan_poll_votes(&poll_id).unwrap();
+            assert_eq!(
+                orphans
+                    .iter()
+                    .map(|orphan| orphan.voted_at_ns)
+                    .collect::<Vec<_>>(),
+                vec![100, 200]
+            );
+            assert!(conn.take_orphan_poll_votes(&poll_id).unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,295 @@
+//! Durable queue of the actions to take once the commit of an intent is merged, such as sending
+//! the welcomes of an add.
+//!
+//! An action is queued in the same transaction that marks its intent committed, so it survives
+//! the process dying before the action is taken. It is marked completed once taken, and completed
+//! actions are kept for [`COMPLETED_ACTION_RETENTION_NS`] so that the queue can tell them apart
+//! from pending ones.
+//!
+//! An action started [`MAX_POST_COMMIT_ACTION_ATTEMPTS`] times without completing, or that can't
+//! be decoded, is dead-lettered instead: it is no longer taken, and is kept as long as completed
+//! actions.
+//!
+//! [`MAX_POST_COMMIT_ACTION_ATTEMPTS`]: crate::configuration::MAX_POST_COMMIT_ACTION_ATTEMPTS
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+use super::{
+    db_connection::DbConnection,
+    schema::post_commit_actions::{self, dsl},
+};
+use crate::storage::{group_intent::ID, StorageError};
+
+/// How long completed actions are kept before they are deleted
+pub const COMPLETED_ACTION_RETENTION_NS: i64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = post_commit_actions)]
+#[diesel(primary_key(id))]
+pub struct StoredPostCommitAction {
+    pub id: i32,
+    pub group_id: Vec<u8>,
+    pub intent_id: ID,
+    /// A serialized [`PostCommitAction`](crate::groups::intents::PostCommitAction)
+    pub action: Vec<u8>,
+    /// Number of times taking the action was started
+    pub attempts: i32,
+    pub created_at_ns: i64,
+    pub completed_at_ns: Option<i64>,
+    /// Time the action was given up on
+    pub dead_lettered_at_ns: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = post_commit_actions)]
+struct NewPostCommitAction<'a> {
+    group_id: &'a [u8],
+    intent_id: ID,
+    action: &'a [u8],
+    attempts: i32,
+    created_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Queue `action` to be taken for the committed intent `intent_id`
+    pub fn enqueue_post_commit_action(
+        &self,
+        group_id: &[u8],
+        intent_id: ID,
+        action: &[u8],
+    ) -> Result<StoredPostCommitAction, StorageError> {
+        let new = NewPostCommitAction {
+            group_id,
+            intent_id,
+            action,
+            attempts: 0,
+            created_at_ns: now_ns(),
+        };
+        Ok(self.raw_query(|conn| {
+            diesel::insert_into(dsl::post_commit_actions)
+                .values(&new)
+                .get_result(conn)
+        })?)
+    }
+
+    /// Queue `action` to continue the action `id`, keeping the attempts counted for it so far
+    pub fn requeue_post_commit_action(
+        &self,
+        id: i32,
+        action: &[u8],
+    ) -> Result<StoredPostCommitAction, StorageError> {
+        Ok(self.raw_query(|conn| {
+            let previous: StoredPostCommitAction = dsl::post_commit_actions.find(id).first(conn)?;
+            let new = NewPostCommitAction {
+                group_id: &previous.group_id,
+                intent_id: previous.intent_id,
+                action,
+                attempts: previous.attempts,
+                created_at_ns: now_ns(),
+            };
+            diesel::insert_into(dsl::post_commit_actions)
+                .values(&new)
+                .get_result(conn)
+        })?)
+    }
+
+    /// The actions of `group_id` that were neither completed nor dead-lettered yet, oldest first
+    pub fn pending_post_commit_actions(
+        &self,
+        group_id: &[u8],
+    ) -> Result<Vec<StoredPostCommitAction>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::post_commit_actions
+                .filter(dsl::group_id.eq(group_id))
+                .filter(dsl::completed_at_ns.is_null())
+                .filter(dsl::dead_lettered_at_ns.is_null())
+                .order(dsl::id.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// The groups with actions that were not completed yet
+    pub fn groups_with_pending_post_commit_actions(&self) -> Result<Vec<Vec<u8>>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::post_commit_actions
+                .filter(dsl::completed_at_ns.is_null())
+                .filter(dsl::dead_lettered_at_ns.is_null())
+                .select(dsl::group_id)
+                .distinct()
+                .load(conn)
+        })?)
+    }
+
+    pub fn increment_post_commit_action_attempts(&self, id: i32) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::post_commit_actions.find(id))
+                .set(dsl::attempts.eq(dsl::attempts + 1))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Mark the action completed. Returns `false` if it already was.
+    pub fn complete_post_commit_action(&self, id: i32) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::post_commit_actions.find(id))
+                .filter(dsl::completed_at_ns.is_null())
+                .set(dsl::completed_at_ns.eq(now_ns()))
+                .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// Give up on the action, so that it is no longer taken
+    pub fn dead_letter_post_commit_action(&self, id: i32) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::post_commit_actions.find(id))
+                .filter(dsl::completed_at_ns.is_null())
+                .set(dsl::dead_lettered_at_ns.eq(now_ns()))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The actions that were given up on, oldest first
+    pub fn dead_lettered_post_commit_actions(
+        &self,
+    ) -> Result<Vec<StoredPostCommitAction>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::post_commit_actions
+                .filter(dsl::dead_lettered_at_ns.is_not_null())
+                .order(dsl::id.asc())
+                .load(conn)
+        })?)
+    }
+
+    /// Delete the actions completed or dead-lettered before `completed_before_ns`
+    pub fn delete_completed_post_commit_actions(
+        &self,
+        completed_before_ns: i64,
+    ) -> Result<usize, StorageError> {
+        Ok(self.raw_query(|conn| {
+            diesel::delete(
+                dsl::post_commit_actions.filter(
+                    dsl::completed_at_ns
+                        .lt(completed_before_ns)
+                        .or(dsl::dead_lettered_at_ns.lt(completed_before_ns)),
+                ),
+            )
+            .execute(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group,
+            group_intent::{IntentKind, IntentState, NewGroupIntent},
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn actions_are_queued_when_intents_commit() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let intent = conn
+                .insert_group_intent(NewGroupIntent::new(
+                    IntentKind::UpdateGroupMembership,
+                    group.id.clone(),
+                    vec![],
+                ))
+                .unwrap();
+            conn.set_group_intent_published(intent.id, vec![1], Some(vec![2]), None, 1)
+                .unwrap();
+            assert!(conn
+                .pending_post_commit_actions(&group.id)
+                .unwrap()
+                .is_empty());
+
+            conn.set_group_intent_committed(intent.id).unwrap();
+            let pending = conn.pending_post_commit_actions(&group.id).unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].intent_id, intent.id);
+            assert_eq!(pending[0].action, vec![2]);
+            assert_eq!(
+                conn.groups_with_pending_post_commit_actions().unwrap(),
+                vec![group.id.clone()]
+            );
+
+            // intents without an action queue nothing
+            let intent = conn
+                .insert_group_intent(NewGroupIntent::new_test(
+                    IntentKind::KeyUpdate,
+                    group.id.clone(),
+                    vec![],
+                    IntentState::Published,
+                ))
+                .unwrap();
+            conn.set_group_intent_committed(intent.id).unwrap();
+            assert_eq!(
+                conn.pending_post_commit_actions(&group.id).unwrap().len(),
+                1
+            );
+
+            conn.increment_post_commit_action_attempts(pending[0].id)
+                .unwrap();
+            let requeued = conn
+                .requeue_post_commit_action(pending[0].id, &[3])
+                .unwrap();
+            assert_eq!(requeued.attempts, 1);
+            assert_eq!(requeued.action, vec![3]);
+            assert_eq!(requeued.intent_id, pending[0].intent_id);
+            assert!(conn.complete_post_commit_action(requeued.id).unwrap());
+            assert!(conn.complete_post_commit_action(pending[0].id).unwrap());
+            assert!(!conn.complete_post_commit_action(pending[0].id).unwrap());
+            assert!(conn
+                .pending_post_commit_actions(&group.id)
+                .unwrap()
+                .is_empty());
+            assert!(conn
+                .groups_with_pending_post_commit_actions()
+                .unwrap()
+                .is_empty());
+
+            // dead-lettered actions are no longer pending
+            let intent = conn
+                .insert_group_intent(NewGroupIntent::new_test(
+                    IntentKind::UpdateGroupMembership,
+                    group.id.clone(),
+                    vec![],
+                    IntentState::Published,
+                ))
+                .unwrap();
+            let poison = conn
+                .enqueue_post_commit_action(&group.id, intent.id, &[4])
+                .unwrap();
+            conn.dead_letter_post_commit_action(poison.id).unwrap();
+            assert!(conn
+                .pending_post_commit_actions(&group.id)
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                conn.dead_lettered_post_commit_actions().unwrap()[0].id,
+                poison.id
+            );
+
+            assert_eq!(conn.delete_completed_post_commit_actions(0).unwrap(), 0);
+            assert_eq!(
+                conn.delete_completed_post_commit_actions(now_ns() + 1)
+                    .unwrap(),
+                3
+            );
+        })
+        .await
+    }
+}
@@ -62,6 +62,8 @@ pub struct RefreshState {
     pub entity_id: Vec<u8>,
     pub entity_kind: EntityKind,
     pub cursor: i64,
+    /// Time in nanoseconds the entity was last synced with the network
+    pub synced_at_ns: Option<i64>,
 }
 
 impl_store!(RefreshState, refresh_state);
@@ -97,6 +99,7 @@ impl DbConnection {
                     entity_id: id.as_ref().to_vec(),
                     entity_kind,
                     cursor: 0,
+                    synced_at_ns: None,
                 };
                 new_state.store_or_ignore(self)?;
                 Ok(0)
@@ -104,6 +107,31 @@ impl DbConnection {
         }
     }
 
+    /// Record that the entity was synced with the network at `synced_at_ns`
+    pub fn update_synced_at<Id: AsRef<[u8]>>(
+        &self,
+        entity_id: Id,
+        entity_kind: EntityKind,
+        synced_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        use super::schema::refresh_state::dsl;
+        let state = RefreshState {
+            entity_id: entity_id.as_ref().to_vec(),
+            entity_kind,
+            cursor: 0,
+            synced_at_ns: Some(synced_at_ns),
+        };
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::refresh_state)
+                .values(&state)
+                .on_conflict((dsl::entity_id, dsl::entity_kind))
+                .do_update()
+                .set(dsl::synced_at_ns.eq(synced_at_ns))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
     pub fn update_cursor<Id: AsRef<[u8]>>(
         &self,
         entity_id: Id,
@@ -158,6 +186,7 @@ pub(crate) mod tests {
                 entity_id: id.clone(),
                 entity_kind,
                 cursor: 123,
+                synced_at_ns: None,
             };
             entry.store(conn).unwrap();
             assert_eq!(conn.get_last_cursor_for_id(&id, entity_kind).unwrap(), 123);
@@ -175,6 +204,7 @@ pub(crate) mod tests {
                 entity_id: id.clone(),
                 entity_kind,
                 cursor: 123,
+                synced_at_ns: None,
             };
             entry.store(conn).unwrap();
             assert!(conn.update_cursor(&id, entity_kind, 124).unwrap());
@@ -195,6 +225,7 @@ pub(crate) mod tests {
                 entity_id: entity_id.clone(),
                 entity_kind,
                 cursor: 123,
+                synced_at_ns: None,
             };
             entry.store(conn).unwrap();
             assert!(!conn.update_cursor(&entity_id, entity_kind, 122).unwrap());
@@ -214,6 +245,7 @@ pub(crate) mod tests {
                 entity_id: entity_id.clone(),
                 entity_kind: EntityKind::Welcome,
                 cursor: 123,
+                synced_at_ns: None,
             };
             welcome_state.store(conn).unwrap();
 
@@ -221,6 +253,7 @@ pub(crate) mod tests {
                 entity_id: entity_id.clone(),
                 entity_kind: EntityKind::Group,
                 cursor: 456,
+                synced_at_ns: None,
             };
             group_state.store(conn).unwrap();
 
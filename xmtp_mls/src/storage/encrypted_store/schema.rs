@@ -21,6 +21,7 @@ diesel::table! {
     content_type -> Nullable<Integer>,
     version_major -> Nullable<Integer>,
     version_minor -> Nullable<Integer>,
-    authority_id -> Nullable<Text>
+    authority_id -> Nullable<Text>,
+    sender_verified -> Nullable<Bool>
   }
 }
@@ -10,11 +10,88 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    avatar_blobs (digest) {
+        digest -> Binary,
+        data -> Binary,
+    }
+}
+
 diesel::table! {
     consent_records (entity_type, entity) {
         entity_type -> Integer,
         state -> Integer,
         entity -> Text,
+        expires_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    conversation_verifications (group_id, peer_inbox_id) {
+        group_id -> Binary,
+        peer_inbox_id -> Text,
+        verification_code -> Text,
+        verified_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    dm_public_ids (dm_id_hash) {
+        dm_id_hash -> Binary,
+        dm_id -> Text,
+    }
+}
+
+diesel::table! {
+    drafts (group_id) {
+        group_id -> Binary,
+        encoded_content -> Binary,
+        updated_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    encrypted_columns (table_name, column_name) {
+        table_name -> Text,
+        column_name -> Text,
+    }
+}
+
+diesel::table! {
+    failed_commits (group_id, cursor) {
+        group_id -> Binary,
+        cursor -> BigInt,
+        error -> Text,
+        failed_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    filtered_messages (message_id) {
+        message_id -> Binary,
+        group_id -> Binary,
+        rule_id -> Text,
+    }
+}
+
+diesel::table! {
+    group_avatars (group_id) {
+        group_id -> Binary,
+        url -> Text,
+        digest -> Nullable<Binary>,
+        updated_at_ns -> BigInt,
+        failed_attempts -> Integer,
+        retry_at_ns -> BigInt,
+        skipped -> Bool,
+        url_lookup -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    group_burst_budgets (group_id) {
+        group_id -> Binary,
+        max_messages -> Integer,
+        window_ms -> BigInt,
     }
 }
 
@@ -33,6 +110,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    group_key_rotation_policies (group_id) {
+        group_id -> Binary,
+        interval_ns -> BigInt,
+        updated_at_ns -> BigInt,
+    }
+}
+
 diesel::table! {
     group_messages (id) {
         id -> Binary,
@@ -48,6 +133,15 @@ diesel::table! {
         version_major -> Integer,
         authority_id -> Text,
         reference_id -> Nullable<Binary>,
+        sent_by_me -> Bool,
+        sender_verified -> Bool,
+    }
+}
+
+diesel::table! {
+    group_names (group_id) {
+        group_id -> Binary,
+        name -> Text,
     }
 }
 
@@ -65,6 +159,21 @@ diesel::table! {
         last_message_ns -> Nullable<BigInt>,
         message_disappear_from_ns -> Nullable<BigInt>,
         message_disappear_in_ns -> Nullable<BigInt>,
+        expires_at_ns -> Nullable<BigInt>,
+        lifecycle_state -> Integer,
+        left_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    history_sync_log (id) {
+        id -> Integer,
+        request_id -> Text,
+        requester_inbox_id -> Text,
+        requester_installation_id -> Binary,
+        kind -> Integer,
+        outcome -> Integer,
+        created_at_ns -> BigInt,
     }
 }
 
@@ -86,6 +195,43 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    installation_activity (installation_id) {
+        installation_id -> Binary,
+        last_active_ns -> BigInt,
+        key_package_hash -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    installation_key_log (inbox_id, installation_id) {
+        inbox_id -> Text,
+        installation_id -> Binary,
+        first_seen_ns -> BigInt,
+        last_seen_ns -> BigInt,
+        revoked_at_ns -> Nullable<BigInt>,
+        acknowledged -> Bool,
+        reappeared_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    installation_retirement (id) {
+        id -> Integer,
+        retired_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    join_requests (group_id, inbox_id) {
+        group_id -> Binary,
+        inbox_id -> Text,
+        message -> Text,
+        requested_at_ns -> BigInt,
+        state -> Integer,
+    }
+}
+
 diesel::table! {
     key_package_history (id) {
         id -> Integer,
@@ -94,6 +240,74 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    leave_requests (group_id, inbox_id) {
+        group_id -> Binary,
+        inbox_id -> Text,
+        requested_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    live_locations (group_id, sender_inbox_id) {
+        group_id -> Binary,
+        sender_inbox_id -> Text,
+        share_id -> Binary,
+        message_id -> Binary,
+        latitude -> Double,
+        longitude -> Double,
+        accuracy_meters -> Double,
+        updated_at_ns -> BigInt,
+        expires_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    mega_group_shards (group_id) {
+        group_id -> Binary,
+        mega_group_id -> Binary,
+        shard_index -> Integer,
+        relayed_until_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    message_annotations (message_id, key) {
+        message_id -> Binary,
+        key -> Text,
+        value -> Text,
+        group_id -> Binary,
+    }
+}
+
+diesel::table! {
+    message_reactions (reference_id, reactor_inbox_id, content) {
+        reference_id -> Binary,
+        reactor_inbox_id -> Text,
+        content -> Text,
+        group_id -> Binary,
+        message_id -> Binary,
+        reacted_at_ns -> BigInt,
+        removed -> Bool,
+    }
+}
+
+diesel::table! {
+    migration_progress (name) {
+        name -> Text,
+        cursor -> BigInt,
+        completed_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    network_environment (id) {
+        id -> Integer,
+        environment -> Text,
+        recorded_at_ns -> BigInt,
+    }
+}
+
 diesel::table! {
     openmls_key_store (key_bytes) {
         key_bytes -> Binary,
@@ -109,11 +323,85 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    orphan_poll_votes (message_id) {
+        message_id -> Binary,
+        poll_id -> Binary,
+        group_id -> Binary,
+        voter_inbox_id -> Text,
+        voted_at_ns -> BigInt,
+        option_indexes -> Text,
+    }
+}
+
+diesel::table! {
+    pending_welcomes (group_id, installation_id) {
+        group_id -> Binary,
+        installation_id -> Binary,
+        sent_at_ns -> BigInt,
+        readd_attempts -> Integer,
+    }
+}
+
+diesel::table! {
+    pinned_messages (group_id, message_id) {
+        group_id -> Binary,
+        message_id -> Binary,
+        position -> Integer,
+    }
+}
+
+diesel::table! {
+    poll_votes (poll_id, voter_inbox_id) {
+        poll_id -> Binary,
+        voter_inbox_id -> Text,
+        message_id -> Binary,
+        voted_at_ns -> BigInt,
+        option_indexes -> Text,
+    }
+}
+
+diesel::table! {
+    polls (id) {
+        id -> Binary,
+        group_id -> Binary,
+        creator_inbox_id -> Text,
+        question -> Text,
+        options -> Text,
+        allow_multiple_choices -> Bool,
+        created_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    post_commit_actions (id) {
+        id -> Integer,
+        group_id -> Binary,
+        intent_id -> Integer,
+        action -> Binary,
+        attempts -> Integer,
+        created_at_ns -> BigInt,
+        completed_at_ns -> Nullable<BigInt>,
+        dead_lettered_at_ns -> Nullable<BigInt>,
+    }
+}
+
 diesel::table! {
     refresh_state (entity_id, entity_kind) {
         entity_id -> Binary,
         entity_kind -> Integer,
         cursor -> BigInt,
+        synced_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    transaction_verifications (message_id) {
+        message_id -> Binary,
+        network_id -> Text,
+        reference -> Text,
+        status -> Integer,
+        checked_at_ns -> BigInt,
     }
 }
 
@@ -121,6 +409,9 @@ diesel::table! {
     user_preferences (id) {
         id -> Integer,
         hmac_key -> Nullable<Binary>,
+        consent_policy -> Nullable<Text>,
+        feature_flags -> Nullable<Text>,
+        mute_rules -> Nullable<Text>,
     }
 }
 
@@ -131,21 +422,69 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(conversation_verifications -> groups (group_id));
+diesel::joinable!(drafts -> groups (group_id));
+diesel::joinable!(filtered_messages -> groups (group_id));
+diesel::joinable!(group_avatars -> groups (group_id));
+diesel::joinable!(group_burst_budgets -> groups (group_id));
 diesel::joinable!(group_intents -> groups (group_id));
+diesel::joinable!(group_key_rotation_policies -> groups (group_id));
 diesel::joinable!(group_messages -> groups (group_id));
+diesel::joinable!(group_names -> groups (group_id));
+diesel::joinable!(join_requests -> groups (group_id));
+diesel::joinable!(leave_requests -> groups (group_id));
+diesel::joinable!(live_locations -> groups (group_id));
+diesel::joinable!(mega_group_shards -> groups (group_id));
+diesel::joinable!(message_annotations -> groups (group_id));
+diesel::joinable!(message_reactions -> groups (group_id));
+diesel::joinable!(orphan_poll_votes -> groups (group_id));
+diesel::joinable!(pending_welcomes -> groups (group_id));
+diesel::joinable!(pinned_messages -> groups (group_id));
+diesel::joinable!(polls -> groups (group_id));
+diesel::joinable!(post_commit_actions -> groups (group_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     association_state,
+    avatar_blobs,
     consent_records,
+    conversation_verifications,
+    dm_public_ids,
+    drafts,
+    encrypted_columns,
+    failed_commits,
+    filtered_messages,
+    group_avatars,
+    group_burst_budgets,
     group_intents,
+    group_key_rotation_policies,
     group_messages,
+    group_names,
     groups,
+    history_sync_log,
     identity,
     identity_updates,
+    installation_activity,
+    installation_key_log,
+    installation_retirement,
+    join_requests,
     key_package_history,
+    leave_requests,
+    live_locations,
+    mega_group_shards,
+    message_annotations,
+    message_reactions,
+    migration_progress,
+    network_environment,
     openmls_key_store,
     openmls_key_value,
+    orphan_poll_votes,
+    pending_welcomes,
+    pinned_messages,
+    poll_votes,
+    polls,
+    post_commit_actions,
     refresh_state,
+    transaction_verifications,
     user_preferences,
     wallet_addresses,
     conversation_list
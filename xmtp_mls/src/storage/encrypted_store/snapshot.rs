@@ -0,0 +1,426 @@
+//! Point-in-time snapshots of the database.
+//!
+//! A snapshot is taken with `VACUUM INTO` on a connection outside the pool, which writes a
+//! compacted, transactionally consistent copy of the database without blocking writers for longer
+//! than a read transaction. The copy is written next to its destination, and only moved into place
+//! once it passes an integrity check and carries the same schema version as the database.
+//! For SQLCipher databases the copy is encrypted with the same key, but keeps its salt in the
+//! file header. When the snapshot is restored, it is migrated back to the plaintext header
+//! the original database used, and a fresh salt file is written.
+//!
+//! Restoring is deferred until the next time the store is opened, so that no pooled
+//! connection ever observes the database file being swapped underneath it.
+//!
+//! A store can also live entirely in memory and be persisted only as an encrypted snapshot,
+//! see [`EncryptedMessageStore::new_in_memory_with_snapshot`]. The store's only connection is
+//! held just long enough to copy the database into a private in-memory database. The copy is
+//! then exported with `sqlcipher_export` on a dedicated connection, off the async runtime, into a
+//! file encrypted with the snapshot key, which replaces the previous snapshot only once it is
+//! complete.
+use std::path::{Path, PathBuf};
+
+use diesel::{
+    connection::SimpleConnection,
+    sql_types::{Nullable, Text},
+    Connection, QueryableByName, RunQueryDsl, SqliteConnection,
+};
+
+use super::{
+    extensions::SqliteExtensions, native::NativeDb, CipherOptions, EncryptedConnection,
+    EncryptedMessageStore, EncryptionKey, StorageError, StorageOption,
+};
+use crate::StreamHandle;
+
+const PENDING_RESTORE_SUFFIX: &str = "pending_restore";
+const SNAPSHOT_TMP_SUFFIX: &str = "tmp";
+
+fn escape_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\'', "''")
+}
+
+fn attach_snapshot(path: &Path, key: &EncryptionKey) -> String {
+    format!(
+        r#"ATTACH DATABASE '{}' AS snapshot KEY "x'{}'";"#,
+        escape_path(path),
+        hex::encode(key)
+    )
+}
+
+impl EncryptedMessageStore {
+    /// Write a consistent copy of the database to `path`.
+    ///
+    /// The file at `path` must not already exist.
+    #[tracing::instrument(level = "debug", skip(self, path), fields(path = %path.as_ref().display()))]
+    pub fn snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), StorageError> {
+        let path = path.as_ref();
+        if path.try_exists()? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("snapshot destination {} already exists", path.display()),
+            )
+            .into());
+        }
+        let tmp_path = Self::snapshot_tmp_file(path);
+        if tmp_path.try_exists()? {
+            // Left over by an interrupted snapshot
+            std::fs::remove_file(&tmp_path)?;
+        }
+        let mut conn = self.db.dedicated_conn()?;
+        let written = conn
+            .batch_execute(&format!("VACUUM INTO '{}';", escape_path(&tmp_path)))
+            .map_err(StorageError::from)
+            .and_then(|_| validate_snapshot(&mut conn, &tmp_path));
+        if let Err(e) = written {
+            if tmp_path.try_exists()? {
+                std::fs::remove_file(&tmp_path)?;
+            }
+            return Err(e);
+        }
+        std::fs::rename(&tmp_path, path)?;
+        tracing::info!("wrote database snapshot to {}", path.display());
+        Ok(())
+    }
+
+    /// Stage the snapshot at `path` to replace this database the next time it is opened.
+    ///
+    /// The current store keeps working against the existing database until it is dropped.
+    /// Re-opening the store with the same [`StorageOption::Persistent`](super::StorageOption)
+    /// path and key will swap the snapshot in before any connection is established.
+    pub fn restore_from_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), StorageError> {
+        let db_path = self.opts.path().ok_or(StorageError::RestoreEphemeral)?;
+        let staged = Self::pending_restore_file(db_path);
+        std::fs::copy(path.as_ref(), &staged)?;
+        tracing::info!(
+            "staged snapshot {} to be restored on next open of {}",
+            path.as_ref().display(),
+            db_path
+        );
+        Ok(())
+    }
+
+    /// Swap in a staged snapshot for the database at `db_path`, if one exists.
+    pub(super) fn apply_pending_restore(
+        db_path: &str,
+        enc_key: Option<EncryptionKey>,
+    ) -> Result<(), StorageError> {
+        let staged = Self::pending_restore_file(db_path);
+        if !staged.try_exists()? {
+            return Ok(());
+        }
+        tracing::info!("restoring database {} from snapshot", db_path);
+
+        for sidecar in ["wal", "shm"] {
+            let sidecar = PathBuf::from(format!("{db_path}-{sidecar}"));
+            if sidecar.try_exists()? {
+                std::fs::remove_file(sidecar)?;
+            }
+        }
+        // The snapshot carries its own salt in the header, the old salt file no longer applies
+        let salt = EncryptedConnection::salt_file(db_path)?;
+        if salt.try_exists()? {
+            std::fs::remove_file(salt)?;
+        }
+        let previous = EncryptedConnection::persisted_cipher_options(db_path)?;
+        std::fs::rename(staged, db_path)?;
+
+        if let Some(previous) = previous {
+            let restored = CipherOptions {
+                plaintext_header_size: 0,
+                ..previous.clone()
+            };
+            EncryptedConnection::persist_cipher_options(db_path, &restored)?;
+            if let (Some(key), true) = (enc_key, previous.has_plaintext_header()) {
+                EncryptedConnection::migrate_to_plaintext_header(
+                    db_path,
+                    key,
+                    previous.plaintext_header_size,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pending_restore_file(db_path: &str) -> PathBuf {
+        PathBuf::from(format!("{db_path}.{PENDING_RESTORE_SUFFIX}"))
+    }
+
+    fn snapshot_tmp_file(path: &Path) -> PathBuf {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(".{SNAPSHOT_TMP_SUFFIX}"));
+        PathBuf::from(tmp_path)
+    }
+}
+
+#[derive(QueryableByName)]
+struct IntegrityCheck {
+    #[diesel(sql_type = Text)]
+    integrity_check: String,
+}
+
+#[derive(QueryableByName)]
+struct SchemaVersion {
+    #[diesel(sql_type = Nullable<Text>)]
+    version: Option<String>,
+}
+
+/// Open the snapshot at `path` next to the database of `conn`, and check that it is intact
+/// and migrated as far as the database is.
+fn validate_snapshot(conn: &mut SqliteConnection, path: &Path) -> Result<(), StorageError> {
+    // Without a key, the snapshot is attached with the key of the main database.
+    // `VACUUM INTO` never writes a plaintext header.
+    conn.batch_execute(&format!(
+        "ATTACH DATABASE '{}' AS snapshot; PRAGMA snapshot.cipher_plaintext_header_size = 0;",
+        escape_path(path)
+    ))?;
+    let validated = check_attached_snapshot(conn);
+    conn.batch_execute("DETACH DATABASE snapshot;")?;
+    validated
+}
+
+fn check_attached_snapshot(conn: &mut SqliteConnection) -> Result<(), StorageError> {
+    let integrity = diesel::sql_query("PRAGMA snapshot.integrity_check")
+        .load::<IntegrityCheck>(conn)?
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .collect::<Vec<_>>();
+    if integrity != ["ok"] {
+        return Err(StorageError::InvalidSnapshot(integrity.join("; ")));
+    }
+
+    let mut version = |schema: &str| {
+        diesel::sql_query(format!(
+            "SELECT MAX(version) AS version FROM {schema}.__diesel_schema_migrations"
+        ))
+        .get_result::<SchemaVersion>(conn)
+        .map(|row| row.version)
+    };
+    let expected = version("main")?;
+    let found = version("snapshot")?;
+    if found != expected {
+        return Err(StorageError::InvalidSnapshot(format!(
+            "schema version {found:?}, expected {expected:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Export the database of `conn` to `path`, encrypted with `key`, through a temporary file
+fn export_snapshot(
+    conn: &mut SqliteConnection,
+    path: &Path,
+    key: &EncryptionKey,
+) -> Result<(), StorageError> {
+    let tmp_path = EncryptedMessageStore::snapshot_tmp_file(path);
+    if tmp_path.try_exists()? {
+        // Left over by an interrupted snapshot
+        std::fs::remove_file(&tmp_path)?;
+    }
+    conn.batch_execute(&attach_snapshot(&tmp_path, key))?;
+    let exported = conn.batch_execute("SELECT sqlcipher_export('snapshot');");
+    conn.batch_execute("DETACH DATABASE snapshot;")?;
+    exported?;
+    std::fs::rename(&tmp_path, path)?;
+    tracing::debug!("wrote encrypted snapshot to {}", path.display());
+    Ok(())
+}
+
+impl EncryptedMessageStore {
+    /// A store kept in memory, loaded from the encrypted snapshot at `snapshot_path` if there
+    /// is one. Nothing is written to disk until [`Self::write_encrypted_snapshot`] is called,
+    /// usually by [`Self::spawn_periodic_snapshots`], so changes since the last snapshot are lost
+    /// if the process exits.
+    pub async fn new_in_memory_with_snapshot(
+        snapshot_path: &str,
+        snapshot_key: EncryptionKey,
+    ) -> Result<Self, StorageError> {
+        let opts = StorageOption::Ephemeral;
+        let db = NativeDb::new(&opts, None, SqliteExtensions::default())?;
+        let mut store = Self {
+            db,
+            opts,
+            cache: None,
+            cursor_store: None,
+            key_store_backend: None,
+            invariant_checker: None,
+        };
+        let snapshot_path = Path::new(snapshot_path);
+        if snapshot_path.try_exists()? {
+            tracing::info!(
+                "loading in-memory store from snapshot {}",
+                snapshot_path.display()
+            );
+            store.conn()?.raw_query(|conn| {
+                conn.batch_execute(&attach_snapshot(snapshot_path, &snapshot_key))?;
+                conn.batch_execute("SELECT sqlcipher_export('main', 'snapshot');")?;
+                conn.batch_execute("DETACH DATABASE snapshot;")
+            })?;
+        }
+        // Snapshots written by an older version are migrated like a database on disk
+        store.init_db(None)?;
+        Ok(store)
+    }
+
+    /// Write the whole database to `path`, encrypted with `key`. The previous snapshot at
+    /// `path` is replaced once the new one is complete.
+    #[tracing::instrument(level = "debug", skip(self, path, key), fields(path = %path.as_ref().display()))]
+    pub async fn write_encrypted_snapshot<P: AsRef<Path>>(
+        &self,
+        path: P,
+        key: &EncryptionKey,
+    ) -> Result<(), StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let key = *key;
+        // A named in-memory database lives as long as a connection to it is open
+        let copy = format!(
+            "file:snapshot-{:016x}?mode=memory&cache=shared",
+            rand::random::<u64>()
+        );
+        let mut export_conn = SqliteConnection::establish(&copy)?;
+        self.conn()?
+            .raw_query(|conn| conn.batch_execute(&format!("VACUUM INTO '{copy}';")))?;
+        tokio::task::spawn_blocking(move || export_snapshot(&mut export_conn, &path, &key))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Write an encrypted snapshot to `path` every `interval`, until the returned handle is
+    /// ended or the store is disconnected. Write a last snapshot with
+    /// [`Self::write_encrypted_snapshot`] when shutting down.
+    pub fn spawn_periodic_snapshots(
+        &self,
+        path: PathBuf,
+        key: EncryptionKey,
+        interval: std::time::Duration,
+    ) -> impl StreamHandle<StreamOutput = ()> {
+        let store = self.clone();
+        crate::spawn(None, async move {
+            loop {
+                xmtp_common::time::sleep(interval).await;
+                match store.write_encrypted_snapshot(&path, &key).await {
+                    Ok(()) => {}
+                    Err(StorageError::PoolNeedsConnection) => {
+                        tracing::warn!("store disconnected, stopping periodic snapshots");
+                        return;
+                    }
+                    Err(e) => tracing::error!("failed to write snapshot: {e}"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        storage::{identity::StoredIdentity, StorageOption},
+        Fetch, Store,
+    };
+    use xmtp_common::{rand_vec, tmp_path};
+
+    #[tokio::test]
+    async fn restores_snapshot_on_next_open() {
+        let db_path = tmp_path();
+        let snapshot_path = tmp_path();
+        let key = EncryptedMessageStore::generate_enc_key();
+        {
+            let store = EncryptedMessageStore::new(StorageOption::Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+            StoredIdentity::new("before".to_string(), rand_vec::<24>(), rand_vec::<24>())
+                .store(&store.conn().unwrap())
+                .unwrap();
+            store.snapshot(&snapshot_path).unwrap();
+
+            store
+                .conn()
+                .unwrap()
+                .raw_query(|conn| conn.batch_execute("DELETE FROM identity;"))
+                .unwrap();
+            store.restore_from_snapshot(&snapshot_path).unwrap();
+        }
+
+        let store = EncryptedMessageStore::new(StorageOption::Persistent(db_path.clone()), key)
+            .await
+            .unwrap();
+        let identity: StoredIdentity = store.conn().unwrap().fetch(&()).unwrap().unwrap();
+        assert_eq!(identity.inbox_id, "before");
+
+        std::fs::remove_file(snapshot_path).unwrap();
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_persists_through_snapshots() {
+        let snapshot_path = tmp_path();
+        let key = EncryptedMessageStore::generate_enc_key();
+        {
+            let store = EncryptedMessageStore::new_in_memory_with_snapshot(&snapshot_path, key)
+                .await
+                .unwrap();
+            StoredIdentity::new("in memory".to_string(), rand_vec::<24>(), rand_vec::<24>())
+                .store(&store.conn().unwrap())
+                .unwrap();
+            store
+                .write_encrypted_snapshot(&snapshot_path, &key)
+                .await
+                .unwrap();
+            // later snapshots replace the earlier ones
+            store
+                .write_encrypted_snapshot(&snapshot_path, &key)
+                .await
+                .unwrap();
+        }
+
+        let store = EncryptedMessageStore::new_in_memory_with_snapshot(&snapshot_path, key)
+            .await
+            .unwrap();
+        let identity: StoredIdentity = store.conn().unwrap().fetch(&()).unwrap().unwrap();
+        assert_eq!(identity.inbox_id, "in memory");
+
+        let wrong_key = EncryptedMessageStore::generate_enc_key();
+        assert!(
+            EncryptedMessageStore::new_in_memory_with_snapshot(&snapshot_path, wrong_key)
+                .await
+                .is_err()
+        );
+        std::fs::remove_file(snapshot_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshot_refuses_to_overwrite() {
+        let store = EncryptedMessageStore::new_test().await;
+        let snapshot_path = tmp_path();
+        std::fs::write(&snapshot_path, b"existing").unwrap();
+
+        assert!(matches!(
+            store.snapshot(&snapshot_path),
+            Err(StorageError::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+        std::fs::remove_file(snapshot_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_snapshot_behind_the_database() {
+        let store = EncryptedMessageStore::new_test().await;
+        let snapshot_path = tmp_path();
+        let mut conn = store.db.dedicated_conn().unwrap();
+        conn.batch_execute(&format!("VACUUM INTO '{snapshot_path}';"))
+            .unwrap();
+        validate_snapshot(&mut conn, Path::new(&snapshot_path)).unwrap();
+
+        conn.batch_execute(&format!(
+            "ATTACH DATABASE '{snapshot_path}' AS snapshot;
+            PRAGMA snapshot.cipher_plaintext_header_size = 0;
+            DELETE FROM snapshot.__diesel_schema_migrations
+                WHERE version = (SELECT MAX(version) FROM snapshot.__diesel_schema_migrations);
+            DETACH DATABASE snapshot;"
+        ))
+        .unwrap();
+        assert!(matches!(
+            validate_snapshot(&mut conn, Path::new(&snapshot_path)),
+            Err(StorageError::InvalidSnapshot(_))
+        ));
+        std::fs::remove_file(snapshot_path).unwrap();
+    }
+}
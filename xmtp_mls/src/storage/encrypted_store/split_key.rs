@@ -0,0 +1,271 @@
+//! Database keys split between the host and a file next to the database.
+//!
+//! The host keeps one half, e.g. in the platform keystore, and the other half, the pepper, is
+//! generated when the database is created and stored in a file next to it. The database key is
+//! derived from both halves with HKDF, so a copy of the database and its files is not enough to
+//! decrypt it without the keystore, and the keystore alone is not enough without the files.
+//!
+//! Either half can be rotated on its own with [`EncryptedMessageStore::rotate_host_key_half`]
+//! and [`EncryptedMessageStore::rotate_pepper`], which re-encrypt the database with the new key.
+use std::path::{Path, PathBuf};
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::{EncryptedConnection, EncryptedMessageStore, EncryptionKey};
+use crate::storage::{NotFound, StorageError};
+
+/// One half of a split key
+pub type KeyHalf = [u8; 32];
+
+const SPLIT_KEY_INFO: &[u8] = b"libxmtp split database key";
+
+/// Derive the database key from the half of the host and the pepper
+fn combine(host_half: &KeyHalf, pepper: &KeyHalf) -> EncryptionKey {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(pepper), host_half)
+        .expand(SPLIT_KEY_INFO, &mut key)
+        .expect("Length is correct");
+    key
+}
+
+fn read_pepper_file(path: &Path) -> Result<Option<KeyHalf>, StorageError> {
+    if !path.try_exists()? {
+        return Ok(None);
+    }
+    let hex_pepper = std::fs::read(path)?;
+    Ok(Some(<KeyHalf as hex::FromHex>::from_hex(hex_pepper)?))
+}
+
+fn read_pepper(db_path: &str) -> Result<Option<KeyHalf>, StorageError> {
+    read_pepper_file(&EncryptedConnection::pepper_file(db_path)?)
+}
+
+/// Write the pepper to a temporary file readable only by the owner, flushed to disk before it is
+/// renamed into place, so that the file always holds a complete pepper
+fn write_pepper_file(path: &Path, pepper: &KeyHalf) -> Result<(), StorageError> {
+    use std::io::Write;
+
+    let mut tmp_path = path.to_path_buf().into_os_string();
+    tmp_path.push(".tmp");
+    if Path::new(&tmp_path).try_exists()? {
+        // Left over by an interrupted write, possibly with other permissions
+        std::fs::remove_file(&tmp_path)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(hex::encode(pepper).as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(tmp_path, path)?;
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// The pepper a rotation re-encrypts the database with, kept next to the current pepper until
+/// the database was rekeyed
+fn staged_pepper_file(db_path: &str) -> Result<PathBuf, StorageError> {
+    let mut path = EncryptedConnection::pepper_file(db_path)?.into_os_string();
+    path.push(".next");
+    Ok(path.into())
+}
+
+/// Finish a pepper rotation that was interrupted, keeping the pepper the database is encrypted
+/// with. Both peppers are kept if neither of them opens the database with `host_half`.
+fn recover_staged_pepper(db_path: &str, host_half: &KeyHalf) -> Result<(), StorageError> {
+    let staged_path = staged_pepper_file(db_path)?;
+    let Some(staged) = read_pepper_file(&staged_path)? else {
+        return Ok(());
+    };
+    let pepper_path = EncryptedConnection::pepper_file(db_path)?;
+    if !PathBuf::from(db_path).try_exists()? {
+        std::fs::remove_file(staged_path)?;
+        return Ok(());
+    }
+    if EncryptedConnection::open_existing(db_path, combine(host_half, &staged)).is_ok() {
+        tracing::info!("completing an interrupted pepper rotation");
+        std::fs::rename(staged_path, pepper_path)?;
+        return Ok(());
+    }
+    let pepper = read_pepper_file(&pepper_path)?.ok_or(NotFound::Pepper(db_path.to_string()))?;
+    // Fails without touching either pepper if the host half is wrong
+    EncryptedConnection::open_existing(db_path, combine(host_half, &pepper))?;
+    tracing::info!("discarding the pepper of an interrupted rotation");
+    std::fs::remove_file(staged_path)?;
+    Ok(())
+}
+
+impl EncryptedMessageStore {
+    /// The key of the database at `db_path`, derived from the half of the host and the pepper
+    /// stored next to the database. A pepper is generated for a new database.
+    ///
+    /// Fails if the database exists without a pepper, since the key cannot be derived.
+    pub fn split_key(db_path: &str, host_half: &KeyHalf) -> Result<EncryptionKey, StorageError> {
+        recover_staged_pepper(db_path, host_half)?;
+        let pepper = match read_pepper(db_path)? {
+            Some(pepper) => pepper,
+            None if PathBuf::from(db_path).try_exists()? => {
+                return Err(NotFound::Pepper(db_path.to_string()).into());
+            }
+            None => {
+                let pepper = xmtp_common::rand_array::<32>();
+                write_pepper_file(&EncryptedConnection::pepper_file(db_path)?, &pepper)?;
+                pepper
+            }
+        };
+        Ok(combine(host_half, &pepper))
+    }
+
+    /// Replace the half of the host, re-encrypting the database with the new key.
+    ///
+    /// Must be called while no store has the database open.
+    pub fn rotate_host_key_half(
+        db_path: &str,
+        host_half: &KeyHalf,
+        new_host_half: &KeyHalf,
+    ) -> Result<(), StorageError> {
+        let pepper = read_pepper(db_path)?.ok_or(NotFound::Pepper(db_path.to_string()))?;
+        EncryptedConnection::rekey(
+            db_path,
+            combine(host_half, &pepper),
+            combine(new_host_half, &pepper),
+        )
+    }
+
+    /// Replace the pepper with a new random one, re-encrypting the database with the new key.
+    ///
+    /// The new pepper is staged next to the current one before the database is rekeyed, and only
+    /// replaces it once the rekey completed. If the rotation is interrupted, the next
+    /// [`split_key`](Self::split_key) keeps whichever pepper opens the database.
+    ///
+    /// Must be called while no store has the database open.
+    pub fn rotate_pepper(db_path: &str, host_half: &KeyHalf) -> Result<(), StorageError> {
+        recover_staged_pepper(db_path, host_half)?;
+        let pepper = read_pepper(db_path)?.ok_or(NotFound::Pepper(db_path.to_string()))?;
+        let new_pepper = xmtp_common::rand_array::<32>();
+        let staged_path = staged_pepper_file(db_path)?;
+        write_pepper_file(&staged_path, &new_pepper)?;
+        EncryptedConnection::rekey(
+            db_path,
+            combine(host_half, &pepper),
+            combine(host_half, &new_pepper),
+        )?;
+        std::fs::rename(staged_path, EncryptedConnection::pepper_file(db_path)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{SqlCipherError, StorageOption::Persistent};
+    use xmtp_common::tmp_path;
+
+    #[tokio::test]
+    async fn test_split_key_rotation() {
+        let db_path = tmp_path();
+        let host_half = [1u8; 32];
+        let key = EncryptedMessageStore::split_key(&db_path, &host_half).unwrap();
+        assert_ne!(key, host_half);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let pepper_path = EncryptedConnection::pepper_file(&db_path).unwrap();
+            let mode = std::fs::metadata(pepper_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        {
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            EncryptedMessageStore::split_key(&db_path, &host_half).unwrap(),
+            key
+        );
+
+        let new_host_half = [2u8; 32];
+        EncryptedMessageStore::rotate_host_key_half(&db_path, &host_half, &new_host_half).unwrap();
+        let res = EncryptedMessageStore::new(Persistent(db_path.clone()), key).await;
+        assert!(matches!(
+            res.err(),
+            Some(StorageError::SqlCipher(SqlCipherError::KeyIncorrect))
+        ));
+        let key = EncryptedMessageStore::split_key(&db_path, &new_host_half).unwrap();
+        {
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+        }
+
+        EncryptedMessageStore::rotate_pepper(&db_path, &new_host_half).unwrap();
+        let rotated = EncryptedMessageStore::split_key(&db_path, &new_host_half).unwrap();
+        assert_ne!(rotated, key);
+        let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), rotated)
+            .await
+            .unwrap();
+
+        // the host half alone does not open the database
+        std::fs::remove_file(EncryptedConnection::pepper_file(&db_path).unwrap()).unwrap();
+        assert!(matches!(
+            EncryptedMessageStore::split_key(&db_path, &new_host_half),
+            Err(StorageError::NotFound(NotFound::Pepper(_)))
+        ));
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_pepper_rotation() {
+        let db_path = tmp_path();
+        let host_half = [1u8; 32];
+        let key = EncryptedMessageStore::split_key(&db_path, &host_half).unwrap();
+        {
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+        }
+        let pepper = read_pepper(&db_path).unwrap().unwrap();
+        let staged_path = staged_pepper_file(&db_path).unwrap();
+
+        // interrupted before the rekey, the current pepper is kept
+        write_pepper_file(&staged_path, &[3u8; 32]).unwrap();
+        assert_eq!(
+            EncryptedMessageStore::split_key(&db_path, &host_half).unwrap(),
+            key
+        );
+        assert!(!staged_path.exists());
+
+        // interrupted after the rekey, the staged pepper replaces the current one
+        let new_pepper = [4u8; 32];
+        write_pepper_file(&staged_path, &new_pepper).unwrap();
+        EncryptedConnection::rekey(
+            &db_path,
+            combine(&host_half, &pepper),
+            combine(&host_half, &new_pepper),
+        )
+        .unwrap();
+        let rotated = EncryptedMessageStore::split_key(&db_path, &host_half).unwrap();
+        assert_eq!(rotated, combine(&host_half, &new_pepper));
+        assert!(!staged_path.exists());
+        let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), rotated)
+            .await
+            .unwrap();
+
+        // a wrong host half does not discard either pepper
+        write_pepper_file(&staged_path, &[5u8; 32]).unwrap();
+        assert!(EncryptedMessageStore::split_key(&db_path, &[9u8; 32]).is_err());
+        assert!(staged_path.exists());
+        assert_eq!(
+            EncryptedMessageStore::split_key(&db_path, &host_half).unwrap(),
+            rotated
+        );
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+}
@@ -12,13 +12,21 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::storage::{NotFound, StorageError};
+use crate::storage::{NotFound, SqlCipherError, StorageError};
 
-use super::{EncryptionKey, StorageOption};
+use super::{
+    busy::busy_timeout_pragma, passphrase::PassphraseKdf, CipherOptions, EncryptionKey,
+    StorageOption,
+};
 
 pub type Salt = [u8; 16];
-const PLAINTEXT_HEADER_SIZE: usize = 32;
 const SALT_FILE_NAME: &str = "sqlcipher_salt";
+const CIPHER_OPTIONS_FILE_NAME: &str = "sqlcipher_options.json";
+const PASSPHRASE_KDF_FILE_NAME: &str = "passphrase_kdf.json";
+const PEPPER_FILE_NAME: &str = "sqlcipher_pepper";
+const SQLITE3_PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// Smallest page size SQLite supports. Any non-empty database is at least one page long.
+const MIN_PAGE_SIZE: u64 = 512;
 
 // For PRAGMA query log statements
 #[derive(QueryableByName, Debug)]
@@ -38,19 +46,33 @@ struct CipherProviderVersion {
 #[derive(Clone, Debug, zeroize::ZeroizeOnDrop)]
 pub struct EncryptedConnection {
     key: EncryptionKey,
-    /// We don't store the salt for Ephemeral Dbs
+    /// We don't store the salt for Ephemeral Dbs,
+    /// or for databases that keep it in an encrypted header
     salt: Option<Salt>,
+    #[zeroize(skip)]
+    cipher: CipherOptions,
 }
 
 impl EncryptedConnection {
     /// Creates a file for the salt and stores it
     pub fn new(key: EncryptionKey, opts: &StorageOption) -> Result<Self, StorageError> {
-        use super::StorageOption::*;
         Self::check_for_sqlcipher(opts)?;
-
-        let salt = match opts {
-            Ephemeral => None,
-            Persistent(ref db_path) => {
+        let cipher = Self::resolve_cipher_options(opts)?;
+
+        let salt = match opts.path() {
+            None => None,
+            Some(db_path) if !cipher.has_plaintext_header() => {
+                if !PathBuf::from(db_path).try_exists()? {
+                    tracing::debug!(
+                        "creating new sqlcipher db=[{}] with encrypted header",
+                        db_path
+                    );
+                    Self::create(db_path, key, &cipher, &mut [0u8; 16])?;
+                }
+                Self::persist_cipher_options(db_path, &cipher)?;
+                None
+            }
+            Some(db_path) => {
                 let mut salt = [0u8; 16];
                 let db_pathbuf = PathBuf::from(db_path);
                 let salt_path = Self::salt_file(db_path)?;
@@ -70,14 +92,10 @@ impl EncryptedConnection {
                             file.bytes().take(32).collect::<Result<Vec<u8>, _>>()?,
                         )?;
                     }
-                    // the db exists and needs to be migrated
+                    // the db exists with an encrypted header, and has to be migrated
+                    // explicitly with `migrate_to_plaintext_header` first
                     (false, true) => {
-                        tracing::debug!(
-                            "migrating sqlcipher db=[{}] to plaintext header with salt=[{}]",
-                            db_pathbuf.display(),
-                            salt_path.display()
-                        );
-                        Self::migrate(db_path, key, &mut salt)?;
+                        return Err(SqlCipherError::PlaintextHeaderMigrationRequired.into());
                     }
                     // the db doesn't exist yet and needs to be created
                     (false, false) => {
@@ -86,7 +104,7 @@ impl EncryptedConnection {
                             db_pathbuf.display(),
                             salt_path.display()
                         );
-                        Self::create(db_path, key, &mut salt)?;
+                        Self::create(db_path, key, &cipher, &mut salt)?;
                     }
                     // the db doesn't exist but the salt does
                     // This generally doesn't make sense & shouldn't happen.
@@ -98,19 +116,186 @@ impl EncryptedConnection {
                             salt_path.display(),
                         );
                         std::fs::remove_file(salt_path)?;
-                        Self::create(db_path, key, &mut salt)?;
+                        Self::create(db_path, key, &cipher, &mut salt)?;
                     }
                 }
+                Self::persist_cipher_options(db_path, &cipher)?;
                 Some(salt)
             }
         };
 
-        Ok(Self { key, salt })
+        Ok(Self { key, salt, cipher })
+    }
+
+    /// Migrate an existing database with an encrypted header to a plaintext header of
+    /// `plaintext_header_size` bytes, moving the salt into a file next to the database.
+    /// A plaintext header lets iOS recognize the file as a SQLite database and keep it open
+    /// while the app is suspended in the background.
+    ///
+    /// Must be called while no store has the database open. Does nothing if the database
+    /// already has a plaintext header.
+    pub fn migrate_to_plaintext_header(
+        db_path: &str,
+        key: EncryptionKey,
+        plaintext_header_size: u32,
+    ) -> Result<(), StorageError> {
+        let opts = StorageOption::Persistent(db_path.to_string());
+        let current = Self::resolve_cipher_options(&opts)?;
+        if current.has_plaintext_header() {
+            return Ok(());
+        }
+        let target = CipherOptions {
+            plaintext_header_size,
+            ..current
+        };
+        target.validate()?;
+
+        tracing::info!(
+            "migrating sqlcipher db=[{}] to a plaintext header of {} bytes",
+            db_path,
+            plaintext_header_size
+        );
+        let salt_path = Self::salt_file(db_path)?;
+        if salt_path.try_exists()? {
+            std::fs::remove_file(salt_path)?;
+        }
+        Self::migrate(&db_path.to_string(), key, &target, &mut [0u8; 16]).map_err(|e| match e {
+            StorageError::DieselResult(_) => diagnose(&opts, &key, None).into(),
+            e => e,
+        })?;
+        Self::persist_cipher_options(db_path, &target)?;
+        Ok(())
+    }
+
+    /// Pick the SQLCipher parameters for this database.
+    /// Parameters persisted when the database was created take precedence over the defaults,
+    /// and must match any parameters the caller asked for explicitly.
+    /// Databases created before parameters were persisted keep their salt in the header,
+    /// unless they already have a salt file.
+    fn resolve_cipher_options(opts: &StorageOption) -> Result<CipherOptions, StorageError> {
+        let mut legacy = false;
+        let persisted = match opts.path() {
+            Some(db_path) if PathBuf::from(db_path).try_exists()? => {
+                let persisted = Self::persisted_cipher_options(db_path)?;
+                legacy = persisted.is_none() && !Self::salt_file(db_path)?.try_exists()?;
+                persisted
+            }
+            _ => None,
+        };
+
+        let cipher = match (opts.cipher_options(), persisted) {
+            (Some(requested), Some(persisted)) if *requested != persisted => {
+                return Err(SqlCipherError::CipherOptionsMismatch.into());
+            }
+            (Some(requested), _) => requested.clone(),
+            (None, Some(persisted)) => persisted,
+            (None, None) if legacy => CipherOptions {
+                plaintext_header_size: 0,
+                ..Default::default()
+            },
+            (None, None) => CipherOptions::default(),
+        };
+        cipher.validate()?;
+        Ok(cipher)
+    }
+
+    /// Read the SQLCipher parameters persisted next to the database, if any
+    pub(super) fn persisted_cipher_options(
+        db_path: &str,
+    ) -> Result<Option<CipherOptions>, StorageError> {
+        let options_path = Self::cipher_options_file(db_path)?;
+        if !options_path.try_exists()? {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(options_path)?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| StorageError::Deserialization(e.to_string()))
     }
 
-    /// create a new database + salt file.
+    /// Write the SQLCipher parameters next to the database so later opens use the same ones
+    pub(super) fn persist_cipher_options(
+        db_path: &str,
+        cipher: &CipherOptions,
+    ) -> Result<(), StorageError> {
+        let options_path = Self::cipher_options_file(db_path)?;
+        let json =
+            serde_json::to_vec(cipher).map_err(|e| StorageError::Serialization(e.to_string()))?;
+        std::fs::write(options_path, json)?;
+        Ok(())
+    }
+
+    /// A single connection to the existing database at `db_path`, encrypted with `key`,
+    /// without running migrations
+    pub(super) fn open_existing(
+        db_path: &str,
+        key: EncryptionKey,
+    ) -> Result<SqliteConnection, StorageError> {
+        if !PathBuf::from(db_path).try_exists()? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no database at {db_path}"),
+            )
+            .into());
+        }
+        let opts = StorageOption::Persistent(db_path.to_string());
+        let current = Self::new(key, &opts)?;
+        let mut conn = SqliteConnection::establish(db_path)?;
+        conn.batch_execute(&format!(
+            "{}\nSELECT count(*) FROM sqlite_master;",
+            current.pragmas()
+        ))
+        .map_err(|_| StorageError::from(diagnose(&opts, &key, current.salt.as_ref())))?;
+        Ok(conn)
+    }
+
+    /// Re-encrypt the existing database at `db_path`, encrypted with `key`, with `new_key`.
+    ///
+    /// Must be called while no store has the database open.
+    pub fn rekey(
+        db_path: &str,
+        key: EncryptionKey,
+        new_key: EncryptionKey,
+    ) -> Result<(), StorageError> {
+        let conn = &mut Self::open_existing(db_path, key)?;
+        conn.batch_execute(&format!(r#"PRAGMA rekey = "x'{}'";"#, hex::encode(new_key)))?;
+        Ok(())
+    }
+
+    /// Read the passphrase salt and parameters persisted next to the database, if any
+    pub(super) fn persisted_passphrase_kdf(
+        db_path: &str,
+    ) -> Result<Option<PassphraseKdf>, StorageError> {
+        let kdf_path = Self::passphrase_kdf_file(db_path)?;
+        if !kdf_path.try_exists()? {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(kdf_path)?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| StorageError::Deserialization(e.to_string()))
+    }
+
+    /// Write the passphrase salt and parameters next to the database
+    pub(super) fn persist_passphrase_kdf(
+        db_path: &str,
+        kdf: &PassphraseKdf,
+    ) -> Result<(), StorageError> {
+        let kdf_path = Self::passphrase_kdf_file(db_path)?;
+        let json =
+            serde_json::to_vec(kdf).map_err(|e| StorageError::Serialization(e.to_string()))?;
+        std::fs::write(kdf_path, json)?;
+        Ok(())
+    }
+
+    /// create a new database, and a salt file if the header is plaintext.
     /// writes the 16-bytes hex-encoded salt to `salt`
-    fn create(path: &String, key: EncryptionKey, salt: &mut [u8]) -> Result<(), StorageError> {
+    fn create(
+        path: &String,
+        key: EncryptionKey,
+        cipher: &CipherOptions,
+        salt: &mut [u8],
+    ) -> Result<(), StorageError> {
         let conn = &mut SqliteConnection::establish(path)?;
         conn.batch_execute(&format!(
             r#"
@@ -119,27 +304,35 @@ impl EncryptedConnection {
             PRAGMA journal_mode = WAL;
         "#,
             pragma_key(hex::encode(key)),
-            pragma_plaintext_header()
+            cipher.pragmas()
         ))?;
 
-        Self::write_salt(path, conn, salt)?;
+        if cipher.has_plaintext_header() {
+            Self::write_salt(path, conn, salt)?;
+        }
         Ok(())
     }
 
     /// Executes the steps outlined in the [SQLCipher Docs](https://www.zetetic.net/sqlcipher/sqlcipher-api/#cipher_plaintext_header_size)
     /// Migrates the database to `cipher_plaintext_header_size` and returns the salt after
     /// persisting it to SALT_FILE_NAME.
-    ///
-    /// if the salt file already exists, deletes it.
-    fn migrate(path: &String, key: EncryptionKey, salt: &mut [u8]) -> Result<(), StorageError> {
+    fn migrate(
+        path: &String,
+        key: EncryptionKey,
+        cipher: &CipherOptions,
+        salt: &mut [u8],
+    ) -> Result<(), StorageError> {
         let conn = &mut SqliteConnection::establish(path)?;
 
+        // the existing header is still encrypted, so everything but the header size applies
         conn.batch_execute(&format!(
             r#"
             {}
+            {}
             select count(*) from sqlite_master; -- trigger header read, currently it is encrypted
         "#,
-            pragma_key(hex::encode(key))
+            pragma_key(hex::encode(key)),
+            cipher.settings_pragmas()
         ))?;
 
         // get the salt and save it for later use
@@ -147,10 +340,10 @@ impl EncryptedConnection {
 
         conn.batch_execute(&format!(
             r#"
-            {}
+            PRAGMA cipher_plaintext_header_size = {};
             PRAGMA user_version = 1; -- force header write
         "#,
-            pragma_plaintext_header()
+            cipher.plaintext_header_size
         ))?;
 
         Ok(())
@@ -190,6 +383,27 @@ impl EncryptedConnection {
     /// If the db file is named `sqlite3_xmtp_db.db3`, the salt file would
     /// be stored next to this file as `sqlite3_xmtp_db.db3.sqlcipher_salt`
     pub(crate) fn salt_file<P: AsRef<Path>>(db_path: P) -> std::io::Result<PathBuf> {
+        Self::sidecar_file(db_path, SALT_FILE_NAME)
+    }
+
+    /// Options file is stored next to the sqlite3 db3 file as `{db_file_name}.CIPHER_OPTIONS_FILE_NAME`.
+    pub(crate) fn cipher_options_file<P: AsRef<Path>>(db_path: P) -> std::io::Result<PathBuf> {
+        Self::sidecar_file(db_path, CIPHER_OPTIONS_FILE_NAME)
+    }
+
+    /// Passphrase salt and parameters are stored next to the sqlite3 db3 file as
+    /// `{db_file_name}.PASSPHRASE_KDF_FILE_NAME`.
+    pub(crate) fn passphrase_kdf_file<P: AsRef<Path>>(db_path: P) -> std::io::Result<PathBuf> {
+        Self::sidecar_file(db_path, PASSPHRASE_KDF_FILE_NAME)
+    }
+
+    /// The half of a split key kept with the database is stored next to the sqlite3 db3 file
+    /// as `{db_file_name}.PEPPER_FILE_NAME`.
+    pub(crate) fn pepper_file<P: AsRef<Path>>(db_path: P) -> std::io::Result<PathBuf> {
+        Self::sidecar_file(db_path, PEPPER_FILE_NAME)
+    }
+
+    fn sidecar_file<P: AsRef<Path>>(db_path: P, suffix: &str) -> std::io::Result<PathBuf> {
         let db_path: &Path = db_path.as_ref();
         let name = db_path.file_name().ok_or(std::io::Error::new(
             std::io::ErrorKind::NotFound,
@@ -199,26 +413,26 @@ impl EncryptedConnection {
             std::io::ErrorKind::NotFound,
             "Parent directory could not be found",
         ))?;
-        Ok(db_path.join(format!("{}.{}", name.to_string_lossy(), SALT_FILE_NAME)))
+        Ok(db_path.join(format!("{}.{}", name.to_string_lossy(), suffix)))
     }
 
     /// Output the corect order of PRAGMAS to instantiate a connection
     fn pragmas(&self) -> impl Display {
-        let Self { ref key, ref salt } = self;
+        let Self {
+            ref key,
+            ref salt,
+            ref cipher,
+        } = self;
 
         if let Some(s) = salt {
             format!(
                 "{}\n{}\n{}",
                 pragma_key(hex::encode(key)),
-                pragma_plaintext_header(),
+                cipher.pragmas(),
                 pragma_salt(hex::encode(s))
             )
         } else {
-            format!(
-                "{}\n{}",
-                pragma_key(hex::encode(key)),
-                pragma_plaintext_header()
-            )
+            format!("{}\n{}", pragma_key(hex::encode(key)), cipher.pragmas())
         }
     }
 
@@ -230,7 +444,7 @@ impl EncryptedConnection {
         let conn = &mut opts.conn()?;
         let cipher_version = sql_query("PRAGMA cipher_version").load::<CipherVersion>(conn)?;
         if cipher_version.is_empty() {
-            return Err(StorageError::SqlCipherNotLoaded);
+            return Err(SqlCipherError::NotLoaded.into());
         }
         Ok(())
     }
@@ -242,7 +456,7 @@ impl super::native::ValidatedConnection for EncryptedConnection {
 
         let cipher_version = sql_query("PRAGMA cipher_version").load::<CipherVersion>(conn)?;
         if cipher_version.is_empty() {
-            return Err(StorageError::SqlCipherNotLoaded);
+            return Err(SqlCipherError::NotLoaded.into());
         }
 
         // test the key according to
@@ -252,7 +466,7 @@ impl super::native::ValidatedConnection for EncryptedConnection {
             SELECT count(*) FROM sqlite_master;",
             self.pragmas()
         ))
-        .map_err(|_| StorageError::SqlCipherKeyIncorrect)?;
+        .map_err(|_| diagnose(opts, &self.key, self.salt.as_ref()))?;
 
         let CipherProviderVersion {
             cipher_provider_version,
@@ -279,17 +493,60 @@ impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
     for EncryptedConnection
 {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
-        conn.batch_execute(&format!(
-            "{}
-            PRAGMA busy_timeout = 5000;",
-            self.pragmas()
-        ))
-        .map_err(diesel::r2d2::Error::QueryError)?;
+        conn.batch_execute(&format!("{}\n{}", self.pragmas(), busy_timeout_pragma()))
+            .map_err(diesel::r2d2::Error::QueryError)?;
 
         Ok(())
     }
 }
 
+/// Figure out why SQLCipher refused to open a database, so callers get an actionable error
+/// rather than SQLite's generic "file is not a database".
+fn diagnose(opts: &StorageOption, key: &EncryptionKey, salt: Option<&Salt>) -> SqlCipherError {
+    let Some(path) = opts.path() else {
+        return SqlCipherError::KeyIncorrect;
+    };
+    let len = match std::fs::metadata(path) {
+        Ok(m) => m.len(),
+        Err(_) => return SqlCipherError::NotADatabase(path.clone()),
+    };
+    if len > 0 && len < MIN_PAGE_SIZE {
+        return SqlCipherError::NotADatabase(path.clone());
+    }
+
+    let mut header = [0u8; 16];
+    let has_plaintext_header = File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok()
+        && &header == SQLITE3_PLAINTEXT_HEADER;
+    // we keep the salt outside of the database only once the header is plaintext
+    if salt.is_some() && !has_plaintext_header {
+        return SqlCipherError::CorruptedHeader;
+    }
+
+    // databases written by older major versions of SQLCipher only open in compatibility mode
+    if !has_plaintext_header {
+        for compatibility in (1..=3).rev() {
+            let opened = SqliteConnection::establish(path)
+                .and_then(|mut conn| {
+                    conn.batch_execute(&format!(
+                        "{}
+                        PRAGMA cipher_compatibility = {compatibility};
+                        SELECT count(*) FROM sqlite_master;",
+                        pragma_key(hex::encode(key))
+                    ))
+                    .map_err(|e| diesel::ConnectionError::BadConnection(e.to_string()))
+                })
+                .is_ok();
+            if opened {
+                return SqlCipherError::UnsupportedCipherVersion { compatibility };
+            }
+        }
+    }
+
+    SqlCipherError::KeyIncorrect
+}
+
 fn pragma_key(key: impl Display) -> impl Display {
     format!(r#"PRAGMA key = "x'{key}'";"#)
 }
@@ -298,10 +555,6 @@ fn pragma_salt(salt: impl Display) -> impl Display {
     format!(r#"PRAGMA cipher_salt="x'{salt}'";"#)
 }
 
-fn pragma_plaintext_header() -> impl Display {
-    format!(r#"PRAGMA cipher_plaintext_header_size={PLAINTEXT_HEADER_SIZE};"#)
-}
-
 #[cfg(test)]
 mod tests {
     use crate::storage::EncryptedMessageStore;
@@ -310,7 +563,6 @@ mod tests {
     use xmtp_common::tmp_path;
 
     use super::*;
-    const SQLITE3_PLAINTEXT_HEADER: &str = "SQLite format 3\0";
     use StorageOption::*;
 
     #[tokio::test]
@@ -333,10 +585,7 @@ mod tests {
             let mut file = File::open(&db_path).unwrap();
             file.read_exact(&mut plaintext_header).unwrap();
 
-            assert_eq!(
-                SQLITE3_PLAINTEXT_HEADER,
-                String::from_utf8(plaintext_header.into()).unwrap()
-            );
+            assert_eq!(SQLITE3_PLAINTEXT_HEADER, &plaintext_header);
         }
         EncryptedMessageStore::remove_db_files(db_path)
     }
@@ -365,11 +614,17 @@ mod tests {
             let mut plaintext_header = [0; 16];
             let mut file = File::open(&db_path).unwrap();
             file.read_exact(&mut plaintext_header).unwrap();
-            assert!(String::from_utf8_lossy(&plaintext_header) != SQLITE3_PLAINTEXT_HEADER);
+            assert!(&plaintext_header != SQLITE3_PLAINTEXT_HEADER);
 
-            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
-                .await
-                .unwrap();
+            // opening does not migrate implicitly
+            {
+                let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                    .await
+                    .unwrap();
+            }
+            assert!(!EncryptedConnection::salt_file(&db_path).unwrap().exists());
+
+            EncryptedMessageStore::migrate_to_plaintext_header(&db_path, key, 32).unwrap();
 
             assert!(EncryptedConnection::salt_file(&db_path).unwrap().exists());
             let bytes = std::fs::read(EncryptedConnection::salt_file(&db_path).unwrap()).unwrap();
@@ -380,11 +635,143 @@ mod tests {
             let mut file = File::open(&db_path).unwrap();
             file.read_exact(&mut plaintext_header).unwrap();
 
-            assert_eq!(
-                SQLITE3_PLAINTEXT_HEADER,
-                String::from_utf8(plaintext_header.into()).unwrap()
-            );
+            assert_eq!(SQLITE3_PLAINTEXT_HEADER, &plaintext_header);
+
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
         }
         EncryptedMessageStore::remove_db_files(db_path)
     }
+
+    #[tokio::test]
+    async fn test_header_size_is_persisted() {
+        let db_path = tmp_path();
+        let key = EncryptedMessageStore::generate_enc_key();
+        {
+            let _ = EncryptedMessageStore::new(
+                PersistentWithCipher(
+                    db_path.clone(),
+                    CipherOptions {
+                        plaintext_header_size: 0,
+                        ..Default::default()
+                    },
+                ),
+                key,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(!EncryptedConnection::salt_file(&db_path).unwrap().exists());
+
+        let res = EncryptedMessageStore::new(
+            PersistentWithCipher(db_path.clone(), CipherOptions::default()),
+            key,
+        )
+        .await;
+        assert!(matches!(
+            res.err(),
+            Some(StorageError::SqlCipher(
+                SqlCipherError::CipherOptionsMismatch
+            ))
+        ));
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+
+    #[tokio::test]
+    async fn test_reports_file_not_a_database() {
+        let db_path = tmp_path();
+        std::fs::write(&db_path, b"definitely not sqlite").unwrap();
+
+        let res = EncryptedMessageStore::new(
+            Persistent(db_path.clone()),
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await;
+        assert!(matches!(
+            res.err(),
+            Some(StorageError::SqlCipher(SqlCipherError::NotADatabase(_)))
+        ));
+        std::fs::remove_file(db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cipher_options_are_persisted() {
+        let db_path = tmp_path();
+        let key = EncryptedMessageStore::generate_enc_key();
+        let cipher = CipherOptions {
+            kdf_iter: Some(64_000),
+            page_size: Some(8192),
+            ..Default::default()
+        };
+        {
+            let _ = EncryptedMessageStore::new(
+                PersistentWithCipher(db_path.clone(), cipher.clone()),
+                key,
+            )
+            .await
+            .unwrap();
+        }
+        // re-opening without options uses the persisted ones
+        {
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+        }
+
+        let res = EncryptedMessageStore::new(
+            PersistentWithCipher(db_path.clone(), CipherOptions::default()),
+            key,
+        )
+        .await;
+        assert!(matches!(
+            res.err(),
+            Some(StorageError::SqlCipher(
+                SqlCipherError::CipherOptionsMismatch
+            ))
+        ));
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
+
+    #[tokio::test]
+    async fn test_key_from_passphrase() {
+        use crate::storage::PassphraseKdfParams;
+
+        let db_path = tmp_path();
+        let params = PassphraseKdfParams {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let key =
+            EncryptedMessageStore::key_from_passphrase(&db_path, "hunter2", Some(params.clone()))
+                .unwrap();
+        {
+            let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), key)
+                .await
+                .unwrap();
+        }
+        // the persisted salt and parameters derive the same key
+        let again = EncryptedMessageStore::key_from_passphrase(&db_path, "hunter2", None).unwrap();
+        assert_eq!(key, again);
+        let _ = EncryptedMessageStore::new(Persistent(db_path.clone()), again)
+            .await
+            .unwrap();
+
+        let wrong = EncryptedMessageStore::key_from_passphrase(&db_path, "hunter3", None).unwrap();
+        assert!(
+            EncryptedMessageStore::new(Persistent(db_path.clone()), wrong)
+                .await
+                .is_err()
+        );
+        assert!(matches!(
+            EncryptedMessageStore::key_from_passphrase(
+                &db_path,
+                "hunter2",
+                Some(PassphraseKdfParams::default())
+            ),
+            Err(StorageError::SqlCipher(SqlCipherError::KeyDerivation(_)))
+        ));
+        EncryptedMessageStore::remove_db_files(db_path)
+    }
 }
@@ -0,0 +1,241 @@
+//! Where databases are kept when an app hosts several inboxes or environments.
+//!
+//! Every database in a directory is named after its environment, inbox id and installation
+//! nonce, see [`db_file_name`], so the stores of a directory can be listed with
+//! [`list_stores`] without opening them.
+use std::path::{Path, PathBuf};
+
+use diesel::{prelude::*, Connection};
+use xmtp_id::InboxId;
+
+use super::{schema::identity, EncryptedConnection, EncryptionKey, SqliteConnection};
+use crate::storage::StorageError;
+
+const DB_FILE_PREFIX: &str = "xmtp";
+const DB_FILE_EXTENSION: &str = "db3";
+
+/// A database found in a directory by [`list_stores`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreEntry {
+    pub path: PathBuf,
+    pub env: String,
+    pub inbox_id: InboxId,
+    pub installation_nonce: u64,
+}
+
+fn invalid_input(reason: String) -> StorageError {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, reason).into()
+}
+
+/// The name of the database of an installation of `inbox_id` in `env`:
+/// `xmtp-{env}-{inbox_id}-{installation_nonce}.db3`. The environment must be alphanumeric.
+pub fn db_file_name(
+    env: &str,
+    inbox_id: &str,
+    installation_nonce: u64,
+) -> Result<String, StorageError> {
+    if env.is_empty() || !env.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid_input(format!("invalid environment name {env:?}")));
+    }
+    if inbox_id.is_empty() || !inbox_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid_input(format!("invalid inbox id {inbox_id:?}")));
+    }
+    Ok(format!(
+        "{DB_FILE_PREFIX}-{env}-{}-{installation_nonce}.{DB_FILE_EXTENSION}",
+        inbox_id.to_lowercase()
+    ))
+}
+
+/// The path of the database of an installation of `inbox_id` in `env`, in `dir`
+pub fn db_path(
+    dir: impl AsRef<Path>,
+    env: &str,
+    inbox_id: &str,
+    installation_nonce: u64,
+) -> Result<PathBuf, StorageError> {
+    Ok(dir
+        .as_ref()
+        .join(db_file_name(env, inbox_id, installation_nonce)?))
+}
+
+/// Parse a path named by [`db_file_name`]. Returns `None` for any other file.
+pub fn parse_db_path(path: impl AsRef<Path>) -> Option<StoreEntry> {
+    let path = path.as_ref();
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(&format!(".{DB_FILE_EXTENSION}"))?;
+    let mut parts = stem.split('-');
+    let (Some(DB_FILE_PREFIX), Some(env), Some(inbox_id), Some(nonce), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return None;
+    };
+    let installation_nonce = nonce.parse().ok()?;
+    // Only names that round trip are ours
+    if db_file_name(env, inbox_id, installation_nonce).ok()? != name {
+        return None;
+    }
+    Some(StoreEntry {
+        path: path.to_path_buf(),
+        env: env.to_string(),
+        inbox_id: inbox_id.to_string(),
+        installation_nonce,
+    })
+}
+
+/// The databases in `dir`, sorted by path
+pub fn list_stores(dir: impl AsRef<Path>) -> Result<Vec<StoreEntry>, StorageError> {
+    let mut stores = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        if let Some(store) = parse_db_path(entry.path()) {
+            stores.push(store);
+        }
+    }
+    stores.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(stores)
+}
+
+/// The inbox id of the identity stored in the database at `path`, without running migrations
+/// or building a client. `None` if no identity was created yet. `enc_key` is `None` for
+/// unencrypted databases.
+pub fn read_store_inbox_id(
+    path: &str,
+    enc_key: Option<EncryptionKey>,
+) -> Result<Option<InboxId>, StorageError> {
+    let mut conn = match enc_key {
+        Some(key) => EncryptedConnection::open_existing(path, key)?,
+        None if !Path::new(path).try_exists()? => {
+            return Err(invalid_input(format!("no database at {path}")));
+        }
+        None => SqliteConnection::establish(path)?,
+    };
+    Ok(identity::table
+        .select(identity::inbox_id)
+        .first(&mut conn)
+        .optional()?)
+}
+
+/// Delete a database and the files kept next to it
+pub fn remove_store_files(path: impl AsRef<Path>) -> Result<(), StorageError> {
+    let path = path.as_ref();
+    let mut files = vec![
+        path.to_path_buf(),
+        EncryptedConnection::salt_file(path)?,
+        EncryptedConnection::cipher_options_file(path)?,
+        EncryptedConnection::passphrase_kdf_file(path)?,
+        EncryptedConnection::pepper_file(path)?,
+    ];
+    for suffix in ["-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        files.push(file.into());
+    }
+    for file in files {
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Delete the databases of `env` in `dir` whose inbox is not one of `inbox_ids`, e.g. the
+/// stores of accounts the user signed out of. Returns the deleted databases.
+pub fn remove_orphaned_stores(
+    dir: impl AsRef<Path>,
+    env: &str,
+    inbox_ids: &[InboxId],
+) -> Result<Vec<StoreEntry>, StorageError> {
+    let orphans: Vec<StoreEntry> = list_stores(dir)?
+        .into_iter()
+        .filter(|store| {
+            store.env == env
+                && !inbox_ids
+                    .iter()
+                    .any(|inbox_id| inbox_id.eq_ignore_ascii_case(&store.inbox_id))
+        })
+        .collect();
+    for store in &orphans {
+        tracing::info!("removing orphaned store {}", store.path.display());
+        remove_store_files(&store.path)?;
+    }
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{identity::StoredIdentity, EncryptedMessageStore, StorageOption};
+    use crate::Store;
+
+    #[test]
+    fn test_db_file_names() {
+        let name = db_file_name("dev", "ABCdef01", 3).unwrap();
+        assert_eq!(name, "xmtp-dev-abcdef01-3.db3");
+        let entry = parse_db_path(Path::new("/tmp").join(&name)).unwrap();
+        assert_eq!(entry.env, "dev");
+        assert_eq!(entry.inbox_id, "abcdef01");
+        assert_eq!(entry.installation_nonce, 3);
+
+        assert!(db_file_name("dev/..", "abcdef01", 0).is_err());
+        assert!(db_file_name("dev", "not-hex", 0).is_err());
+        assert!(parse_db_path("xmtp-dev-abcdef01-3.db3-wal").is_none());
+        assert!(parse_db_path("xmtp-dev-abcdef01.db3").is_none());
+        assert!(parse_db_path("xmtp-dev-ABCDEF01-3.db3").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_store_discovery() {
+        let dir = std::env::temp_dir().join(format!("xmtp-stores-{}", xmtp_common::rand_u64()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = EncryptedMessageStore::generate_enc_key();
+
+        let kept = db_path(&dir, "dev", "aa11", 0).unwrap();
+        let orphan = db_path(&dir, "dev", "bb22", 0).unwrap();
+        let other_env = db_path(&dir, "production", "bb22", 0).unwrap();
+        for path in [&kept, &orphan, &other_env] {
+            let path = path.to_str().unwrap().to_string();
+            let _ = EncryptedMessageStore::new(StorageOption::Persistent(path), key)
+                .await
+                .unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), b"not a store").unwrap();
+
+        let stores = list_stores(&dir).unwrap();
+        assert_eq!(stores.len(), 3);
+
+        let kept_path = kept.to_str().unwrap();
+        assert_eq!(read_store_inbox_id(kept_path, Some(key)).unwrap(), None);
+        {
+            let store =
+                EncryptedMessageStore::new(StorageOption::Persistent(kept_path.into()), key)
+                    .await
+                    .unwrap();
+            StoredIdentity::new("aa11".to_string(), vec![1], vec![2])
+                .store(&store.conn().unwrap())
+                .unwrap();
+        }
+        assert_eq!(
+            read_store_inbox_id(kept_path, Some(key))
+                .unwrap()
+                .as_deref(),
+            Some("aa11")
+        );
+
+        let removed = remove_orphaned_stores(&dir, "dev", &["AA11".to_string()]).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path, orphan);
+        assert!(!orphan.exists());
+        assert!(!EncryptedConnection::salt_file(&orphan).unwrap().exists());
+        assert_eq!(list_stores(&dir).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+//! Detection of transactions that hold the database lock for too long.
+//!
+//! Every transaction of [`ProviderTransactions`](super::ProviderTransactions) is timed from begin
+//! to commit or rollback, and tagged with where it was started: the tracing span it runs in, or
+//! the caller of a synchronous transaction. Transactions that take longer than the
+//! [threshold](set_long_transaction_threshold) are logged as warnings and kept in a short list
+//! for debugging, see [`recent_long_transactions`]. Tests can make them panic instead with
+//! [`panic_on_long_transactions`], to catch lock-hogging code paths before they ship.
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use xmtp_common::time::now_ns;
+
+/// Transactions held longer than this are reported, unless a threshold is set
+pub const DEFAULT_LONG_TRANSACTION_THRESHOLD: Duration = Duration::from_millis(500);
+/// Long transactions kept by [`recent_long_transactions`]
+const MAX_RECENT_LONG_TRANSACTIONS: usize = 32;
+
+static THRESHOLD_MS: AtomicU64 =
+    AtomicU64::new(DEFAULT_LONG_TRANSACTION_THRESHOLD.as_millis() as u64);
+static PANIC_ON_LONG: AtomicBool = AtomicBool::new(false);
+static RECENT: Mutex<VecDeque<LongTransaction>> = parking_lot::const_mutex(VecDeque::new());
+
+/// A transaction held longer than the threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LongTransaction {
+    /// Where the transaction was started
+    pub origin: String,
+    pub duration: Duration,
+    pub committed: bool,
+    /// Time in nanoseconds the transaction began
+    pub started_at_ns: i64,
+}
+
+/// Report transactions held longer than `threshold`
+pub fn set_long_transaction_threshold(threshold: Duration) {
+    THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Panic when a transaction is held longer than the threshold, instead of logging it
+#[cfg(any(test, feature = "test-utils"))]
+pub fn panic_on_long_transactions(enabled: bool) {
+    PANIC_ON_LONG.store(enabled, Ordering::Relaxed);
+}
+
+/// The latest transactions that were held longer than the threshold, oldest first
+pub fn recent_long_transactions() -> Vec<LongTransaction> {
+    RECENT.lock().iter().cloned().collect()
+}
+
+/// Where a transaction is started from: `caller` if it is known, or else the current tracing span
+pub(super) fn origin(caller: Option<&std::panic::Location<'_>>) -> String {
+    if let Some(caller) = caller {
+        return caller.to_string();
+    }
+    match tracing::Span::current().metadata() {
+        Some(metadata) => format!(
+            "{} ({}:{})",
+            metadata.name(),
+            metadata.file().unwrap_or("unknown"),
+            metadata.line().unwrap_or_default()
+        ),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Times a transaction from begin until [`Self::end`]
+pub(super) struct TransactionTimer {
+    origin: String,
+    started_at_ns: i64,
+}
+
+impl TransactionTimer {
+    pub(super) fn begin(origin: String) -> Self {
+        tracing::trace!(origin = %origin, "transaction began");
+        Self {
+            origin,
+            started_at_ns: now_ns(),
+        }
+    }
+
+    pub(super) fn end(self, committed: bool) {
+        let duration = Duration::from_nanos((now_ns() - self.started_at_ns).max(0) as u64);
+        tracing::trace!(
+            origin = %self.origin,
+            duration_ms = duration.as_millis() as u64,
+            committed,
+            "transaction ended"
+        );
+        if duration.as_millis() as u64 <= THRESHOLD_MS.load(Ordering::Relaxed) {
+            return;
+        }
+        if PANIC_ON_LONG.load(Ordering::Relaxed) {
+            panic!(
+                "transaction started at {} was held for {}ms",
+                self.origin,
+                duration.as_millis()
+            );
+        }
+        tracing::warn!(
+            origin = %self.origin,
+            duration_ms = duration.as_millis() as u64,
+            committed,
+            "long transaction held the database lock"
+        );
+        let mut recent = RECENT.lock();
+        if recent.len() == MAX_RECENT_LONG_TRANSACTIONS {
+            recent.pop_front();
+        }
+        recent.push_back(LongTransaction {
+            origin: self.origin,
+            duration,
+            committed,
+            started_at_ns: self.started_at_ns,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_long_transactions() {
+        let origin = "records_long_transactions".to_string();
+        TransactionTimer::begin(origin.clone()).end(true);
+        assert!(!recent_long_transactions()
+            .iter()
+            .any(|long| long.origin == origin));
+
+        let timer = TransactionTimer {
+            origin: origin.clone(),
+            started_at_ns: now_ns() - 2 * DEFAULT_LONG_TRANSACTION_THRESHOLD.as_nanos() as i64,
+        };
+        timer.end(false);
+        let long = recent_long_transactions()
+            .into_iter()
+            .find(|long| long.origin == origin)
+            .unwrap();
+        assert!(!long.committed);
+        assert!(long.duration >= DEFAULT_LONG_TRANSACTION_THRESHOLD);
+    }
+}
@@ -0,0 +1,142 @@
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    db_connection::DbConnection,
+    schema::transaction_verifications::{self, dsl},
+    Sqlite,
+};
+use crate::storage::StorageError;
+
+/// Status of the transaction behind a transaction reference message
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Integer)]
+pub enum TransactionStatus {
+    /// No verifier is configured to check the transaction
+    Unverified = 0,
+    /// The transaction is not final yet
+    Pending = 1,
+    Confirmed = 2,
+    /// The transaction failed or does not exist
+    Failed = 3,
+    /// The message does not hold well formed chain metadata
+    Invalid = 4,
+}
+
+impl TransactionStatus {
+    /// Whether the status will not change anymore, so it does not need to be checked again
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Confirmed | Self::Failed | Self::Invalid)
+    }
+}
+
+impl ToSql<Integer, Sqlite> for TransactionStatus
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for TransactionStatus
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            0 => Ok(TransactionStatus::Unverified),
+            1 => Ok(TransactionStatus::Pending),
+            2 => Ok(TransactionStatus::Confirmed),
+            3 => Ok(TransactionStatus::Failed),
+            4 => Ok(TransactionStatus::Invalid),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+/// The last checked status of a transaction reference message
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = transaction_verifications)]
+#[diesel(primary_key(message_id))]
+pub struct StoredTransactionVerification {
+    pub message_id: Vec<u8>,
+    pub network_id: String,
+    /// Hash of the transaction
+    pub reference: String,
+    pub status: TransactionStatus,
+    pub checked_at_ns: i64,
+}
+
+impl DbConnection {
+    pub fn get_transaction_verification<MessageId: AsRef<[u8]>>(
+        &self,
+        message_id: MessageId,
+    ) -> Result<Option<StoredTransactionVerification>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::transaction_verifications
+                .find(message_id.as_ref())
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    /// Insert or replace the status of a transaction reference message
+    pub fn set_transaction_verification(
+        &self,
+        verification: &StoredTransactionVerification,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::transaction_verifications)
+                .values(verification)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_replaces_the_status() {
+        with_connection(|conn| {
+            let mut verification = StoredTransactionVerification {
+                message_id: vec![1; 32],
+                network_id: "1".to_string(),
+                reference: format!("0x{}", "ab".repeat(32)),
+                status: TransactionStatus::Pending,
+                checked_at_ns: 1,
+            };
+            conn.set_transaction_verification(&verification).unwrap();
+            verification.status = TransactionStatus::Confirmed;
+            verification.checked_at_ns = 2;
+            conn.set_transaction_verification(&verification).unwrap();
+
+            assert_eq!(
+                conn.get_transaction_verification(vec![1; 32]).unwrap(),
+                Some(verification)
+            );
+            assert_eq!(
+                conn.get_transaction_verification(vec![2; 32]).unwrap(),
+                None
+            );
+        })
+        .await
+    }
+}
@@ -19,18 +19,30 @@ pub struct StoredUserPreferences {
     pub id: i32,
     /// Randomly generated hmac key root
     pub hmac_key: Option<Vec<u8>>,
+    /// JSON-encoded [`ConsentPolicy`](super::consent_policy::ConsentPolicy)
+    pub consent_policy: Option<String>,
+    /// JSON-encoded [`FeatureFlags`](super::feature_flag::FeatureFlags)
+    pub feature_flags: Option<String>,
+    /// JSON-encoded list of [`MuteRule`](super::mute_rule::MuteRule)
+    pub mute_rules: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = user_preferences)]
 pub struct NewStoredUserPreferences<'a> {
     hmac_key: Option<&'a Vec<u8>>,
+    consent_policy: Option<&'a String>,
+    feature_flags: Option<&'a String>,
+    mute_rules: Option<&'a String>,
 }
 
 impl<'a> From<&'a StoredUserPreferences> for NewStoredUserPreferences<'a> {
     fn from(value: &'a StoredUserPreferences) -> Self {
         Self {
             hmac_key: value.hmac_key.as_ref(),
+            consent_policy: value.consent_policy.as_ref(),
+            feature_flags: value.feature_flags.as_ref(),
+            mute_rules: value.mute_rules.as_ref(),
         }
     }
 }
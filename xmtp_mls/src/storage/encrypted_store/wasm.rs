@@ -25,11 +25,10 @@ impl std::fmt::Debug for WasmDb {
 
 impl WasmDb {
     pub async fn new(opts: &StorageOption) -> Result<Self, StorageError> {
-        use super::StorageOption::*;
         sqlite_web::init_sqlite().await;
-        let conn = match opts {
-            Ephemeral => SqliteConnection::establish(":memory:"),
-            Persistent(ref db_path) => SqliteConnection::establish(db_path),
+        let conn = match opts.path() {
+            None => SqliteConnection::establish(":memory:"),
+            Some(db_path) => SqliteConnection::establish(db_path),
         }?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
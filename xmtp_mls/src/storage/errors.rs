@@ -8,7 +8,7 @@ use super::{
     sql_key_store::{self, SqlKeyStoreError},
 };
 use crate::{groups::intents::IntentError, types::InstallationId};
-use xmtp_common::{retryable, RetryableError};
+use xmtp_common::{retryable, ErrorClass, RetryableError};
 
 pub struct Mls;
 
@@ -38,10 +38,14 @@ pub enum StorageError {
     PoolNeedsConnection,
     #[error(transparent)]
     Intent(#[from] IntentError),
-    #[error("The SQLCipher Sqlite extension is not present, but an encryption key is given")]
-    SqlCipherNotLoaded,
-    #[error("PRAGMA key or salt has incorrect value")]
-    SqlCipherKeyIncorrect,
+    #[error("{0}. {hint}", hint = _0.recovery_hint())]
+    SqlCipher(#[from] SqlCipherError),
+    #[error("snapshots can only be restored into a persistent database")]
+    RestoreEphemeral,
+    #[error("snapshots can only be taken of a persistent database")]
+    SnapshotEphemeral,
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -50,6 +54,65 @@ pub enum StorageError {
     Duplicate(DuplicateItem),
     #[error(transparent)]
     OpenMlsStorage(#[from] SqlKeyStoreError),
+    #[error("external cursor store: {0}")]
+    CursorStore(String),
+    #[error("column encryption: {0}")]
+    ColumnEncryption(String),
+    #[error("key store export: {0}")]
+    KeyStoreExport(String),
+}
+
+/// Reasons SQLCipher can refuse to open a database
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SqlCipherError {
+    #[error("The SQLCipher Sqlite extension is not present, but an encryption key is given")]
+    NotLoaded,
+    #[error("PRAGMA key or salt has incorrect value")]
+    KeyIncorrect,
+    #[error("the plaintext database header is missing or corrupted")]
+    CorruptedHeader,
+    #[error("database was written with SQLCipher {compatibility}.x, which is no longer supported")]
+    UnsupportedCipherVersion { compatibility: u32 },
+    #[error("file at [`{0}`] is not a database")]
+    NotADatabase(String),
+    #[error("invalid SQLCipher options: {0}")]
+    InvalidCipherOptions(String),
+    #[error("SQLCipher options differ from the ones the database was created with")]
+    CipherOptionsMismatch,
+    #[error("database has an encrypted header, but a plaintext header was requested")]
+    PlaintextHeaderMigrationRequired,
+    #[error("unable to derive the encryption key from the passphrase: {0}")]
+    KeyDerivation(String),
+}
+
+impl SqlCipherError {
+    /// A short, user-presentable suggestion for how to recover from this error
+    pub fn recovery_hint(&self) -> &'static str {
+        use SqlCipherError::*;
+        match self {
+            NotLoaded => "Link libxmtp against SQLCipher or open the database without an encryption key",
+            KeyIncorrect => "Retry with the encryption key the database was created with",
+            CorruptedHeader => "Restore the database from a backup, or delete it and sync from the network",
+            UnsupportedCipherVersion { .. } => {
+                "Open the database with the app version that created it and export its data, or delete it and sync from the network"
+            }
+            NotADatabase(_) => "Check the database path, or delete the file and sync from the network",
+            InvalidCipherOptions(_) => "Fix the SQLCipher options passed in the storage configuration",
+            PlaintextHeaderMigrationRequired => {
+                "Call migrate_to_plaintext_header before opening the database"
+            }
+            CipherOptionsMismatch => {
+                "Open the database without explicit SQLCipher options to use the ones it was created with"
+            }
+            KeyDerivation(_) => "Check the passphrase and the key derivation parameters",
+        }
+    }
+}
+
+impl RetryableError for SqlCipherError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, Self::NotLoaded)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -59,6 +122,8 @@ pub enum NotFound {
     GroupByWelcome(i64),
     #[error("group with id {id} not found", id = hex::encode(_0))]
     GroupById(Vec<u8>),
+    #[error("group {id} is not a shard of a mega group", id = hex::encode(_0))]
+    MegaGroupShard(Vec<u8>),
     #[error("installation time for group {id}", id = hex::encode(_0))]
     InstallationTimeForGroup(Vec<u8>),
     #[error("inbox id for address {0} not found")]
@@ -67,6 +132,8 @@ pub enum NotFound {
     MessageById(Vec<u8>),
     #[error("dm by dm_target_inbox_id {0} not found")]
     DmByInbox(String),
+    #[error("conversation with public id {0} not found")]
+    GroupByPublicId(String),
     #[error("intent with id {0} for state Publish from ToPublish not found")]
     IntentForToPublish(i32),
     #[error("intent with id {0} for state ToPublish from Published not found")]
@@ -79,10 +146,16 @@ pub enum NotFound {
     RefreshStateByIdAndKind(Vec<u8>, EntityKind),
     #[error("Cipher salt for db at [`{0}`] not found")]
     CipherSalt(String),
+    #[error("pepper of the split key for db at [`{0}`] not found")]
+    Pepper(String),
     #[error("Sync Group for installation {0} not found")]
     SyncGroup(InstallationId),
     #[error("MLS Group Not Found")]
     MlsGroup,
+    #[error("poll with id {id} not found", id = hex::encode(_0))]
+    PollById(Vec<u8>),
+    #[error("pending join request from {0} not found")]
+    JoinRequest(String),
 }
 
 #[derive(Error, Debug)]
@@ -122,6 +195,10 @@ impl RetryableError<Mls> for diesel::result::Error {
             _ => true,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Storage)
+    }
 }
 
 impl RetryableError for StorageError {
@@ -131,19 +208,27 @@ impl RetryableError for StorageError {
             Self::DieselResult(result) => retryable!(result),
             Self::Pool(_) => true,
             Self::Lock(_) => true,
-            Self::SqlCipherNotLoaded => true,
+            Self::SqlCipher(s) => retryable!(s),
             Self::PoolNeedsConnection => true,
-            Self::SqlCipherKeyIncorrect => false,
             Self::Duplicate(d) => retryable!(d),
+            Self::CursorStore(_) => true,
             _ => false,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Storage)
+    }
 }
 
 impl RetryableError for NotFound {
     fn is_retryable(&self) -> bool {
         true
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Storage)
+    }
 }
 
 // OpenMLS KeyStore errors
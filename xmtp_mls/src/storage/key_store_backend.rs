@@ -0,0 +1,261 @@
+//! Optional host-provided secure module for the private keys of the installation.
+//!
+//! By default every secret lives in the SQLCipher database. With a [`KeyStoreBackend`] attached to
+//! the [`EncryptedMessageStore`](super::EncryptedMessageStore), private keys are held by a secure
+//! module of the host such as the Secure Enclave, Android StrongBox or an HSM, and are only used
+//! through handles:
+//!
+//! - The installation signing key is generated in the module. The identity stores its handle in
+//!   place of the private key, and every signature of the installation is made by the module.
+//! - The MLS secrets of the [`SqlKeyStore`](super::sql_key_store::SqlKeyStore) (key packages, HPKE
+//!   and epoch key pairs, epoch and message secrets) are sealed by a key of the module before they
+//!   are written to the database, and unsealed when OpenMLS reads them. They stay in the database,
+//!   so they change atomically with the group state they belong to.
+//!
+//! The handle of the sealing key is kept in the key store, and the sealed entries are
+//! authenticated with their storage key, so a sealed secret cannot be read in place of another.
+use std::{fmt::Debug, sync::Arc};
+
+use openmls_traits::signatures::SignerError;
+use xmtp_cryptography::{ExternalInstallationKey, XmtpInstallationCredential};
+
+use super::sql_key_store::SqlKeyStoreError;
+
+/// Secure module of the host holding the private keys of the installation.
+///
+/// Keys are referred to by opaque handles returned by the module. Errors should be reported as
+/// [`SqlKeyStoreError::Backend`].
+pub trait KeyStoreBackend: Send + Sync + Debug {
+    /// Generate an Ed25519 signing key in the module, returning its handle
+    fn generate_signing_key(&self) -> Result<Vec<u8>, SqlKeyStoreError>;
+
+    /// The Ed25519 public key of the signing key `handle`
+    fn public_key(&self, handle: &[u8]) -> Result<[u8; 32], SqlKeyStoreError>;
+
+    /// Sign `payload` with Ed25519 using the signing key `handle`
+    fn sign(&self, handle: &[u8], payload: &[u8]) -> Result<[u8; 64], SqlKeyStoreError>;
+
+    /// Sign `message` with Ed25519ph under `context` using the signing key `handle`
+    fn sign_with_context(
+        &self,
+        handle: &[u8],
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<[u8; 64], SqlKeyStoreError>;
+
+    /// Generate a key in the module to seal secrets with, returning its handle
+    fn generate_sealing_key(&self) -> Result<Vec<u8>, SqlKeyStoreError>;
+
+    /// Encrypt `plaintext` with the sealing key `handle`, authenticating `aad`
+    fn seal(
+        &self,
+        handle: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, SqlKeyStoreError>;
+
+    /// Decrypt `ciphertext` of [`Self::seal`] with the sealing key `handle`, failing if `aad`
+    /// does not match
+    fn unseal(
+        &self,
+        handle: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, SqlKeyStoreError>;
+
+    /// Delete the key `handle` from the module
+    fn delete_key(&self, handle: &[u8]) -> Result<(), SqlKeyStoreError>;
+}
+
+/// A signing key of a [`KeyStoreBackend`], signing for an [`XmtpInstallationCredential`]
+#[derive(Debug)]
+struct BackendSigningKey {
+    backend: Arc<dyn KeyStoreBackend>,
+    handle: Vec<u8>,
+}
+
+impl ExternalInstallationKey for BackendSigningKey {
+    fn handle(&self) -> &[u8] {
+        &self.handle
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<[u8; 64], SignerError> {
+        self.backend.sign(&self.handle, payload).map_err(|e| {
+            tracing::error!("key store backend failed to sign: {e}");
+            SignerError::SigningError
+        })
+    }
+
+    fn sign_with_context(&self, context: &[u8], message: &[u8]) -> Result<[u8; 64], SignerError> {
+        self.backend
+            .sign_with_context(&self.handle, context, message)
+            .map_err(|e| {
+                tracing::error!("key store backend failed to sign: {e}");
+                SignerError::SigningError
+            })
+    }
+}
+
+/// The installation credential signing with the key `handle` of `backend`
+pub(crate) fn installation_credential(
+    backend: &Arc<dyn KeyStoreBackend>,
+    handle: Vec<u8>,
+) -> Result<XmtpInstallationCredential, SqlKeyStoreError> {
+    let public_key = backend.public_key(&handle)?;
+    XmtpInstallationCredential::external(
+        &public_key,
+        Arc::new(BackendSigningKey {
+            backend: backend.clone(),
+            handle,
+        }),
+    )
+    .map_err(|e| SqlKeyStoreError::Backend(e.to_string()))
+}
+
+/// A new installation credential, generated in `backend` if one is attached
+pub(crate) fn generate_installation_credential(
+    backend: Option<&Arc<dyn KeyStoreBackend>>,
+) -> Result<XmtpInstallationCredential, SqlKeyStoreError> {
+    match backend {
+        Some(backend) => installation_credential(backend, backend.generate_signing_key()?),
+        None => Ok(XmtpInstallationCredential::new()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use aes_gcm::{
+        aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+        Aes256Gcm,
+    };
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    const NONCE_SIZE: usize = 12;
+
+    /// A [`KeyStoreBackend`] keeping its keys in memory, standing in for a secure module
+    #[derive(Debug, Default)]
+    pub(crate) struct InMemoryKeyStoreBackend {
+        pub(crate) signing_keys: Mutex<HashMap<Vec<u8>, XmtpInstallationCredential>>,
+        pub(crate) sealing_keys: Mutex<HashMap<Vec<u8>, [u8; 32]>>,
+    }
+
+    fn backend_error(e: impl std::fmt::Display) -> SqlKeyStoreError {
+        SqlKeyStoreError::Backend(e.to_string())
+    }
+
+    impl InMemoryKeyStoreBackend {
+        fn signing_key(
+            &self,
+            handle: &[u8],
+        ) -> Result<XmtpInstallationCredential, SqlKeyStoreError> {
+            self.signing_keys
+                .lock()
+                .get(handle)
+                .cloned()
+                .ok_or_else(|| backend_error("unknown signing key"))
+        }
+
+        fn cipher(&self, handle: &[u8]) -> Result<Aes256Gcm, SqlKeyStoreError> {
+            let keys = self.sealing_keys.lock();
+            let key = keys
+                .get(handle)
+                .ok_or_else(|| backend_error("unknown sealing key"))?;
+            Ok(Aes256Gcm::new(GenericArray::from_slice(key)))
+        }
+    }
+
+    impl KeyStoreBackend for InMemoryKeyStoreBackend {
+        fn generate_signing_key(&self) -> Result<Vec<u8>, SqlKeyStoreError> {
+            let handle = xmtp_common::rand_vec::<16>();
+            self.signing_keys
+                .lock()
+                .insert(handle.clone(), XmtpInstallationCredential::new());
+            Ok(handle)
+        }
+
+        fn public_key(&self, handle: &[u8]) -> Result<[u8; 32], SqlKeyStoreError> {
+            Ok(*self.signing_key(handle)?.public_bytes())
+        }
+
+        fn sign(&self, handle: &[u8], payload: &[u8]) -> Result<[u8; 64], SqlKeyStoreError> {
+            let signature =
+                openmls_traits::signatures::Signer::sign(&self.signing_key(handle)?, payload)
+                    .map_err(|e| backend_error(format!("{e:?}")))?;
+            signature
+                .try_into()
+                .map_err(|_| backend_error("bad signature"))
+        }
+
+        fn sign_with_context(
+            &self,
+            handle: &[u8],
+            context: &[u8],
+            message: &[u8],
+        ) -> Result<[u8; 64], SqlKeyStoreError> {
+            Ok(self
+                .signing_key(handle)?
+                .sign_with_context(context, message)
+                .map_err(backend_error)?
+                .to_bytes())
+        }
+
+        fn generate_sealing_key(&self) -> Result<Vec<u8>, SqlKeyStoreError> {
+            let handle = xmtp_common::rand_vec::<16>();
+            self.sealing_keys
+                .lock()
+                .insert(handle.clone(), xmtp_common::rand_array::<32>());
+            Ok(handle)
+        }
+
+        fn seal(
+            &self,
+            handle: &[u8],
+            aad: &[u8],
+            plaintext: &[u8],
+        ) -> Result<Vec<u8>, SqlKeyStoreError> {
+            let nonce = xmtp_common::rand_array::<NONCE_SIZE>();
+            let ciphertext = self
+                .cipher(handle)?
+                .encrypt(
+                    GenericArray::from_slice(&nonce),
+                    Payload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(backend_error)?;
+            Ok([nonce.as_slice(), &ciphertext].concat())
+        }
+
+        fn unseal(
+            &self,
+            handle: &[u8],
+            aad: &[u8],
+            ciphertext: &[u8],
+        ) -> Result<Vec<u8>, SqlKeyStoreError> {
+            if ciphertext.len() < NONCE_SIZE {
+                return Err(backend_error("sealed value is truncated"));
+            }
+            let (nonce, ciphertext) = ciphertext.split_at(NONCE_SIZE);
+            self.cipher(handle)?
+                .decrypt(
+                    GenericArray::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad,
+                    },
+                )
+                .map_err(backend_error)
+        }
+
+        fn delete_key(&self, handle: &[u8]) -> Result<(), SqlKeyStoreError> {
+            self.signing_keys.lock().remove(handle);
+            self.sealing_keys.lock().remove(handle);
+            Ok(())
+        }
+    }
+}
@@ -1,5 +1,6 @@
 pub(super) mod encrypted_store;
 mod errors;
+pub mod key_store_backend;
 pub mod serialization;
 pub mod sql_key_store;
 pub mod xmtp_openmls_provider;
@@ -1,6 +1,7 @@
-use xmtp_common::{retryable, RetryableError};
+use xmtp_common::{retryable, ErrorClass, RetryableError};
 
 use super::encrypted_store::db_connection::DbConnectionPrivate;
+use super::key_store_backend::KeyStoreBackend;
 use bincode;
 use diesel::{
     prelude::*,
@@ -17,6 +18,8 @@ const REPLACE_QUERY: &str =
 const UPDATE_QUERY: &str =
     "UPDATE openmls_key_value SET value_bytes = ? WHERE key_bytes = ? AND version = ?";
 const DELETE_QUERY: &str = "DELETE FROM openmls_key_value WHERE key_bytes = ? AND version = ?";
+const INSERT_IGNORE_QUERY: &str =
+    "INSERT OR IGNORE INTO openmls_key_value (key_bytes, version, value_bytes) VALUES (?, ?, ?)";
 
 #[derive(QueryableByName, Debug, Clone, PartialEq, Eq)]
 #[diesel(table_name = openmls_key_value)]
@@ -45,44 +48,82 @@ impl<C> SqlKeyStore<C>
 where
     C: diesel::Connection<Backend = crate::storage::Sqlite> + diesel::connection::LoadConnection,
 {
+    /// The host-provided backend sealing the entry under `storage_key`, if one is attached and
+    /// the entry holds secrets
+    fn sealing_backend(&self, storage_key: &[u8]) -> Option<&dyn KeyStoreBackend> {
+        self.conn_ref()
+            .key_store_backend()
+            .map(AsRef::as_ref)
+            .filter(|_| is_secret_entry(storage_key))
+    }
+
+    /// `value`, sealed by the backend if the entry under `storage_key` holds secrets
+    fn seal(&self, storage_key: &[u8], value: &[u8]) -> Result<Vec<u8>, SqlKeyStoreError> {
+        let Some(backend) = self.sealing_backend(storage_key) else {
+            return Ok(value.to_vec());
+        };
+        let handle = self
+            .conn_ref()
+            .raw_query(|conn| sealing_key(conn, backend))?;
+        backend.seal(&handle, storage_key, value)
+    }
+
     fn select_query<const VERSION: u16>(
         &self,
         storage_key: &Vec<u8>,
-    ) -> Result<Vec<StorageData>, diesel::result::Error> {
-        self.conn_ref().raw_query(|conn| {
+    ) -> Result<Vec<StorageData>, SqlKeyStoreError> {
+        let data: Vec<StorageData> = self.conn_ref().raw_query(|conn| {
             sql_query(SELECT_QUERY)
                 .bind::<diesel::sql_types::Binary, _>(&storage_key)
                 .bind::<diesel::sql_types::Integer, _>(VERSION as i32)
                 .load(conn)
-        })
+        })?;
+        let Some(backend) = self
+            .sealing_backend(storage_key)
+            .filter(|_| !data.is_empty())
+        else {
+            return Ok(data);
+        };
+        let handle = self
+            .conn_ref()
+            .raw_query(|conn| sealing_key(conn, backend))?;
+        data.into_iter()
+            .map(|entry| {
+                backend
+                    .unseal(&handle, storage_key, &entry.value_bytes)
+                    .map(|value_bytes| StorageData { value_bytes })
+            })
+            .collect()
     }
 
     fn replace_query<const VERSION: u16>(
         &self,
         storage_key: &Vec<u8>,
         value: &[u8],
-    ) -> Result<usize, diesel::result::Error> {
-        self.conn_ref().raw_query(|conn| {
+    ) -> Result<usize, SqlKeyStoreError> {
+        let value = self.seal(storage_key, value)?;
+        Ok(self.conn_ref().raw_query(|conn| {
             sql_query(REPLACE_QUERY)
                 .bind::<diesel::sql_types::Binary, _>(&storage_key)
                 .bind::<diesel::sql_types::Integer, _>(VERSION as i32)
                 .bind::<diesel::sql_types::Binary, _>(&value)
                 .execute(conn)
-        })
+        })?)
     }
 
     fn update_query<const VERSION: u16>(
         &self,
         storage_key: &Vec<u8>,
         modified_data: &Vec<u8>,
-    ) -> Result<usize, diesel::result::Error> {
-        self.conn_ref().raw_query(|conn| {
+    ) -> Result<usize, SqlKeyStoreError> {
+        let modified_data = self.seal(storage_key, modified_data)?;
+        Ok(self.conn_ref().raw_query(|conn| {
             sql_query(UPDATE_QUERY)
                 .bind::<diesel::sql_types::Binary, _>(&modified_data)
                 .bind::<diesel::sql_types::Binary, _>(&storage_key)
                 .bind::<diesel::sql_types::Integer, _>(VERSION as i32)
                 .execute(conn)
-        })
+        })?)
     }
 
     pub fn write<const VERSION: u16>(
@@ -247,6 +288,8 @@ pub enum SqlKeyStoreError {
     NotFound,
     #[error("database error: {0}")]
     Storage(#[from] diesel::result::Error),
+    #[error("key store backend: {0}")]
+    Backend(String),
 }
 
 impl RetryableError for SqlKeyStoreError {
@@ -257,8 +300,13 @@ impl RetryableError for SqlKeyStoreError {
             SqlKeyStoreError::UnsupportedMethod => false,
             SqlKeyStoreError::UnsupportedValueTypeBytes => false,
             SqlKeyStoreError::NotFound => false,
+            SqlKeyStoreError::Backend(_) => true,
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        Some(ErrorClass::Storage)
+    }
 }
 
 const KEY_PACKAGE_LABEL: &[u8] = b"KeyPackage";
@@ -286,6 +334,68 @@ const QUEUED_PROPOSAL_LABEL: &[u8] = b"QueuedProposal";
 const PROPOSAL_QUEUE_REFS_LABEL: &[u8] = b"ProposalQueueRefs";
 const RESUMPTION_PSK_STORE_LABEL: &[u8] = b"ResumptionPskStore";
 
+/// Handle of the key of the [`KeyStoreBackend`] sealing the secrets
+const SEALING_KEY_LABEL: &[u8] = b"SealingKey";
+
+/// Entries holding private keys or secrets, sealed by the [`KeyStoreBackend`] when one is attached
+const SECRET_LABELS: &[&[u8]] = &[
+    KEY_PACKAGE_LABEL,
+    ENCRYPTION_KEY_PAIR_LABEL,
+    SIGNATURE_KEY_PAIR_LABEL,
+    EPOCH_KEY_PAIRS_LABEL,
+    EPOCH_SECRETS_LABEL,
+    MESSAGE_SECRETS_LABEL,
+    RESUMPTION_PSK_STORE_LABEL,
+];
+
+/// Whether the entry under `storage_key` holds private keys or secrets
+pub(crate) fn is_secret_entry(storage_key: &[u8]) -> bool {
+    // The only label starting with another one
+    !storage_key.starts_with(KEY_PACKAGE_REFERENCES)
+        && SECRET_LABELS
+            .iter()
+            .any(|label| storage_key.starts_with(label))
+}
+
+/// The storage key of the handle of the sealing key
+pub(crate) fn sealing_key_storage_key() -> Vec<u8> {
+    build_key_from_vec::<CURRENT_VERSION>(SEALING_KEY_LABEL, vec![])
+}
+
+/// The handle of the key of `backend` sealing the secrets of the key store, generated on first
+/// use. A key generated in a transaction that is rolled back is left unused in the backend.
+pub(crate) fn sealing_key<C>(
+    conn: &mut C,
+    backend: &dyn KeyStoreBackend,
+) -> Result<Vec<u8>, SqlKeyStoreError>
+where
+    C: diesel::Connection<Backend = crate::storage::Sqlite> + diesel::connection::LoadConnection,
+{
+    let storage_key = sealing_key_storage_key();
+    let select = |conn: &mut C| {
+        sql_query(SELECT_QUERY)
+            .bind::<diesel::sql_types::Binary, _>(&storage_key)
+            .bind::<diesel::sql_types::Integer, _>(CURRENT_VERSION as i32)
+            .load::<StorageData>(conn)
+            .map(|data| data.into_iter().next().map(|entry| entry.value_bytes))
+    };
+    if let Some(handle) = select(conn)? {
+        return Ok(handle);
+    }
+    let handle = backend.generate_sealing_key()?;
+    sql_query(INSERT_IGNORE_QUERY)
+        .bind::<diesel::sql_types::Binary, _>(&storage_key)
+        .bind::<diesel::sql_types::Integer, _>(CURRENT_VERSION as i32)
+        .bind::<diesel::sql_types::Binary, _>(&handle)
+        .execute(conn)?;
+    // Another connection may have stored its key first
+    let stored = select(conn)?.ok_or(SqlKeyStoreError::NotFound)?;
+    if stored != handle {
+        backend.delete_key(&handle)?;
+    }
+    Ok(stored)
+}
+
 impl<C> StorageProvider<CURRENT_VERSION> for SqlKeyStore<C>
 where
     C: diesel::Connection<Backend = crate::storage::Sqlite> + diesel::connection::LoadConnection,
@@ -1041,11 +1151,21 @@ pub(crate) mod tests {
     use crate::{
         configuration::CIPHERSUITE,
         storage::{
-            sql_key_store::SqlKeyStoreError, xmtp_openmls_provider::XmtpOpenMlsProvider,
+            identity::StoredIdentity,
+            key_store_backend::{
+                generate_installation_credential, installation_credential,
+                tests::InMemoryKeyStoreBackend,
+            },
+            sql_key_store::SqlKeyStoreError,
+            xmtp_openmls_provider::XmtpOpenMlsProvider,
             EncryptedMessageStore, StorageOption,
         },
+        Fetch, Store,
     };
+    use std::sync::Arc;
     use xmtp_common::tmp_path;
+    use xmtp_cryptography::{CredentialSign, CredentialVerify};
+    use xmtp_id::associations::InstallationKeyContext;
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
@@ -1212,4 +1332,73 @@ pub(crate) mod tests {
         let group_state: Option<GroupState> = provider.storage().group_state(&group_id).unwrap();
         assert_eq!(GroupState(77), group_state.unwrap());
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn key_store_backend_holds_private_keys() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Persistent(tmp_path()),
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        let backend = Arc::new(InMemoryKeyStoreBackend::default());
+        let with_backend = store.clone().with_key_store_backend(backend.clone());
+        let conn = with_backend.conn().unwrap();
+
+        // The installation key is generated in the backend, the identity only holds its handle
+        let installation_keys = generate_installation_credential(conn.key_store_backend()).unwrap();
+        let handle = installation_keys.external_handle().unwrap().to_vec();
+        assert!(backend.signing_keys.lock().contains_key(&handle));
+        StoredIdentity::new("inbox".into(), handle.clone(), vec![4])
+            .store(&conn)
+            .unwrap();
+        let identity: StoredIdentity = store.conn().unwrap().fetch(&()).unwrap().unwrap();
+        assert_eq!(identity.installation_keys, handle);
+
+        // and signs through the backend once loaded again
+        let loaded = installation_credential(conn.key_store_backend().unwrap(), handle).unwrap();
+        assert_eq!(loaded.public_bytes(), installation_keys.public_bytes());
+        let signature = loaded
+            .credential_sign::<InstallationKeyContext>("text")
+            .unwrap();
+        installation_keys
+            .verifying_key()
+            .credential_verify::<InstallationKeyContext>("text", &signature.try_into().unwrap())
+            .unwrap();
+
+        // MLS secrets are sealed by the backend, the public group state is not
+        let provider = XmtpOpenMlsProvider::new(conn);
+        let signature_keys = SignatureKeyPair::new(CIPHERSUITE.signature_algorithm()).unwrap();
+        let public_key = StorageId::from(signature_keys.to_public_vec());
+        provider
+            .storage()
+            .write_signature_key_pair::<StorageId, SignatureKeyPair>(&public_key, &signature_keys)
+            .unwrap();
+        let group_id = GroupId::random(provider.rand());
+        provider
+            .storage()
+            .queue_proposal::<GroupId, ProposalRef, Proposal>(
+                &group_id,
+                &ProposalRef(0),
+                &Proposal(vec![1]),
+            )
+            .unwrap();
+        assert_eq!(backend.sealing_keys.lock().len(), 1);
+        assert!(provider
+            .storage()
+            .signature_key_pair::<StorageId, SignatureKeyPair>(&public_key)
+            .unwrap()
+            .is_some());
+        let without_backend = SqlKeyStore::new(store.conn().unwrap());
+        assert!(without_backend
+            .signature_key_pair::<StorageId, SignatureKeyPair>(&public_key)
+            .is_err());
+        assert_eq!(
+            without_backend
+                .queued_proposal_refs::<GroupId, ProposalRef>(&group_id)
+                .unwrap(),
+            vec![ProposalRef(0)]
+        );
+    }
 }
@@ -1,6 +1,9 @@
 //! Consistent Stream behavior between WebAssembly and Native utilizing `tokio::task::spawn` in native and
 //! `wasm_bindgen_futures::spawn` for web.
+use std::sync::Arc;
+
 use futures::FutureExt;
+use parking_lot::Mutex;
 
 #[cfg(target_arch = "wasm32")]
 pub type GenericStreamHandle<O> = dyn StreamHandle<StreamOutput = O>;
@@ -20,6 +23,61 @@ pub enum StreamHandleError {
     Cancelled,
     #[error("Stream Panicked With {0}")]
     Panicked(String),
+    #[error("The stream cannot be paused or replayed")]
+    NotControllable,
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: bool,
+    replay_from_ns: Option<i64>,
+}
+
+/// Shared by a [`StreamHandle`] and its stream to pause delivery and replay stored items without
+/// closing the network subscription.
+///
+/// Items received while the stream is paused are still processed and stored, but are not
+/// delivered. They are replayed from storage on resume.
+#[derive(Debug, Default)]
+pub struct StreamControl {
+    state: Mutex<ControlState>,
+    changed: tokio::sync::Notify,
+}
+
+impl StreamControl {
+    pub fn pause(&self) {
+        self.state.lock().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.state.lock().paused = false;
+        self.changed.notify_one();
+    }
+
+    /// Deliver again the stored items sent after `cursor_ns`, a timestamp in nanoseconds, then
+    /// continue with live items. Takes effect once the stream is not paused.
+    pub fn replay_from(&self, cursor_ns: i64) {
+        self.state.lock().replay_from_ns = Some(cursor_ns);
+        self.changed.notify_one();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().paused
+    }
+
+    /// The requested replay cursor, if any, unless the stream is paused
+    pub(crate) fn take_replay(&self) -> Option<i64> {
+        let mut state = self.state.lock();
+        if state.paused {
+            return None;
+        }
+        state.replay_from_ns.take()
+    }
+
+    /// Wait until the stream is resumed or a replay is requested
+    pub(crate) async fn changed(&self) {
+        self.changed.notified().await
+    }
 }
 /// A handle to a spawned Stream
 /// the spawned stream can be 'joined` by awaiting its Future implementation.
@@ -54,6 +112,33 @@ pub trait StreamHandle {
     /// This handle may be cloned/sent/etc easily
     /// and many handles may exist at once.
     fn abort_handle(&self) -> Box<dyn AbortHandle>;
+
+    /// The control of the stream, if it can be paused and replayed
+    fn control(&self) -> Option<Arc<StreamControl>>;
+
+    /// Stop delivering items without closing the network subscription
+    fn pause(&self) -> Result<(), StreamHandleError> {
+        self.control()
+            .ok_or(StreamHandleError::NotControllable)?
+            .pause();
+        Ok(())
+    }
+
+    /// Deliver the items missed while paused from storage, then continue with live items
+    fn resume(&self) -> Result<(), StreamHandleError> {
+        self.control()
+            .ok_or(StreamHandleError::NotControllable)?
+            .resume();
+        Ok(())
+    }
+
+    /// Deliver again the stored items sent after `cursor_ns`, then continue with live items
+    fn replay_from(&self, cursor_ns: i64) -> Result<(), StreamHandleError> {
+        self.control()
+            .ok_or(StreamHandleError::NotControllable)?
+            .replay_from(cursor_ns);
+        Ok(())
+    }
 }
 
 /// A handle that can be moved/cloned/sent, but can only close the stream.
@@ -88,6 +173,7 @@ mod wasm {
         // so we use mpsc here to keep the `&self` on `end`.
         closer: tokio::sync::mpsc::Sender<()>,
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        control: Option<Arc<StreamControl>>,
     }
 
     impl<T> Future for WasmStreamHandle<Result<T, StreamHandleError>> {
@@ -127,6 +213,10 @@ mod wasm {
             Box::new(CloseHandle(self.closer.clone()))
         }
 
+        fn control(&self) -> Option<Arc<StreamControl>> {
+            self.control.clone()
+        }
+
         async fn join(self) -> Result<Self::StreamOutput, StreamHandleError> {
             self.await
         }
@@ -151,6 +241,19 @@ mod wasm {
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
         future: F,
     ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        spawn_controlled(ready, None, future)
+    }
+
+    /// Like [`spawn`], with a `control` the stream checks to pause and replay
+    pub fn spawn_controlled<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        control: Option<Arc<StreamControl>>,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
     where
         F: Future + 'static,
         F::Output: 'static,
@@ -163,6 +266,7 @@ mod wasm {
             result: res_rx,
             closer: closer_tx,
             ready,
+            control,
         };
         tracing::info!("Spawning local task on web executor");
         wasm_bindgen_futures::spawn_local(async move {
@@ -217,6 +321,7 @@ mod native {
     pub struct TokioStreamHandle<T> {
         inner: JoinHandle<T>,
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        control: Option<Arc<StreamControl>>,
     }
 
     impl<T> Future for TokioStreamHandle<T> {
@@ -260,6 +365,10 @@ mod native {
             Box::new(self.inner.abort_handle())
         }
 
+        fn control(&self) -> Option<Arc<StreamControl>> {
+            self.control.clone()
+        }
+
         async fn join(self) -> Result<Self::StreamOutput, StreamHandleError> {
             self.await
         }
@@ -279,6 +388,19 @@ mod native {
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
         future: F,
     ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        spawn_controlled(ready, None, future)
+    }
+
+    /// Like [`spawn`], with a `control` the stream checks to pause and replay
+    pub fn spawn_controlled<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        control: Option<Arc<StreamControl>>,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
     where
         F: Future + Send + 'static,
         F::Output: Send + 'static,
@@ -286,6 +408,7 @@ mod native {
         TokioStreamHandle {
             inner: tokio::task::spawn(future),
             ready,
+            control,
         }
     }
 }
@@ -0,0 +1,255 @@
+//! Coalesces bursts of messages in chatty groups.
+//!
+//! A bot can post hundreds of messages to a group in a few seconds. A group can be given a
+//! [`BurstBudget`] with [`Client::set_group_burst_budget`]: the `*_with_callback` message streams
+//! then deliver at most `max_messages` messages of the group per window. The messages over the
+//! budget are still stored as usual, and are reported in a single [`MessagesCoalesced`] local
+//! event when the window ends, instead of one callback each.
+//!
+//! Budgets are stored and set again when the client is built. The count of the current window
+//! is kept in memory only, so a restart starts a new window.
+use std::{collections::HashMap, time::Duration};
+
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use xmtp_common::time::Instant;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::trait_impls::XmtpApi;
+
+use super::{LocalEvents, MessagesCoalesced};
+use crate::{
+    client::ClientError,
+    groups::{scoped_client::ScopedGroupClient, GroupError, MlsGroup},
+    storage::{group_burst_budget::StoredGroupBurstBudget, group_message::StoredGroupMessage},
+    Client,
+};
+
+/// Number of messages of a group delivered to stream callbacks per window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstBudget {
+    pub max_messages: u32,
+    pub window: Duration,
+}
+
+impl BurstBudget {
+    fn stored(&self, group_id: &[u8]) -> StoredGroupBurstBudget {
+        StoredGroupBurstBudget {
+            group_id: group_id.to_vec(),
+            max_messages: i32::try_from(self.max_messages).unwrap_or(i32::MAX),
+            window_ms: i64::try_from(self.window.as_millis()).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+impl From<&StoredGroupBurstBudget> for BurstBudget {
+    fn from(budget: &StoredGroupBurstBudget) -> Self {
+        Self {
+            max_messages: budget.max_messages.max(0) as u32,
+            window: Duration::from_millis(budget.window_ms.max(0) as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GroupBurst {
+    budget: BurstBudget,
+    window_start: Instant,
+    delivered: u32,
+    coalesced: Option<MessagesCoalesced>,
+}
+
+impl GroupBurst {
+    fn window_ended(&self, now: Instant) -> bool {
+        now.duration_since(self.window_start) >= self.budget.window
+    }
+}
+
+/// The burst budgets of the groups of a client, shared by its streams
+#[derive(Debug)]
+pub struct BurstLimiter {
+    groups: Mutex<HashMap<Vec<u8>, GroupBurst>>,
+    local_events: broadcast::Sender<LocalEvents>,
+}
+
+impl BurstLimiter {
+    pub(crate) fn new(local_events: broadcast::Sender<LocalEvents>) -> Self {
+        Self {
+            groups: Mutex::default(),
+            local_events,
+        }
+    }
+
+    /// Set the budget of a group, or remove it with `None`. Messages coalesced so far are
+    /// reported right away.
+    pub fn set_budget(&self, group_id: &[u8], budget: Option<BurstBudget>) {
+        let mut groups = self.groups.lock();
+        if let Some(coalesced) = groups.remove(group_id).and_then(|burst| burst.coalesced) {
+            self.report(coalesced);
+        }
+        if let Some(budget) = budget {
+            groups.insert(
+                group_id.to_vec(),
+                GroupBurst {
+                    budget,
+                    window_start: Instant::now(),
+                    delivered: 0,
+                    coalesced: None,
+                },
+            );
+        }
+    }
+
+    /// Whether `message` fits the budget of its group and should be delivered. Messages over
+    /// the budget are counted in the event sent when the window ends.
+    pub(crate) fn admit(&self, message: &StoredGroupMessage) -> bool {
+        let now = Instant::now();
+        let mut groups = self.groups.lock();
+        let Some(burst) = groups.get_mut(&message.group_id) else {
+            return true;
+        };
+        if burst.window_ended(now) {
+            if let Some(coalesced) = burst.coalesced.take() {
+                self.report(coalesced);
+            }
+            burst.window_start = now;
+            burst.delivered = 0;
+        }
+        if burst.delivered < burst.budget.max_messages {
+            burst.delivered += 1;
+            return true;
+        }
+
+        let coalesced = burst.coalesced.get_or_insert_with(|| MessagesCoalesced {
+            group_id: message.group_id.clone(),
+            count: 0,
+            first_sent_at_ns: message.sent_at_ns,
+            last_sent_at_ns: message.sent_at_ns,
+        });
+        coalesced.count += 1;
+        coalesced.first_sent_at_ns = coalesced.first_sent_at_ns.min(message.sent_at_ns);
+        coalesced.last_sent_at_ns = coalesced.last_sent_at_ns.max(message.sent_at_ns);
+        false
+    }
+
+    /// Time until the first window with coalesced messages ends
+    pub(crate) fn next_report_in(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.groups
+            .lock()
+            .values()
+            .filter(|burst| burst.coalesced.is_some())
+            .map(|burst| {
+                burst
+                    .budget
+                    .window
+                    .saturating_sub(now.duration_since(burst.window_start))
+            })
+            .min()
+    }
+
+    /// Report the messages coalesced in the windows that ended
+    pub(crate) fn report_ended(&self) {
+        let now = Instant::now();
+        for burst in self.groups.lock().values_mut() {
+            if burst.window_ended(now) {
+                if let Some(coalesced) = burst.coalesced.take() {
+                    self.report(coalesced);
+                }
+            }
+        }
+    }
+
+    fn report(&self, coalesced: MessagesCoalesced) {
+        let _ = self
+            .local_events
+            .send(LocalEvents::MessagesCoalesced(coalesced));
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Deliver at most `budget.max_messages` messages of a group per window to the message
+    /// stream callbacks, and report the others in a [`MessagesCoalesced`] event. `None` delivers
+    /// every message. The budget is kept across restarts.
+    pub fn set_group_burst_budget(
+        &self,
+        group_id: &[u8],
+        budget: Option<BurstBudget>,
+    ) -> Result<(), ClientError> {
+        self.store()
+            .conn()?
+            .set_group_burst_budget(group_id, budget.map(|b| b.stored(group_id)))?;
+        self.stream_bursts.set_budget(group_id, budget);
+        Ok(())
+    }
+
+    /// Set the stored budgets on the streams, when the client is built
+    pub(crate) fn load_group_burst_budgets(&self) -> Result<(), ClientError> {
+        for budget in self.store().conn()?.group_burst_budgets()? {
+            self.stream_bursts
+                .set_budget(&budget.group_id, Some((&budget).into()));
+        }
+        Ok(())
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Set the burst budget of this group, see [`Client::set_group_burst_budget`]
+    pub fn set_burst_budget(&self, budget: Option<BurstBudget>) -> Result<(), GroupError> {
+        self.client
+            .store()
+            .conn()?
+            .set_group_burst_budget(&self.group_id, budget.map(|b| b.stored(&self.group_id)))?;
+        self.client
+            .stream_bursts()
+            .set_budget(&self.group_id, budget);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::group_message::tests::generate_message;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_coalesces_messages_over_the_budget() {
+        let (tx, mut rx) = broadcast::channel(8);
+        let limiter = BurstLimiter::new(tx);
+        let group_id = vec![1; 16];
+        limiter.set_budget(
+            &group_id,
+            Some(BurstBudget {
+                max_messages: 2,
+                window: Duration::from_millis(50),
+            }),
+        );
+
+        let admitted = (0..5)
+            .map(|i| generate_message(None, Some(&group_id), Some(i), None))
+            .filter(|message| limiter.admit(message))
+            .count();
+        assert_eq!(admitted, 2);
+        // Other groups are not limited
+        assert!(limiter.admit(&generate_message(None, None, None, None)));
+        assert!(rx.try_recv().is_err());
+
+        let wait = limiter.next_report_in().unwrap();
+        xmtp_common::time::sleep(wait).await;
+        limiter.report_ended();
+        let LocalEvents::MessagesCoalesced(coalesced) = rx.try_recv().unwrap() else {
+            panic!("expected coalesced messages");
+        };
+        assert_eq!(coalesced.count, 3);
+        assert_eq!(coalesced.first_sent_at_ns, 2);
+        assert_eq!(coalesced.last_sent_at_ns, 4);
+        assert_eq!(limiter.next_report_in(), None);
+    }
+}
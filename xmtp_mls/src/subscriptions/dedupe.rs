@@ -0,0 +1,119 @@
+//! Drops messages already delivered by another stream of the same client.
+//!
+//! An app streaming all messages and the messages of some groups at the same time receives the
+//! messages of those groups from several streams. The `*_with_callback` streams used by the
+//! bindings can check every message against the ids recently delivered by any stream of the client,
+//! so each message reaches the app once.
+//!
+//! Deduplication is off by default: streams feeding independent consumers must each receive every
+//! message. Apps funnelling several streams into one consumer opt in with
+//! [`Client::set_stream_dedupe_window`], typically with
+//! [`STREAM_DEDUPE_WINDOW`](crate::configuration::STREAM_DEDUPE_WINDOW).
+use std::collections::{HashSet, VecDeque};
+
+use parking_lot::Mutex;
+
+use crate::Client;
+
+#[derive(Debug)]
+struct Window {
+    capacity: usize,
+    order: VecDeque<Vec<u8>>,
+    ids: HashSet<Vec<u8>>,
+}
+
+/// The ids of the messages most recently delivered by the streams of a client, shared by its
+/// clones
+#[derive(Debug)]
+pub struct StreamDedupe {
+    window: Mutex<Window>,
+}
+
+/// Disabled until a window is set
+impl Default for StreamDedupe {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl StreamDedupe {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            window: Mutex::new(Window {
+                capacity,
+                order: VecDeque::with_capacity(capacity),
+                ids: HashSet::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Record the delivery of `message_id`, returning false if it is in the window already.
+    /// Always true when the window is disabled.
+    pub fn first_delivery(&self, message_id: &[u8]) -> bool {
+        let mut window = self.window.lock();
+        if window.capacity == 0 {
+            return true;
+        }
+        if !window.ids.insert(message_id.to_vec()) {
+            return false;
+        }
+        window.order.push_back(message_id.to_vec());
+        while window.order.len() > window.capacity {
+            if let Some(oldest) = window.order.pop_front() {
+                window.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    fn set_capacity(&self, capacity: usize) {
+        let mut window = self.window.lock();
+        window.capacity = capacity;
+        while window.order.len() > capacity {
+            if let Some(oldest) = window.order.pop_front() {
+                window.ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Set how many recently streamed message ids are remembered to drop duplicates across the
+    /// streams of this client. `0`, the default, disables deduplication, so overlapping streams
+    /// each deliver their messages.
+    pub fn set_stream_dedupe_window(&self, size: usize) {
+        self.stream_dedupe.set_capacity(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn drops_duplicates_within_window() {
+        let dedupe = StreamDedupe::new(2);
+        assert!(dedupe.first_delivery(b"a"));
+        assert!(!dedupe.first_delivery(b"a"));
+        assert!(dedupe.first_delivery(b"b"));
+        assert!(dedupe.first_delivery(b"c"));
+        // `a` slid out of the window
+        assert!(dedupe.first_delivery(b"a"));
+        assert!(!dedupe.first_delivery(b"c"));
+
+        dedupe.set_capacity(0);
+        assert!(dedupe.first_delivery(b"c"));
+        assert!(dedupe.first_delivery(b"c"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn disabled_by_default() {
+        let dedupe = StreamDedupe::default();
+        assert!(dedupe.first_delivery(b"a"));
+        assert!(dedupe.first_delivery(b"a"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Delivery of streamed messages to the callbacks of the bindings, following the
+//! [`StreamControl`] of the stream.
+use futures::{future::Either, Stream, StreamExt};
+
+use super::{burst::BurstLimiter, dedupe::StreamDedupe, Result};
+use crate::{
+    storage::{
+        group_message::{MsgQueryArgs, StoredGroupMessage},
+        DbConnection, StorageError,
+    },
+    StreamControl,
+};
+
+/// The stored messages of `group_ids` sent after `cursor_ns`, oldest first
+pub(crate) fn stored_messages_after<GroupId: AsRef<[u8]>>(
+    conn: &DbConnection,
+    group_ids: impl IntoIterator<Item = GroupId>,
+    cursor_ns: i64,
+) -> std::result::Result<Vec<StoredGroupMessage>, StorageError> {
+    let args = MsgQueryArgs {
+        sent_after_ns: Some(cursor_ns),
+        ..Default::default()
+    };
+    let mut messages = vec![];
+    for group_id in group_ids {
+        messages.extend(conn.get_group_messages(group_id.as_ref(), &args)?);
+    }
+    // DMs stitched together return each other's messages
+    messages.sort_by(|a, b| (a.sent_at_ns, &a.id).cmp(&(b.sent_at_ns, &b.id)));
+    messages.dedup_by(|a, b| a.id == b.id);
+    Ok(messages)
+}
+
+/// Pass the messages of `stream` to `callback` once each, across the streams sharing `dedupe`.
+///
+/// While `control` is paused, messages are dropped. On resume, the messages dropped are loaded
+/// again with `replay`, which returns the stored messages sent after a cursor. A replay requested
+/// with [`StreamControl::replay_from`] delivers messages even if they were delivered already.
+///
+/// Messages over the burst budget of their group are left to the event reported by `bursts`.
+pub(crate) async fn deliver_messages<S>(
+    stream: S,
+    control: &StreamControl,
+    dedupe: &StreamDedupe,
+    bursts: &BurstLimiter,
+    replay: impl Fn(i64) -> Result<Vec<StoredGroupMessage>>,
+    mut callback: impl FnMut(Result<StoredGroupMessage>),
+) where
+    S: Stream<Item = Result<StoredGroupMessage>>,
+{
+    futures::pin_mut!(stream);
+    let mut deliver = |message: Result<StoredGroupMessage>, once: bool| {
+        if let Ok(m) = &message {
+            if once && (!dedupe.first_delivery(&m.id) || !bursts.admit(m)) {
+                return;
+            }
+        }
+        callback(message)
+    };
+    // Cursor before the oldest message dropped while paused
+    let mut missed_from: Option<i64> = None;
+
+    loop {
+        let next = {
+            let changed = control.changed();
+            // Wake up when the coalesced messages of a group are due to be reported
+            let report_in = bursts.next_report_in();
+            let report = async move {
+                match report_in {
+                    Some(wait) => xmtp_common::time::sleep(wait).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            futures::pin_mut!(changed, report);
+            let woken = futures::future::select(changed, report);
+            match futures::future::select(stream.next(), woken).await {
+                Either::Left((next, _)) => Some(next),
+                Either::Right(_) => None,
+            }
+        };
+        bursts.report_ended();
+        match next {
+            Some(None) => break,
+            Some(Some(message)) if control.is_paused() => match message {
+                Ok(m) => {
+                    let cursor = m.sent_at_ns - 1;
+                    missed_from = Some(missed_from.map_or(cursor, |c| c.min(cursor)));
+                }
+                Err(e) => tracing::warn!("error in paused stream: {e}"),
+            },
+            Some(Some(message)) => deliver(message, true),
+            None => (),
+        }
+
+        if control.is_paused() {
+            continue;
+        }
+        if let Some(cursor) = missed_from.take() {
+            match replay(cursor) {
+                Ok(messages) => messages.into_iter().for_each(|m| deliver(Ok(m), true)),
+                Err(e) => deliver(Err(e), true),
+            }
+        }
+        if let Some(cursor) = control.take_replay() {
+            match replay(cursor) {
+                Ok(messages) => messages.into_iter().for_each(|m| deliver(Ok(m), false)),
+                Err(e) => deliver(Err(e), false),
+            }
+        }
+    }
+}
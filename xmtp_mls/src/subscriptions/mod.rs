@@ -10,6 +10,9 @@ use xmtp_proto::{api_client::XmtpMlsStreams, xmtp::mls::api::v1::WelcomeMessage}
 use stream_all::StreamAllMessages;
 use stream_conversations::{ProcessWelcomeFuture, StreamConversations, WelcomeOrGroup};
 
+pub mod burst;
+pub mod dedupe;
+pub(crate) mod delivery;
 mod stream_all;
 mod stream_conversations;
 pub(crate) mod stream_messages;
@@ -17,16 +20,20 @@ pub(crate) mod stream_messages;
 use crate::{
     groups::{
         device_sync::preference_sync::UserPreferenceUpdate, mls_sync::GroupMessageProcessingError,
-        GroupError, MlsGroup,
+        welcomes::WelcomeSendReport, GroupError, MlsGroup,
     },
+    identity_updates::IdentityChange,
     storage::{
-        consent_record::StoredConsentRecord, group::ConversationType,
-        group_message::StoredGroupMessage, NotFound, StorageError,
+        consent_record::StoredConsentRecord,
+        group::{ConversationType, GroupQueryArgs},
+        group_message::{DeliveryStatus, StoredGroupMessage},
+        NotFound, StorageError,
     },
-    Client, XmtpApi,
+    sync_progress::SyncStatus,
+    Client, StreamControl, XmtpApi,
 };
 use thiserror::Error;
-use xmtp_common::{retryable, RetryableError};
+use xmtp_common::{retryable, ErrorClass, RetryState, RetryableError};
 
 pub(crate) type Result<T> = std::result::Result<T, SubscribeError>;
 
@@ -51,6 +58,104 @@ pub enum LocalEvents {
     SyncMessage(SyncMessage),
     OutgoingPreferenceUpdates(Vec<UserPreferenceUpdate>),
     IncomingPreferenceUpdate(Vec<UserPreferenceUpdate>),
+    MessageDelivery(MessageDelivery),
+    WelcomeUndelivered(UndeliveredWelcome),
+    VerificationReset(VerificationReset),
+    IdentityChanges(Vec<IdentityChange>),
+    StreamResubscribed(StreamResubscribed),
+    MessagesCoalesced(MessagesCoalesced),
+    GroupLeft(GroupLeft),
+    FeatureFlagsChanged(Vec<FeatureFlagChange>),
+    WelcomesSent(WelcomeSendReport),
+    KeyRotated(KeyRotated),
+    MessageStored(MessageStored),
+    /// A request, database operation or MLS operation failed and is about to be retried
+    Retrying(RetryState),
+}
+
+/// The delivery status of a message sent from this installation changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageDelivery {
+    pub group_id: Vec<u8>,
+    /// Id returned when the message was sent
+    pub message_id: Vec<u8>,
+    pub status: DeliveryStatus,
+}
+
+/// An installation added to a group never processed its welcome, even after being re-added
+/// with fresh key packages. Removing and adding the member again is left to the user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndeliveredWelcome {
+    pub group_id: Vec<u8>,
+    pub installation_id: Vec<u8>,
+    pub readd_attempts: i32,
+}
+
+/// The keys of a peer that the user verified changed, so the peer is no longer verified and
+/// the verification code has to be compared again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationReset {
+    pub group_id: Vec<u8>,
+    pub peer_inbox_id: String,
+}
+
+/// A message stream received nothing for longer than the silence window, and was subscribed
+/// again in case its connection died without an error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamResubscribed {
+    /// Number of groups in the stream
+    pub group_count: usize,
+    /// Time since the stream last received a message, or since it was subscribed
+    pub gap: std::time::Duration,
+}
+
+/// Messages of a group over its burst budget, stored but not passed to the stream callbacks,
+/// see [`burst`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessagesCoalesced {
+    pub group_id: Vec<u8>,
+    pub count: u32,
+    pub first_sent_at_ns: i64,
+    pub last_sent_at_ns: i64,
+}
+
+/// A message received by a sync or a stream was stored, see the notify stage of the
+/// [`pipeline`](crate::groups::pipeline)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageStored {
+    pub group_id: Vec<u8>,
+    pub message_id: Vec<u8>,
+    /// Cursor of the envelope the message was received in
+    pub cursor: u64,
+}
+
+/// This installation left a group, see [`crate::groups::MlsGroup::leave`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupLeft {
+    pub group_id: Vec<u8>,
+    /// Whether the removal is still waiting for a commit from another member
+    pub pending: bool,
+}
+
+/// A commit rotating the leaf key of an installation in a group was merged, whether sent by this
+/// installation, see [`crate::groups::MlsGroup::key_update`], or by another member
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRotated {
+    pub group_id: Vec<u8>,
+    /// The installation whose leaf key was rotated
+    pub installation_id: Vec<u8>,
+    /// Epoch of the group after the rotation
+    pub epoch: u64,
+    pub rotated_at_ns: i64,
+}
+
+/// A feature flag was set or unset on this installation, see
+/// [`Client::set_feature_flag`](crate::Client::set_feature_flag)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureFlagChange {
+    pub name: String,
+    /// `None` when the flag was unset and the default of the client applies again
+    pub enabled: Option<bool>,
 }
 
 #[derive(Clone)]
@@ -108,6 +213,125 @@ impl LocalEvents {
         }
     }
 
+    fn delivery_filter(self) -> Option<MessageDelivery> {
+        use LocalEvents::*;
+
+        match self {
+            MessageDelivery(delivery) => Some(delivery),
+            _ => None,
+        }
+    }
+
+    fn undelivered_welcome_filter(self) -> Option<UndeliveredWelcome> {
+        use LocalEvents::*;
+
+        match self {
+            WelcomeUndelivered(welcome) => Some(welcome),
+            _ => None,
+        }
+    }
+
+    fn verification_reset_filter(self) -> Option<VerificationReset> {
+        use LocalEvents::*;
+
+        match self {
+            VerificationReset(reset) => Some(reset),
+            _ => None,
+        }
+    }
+
+    fn identity_changes_filter(self) -> Option<Vec<IdentityChange>> {
+        use LocalEvents::*;
+
+        match self {
+            IdentityChanges(changes) => Some(changes),
+            _ => None,
+        }
+    }
+
+    fn stream_resubscribed_filter(self) -> Option<StreamResubscribed> {
+        use LocalEvents::*;
+
+        match self {
+            StreamResubscribed(resubscribed) => Some(resubscribed),
+            _ => None,
+        }
+    }
+
+    fn messages_coalesced_filter(self) -> Option<MessagesCoalesced> {
+        use LocalEvents::*;
+
+        match self {
+            MessagesCoalesced(coalesced) => Some(coalesced),
+            _ => None,
+        }
+    }
+
+    fn welcome_report_filter(self) -> Option<WelcomeSendReport> {
+        use LocalEvents::*;
+
+        match self {
+            WelcomesSent(report) => Some(report),
+            _ => None,
+        }
+    }
+
+    fn key_rotated_filter(self) -> Option<KeyRotated> {
+        use LocalEvents::*;
+
+        match self {
+            KeyRotated(rotated) => Some(rotated),
+            _ => None,
+        }
+    }
+
+    fn retry_filter(self) -> Option<RetryState> {
+        use LocalEvents::*;
+
+        match self {
+            Retrying(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Report the retries of `retry_async!` to the streams of a client
+    pub(crate) fn report_retries(
+        sender: &broadcast::Sender<LocalEvents>,
+    ) -> impl FnMut(&RetryState) + '_ {
+        move |state| {
+            let _ = sender.send(LocalEvents::Retrying(state.clone()));
+        }
+    }
+
+    fn group_left_filter(self) -> Option<GroupLeft> {
+        use LocalEvents::*;
+
+        match self {
+            GroupLeft(left) => Some(left),
+            _ => None,
+        }
+    }
+
+    fn feature_flag_filter(self) -> Option<Vec<FeatureFlagChange>> {
+        use LocalEvents::*;
+
+        let changes: Vec<_> = match self {
+            FeatureFlagsChanged(changes) => changes,
+            // Flags synced from other installations
+            IncomingPreferenceUpdate(updates) => updates
+                .into_iter()
+                .filter_map(|pu| match pu {
+                    UserPreferenceUpdate::FeatureFlagUpdate { name, enabled } => {
+                        Some(FeatureFlagChange { name, enabled })
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => return None,
+        };
+        (!changes.is_empty()).then_some(changes)
+    }
+
     fn preference_filter(self) -> Option<Vec<UserPreferenceUpdate>> {
         use LocalEvents::*;
 
@@ -141,6 +365,17 @@ pub(crate) trait StreamMessages {
     fn stream_sync_messages(self) -> impl Stream<Item = Result<LocalEvents>>;
     fn stream_consent_updates(self) -> impl Stream<Item = Result<Vec<StoredConsentRecord>>>;
     fn stream_preference_updates(self) -> impl Stream<Item = Result<Vec<UserPreferenceUpdate>>>;
+    fn stream_message_deliveries(self) -> impl Stream<Item = Result<MessageDelivery>>;
+    fn stream_undelivered_welcomes(self) -> impl Stream<Item = Result<UndeliveredWelcome>>;
+    fn stream_verification_resets(self) -> impl Stream<Item = Result<VerificationReset>>;
+    fn stream_identity_changes(self) -> impl Stream<Item = Result<Vec<IdentityChange>>>;
+    fn stream_resubscriptions(self) -> impl Stream<Item = Result<StreamResubscribed>>;
+    fn stream_coalesced_messages(self) -> impl Stream<Item = Result<MessagesCoalesced>>;
+    fn stream_groups_left(self) -> impl Stream<Item = Result<GroupLeft>>;
+    fn stream_welcome_reports(self) -> impl Stream<Item = Result<WelcomeSendReport>>;
+    fn stream_key_rotations(self) -> impl Stream<Item = Result<KeyRotated>>;
+    fn stream_feature_flags(self) -> impl Stream<Item = Result<Vec<FeatureFlagChange>>>;
+    fn stream_retries(self) -> impl Stream<Item = Result<RetryState>>;
 }
 
 impl StreamMessages for broadcast::Receiver<LocalEvents> {
@@ -168,6 +403,94 @@ impl StreamMessages for broadcast::Receiver<LocalEvents> {
                 .map(Result::Ok)
         })
     }
+
+    fn stream_message_deliveries(self) -> impl Stream<Item = Result<MessageDelivery>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::delivery_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_undelivered_welcomes(self) -> impl Stream<Item = Result<UndeliveredWelcome>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::undelivered_welcome_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_verification_resets(self) -> impl Stream<Item = Result<VerificationReset>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::verification_reset_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_identity_changes(self) -> impl Stream<Item = Result<Vec<IdentityChange>>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::identity_changes_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_resubscriptions(self) -> impl Stream<Item = Result<StreamResubscribed>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::stream_resubscribed_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_coalesced_messages(self) -> impl Stream<Item = Result<MessagesCoalesced>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::messages_coalesced_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_groups_left(self) -> impl Stream<Item = Result<GroupLeft>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::group_left_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_welcome_reports(self) -> impl Stream<Item = Result<WelcomeSendReport>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::welcome_report_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_key_rotations(self) -> impl Stream<Item = Result<KeyRotated>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::key_rotated_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_feature_flags(self) -> impl Stream<Item = Result<Vec<FeatureFlagChange>>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::feature_flag_filter)
+                .map(Result::Ok)
+        })
+    }
+
+    fn stream_retries(self) -> impl Stream<Item = Result<RetryState>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            xmtp_common::optify!(event, "Missed message due to event queue lag")
+                .and_then(LocalEvents::retry_filter)
+                .map(Result::Ok)
+        })
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -211,6 +534,19 @@ impl RetryableError for SubscribeError {
             ConversationStream(e) => retryable!(e),
         }
     }
+
+    fn error_class(&self) -> Option<ErrorClass> {
+        use SubscribeError::*;
+        match self {
+            Group(e) => e.error_class(),
+            ReceiveGroup(e) => e.error_class(),
+            Database(e) => e.error_class(),
+            Storage(e) => e.error_class(),
+            Api(e) => e.error_class(),
+            NotFound(e) => e.error_class(),
+            _ => None,
+        }
+    }
 }
 
 impl<ApiClient, V> Client<ApiClient, V>
@@ -308,15 +644,293 @@ where
         #[cfg(target_arch = "wasm32")] mut callback: impl FnMut(Result<StoredGroupMessage>) + 'static,
     ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
         let (tx, rx) = oneshot::channel();
+        let control = Arc::new(StreamControl::default());
 
-        crate::spawn(Some(rx), async move {
+        crate::spawn_controlled(Some(rx), Some(control.clone()), async move {
             let stream = client.stream_all_messages(conversation_type).await?;
+            let _ = tx.send(());
+            let replay = |cursor_ns| {
+                let conn = client.store().conn()?;
+                let groups = conn.find_groups(
+                    GroupQueryArgs::default().maybe_conversation_type(conversation_type),
+                )?;
+                Ok(delivery::stored_messages_after(
+                    &conn,
+                    groups.iter().map(|g| &g.id),
+                    cursor_ns,
+                )?)
+            };
+            delivery::deliver_messages(
+                stream,
+                &control,
+                &client.stream_dedupe,
+                &client.stream_bursts,
+                replay,
+                &mut callback,
+            )
+            .await;
+            tracing::debug!("`stream_all_messages` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream delivery status changes of messages sent from this installation,
+    /// such as those sent with [`MlsGroup::send_message_optimistic`]
+    pub fn stream_message_deliveries_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<MessageDelivery>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_message_deliveries();
+
             futures::pin_mut!(stream);
             let _ = tx.send(());
-            while let Some(message) = stream.next().await {
-                callback(message)
+            while let Some(delivery) = stream.next().await {
+                callback(delivery)
             }
-            tracing::debug!("`stream_all_messages` stream ended, dropping stream");
+            tracing::debug!("`stream_message_deliveries` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream installations that were added to a group by this installation, but never
+    /// processed their welcome, and need to be removed and added again manually
+    pub fn stream_undelivered_welcomes_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<UndeliveredWelcome>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_undelivered_welcomes();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(welcome) = stream.next().await {
+                callback(welcome)
+            }
+            tracing::debug!("`stream_undelivered_welcomes` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream peers whose verification was reset because their keys changed
+    pub fn stream_verification_resets_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<VerificationReset>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_verification_resets();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(reset) = stream.next().await {
+                callback(reset)
+            }
+            tracing::debug!("`stream_verification_resets` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream wallets and installations that were added to or removed from the inboxes of
+    /// the members of this client's conversations
+    pub fn stream_identity_changes_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<Vec<IdentityChange>>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_identity_changes();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(changes) = stream.next().await {
+                callback(changes)
+            }
+            tracing::debug!("`stream_identity_changes` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the phases of syncs, see [`crate::sync_progress`]
+    pub fn stream_sync_progress_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<SyncStatus>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.sync_progress.subscribe();
+            let stream = BroadcastStream::new(receiver).filter_map(|status| async {
+                xmtp_common::optify!(status, "Missed sync progress due to lag").map(Result::Ok)
+            });
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(status) = stream.next().await {
+                callback(status)
+            }
+            tracing::debug!("`stream_sync_progress` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the message streams of this client subscribed again by the watchdog, see
+    /// [`ClientBuilder::stream_silence_window`](crate::builder::ClientBuilder::stream_silence_window)
+    pub fn stream_resubscriptions_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<StreamResubscribed>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_resubscriptions();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(resubscribed) = stream.next().await {
+                callback(resubscribed)
+            }
+            tracing::debug!("`stream_resubscriptions` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the messages of groups over their burst budget, see [`burst`]
+    pub fn stream_coalesced_messages_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<MessagesCoalesced>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_coalesced_messages();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(coalesced) = stream.next().await {
+                callback(coalesced)
+            }
+            tracing::debug!("`stream_coalesced_messages` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the groups that this installation leaves or is removed from
+    pub fn stream_groups_left_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<GroupLeft>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_groups_left();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(left) = stream.next().await {
+                callback(left)
+            }
+            tracing::debug!("`stream_groups_left` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the outcome of the welcomes this installation sends to the members it adds, see
+    /// [`crate::groups::welcomes`]
+    pub fn stream_welcome_reports_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<WelcomeSendReport>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_welcome_reports();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(report) = stream.next().await {
+                callback(report)
+            }
+            tracing::debug!("`stream_welcome_reports` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the rotations of leaf keys in the groups of this installation, its own whether they
+    /// were requested or due to the rotation interval, and those of other members
+    pub fn stream_key_rotations_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<KeyRotated>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_key_rotations();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(rotated) = stream.next().await {
+                callback(rotated)
+            }
+            tracing::debug!("`stream_key_rotations` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the feature flags set on this installation, or synced from another one
+    pub fn stream_feature_flags_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<Vec<FeatureFlagChange>>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_feature_flags();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(changes) = stream.next().await {
+                callback(changes)
+            }
+            tracing::debug!("`stream_feature_flags` stream ended, dropping stream");
+            Ok::<_, SubscribeError>(())
+        })
+    }
+
+    /// Stream the retries of failed requests, database operations and MLS operations, to
+    /// observe a degraded network or a busy database
+    pub fn stream_retries_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<RetryState>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<()>> {
+        let (tx, rx) = oneshot::channel();
+
+        crate::spawn(Some(rx), async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_retries();
+
+            futures::pin_mut!(stream);
+            let _ = tx.send(());
+            while let Some(state) = stream.next().await {
+                callback(state)
+            }
+            tracing::debug!("`stream_retries` stream ended, dropping stream");
             Ok::<_, SubscribeError>(())
         })
     }
@@ -2,16 +2,19 @@ use std::{
     collections::HashSet,
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{ready, Context, Poll},
 };
 
 use crate::{
+    api::{NetworkSubsystem, NetworkUsageTracker},
     groups::{scoped_client::ScopedGroupClient, MlsGroup},
     storage::{group::ConversationType, refresh_state::EntityKind, NotFound, ProviderTransactions},
     Client, XmtpOpenMlsProvider,
 };
 use futures::{prelude::stream::Select, Stream};
 use pin_project_lite::pin_project;
+use prost::Message;
 use tokio_stream::wrappers::BroadcastStream;
 use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
 use xmtp_proto::{
@@ -83,12 +86,13 @@ pin_project! {
     /// Subscription Stream mapped to WelcomeOrGroup
     pub(super) struct SubscriptionStream<S> {
         #[pin] inner: S,
+        usage: Arc<NetworkUsageTracker>,
     }
 }
 
 impl<S> SubscriptionStream<S> {
-    fn new(inner: S) -> Self {
-        Self { inner }
+    fn new(inner: S, usage: Arc<NetworkUsageTracker>) -> Self {
+        Self { inner, usage }
     }
 }
 
@@ -105,6 +109,8 @@ where
         match this.inner.poll_next(cx) {
             Ready(Some(welcome)) => {
                 let welcome = welcome.map_err(SubscribeError::from)?;
+                this.usage
+                    .record(NetworkSubsystem::Streams, 0, welcome.encoded_len());
                 Ready(Some(Ok(WelcomeOrGroup::Welcome(welcome))))
             }
             Pending => Pending,
@@ -173,7 +179,7 @@ where
             .api_client
             .subscribe_welcome_messages(installation_key.as_ref(), Some(id_cursor as u64))
             .await?;
-        let subscription = SubscriptionStream::new(subscription);
+        let subscription = SubscriptionStream::new(subscription, client.api_client.usage.clone());
         let known_welcome_ids = HashSet::from_iter(conn.group_welcome_ids()?.into_iter());
 
         let stream = futures::stream::select(events, subscription);
@@ -395,7 +401,7 @@ where
         );
 
         let group = provider
-            .retryable_transaction_async(None, |provider| async {
+            .retryable_transaction_async(Some(client.retry_policies().storage), |provider| async {
                 MlsGroup::create_from_encrypted_welcome(
                     client,
                     provider,
@@ -5,20 +5,26 @@ use std::{
     task::{ready, Context, Poll},
 };
 
-use super::{Result, SubscribeError};
+use super::{LocalEvents, Result, StreamResubscribed, SubscribeError};
 use crate::{
-    api::GroupFilter,
-    groups::{scoped_client::ScopedGroupClient, MlsGroup},
+    api::{GroupFilter, NetworkSubsystem},
+    groups::{
+        mls_sync::GroupMessageProcessingError,
+        pipeline::{self, EnvelopeTrace, PipelineStage},
+        scoped_client::ScopedGroupClient,
+        MlsGroup,
+    },
     storage::{
         encrypted_store::ProviderTransactions, group::StoredGroup,
-        group_message::StoredGroupMessage, refresh_state::EntityKind, StorageError,
+        group_message::StoredGroupMessage, refresh_state::EntityKind,
     },
     types::GroupId,
     XmtpOpenMlsProvider,
 };
-use futures::Stream;
+use futures::{FutureExt, Stream};
 use pin_project_lite::pin_project;
-use xmtp_common::FutureWrapper;
+use prost::Message;
+use xmtp_common::{time::Instant, FutureWrapper};
 use xmtp_id::InboxIdRef;
 use xmtp_proto::{
     api_client::{trait_impls::XmtpApi, XmtpMlsStreams},
@@ -98,6 +104,9 @@ pin_project! {
         client: &'a C,
         group_list: HashMap<GroupId, MessagePosition>,
         drained: VecDeque<Option<Result<GroupMessage>>>,
+        // Completes when the subscription was silent for the client's silence window
+        watchdog: Option<FutureWrapper<'a, ()>>,
+        last_received: Instant,
     }
 }
 
@@ -115,6 +124,10 @@ pin_project! {
         },
         Adding {
             #[pin] future: FutureWrapper<'a, Result<(Out, Vec<u8>, Option<u64>)>>
+        },
+        /// state that indicates the subscription was silent for too long and is being replaced
+        Resubscribing {
+            #[pin] future: FutureWrapper<'a, Result<Out>>
         }
     }
 }
@@ -178,6 +191,17 @@ where
             state: Default::default(),
             group_list: group_list.into_iter().map(|(g, c)| (g, c.into())).collect(),
             drained: VecDeque::new(),
+            watchdog: Self::watchdog(client),
+            last_received: Instant::now(),
+        })
+    }
+
+    fn watchdog(client: &'a C) -> Option<FutureWrapper<'a, ()>> {
+        let data_mode = client.data_mode();
+        client.stream_silence_window().map(|window| {
+            FutureWrapper::new(xmtp_common::time::sleep(
+                data_mode.stream_silence_window(window),
+            ))
         })
     }
 
@@ -274,23 +298,62 @@ where
         match this.state.as_mut().project() {
             Waiting => {
                 if let Some(envelope) = this.drained.pop_front().flatten() {
-                    let future = ProcessMessageFuture::new(*this.client, envelope?)?;
+                    let envelope = envelope?;
+                    this.client.api().usage().record(
+                        NetworkSubsystem::Streams,
+                        0,
+                        envelope.encoded_len(),
+                    );
+                    let future = ProcessMessageFuture::new(*this.client, envelope)?;
                     let future = future.process();
                     this.state.set(State::Processing {
                         future: FutureWrapper::new(future),
                     });
                     return self.try_update_state(cx);
                 }
-                if let Some(envelope) = ready!(this.inner.poll_next(cx)) {
-                    let future = ProcessMessageFuture::new(*this.client, envelope?)?;
-                    let future = future.process();
-                    this.state.set(State::Processing {
-                        future: FutureWrapper::new(future),
-                    });
-                    self.try_update_state(cx)
-                } else {
+                match this.inner.poll_next(cx) {
+                    Ready(Some(envelope)) => {
+                        *this.last_received = Instant::now();
+                        *this.watchdog = Self::watchdog(this.client);
+                        let envelope = envelope?;
+                        this.client.api().usage().record(
+                            NetworkSubsystem::Streams,
+                            0,
+                            envelope.encoded_len(),
+                        );
+                        let future = ProcessMessageFuture::new(*this.client, envelope)?;
+                        let future = future.process();
+                        this.state.set(State::Processing {
+                            future: FutureWrapper::new(future),
+                        });
+                        self.try_update_state(cx)
+                    }
                     // the stream ended
-                    Ready(None)
+                    Ready(None) => Ready(None),
+                    Pending => {
+                        let silent = this
+                            .watchdog
+                            .as_mut()
+                            .is_some_and(|w| w.poll_unpin(cx).is_ready());
+                        if !silent {
+                            return Pending;
+                        }
+                        tracing::warn!(
+                            inbox_id = this.client.inbox_id(),
+                            "messages stream silent for {:?}, subscribing again",
+                            this.last_received.elapsed()
+                        );
+                        let client = *this.client;
+                        let filters = self.filters();
+                        let future = async move {
+                            Ok(client.api().subscribe_group_messages(filters).await?)
+                        };
+                        let mut this = self.as_mut().project();
+                        this.state.set(State::Resubscribing {
+                            future: FutureWrapper::new(future),
+                        });
+                        self.poll_next(cx)
+                    }
                 }
             }
             Processing { .. } => self.try_update_state(cx),
@@ -314,6 +377,27 @@ where
                 this.state.as_mut().set(State::Waiting);
                 self.poll_next(cx)
             }
+            Resubscribing { future } => {
+                let result = ready!(future.poll(cx));
+                let mut this = self.as_mut().project();
+                this.state.set(State::Waiting);
+                *this.watchdog = Self::watchdog(this.client);
+                let stream = result?;
+                let drained = self.as_mut().drain(cx);
+                let mut this = self.as_mut().project();
+                this.drained.extend(drained);
+                this.inner.set(stream);
+                let resubscribed = StreamResubscribed {
+                    group_count: this.group_list.len(),
+                    gap: this.last_received.elapsed(),
+                };
+                *this.last_received = Instant::now();
+                let _ = this
+                    .client
+                    .local_events()
+                    .send(LocalEvents::StreamResubscribed(resubscribed));
+                self.poll_next(cx)
+            }
         }
     }
 }
@@ -412,16 +496,53 @@ where
             &cursor_id
         );
 
-        if self.needs_to_sync(*cursor_id)? {
-            self.process_stream_entry().await
-        }
+        // The same stages as a sync, see the pipeline module
+        let mut trace = EnvelopeTrace::new(self.client.envelope_interceptors(), &self.msg.group_id);
+        let conn = self.provider.conn_ref();
+        let checked = trace
+            .record(PipelineStage::Decode, pipeline::decode_v1(&self.msg))
+            .and_then(|decoded| {
+                trace.decoded(&decoded);
+                trace.record(
+                    PipelineStage::Dedupe,
+                    pipeline::dedupe(conn, &self.msg.group_id, &decoded),
+                )
+            })
+            .and_then(|_| {
+                trace.record(
+                    PipelineStage::ConsentCheck,
+                    pipeline::consent_check(conn, &self.msg.group_id).map_err(Into::into),
+                )
+            });
+        let stored = match checked {
+            Ok(consent_state) => {
+                trace.consent_checked(consent_state);
+                self.process_stream_entry(&mut trace).await
+            }
+            // already processed through a sync
+            Err(GroupMessageProcessingError::AlreadyProcessed(_)) => false,
+            Err(e) => {
+                tracing::warn!(
+                    cursor_id,
+                    group_id = hex::encode(&self.msg.group_id),
+                    "skipping streamed envelope: {e}"
+                );
+                false
+            }
+        };
 
-        // Load the message from the DB to handle cases where it may have been already processed in
-        // another thread
-        let new_message = self
-            .provider
-            .conn_ref()
-            .get_group_message_by_timestamp(&self.msg.group_id, *created_ns as i64)?;
+        let new_message = if stored {
+            trace
+                .record(
+                    PipelineStage::Notify,
+                    pipeline::notify(conn, self.client.local_events(), &self.msg),
+                )
+                .map_err(SubscribeError::ReceiveGroup)?
+        } else {
+            // Load the message from the DB to handle cases where it may have been already
+            // processed in another thread, which notified about it
+            conn.get_group_message_by_timestamp(&self.msg.group_id, *created_ns as i64)?
+        };
 
         if let Some(msg) = new_message {
             Ok(Some((msg, *cursor_id)))
@@ -437,27 +558,42 @@ where
         }
     }
 
-    /// stream processing function
-    async fn process_stream_entry(&self) {
+    /// stream processing function, returns whether this stream stored the message
+    async fn process_stream_entry(&self, trace: &mut EnvelopeTrace<'_>) -> bool {
         let process_result = self
             .provider
-            .retryable_transaction_async(None, |provider| async move {
-                let (group, _) =
-                    MlsGroup::new_validated(&self.client, self.msg.group_id.clone(), provider)?;
-                tracing::debug!(
-                    inbox_id = self.inbox_id(),
-                    group_id = hex::encode(&self.msg.group_id),
-                    cursor_id = self.msg.id,
-                    "current epoch for [{}] in process_stream_entry()",
-                    self.inbox_id(),
-                );
-                group
-                    .process_message(provider, &self.msg, false)
-                    .await
-                    // NOTE: We want to make sure we retry an error in process_message
-                    .map_err(SubscribeError::ReceiveGroup)
-            })
+            .retryable_transaction_async(
+                Some(self.client.retry_policies().storage),
+                |provider| async move {
+                    let (group, _) =
+                        MlsGroup::new_validated(&self.client, self.msg.group_id.clone(), provider)?;
+                    tracing::debug!(
+                        inbox_id = self.inbox_id(),
+                        group_id = hex::encode(&self.msg.group_id),
+                        cursor_id = self.msg.id,
+                        "current epoch for [{}] in process_stream_entry()",
+                        self.inbox_id(),
+                    );
+                    group
+                        .process_message(provider, &self.msg, false)
+                        .await
+                        // NOTE: We want to make sure we retry an error in process_message
+                        .map_err(SubscribeError::ReceiveGroup)
+                },
+            )
             .await;
+        // Failures cannot be told apart from failures to store, as in a sync
+        let process_result = match process_result {
+            Ok(()) => trace
+                .record(PipelineStage::MlsValidate, Ok(()))
+                .and_then(|_| trace.record(PipelineStage::Store, Ok(())))
+                .map_err(SubscribeError::ReceiveGroup),
+            Err(SubscribeError::ReceiveGroup(e)) => trace
+                .record(PipelineStage::MlsValidate, Err(e))
+                .map_err(SubscribeError::ReceiveGroup),
+            Err(e) => Err(e),
+        };
+        let stored = process_result.is_ok();
 
         if let Err(SubscribeError::ReceiveGroup(e)) = process_result {
             tracing::warn!("error processing streamed message {e}");
@@ -481,18 +617,7 @@ where
                 "message process in stream success"
             );
         }
-    }
-
-    /// Checks if a message has already been processed through a sync
-    fn needs_to_sync(&self, current_msg_cursor: u64) -> Result<bool> {
-        let check_for_last_cursor = || -> std::result::Result<i64, StorageError> {
-            self.provider
-                .conn_ref()
-                .get_last_cursor_for_id(&self.msg.group_id, EntityKind::Group)
-        };
-
-        let last_synced_id = check_for_last_cursor()?;
-        Ok(last_synced_id < current_msg_cursor as i64)
+        stored
     }
 
     /// Attempt a recovery sync if a group message failed to process
@@ -531,13 +656,13 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
     use futures::stream::StreamExt;
     use wasm_bindgen_test::wasm_bindgen_test;
 
     use crate::assert_msg;
-    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::LocalEvents};
     use xmtp_cryptography::utils::generate_local_wallet;
 
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
@@ -572,4 +697,48 @@ mod tests {
         bob_group.send_message(b"hello2").await.unwrap();
         assert_msg!(stream, "hello2");
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_silent_stream_subscribes_again() {
+        let mut alice = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        alice.stream_silence_window = Some(Duration::from_millis(500));
+        let bob = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alice_group = alice
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alice_group
+            .add_members_by_inbox_id(&[bob.inbox_id()])
+            .await
+            .unwrap();
+        let bob_groups = bob
+            .sync_welcomes(&bob.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bob_group = bob_groups.first().unwrap();
+        alice_group.sync().await.unwrap();
+
+        let mut events = alice.local_events.subscribe();
+        let stream = alice_group.stream().await.unwrap();
+        futures::pin_mut!(stream);
+        let send_later = async {
+            xmtp_common::time::sleep(Duration::from_millis(1500)).await;
+            bob_group.send_message(b"after silence").await.unwrap();
+        };
+        let (_, message) = futures::join!(send_later, stream.next());
+        assert_eq!(
+            message.unwrap().unwrap().decrypted_message_bytes,
+            b"after silence"
+        );
+
+        let mut resubscribed = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let LocalEvents::StreamResubscribed(r) = event {
+                resubscribed.push(r);
+            }
+        }
+        assert!(!resubscribed.is_empty());
+        assert_eq!(resubscribed[0].group_count, 1);
+        assert!(resubscribed[0].gap >= Duration::from_millis(500));
+    }
 }
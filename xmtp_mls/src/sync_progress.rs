@@ -0,0 +1,192 @@
+//! Progress of [`Client::sync_all_welcomes_and_groups`], [`Client::cold_restore`] and of their
+//! steps, for apps to show how far along a sync is instead of a spinner.
+//!
+//! The latest [`SyncStatus`] is available from [`Client::sync_status`], and every change is
+//! streamed by [`Client::stream_sync_progress_with_callback`]. Progress has its own channel, as a
+//! sync of many groups reports far more changes than the local events channel can hold.
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use xmtp_common::time::now_ns;
+
+use crate::Client;
+
+/// Changes kept for each subscriber of the progress channel. A subscriber that falls further
+/// behind skips to the latest changes.
+const PROGRESS_CHANNEL_CAPACITY: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncPhase {
+    /// No sync ran since the client was created
+    Idle,
+    /// Loading the identity updates of this inbox, or of the members of its groups
+    FetchingIdentityUpdates { inboxes: usize },
+    /// Querying the network for new welcomes
+    FetchingWelcomes,
+    /// Joining the groups of the welcomes fetched
+    ProcessingWelcomes { processed: usize, total: usize },
+    /// Asking the other installations of this inbox for the consent and the message history
+    RequestingHistory,
+    /// Receiving the new messages and commits of each group
+    SyncingGroups { synced: usize, total: usize },
+    /// Storing the messages of a history reply from another installation of this inbox
+    BackfillingMessages { stored: usize, total: usize },
+    /// The sync finished, with the number of active groups synced
+    Complete { active_groups: usize },
+    /// The sync stopped at an error, the phases reported before it are not complete
+    Failed { error: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub phase: SyncPhase,
+    /// Time in nanoseconds the phase last changed
+    pub updated_at_ns: i64,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        Self {
+            phase: SyncPhase::Idle,
+            updated_at_ns: now_ns(),
+        }
+    }
+}
+
+/// Tracks the sync status of a client
+#[derive(Debug)]
+pub struct SyncProgress {
+    status: Mutex<SyncStatus>,
+    changes: broadcast::Sender<SyncStatus>,
+}
+
+impl Default for SyncProgress {
+    fn default() -> Self {
+        let (changes, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        Self {
+            status: Default::default(),
+            changes,
+        }
+    }
+}
+
+impl SyncProgress {
+    pub fn status(&self) -> SyncStatus {
+        self.status.lock().clone()
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<SyncStatus> {
+        self.changes.subscribe()
+    }
+
+    pub(crate) fn report(&self, phase: SyncPhase) {
+        let status = SyncStatus {
+            phase,
+            updated_at_ns: now_ns(),
+        };
+        *self.status.lock() = status.clone();
+        // Nobody may be listening
+        let _ = self.changes.send(status);
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// The phase of the latest sync. Changes are streamed by
+    /// [`Client::stream_sync_progress_with_callback`].
+    pub fn sync_status(&self) -> SyncStatus {
+        self.sync_progress.status()
+    }
+
+    pub(crate) fn report_sync_phase(&self, phase: SyncPhase) {
+        self.sync_progress.report(phase);
+    }
+
+    /// Report [`SyncPhase::Failed`] if the sync returned an error, rather than leaving the status
+    /// in the phase that failed
+    pub(crate) fn report_sync_result<T, E: std::fmt::Display>(
+        &self,
+        result: Result<T, E>,
+    ) -> Result<T, E> {
+        if let Err(err) = &result {
+            self.report_sync_phase(SyncPhase::Failed {
+                error: err.to_string(),
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_sync_progress() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert_eq!(bola.sync_status().phase, SyncPhase::Idle);
+
+        for _ in 0..2 {
+            let group = amal
+                .create_group(None, GroupMetadataOptions::default())
+                .unwrap();
+            group
+                .add_members_by_inbox_id(&[bola.inbox_id()])
+                .await
+                .unwrap();
+        }
+
+        let mut changes = bola.sync_progress.subscribe();
+        let provider = bola.mls_provider().unwrap();
+        bola.sync_all_welcomes_and_groups(&provider, None)
+            .await
+            .unwrap();
+
+        let mut phases = vec![];
+        while let Ok(status) = changes.try_recv() {
+            phases.push(status.phase);
+        }
+
+        assert_eq!(phases.first(), Some(&SyncPhase::FetchingWelcomes));
+        assert!(phases.contains(&SyncPhase::ProcessingWelcomes {
+            processed: 2,
+            total: 2
+        }));
+        assert!(phases.contains(&SyncPhase::SyncingGroups {
+            synced: 2,
+            total: 2
+        }));
+        assert_eq!(
+            phases.last(),
+            Some(&SyncPhase::Complete { active_groups: 2 })
+        );
+        assert_eq!(
+            bola.sync_status().phase,
+            SyncPhase::Complete { active_groups: 2 }
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_failed_sync_is_reported() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        amal.report_sync_phase(SyncPhase::FetchingWelcomes);
+
+        let result = amal.report_sync_result::<(), _>(Err("network unavailable"));
+        assert!(result.is_err());
+        assert_eq!(
+            amal.sync_status().phase,
+            SyncPhase::Failed {
+                error: "network unavailable".to_string()
+            }
+        );
+
+        amal.report_sync_result::<_, String>(Ok(())).unwrap();
+        assert!(matches!(amal.sync_status().phase, SyncPhase::Failed { .. }));
+    }
+}
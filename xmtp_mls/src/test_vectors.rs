@@ -0,0 +1,185 @@
+//! Golden serializations of the values persisted in the database or sent over the wire.
+//!
+//! Each test serializes fixed fixtures and compares the bytes to the vectors committed under
+//! `<PackageRoot>/test_vectors/`, then checks that the committed bytes still decode. A change that
+//! breaks storage or wire compatibility fails here, and bindings in other languages can check
+//! their own serialization against the same files.
+//!
+//! A missing vector file is a failure. To add vectors or deliberately accept a new serialization,
+//! run the tests with `XMTP_UPDATE_TEST_VECTORS=1` and commit the written files.
+#![allow(clippy::unwrap_used)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use prost::Message;
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_id::associations::AssociationState;
+use xmtp_proto::xmtp::{
+    identity::associations::AssociationState as AssociationStateProto,
+    mls::message_contents::EncodedContent,
+};
+
+use crate::{
+    groups::intents::{
+        AdminListActionType, SendMessageIntentData, UpdateAdminListIntentData,
+        UpdateGroupMembershipIntentData, UpdateMetadataIntentData,
+    },
+    storage::group::{ConversationType, GroupLifecycleState, GroupMembershipState, StoredGroup},
+};
+
+const UPDATE_ENV: &str = "XMTP_UPDATE_TEST_VECTORS";
+
+fn vectors_path(category: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test_vectors")
+        .join(format!("{category}.json"))
+}
+
+/// Compare `vectors` to the committed vectors of `category`, returning the committed bytes
+fn check_vectors(category: &str, vectors: BTreeMap<&str, Vec<u8>>) -> BTreeMap<String, Vec<u8>> {
+    let path = vectors_path(category);
+    let current: BTreeMap<String, String> = vectors
+        .iter()
+        .map(|(name, bytes)| (name.to_string(), hex::encode(bytes)))
+        .collect();
+
+    if std::env::var(UPDATE_ENV).is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            serde_json::to_string_pretty(&current).unwrap() + "\n",
+        )
+        .unwrap();
+        tracing::warn!("wrote test vectors to {}", path.display());
+    }
+
+    let golden = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "no test vectors at {} ({err}), run with {UPDATE_ENV}=1 and commit them",
+            path.display()
+        )
+    });
+    let golden: BTreeMap<String, String> = serde_json::from_str(&golden).unwrap();
+    for (name, bytes) in &current {
+        let expected = golden.get(name).unwrap_or_else(|| {
+            panic!(
+                "no `{name}` vector in {}, run with {UPDATE_ENV}=1",
+                path.display()
+            )
+        });
+        assert_eq!(
+            bytes, expected,
+            "serialization of `{name}` changed, run with {UPDATE_ENV}=1 if this is deliberate"
+        );
+    }
+    golden
+        .into_iter()
+        .map(|(name, bytes)| (name, hex::decode(bytes).unwrap()))
+        .collect()
+}
+
+fn stored_group() -> StoredGroup {
+    StoredGroup {
+        id: vec![1; 16],
+        created_at_ns: 1_700_000_000_000_000_000,
+        membership_state: GroupMembershipState::Allowed,
+        installations_last_checked: 1_700_000_000_000_000_001,
+        added_by_inbox_id: "added_by_inbox_id".into(),
+        welcome_id: Some(42),
+        rotated_at_ns: 1_700_000_000_000_000_002,
+        conversation_type: ConversationType::Group,
+        dm_id: None,
+        last_message_ns: Some(1_700_000_000_000_000_003),
+        message_disappear_from_ns: Some(1_700_000_000_000_000_004),
+        message_disappear_in_ns: Some(1_000_000_000),
+        expires_at_ns: Some(1_800_000_000_000_000_000),
+        lifecycle_state: GroupLifecycleState::Left,
+        left_at_ns: Some(1_700_000_000_000_000_005),
+    }
+}
+
+#[test]
+fn stored_group_vectors() {
+    let group = stored_group();
+    let golden = check_vectors(
+        "stored_group",
+        BTreeMap::from([("group", serde_json::to_vec(&group).unwrap())]),
+    );
+    let decoded: StoredGroup = serde_json::from_slice(&golden["group"]).unwrap();
+    assert_eq!(decoded, group);
+}
+
+#[test]
+fn intent_vectors() {
+    let golden = check_vectors(
+        "intents",
+        BTreeMap::from([
+            (
+                "send_message",
+                SendMessageIntentData::new(b"hello".to_vec()).to_bytes(),
+            ),
+            (
+                "update_group_name",
+                UpdateMetadataIntentData::new_update_group_name("group name".into()).into(),
+            ),
+            (
+                "update_group_membership",
+                UpdateGroupMembershipIntentData::new(
+                    HashMap::from([("added_inbox_id".to_string(), 7)]),
+                    vec!["removed_inbox_id".into()],
+                )
+                .into(),
+            ),
+            (
+                "update_admin_list",
+                UpdateAdminListIntentData::new(AdminListActionType::Add, "admin_inbox_id".into())
+                    .into(),
+            ),
+        ]),
+    );
+
+    let send_message = SendMessageIntentData::from_bytes(&golden["send_message"]).unwrap();
+    assert_eq!(send_message.message, b"hello");
+    let update_name = UpdateMetadataIntentData::try_from(golden["update_group_name"].clone());
+    assert_eq!(update_name.unwrap().field_value, "group name");
+    let membership =
+        UpdateGroupMembershipIntentData::try_from(golden["update_group_membership"].clone())
+            .unwrap();
+    assert_eq!(membership.membership_updates["added_inbox_id"], 7);
+    assert_eq!(membership.removed_members, vec!["removed_inbox_id"]);
+    let admin_list =
+        UpdateAdminListIntentData::try_from(golden["update_admin_list"].clone()).unwrap();
+    assert_eq!(admin_list.inbox_id, "admin_inbox_id");
+}
+
+#[test]
+fn encoded_content_vectors() {
+    let text = TextCodec::encode("hello".into()).unwrap();
+    let golden = check_vectors(
+        "encoded_content",
+        BTreeMap::from([("text", text.encode_to_vec())]),
+    );
+    let decoded = EncodedContent::decode(golden["text"].as_slice()).unwrap();
+    assert_eq!(TextCodec::decode(decoded).unwrap(), "hello");
+}
+
+#[test]
+fn association_state_vectors() {
+    let state = AssociationState::new("0x0000000000000000000000000000000000000001".into(), 0, None)
+        .unwrap();
+    let golden = check_vectors(
+        "association_state",
+        BTreeMap::from([(
+            "single_address",
+            AssociationStateProto::from(state.clone()).encode_to_vec(),
+        )]),
+    );
+    let proto = AssociationStateProto::decode(golden["single_address"].as_slice()).unwrap();
+    let decoded = AssociationState::try_from(proto).unwrap();
+    assert_eq!(decoded.inbox_id(), state.inbox_id());
+    assert_eq!(decoded.members(), state.members());
+    assert_eq!(decoded.recovery_address(), state.recovery_address());
+}
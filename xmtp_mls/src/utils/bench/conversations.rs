@@ -0,0 +1,86 @@
+//! Filling a database with conversations, to benchmark queries over large message histories
+//! without syncing them from the network.
+use diesel::{Connection, RunQueryDsl};
+
+use crate::storage::{
+    group::{GroupMembershipState, StoredGroup},
+    group_message::{ContentType, DeliveryStatus, GroupMessageKind, StoredGroupMessage},
+    schema::{group_messages, groups},
+    DbConnection, StorageError,
+};
+
+/// Rows written per insert statement
+const INSERT_BATCH_SIZE: usize = 1_000;
+
+/// Content types cycled through by the generated messages, readable and not
+const CONTENT_TYPES: [ContentType; 4] = [
+    ContentType::Text,
+    ContentType::Reaction,
+    ContentType::ReadReceipt,
+    ContentType::GroupUpdated,
+];
+
+/// Insert `num_groups` groups with `messages_per_group` messages each, interleaved in time like
+/// conversations that are active at the same time
+pub fn populate_conversations(
+    conn: &DbConnection,
+    num_groups: usize,
+    messages_per_group: usize,
+) -> Result<(), StorageError> {
+    let group_id = |i: usize| format!("group-{i:08}").into_bytes();
+    let stored_groups = (0..num_groups)
+        .map(|i| {
+            StoredGroup::new(
+                group_id(i),
+                i as i64,
+                GroupMembershipState::Allowed,
+                "bench".to_string(),
+                None,
+            )
+        })
+        .collect::<Vec<_>>();
+    let messages = (0..num_groups * messages_per_group).map(|i| StoredGroupMessage {
+        id: format!("message-{i:010}").into_bytes(),
+        group_id: group_id(i % num_groups),
+        decrypted_message_bytes: vec![0; 64],
+        sent_at_ns: (num_groups + i) as i64,
+        kind: GroupMessageKind::Application,
+        sender_installation_id: vec![1; 32],
+        sender_inbox_id: "bench".to_string(),
+        delivery_status: DeliveryStatus::Published,
+        content_type: CONTENT_TYPES[i % CONTENT_TYPES.len()],
+        version_major: 1,
+        version_minor: 0,
+        authority_id: "xmtp.org".to_string(),
+        reference_id: None,
+        sent_by_me: false,
+        sender_verified: true,
+    });
+
+    conn.raw_query(|conn| {
+        conn.transaction(|conn| {
+            for batch in stored_groups.chunks(INSERT_BATCH_SIZE) {
+                diesel::insert_into(groups::table)
+                    .values(batch)
+                    .execute(conn)?;
+            }
+            let mut batch = Vec::with_capacity(INSERT_BATCH_SIZE);
+            for message in messages {
+                batch.push(message);
+                if batch.len() == INSERT_BATCH_SIZE {
+                    diesel::insert_into(group_messages::table)
+                        .values(&batch)
+                        .execute(conn)?;
+                    batch.clear();
+                }
+            }
+            if !batch.is_empty() {
+                diesel::insert_into(group_messages::table)
+                    .values(&batch)
+                    .execute(conn)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })
+    })?;
+    Ok(())
+}
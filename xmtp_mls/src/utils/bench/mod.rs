@@ -7,6 +7,8 @@ mod identity_gen;
 pub use identity_gen::*;
 pub mod clients;
 pub use clients::*;
+pub mod conversations;
+pub use conversations::*;
 
 use thiserror::Error;
 /// Re-export of functions in private modules for benchmarks
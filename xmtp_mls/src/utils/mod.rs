@@ -39,4 +39,43 @@ pub mod id {
     pub fn serialize_group_id(group_id: &[u8]) -> String {
         hex::encode(group_id)
     }
+
+    const GROUP_PUBLIC_ID_PREFIX: &str = "g-";
+    const DM_PUBLIC_ID_PREFIX: &str = "dm-";
+
+    /// A stable, URL-safe identifier of a conversation, the same on every installation and in
+    /// every app, e.g. for deep links and push payloads. A DM is identified by a hash of its
+    /// members, so the id does not change when duplicate DM groups are stitched together.
+    ///
+    /// The hash is not keyed, since every app must derive the same id, so it only hides the
+    /// members from someone who can't guess them: hashing the `dm_id` of candidate inbox ids
+    /// reveals which DM an id refers to. Don't put the id of a DM where its members must stay
+    /// private.
+    pub fn conversation_public_id(group_id: &[u8], dm_id: Option<&str>) -> String {
+        match dm_id {
+            Some(dm_id) => format!(
+                "{DM_PUBLIC_ID_PREFIX}{}",
+                hex::encode(super::hash::sha256(dm_id.as_bytes()))
+            ),
+            None => format!("{GROUP_PUBLIC_ID_PREFIX}{}", hex::encode(group_id)),
+        }
+    }
+
+    /// What a [`conversation_public_id`] refers to
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ConversationPublicId {
+        Group(Vec<u8>),
+        /// The SHA-256 of the `dm_id` of the DM
+        Dm(Vec<u8>),
+    }
+
+    /// Parse an id made by [`conversation_public_id`]. Returns `None` for any other string.
+    pub fn parse_conversation_public_id(public_id: &str) -> Option<ConversationPublicId> {
+        if let Some(hash) = public_id.strip_prefix(DM_PUBLIC_ID_PREFIX) {
+            let hash = hex::decode(hash).ok()?;
+            return (hash.len() == 32).then_some(ConversationPublicId::Dm(hash));
+        }
+        let group_id = hex::decode(public_id.strip_prefix(GROUP_PUBLIC_ID_PREFIX)?).ok()?;
+        (!group_id.is_empty()).then_some(ConversationPublicId::Group(group_id))
+    }
 }
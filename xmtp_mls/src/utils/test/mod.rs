@@ -53,7 +53,10 @@ impl EncryptedMessageStore {
 
         let path = path.as_ref();
         std::fs::remove_file(path).unwrap();
-        std::fs::remove_file(EncryptedConnection::salt_file(path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(EncryptedConnection::salt_file(path).unwrap());
+        let _ = std::fs::remove_file(EncryptedConnection::cipher_options_file(path).unwrap());
+        let _ = std::fs::remove_file(EncryptedConnection::passphrase_kdf_file(path).unwrap());
+        let _ = std::fs::remove_file(EncryptedConnection::pepper_file(path).unwrap());
     }
 
     /// just a no-op on wasm32
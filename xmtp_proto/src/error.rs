@@ -40,6 +40,10 @@ impl xmtp_common::RetryableError for Error {
     fn is_retryable(&self) -> bool {
         true
     }
+
+    fn error_class(&self) -> Option<xmtp_common::ErrorClass> {
+        Some(xmtp_common::ErrorClass::Network)
+    }
 }
 
 impl Error {
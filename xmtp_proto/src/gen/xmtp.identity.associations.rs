@@ -34,6 +34,19 @@ pub struct SmartContractWalletSignature {
     #[prost(bytes="vec", tag="3")]
     pub signature: ::prost::alloc::vec::Vec<u8>,
 }
+/// EdDSA signature for 25519 of an account of a non-EVM chain, such as Solana,
+/// whose address is its public key
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Ed25519AccountSignature {
+    /// CAIP-10 string
+    /// <https://github.com/ChainAgnostic/CAIPs/blob/main/CAIPs/caip-10.md>
+    #[prost(string, tag="1")]
+    pub account_id: ::prost::alloc::string::String,
+    /// 64 bytes \[R(32 bytes) || S(32 bytes)\]
+    #[prost(bytes="vec", tag="2")]
+    pub bytes: ::prost::alloc::vec::Vec<u8>,
+}
 /// An existing address on xmtpv2 may have already signed a legacy identity key
 /// of type SignedPublicKey via the 'Create Identity' signature.
 /// For migration to xmtpv3, the legacy key is permitted to sign on behalf of the
@@ -57,7 +70,7 @@ pub struct Signature {
     ///     recoverable, or specified as a field.
     /// 2. The signer certifies that the signing payload is correct. The payload
     ///     must be inferred from the context in which the signature is provided.
-    #[prost(oneof="signature::Signature", tags="1, 2, 3, 4")]
+    #[prost(oneof="signature::Signature", tags="1, 2, 3, 4, 5")]
     pub signature: ::core::option::Option<signature::Signature>,
 }
 /// Nested message and enum types in `Signature`.
@@ -78,6 +91,8 @@ pub mod signature {
         InstallationKey(super::RecoverableEd25519Signature),
         #[prost(message, tag="4")]
         DelegatedErc191(super::LegacyDelegatedSignature),
+        #[prost(message, tag="5")]
+        Ed25519Account(super::Ed25519AccountSignature),
     }
 }
 /// The identifier for a member of an XID
@@ -198,6 +213,10 @@ pub struct IdentityUpdate {
     pub client_timestamp_ns: u64,
     #[prost(string, tag="3")]
     pub inbox_id: ::prost::alloc::string::String,
+    /// Version of the rules for the account addresses in the update. 0 for plain
+    /// Ethereum addresses, 1 for CAIP-10 account ids of non-EVM chains.
+    #[prost(uint32, tag="4")]
+    pub address_rules_version: u32,
 }
 /// Map of members belonging to an inbox_id
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -626,6 +626,119 @@ impl<'de> serde::Deserialize<'de> for CreateInbox {
         deserializer.deserialize_struct("xmtp.identity.associations.CreateInbox", FIELDS, GeneratedVisitor)
     }
 }
+impl serde::Serialize for Ed25519AccountSignature {
+    #[allow(deprecated)]
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut len = 0;
+        if !self.account_id.is_empty() {
+            len += 1;
+        }
+        if !self.bytes.is_empty() {
+            len += 1;
+        }
+        let mut struct_ser = serializer.serialize_struct("xmtp.identity.associations.Ed25519AccountSignature", len)?;
+        if !self.account_id.is_empty() {
+            struct_ser.serialize_field("accountId", &self.account_id)?;
+        }
+        if !self.bytes.is_empty() {
+            #[allow(clippy::needless_borrow)]
+            #[allow(clippy::needless_borrows_for_generic_args)]
+            struct_ser.serialize_field("bytes", pbjson::private::base64::encode(&self.bytes).as_str())?;
+        }
+        struct_ser.end()
+    }
+}
+impl<'de> serde::Deserialize<'de> for Ed25519AccountSignature {
+    #[allow(deprecated)]
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        const FIELDS: &[&str] = &[
+            "account_id",
+            "accountId",
+            "bytes",
+        ];
+
+        #[allow(clippy::enum_variant_names)]
+        enum GeneratedField {
+            AccountId,
+            Bytes,
+        }
+        impl<'de> serde::Deserialize<'de> for GeneratedField {
+            fn deserialize<D>(deserializer: D) -> std::result::Result<GeneratedField, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct GeneratedVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for GeneratedVisitor {
+                    type Value = GeneratedField;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                        write!(formatter, "expected one of: {:?}", &FIELDS)
+                    }
+
+                    #[allow(unused_variables)]
+                    fn visit_str<E>(self, value: &str) -> std::result::Result<GeneratedField, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        match value {
+                            "accountId" | "account_id" => Ok(GeneratedField::AccountId),
+                            "bytes" => Ok(GeneratedField::Bytes),
+                            _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
+                        }
+                    }
+                }
+                deserializer.deserialize_identifier(GeneratedVisitor)
+            }
+        }
+        struct GeneratedVisitor;
+        impl<'de> serde::de::Visitor<'de> for GeneratedVisitor {
+            type Value = Ed25519AccountSignature;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("struct xmtp.identity.associations.Ed25519AccountSignature")
+            }
+
+            fn visit_map<V>(self, mut map_: V) -> std::result::Result<Ed25519AccountSignature, V::Error>
+                where
+                    V: serde::de::MapAccess<'de>,
+            {
+                let mut account_id__ = None;
+                let mut bytes__ = None;
+                while let Some(k) = map_.next_key()? {
+                    match k {
+                        GeneratedField::AccountId => {
+                            if account_id__.is_some() {
+                                return Err(serde::de::Error::duplicate_field("accountId"));
+                            }
+                            account_id__ = Some(map_.next_value()?);
+                        }
+                        GeneratedField::Bytes => {
+                            if bytes__.is_some() {
+                                return Err(serde::de::Error::duplicate_field("bytes"));
+                            }
+                            bytes__ = 
+                                Some(map_.next_value::<::pbjson::private::BytesDeserialize<_>>()?.0)
+                            ;
+                        }
+                    }
+                }
+                Ok(Ed25519AccountSignature {
+                    account_id: account_id__.unwrap_or_default(),
+                    bytes: bytes__.unwrap_or_default(),
+                })
+            }
+        }
+        deserializer.deserialize_struct("xmtp.identity.associations.Ed25519AccountSignature", FIELDS, GeneratedVisitor)
+    }
+}
 impl serde::Serialize for IdentityAction {
     #[allow(deprecated)]
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
@@ -780,6 +893,9 @@ impl serde::Serialize for IdentityUpdate {
         if !self.inbox_id.is_empty() {
             len += 1;
         }
+        if self.address_rules_version != 0 {
+            len += 1;
+        }
         let mut struct_ser = serializer.serialize_struct("xmtp.identity.associations.IdentityUpdate", len)?;
         if !self.actions.is_empty() {
             struct_ser.serialize_field("actions", &self.actions)?;
@@ -792,6 +908,9 @@ impl serde::Serialize for IdentityUpdate {
         if !self.inbox_id.is_empty() {
             struct_ser.serialize_field("inboxId", &self.inbox_id)?;
         }
+        if self.address_rules_version != 0 {
+            struct_ser.serialize_field("addressRulesVersion", &self.address_rules_version)?;
+        }
         struct_ser.end()
     }
 }
@@ -807,6 +926,8 @@ impl<'de> serde::Deserialize<'de> for IdentityUpdate {
             "clientTimestampNs",
             "inbox_id",
             "inboxId",
+            "address_rules_version",
+            "addressRulesVersion",
         ];
 
         #[allow(clippy::enum_variant_names)]
@@ -814,6 +935,7 @@ impl<'de> serde::Deserialize<'de> for IdentityUpdate {
             Actions,
             ClientTimestampNs,
             InboxId,
+            AddressRulesVersion,
         }
         impl<'de> serde::Deserialize<'de> for GeneratedField {
             fn deserialize<D>(deserializer: D) -> std::result::Result<GeneratedField, D::Error>
@@ -838,6 +960,7 @@ impl<'de> serde::Deserialize<'de> for IdentityUpdate {
                             "actions" => Ok(GeneratedField::Actions),
                             "clientTimestampNs" | "client_timestamp_ns" => Ok(GeneratedField::ClientTimestampNs),
                             "inboxId" | "inbox_id" => Ok(GeneratedField::InboxId),
+                            "addressRulesVersion" | "address_rules_version" => Ok(GeneratedField::AddressRulesVersion),
                             _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -860,6 +983,7 @@ impl<'de> serde::Deserialize<'de> for IdentityUpdate {
                 let mut actions__ = None;
                 let mut client_timestamp_ns__ = None;
                 let mut inbox_id__ = None;
+                let mut address_rules_version__ = None;
                 while let Some(k) = map_.next_key()? {
                     match k {
                         GeneratedField::Actions => {
@@ -882,12 +1006,21 @@ impl<'de> serde::Deserialize<'de> for IdentityUpdate {
                             }
                             inbox_id__ = Some(map_.next_value()?);
                         }
+                        GeneratedField::AddressRulesVersion => {
+                            if address_rules_version__.is_some() {
+                                return Err(serde::de::Error::duplicate_field("addressRulesVersion"));
+                            }
+                            address_rules_version__ = 
+                                Some(map_.next_value::<::pbjson::private::NumberDeserialize<_>>()?.0)
+                            ;
+                        }
                     }
                 }
                 Ok(IdentityUpdate {
                     actions: actions__.unwrap_or_default(),
                     client_timestamp_ns: client_timestamp_ns__.unwrap_or_default(),
                     inbox_id: inbox_id__.unwrap_or_default(),
+                    address_rules_version: address_rules_version__.unwrap_or_default(),
                 })
             }
         }
@@ -1722,6 +1855,9 @@ impl serde::Serialize for Signature {
                 signature::Signature::DelegatedErc191(v) => {
                     struct_ser.serialize_field("delegatedErc191", v)?;
                 }
+                signature::Signature::Ed25519Account(v) => {
+                    struct_ser.serialize_field("ed25519Account", v)?;
+                }
             }
         }
         struct_ser.end()
@@ -1742,6 +1878,8 @@ impl<'de> serde::Deserialize<'de> for Signature {
             "installationKey",
             "delegated_erc_191",
             "delegatedErc191",
+            "ed25519_account",
+            "ed25519Account",
         ];
 
         #[allow(clippy::enum_variant_names)]
@@ -1750,6 +1888,7 @@ impl<'de> serde::Deserialize<'de> for Signature {
             Erc6492,
             InstallationKey,
             DelegatedErc191,
+            Ed25519Account,
         }
         impl<'de> serde::Deserialize<'de> for GeneratedField {
             fn deserialize<D>(deserializer: D) -> std::result::Result<GeneratedField, D::Error>
@@ -1775,6 +1914,7 @@ impl<'de> serde::Deserialize<'de> for Signature {
                             "erc6492" | "erc_6492" => Ok(GeneratedField::Erc6492),
                             "installationKey" | "installation_key" => Ok(GeneratedField::InstallationKey),
                             "delegatedErc191" | "delegated_erc_191" => Ok(GeneratedField::DelegatedErc191),
+                            "ed25519Account" | "ed25519_account" => Ok(GeneratedField::Ed25519Account),
                             _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -1823,6 +1963,13 @@ impl<'de> serde::Deserialize<'de> for Signature {
                                 return Err(serde::de::Error::duplicate_field("delegatedErc191"));
                             }
                             signature__ = map_.next_value::<::std::option::Option<_>>()?.map(signature::Signature::DelegatedErc191)
+;
+                        }
+                        GeneratedField::Ed25519Account => {
+                            if signature__.is_some() {
+                                return Err(serde::de::Error::duplicate_field("ed25519Account"));
+                            }
+                            signature__ = map_.next_value::<::std::option::Option<_>>()?.map(signature::Signature::Ed25519Account)
 ;
                         }
                     }